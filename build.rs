@@ -0,0 +1,6 @@
+fn main() {
+    println!("cargo:rerun-if-changed=proto/wallet.proto");
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/wallet.proto")
+        .expect("Failed to compile proto/wallet.proto, which needs protoc");
+}
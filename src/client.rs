@@ -1,5 +1,5 @@
-#[cfg(feature = "esplora")]
-use bdk_esplora::EsploraAsyncExt;
+#[cfg(any(feature = "electrum", feature = "esplora"))]
+use bdk_wallet::chain::spk_client::SyncItem;
 #[cfg(any(
     feature = "electrum",
     feature = "esplora",
@@ -8,22 +8,35 @@ use bdk_esplora::EsploraAsyncExt;
 ))]
 use {
     crate::commands::WalletOpts,
-    crate::error::BDKCliError as Error,
+    crate::error::{BDKCliError as Error, ErrorCode},
+    crate::utils::progress::{Progress, ProgressFormat},
+    crate::utils::stats::{self, Phase},
     bdk_wallet::{
         Wallet,
-        bitcoin::{Transaction, Txid},
+        bitcoin::{BlockHash, Network, Transaction, Txid, constants::genesis_block},
     },
     clap::ValueEnum,
     std::path::PathBuf,
 };
 #[cfg(feature = "rpc")]
 use {
+    crate::utils::bitcoind::NodeRpc,
+    crate::utils::signet::{
+        challenge_hex, default_signet_challenge, parse_signet_challenge, signet_magic,
+    },
     bdk_bitcoind_rpc::{Emitter, bitcoincore_rpc::RpcApi},
+    bdk_wallet::bitcoin::{Script, ScriptBuf},
     bdk_wallet::chain::CanonicalizationParams,
 };
+#[cfg(feature = "esplora")]
+use {crate::utils::esplora::with_backoff, bdk_esplora::EsploraAsyncExt};
 
 #[cfg(feature = "cbf")]
-use {crate::utils::trace_logger, bdk_kyoto::BuilderExt};
+use {
+    crate::utils::signet::is_custom_signet,
+    crate::utils::{parse_cbf_peer, trace_logger},
+    bdk_kyoto::BuilderExt,
+};
 
 #[cfg(any(
     feature = "electrum",
@@ -52,17 +65,23 @@ pub enum ClientType {
 pub(crate) enum BlockchainClient {
     #[cfg(feature = "electrum")]
     Electrum {
-        client: Box<bdk_electrum::BdkElectrumClient<bdk_electrum::electrum_client::Client>>,
+        client: Box<bdk_electrum::BdkElectrumClient<crate::utils::electrum::ElectrumClient>>,
         batch_size: usize,
     },
     #[cfg(feature = "esplora")]
     Esplora {
-        client: Box<bdk_esplora::esplora_client::AsyncClient>,
+        client:
+            Box<bdk_esplora::esplora_client::AsyncClient<crate::utils::esplora::BackoffSleeper>>,
         parallel_requests: usize,
+        /// First backoff of the retries, in milliseconds, of the requests run
+        /// [`with_backoff`](crate::utils::esplora::with_backoff).
+        backoff: u64,
     },
     #[cfg(feature = "rpc")]
     RpcClient {
         client: Box<bdk_bitcoind_rpc::bitcoincore_rpc::Client>,
+        /// Challenge of the custom signet of the wallet, checked against the node.
+        signet_challenge: Option<ScriptBuf>,
     },
 
     #[cfg(feature = "cbf")]
@@ -76,31 +95,53 @@ pub(crate) enum BlockchainClient {
     feature = "cbf"
 ))]
 impl BlockchainClient {
+    /// Whether the client counts the requests it sends itself, as the Electrum client does with
+    /// its batches.
+    fn counts_requests(&self) -> bool {
+        #[cfg(feature = "electrum")]
+        if let Self::Electrum { .. } = self {
+            return true;
+        }
+        false
+    }
+
+    /// Counts a request to the backend, unless the client counts it.
+    fn add_request(&self) {
+        if !self.counts_requests() {
+            stats::add_requests(1);
+        }
+    }
+
     pub async fn broadcast(&self, tx: Transaction) -> Result<Txid, Error> {
+        self.add_request();
         match self {
             #[cfg(feature = "electrum")]
             Self::Electrum { client, .. } => client
                 .transaction_broadcast(&tx)
-                .map_err(|e| Error::Generic(e.to_string())),
+                .map_err(|e| Error::coded(ErrorCode::Broadcast, e.to_string())),
 
             #[cfg(feature = "esplora")]
-            Self::Esplora { client, .. } => client
-                .broadcast(&tx)
+            Self::Esplora {
+                client, backoff, ..
+            } => with_backoff(*backoff, client.broadcast(&tx))
                 .await
                 .map(|()| tx.compute_txid())
-                .map_err(|e| Error::Generic(e.to_string())),
+                .map_err(|e| Error::coded(ErrorCode::Broadcast, e.to_string())),
 
             #[cfg(feature = "rpc")]
-            Self::RpcClient { client } => client
+            Self::RpcClient { client, .. } => client
                 .send_raw_transaction(&tx)
-                .map_err(|e| Error::Generic(e.to_string())),
+                .map_err(|e| Error::coded(ErrorCode::Broadcast, e.to_string())),
 
             #[cfg(feature = "cbf")]
             Self::KyotoClient { client } => {
                 let txid = tx.compute_txid();
                 let wtxid = client.requester.submit_package(tx).await.map_err(|_| {
                     tracing::warn!("Broadcast was unsuccessful");
-                    Error::Generic("Transaction broadcast timed out after 30 seconds".into())
+                    Error::coded(
+                        ErrorCode::Broadcast,
+                        "Transaction broadcast timed out after 30 seconds".into(),
+                    )
                 })?;
                 tracing::info!("Successfully broadcast WTXID: {wtxid}");
                 Ok(txid)
@@ -108,13 +149,197 @@ impl BlockchainClient {
         }
     }
 
+    /// Fails when the backend serves another chain than `network`, told by its genesis block, so
+    /// a wallet does not sync or broadcast against the wrong chain.
+    pub async fn check_network(&self, network: Network) -> Result<(), Error> {
+        self.add_request();
+        let backend: Option<(&str, BlockHash)> = match self {
+            #[cfg(feature = "electrum")]
+            Self::Electrum { client, .. } => {
+                use bdk_electrum::electrum_client::ElectrumApi;
+                Some(("electrum", client.inner.block_header(0)?.block_hash()))
+            }
+            #[cfg(feature = "esplora")]
+            Self::Esplora {
+                client, backoff, ..
+            } => Some((
+                "esplora",
+                with_backoff(*backoff, client.get_block_hash(0)).await?,
+            )),
+            #[cfg(feature = "rpc")]
+            Self::RpcClient { client, .. } => Some(("rpc", client.get_block_hash(0)?)),
+            // Compact block filter peers are only found and reached on the network of the wallet.
+            #[cfg(feature = "cbf")]
+            Self::KyotoClient { .. } => None,
+        };
+        let Some((backend, genesis)) = backend else {
+            return Ok(());
+        };
+        if genesis == genesis_block(network).block_hash() {
+            // Every signet shares its genesis block, only a node tells which signet it is on.
+            #[cfg(feature = "rpc")]
+            if let Self::RpcClient {
+                client,
+                signet_challenge,
+            } = self
+                && network == Network::Signet
+            {
+                check_signet_challenge(client, signet_challenge.as_deref())?;
+            }
+            return Ok(());
+        }
+        let chain = [
+            Network::Bitcoin,
+            Network::Testnet,
+            Network::Testnet4,
+            Network::Signet,
+            Network::Regtest,
+        ]
+        .into_iter()
+        .find(|chain| genesis_block(*chain).block_hash() == genesis)
+        .map_or_else(
+            || format!("an unknown chain with genesis block {genesis}"),
+            |chain| chain.to_string(),
+        );
+        Err(Error::coded(
+            ErrorCode::NetworkMismatch,
+            format!(
+                "Network mismatch: the {backend} server is on {chain}, but the wallet is on {network}"
+            ),
+        ))
+    }
+
+    /// Height of the chain tip the backend serves, `None` for compact block filters where the
+    /// peers are not asked for it.
+    pub async fn tip_height(&self) -> Result<Option<u32>, Error> {
+        self.add_request();
+        match self {
+            #[cfg(feature = "electrum")]
+            Self::Electrum { client, .. } => {
+                use bdk_electrum::electrum_client::ElectrumApi;
+                Ok(Some(client.inner.block_headers_subscribe()?.height as u32))
+            }
+            #[cfg(feature = "esplora")]
+            Self::Esplora {
+                client, backoff, ..
+            } => Ok(Some(with_backoff(*backoff, client.get_height()).await?)),
+            #[cfg(feature = "rpc")]
+            Self::RpcClient { client, .. } => Ok(Some(client.get_block_count()? as u32)),
+            #[cfg(feature = "cbf")]
+            Self::KyotoClient { .. } => Ok(None),
+        }
+    }
+
+    /// Whether the backend knows the transaction `txid`, in its mempool or in a block (with the
+    /// `txindex` of Bitcoin Core), `None` for compact block filters where the peers are not asked
+    /// for it.
+    pub async fn has_tx(&self, txid: Txid) -> Result<Option<bool>, Error> {
+        self.add_request();
+        match self {
+            #[cfg(feature = "electrum")]
+            Self::Electrum { client, .. } => {
+                use bdk_electrum::electrum_client::ElectrumApi;
+                // Electrum servers answer an unknown transaction with an error.
+                match client.inner.transaction_get(&txid) {
+                    Ok(_) => Ok(Some(true)),
+                    Err(e) if crate::utils::electrum::is_unknown_tx(&e) => Ok(Some(false)),
+                    Err(e) => Err(e.into()),
+                }
+            }
+            #[cfg(feature = "esplora")]
+            Self::Esplora {
+                client, backoff, ..
+            } => Ok(Some(
+                with_backoff(*backoff, client.get_tx(&txid))
+                    .await?
+                    .is_some(),
+            )),
+            #[cfg(feature = "rpc")]
+            Self::RpcClient { client, .. } => {
+                use bdk_bitcoind_rpc::bitcoincore_rpc::{self, jsonrpc};
+                // -5 (RPC_INVALID_ADDRESS_OR_KEY) is Bitcoin Core's unknown transaction.
+                match client.get_raw_transaction_info(&txid, None) {
+                    Ok(_) => Ok(Some(true)),
+                    Err(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(e)))
+                        if e.code == -5 =>
+                    {
+                        Ok(Some(false))
+                    }
+                    Err(e) => Err(e.into()),
+                }
+            }
+            #[cfg(feature = "cbf")]
+            Self::KyotoClient { .. } => Ok(None),
+        }
+    }
+
+    /// Estimates the fee rate, in sat/vB, to confirm within `target_blocks`.
+    pub async fn estimate_fee(&self, target_blocks: u16) -> Result<f64, Error> {
+        self.add_request();
+        let fee_rate = match self {
+            #[cfg(feature = "electrum")]
+            Self::Electrum { client, .. } => {
+                use bdk_electrum::electrum_client::ElectrumApi;
+                // In BTC/kvB, negative when the server has no estimate.
+                let btc_per_kvb = client.inner.estimate_fee(target_blocks as usize, None)?;
+                (btc_per_kvb >= 0.0).then_some(btc_per_kvb * 100_000.0)
+            }
+            #[cfg(feature = "esplora")]
+            Self::Esplora {
+                client, backoff, ..
+            } => {
+                let estimates = with_backoff(*backoff, client.get_fee_estimates()).await?;
+                bdk_esplora::esplora_client::convert_fee_rate(target_blocks as usize, estimates)
+                    .map(f64::from)
+            }
+            #[cfg(feature = "rpc")]
+            Self::RpcClient { client, .. } => client
+                .estimate_smart_fee(target_blocks, None)?
+                .fee_rate
+                .map(|per_kvb| per_kvb.to_sat() as f64 / 1000.0),
+            // Compact block filters carry no fee estimates.
+            #[cfg(feature = "cbf")]
+            Self::KyotoClient { .. } => None,
+        };
+        fee_rate.ok_or_else(|| {
+            Error::coded(ErrorCode::Backend, format!(
+                "The backend has no fee estimate for {target_blocks} blocks, use --provider mempool"
+            ))
+        })
+    }
+
+    /// Reports the progress of a scan in `format`, through the node of the kyoto client which
+    /// logs the filter progress from its own task.
+    pub fn progress(&self, format: Option<ProgressFormat>) -> Progress {
+        match self {
+            #[cfg(feature = "cbf")]
+            Self::KyotoClient { client } => {
+                client.progress.reset(format);
+                client.progress.clone()
+            }
+            #[cfg(feature = "electrum")]
+            Self::Electrum { .. } => Progress::new(format).batched(),
+            #[allow(unreachable_patterns)]
+            _ => Progress::new(format),
+        }
+    }
+
     pub async fn sync_wallet(&self, wallet: &mut Wallet) -> Result<(), Error> {
+        let _timer = stats::timer(Phase::NetworkSync);
+        #[cfg(any(feature = "electrum", feature = "esplora"))]
+        let item_requests = !self.counts_requests();
         #[cfg(any(feature = "electrum", feature = "esplora"))]
         let request = wallet
             .start_sync_with_revealed_spks()
-            .inspect(|item, progress| {
+            .inspect(move |item, progress| {
+                if let SyncItem::Spk(..) = item {
+                    stats::add_script();
+                }
+                if item_requests {
+                    stats::add_requests(1);
+                }
                 let pc = (100 * progress.consumed()) as f32 / progress.total() as f32;
-                eprintln!("[ SCANNING {pc:03.0}% ] {item}");
+                log::info!("[ SCANNING {pc:03.0}% ] {item}");
             });
         match self {
             #[cfg(feature = "electrum")]
@@ -126,23 +351,23 @@ impl BlockchainClient {
                 let update = client.sync(request, *batch_size, false)?;
                 wallet
                     .apply_update(update)
-                    .map_err(|e| Error::Generic(e.to_string()))
+                    .map_err(|e| Error::coded(ErrorCode::ChainUpdate, e.to_string()))
             }
             #[cfg(feature = "esplora")]
             Self::Esplora {
                 client,
                 parallel_requests,
+                backoff,
             } => {
-                let update = client
-                    .sync(request, *parallel_requests)
+                let update = with_backoff(*backoff, client.sync(request, *parallel_requests))
                     .await
                     .map_err(|e| *e)?;
                 wallet
                     .apply_update(update)
-                    .map_err(|e| Error::Generic(e.to_string()))
+                    .map_err(|e| Error::coded(ErrorCode::ChainUpdate, e.to_string()))
             }
             #[cfg(feature = "rpc")]
-            Self::RpcClient { client } => {
+            Self::RpcClient { client, .. } => {
                 let blockchain_info = client.get_blockchain_info()?;
                 let wallet_cp = wallet.latest_checkpoint();
 
@@ -163,6 +388,7 @@ impl BlockchainClient {
                 );
 
                 while let Some(block_event) = emitter.next_block()? {
+                    stats::add_requests(1);
                     if block_event.block_height() % 10_000 == 0 {
                         let percent_done = f64::from(block_event.block_height())
                             / f64::from(blockchain_info.headers as u32)
@@ -186,9 +412,11 @@ impl BlockchainClient {
                 Ok(())
             }
             #[cfg(feature = "cbf")]
-            Self::KyotoClient { client } => sync_kyoto_client(wallet, client)
-                .await
-                .map_err(|e| Error::Generic(e.to_string())),
+            Self::KyotoClient { client } => {
+                sync_kyoto_client(wallet, client, crate::utils::EventFormat::Summary)
+                    .await
+                    .map_err(|e| Error::coded(ErrorCode::ChainUpdate, e.to_string()))
+            }
         }
     }
 }
@@ -200,6 +428,8 @@ pub struct KyotoClientHandle {
     pub requester: bdk_kyoto::Requester,
     pub update_subscriber:
         tokio::sync::Mutex<bdk_kyoto::UpdateSubscriber<bdk_kyoto::wallets::Single>>,
+    /// Reports the filter progress the node logs from its own task.
+    pub progress: Progress,
 }
 
 #[cfg(any(
@@ -214,52 +444,101 @@ pub(crate) fn new_blockchain_client(
     _wallet: &Wallet,
     _datadir: PathBuf,
 ) -> Result<BlockchainClient, Error> {
-    #[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
-    let url = &wallet_opts.url;
+    #[cfg(feature = "esplora")]
+    let single_url = || match wallet_opts.url.as_slice() {
+        [url] => Ok(url),
+        _ => Err(Error::coded(
+            ErrorCode::Config,
+            "Only the electrum client takes several server urls".to_string(),
+        )),
+    };
     let client = match wallet_opts.client_type {
         #[cfg(feature = "electrum")]
         ClientType::Electrum => {
-            let client = bdk_electrum::electrum_client::Client::new(url)
-                .map(bdk_electrum::BdkElectrumClient::new)?;
+            let client = connect_electrum(
+                wallet_opts,
+                _wallet.network(),
+                &_wallet.local_chain().tip(),
+                &_datadir,
+            )
+            .map(bdk_electrum::BdkElectrumClient::new)?;
             BlockchainClient::Electrum {
                 client: Box::new(client),
-                batch_size: wallet_opts.batch_size,
+                batch_size: wallet_opts.parallel.unwrap_or(wallet_opts.batch_size),
             }
         }
         #[cfg(feature = "esplora")]
         ClientType::Esplora => {
-            let client = bdk_esplora::esplora_client::Builder::new(url).build_async()?;
+            let proxy_opts = &wallet_opts.proxy_opts;
+            let mut builder = bdk_esplora::esplora_client::Builder::new(single_url()?);
+            if let Some(proxy) = crate::utils::http::proxy_url(proxy_opts) {
+                builder = builder.proxy(&proxy);
+            }
+            if let Some(timeout) = proxy_opts.timeout {
+                builder = builder.timeout(timeout.into());
+            }
+            for (name, value) in &wallet_opts.esplora_headers {
+                builder = builder.header(name, value);
+            }
+            let client = builder
+                .max_retries(wallet_opts.esplora_retries)
+                .build_async_with_sleeper()?;
             BlockchainClient::Esplora {
                 client: Box::new(client),
-                parallel_requests: wallet_opts.parallel_requests,
+                parallel_requests: wallet_opts
+                    .parallel
+                    .unwrap_or(wallet_opts.parallel_requests),
+                backoff: wallet_opts.esplora_backoff,
             }
         }
 
         #[cfg(feature = "rpc")]
         ClientType::Rpc => {
-            let auth = match &wallet_opts.cookie {
-                Some(cookie) => bdk_bitcoind_rpc::bitcoincore_rpc::Auth::CookieFile(cookie.into()),
-                None => bdk_bitcoind_rpc::bitcoincore_rpc::Auth::UserPass(
-                    wallet_opts.basic_auth.0.clone(),
-                    wallet_opts.basic_auth.1.clone(),
-                ),
+            ensure_no_proxy(wallet_opts)?;
+            let rpc = rpc_endpoint(wallet_opts, _wallet.network())?;
+            // Calls to `/wallet/<name>` go to that wallet of a node with several loaded.
+            let url = match &rpc.wallet {
+                Some(wallet) => format!("{}/wallet/{wallet}", rpc.url.trim_end_matches('/')),
+                None => rpc.url,
             };
-            let client = bdk_bitcoind_rpc::bitcoincore_rpc::Client::new(url, auth)
-                .map_err(|e| Error::Generic(e.to_string()))?;
+            let client = bdk_bitcoind_rpc::bitcoincore_rpc::Client::new(&url, rpc.auth)
+                .map_err(|e| Error::coded(ErrorCode::BitcoinCoreRpc, e.to_string()))?;
             BlockchainClient::RpcClient {
                 client: Box::new(client),
+                signet_challenge: wallet_opts.signet_challenge.clone(),
             }
         }
 
         #[cfg(feature = "cbf")]
         ClientType::Cbf => {
+            ensure_no_proxy(wallet_opts)?;
+            if is_custom_signet(wallet_opts.signet_challenge.as_deref()) {
+                return Err(Error::coded(
+                    ErrorCode::Config,
+                    "Compact block filters only reach the peers of the default signet, use \
+                     electrum, esplora or rpc for a custom signet"
+                        .to_string(),
+                ));
+            }
+            let cbf_opts = &wallet_opts.compactfilter_opts;
             let scan_type = bdk_kyoto::ScanType::Sync;
-            let builder = bdk_kyoto::builder::Builder::new(_wallet.network());
+            let peers = cbf_opts
+                .peers
+                .iter()
+                .map(|peer| parse_cbf_peer(peer))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut builder = bdk_kyoto::builder::Builder::new(_wallet.network())
+                .required_peers(cbf_opts.conn_count)
+                .add_peers(peers)
+                .data_dir(&_datadir);
+            if cbf_opts.whitelist_only {
+                builder = builder.whitelist_only();
+            }
+            if let Some(timeout) = cbf_opts.response_timeout {
+                builder = builder.response_timeout(std::time::Duration::from_secs(timeout));
+            }
 
-            let light_client = builder
-                .required_peers(wallet_opts.compactfilter_opts.conn_count)
-                .data_dir(&_datadir)
-                .build_with_wallet(_wallet, scan_type)?;
+            let light_client = builder.build_with_wallet(_wallet, scan_type)?;
 
             let (client, logging, update_subscriber) = light_client.subscribe();
             // `start()` spawns the node's run loop on a tokio task internally.
@@ -268,14 +547,24 @@ pub(crate) fn new_blockchain_client(
             let subscriber = tracing_subscriber::FmtSubscriber::new();
             let _ = tracing::subscriber::set_global_default(subscriber);
 
-            tokio::task::spawn(async move {
-                trace_logger(logging.info_subscriber, logging.warning_subscriber).await
+            let progress = Progress::new(None);
+            tokio::task::spawn({
+                let progress = progress.clone();
+                async move {
+                    trace_logger(
+                        logging.info_subscriber,
+                        logging.warning_subscriber,
+                        progress,
+                    )
+                    .await
+                }
             });
 
             BlockchainClient::KyotoClient {
                 client: Box::new(KyotoClientHandle {
                     requester,
                     update_subscriber: tokio::sync::Mutex::new(update_subscriber),
+                    progress,
                 }),
             }
         }
@@ -283,24 +572,261 @@ pub(crate) fn new_blockchain_client(
     Ok(client)
 }
 
+/// The url, credentials and node wallet of the Bitcoin Core rpc of the wallet, read from the
+/// `bitcoin.conf` of `--node` or else taken from its options, `--rpc-wallet` overriding the
+/// `rpcwallet` of `bitcoin.conf`.
+#[cfg(feature = "rpc")]
+pub(crate) fn rpc_endpoint(wallet_opts: &WalletOpts, network: Network) -> Result<NodeRpc, Error> {
+    use bdk_bitcoind_rpc::bitcoincore_rpc::Auth;
+
+    if let Some(node) = &wallet_opts.node {
+        let rpc = crate::utils::bitcoind::node_rpc(node, network)?;
+        return Ok(NodeRpc {
+            wallet: wallet_opts.rpc_wallet.clone().or(rpc.wallet),
+            ..rpc
+        });
+    }
+    let url = match wallet_opts.url.as_slice() {
+        [url] => url.clone(),
+        _ => {
+            return Err(Error::coded(
+                ErrorCode::Config,
+                "Only the electrum client takes several server urls".to_string(),
+            ));
+        }
+    };
+    let auth = match &wallet_opts.cookie {
+        Some(cookie) => Auth::CookieFile(cookie.into()),
+        None => Auth::UserPass(
+            wallet_opts.basic_auth.0.clone(),
+            wallet_opts.basic_auth.1.clone(),
+        ),
+    };
+    Ok(NodeRpc {
+        url,
+        auth,
+        wallet: wallet_opts.rpc_wallet.clone(),
+    })
+}
+
+/// Fails when the signet node of `client` runs another signet than the one of `challenge`, the
+/// default signet when `None`.
+#[cfg(feature = "rpc")]
+fn check_signet_challenge(
+    client: &bdk_bitcoind_rpc::bitcoincore_rpc::Client,
+    challenge: Option<&Script>,
+) -> Result<(), Error> {
+    let info: serde_json::Value = client.call("getblockchaininfo", &[])?;
+    // Older nodes leave the challenge out.
+    let Some(node_challenge) = info["signet_challenge"].as_str() else {
+        return Ok(());
+    };
+    let node_challenge = parse_signet_challenge(node_challenge)?;
+    let challenge = challenge.map_or_else(default_signet_challenge, Script::to_owned);
+    if node_challenge == challenge {
+        return Ok(());
+    }
+    Err(Error::coded(
+        ErrorCode::NetworkMismatch,
+        format!(
+            "Network mismatch: the rpc server is on the signet of challenge {} (magic {}), but the \
+         wallet is on the signet of challenge {} (magic {})",
+            challenge_hex(&node_challenge),
+            signet_magic(&node_challenge),
+            challenge_hex(&challenge),
+            signet_magic(&challenge)
+        ),
+    ))
+}
+
+/// Connects to an electrum (`tcp://`, `ssl://`) or esplora (`http://`, `https://`) url, with the
+/// proxy and TLS options of the wallet, such as the server of `sync --verify-with`.
+#[cfg(any(feature = "electrum", feature = "esplora"))]
+pub(crate) fn new_url_client(
+    wallet_opts: &WalletOpts,
+    url: &str,
+    wallet: &Wallet,
+    datadir: PathBuf,
+) -> Result<BlockchainClient, Error> {
+    let client_type = match url.split_once("://") {
+        #[cfg(feature = "electrum")]
+        Some(("tcp" | "ssl", _)) => ClientType::Electrum,
+        #[cfg(feature = "esplora")]
+        Some(("http" | "https", _)) => ClientType::Esplora,
+        _ => {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "Unsupported server {url}, expected an electrum (tcp://, ssl://) or esplora (http://, https://) url"
+                ),
+            ));
+        }
+    };
+    let url_opts = WalletOpts {
+        client_type,
+        url: vec![url.to_string()],
+        // The headers, such as an API key, are only for the esplora server of the wallet.
+        #[cfg(feature = "esplora")]
+        esplora_headers: if wallet_opts.url.iter().any(|wallet_url| wallet_url == url) {
+            wallet_opts.esplora_headers.clone()
+        } else {
+            Vec::new()
+        },
+        ..wallet_opts.clone()
+    };
+    new_blockchain_client(&url_opts, wallet, datadir)
+}
+
+/// The electrum or esplora servers of the wallet as urls of [`new_url_client`], the electrum
+/// servers without a scheme being plain tcp as for `electrum_client`. Empty for other backends.
+#[cfg(any(feature = "electrum", feature = "esplora"))]
+pub(crate) fn server_urls(wallet_opts: &WalletOpts) -> Vec<String> {
+    match wallet_opts.client_type {
+        #[cfg(feature = "electrum")]
+        ClientType::Electrum => wallet_opts
+            .url
+            .iter()
+            .map(|url| match url.contains("://") {
+                true => url.clone(),
+                false => format!("tcp://{url}"),
+            })
+            .collect(),
+        #[cfg(feature = "esplora")]
+        ClientType::Esplora => wallet_opts.url.clone(),
+        #[allow(unreachable_patterns)]
+        _ => Vec::new(),
+    }
+}
+
+/// Fails when a proxy is set for a client that would connect around it.
+#[cfg(any(feature = "rpc", feature = "cbf"))]
+fn ensure_no_proxy(_wallet_opts: &WalletOpts) -> Result<(), Error> {
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    if _wallet_opts.proxy_opts.proxy.is_some() {
+        return Err(Error::coded(
+            ErrorCode::Config,
+            format!(
+                "The {:?} client does not connect through a proxy",
+                _wallet_opts.client_type
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// File of the wallet data directory naming the last Electrum server that answered.
+#[cfg(feature = "electrum")]
+const ELECTRUM_SERVER_FILE: &str = "electrum_server";
+
+/// Connects to the first Electrum server that answers a ping, in the given order except for the
+/// last healthy server of the wallet, which is tried first.
+#[cfg(feature = "electrum")]
+fn connect_electrum(
+    wallet_opts: &WalletOpts,
+    network: Network,
+    chain: &bdk_wallet::chain::CheckPoint,
+    datadir: &std::path::Path,
+) -> Result<crate::utils::electrum::ElectrumClient, Error> {
+    use crate::utils::electrum::{ElectrumClient, HeaderCache, tls_config};
+    use bdk_electrum::electrum_client::{ConfigBuilder, ElectrumApi, Socks5Config};
+
+    let (proxy_opts, tls_opts) = (&wallet_opts.proxy_opts, &wallet_opts.electrum_tls_opts);
+    let tls = tls_config(tls_opts)?;
+    if (tls_opts.require_tls || tls.is_some())
+        && let Some(server) = wallet_opts
+            .url
+            .iter()
+            .find(|url| !url.starts_with("ssl://"))
+    {
+        return Err(Error::coded(
+            ErrorCode::Backend,
+            format!("Electrum server {server} is not a TLS server, use an ssl:// url"),
+        ));
+    }
+
+    // Only a list of servers has a last healthy one worth trying first.
+    let server_file = (wallet_opts.url.len() > 1).then(|| datadir.join(ELECTRUM_SERVER_FILE));
+    let last_healthy = server_file
+        .as_ref()
+        .and_then(|file| std::fs::read_to_string(file).ok());
+    let mut servers: Vec<&str> = wallet_opts.url.iter().map(String::as_str).collect();
+    servers.sort_by_key(|server| Some(*server) != last_healthy.as_deref());
+
+    // The server name is sent to the proxy unresolved, so onion urls work.
+    let socks5 = proxy_opts
+        .proxy
+        .as_ref()
+        .map(|proxy| match &proxy_opts.proxy_auth {
+            Some((user, passwd)) => {
+                Socks5Config::with_credentials(proxy, user.clone(), passwd.clone())
+            }
+            None => Socks5Config::new(proxy),
+        });
+    let mut config = ConfigBuilder::new().timeout(
+        proxy_opts
+            .timeout
+            .map(|secs| std::time::Duration::from_secs(secs.into())),
+    );
+    if socks5.is_some() {
+        config = config.socks5(socks5).retry(proxy_opts.retries);
+    }
+    let config = config.build();
+    let batch_size = wallet_opts.parallel.unwrap_or(wallet_opts.batch_size);
+    let mut errors = Vec::new();
+    for server in servers {
+        match ElectrumClient::connect(server, &config, tls.as_ref(), batch_size)
+            .and_then(|client| client.ping().map(|()| client))
+        {
+            Ok(client) => {
+                if let Some(server_file) = &server_file
+                    && last_healthy.as_deref() != Some(server)
+                    && let Err(e) = std::fs::write(server_file, server)
+                {
+                    log::warn!("Failed to remember the Electrum server {server}: {e}");
+                }
+                return Ok(client.with_header_cache(HeaderCache::open(
+                    datadir,
+                    network,
+                    chain.clone(),
+                )));
+            }
+            Err(e) => {
+                log::warn!("Electrum server {server} is unavailable: {e}");
+                errors.push(format!("{server}: {e}"));
+            }
+        }
+    }
+    Err(Error::coded(
+        ErrorCode::Backend,
+        format!("No Electrum server is available ({})", errors.join(", ")),
+    ))
+}
+
 // Handle Kyoto Client sync
 #[cfg(feature = "cbf")]
 pub async fn sync_kyoto_client(
     wallet: &mut Wallet,
     handle: &KyotoClientHandle,
+    format: crate::utils::EventFormat,
 ) -> Result<(), Error> {
     if !handle.requester.is_running() {
         tracing::error!("Kyoto node is not running");
-        return Err(Error::Generic("Kyoto node failed to start".to_string()));
+        return Err(Error::coded(
+            ErrorCode::CbfBuilder,
+            "Kyoto node failed to start".to_string(),
+        ));
     }
     tracing::info!("Kyoto node is running");
 
     let update = handle.update_subscriber.lock().await.update().await?;
     tracing::info!("Received update: applying to wallet");
-    let events = wallet
-        .apply_update_events(update)
-        .map_err(|e| Error::Generic(format!("Failed to apply update: {e}")))?;
-    crate::utils::print_wallet_events(&events);
+    let events = wallet.apply_update_events(update).map_err(|e| {
+        Error::coded(
+            ErrorCode::ChainUpdate,
+            format!("Failed to apply update: {e}"),
+        )
+    })?;
+    crate::utils::print_wallet_events(&events, format);
 
     tracing::info!(
         "Chain tip: {}, Transactions: {}, Balance: {}",
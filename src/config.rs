@@ -6,11 +6,18 @@
 ))]
 use crate::client::ClientType;
 use crate::commands::WalletOpts;
-use crate::error::BDKCliError as Error;
-#[cfg(feature = "sqlite")]
-use crate::persister::DatabaseType;
-use bdk_wallet::bitcoin::Network;
+use crate::error::{BDKCliError as Error, ErrorCode};
 #[cfg(any(feature = "sqlite", feature = "redb"))]
+use crate::persister::parse_database_type;
+use crate::utils::amount::Unit;
+use crate::utils::signet::parse_signet_challenge;
+use bdk_wallet::bitcoin::Network;
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -20,17 +27,35 @@ use std::str::FromStr;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WalletConfig {
+    /// Refuses every command that needs network access, for all the wallets of the data
+    /// directory.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub offline: bool,
+    /// URL templates of the block explorers of `--links`, by network.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub explorer_urls: HashMap<String, String>,
+    /// Unit of the amounts when `--unit` is not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<Unit>,
     pub wallets: HashMap<String, WalletConfigInner>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct WalletConfigInner {
     pub wallet: String,
     pub network: String,
     pub ext_descriptor: String,
     pub int_descriptor: Option<String>,
+    #[serde(default)]
+    pub stop_gap: Option<usize>,
+    /// Hex of the challenge of a custom signet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signet_challenge: Option<String>,
     #[cfg(any(feature = "sqlite", feature = "redb"))]
     pub database_type: String,
+    #[cfg(feature = "sqlite")]
+    #[serde(default)]
+    pub encrypt_db: Option<bool>,
     #[cfg(any(
         feature = "electrum",
         feature = "esplora",
@@ -48,6 +73,18 @@ pub struct WalletConfigInner {
     pub batch_size: Option<usize>,
     #[cfg(feature = "esplora")]
     pub parallel_requests: Option<usize>,
+    #[cfg(feature = "esplora")]
+    #[serde(default)]
+    pub esplora_retries: Option<usize>,
+    #[cfg(feature = "esplora")]
+    #[serde(default)]
+    pub esplora_backoff: Option<u64>,
+    #[cfg(feature = "esplora")]
+    #[serde(default)]
+    pub esplora_headers: Option<Vec<String>>,
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    #[serde(default)]
+    pub parallel: Option<usize>,
     #[cfg(feature = "rpc")]
     pub cookie: Option<String>,
     #[cfg(any(feature = "electrum", feature = "esplora"))]
@@ -62,9 +99,33 @@ pub struct WalletConfigInner {
     #[cfg(any(feature = "electrum", feature = "esplora"))]
     #[serde(default)]
     pub proxy_timeout: Option<u8>,
+    #[cfg(feature = "electrum")]
+    #[serde(default)]
+    pub require_tls: Option<bool>,
+    #[cfg(feature = "electrum")]
+    #[serde(default)]
+    pub tls_cert_fingerprint: Option<String>,
+    #[cfg(feature = "electrum")]
+    #[serde(default)]
+    pub tls_ca: Option<String>,
+    #[cfg(feature = "rpc")]
+    #[serde(default)]
+    pub node: Option<String>,
+    #[cfg(feature = "rpc")]
+    #[serde(default)]
+    pub rpc_wallet: Option<String>,
     #[cfg(feature = "cbf")]
     #[serde(default)]
     pub conn_count: Option<u8>,
+    #[cfg(feature = "cbf")]
+    #[serde(default)]
+    pub cbf_peers: Option<Vec<String>>,
+    #[cfg(feature = "cbf")]
+    #[serde(default)]
+    pub cbf_whitelist_only: Option<bool>,
+    #[cfg(feature = "cbf")]
+    #[serde(default)]
+    pub cbf_response_timeout: Option<u64>,
 }
 
 impl WalletConfig {
@@ -74,22 +135,41 @@ impl WalletConfig {
         if !config_path.exists() {
             return Ok(None);
         }
-        let config_content = fs::read_to_string(&config_path)
-            .map_err(|e| Error::Generic(format!("Failed to read config file: {e}")))?;
-        let config: WalletConfig = toml::from_str(&config_content)
-            .map_err(|e| Error::Generic(format!("Failed to parse config file: {e}")))?;
+        let config_content = fs::read_to_string(&config_path).map_err(|e| {
+            Error::coded(
+                ErrorCode::Config,
+                format!("Failed to read config file: {e}"),
+            )
+        })?;
+        let config: WalletConfig = toml::from_str(&config_content).map_err(|e| {
+            Error::coded(
+                ErrorCode::Config,
+                format!("Failed to parse config file: {e}"),
+            )
+        })?;
         Ok(Some(config))
     }
 
     /// Save configuration to a TOML file
     pub fn save(&self, datadir: &Path) -> Result<(), Error> {
         let config_path = datadir.join("config.toml");
-        let config_content = toml::to_string_pretty(self)
-            .map_err(|e| Error::Generic(format!("Failed to serialize config: {e}")))?;
-        fs::create_dir_all(datadir)
-            .map_err(|e| Error::Generic(format!("Failed to create directory {datadir:?}: {e}")))?;
+        let config_content = toml::to_string_pretty(self).map_err(|e| {
+            Error::coded(
+                ErrorCode::Config,
+                format!("Failed to serialize config: {e}"),
+            )
+        })?;
+        fs::create_dir_all(datadir).map_err(|e| {
+            Error::coded(
+                ErrorCode::Io,
+                format!("Failed to create directory {datadir:?}: {e}"),
+            )
+        })?;
         fs::write(&config_path, config_content).map_err(|e| {
-            Error::Generic(format!("Failed to write config file {config_path:?}: {e}"))
+            Error::coded(
+                ErrorCode::Io,
+                format!("Failed to write config file {config_path:?}: {e}"),
+            )
         })?;
         log::debug!("Saved config to {config_path:?}");
         Ok(())
@@ -99,7 +179,7 @@ impl WalletConfig {
     pub fn get_wallet_opts(&self, wallet_name: &str) -> Result<WalletOpts, Error> {
         self.wallets
             .get(wallet_name)
-            .ok_or_else(|| Error::Generic(format!("Wallet {wallet_name} not found in config")))?
+            .ok_or_else(|| Error::WalletNotFound(wallet_name.to_string()))?
             .try_into()
     }
 }
@@ -109,11 +189,10 @@ impl TryFrom<&WalletConfigInner> for WalletOpts {
 
     fn try_from(config: &WalletConfigInner) -> Result<Self, Self::Error> {
         Network::from_str(&config.network)
-            .map_err(|_| Error::Generic("Invalid network".to_string()))?;
+            .map_err(|_| Error::coded(ErrorCode::Config, "Invalid network".to_string()))?;
 
         #[cfg(any(feature = "sqlite", feature = "redb"))]
-        let database_type = DatabaseType::from_str(&config.database_type, true)
-            .map_err(|_| Error::Generic("Invalid database type".to_string()))?;
+        let database_type = parse_database_type(&config.database_type)?;
 
         #[cfg(any(
             feature = "electrum",
@@ -124,16 +203,22 @@ impl TryFrom<&WalletConfigInner> for WalletOpts {
         let client_type = config
             .client_type
             .as_deref()
-            .ok_or_else(|| Error::Generic("Client type missing".into()))
+            .ok_or_else(|| Error::coded(ErrorCode::Config, "Client type missing".into()))
             .and_then(|s| {
                 ClientType::from_str(s, true)
-                    .map_err(|_| Error::Generic("Invalid client type".into()))
+                    .map_err(|_| Error::coded(ErrorCode::Config, "Invalid client type".into()))
             })?;
 
         Ok(WalletOpts {
             wallet: Some(config.wallet.clone()),
             ext_descriptor: config.ext_descriptor.clone(),
             int_descriptor: config.int_descriptor.clone(),
+            stop_gap: config.stop_gap.unwrap_or(20),
+            signet_challenge: config
+                .signet_challenge
+                .as_deref()
+                .map(parse_signet_challenge)
+                .transpose()?,
 
             #[cfg(any(
                 feature = "electrum",
@@ -146,11 +231,18 @@ impl TryFrom<&WalletConfigInner> for WalletOpts {
             #[cfg(any(feature = "sqlite", feature = "redb"))]
             database_type,
 
+            #[cfg(feature = "sqlite")]
+            encrypt_db: config.encrypt_db.unwrap_or(false),
+
             #[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
             url: config
                 .server_url
-                .clone()
-                .ok_or_else(|| Error::Generic("Server url not found".into()))?,
+                .as_deref()
+                .ok_or_else(|| Error::coded(ErrorCode::Config, "Server url not found".into()))?
+                .split(',')
+                .map(|url| url.trim().to_string())
+                .filter(|url| !url.is_empty())
+                .collect(),
 
             #[cfg(feature = "electrum")]
             batch_size: config.batch_size.unwrap_or(10),
@@ -158,6 +250,25 @@ impl TryFrom<&WalletConfigInner> for WalletOpts {
             #[cfg(feature = "esplora")]
             parallel_requests: config.parallel_requests.unwrap_or(5),
 
+            #[cfg(feature = "esplora")]
+            esplora_retries: config.esplora_retries.unwrap_or(6),
+
+            #[cfg(feature = "esplora")]
+            esplora_backoff: config
+                .esplora_backoff
+                .unwrap_or(crate::utils::esplora::DEFAULT_BACKOFF_MILLIS),
+
+            #[cfg(feature = "esplora")]
+            esplora_headers: config
+                .esplora_headers
+                .iter()
+                .flatten()
+                .map(|header| crate::utils::parse_http_header(header))
+                .collect::<Result<_, _>>()?,
+
+            #[cfg(any(feature = "electrum", feature = "esplora"))]
+            parallel: config.parallel,
+
             #[cfg(feature = "rpc")]
             basic_auth: (
                 config.rpc_user.clone().unwrap_or_else(|| "user".into()),
@@ -170,9 +281,18 @@ impl TryFrom<&WalletConfigInner> for WalletOpts {
             #[cfg(feature = "rpc")]
             cookie: config.cookie.clone(),
 
+            #[cfg(feature = "rpc")]
+            node: config.node.as_ref().map(Into::into),
+
+            #[cfg(feature = "rpc")]
+            rpc_wallet: config.rpc_wallet.clone(),
+
             #[cfg(any(feature = "electrum", feature = "esplora"))]
             proxy_opts: crate::commands::ProxyOpts {
-                proxy: config.proxy.clone(),
+                proxy: match &config.proxy {
+                    Some(s) => Some(crate::utils::parse_proxy(s)?),
+                    None => None,
+                },
                 proxy_auth: match &config.proxy_auth {
                     Some(s) => Some(crate::utils::parse_proxy_auth(s)?),
                     None => None,
@@ -181,9 +301,27 @@ impl TryFrom<&WalletConfigInner> for WalletOpts {
                 timeout: config.proxy_timeout,
             },
 
+            #[cfg(feature = "electrum")]
+            electrum_tls_opts: crate::commands::ElectrumTlsOpts {
+                require_tls: config.require_tls.unwrap_or(false),
+                cert_fingerprint: match &config.tls_cert_fingerprint {
+                    Some(s) => Some(crate::utils::electrum::parse_cert_fingerprint(s)?),
+                    None => None,
+                },
+                ca_file: config.tls_ca.as_ref().map(Into::into),
+            },
+
             #[cfg(feature = "cbf")]
             compactfilter_opts: crate::commands::CompactFilterOpts {
                 conn_count: config.conn_count.unwrap_or(2),
+                peers: config
+                    .cbf_peers
+                    .iter()
+                    .flatten()
+                    .map(|peer| crate::utils::parse_cbf_peer(peer).map(|_| peer.clone()))
+                    .collect::<Result<_, _>>()?,
+                whitelist_only: config.cbf_whitelist_only.unwrap_or(false),
+                response_timeout: config.cbf_response_timeout,
             },
         })
     }
@@ -223,8 +361,12 @@ mod tests {
             network: "testnet4".to_string(),
             ext_descriptor: EXT_DESCRIPTOR.to_string(),
             int_descriptor: Some(INT_DESCRIPTOR.to_string()),
+            stop_gap: None,
+            signet_challenge: None,
             #[cfg(any(feature = "sqlite", feature = "redb"))]
             database_type: "sqlite".to_string(),
+            #[cfg(feature = "sqlite")]
+            encrypt_db: None,
 
             #[cfg(any(
                 feature = "electrum",
@@ -240,6 +382,14 @@ mod tests {
             batch_size: None,
             #[cfg(feature = "esplora")]
             parallel_requests: None,
+            #[cfg(feature = "esplora")]
+            esplora_retries: None,
+            #[cfg(feature = "esplora")]
+            esplora_backoff: None,
+            #[cfg(feature = "esplora")]
+            esplora_headers: Some(vec!["Authorization: Bearer key".to_string()]),
+            #[cfg(any(feature = "electrum", feature = "esplora"))]
+            parallel: None,
             #[cfg(feature = "rpc")]
             rpc_user: None,
             #[cfg(feature = "rpc")]
@@ -254,8 +404,24 @@ mod tests {
             proxy_retries: None,
             #[cfg(any(feature = "electrum", feature = "esplora"))]
             proxy_timeout: None,
+            #[cfg(feature = "electrum")]
+            require_tls: None,
+            #[cfg(feature = "electrum")]
+            tls_cert_fingerprint: None,
+            #[cfg(feature = "electrum")]
+            tls_ca: None,
+            #[cfg(feature = "rpc")]
+            node: None,
+            #[cfg(feature = "rpc")]
+            rpc_wallet: None,
             #[cfg(feature = "cbf")]
             conn_count: None,
+            #[cfg(feature = "cbf")]
+            cbf_peers: None,
+            #[cfg(feature = "cbf")]
+            cbf_whitelist_only: None,
+            #[cfg(feature = "cbf")]
+            cbf_response_timeout: None,
         };
 
         let opts: WalletOpts = (&wallet_config)
@@ -286,15 +452,25 @@ mod tests {
         assert_eq!(opts.client_type, ClientType::Cbf);
 
         #[cfg(feature = "sqlite")]
-        assert_eq!(opts.database_type, DatabaseType::Sqlite);
+        assert_eq!(opts.database_type, crate::persister::DatabaseType::Sqlite);
 
         assert_eq!(opts.ext_descriptor, EXT_DESCRIPTOR);
+        assert_eq!(opts.stop_gap, 20);
+
+        #[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
+        assert_eq!(opts.url, ["https://example.com/testnet/api"]);
 
         #[cfg(feature = "electrum")]
         assert_eq!(opts.batch_size, 10);
 
         #[cfg(feature = "esplora")]
         assert_eq!(opts.parallel_requests, 5);
+
+        #[cfg(feature = "esplora")]
+        assert_eq!(
+            opts.esplora_headers,
+            [("Authorization".to_string(), "Bearer key".to_string())]
+        );
     }
 
     #[cfg(any(
@@ -310,8 +486,12 @@ mod tests {
             network: "regtest".to_string(),
             ext_descriptor: "desc".to_string(),
             int_descriptor: None,
+            stop_gap: None,
+            signet_challenge: None,
             #[cfg(any(feature = "sqlite", feature = "redb"))]
             database_type: "sqlite".to_string(),
+            #[cfg(feature = "sqlite")]
+            encrypt_db: None,
             #[cfg(any(
                 feature = "electrum",
                 feature = "esplora",
@@ -325,6 +505,14 @@ mod tests {
             batch_size: None,
             #[cfg(feature = "esplora")]
             parallel_requests: None,
+            #[cfg(feature = "esplora")]
+            esplora_retries: None,
+            #[cfg(feature = "esplora")]
+            esplora_backoff: None,
+            #[cfg(feature = "esplora")]
+            esplora_headers: None,
+            #[cfg(any(feature = "electrum", feature = "esplora"))]
+            parallel: None,
             #[cfg(feature = "rpc")]
             rpc_user: None,
             #[cfg(feature = "rpc")]
@@ -339,8 +527,24 @@ mod tests {
             proxy_retries: None,
             #[cfg(any(feature = "electrum", feature = "esplora"))]
             proxy_timeout: None,
+            #[cfg(feature = "electrum")]
+            require_tls: None,
+            #[cfg(feature = "electrum")]
+            tls_cert_fingerprint: None,
+            #[cfg(feature = "electrum")]
+            tls_ca: None,
+            #[cfg(feature = "rpc")]
+            node: None,
+            #[cfg(feature = "rpc")]
+            rpc_wallet: None,
             #[cfg(feature = "cbf")]
             conn_count: None,
+            #[cfg(feature = "cbf")]
+            cbf_peers: None,
+            #[cfg(feature = "cbf")]
+            cbf_whitelist_only: None,
+            #[cfg(feature = "cbf")]
+            cbf_response_timeout: None,
         };
 
         let result: Result<WalletOpts, Error> = (&inner).try_into();
@@ -1,6 +1,9 @@
 use bdk_wallet::bitcoin::hex::HexToBytesError;
 use bdk_wallet::bitcoin::psbt::ExtractTxError;
 use bdk_wallet::bitcoin::{base64, consensus};
+use bdk_wallet::error::{BuildFeeBumpError, CreateTxError};
+use serde::Serialize;
+use serde_json::{Value, json};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -37,6 +40,36 @@ pub enum BDKCliError {
     #[error("Generic error: {0}")]
     Generic(String),
 
+    #[error(
+        "This command needs network access, which is disabled by --offline or `offline = true` in config.toml"
+    )]
+    Offline,
+
+    #[error("{0}")]
+    ReadOnly(String),
+
+    #[error("{0}")]
+    Unauthorized(String),
+
+    #[error("No wallet config found")]
+    NoConfig,
+
+    #[error("Wallet '{0}' not found in config")]
+    WalletNotFound(String),
+
+    #[error("Wrong password, or {0} is corrupted")]
+    WrongPassword(String),
+
+    #[error("Confirm {0} in a terminal, or pass --yes to skip the confirmation")]
+    ConfirmationRequired(String),
+
+    #[error("Cancelled {0}")]
+    Cancelled(String),
+
+    /// A failure with its own code but no error type, such as a network mismatch.
+    #[error("{1}")]
+    Coded(ErrorCode, String),
+
     #[error("Hex conversion error: {0}")]
     HexToArrayError(#[from] bdk_wallet::bitcoin::hashes::hex::HexToArrayError),
 
@@ -199,3 +232,258 @@ where
         BDKCliError::Generic(e.to_string())
     }
 }
+
+/// The kinds of failure, each exiting with its own process exit code. Exit code 2 is left to the
+/// invalid command lines rejected by clap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// A failure without a more precise category, exit code 1.
+    Internal,
+    /// Invalid keys, descriptors, addresses, passwords or encodings, exit code 3.
+    InvalidInput,
+    /// A missing or unreadable wallet, config or database, exit code 4.
+    Wallet,
+    /// A transaction that cannot be built, bumped or signed, exit code 5.
+    Transaction,
+    /// A failure of the blockchain backend or of another server, exit code 6.
+    Network,
+    /// A command refused by `--offline` or `--read-only`, or not confirmed, exit code 7.
+    Refused,
+}
+
+impl ErrorCategory {
+    /// The process exit code of the failures of this category.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Internal => 1,
+            Self::InvalidInput => 3,
+            Self::Wallet => 4,
+            Self::Transaction => 5,
+            Self::Network => 6,
+            Self::Refused => 7,
+        }
+    }
+}
+
+impl BDKCliError {
+    /// A failure with the stable `code`.
+    pub(crate) fn coded(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self::Coded(code, message.into())
+    }
+
+    /// The stable code of the failure, for scripts to branch on rather than on the message.
+    pub fn code(&self) -> ErrorCode {
+        use ErrorCode::*;
+        match self {
+            Self::BIP39Error(_) => InvalidMnemonic,
+            Self::BIP32Error(_) => InvalidBip32,
+            Self::BuildFeeBumpError(e) => match e {
+                BuildFeeBumpError::TransactionNotFound(_) => TxNotFound,
+                BuildFeeBumpError::TransactionConfirmed(_) => TxAlreadyConfirmed,
+                BuildFeeBumpError::IrreplaceableTransaction(_) => TxNotReplaceable,
+                _ => TxFeeBump,
+            },
+            Self::ChecksumMismatch => ChecksumMismatch,
+            Self::CreateTx(e) => match e {
+                CreateTxError::FeeTooLow { .. } => TxFeeTooLow,
+                CreateTxError::FeeRateTooLow { .. } => TxFeeRateTooLow,
+                CreateTxError::CoinSelection(_) => InsufficientFunds,
+                CreateTxError::NoRecipients => TxNoRecipients,
+                CreateTxError::NoUtxosSelected => TxNoUtxosSelected,
+                CreateTxError::OutputBelowDustLimit(_) => TxOutputBelowDust,
+                _ => TxCreate,
+            },
+            #[cfg(feature = "silent-payments")]
+            Self::SilentPaymentParseError(_) => InvalidSilentPaymentAddress,
+            Self::DescriptorError(_) => InvalidDescriptor,
+            Self::DescriptorKeyParseError(_) => InvalidDescriptorKey,
+            Self::DecodeError(_) => InvalidBase64,
+            Self::Generic(_) => Generic,
+            Self::Offline => OfflineMode,
+            Self::ReadOnly(_) => ReadOnlyMode,
+            Self::Unauthorized(_) => Unauthorized,
+            Self::NoConfig => NoWalletConfig,
+            Self::WalletNotFound(_) => WalletNotFound,
+            Self::WrongPassword(_) => WrongPassword,
+            Self::ConfirmationRequired(_) => ConfirmationRequired,
+            Self::Cancelled(_) => Cancelled,
+            Self::Coded(code, _) => *code,
+            Self::HexToArrayError(_) | Self::Hex(_) => InvalidHex,
+            Self::KeyError(_) => InvalidKey,
+            Self::LocalChainError(_) => ChainUpdate,
+            Self::MiniscriptError(_) => InvalidMiniscript,
+            Self::MiniscriptCompilerError(_) => PolicyCompilation,
+            Self::ParseError(_) => InvalidAddress,
+            Self::ParseOutPointError(_) => InvalidOutpoint,
+            Self::PsbtExtractTxError(_) => PsbtExtractTx,
+            Self::PsbtError(_) => InvalidPsbt,
+            #[cfg(feature = "sqlite")]
+            Self::RusqliteError(_) => Database,
+            #[cfg(feature = "redb")]
+            Self::RedbStoreError(_) | Self::RedbDatabaseError(_) => Database,
+            Self::SerdeJson(_) => InvalidJson,
+            Self::Serde(_) => InvalidEncoding,
+            Self::SignerError(_) => SigningFailed,
+            #[cfg(feature = "compiler")]
+            Self::Secp256k1Error(_) => Secp256k1,
+            #[cfg(feature = "electrum")]
+            Self::Electrum(_) => Electrum,
+            #[cfg(feature = "esplora")]
+            Self::Esplora(_) => Esplora,
+            Self::Chain(_) => ChainCannotConnect,
+            #[cfg(feature = "rpc")]
+            Self::BitcoinCoreRpcError(_) => BitcoinCoreRpc,
+            #[cfg(feature = "cbf")]
+            Self::KyotoBuilderError(_) => CbfBuilder,
+            #[cfg(feature = "cbf")]
+            Self::KyotoUpdateError(_) => CbfUpdate,
+            #[cfg(any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf",
+            ))]
+            Self::ReqwestError(_) => Http,
+            #[cfg(feature = "payjoin")]
+            Self::PayjoinUrlParse(_) => PayjoinUrl,
+            #[cfg(feature = "payjoin")]
+            Self::PayjoinSendResponse(_)
+            | Self::PayjoinSenderBuild(_)
+            | Self::PayjoinReceive(_)
+            | Self::PayjoinSelection(_)
+            | Self::PayjoinInputContribution(_)
+            | Self::PayjoinCreateRequest(_) => Payjoin,
+            #[cfg(feature = "payjoin")]
+            Self::PayjoinDb(_) => PayjoinDatabase,
+            #[cfg(feature = "bip322")]
+            Self::Bip322Error(_) => Bip322,
+        }
+    }
+
+    /// The category of the failure, which sets the process exit code.
+    pub fn category(&self) -> ErrorCategory {
+        self.code().category()
+    }
+
+    /// The failure as the `{"error": {"code", "category", "message"}}` output of `--output json`.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "error": {
+                "code": self.code(),
+                "category": self.category(),
+                "message": self.to_string(),
+            }
+        })
+    }
+}
+
+/// Declares the [`ErrorCode`]s, each with its stable name and its category.
+macro_rules! error_codes {
+    ($($variant:ident => ($name:literal, $category:ident),)*) => {
+        /// The stable codes of the failures, for scripts to branch on rather than on the
+        /// messages.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+        pub enum ErrorCode {
+            $(
+                #[serde(rename = $name)]
+                $variant,
+            )*
+        }
+
+        impl ErrorCode {
+            /// The name of the code, such as `TX_FEE_TOO_LOW`.
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $name,)*
+                }
+            }
+
+            /// The category of the failures of this code.
+            pub fn category(self) -> ErrorCategory {
+                match self {
+                    $(Self::$variant => ErrorCategory::$category,)*
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    Generic => ("GENERIC", Internal),
+    Io => ("IO", Internal),
+    InvalidArgument => ("INVALID_ARGUMENT", InvalidInput),
+    InvalidMnemonic => ("INVALID_MNEMONIC", InvalidInput),
+    InvalidBip32 => ("INVALID_BIP32", InvalidInput),
+    ChecksumMismatch => ("CHECKSUM_MISMATCH", InvalidInput),
+    InvalidSilentPaymentAddress => ("INVALID_SILENT_PAYMENT_ADDRESS", InvalidInput),
+    InvalidDescriptor => ("INVALID_DESCRIPTOR", InvalidInput),
+    InvalidDescriptorKey => ("INVALID_DESCRIPTOR_KEY", InvalidInput),
+    InvalidBase64 => ("INVALID_BASE64", InvalidInput),
+    WrongPassword => ("WRONG_PASSWORD", InvalidInput),
+    InvalidHex => ("INVALID_HEX", InvalidInput),
+    InvalidKey => ("INVALID_KEY", InvalidInput),
+    InvalidMiniscript => ("INVALID_MINISCRIPT", InvalidInput),
+    PolicyCompilation => ("POLICY_COMPILATION", InvalidInput),
+    InvalidAddress => ("INVALID_ADDRESS", InvalidInput),
+    InvalidOutpoint => ("INVALID_OUTPOINT", InvalidInput),
+    InvalidJson => ("INVALID_JSON", InvalidInput),
+    InvalidEncoding => ("INVALID_ENCODING", InvalidInput),
+    Secp256k1 => ("SECP256K1", InvalidInput),
+    PayjoinUrl => ("PAYJOIN_URL", InvalidInput),
+    Bip322 => ("BIP322", InvalidInput),
+    NetworkMismatch => ("NETWORK_MISMATCH", InvalidInput),
+    InvalidPsbtVersion => ("INVALID_PSBT_VERSION", InvalidInput),
+    InvalidUr => ("INVALID_UR", InvalidInput),
+    InvalidBbqr => ("INVALID_BBQR", InvalidInput),
+    InvalidLabel => ("INVALID_LABEL", InvalidInput),
+    NoWalletConfig => ("NO_WALLET_CONFIG", Wallet),
+    WalletNotFound => ("WALLET_NOT_FOUND", Wallet),
+    WalletExists => ("WALLET_EXISTS", Wallet),
+    WalletLocked => ("WALLET_LOCKED", Wallet),
+    Config => ("CONFIG", Wallet),
+    Database => ("DATABASE", Wallet),
+    PayjoinDatabase => ("PAYJOIN_DATABASE", Wallet),
+    Backup => ("BACKUP", Wallet),
+    Keystore => ("KEYSTORE", Wallet),
+    LabelsFile => ("LABELS_FILE", Wallet),
+    TxNotFound => ("TX_NOT_FOUND", Transaction),
+    TxAlreadyConfirmed => ("TX_ALREADY_CONFIRMED", Transaction),
+    TxNotReplaceable => ("TX_NOT_REPLACEABLE", Transaction),
+    TxFeeBump => ("TX_FEE_BUMP", Transaction),
+    TxFeeTooLow => ("TX_FEE_TOO_LOW", Transaction),
+    TxFeeRateTooLow => ("TX_FEE_RATE_TOO_LOW", Transaction),
+    InsufficientFunds => ("INSUFFICIENT_FUNDS", Transaction),
+    TxNoRecipients => ("TX_NO_RECIPIENTS", Transaction),
+    TxNoUtxosSelected => ("TX_NO_UTXOS_SELECTED", Transaction),
+    TxOutputBelowDust => ("TX_OUTPUT_BELOW_DUST", Transaction),
+    TxCreate => ("TX_CREATE", Transaction),
+    NoSpendableUtxos => ("NO_SPENDABLE_UTXOS", Transaction),
+    PsbtExtractTx => ("PSBT_EXTRACT_TX", Transaction),
+    InvalidPsbt => ("INVALID_PSBT", Transaction),
+    SigningFailed => ("SIGNING_FAILED", Transaction),
+    ExternalSigner => ("EXTERNAL_SIGNER", Transaction),
+    HardwareWallet => ("HARDWARE_WALLET", Transaction),
+    ChainUpdate => ("CHAIN_UPDATE", Network),
+    ChainCannotConnect => ("CHAIN_CANNOT_CONNECT", Network),
+    Backend => ("BACKEND", Network),
+    Broadcast => ("BROADCAST", Network),
+    Electrum => ("ELECTRUM", Network),
+    Esplora => ("ESPLORA", Network),
+    BitcoinCoreRpc => ("BITCOIN_CORE_RPC", Network),
+    CbfBuilder => ("CBF_BUILDER", Network),
+    CbfUpdate => ("CBF_UPDATE", Network),
+    Http => ("HTTP", Network),
+    Payjoin => ("PAYJOIN", Network),
+    OfflineMode => ("OFFLINE_MODE", Refused),
+    ReadOnlyMode => ("READ_ONLY_MODE", Refused),
+    Unauthorized => ("UNAUTHORIZED", Refused),
+    ConfirmationRequired => ("CONFIRMATION_REQUIRED", Refused),
+    Cancelled => ("CANCELLED", Refused),
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Backend benchmarks
+//!
+//! `backend bench` measures the electrum and esplora servers of a wallet, and candidate servers,
+//! with the requests a sync makes, to choose the fastest one.
+
+use crate::client::{BlockchainClient, new_url_client, server_urls};
+use crate::commands::BackendSubCommand;
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::handlers::{AppContext, AsyncAppCommand, OfflineOperations};
+use crate::utils::output::{FormatOutput, ListResult};
+use crate::utils::types::ServerBench;
+use crate::utils::{load_wallet_config, parse_raw_tx};
+use bdk_wallet::bitcoin::{ScriptBuf, Transaction};
+use bdk_wallet::chain::spk_client::SyncRequest;
+use bdk_wallet::{KeychainKind, Wallet};
+use clap::Parser;
+use clap::builder::RangedU64ValueParser;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+#[cfg(feature = "electrum")]
+use {crate::client::BlockchainClient::Electrum, bdk_electrum::electrum_client::ElectrumApi};
+#[cfg(feature = "esplora")]
+use {
+    crate::client::BlockchainClient::Esplora, crate::utils::esplora::with_backoff,
+    bdk_esplora::EsploraAsyncExt,
+};
+
+impl BackendSubCommand {
+    pub async fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<(), Error> {
+        match self {
+            BackendSubCommand::Bench(bench_command) => bench_command
+                .execute(ctx)
+                .await?
+                .write_out(std::io::stdout()),
+        }
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct BenchCommand {
+    /// Candidate electrum (tcp://, ssl://) or esplora (http://, https://) server, measured after
+    /// the servers of the wallet. Can be repeated or comma separated.
+    #[arg(env = "BENCH_URL", long = "url", value_delimiter = ',')]
+    urls: Vec<String>,
+    /// Number of wallet scripts queried to measure the throughput of each server.
+    #[arg(
+        long = "scripts",
+        default_value_t = 50,
+        value_parser = RangedU64ValueParser::<u32>::new().range(1..)
+    )]
+    scripts: u32,
+    /// Signed raw transaction, hex or BBQr encoded, broadcast to every server to measure the
+    /// broadcast round-trip. Without it, no broadcast is measured.
+    #[arg(long = "tx")]
+    tx: Option<String>,
+}
+
+impl AsyncAppCommand<AppContext<OfflineOperations<'_>>> for BenchCommand {
+    type Output = ListResult<ServerBench>;
+
+    async fn execute(
+        &self,
+        ctx: &mut AppContext<OfflineOperations<'_>>,
+    ) -> Result<Self::Output, Error> {
+        let (wallet_opts, _) = load_wallet_config(&ctx.datadir, &ctx.state.wallet_name)?;
+        let tx = self.tx.as_deref().map(parse_raw_tx).transpose()?;
+        let datadir = ctx.datadir.join(&ctx.state.wallet_name);
+
+        let mut urls: Vec<String> = Vec::new();
+        for url in server_urls(&wallet_opts)
+            .into_iter()
+            .chain(self.urls.iter().cloned())
+        {
+            if !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+        if urls.is_empty() {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                "No electrum or esplora server to measure, pass one with --url".to_string(),
+            ));
+        }
+
+        let scripts: Vec<ScriptBuf> = (0..self.scripts)
+            .map(|index| {
+                ctx.state
+                    .wallet
+                    .peek_address(KeychainKind::External, index)
+                    .script_pubkey()
+            })
+            .collect();
+
+        let mut servers = Vec::with_capacity(urls.len());
+        for url in urls {
+            let mut bench = ServerBench {
+                backend: match url.split_once("://") {
+                    Some(("tcp" | "ssl", _)) => "electrum",
+                    Some(("http" | "https", _)) => "esplora",
+                    _ => "unknown",
+                }
+                .to_string(),
+                url,
+                connect_ms: None,
+                header_ms: None,
+                scripts: scripts.len(),
+                scripts_per_sec: None,
+                broadcast_ms: None,
+                error: None,
+            };
+            let measured = measure(
+                &mut bench,
+                &wallet_opts,
+                ctx.state.wallet,
+                datadir.clone(),
+                &scripts,
+                tx.as_ref(),
+            )
+            .await;
+            if let Err(e) = measured {
+                bench.error = Some(e.to_string());
+            }
+            servers.push(bench);
+        }
+
+        Ok(ListResult::new(servers))
+    }
+}
+
+/// Measures the server of `bench` step by step, keeping the measurements taken before a failure.
+async fn measure(
+    bench: &mut ServerBench,
+    wallet_opts: &crate::commands::WalletOpts,
+    wallet: &Wallet,
+    datadir: PathBuf,
+    scripts: &[ScriptBuf],
+    tx: Option<&Transaction>,
+) -> Result<(), Error> {
+    // Connecting includes the first round-trip, as esplora connects on its first request.
+    let start = Instant::now();
+    let client = new_url_client(wallet_opts, &bench.url, wallet, datadir)?;
+    match &client {
+        #[cfg(feature = "electrum")]
+        Electrum { client, .. } => client.inner.ping()?,
+        #[cfg(feature = "esplora")]
+        Esplora {
+            client, backoff, ..
+        } => {
+            with_backoff(*backoff, client.get_height()).await?;
+        }
+        #[allow(unreachable_patterns)]
+        _ => {}
+    }
+    bench.connect_ms = Some(millis(start.elapsed()));
+
+    let start = Instant::now();
+    match &client {
+        #[cfg(feature = "electrum")]
+        Electrum { client, .. } => {
+            client.inner.block_headers_subscribe()?;
+        }
+        #[cfg(feature = "esplora")]
+        Esplora {
+            client, backoff, ..
+        } => {
+            with_backoff(*backoff, async {
+                let tip = client.get_tip_hash().await?;
+                client.get_header_by_hash(&tip).await
+            })
+            .await?;
+        }
+        #[allow(unreachable_patterns)]
+        _ => {}
+    }
+    bench.header_ms = Some(millis(start.elapsed()));
+
+    let request = SyncRequest::builder().spks(scripts.iter().cloned()).build();
+    let start = Instant::now();
+    match &client {
+        #[cfg(feature = "electrum")]
+        Electrum { client, batch_size } => {
+            let _ = client.sync(request, *batch_size, false)?;
+        }
+        #[cfg(feature = "esplora")]
+        Esplora {
+            client,
+            parallel_requests,
+            backoff,
+        } => {
+            let _ = with_backoff(*backoff, client.sync(request, *parallel_requests))
+                .await
+                .map_err(|e| *e)?;
+        }
+        #[allow(unreachable_patterns)]
+        _ => {}
+    }
+    bench.scripts_per_sec = Some(scripts.len() as f64 / start.elapsed().as_secs_f64());
+
+    // Only the round-trip counts: the servers after the first one may answer that they already
+    // have the transaction.
+    if let Some(tx) = tx {
+        let start = Instant::now();
+        let _ = BlockchainClient::broadcast(&client, tx.clone()).await;
+        bench.broadcast_ms = Some(millis(start.elapsed()));
+    }
+
+    Ok(())
+}
+
+fn millis(elapsed: Duration) -> f64 {
+    (elapsed.as_secs_f64() * 10_000.0).round() / 10.0
+}
@@ -0,0 +1,297 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Wallet backup bundles
+//!
+//! `wallet backup` packs what it takes to move a wallet to another machine into one zstd
+//! compressed tar archive: a manifest, the wallet config with its public descriptors, the changes
+//! recorded in its database and its labels. The database changes restore into either database
+//! type. Encrypted bundles are sealed like the keystore.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+
+use crate::config::{WalletConfig, WalletConfigInner};
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::handlers::{AppCommand, AppContext, Init};
+use crate::labels::LABELS_FILE;
+use crate::utils::keystore::{self, WalletDescriptors};
+use crate::utils::read_backup_password;
+use crate::utils::types::BackupResult;
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+#[cfg(any(feature = "sqlite", feature = "redb"))]
+use {
+    crate::commands::WalletOpts,
+    crate::persister::{Persister, new_persisted_wallet},
+    crate::utils::prepare_wallet_db_dir,
+    bdk_wallet::{ChangeSet, WalletPersister, bitcoin::Network},
+    std::str::FromStr,
+};
+
+/// Environment variable holding the password of encrypted backup bundles, asked for on the
+/// terminal otherwise.
+pub const PASSWORD_ENV: &str = "BDK_CLI_BACKUP_PASSWORD";
+/// Layout version of the bundles, refused on restore when unknown.
+const BUNDLE_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "manifest.json";
+const CONFIG_FILE: &str = "wallet.toml";
+#[cfg_attr(not(any(feature = "sqlite", feature = "redb")), allow(dead_code))]
+const CHANGESET_FILE: &str = "changeset.json";
+/// The first bytes of a zstd frame, telling plain bundles from encrypted ones.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    wallet: String,
+    network: String,
+    created_at: u64,
+    bdk_cli_version: String,
+}
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct BackupCommand {
+    /// Writes the bundle to this file, which must not exist yet.
+    #[arg(short = 'o', long = "out")]
+    pub(crate) out: PathBuf,
+
+    /// Encrypts the bundle under the password of `BDK_CLI_BACKUP_PASSWORD`, or asked for on the
+    /// terminal.
+    #[arg(long = "encrypt")]
+    pub(crate) encrypt: bool,
+
+    /// The wallet to back up, set from `--wallet`.
+    #[arg(skip)]
+    pub(crate) wallet: String,
+}
+
+impl AppCommand<AppContext<Init>> for BackupCommand {
+    type Output = BackupResult;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let mut config = WalletConfig::load(&ctx.datadir)?.ok_or(Error::NoConfig)?;
+        let mut wallet_config = config
+            .wallets
+            .remove(&self.wallet)
+            .ok_or_else(|| Error::WalletNotFound(self.wallet.clone()))?;
+        let network = wallet_config.network.clone();
+
+        let mut files = BTreeMap::new();
+
+        #[cfg(any(feature = "sqlite", feature = "redb"))]
+        {
+            let wallet_opts = WalletOpts::try_from(&wallet_config)?;
+            let wallet_dir = prepare_wallet_db_dir(&ctx.datadir, &self.wallet)?;
+            let mut persister =
+                Persister::open(&wallet_opts, &ctx.datadir, &wallet_dir, &self.wallet)?;
+            let changeset = WalletPersister::initialize(&mut persister)?;
+            files.insert(CHANGESET_FILE, serde_json::to_vec(&changeset)?);
+        }
+
+        // Private keys stay behind, in the config or the keystore of this machine.
+        let descriptors = WalletDescriptors {
+            ext_descriptor: wallet_config.ext_descriptor.clone(),
+            int_descriptor: wallet_config.int_descriptor.clone(),
+        };
+        if let Some(public) = keystore::public_descriptors(&descriptors)? {
+            wallet_config.ext_descriptor = public.ext_descriptor;
+            wallet_config.int_descriptor = public.int_descriptor;
+        }
+        let wallet_toml = toml::to_string_pretty(&wallet_config)
+            .map_err(|e| backup_error(format!("Failed to serialize config: {e}")))?;
+        files.insert(CONFIG_FILE, wallet_toml.into_bytes());
+
+        let labels_path = ctx.datadir.join(&self.wallet).join(LABELS_FILE);
+        if labels_path.exists() {
+            let labels = fs::read(&labels_path)
+                .map_err(|e| backup_error(format!("Failed to read {labels_path:?}: {e}")))?;
+            files.insert(LABELS_FILE, labels);
+        }
+
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        let manifest = Manifest {
+            version: BUNDLE_VERSION,
+            wallet: self.wallet.clone(),
+            network: network.clone(),
+            created_at,
+            bdk_cli_version: env!("CARGO_PKG_VERSION").to_string(),
+        };
+        files.insert(MANIFEST_FILE, serde_json::to_vec_pretty(&manifest)?);
+
+        let mut bundle = pack(&files, created_at)?;
+        if self.encrypt {
+            let password = read_backup_password(true)?;
+            bundle = keystore::encrypt(&bundle, &password)?;
+        }
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let write = |mut file: fs::File| std::io::Write::write_all(&mut file, &bundle);
+        options
+            .open(&self.out)
+            .and_then(write)
+            .map_err(|e| backup_error(format!("Failed to write {:?}: {e}", self.out)))?;
+
+        Ok(BackupResult {
+            wallet: self.wallet.clone(),
+            network,
+            file: self.out.display().to_string(),
+            encrypted: self.encrypt,
+            contents: files.keys().map(|name| name.to_string()).collect(),
+        })
+    }
+}
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct RestoreCommand {
+    /// The bundle written by `wallet backup`.
+    pub(crate) file: PathBuf,
+
+    /// The wallet to restore the bundle as, set from `--wallet`.
+    #[arg(skip)]
+    pub(crate) wallet: String,
+}
+
+impl AppCommand<AppContext<Init>> for RestoreCommand {
+    type Output = BackupResult;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let bundle = fs::read(&self.file)
+            .map_err(|e| backup_error(format!("Failed to read {:?}: {e}", self.file)))?;
+        let encrypted = !bundle.starts_with(&ZSTD_MAGIC);
+        let bundle = if encrypted {
+            let password = read_backup_password(false)?;
+            keystore::decrypt(&bundle, &password, "the backup bundle")?
+        } else {
+            Zeroizing::new(bundle)
+        };
+        let mut files = unpack(&bundle)?;
+        let mut take = |name: &str| {
+            files
+                .remove(name)
+                .ok_or_else(|| backup_error(format!("The backup bundle has no {name}")))
+        };
+
+        let manifest: Manifest = serde_json::from_slice(&take(MANIFEST_FILE)?)?;
+        if manifest.version != BUNDLE_VERSION {
+            return Err(backup_error(format!(
+                "Unsupported backup bundle version {}, this bdk-cli reads version {BUNDLE_VERSION}",
+                manifest.version
+            )));
+        }
+        let wallet_toml = String::from_utf8_lossy(&take(CONFIG_FILE)?).into_owned();
+        let mut wallet_config: WalletConfigInner = toml::from_str(&wallet_toml)
+            .map_err(|e| backup_error(format!("Failed to parse the bundled config: {e}")))?;
+        wallet_config.wallet = self.wallet.clone();
+
+        let mut config = WalletConfig::load(&ctx.datadir)?.unwrap_or(WalletConfig {
+            offline: false,
+            explorer_urls: HashMap::new(),
+            unit: None,
+            wallets: HashMap::new(),
+        });
+        let wallet_dir = ctx.datadir.join(&self.wallet);
+        if config.wallets.contains_key(&self.wallet) || wallet_dir.join("wallet.sqlite").exists() {
+            return Err(Error::coded(
+                ErrorCode::WalletExists,
+                format!(
+                    "Wallet '{}' already exists, restore the bundle under another name with --wallet",
+                    self.wallet
+                ),
+            ));
+        }
+
+        #[cfg(any(feature = "sqlite", feature = "redb"))]
+        {
+            let changeset: ChangeSet = serde_json::from_slice(&take(CHANGESET_FILE)?)?;
+            let wallet_opts = WalletOpts::try_from(&wallet_config)?;
+            let network = Network::from_str(&wallet_config.network)
+                .map_err(|_| backup_error("Invalid network in the bundled config".to_string()))?;
+            let wallet_dir = prepare_wallet_db_dir(&ctx.datadir, &self.wallet)?;
+            let mut persister =
+                Persister::open(&wallet_opts, &ctx.datadir, &wallet_dir, &self.wallet)?;
+            WalletPersister::persist(&mut persister, &changeset)?;
+            // Loading checks the restored changes against the descriptors of the config.
+            new_persisted_wallet(network, &mut persister, &wallet_opts)?;
+        }
+
+        if let Ok(labels) = take(LABELS_FILE) {
+            fs::create_dir_all(&wallet_dir)
+                .map_err(|e| backup_error(format!("Failed to create {wallet_dir:?}: {e}")))?;
+            let labels_path = wallet_dir.join(LABELS_FILE);
+            fs::write(&labels_path, labels)
+                .map_err(|e| backup_error(format!("Failed to write {labels_path:?}: {e}")))?;
+        }
+
+        let network = wallet_config.network.clone();
+        config.wallets.insert(self.wallet.clone(), wallet_config);
+        config.save(&ctx.datadir)?;
+
+        let mut contents = vec![MANIFEST_FILE.to_string(), CONFIG_FILE.to_string()];
+        #[cfg(any(feature = "sqlite", feature = "redb"))]
+        contents.push(CHANGESET_FILE.to_string());
+        if wallet_dir.join(LABELS_FILE).exists() {
+            contents.push(LABELS_FILE.to_string());
+        }
+        contents.sort();
+        Ok(BackupResult {
+            wallet: self.wallet.clone(),
+            network,
+            file: self.file.display().to_string(),
+            encrypted,
+            contents,
+        })
+    }
+}
+
+/// A zstd compressed tar archive of `files`.
+fn pack(files: &BTreeMap<&str, Vec<u8>>, mtime: u64) -> Result<Vec<u8>, Error> {
+    let error = |e: std::io::Error| backup_error(format!("Cannot pack the backup bundle: {e}"));
+    let encoder = zstd::Encoder::new(Vec::new(), 0).map_err(error)?;
+    let mut builder = tar::Builder::new(encoder);
+    for (name, data) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o600);
+        header.set_mtime(mtime);
+        builder
+            .append_data(&mut header, name, data.as_slice())
+            .map_err(error)?;
+    }
+    builder.into_inner().and_then(|e| e.finish()).map_err(error)
+}
+
+/// The files of a bundle written by [`pack`].
+fn unpack(bundle: &[u8]) -> Result<HashMap<String, Vec<u8>>, Error> {
+    let error = |e: std::io::Error| backup_error(format!("Cannot unpack the backup bundle: {e}"));
+    let decoder = zstd::Decoder::new(bundle).map_err(error)?;
+    let mut archive = tar::Archive::new(decoder);
+    let mut files = HashMap::new();
+    for entry in archive.entries().map_err(error)? {
+        let mut entry = entry.map_err(error)?;
+        let name = entry.path().map_err(error)?.display().to_string();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).map_err(error)?;
+        files.insert(name, data);
+    }
+    Ok(files)
+}
+
+/// A backup bundle that cannot be written, read or restored.
+fn backup_error(message: String) -> Error {
+    Error::coded(ErrorCode::Backup, message)
+}
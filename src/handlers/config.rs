@@ -9,12 +9,21 @@ use std::collections::HashMap;
 use crate::client::ClientType;
 use crate::commands::WalletOpts;
 use crate::config::{WalletConfig, WalletConfigInner};
-use crate::error::BDKCliError as Error;
+use crate::error::{BDKCliError as Error, ErrorCode};
 use crate::handlers::Init;
 use crate::handlers::{AppCommand, AppContext};
-#[cfg(any(feature = "sqlite", feature = "redb"))]
+use crate::labels::LABELS_FILE;
+#[cfg(feature = "sqlcipher")]
 use crate::persister::DatabaseType;
-use crate::utils::types::{StatusResult, WalletsListResult};
+#[cfg(any(feature = "sqlite", feature = "redb"))]
+use crate::persister::has_wallet;
+use crate::utils::amount::Unit;
+use crate::utils::descriptors::check_descriptor_network;
+use crate::utils::keystore::{self, WalletDescriptors, keystore_path};
+use crate::utils::read_key_password;
+use crate::utils::signet::{challenge_hex, is_custom_signet, signet_magic};
+use crate::utils::snapshot::BACKUPS_DIR;
+use crate::utils::types::{PathsResult, StatusResult, WalletPaths, WalletsListResult};
 use bdk_wallet::bitcoin::Network;
 use clap::Args;
 
@@ -24,6 +33,25 @@ pub struct SaveConfigCommand {
     #[arg(short = 'f', long = "force", default_value_t = false)]
     pub(crate) force: bool,
 
+    /// Keeps the private keys of the descriptors in a password encrypted keystore, and only the
+    /// public descriptors in the config file.
+    #[arg(long = "encrypt", default_value_t = false)]
+    pub(crate) encrypt: bool,
+
+    /// Saves offline mode in the config file, set from the global `--offline` flag.
+    #[arg(skip)]
+    pub(crate) offline: bool,
+
+    /// Saves the block explorer template of the network in the config file, set from the global
+    /// `--explorer-url` option.
+    #[arg(skip)]
+    pub(crate) explorer_url: Option<String>,
+
+    /// Saves the unit of the amounts in the config file, set from the global `--unit` option
+    /// when it is given.
+    #[arg(skip)]
+    pub(crate) unit: Option<Unit>,
+
     #[command(flatten)]
     pub(crate) wallet_opts: WalletOpts,
 }
@@ -32,47 +60,155 @@ impl AppCommand<AppContext<Init>> for SaveConfigCommand {
     type Output = StatusResult;
 
     fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
-        if ctx.network == Network::Bitcoin {
-            eprintln!("WARNING: Configuring for Bitcoin MAINNET. Experimental software!");
+        #[allow(unused_mut)]
+        let mut network = ctx.network;
+        // The chain set in the `bitcoin.conf` of the node wins over the `--network` default.
+        #[cfg(feature = "rpc")]
+        if let Some(node) = &self.wallet_opts.node
+            && let Some(node_network) = crate::utils::bitcoind::node_network(node)?
+            && node_network != network
+        {
+            log::info!(
+                "Bitcoin Core runs on {node_network}, configuring the wallet for {node_network}."
+            );
+            network = node_network;
+        }
+
+        if network == Network::Bitcoin {
+            log::warn!("Configuring for Bitcoin MAINNET. Experimental software!");
+        }
+
+        #[allow(unused_mut)]
+        let mut signet_challenge = self.wallet_opts.signet_challenge.clone();
+        // A signet node runs the custom signet of its `bitcoin.conf`.
+        #[cfg(feature = "rpc")]
+        if signet_challenge.is_none()
+            && network == Network::Signet
+            && let Some(node) = &self.wallet_opts.node
+        {
+            signet_challenge = crate::utils::bitcoind::node_signet_challenge(node)?;
+        }
+        if signet_challenge.is_some() && network != Network::Signet {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                format!("--signet-challenge only applies to signet wallets, not to {network}"),
+            ));
+        }
+        // The default signet is saved without its challenge.
+        let signet_challenge =
+            signet_challenge.filter(|challenge| is_custom_signet(Some(challenge.as_script())));
+
+        // The network presets leave the url of the backends without a public server unset.
+        #[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
+        {
+            #[cfg(feature = "rpc")]
+            let has_node = self.wallet_opts.node.is_some();
+            #[cfg(not(feature = "rpc"))]
+            let has_node = false;
+            // The cbf backend connects to peers rather than to a server.
+            #[cfg(feature = "cbf")]
+            let has_node = has_node || self.wallet_opts.client_type == ClientType::Cbf;
+            if self.wallet_opts.url.is_empty() && !has_node {
+                return Err(Error::coded(
+                    ErrorCode::InvalidArgument,
+                    "The backend needs a server, pass it with --url".to_string(),
+                ));
+            }
         }
 
         let wallet_name = match &self.wallet_opts.wallet {
             Some(wallet) => wallet,
-            None => return Err(Error::Generic("wallet is required".to_owned())),
+            None => {
+                return Err(Error::coded(
+                    ErrorCode::InvalidArgument,
+                    "wallet is required".to_owned(),
+                ));
+            }
         };
 
         let ext_descriptor = self.wallet_opts.ext_descriptor.clone();
         let int_descriptor = self.wallet_opts.int_descriptor.clone();
 
-        if ext_descriptor.contains("xprv") || ext_descriptor.contains("tprv") {
-            eprintln!(
-                "WARNING: Your external descriptor contains PRIVATE KEYS.
-             Private keys will be saved in PLAINTEXT in the config file.
-             This is a security risk. Consider using public descriptors instead.\n"
+        if !self.encrypt && (ext_descriptor.contains("xprv") || ext_descriptor.contains("tprv")) {
+            log::warn!(
+                "Your external descriptor contains PRIVATE KEYS, saved in PLAINTEXT in the config file. This is a security risk. Consider using --encrypt or public descriptors instead."
             );
         }
 
-        if let Some(ref internal_desc) = int_descriptor
+        if !self.encrypt
+            && let Some(ref internal_desc) = int_descriptor
             && (internal_desc.contains("xprv") || internal_desc.contains("tprv"))
         {
-            eprintln!(
-                "WARNING: Your internal descriptor contains PRIVATE KEYS.
-                 Private keys will be saved in PLAINTEXT in the config file.
-                 This is a security risk. Consider using public descriptors instead.\n"
+            log::warn!(
+                "Your internal descriptor contains PRIVATE KEYS, saved in PLAINTEXT in the config file. This is a security risk. Consider using --encrypt or public descriptors instead."
             );
         }
 
         let mut config = WalletConfig::load(&ctx.datadir)?.unwrap_or(WalletConfig {
+            offline: false,
+            explorer_urls: HashMap::new(),
+            unit: None,
             wallets: HashMap::new(),
         });
 
         if config.wallets.contains_key(wallet_name.as_str()) && !self.force {
-            return Err(Error::Generic(format!(
-                "Wallet '{}' already exists. Use --force to overwrite.",
-                wallet_name
-            )));
+            return Err(Error::coded(
+                ErrorCode::WalletExists,
+                format!(
+                    "Wallet '{}' already exists. Use --force to overwrite.",
+                    wallet_name
+                ),
+            ));
         };
 
+        // The database of the wallet holds the chain of its network, such as testnet3 for a
+        // wallet moving to testnet4, in whichever backend it was configured with.
+        #[cfg(any(feature = "sqlite", feature = "redb"))]
+        if let Some(existing) = config.wallets.get(wallet_name.as_str())
+            && existing.network != network.to_string()
+            && has_wallet(&WalletOpts::try_from(existing)?, &ctx.datadir, wallet_name)?
+        {
+            return Err(Error::coded(
+                ErrorCode::WalletExists,
+                format!(
+                    "Wallet '{wallet_name}' has a {} database. Configure the {network} wallet \
+                     under another name, or remove that database first.",
+                    existing.network
+                ),
+            ));
+        }
+
+        #[cfg(feature = "sqlcipher")]
+        if self.wallet_opts.encrypt_db && self.wallet_opts.database_type != DatabaseType::Sqlite {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                "--encrypt-db only encrypts sqlite databases".to_string(),
+            ));
+        }
+
+        for descriptor in [Some(&ext_descriptor), int_descriptor.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            check_descriptor_network(descriptor, network)?;
+        }
+
+        let mut descriptors = WalletDescriptors {
+            ext_descriptor,
+            int_descriptor,
+        };
+        if self.encrypt {
+            match keystore::public_descriptors(&descriptors)? {
+                Some(public) => {
+                    let password = read_key_password(true)?;
+                    let path = keystore::save(&ctx.datadir, wallet_name, &descriptors, &password)?;
+                    log::info!("Private keys of wallet '{wallet_name}' encrypted in {path:?}");
+                    descriptors = public;
+                }
+                None => log::warn!("The descriptors hold no private key, nothing to encrypt."),
+            }
+        }
+
         #[cfg(any(
             feature = "electrum",
             feature = "esplora",
@@ -92,16 +228,15 @@ impl AppCommand<AppContext<Init>> for SaveConfigCommand {
 
         let wallet_config = WalletConfigInner {
             wallet: wallet_name.clone(),
-            network: ctx.network.to_string(),
-            ext_descriptor: self.wallet_opts.ext_descriptor.clone(),
-            int_descriptor: self.wallet_opts.int_descriptor.clone(),
+            network: network.to_string(),
+            ext_descriptor: descriptors.ext_descriptor,
+            int_descriptor: descriptors.int_descriptor,
+            stop_gap: Some(self.wallet_opts.stop_gap),
+            signet_challenge: signet_challenge.as_deref().map(challenge_hex),
             #[cfg(any(feature = "sqlite", feature = "redb"))]
-            database_type: match self.wallet_opts.database_type {
-                #[cfg(feature = "sqlite")]
-                DatabaseType::Sqlite => "sqlite".to_string(),
-                #[cfg(feature = "redb")]
-                DatabaseType::Redb => "redb".to_string(),
-            },
+            database_type: self.wallet_opts.database_type.to_string(),
+            #[cfg(feature = "sqlite")]
+            encrypt_db: Some(self.wallet_opts.encrypt_db),
 
             #[cfg(any(
                 feature = "electrum",
@@ -112,7 +247,7 @@ impl AppCommand<AppContext<Init>> for SaveConfigCommand {
             client_type: Some(client_type),
 
             #[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
-            server_url: Some(self.wallet_opts.url.clone()),
+            server_url: Some(self.wallet_opts.url.join(",")),
 
             #[cfg(feature = "rpc")]
             rpc_user: Some(self.wallet_opts.basic_auth.0.clone()),
@@ -122,8 +257,30 @@ impl AppCommand<AppContext<Init>> for SaveConfigCommand {
             batch_size: Some(self.wallet_opts.batch_size),
             #[cfg(feature = "esplora")]
             parallel_requests: Some(self.wallet_opts.parallel_requests),
+            #[cfg(feature = "esplora")]
+            esplora_retries: Some(self.wallet_opts.esplora_retries),
+            #[cfg(feature = "esplora")]
+            esplora_backoff: Some(self.wallet_opts.esplora_backoff),
+            #[cfg(feature = "esplora")]
+            esplora_headers: (!self.wallet_opts.esplora_headers.is_empty()).then(|| {
+                self.wallet_opts
+                    .esplora_headers
+                    .iter()
+                    .map(|(name, value)| format!("{name}: {value}"))
+                    .collect()
+            }),
+            #[cfg(any(feature = "electrum", feature = "esplora"))]
+            parallel: self.wallet_opts.parallel,
             #[cfg(feature = "rpc")]
             cookie: self.wallet_opts.cookie.clone(),
+            #[cfg(feature = "rpc")]
+            node: self
+                .wallet_opts
+                .node
+                .as_ref()
+                .map(|node| node.display().to_string()),
+            #[cfg(feature = "rpc")]
+            rpc_wallet: self.wallet_opts.rpc_wallet.clone(),
 
             #[cfg(any(feature = "electrum", feature = "esplora"))]
             proxy: self.wallet_opts.proxy_opts.proxy.clone(),
@@ -138,22 +295,55 @@ impl AppCommand<AppContext<Init>> for SaveConfigCommand {
             proxy_retries: Some(self.wallet_opts.proxy_opts.retries),
             #[cfg(any(feature = "electrum", feature = "esplora"))]
             proxy_timeout: self.wallet_opts.proxy_opts.timeout,
+            #[cfg(feature = "electrum")]
+            require_tls: Some(self.wallet_opts.electrum_tls_opts.require_tls),
+            #[cfg(feature = "electrum")]
+            tls_cert_fingerprint: self.wallet_opts.electrum_tls_opts.cert_fingerprint.clone(),
+            #[cfg(feature = "electrum")]
+            tls_ca: self
+                .wallet_opts
+                .electrum_tls_opts
+                .ca_file
+                .as_ref()
+                .map(|path| path.display().to_string()),
             #[cfg(feature = "cbf")]
             conn_count: Some(self.wallet_opts.compactfilter_opts.conn_count),
+            #[cfg(feature = "cbf")]
+            cbf_peers: Some(self.wallet_opts.compactfilter_opts.peers.clone()),
+            #[cfg(feature = "cbf")]
+            cbf_whitelist_only: Some(self.wallet_opts.compactfilter_opts.whitelist_only),
+            #[cfg(feature = "cbf")]
+            cbf_response_timeout: self.wallet_opts.compactfilter_opts.response_timeout,
         };
 
         config.wallets.insert(wallet_name.clone(), wallet_config);
+        // Offline mode is only ever turned on here, so a signing machine cannot lose it by
+        // reconfiguring a wallet.
+        config.offline |= self.offline;
+        if let Some(unit) = self.unit {
+            config.unit = Some(unit);
+        }
+        if let Some(explorer_url) = &self.explorer_url {
+            config
+                .explorer_urls
+                .insert(network.to_string(), explorer_url.clone());
+        }
         config
             .save(&ctx.datadir)
             .map_err(|error| Error::Generic(error.to_string()))?;
 
-        Ok(StatusResult {
-            message: format!(
-                "Wallet '{}' initialized successfully in {:?}",
-                wallet_name,
-                ctx.datadir.join("config.toml")
-            ),
-        })
+        let mut message = format!(
+            "Wallet '{}' initialized successfully in {:?}",
+            wallet_name,
+            ctx.datadir.join("config.toml")
+        );
+        if let Some(challenge) = &signet_challenge {
+            message.push_str(&format!(
+                ", on the custom signet of magic {}",
+                signet_magic(challenge)
+            ));
+        }
+        Ok(StatusResult { message })
     }
 }
 
@@ -166,9 +356,65 @@ impl AppCommand<AppContext<Init>> for ListWalletsCommand {
     fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
         let config = match WalletConfig::load(&ctx.datadir)? {
             Some(cfg) => cfg,
-            None => return Err(Error::Generic("No wallets configured yet.".into())),
+            None => {
+                return Err(Error::coded(
+                    ErrorCode::NoWalletConfig,
+                    "No wallets configured yet.".into(),
+                ));
+            }
         };
 
         Ok(WalletsListResult(config.wallets))
     }
 }
+
+#[derive(Args, Debug, Clone, PartialEq)]
+pub struct PathsCommand {
+    /// Also prints the files of this wallet.
+    #[arg(env = "WALLET_NAME", short = 'w', long = "wallet")]
+    pub(crate) wallet: Option<String>,
+
+    /// Where the data directory comes from, set from the global options.
+    #[arg(skip)]
+    pub(crate) source: String,
+}
+
+impl AppCommand<AppContext<Init>> for PathsCommand {
+    type Output = PathsResult;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let datadir = &ctx.datadir;
+        let wallet = match &self.wallet {
+            Some(name) => {
+                let config = WalletConfig::load(datadir)?.ok_or(Error::NoConfig)?;
+                let wallet_config = config
+                    .wallets
+                    .get(name)
+                    .ok_or_else(|| Error::WalletNotFound(name.clone()))?;
+                let wallet_dir = datadir.join(name);
+                // sqlite wallets have their own database, redb ones share the one of the datadir.
+                let database = match wallet_config.database_type.to_lowercase().as_str() {
+                    "sqlite" => Some(wallet_dir.join("wallet.sqlite")),
+                    "redb" => Some(datadir.join("wallet.redb")),
+                    _ => None,
+                };
+                Some(WalletPaths {
+                    name: name.clone(),
+                    directory: wallet_dir.display().to_string(),
+                    database: database.map(|path| path.display().to_string()),
+                    labels: wallet_dir.join(LABELS_FILE).display().to_string(),
+                    keystore: keystore_path(datadir, name).display().to_string(),
+                    backups: wallet_dir.join(BACKUPS_DIR).display().to_string(),
+                })
+            }
+            None => None,
+        };
+
+        Ok(PathsResult {
+            datadir: datadir.display().to_string(),
+            source: self.source.clone(),
+            config: datadir.join("config.toml").display().to_string(),
+            wallet,
+        })
+    }
+}
@@ -0,0 +1,270 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Wallet contacts
+//!
+//! Contacts are the named recipients of a wallet, paid with `create_tx --to @alice:10000`. They
+//! are stored as the [BIP-329] labels of their addresses, so they are exported and imported with
+//! the other labels: a contact is the label `@<name>` of an address that is not one of the wallet.
+//! The other address labels are never taken for contacts.
+//!
+//! [BIP-329]: https://github.com/bitcoin/bips/blob/master/bip-0329.mediawiki
+
+use std::str::FromStr;
+
+use crate::commands::WalletContactsSubCommand;
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::handlers::{AppCommand, AppContext, OfflineOperations};
+use crate::labels::{Label, LabelManager, LabelType};
+use crate::utils::output::{FormatOutput, ListResult};
+use crate::utils::types::ContactDetails;
+use crate::utils::{Payee, amount, confirm};
+use bdk_wallet::Wallet;
+use bdk_wallet::bitcoin::{Address, ScriptBuf};
+use clap::Parser;
+
+impl WalletContactsSubCommand {
+    pub fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<(), Error> {
+        match self {
+            Self::Add(add_command) => add_command.execute(ctx)?.write_out(std::io::stdout()),
+            Self::List(list_command) => list_command.execute(ctx)?.write_out(std::io::stdout()),
+            Self::Remove(remove_command) => {
+                remove_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+        }
+    }
+}
+
+/// Parse a contact name, letters, digits, `-`, `_` and `.`, with or without its leading `@`.
+pub(crate) fn parse_contact_name(s: &str) -> Result<String, String> {
+    let name = s.strip_prefix('@').unwrap_or(s);
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(format!(
+            "Invalid contact name '{s}': use letters, digits, '-', '_' and '.'"
+        ));
+    }
+    Ok(name.to_string())
+}
+
+/// The label marking the address of the contact `name`.
+fn contact_label(name: &str) -> String {
+    format!("@{name}")
+}
+
+/// The name of the contact marked by `label`, an address label `@<name>`.
+fn contact_name(label: &Label) -> Option<&str> {
+    let name = label.label.strip_prefix('@')?;
+    (label.label_type == LabelType::Addr && parse_contact_name(name).as_deref() == Ok(name))
+        .then_some(name)
+}
+
+/// The contacts of `wallet` among its `labels`: the contact labels of the addresses of its
+/// network that are not its own.
+fn contacts(labels: &LabelManager, wallet: &Wallet) -> Vec<(String, Address)> {
+    labels
+        .labels()
+        .iter()
+        .filter_map(|label| {
+            let name = contact_name(label)?;
+            let address = Address::from_str(&label.reference)
+                .ok()?
+                .require_network(wallet.network())
+                .ok()?;
+            (!wallet.is_mine(address.script_pubkey())).then(|| (name.to_string(), address))
+        })
+        .collect()
+}
+
+fn contact_list(labels: &LabelManager, wallet: &Wallet) -> ListResult<ContactDetails> {
+    let mut contacts: Vec<ContactDetails> = contacts(labels, wallet)
+        .into_iter()
+        .map(|(name, address)| ContactDetails {
+            name,
+            address: address.to_string(),
+        })
+        .collect();
+    contacts.sort_by(|a, b| a.name.cmp(&b.name));
+    ListResult::new(contacts)
+}
+
+/// The scripts of the `recipients` of a transaction, their contacts resolved to their addresses.
+/// The resolved addresses are shown and confirmed before the transaction is built.
+pub(crate) fn resolve_recipients(
+    ctx: &AppContext<OfflineOperations<'_>>,
+    recipients: &[(Payee, u64)],
+) -> Result<Vec<(ScriptBuf, u64)>, Error> {
+    if recipients
+        .iter()
+        .all(|(payee, _)| matches!(payee, Payee::Script(_)))
+    {
+        return Ok(recipients
+            .iter()
+            .map(|(payee, amount)| match payee {
+                Payee::Script(script) => (script.clone(), *amount),
+                Payee::Contact(_) => unreachable!("only scripts"),
+            })
+            .collect());
+    }
+
+    let labels = LabelManager::load(&ctx.datadir.join(&ctx.state.wallet_name))?;
+    let contacts = contacts(&labels, &ctx.state.wallet);
+    let mut resolved = Vec::new();
+    let mut summary = vec!["Paying the contacts:".to_string()];
+    for (payee, amount) in recipients {
+        let script = match payee {
+            Payee::Script(script) => script.clone(),
+            Payee::Contact(name) => {
+                let addresses: Vec<&Address> = contacts
+                    .iter()
+                    .filter(|(contact, _)| contact == name)
+                    .map(|(_, address)| address)
+                    .collect();
+                let address = match addresses.as_slice() {
+                    [address] => *address,
+                    [] => {
+                        return Err(Error::coded(
+                            ErrorCode::InvalidArgument,
+                            format!("Unknown contact '@{name}', add it with `wallet contacts add`"),
+                        ));
+                    }
+                    _ => {
+                        return Err(Error::coded(
+                            ErrorCode::InvalidArgument,
+                            format!(
+                                "Contact '@{name}' is ambiguous, it names {} addresses: set its \
+                             address again with `wallet contacts add`",
+                                addresses.len()
+                            ),
+                        ));
+                    }
+                };
+                summary.push(format!(
+                    "  @{name} at {address}: {}",
+                    amount::display(*amount)
+                ));
+                address.script_pubkey()
+            }
+        };
+        resolved.push((script, *amount));
+    }
+    confirm("the contact addresses", &summary.join("\n"))?;
+    Ok(resolved)
+}
+
+#[derive(Parser, Debug, Clone, PartialEq)]
+pub struct AddContactCommand {
+    /// Name of the contact, paid with `--to @<name>:<amount>`.
+    #[arg(env = "CONTACT", long = "name", value_parser = parse_contact_name)]
+    pub name: String,
+
+    /// Address of the contact. Replaces the previous address of the contact.
+    #[arg(env = "ADDRESS", long = "address")]
+    pub address: String,
+}
+
+impl AppCommand<AppContext<OfflineOperations<'_>>> for AddContactCommand {
+    type Output = ListResult<ContactDetails>;
+
+    fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        let wallet = &ctx.state.wallet;
+        let address = Address::from_str(&self.address)?.require_network(wallet.network())?;
+        if wallet.is_mine(address.script_pubkey()) {
+            return Err(Error::coded(
+                ErrorCode::InvalidAddress,
+                format!("{address} is an address of this wallet, not of a contact"),
+            ));
+        }
+
+        let mut labels = LabelManager::load(&ctx.datadir.join(&ctx.state.wallet_name))?;
+        for (_, previous) in contacts(&labels, wallet)
+            .into_iter()
+            .filter(|(name, _)| name == &self.name)
+        {
+            labels.remove(&previous.to_string());
+        }
+        labels.set(Label::new(&address.to_string(), contact_label(&self.name))?);
+        labels.save()?;
+        Ok(contact_list(&labels, wallet))
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq)]
+pub struct ListContactsCommand;
+
+impl AppCommand<AppContext<OfflineOperations<'_>>> for ListContactsCommand {
+    type Output = ListResult<ContactDetails>;
+
+    fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        let labels = LabelManager::load(&ctx.datadir.join(&ctx.state.wallet_name))?;
+        Ok(contact_list(&labels, &ctx.state.wallet))
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq)]
+pub struct RemoveContactCommand {
+    /// Name of the contact to remove, along with the label of its address.
+    #[arg(env = "CONTACT", long = "name", value_parser = parse_contact_name)]
+    pub name: String,
+}
+
+impl AppCommand<AppContext<OfflineOperations<'_>>> for RemoveContactCommand {
+    type Output = ListResult<ContactDetails>;
+
+    fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        let wallet = &ctx.state.wallet;
+        let mut labels = LabelManager::load(&ctx.datadir.join(&ctx.state.wallet_name))?;
+        let addresses: Vec<Address> = contacts(&labels, wallet)
+            .into_iter()
+            .filter(|(name, _)| name == &self.name)
+            .map(|(_, address)| address)
+            .collect();
+        if addresses.is_empty() {
+            log::warn!("there is no contact '@{}'; nothing removed", self.name);
+        }
+        for address in addresses {
+            labels.remove(&address.to_string());
+        }
+        labels.save()?;
+        Ok(contact_list(&labels, wallet))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_contact_name() {
+        assert_eq!(parse_contact_name("alice"), Ok("alice".to_string()));
+        assert_eq!(
+            parse_contact_name("@bob.smith-2"),
+            Ok("bob.smith-2".to_string())
+        );
+        assert!(parse_contact_name("@").is_err());
+        assert!(parse_contact_name("alice:10000").is_err());
+        assert!(parse_contact_name("my friend").is_err());
+    }
+
+    #[test]
+    fn test_contact_name() {
+        let address = "tb1p4tp4l6glyr2gs94neqcpr5gha7344nfyznfkc8szkreflscsdkgqsdent4";
+        let label = |text: &str| Label::new(address, text.to_string()).unwrap();
+        assert_eq!(contact_name(&label(&contact_label("alice"))), Some("alice"));
+        assert_eq!(contact_name(&label("alice")), None);
+        assert_eq!(contact_name(&label("@my friend")), None);
+        let txid = "0000000000000000000000000000000000000000000000000000000000000001";
+        assert_eq!(
+            contact_name(&Label::new(txid, "@alice".to_string()).unwrap()),
+            None
+        );
+    }
+}
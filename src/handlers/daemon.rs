@@ -0,0 +1,314 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Daemon
+//!
+//! `daemon` keeps wallets loaded and syncs them on an interval, and on each block announced by
+//! the ZMQ notifications of their node with the `rpc` backend. Without `--wallet` it hosts every
+//! wallet of the data directory. Each wallet has its own schedule, the first syncs being spread
+//! over the first interval so that the wallets are loaded one after the other rather than all at
+//! startup. With `--listen` or `--grpc` it also serves the wallets as `serve` does, routed by
+//! wallet name, each wallet loaded on its first request or sync and staying loaded after it.
+//! With `--nwc` it answers the Nostr Wallet Connect requests of the wallets, as described in
+//! [`crate::handlers::nwc`].
+//! On Ctrl-C or SIGTERM the syncs and the servers stop and the wallets are persisted. A loaded
+//! wallet stays locked, so the commands run on it next to the daemon fail rather than load a copy
+//! the daemon would not see.
+
+use crate::commands::OnlineWalletSubCommand;
+use crate::config::WalletConfig;
+use crate::error::BDKCliError as Error;
+#[cfg(feature = "nwc")]
+use crate::handlers::nwc;
+use crate::handlers::online::SyncCommand;
+use crate::handlers::server::{Runner, online_command, serve};
+use crate::utils::auth::{Scope, ServerSecurity};
+use clap::Parser;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+#[cfg(feature = "rpc")]
+use {
+    crate::handlers::online::next_notification,
+    crate::utils::bitcoind::node_zmq,
+    crate::utils::load_wallet_config,
+    crate::utils::zmq::{self, Notification},
+    tokio::sync::mpsc::UnboundedReceiver,
+};
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct DaemonCommand {
+    /// Wallet kept synced, every INTERVAL when given rather than every `--sync-interval`. Can be
+    /// repeated. Defaults to every wallet of the data directory.
+    #[arg(
+        env = "WALLET_NAME",
+        short = 'w',
+        long = "wallet",
+        value_name = "NAME[=INTERVAL]",
+        value_parser = parse_synced_wallet
+    )]
+    pub wallets: Vec<SyncedWallet>,
+    /// Time between two syncs, such as `90s`, `5m` or `1h`, in seconds without a unit.
+    #[arg(
+        env = "SYNC_INTERVAL",
+        long = "sync-interval",
+        default_value = "60s",
+        value_parser = parse_interval
+    )]
+    pub sync_interval: Duration,
+    /// Also serves the wallets over the REST API, on this address and port.
+    #[arg(env = "LISTEN", long = "listen", value_name = "ADDRESS")]
+    pub listen: Option<SocketAddr>,
+    /// Also serves the wallets over gRPC, on this address and port.
+    #[cfg(feature = "grpc")]
+    #[arg(env = "GRPC_LISTEN", long = "grpc", value_name = "ADDRESS")]
+    pub grpc: Option<SocketAddr>,
+    /// Also answers the Nostr Wallet Connect requests of the connections of the wallets.
+    #[cfg(feature = "nwc")]
+    #[arg(long = "nwc")]
+    pub nwc: bool,
+    #[command(flatten)]
+    pub security: ServerSecurity,
+}
+
+/// A wallet synced by the daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncedWallet {
+    pub name: String,
+    /// Time between two syncs of this wallet, `--sync-interval` when `None`.
+    pub interval: Option<Duration>,
+}
+
+/// Parses a wallet name, optionally followed by `=` and its sync interval.
+fn parse_synced_wallet(s: &str) -> Result<SyncedWallet, String> {
+    let (name, interval) = match s.rsplit_once('=') {
+        Some((name, interval)) => (name, Some(parse_interval(interval)?)),
+        None => (s, None),
+    };
+    if name.is_empty() {
+        return Err("Expected a wallet name".to_string());
+    }
+    Ok(SyncedWallet {
+        name: name.to_string(),
+        interval,
+    })
+}
+
+/// Parses an interval such as `90s`, `5m` or `1h`, or a number of seconds.
+pub(crate) fn parse_interval(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        unit => return Err(format!("Unknown unit '{unit}', expected s, m or h")),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid interval '{s}', expected e.g. 60s, 5m or 1h"))?;
+    if number == 0 {
+        return Err("The interval must be positive".to_string());
+    }
+    Ok(Duration::from_secs(number * seconds))
+}
+
+impl DaemonCommand {
+    /// Syncs the wallets and serves the wallets of `datadir` until Ctrl-C or SIGTERM, then
+    /// persists them.
+    pub async fn run(&self, datadir: PathBuf) -> Result<(), Error> {
+        let wallets = self.synced_wallets(&datadir)?;
+        let tls = if self.serves() {
+            self.security.tls(&datadir)?
+        } else {
+            None
+        };
+        let runner = Runner::new(datadir.clone(), false, true, self.security.auth()?);
+        let names: Vec<String> = wallets.iter().map(|wallet| wallet.name.clone()).collect();
+        log::info!("Syncing {}. Press Ctrl-C to stop.", names.join(", "));
+
+        let mut syncs = JoinSet::new();
+        let count = wallets.len() as u32;
+        for (i, wallet) in wallets.into_iter().enumerate() {
+            let interval = wallet.interval.unwrap_or(self.sync_interval);
+            let delay = interval * i as u32 / count;
+            syncs.spawn(schedule(
+                runner.clone(),
+                datadir.clone(),
+                wallet.name,
+                interval,
+                delay,
+            ));
+        }
+        #[cfg(feature = "nwc")]
+        if self.nwc {
+            for wallet in names {
+                if nwc::has_connections(&datadir, &wallet) {
+                    syncs.spawn(nwc::provide(runner.clone(), datadir.clone(), wallet));
+                }
+            }
+        }
+
+        let stopped = async {
+            if self.serves() {
+                serve(
+                    runner.clone(),
+                    self.listen,
+                    #[cfg(feature = "grpc")]
+                    self.grpc,
+                    tls,
+                )
+                .await
+            } else {
+                // Without a server, the signal alone stops the daemon.
+                crate::utils::shutdown_signal().await;
+                Ok(())
+            }
+        };
+        let result = tokio::select! {
+            result = stopped => result,
+            _ = async { while syncs.join_next().await.is_some() {} } => Ok(()),
+        };
+        syncs.shutdown().await;
+        log::info!("Persisting the wallets.");
+        runner.close().await?;
+        result
+    }
+
+    /// The wallets of `--wallet`, or else every wallet of the data directory.
+    fn synced_wallets(&self, datadir: &Path) -> Result<Vec<SyncedWallet>, Error> {
+        if !self.wallets.is_empty() {
+            return Ok(self.wallets.clone());
+        }
+        let config = WalletConfig::load(datadir)?.ok_or(Error::NoConfig)?;
+        let mut names: Vec<String> = config.wallets.into_keys().collect();
+        if names.is_empty() {
+            return Err(Error::NoConfig);
+        }
+        names.sort();
+        Ok(names
+            .into_iter()
+            .map(|name| SyncedWallet {
+                name,
+                interval: None,
+            })
+            .collect())
+    }
+
+    fn serves(&self) -> bool {
+        #[cfg(feature = "grpc")]
+        if self.grpc.is_some() {
+            return true;
+        }
+        self.listen.is_some()
+    }
+}
+
+/// Syncs `wallet` every `interval` after a first sync in `delay`, and on the blocks of the ZMQ
+/// notifications of its node.
+async fn schedule(
+    runner: Arc<Runner>,
+    datadir: PathBuf,
+    wallet: String,
+    interval: Duration,
+    delay: Duration,
+) {
+    #[cfg(feature = "rpc")]
+    let mut blocks = match block_notifications(&datadir, &wallet).await {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            log::warn!("{wallet}: {e}, syncing on the interval only.");
+            None
+        }
+    };
+    #[cfg(not(feature = "rpc"))]
+    let _ = datadir;
+
+    let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + delay, interval);
+    loop {
+        #[cfg(feature = "rpc")]
+        tokio::select! {
+            _ = ticks.tick() => {}
+            notification = next_notification(&mut blocks) => match notification {
+                Some(Ok(Notification::Block)) => ticks.reset(),
+                Some(Ok(Notification::Tx(_))) => continue,
+                Some(Err(e)) => {
+                    log::warn!("{wallet}: {e}, syncing on the interval only.");
+                    blocks = None;
+                    continue;
+                }
+                None => {
+                    blocks = None;
+                    continue;
+                }
+            },
+        }
+        #[cfg(not(feature = "rpc"))]
+        ticks.tick().await;
+        let result = runner
+            .clone()
+            .run(
+                wallet.clone(),
+                online_command(OnlineWalletSubCommand::Sync(SyncCommand::default())),
+                Scope::Read,
+                #[cfg(feature = "grpc")]
+                None,
+            )
+            .await;
+        if let Err(e) = result {
+            log::warn!("{wallet}: sync failed: {}", e.error);
+        }
+    }
+}
+
+/// The block notifications of the node of `wallet`, `None` without a node publishing them.
+#[cfg(feature = "rpc")]
+async fn block_notifications(
+    datadir: &Path,
+    wallet: &str,
+) -> Result<Option<UnboundedReceiver<Result<Notification, Error>>>, Error> {
+    let (wallet_opts, network) = load_wallet_config(datadir, wallet)?;
+    let Some(node) = &wallet_opts.node else {
+        return Ok(None);
+    };
+    match node_zmq(node, network)? {
+        (Some(block), _) => Ok(Some(zmq::subscribe(Some(&block), None).await?)),
+        (None, _) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("60s"), Ok(Duration::from_secs(60)));
+        assert_eq!(parse_interval("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_interval("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("10d").is_err());
+        assert!(parse_interval("m").is_err());
+    }
+
+    #[test]
+    fn test_parse_synced_wallet() {
+        let wallet = parse_synced_wallet("hot=30s").unwrap();
+        assert_eq!(wallet.name, "hot");
+        assert_eq!(wallet.interval, Some(Duration::from_secs(30)));
+        assert_eq!(parse_synced_wallet("cold").unwrap().interval, None);
+        assert!(parse_synced_wallet("=5m").is_err());
+        assert!(parse_synced_wallet("hot=soon").is_err());
+    }
+}
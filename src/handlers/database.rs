@@ -0,0 +1,120 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Wallet database maintenance
+//!
+//! `wallet db vacuum` compacts the sqlite database of a wallet, after optionally pruning the
+//! unconfirmed transactions the wallet no longer considers, such as replaced or evicted ones.
+
+use std::collections::HashSet;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::handlers::{AppCommand, AppContext, Init};
+use crate::persister::{DatabaseType, new_persisted_wallet, open_sqlite};
+use crate::utils::runtime::WalletRuntime;
+use crate::utils::snapshot::auto_snapshot;
+use crate::utils::types::VacuumResult;
+use bdk_wallet::WalletPersister;
+use bdk_wallet::chain::{ConfirmationBlockTime, tx_graph};
+use clap::Args;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct VacuumCommand {
+    /// Also deletes the unconfirmed transactions outside of the wallet history, replaced,
+    /// conflicting or evicted from the mempool, last seen more than this many days ago.
+    #[arg(long = "prune-older-than", value_name = "DAYS")]
+    pub(crate) prune_older_than: Option<u64>,
+
+    /// The wallet whose database is compacted, set from `--wallet`.
+    #[arg(skip)]
+    pub(crate) wallet: String,
+}
+
+impl AppCommand<AppContext<Init>> for VacuumCommand {
+    type Output = VacuumResult;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let runtime = WalletRuntime::load(&ctx.datadir, &self.wallet)?;
+        if runtime.wallet_opts.database_type != DatabaseType::Sqlite {
+            return Err(Error::coded(
+                ErrorCode::Database,
+                "Only sqlite wallet databases can be vacuumed".to_string(),
+            ));
+        }
+        let path = runtime.database_path.join("wallet.sqlite");
+        let size = || {
+            fs::metadata(&path)
+                .map(|metadata| metadata.len())
+                .unwrap_or(0)
+        };
+        let size_before = size();
+
+        let mut connection = open_sqlite(&path, runtime.wallet_opts.encrypt_db)?;
+        let mut pruned = Vec::new();
+        if let Some(days) = self.prune_older_than {
+            let changeset = WalletPersister::initialize(&mut connection)?;
+            let wallet =
+                new_persisted_wallet(runtime.network, &mut connection, &runtime.wallet_opts)?;
+            let canonical: HashSet<_> = wallet.transactions().map(|tx| tx.tx_node.txid).collect();
+            let anchored: HashSet<_> = changeset
+                .tx_graph
+                .anchors
+                .iter()
+                .map(|(_, txid)| *txid)
+                .collect();
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default();
+            let cutoff = now.saturating_sub(days.saturating_mul(SECONDS_PER_DAY));
+            let graph = &changeset.tx_graph;
+            for tx in &graph.txs {
+                let txid = tx.compute_txid();
+                if canonical.contains(&txid) || anchored.contains(&txid) {
+                    continue;
+                }
+                let last_seen = [&graph.first_seen, &graph.last_seen, &graph.last_evicted]
+                    .into_iter()
+                    .filter_map(|times| times.get(&txid).copied())
+                    .max()
+                    .unwrap_or(0);
+                if last_seen < cutoff {
+                    pruned.push(txid);
+                }
+            }
+        }
+
+        if !pruned.is_empty() {
+            auto_snapshot(&runtime.database_path, "prune")?;
+            let db_tx = connection.transaction()?;
+            let delete = format!(
+                "DELETE FROM {} WHERE txid = ?1",
+                tx_graph::ChangeSet::<ConfirmationBlockTime>::TXS_TABLE_NAME
+            );
+            for txid in &pruned {
+                db_tx.execute(&delete, [txid.to_string()])?;
+            }
+            db_tx.commit()?;
+        }
+        connection.execute_batch("VACUUM")?;
+        drop(connection);
+
+        let size_after = size();
+        Ok(VacuumResult {
+            database: path.display().to_string(),
+            size_before,
+            size_after,
+            reclaimed: size_before.saturating_sub(size_after),
+            pruned: pruned.iter().map(|txid| txid.to_string()).collect(),
+        })
+    }
+}
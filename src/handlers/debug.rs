@@ -0,0 +1,170 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Wallet database inspection
+//!
+//! `wallet debug changesets` reads the changesets persisted in the wallet database without
+//! loading the wallet, to diagnose a wallet whose balance or history differs from the chain. The
+//! Postgres store keeps each changeset, while the sqlite and redb stores merge them into one.
+
+use std::collections::BTreeMap;
+
+use crate::commands::WalletOpts;
+use crate::config::WalletConfig;
+use crate::error::BDKCliError as Error;
+use crate::handlers::{AppCommand, AppContext, Init};
+use crate::persister::read_changesets;
+use crate::utils::output::ListResult;
+use crate::utils::types::{
+    ChangesetAnchor, ChangesetBlock, ChangesetDump, ChangesetTx, ChangesetTxOut,
+};
+use bdk_wallet::ChangeSet;
+use bdk_wallet::bitcoin::Txid;
+use bdk_wallet::chain::{DescriptorExt, DescriptorId};
+use clap::Args;
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct ChangesetsCommand {
+    /// Only shows the blocks and the transactions confirmed from this height, along with the
+    /// unconfirmed transactions.
+    #[arg(long = "since", value_name = "HEIGHT")]
+    pub(crate) since: Option<u32>,
+
+    /// The wallet whose database is read, set from `--wallet`.
+    #[arg(skip)]
+    pub(crate) wallet: String,
+}
+
+impl AppCommand<AppContext<Init>> for ChangesetsCommand {
+    type Output = ListResult<ChangesetDump>;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let config = WalletConfig::load(&ctx.datadir)?.ok_or(Error::NoConfig)?;
+        let wallet_config = config
+            .wallets
+            .get(&self.wallet)
+            .ok_or_else(|| Error::WalletNotFound(self.wallet.clone()))?;
+        let wallet_opts = WalletOpts::try_from(wallet_config)?;
+        let wallet_dir = ctx.datadir.join(&self.wallet);
+        let changesets = read_changesets(&wallet_opts, &ctx.datadir, &wallet_dir, &self.wallet)?;
+
+        // The revealed indices are keyed by descriptor id, named after the keychain when known.
+        let mut keychains = BTreeMap::new();
+        for changeset in &changesets {
+            if let Some(descriptor) = &changeset.descriptor {
+                keychains.insert(descriptor.descriptor_id(), "external");
+            }
+            if let Some(descriptor) = &changeset.change_descriptor {
+                keychains.insert(descriptor.descriptor_id(), "internal");
+            }
+        }
+        let since = self.since.unwrap_or(0);
+        Ok(ListResult::new(
+            changesets
+                .iter()
+                .map(|changeset| dump(changeset, &keychains, since))
+                .collect(),
+        ))
+    }
+}
+
+/// The changes of `changeset` in readable form, keeping the blocks and the confirmations from
+/// height `since`.
+fn dump(
+    changeset: &ChangeSet,
+    keychains: &BTreeMap<DescriptorId, &str>,
+    since: u32,
+) -> ChangesetDump {
+    let last_revealed = changeset
+        .indexer
+        .last_revealed
+        .iter()
+        .map(|(id, index)| {
+            let name = keychains
+                .get(id)
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| id.to_string());
+            (name, *index)
+        })
+        .collect();
+
+    let blocks = changeset
+        .local_chain
+        .blocks
+        .range(since..)
+        .map(|(height, hash)| ChangesetBlock {
+            height: *height,
+            hash: hash.map(|hash| hash.to_string()),
+        })
+        .collect();
+
+    let mut transactions = BTreeMap::new();
+    for tx in &changeset.tx_graph.txs {
+        let tx_entry = entry(&mut transactions, tx.compute_txid());
+        tx_entry.stored = true;
+        tx_entry.inputs = Some(tx.input.len());
+        tx_entry.outputs = Some(tx.output.len());
+    }
+    for (anchor, txid) in &changeset.tx_graph.anchors {
+        entry(&mut transactions, *txid)
+            .anchors
+            .push(ChangesetAnchor {
+                height: anchor.block_id.height,
+                block_hash: anchor.block_id.hash.to_string(),
+                confirmation_time: anchor.confirmation_time,
+            });
+    }
+    for (txid, seen) in &changeset.tx_graph.first_seen {
+        entry(&mut transactions, *txid).first_seen = Some(*seen);
+    }
+    for (txid, seen) in &changeset.tx_graph.last_seen {
+        entry(&mut transactions, *txid).last_seen = Some(*seen);
+    }
+    for (txid, evicted) in &changeset.tx_graph.last_evicted {
+        entry(&mut transactions, *txid).last_evicted = Some(*evicted);
+    }
+    let transactions = transactions
+        .into_values()
+        .filter(|tx| tx.anchors.is_empty() || tx.anchors.iter().any(|a| a.height >= since))
+        .collect();
+
+    let floating_txouts = changeset
+        .tx_graph
+        .txouts
+        .iter()
+        .map(|(outpoint, txout)| ChangesetTxOut {
+            outpoint: outpoint.to_string(),
+            value: txout.value.to_sat(),
+            script_pubkey: txout.script_pubkey.to_hex_string(),
+        })
+        .collect();
+
+    ChangesetDump {
+        network: changeset.network.map(|network| network.to_string()),
+        external_descriptor: changeset.descriptor.as_ref().map(|d| d.to_string()),
+        internal_descriptor: changeset.change_descriptor.as_ref().map(|d| d.to_string()),
+        last_revealed,
+        blocks,
+        transactions,
+        floating_txouts,
+    }
+}
+
+/// The entry of `txid` in `transactions`, added when missing.
+fn entry(transactions: &mut BTreeMap<Txid, ChangesetTx>, txid: Txid) -> &mut ChangesetTx {
+    transactions.entry(txid).or_insert_with(|| ChangesetTx {
+        txid: txid.to_string(),
+        stored: false,
+        inputs: None,
+        outputs: None,
+        anchors: Vec::new(),
+        first_seen: None,
+        last_seen: None,
+        last_evicted: None,
+    })
+}
@@ -1,28 +1,30 @@
 use crate::handlers::Init;
 use crate::utils::types::DescriptorResult;
 use crate::{
-    error::BDKCliError as Error,
+    commands::PassphraseOpts,
+    error::{BDKCliError as Error, ErrorCode},
     handlers::{AppCommand, AppContext},
     utils::{
         descriptors::{
             generate_descriptor_from_mnemonic, generate_descriptor_with_mnemonic,
             generate_descriptors,
         },
-        is_mnemonic,
+        is_mnemonic, read_passphrase,
     },
 };
 use clap::Parser;
 #[cfg(feature = "compiler")]
 use {
+    crate::utils::policy::analyze,
     bdk_wallet::{
         bitcoin::{
             XOnlyPublicKey,
             key::{Parity, rand},
             secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey},
         },
-        miniscript::{Descriptor, Miniscript, descriptor::TapTree, policy::Concrete},
+        miniscript::{Descriptor, Miniscript, policy::Concrete},
     },
-    std::{str::FromStr, sync::Arc},
+    std::str::FromStr,
 };
 
 #[cfg(feature = "silent-payments")]
@@ -45,20 +47,38 @@ pub struct DescriptorCommand {
 
     /// Optional key: xprv, xpub, or mnemonic phrase
     key: Option<String>,
+
+    /// BIP39 passphrase of a given or generated mnemonic.
+    #[command(flatten)]
+    passphrase: PassphraseOpts,
 }
 impl AppCommand<AppContext<Init>> for DescriptorCommand {
     type Output = DescriptorResult;
 
     fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
         match &self.key {
+            Some(key) if is_mnemonic(key) => {
+                let passphrase = read_passphrase(&self.passphrase, false)?.unwrap_or_default();
+                generate_descriptor_from_mnemonic(
+                    key,
+                    ctx.network.into(),
+                    &self.desc_type,
+                    &passphrase,
+                )
+            }
             Some(key) => {
-                if is_mnemonic(key) {
-                    generate_descriptor_from_mnemonic(key, ctx.network.into(), &self.desc_type)
-                } else {
-                    generate_descriptors(&self.desc_type, key, ctx.network.into())
+                if self.passphrase.passphrase.is_some() || self.passphrase.ask_passphrase {
+                    return Err(Error::coded(
+                        ErrorCode::InvalidArgument,
+                        "A passphrase can only be used with a mnemonic".to_string(),
+                    ));
                 }
+                generate_descriptors(&self.desc_type, key, ctx.network.into())
+            }
+            None => {
+                let passphrase = read_passphrase(&self.passphrase, true)?.unwrap_or_default();
+                generate_descriptor_with_mnemonic(ctx.network.into(), &self.desc_type, &passphrase)
             }
-            None => generate_descriptor_with_mnemonic(ctx.network.into(), &self.desc_type),
         }
     }
 }
@@ -69,10 +89,23 @@ pub struct CompileCommand {
     /// Sets the spending policy to compile.
     #[arg(env = "POLICY", required = true, index = 1)]
     policy: String,
-    /// Sets the script type used to embed the compiled policy.
+    /// Sets the script type used to embed the compiled policy. With tr, the most likely key
+    /// spends through the key path and the other branches become script tree leaves.
     #[arg(env = "TYPE", short = 't', long = "type", default_value = "wsh", value_parser = ["sh","wsh", "sh-wsh", "tr"]
         )]
     script_type: String,
+    /// Also reports the signers, timelocks, satisfaction weight and fee of each spending path.
+    #[arg(long = "analyze")]
+    analyze: bool,
+    /// Sets the fee rate, in sat/vB, used to estimate the fee of each spending path.
+    #[arg(
+        env = "SATS_VBYTE",
+        short = 'f',
+        long = "fee_rate",
+        default_value = "1.0",
+        requires = "analyze"
+    )]
+    fee_rate: f32,
 }
 
 #[cfg(feature = "compiler")]
@@ -80,18 +113,16 @@ impl AppCommand<AppContext<Init>> for CompileCommand {
     type Output = DescriptorResult;
 
     fn execute(&self, _ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
-        let policy: Concrete<String> = Concrete::from_str(&self.policy)
-            .map_err(|e| Error::Generic(format!("Invalid policy: {e}")))?;
+        let policy: Concrete<String> = Concrete::from_str(&self.policy).map_err(|e| {
+            Error::coded(ErrorCode::InvalidMiniscript, format!("Invalid policy: {e}"))
+        })?;
 
         let legacy_policy: Miniscript<String, bdk_wallet::miniscript::Legacy> = policy
             .compile()
-            .map_err(|e| Error::Generic(e.to_string()))?;
+            .map_err(|e| Error::coded(ErrorCode::PolicyCompilation, e.to_string()))?;
         let segwit_policy: Miniscript<String, bdk_wallet::miniscript::Segwitv0> = policy
             .compile()
-            .map_err(|e| Error::Generic(e.to_string()))?;
-        let taproot_policy: Miniscript<String, bdk_wallet::miniscript::Tap> = policy
-            .compile()
-            .map_err(|e| Error::Generic(e.to_string()))?;
+            .map_err(|e| Error::coded(ErrorCode::PolicyCompilation, e.to_string()))?;
 
         let mut r = None;
 
@@ -100,34 +131,55 @@ impl AppCommand<AppContext<Init>> for CompileCommand {
             "wsh" => Descriptor::new_wsh(segwit_policy),
             "sh-wsh" => Descriptor::new_sh_wsh(segwit_policy),
             "tr" => {
-                // Use a randomized unspendable internal key (H + rG) instead of a fixed NUMS
-                // point. This improves privacy by preventing observers from determining whether
+                // The compiler moves the most likely key to the key path and the other branches
+                // to leaves, weighted by their probability. When no key can be extracted, use a
+                // randomized unspendable internal key (H + rG) instead of a fixed NUMS point.
+                // This improves privacy by preventing observers from determining whether
                 // key-path spending is disabled. `r` is returned so the user can verify the key
                 // is derived from the NUMS point. See BIP-341.
                 let secp = Secp256k1::new();
                 let r_secret = SecretKey::new(&mut rand::thread_rng());
                 r = Some(r_secret.display_secret().to_string());
 
-                let nums_key = XOnlyPublicKey::from_str(NUMS_UNSPENDABLE_KEY_HEX)
-                    .map_err(|e| Error::Generic(format!("Invalid NUMS key: {e}")))?;
+                let nums_key = XOnlyPublicKey::from_str(NUMS_UNSPENDABLE_KEY_HEX).map_err(|e| {
+                    Error::coded(ErrorCode::InvalidKey, format!("Invalid NUMS key: {e}"))
+                })?;
                 let nums_point = PublicKey::from_x_only_public_key(nums_key, Parity::Even);
 
-                let internal_key_point =
-                    nums_point
-                        .add_exp_tweak(&secp, &Scalar::from(r_secret))
-                        .map_err(|e| Error::Generic(format!("Failed to tweak NUMS key: {e}")))?;
+                let internal_key_point = nums_point
+                    .add_exp_tweak(&secp, &Scalar::from(r_secret))
+                    .map_err(|e| {
+                    Error::coded(
+                        ErrorCode::InvalidKey,
+                        format!("Failed to tweak NUMS key: {e}"),
+                    )
+                })?;
                 let (xonly_internal_key, _) = internal_key_point.x_only_public_key();
 
-                let tree = TapTree::Leaf(Arc::new(taproot_policy));
-                Descriptor::new_tr(xonly_internal_key.to_string(), Some(tree))
+                let unspendable_key = xonly_internal_key.to_string();
+                let descriptor = policy
+                    .compile_tr(Some(unspendable_key.clone()))
+                    .map_err(|e| Error::coded(ErrorCode::InvalidDescriptor, e.to_string()))?;
+                if let Descriptor::Tr(tr) = &descriptor
+                    && *tr.internal_key() != unspendable_key
+                {
+                    r = None;
+                }
+                Ok(descriptor)
             }
             _ => {
-                return Err(Error::Generic(
+                return Err(Error::coded(
+                    ErrorCode::InvalidArgument,
                     "Invalid script type. Supported: sh, wsh, sh-wsh, tr".into(),
                 ));
             }
         }?;
 
+        let spending_paths = match self.analyze {
+            true => Some(analyze(&policy, &descriptor, self.fee_rate)?),
+            false => None,
+        };
+
         Ok(DescriptorResult {
             descriptor: Some(descriptor.to_string()),
             mnemonic: None,
@@ -136,6 +188,7 @@ impl AppCommand<AppContext<Init>> for CompileCommand {
             private_descriptors: None,
             fingerprint: None,
             r,
+            spending_paths,
         })
     }
 }
@@ -6,7 +6,7 @@ use bitcoin_payment_instructions::{
 use core::{net::SocketAddr, str::FromStr};
 use tokio::time::timeout;
 
-use crate::error::BDKCliError as Error;
+use crate::error::{BDKCliError as Error, ErrorCode};
 
 #[derive(Debug)]
 pub struct ResolvedPaymentInfo {
@@ -76,15 +76,20 @@ fn get_onchain_info(
         .methods()
         .iter()
         .find(|ix| matches!(ix, PaymentMethod::OnChain(_)))
-        .ok_or(Error::Generic(
+        .ok_or(Error::coded(
+            ErrorCode::Payjoin,
             "Missing Onchain payment method option.".to_string(),
         ))?
     else {
-        return Err(Error::Generic("Unsupported payment method".to_string()));
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            "Unsupported payment method".to_string(),
+        ));
     };
 
     let Some(onchain_amount) = instructions.onchain_payment_amount() else {
-        return Err(Error::Generic(
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
             "On chain amount should be specified".to_string(),
         ));
     };
@@ -105,7 +110,10 @@ pub async fn process_instructions(
                 .methods()
                 .any(|method| matches!(method.method_type(), PaymentMethodType::OnChain))
             {
-                return Err(Error::Generic("Unsupported payment method".to_string()));
+                return Err(Error::coded(
+                    ErrorCode::InvalidArgument,
+                    "Unsupported payment method".to_string(),
+                ));
             }
 
             let min_amount = instructions
@@ -117,7 +125,8 @@ pub async fn process_instructions(
                 .map(|amnt| Amount::from_sat(amnt.sats_rounding_up()));
 
             if min_amount.is_some_and(|min| amount_to_send < min) {
-                return Err(Error::Generic(
+                return Err(Error::coded(
+                    ErrorCode::InvalidArgument,
                     format!(
                         "Amount to send should be greater than min {}",
                         min_amount.unwrap()
@@ -127,7 +136,8 @@ pub async fn process_instructions(
             }
 
             if max_amount.is_some_and(|max| amount_to_send > max) {
-                return Err(Error::Generic(
+                return Err(Error::coded(
+                    ErrorCode::InvalidArgument,
                     format!(
                         "Amount to send should be lower than max {}",
                         max_amount.unwrap()
@@ -144,7 +154,10 @@ pub async fn process_instructions(
                 )
                 .await
                 .map_err(|err| {
-                    Error::Generic(format!("Error occured while parsing instructions {err}"))
+                    Error::coded(
+                        ErrorCode::InvalidArgument,
+                        format!("Error occured while parsing instructions {err}"),
+                    )
                 })?;
 
             let onchain_details = get_onchain_info(&fixed_instructions)?;
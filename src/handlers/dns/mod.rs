@@ -1,10 +1,11 @@
 mod dns_payment_instructions;
 
-use crate::error::BDKCliError as Error;
+use crate::error::{BDKCliError as Error, ErrorCode};
 use crate::handlers::dns::dns_payment_instructions::{
     parse_dns_instructions, process_instructions, resolve_dns_recipient,
 };
 use crate::handlers::{AppContext, AsyncAppCommand, Init, OfflineOperations};
+use crate::utils::stats::{self, Phase};
 use crate::utils::types::{PsbtResult, StatusResult};
 use crate::utils::{parse_dns_recipient, parse_outpoint, parse_recipient};
 use bdk_wallet::KeychainKind;
@@ -94,13 +95,19 @@ impl AsyncAppCommand<AppContext<OfflineOperations<'_>>> for CreateDnsTxCommand {
             let amount = Amount::from_sat(*amount_sat);
             let (resolver, instructions) = parse_dns_instructions(hrn, network, &self.dns_resolver)
                 .await
-                .map_err(|e| Error::Generic(format!("Parsing error occured {e:#?}")))?;
+                .map_err(|e| {
+                    Error::coded(
+                        ErrorCode::InvalidArgument,
+                        format!("Parsing error occured {e:#?}"),
+                    )
+                })?;
             let payment = process_instructions(amount, &instructions, resolver).await?;
             recipients.push((payment.0.into(), payment.1.to_sat()));
         }
 
         if recipients.is_empty() {
-            return Err(Error::Generic(
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
                 "Either --to or --to_dns parameters must be specified".to_string(),
             ));
         }
@@ -111,7 +118,8 @@ impl AsyncAppCommand<AppContext<OfflineOperations<'_>>> for CreateDnsTxCommand {
             if recipients.len() == 1 {
                 tx_builder.drain_wallet().drain_to(recipients[0].0.clone());
             } else {
-                return Err(Error::Generic(
+                return Err(Error::coded(
+                    ErrorCode::TxCreate,
                     "Wallet can only be drained to a single output".to_string(),
                 ));
             }
@@ -145,14 +153,14 @@ impl AsyncAppCommand<AppContext<OfflineOperations<'_>>> for CreateDnsTxCommand {
         if let Some(base64_data) = &self.add_data {
             let op_return_data = BASE64_STANDARD
                 .decode(base64_data)
-                .map_err(|e| Error::Generic(e.to_string()))?;
+                .map_err(|e| Error::coded(ErrorCode::InvalidBase64, e.to_string()))?;
             tx_builder.add_data(
                 &PushBytesBuf::try_from(op_return_data)
-                    .map_err(|e| Error::Generic(e.to_string()))?,
+                    .map_err(|e| Error::coded(ErrorCode::TxCreate, e.to_string()))?,
             );
         } else if let Some(string_data) = &self.add_string {
             let data = PushBytesBuf::try_from(string_data.as_bytes().to_vec())
-                .map_err(|e| Error::Generic(e.to_string()))?;
+                .map_err(|e| Error::coded(ErrorCode::TxCreate, e.to_string()))?;
             tx_builder.add_data(&data);
         }
 
@@ -169,7 +177,10 @@ impl AsyncAppCommand<AppContext<OfflineOperations<'_>>> for CreateDnsTxCommand {
             tx_builder.policy_path(policy, keychain);
         }
 
-        let psbt = tx_builder.finish()?;
+        let psbt = {
+            let _timer = stats::timer(Phase::CoinSelection);
+            tx_builder.finish()?
+        };
         Ok(PsbtResult::new(&psbt, Some(false)))
     }
 }
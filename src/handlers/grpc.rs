@@ -0,0 +1,411 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! gRPC server
+//!
+//! `serve --grpc` serves the wallet operations of `proto/wallet.proto` next to the REST API.
+//! The requests run the same `wallet` commands through the same [`Runner`], and the replies
+//! carry the JSON output of the commands. `Sync` streams the progress and wallet events of the
+//! sync before its output, and `Watch` syncs the wallet on an interval, streaming its wallet
+//! events until the client hangs up. The calls authenticate with the `authorization` metadata
+//! of their request, as the REST requests do with their header.
+
+use crate::commands::{OfflineWalletSubCommand, WalletSubCommand};
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::handlers::offline::{
+    BalanceCommand, NewAddressCommand, TransactionsCommand, UnspentCommand,
+};
+use crate::handlers::server::{ApiError, Runner, create_tx_command, offline_command};
+use crate::utils::auth::{Scope, TlsIdentity};
+use crate::utils::events::{self, EventSender};
+use axum::http::StatusCode;
+use proto::wallet_server::{Wallet, WalletServer};
+use proto::{BroadcastRequest, CreatePsbtRequest, Event, JsonReply, WalletRequest, WatchRequest};
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::transport::{Identity, ServerTlsConfig};
+use tonic::{Request, Response, Status};
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+use {
+    crate::commands::OnlineWalletSubCommand,
+    crate::handlers::online::SyncCommand,
+    crate::handlers::server::{broadcast_command, online_command},
+    proto::broadcast_request::Transaction,
+};
+
+pub mod proto {
+    tonic::include_proto!("bdk_cli.wallet.v1");
+}
+
+/// Seconds between two syncs of `Watch` when the request leaves it unset.
+const DEFAULT_WATCH_INTERVAL: u64 = 30;
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<Event, Status>> + Send>>;
+
+struct WalletService {
+    runner: Arc<Runner>,
+}
+
+/// The gRPC status of a failed request, mirroring the HTTP status of the REST API.
+fn status(e: ApiError) -> Status {
+    let code = match e.status {
+        StatusCode::BAD_REQUEST => tonic::Code::InvalidArgument,
+        StatusCode::UNAUTHORIZED => tonic::Code::Unauthenticated,
+        StatusCode::NOT_FOUND => tonic::Code::NotFound,
+        StatusCode::FORBIDDEN => tonic::Code::PermissionDenied,
+        StatusCode::UNPROCESSABLE_ENTITY => tonic::Code::FailedPrecondition,
+        StatusCode::BAD_GATEWAY => tonic::Code::Unavailable,
+        _ => tonic::Code::Internal,
+    };
+    Status::new(code, e.error.to_string())
+}
+
+fn event(kind: &str, json: &Value) -> Event {
+    Event {
+        kind: kind.to_string(),
+        json: json.to_string(),
+    }
+}
+
+impl WalletService {
+    /// The scope of `request`, from its `authorization` metadata.
+    fn authorize<T>(&self, request: &Request<T>) -> Result<Scope, Status> {
+        let authorization = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok());
+        self.runner.authorize(authorization).map_err(status)
+    }
+
+    async fn reply(
+        &self,
+        wallet: String,
+        command: WalletSubCommand,
+        scope: Scope,
+    ) -> Result<Response<JsonReply>, Status> {
+        let output = self
+            .runner
+            .clone()
+            .run(wallet, command, scope, None)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(JsonReply {
+            json: output.to_string(),
+        }))
+    }
+}
+
+/// Sends the events of `receiver` to `stream`, only the wallet events unless `progress`.
+async fn relay(
+    mut receiver: mpsc::UnboundedReceiver<events::Event>,
+    stream: mpsc::UnboundedSender<Result<Event, Status>>,
+    progress: bool,
+) {
+    while let Some(forwarded) = receiver.recv().await {
+        let message = match forwarded {
+            events::Event::Progress(json) if progress => event("progress", &json),
+            events::Event::Progress(_) => continue,
+            events::Event::Wallet(json) => event("wallet", &json),
+        };
+        if stream.send(Ok(message)).is_err() {
+            break;
+        }
+    }
+}
+
+/// Runs `sync` on `wallet`, its events relayed to `stream`, returning its output.
+async fn sync(
+    runner: Arc<Runner>,
+    wallet: String,
+    scope: Scope,
+    stream: &mpsc::UnboundedSender<Result<Event, Status>>,
+    progress: bool,
+) -> Result<Value, ApiError> {
+    let command = sync_command()?;
+    let (sender, receiver): (EventSender, _) = mpsc::unbounded_channel();
+    let relayed = tokio::spawn(relay(receiver, stream.clone(), progress));
+    let output = runner.run(wallet, command, scope, Some(sender)).await;
+    // The runner dropped the sender once the sync ended, which ends the relay.
+    let _ = relayed.await;
+    output
+}
+
+/// The `sync` of the `Sync` and `Watch` calls.
+fn sync_command() -> Result<WalletSubCommand, ApiError> {
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    return Ok(online_command(OnlineWalletSubCommand::Sync(
+        SyncCommand::default(),
+    )));
+    #[cfg(not(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    )))]
+    Err(ApiError::bad_request("Built without a blockchain backend"))
+}
+
+#[tonic::async_trait]
+impl Wallet for WalletService {
+    async fn balance(
+        &self,
+        request: Request<WalletRequest>,
+    ) -> Result<Response<JsonReply>, Status> {
+        let scope = self.authorize(&request)?;
+        let wallet = request.into_inner().wallet;
+        let command = OfflineWalletSubCommand::Balance(BalanceCommand::default());
+        self.reply(wallet, offline_command(command), scope).await
+    }
+
+    async fn list_unspent(
+        &self,
+        request: Request<WalletRequest>,
+    ) -> Result<Response<JsonReply>, Status> {
+        let scope = self.authorize(&request)?;
+        let wallet = request.into_inner().wallet;
+        let command = OfflineWalletSubCommand::Unspent(UnspentCommand::default());
+        self.reply(wallet, offline_command(command), scope).await
+    }
+
+    async fn list_transactions(
+        &self,
+        request: Request<WalletRequest>,
+    ) -> Result<Response<JsonReply>, Status> {
+        let scope = self.authorize(&request)?;
+        let wallet = request.into_inner().wallet;
+        let command = OfflineWalletSubCommand::Transactions(TransactionsCommand::default());
+        self.reply(wallet, offline_command(command), scope).await
+    }
+
+    async fn new_address(
+        &self,
+        request: Request<WalletRequest>,
+    ) -> Result<Response<JsonReply>, Status> {
+        let scope = self.authorize(&request)?;
+        let wallet = request.into_inner().wallet;
+        let command = OfflineWalletSubCommand::NewAddress(NewAddressCommand);
+        self.reply(wallet, offline_command(command), scope).await
+    }
+
+    async fn create_psbt(
+        &self,
+        request: Request<CreatePsbtRequest>,
+    ) -> Result<Response<JsonReply>, Status> {
+        let scope = self.authorize(&request)?;
+        let request = request.into_inner();
+        let recipients = request
+            .recipients
+            .into_iter()
+            .map(|recipient| (recipient.address, recipient.amount));
+        let command =
+            create_tx_command(recipients, request.fee_rate, request.send_all).map_err(status)?;
+        self.reply(request.wallet, command, scope).await
+    }
+
+    async fn broadcast(
+        &self,
+        request: Request<BroadcastRequest>,
+    ) -> Result<Response<JsonReply>, Status> {
+        let scope = self.authorize(&request)?;
+        #[cfg(any(
+            feature = "electrum",
+            feature = "esplora",
+            feature = "rpc",
+            feature = "cbf"
+        ))]
+        {
+            let request = request.into_inner();
+            let (psbt, tx) = match request.transaction {
+                Some(Transaction::Psbt(psbt)) => (Some(psbt), None),
+                Some(Transaction::Tx(tx)) => (None, Some(tx)),
+                None => (None, None),
+            };
+            let command = broadcast_command(psbt, tx).map_err(status)?;
+            self.reply(request.wallet, command, scope).await
+        }
+        #[cfg(not(any(
+            feature = "electrum",
+            feature = "esplora",
+            feature = "rpc",
+            feature = "cbf"
+        )))]
+        {
+            let _ = (request, scope);
+            Err(Status::unimplemented("Built without a blockchain backend"))
+        }
+    }
+
+    type SyncStream = EventStream;
+
+    async fn sync(
+        &self,
+        request: Request<WalletRequest>,
+    ) -> Result<Response<Self::SyncStream>, Status> {
+        let scope = self.authorize(&request)?;
+        let wallet = request.into_inner().wallet;
+        let runner = self.runner.clone();
+        let (stream, messages) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let result = match sync(runner, wallet, scope, &stream, true).await {
+                Ok(output) => Ok(event("result", &output)),
+                Err(e) => Err(status(e)),
+            };
+            let _ = stream.send(result);
+        });
+        Ok(Response::new(Box::pin(UnboundedReceiverStream::new(
+            messages,
+        ))))
+    }
+
+    type WatchStream = EventStream;
+
+    async fn watch(
+        &self,
+        request: Request<WatchRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let scope = self.authorize(&request)?;
+        let request = request.into_inner();
+        let interval = match request.interval_secs {
+            0 => DEFAULT_WATCH_INTERVAL,
+            secs => secs,
+        };
+        let runner = self.runner.clone();
+        let (stream, messages) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(Duration::from_secs(interval));
+            // The other requests run between two syncs, as the runner is only held by each sync.
+            while !stream.is_closed() {
+                ticks.tick().await;
+                if let Err(e) = sync(
+                    runner.clone(),
+                    request.wallet.clone(),
+                    scope,
+                    &stream,
+                    false,
+                )
+                .await
+                {
+                    let _ = stream.send(Err(status(e)));
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(UnboundedReceiverStream::new(
+            messages,
+        ))))
+    }
+}
+
+/// Serves the wallet operations over gRPC on `address` until Ctrl-C or SIGTERM, over TLS with
+/// `tls`.
+pub(crate) async fn serve(
+    runner: Arc<Runner>,
+    address: SocketAddr,
+    tls: Option<&TlsIdentity>,
+) -> Result<(), Error> {
+    let mut server = tonic::transport::Server::builder();
+    if let Some(tls) = tls {
+        let identity = Identity::from_pem(&tls.cert, &tls.key);
+        server = server
+            .tls_config(ServerTlsConfig::new().identity(identity))
+            .map_err(|e| {
+                Error::coded(
+                    ErrorCode::Config,
+                    format!("Invalid TLS certificate or key: {e}"),
+                )
+            })?;
+        log::info!("gRPC over TLS on {address}");
+    } else {
+        log::info!("gRPC on {address}");
+    }
+    server
+        .add_service(WalletServer::new(WalletService { runner }))
+        .serve_with_shutdown(address, async {
+            crate::utils::shutdown_signal().await;
+        })
+        .await
+        .map_err(|e| Error::coded(ErrorCode::Io, format!("The gRPC server failed: {e}")))
+}
+
+#[cfg(all(test, feature = "esplora"))]
+mod tests {
+    use super::*;
+    use crate::utils::auth::ServerSecurity;
+    use crate::utils::fake_http::{Response, serve_esplora};
+    use bdk_wallet::bitcoin::Network;
+    use bdk_wallet::bitcoin::bip32::{Xpriv, Xpub};
+    use bdk_wallet::bitcoin::secp256k1::Secp256k1;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_broadcast_is_confirmed_by_the_spend_credential() {
+        let datadir = tempfile::tempdir().unwrap();
+        let xpriv = Xpriv::new_master(Network::Regtest, &[7; 32]).unwrap();
+        let xpub = Xpub::from_priv(&Secp256k1::new(), &xpriv);
+        // A regtest esplora server accepting every broadcast.
+        let esplora = serve_esplora(|_| Response::status("200 OK"));
+        std::fs::write(
+            datadir.path().join("config.toml"),
+            format!(
+                "[wallets.grpc]\nwallet = \"grpc\"\nnetwork = \"regtest\"\n\
+                 ext_descriptor = \"tr({xpub}/0/*)\"\ndatabase_type = \"sqlite\"\n\
+                 client_type = \"esplora\"\nserver_url = \"{esplora}\"\n"
+            ),
+        )
+        .unwrap();
+        let security = ServerSecurity {
+            tokens: vec!["spender".to_string()],
+            read_tokens: vec!["reader".to_string()],
+            ..Default::default()
+        };
+        let runner = Runner::new(
+            datadir.path().to_path_buf(),
+            false,
+            false,
+            security.auth().unwrap(),
+        );
+        let service = WalletService { runner };
+        let request = |token: &str| {
+            let tx = format!(
+                "0200000001{}ffffffff00ffffffff010000000000000000016a00000000",
+                "00".repeat(32)
+            );
+            let mut request = Request::new(BroadcastRequest {
+                wallet: "grpc".to_string(),
+                transaction: Some(Transaction::Tx(tx)),
+            });
+            let authorization = format!("Bearer {token}").parse().unwrap();
+            request
+                .metadata_mut()
+                .insert("authorization", authorization);
+            request
+        };
+
+        let denied = service.broadcast(request("reader")).await.unwrap_err();
+        assert_eq!(denied.code(), tonic::Code::PermissionDenied);
+        // No one is asked for a confirmation, the spend credential standing for it.
+        let reply = service.broadcast(request("spender")).await.unwrap();
+        let output: Value = serde_json::from_str(&reply.into_inner().json).unwrap();
+        assert_eq!(output["txid"].as_str().map(str::len), Some(64));
+    }
+}
@@ -0,0 +1,514 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Wallet setup wizard
+//!
+//! `init` asks for the network, the keys, the script type and the backend of a new wallet, and
+//! saves them with `wallet config` before its first scan. The answers can also be given as
+//! options, and piped answers are read one per line, an empty line or the end of the input
+//! taking the default answer.
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+use std::path::Path;
+use std::str::FromStr;
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+use crate::client::ClientType;
+use crate::commands::WalletOpts;
+use crate::config::{WalletConfig, WalletConfigInner};
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::handlers::config::SaveConfigCommand;
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+use crate::handlers::online::scan_wallet;
+use crate::handlers::{AppCommand, AppContext, Init};
+#[cfg(any(feature = "sqlite", feature = "redb"))]
+use crate::persister::{DatabaseType, parse_database_type};
+use crate::utils::descriptors::{
+    generate_descriptor_from_mnemonic, generate_descriptor_with_mnemonic, generate_descriptors,
+};
+#[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
+use crate::utils::network::default_server_url;
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+use crate::utils::runtime::WalletRuntime;
+use crate::utils::types::{DescriptorResult, InitResult};
+use crate::utils::{is_mnemonic, read_answer, read_hidden_line};
+use bdk_wallet::bitcoin::Network;
+use clap::Parser;
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+use clap::ValueEnum;
+
+/// The script types of single key wallets, with the BIP of their derivation paths.
+const SCRIPT_TYPES: &[(&str, &str)] = &[
+    ("wpkh", "native segwit, BIP-84"),
+    ("tr", "taproot, BIP-86"),
+    ("sh", "nested segwit, BIP-49"),
+    ("pkh", "legacy, BIP-44"),
+];
+
+#[derive(Parser, Debug, Clone, PartialEq)]
+pub struct InitCommand {
+    /// Name of the new wallet, letters, digits, `-`, `_` and `.`.
+    #[arg(long = "wallet", short = 'w', value_parser = parse_wallet_name)]
+    pub wallet: Option<String>,
+
+    /// Imports a mnemonic, xprv or xpub rather than asking whether to generate a new mnemonic.
+    /// An xpub makes a watch-only wallet.
+    #[arg(long = "import", value_name = "KEY", conflicts_with = "generate")]
+    pub import: Option<String>,
+
+    /// Generates a new 12 word mnemonic without asking.
+    #[arg(long = "generate")]
+    pub generate: bool,
+
+    /// Script type of the addresses.
+    #[arg(long = "script-type", value_parser = ["wpkh", "tr", "sh", "pkh"])]
+    pub script_type: Option<String>,
+
+    /// Wallet database.
+    #[cfg(any(feature = "sqlite", feature = "redb"))]
+    #[arg(long = "database-type", short = 'd', value_parser = parse_database_type)]
+    pub database_type: Option<DatabaseType>,
+
+    /// Blockchain backend.
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    #[arg(long = "client-type", short = 'c', value_enum)]
+    pub client_type: Option<ClientType>,
+
+    /// Server of the blockchain backend.
+    #[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
+    #[arg(long = "url", short = 'u', visible_alias = "server")]
+    pub url: Option<String>,
+
+    /// Challenge of the custom signet of a signet wallet, the default signet when left out.
+    #[arg(long = "signet-challenge", value_name = "HEX")]
+    pub signet_challenge: Option<String>,
+
+    /// Keeps the private keys in a password encrypted keystore without asking.
+    #[arg(long = "encrypt")]
+    pub encrypt: bool,
+
+    /// Does not scan the blockchain for the transactions of the wallet once it is set up.
+    #[arg(long = "no-scan")]
+    pub no_scan: bool,
+
+    /// The network of the global `--network` option, when given rather than asked for.
+    #[arg(skip)]
+    pub(crate) network: Option<Network>,
+
+    /// Saves offline mode in the config file, set from the global `--offline` flag.
+    #[arg(skip)]
+    pub(crate) offline: bool,
+}
+
+/// Parse a wallet name, the name of its directory in the data directory: letters, digits, `-`,
+/// `_` and `.`, not starting with a `.`.
+pub(crate) fn parse_wallet_name(s: &str) -> Result<String, String> {
+    if s.is_empty()
+        || s.starts_with('.')
+        || !s
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(format!(
+            "Invalid wallet name '{s}': use letters, digits, '-', '_' and '.', not starting with '.'"
+        ));
+    }
+    Ok(s.to_string())
+}
+
+/// Asks the questions of the wizard on stderr and reads the answers from stdin.
+struct Prompter;
+
+impl Prompter {
+    /// The answer to `question`, or `default` for an empty answer or at the end of the input.
+    /// Fails at the end of the input for a question without a default, naming the `option`
+    /// giving the answer instead.
+    fn ask(&self, question: &str, default: Option<&str>, option: &str) -> Result<String, Error> {
+        let prompt = match default {
+            Some(default) => format!("{question} [{default}]: "),
+            None => format!("{question}: "),
+        };
+        loop {
+            match read_answer(&prompt)? {
+                Some(answer) if !answer.is_empty() => return Ok(answer),
+                Some(_) => {
+                    if let Some(default) = default {
+                        return Ok(default.to_string());
+                    }
+                }
+                None => {
+                    eprintln!();
+                    return default.map(ToString::to_string).ok_or_else(|| {
+                        Error::coded(
+                            ErrorCode::ConfirmationRequired,
+                            format!(
+                                "init needs an answer to '{question}', pass {option} or run it in a terminal"
+                            ),
+                        )
+                    });
+                }
+            }
+        }
+    }
+
+    /// One of `choices`, asked again until the answer is one of them.
+    fn choose(
+        &self,
+        question: &str,
+        choices: &[String],
+        default: &str,
+        option: &str,
+    ) -> Result<String, Error> {
+        let question = format!("{question} ({})", choices.join("/"));
+        loop {
+            let answer = self.ask(&question, Some(default), option)?.to_lowercase();
+            if choices.contains(&answer) {
+                return Ok(answer);
+            }
+            eprintln!("Please answer one of {}.", choices.join(", "));
+        }
+    }
+
+    fn yes(&self, question: &str, default: bool, option: &str) -> Result<bool, Error> {
+        let choices = ["y".to_string(), "n".to_string()];
+        let default = if default { "y" } else { "n" };
+        Ok(self.choose(question, &choices, default, option)? == "y")
+    }
+}
+
+/// The names of the variants of a value enum, such as the compiled in backends.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+fn variant_names<T: ValueEnum>() -> Vec<String> {
+    T::value_variants()
+        .iter()
+        .filter_map(|variant| variant.to_possible_value())
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+impl InitCommand {
+    /// The answer of `option` when given, or the one of the user among `choices`, the first
+    /// being the default. A single choice is taken without asking.
+    fn choose(
+        &self,
+        prompter: &Prompter,
+        given: Option<String>,
+        question: &str,
+        choices: &[String],
+        option: &str,
+    ) -> Result<String, Error> {
+        match (given, choices) {
+            (Some(given), _) => Ok(given),
+            (None, [only]) => Ok(only.clone()),
+            (None, choices) => prompter.choose(question, choices, &choices[0], option),
+        }
+    }
+
+    /// The descriptors of a new mnemonic, or of the key the user imports.
+    fn descriptors(
+        &self,
+        prompter: &Prompter,
+        network: Network,
+        script_type: &str,
+    ) -> Result<DescriptorResult, Error> {
+        let import = match (&self.import, self.generate) {
+            (Some(key), _) => Some(key.clone()),
+            (None, true) => None,
+            (None, false) => {
+                let choices = ["new".to_string(), "import".to_string()];
+                let answer = prompter.choose(
+                    "Generate a new mnemonic or import a key?",
+                    &choices,
+                    "new",
+                    "--generate or --import",
+                )?;
+                match answer.as_str() {
+                    "import" => match read_hidden_line("Mnemonic, xprv or xpub (hidden): ")? {
+                        key if key.trim().is_empty() => {
+                            return Err(Error::coded(
+                                ErrorCode::ConfirmationRequired,
+                                "init needs the key to import, pass --import or run it in a terminal"
+                                    .to_string(),
+                            ));
+                        }
+                        key => Some(key),
+                    },
+                    _ => None,
+                }
+            }
+        };
+
+        match import {
+            Some(key) if is_mnemonic(key.trim()) => {
+                generate_descriptor_from_mnemonic(key.trim(), network.into(), script_type, "")
+            }
+            Some(key) => generate_descriptors(script_type, key.trim(), network.into()),
+            None => {
+                let result = generate_descriptor_with_mnemonic(network.into(), script_type, "")?;
+                if let Some(mnemonic) = &result.mnemonic {
+                    eprintln!(
+                        "\nWrite down these words, in order. They are the only backup of the wallet:\n\n  {mnemonic}\n"
+                    );
+                }
+                Ok(result)
+            }
+        }
+    }
+}
+
+impl AppCommand<AppContext<Init>> for InitCommand {
+    type Output = InitResult;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let prompter = Prompter;
+        eprintln!("Setting up a new wallet. Press enter to take the [default] answer.\n");
+
+        let network = match self.network {
+            Some(network) => network,
+            None => {
+                let choices: Vec<String> = ["testnet4", "signet", "testnet", "regtest", "bitcoin"]
+                    .iter()
+                    .map(|network| network.to_string())
+                    .collect();
+                let answer =
+                    prompter.choose("Network", &choices, &choices[0], "--network <NETWORK>")?;
+                Network::from_str(&answer).map_err(|e| {
+                    Error::coded(
+                        ErrorCode::InvalidArgument,
+                        format!("Invalid network '{answer}': {e}"),
+                    )
+                })?
+            }
+        };
+        if network == Network::Bitcoin
+            && !prompter.yes(
+                "Bitcoin mainnet holds real funds and bdk-cli is experimental software. Go on?",
+                false,
+                "--network",
+            )?
+        {
+            return Err(Error::Cancelled("the wallet setup".to_string()));
+        }
+
+        let existing = WalletConfig::load(&ctx.datadir)?
+            .map(|config| config.wallets.into_keys().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let wallet = match &self.wallet {
+            Some(wallet) => wallet.clone(),
+            None => loop {
+                let answer = prompter.ask("Wallet name", None, "--wallet <NAME>")?;
+                match parse_wallet_name(&answer) {
+                    Ok(wallet) if existing.contains(&wallet) => {
+                        eprintln!("Wallet '{wallet}' already exists, pick another name.")
+                    }
+                    Ok(wallet) => break wallet,
+                    Err(e) => eprintln!("{e}."),
+                }
+            },
+        };
+        if existing.contains(&wallet) {
+            return Err(Error::coded(
+                ErrorCode::WalletExists,
+                format!("Wallet '{wallet}' already exists"),
+            ));
+        }
+
+        let script_types: Vec<String> = SCRIPT_TYPES
+            .iter()
+            .map(|(script_type, _)| script_type.to_string())
+            .collect();
+        if self.script_type.is_none() {
+            eprintln!("Script types:");
+            for (script_type, description) in SCRIPT_TYPES {
+                eprintln!("  {script_type:5} {description}");
+            }
+        }
+        let script_type = self.choose(
+            &prompter,
+            self.script_type.clone(),
+            "Script type",
+            &script_types,
+            "--script-type",
+        )?;
+
+        let descriptors = self.descriptors(&prompter, network, &script_type)?;
+        let public_descriptors = descriptors
+            .public_descriptors
+            .ok_or_else(|| Error::Generic("The key gave no public descriptors".to_string()))?;
+        let (ext_descriptor, int_descriptor) = match descriptors.private_descriptors {
+            Some(private) => (private.external, private.internal),
+            None => (
+                public_descriptors.external.clone(),
+                public_descriptors.internal.clone(),
+            ),
+        };
+        let has_private_keys = ext_descriptor != public_descriptors.external;
+        let encrypt = has_private_keys
+            && (self.encrypt
+                || prompter.yes(
+                    "Encrypt the private keys with a password?",
+                    false,
+                    "--encrypt",
+                )?);
+
+        #[cfg(any(feature = "sqlite", feature = "redb"))]
+        let database_type = self.choose(
+            &prompter,
+            self.database_type.as_ref().map(ToString::to_string),
+            "Wallet database",
+            &DatabaseType::file_databases(),
+            "--database-type",
+        )?;
+
+        #[cfg(any(
+            feature = "electrum",
+            feature = "esplora",
+            feature = "rpc",
+            feature = "cbf"
+        ))]
+        let client_type = {
+            let given = self
+                .client_type
+                .as_ref()
+                .and_then(|client| client.to_possible_value())
+                .map(|value| value.get_name().to_string());
+            self.choose(
+                &prompter,
+                given,
+                "Blockchain backend",
+                &variant_names::<ClientType>(),
+                "--client-type",
+            )?
+        };
+
+        #[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
+        let server_url = match (client_type.as_str(), &self.url) {
+            ("cbf", _) => String::new(),
+            (_, Some(url)) => url.clone(),
+            (client, None) => prompter.ask(
+                "Server URL",
+                default_server_url(network, client),
+                "--url <URL>",
+            )?,
+        };
+
+        let wallet_config = WalletConfigInner {
+            wallet: wallet.clone(),
+            network: network.to_string(),
+            ext_descriptor,
+            int_descriptor: Some(int_descriptor),
+            signet_challenge: self.signet_challenge.clone(),
+            #[cfg(any(feature = "sqlite", feature = "redb"))]
+            database_type,
+            #[cfg(any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf"
+            ))]
+            client_type: Some(client_type),
+            #[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
+            server_url: Some(server_url),
+            ..Default::default()
+        };
+        let config = SaveConfigCommand {
+            force: false,
+            encrypt,
+            offline: self.offline,
+            explorer_url: None,
+            unit: None,
+            wallet_opts: WalletOpts::try_from(&wallet_config)?,
+        };
+        let mut config_ctx = AppContext::new(network, ctx.datadir.clone());
+        let status = config.execute(&mut config_ctx)?;
+        log::info!("{}", status.message);
+
+        let scan = !self.no_scan
+            && cfg!(any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf"
+            ))
+            && prompter.yes(
+                "Scan the blockchain for the transactions of the wallet now?",
+                true,
+                "--no-scan",
+            )?;
+
+        Ok(InitResult {
+            wallet,
+            network: network.to_string(),
+            public_descriptors,
+            encrypted: encrypt,
+            scan,
+            scanned: false,
+        })
+    }
+}
+
+/// Scans the blockchain for the transactions of the new `wallet`, as `wallet scan` does.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub async fn first_scan(home_dir: &Path, wallet: &str) -> Result<(), Error> {
+    let runtime = WalletRuntime::load(home_dir, wallet)?;
+    let mut wallet = runtime.build_wallet(true)?;
+    let client = runtime.build_client(&wallet)?;
+    {
+        let mut ctx = AppContext::new_online_wallet(
+            runtime.network,
+            runtime.home_dir.clone(),
+            &mut wallet,
+            &client,
+            runtime.wallet_name.clone(),
+            runtime.wallet_opts.stop_gap,
+        );
+        scan_wallet(&mut ctx, None, None, None).await?;
+    }
+    wallet.persist()?;
+    Ok(())
+}
@@ -1,15 +1,34 @@
-use crate::commands::KeySubCommand;
-use crate::error::BDKCliError as Error;
+use crate::commands::{KeyHwiSubCommand, KeySubCommand, MnemonicLanguage, PassphraseOpts};
+use crate::config::WalletConfig;
+use crate::error::{BDKCliError as Error, ErrorCode};
 use crate::handlers::{AppCommand, AppContext, Init};
-use crate::utils::{output::FormatOutput, types::KeyResult};
-use bdk_wallet::bip39::{Language, Mnemonic};
-use bdk_wallet::bitcoin::bip32::{DerivationPath, KeySource, Xpriv};
+use crate::utils::bip85::{self, Application};
+use crate::utils::descriptors::{generate_descriptors, template_descriptors};
+use crate::utils::hwi::{self, Hwi, HwiDevice};
+use crate::utils::keystore::{self, WalletDescriptors};
+use crate::utils::slip39::{self, GroupSpec};
+use crate::utils::{
+    output::{FormatOutput, ListResult},
+    read_key_password, read_passphrase,
+    types::{
+        Bip85Result, DescriptorCheck, DescriptorResult, KeyDescriptors, KeyInfo, KeyInfoResult,
+        KeyResult, StatusResult,
+    },
+};
+use bdk_wallet::bip39::Mnemonic;
+use bdk_wallet::bitcoin::NetworkKind;
+use bdk_wallet::bitcoin::bip32::{DerivationPath, Fingerprint, KeySource, Xpriv, Xpub};
+use bdk_wallet::bitcoin::hex::{DisplayHex, FromHex};
 use bdk_wallet::bitcoin::key::Secp256k1;
-use bdk_wallet::keys::{
-    DerivableKey, DescriptorKey, ExtendedKey, GeneratableKey, GeneratedKey, bip39::WordCount,
+use bdk_wallet::bitcoin::secp256k1::rand::{RngCore, thread_rng};
+use bdk_wallet::keys::{DerivableKey, DescriptorKey, ExtendedKey};
+use bdk_wallet::miniscript::descriptor::checksum::desc_checksum;
+use bdk_wallet::miniscript::descriptor::{
+    Descriptor, DescriptorPublicKey, DescriptorSecretKey, DescriptorType, Wildcard,
 };
-use bdk_wallet::miniscript::{self, Segwitv0};
+use bdk_wallet::miniscript::{ForEachKey, Segwitv0};
 use clap::Parser;
+use std::str::FromStr;
 
 impl KeySubCommand {
     pub fn execute(&self, ctx: &mut AppContext<Init>) -> Result<(), Error> {
@@ -23,22 +42,74 @@ impl KeySubCommand {
             KeySubCommand::Derive(derive_key_command) => derive_key_command
                 .execute(ctx)?
                 .write_out(std::io::stdout()),
+            KeySubCommand::Checksum(checksum_command) => {
+                checksum_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            KeySubCommand::Info(info_command) => {
+                info_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            KeySubCommand::Template(template_command) => {
+                template_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            KeySubCommand::Bip85(bip85_command) => {
+                bip85_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            KeySubCommand::Encrypt(encrypt_command) => {
+                encrypt_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            KeySubCommand::Decrypt(decrypt_command) => {
+                decrypt_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            KeySubCommand::Hwi { subcommand } => match subcommand {
+                KeyHwiSubCommand::List(list_command) => {
+                    list_command.execute(ctx)?.write_out(std::io::stdout())
+                }
+            },
         }
     }
 }
 #[derive(Parser, Debug, Clone, PartialEq, Eq)]
 pub struct GenerateKeyCommand {
-    /// Entropy level based on number of random seed mnemonic words.
+    /// Number of mnemonic words: 12, 15, 18, 21 or 24. SLIP-39 shares carry 128 bits of entropy
+    /// for 12 and 256 bits otherwise.
     #[arg(
         env = "WORD_COUNT",
         short = 'e',
-        long = "entropy",
+        long = "words",
+        visible_alias = "entropy",
         default_value = "12"
     )]
     word_count: usize,
-    /// Seed password.
-    #[arg(env = "PASSWORD", short = 'p', long = "password")]
-    password: Option<String>,
+    /// Uses this hex encoded entropy instead of a random one. Its length sets the word count.
+    #[arg(long = "entropy-hex", conflicts_with_all = ["word_count", "dice"])]
+    entropy_hex: Option<String>,
+    /// Derives the entropy from six-sided dice rolls (eg. "3512..."). Rolls of 1 to 4 give two
+    /// bits and rolls of 5 or 6 one bit, so a fair die gives unbiased bits.
+    #[arg(long = "dice")]
+    dice: Option<String>,
+    #[command(flatten)]
+    passphrase: PassphraseOpts,
+    /// Generates SLIP-39 Shamir backup shares instead of a BIP39 mnemonic.
+    #[arg(long = "slip39", requires = "groups")]
+    slip39: bool,
+    /// SLIP-39 groups of shares, each as <THRESHOLD>-of-<COUNT> (eg. "2-of-3,3-of-5").
+    #[arg(long = "groups", value_delimiter = ',', requires = "slip39")]
+    groups: Vec<GroupSpec>,
+    /// Number of SLIP-39 groups needed to recover the key.
+    #[arg(long = "group-threshold", default_value = "1", requires = "slip39")]
+    group_threshold: u8,
+    /// Wordlist of the mnemonic.
+    #[arg(
+        long = "language",
+        value_enum,
+        default_value = "english",
+        conflicts_with = "slip39"
+    )]
+    language: MnemonicLanguage,
+    /// Also outputs the external and internal descriptors of this script type, on its BIP-44,
+    /// 49, 84 or 86 account path.
+    #[arg(long = "type", short = 't', value_parser = ["pkh", "wpkh", "sh", "wsh", "tr"])]
+    desc_type: Option<String>,
 }
 
 impl AppCommand<AppContext<Init>> for GenerateKeyCommand {
@@ -46,16 +117,57 @@ impl AppCommand<AppContext<Init>> for GenerateKeyCommand {
 
     fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
         let secp = Secp256k1::new();
-        let mnemonic_type = match self.word_count {
-            12 => WordCount::Words12,
-            _ => WordCount::Words24,
+        if ![12, 15, 18, 21, 24].contains(&self.word_count) {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "Invalid word count {}, use 12, 15, 18, 21 or 24",
+                    self.word_count
+                ),
+            ));
+        }
+        let entropy_len = match (self.slip39, self.word_count) {
+            (true, 12) => 16,
+            (true, _) => 32,
+            (false, word_count) => word_count * 4 / 3,
         };
+        let entropy = match (&self.entropy_hex, &self.dice) {
+            (Some(hex), _) => Vec::<u8>::from_hex(hex)?,
+            (None, Some(rolls)) => dice_entropy(rolls, entropy_len)?,
+            (None, None) => {
+                let mut entropy = vec![0; entropy_len];
+                thread_rng().fill_bytes(&mut entropy);
+                entropy
+            }
+        };
+
+        if self.slip39 {
+            let master_secret = entropy;
+            let passphrase = read_passphrase(&self.passphrase, true)?.unwrap_or_default();
+            let shares = slip39::generate(
+                &master_secret,
+                &passphrase,
+                self.group_threshold,
+                &self.groups,
+            )?;
+            let xprv = Xpriv::new_master(ctx.network, &master_secret)?;
+
+            return Ok(KeyResult {
+                xprv: xprv.to_string(),
+                mnemonic: None,
+                language: None,
+                shares: Some(shares),
+                fingerprint: Some(xprv.fingerprint(&secp).to_string()),
+                descriptor_key: None,
+                descriptor_secret_key: None,
+                descriptors: key_descriptors(self.desc_type.as_deref(), &xprv, ctx.network.into())?,
+                xpub: None,
+            });
+        }
 
-        let mnemonic: GeneratedKey<_, miniscript::BareCtx> =
-            Mnemonic::generate((mnemonic_type, Language::English))
-                .map_err(|_| Error::Generic("Mnemonic generation error".to_string()))?;
-        let mnemonic = mnemonic.into_key();
-        let xkey: ExtendedKey = (mnemonic.clone(), self.password.clone()).into_extended_key()?;
+        let mnemonic = Mnemonic::from_entropy_in(self.language.into(), &entropy)?;
+        let passphrase = read_passphrase(&self.passphrase, true)?;
+        let xkey: ExtendedKey = (mnemonic.clone(), passphrase).into_extended_key()?;
         let xprv = xkey.into_xprv(ctx.network.into()).ok_or_else(|| {
             Error::Generic("Privatekey info not found (should not happen)".to_string())
         })?;
@@ -69,19 +181,84 @@ impl AppCommand<AppContext<Init>> for GenerateKeyCommand {
         Ok(KeyResult {
             xprv: xprv.to_string(),
             mnemonic: Some(phrase),
+            language: Some(MnemonicLanguage::from(mnemonic.language()).to_string()),
+            shares: None,
             fingerprint: Some(fingerprint.to_string()),
+            descriptor_key: None,
+            descriptor_secret_key: None,
+            descriptors: key_descriptors(self.desc_type.as_deref(), &xprv, ctx.network.into())?,
             xpub: None,
         })
     }
 }
 
+/// Turn dice rolls into `len` bytes of entropy.
+/// Descriptors of the master key for both keychains, when a script type is requested.
+fn key_descriptors(
+    desc_type: Option<&str>,
+    xprv: &Xpriv,
+    network: NetworkKind,
+) -> Result<Option<KeyDescriptors>, Error> {
+    let Some(desc_type) = desc_type else {
+        return Ok(None);
+    };
+    let result = generate_descriptors(desc_type, &xprv.to_string(), network)?;
+    match (result.public_descriptors, result.private_descriptors) {
+        (Some(public_descriptors), Some(private_descriptors)) => Ok(Some(KeyDescriptors {
+            public_descriptors,
+            private_descriptors,
+            multipath_descriptor: result.multipath_descriptor,
+        })),
+        _ => Err(Error::Generic(
+            "Descriptors not generated (should not happen)".to_string(),
+        )),
+    }
+}
+
+fn dice_entropy(rolls: &str, len: usize) -> Result<Vec<u8>, Error> {
+    let mut bits = Vec::new();
+    for roll in rolls.chars().filter(|c| !c.is_whitespace() && *c != ',') {
+        match roll.to_digit(10) {
+            Some(roll @ 1..=4) => bits.extend([(roll - 1) >> 1, (roll - 1) & 1]),
+            Some(roll @ 5..=6) => bits.push(roll - 5),
+            _ => {
+                return Err(Error::coded(
+                    ErrorCode::InvalidArgument,
+                    format!("Invalid dice roll '{roll}', use 1 to 6"),
+                ));
+            }
+        }
+    }
+    if bits.len() < len * 8 {
+        let missing = len * 8 - bits.len();
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!(
+                "The dice rolls give {} of the {} bits of entropy needed, roll about {} more times",
+                bits.len(),
+                len * 8,
+                (missing * 3).div_ceil(5)
+            ),
+        ));
+    }
+    Ok(bits[..len * 8]
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0, |acc, bit| (acc << 1) | *bit as u8))
+        .collect())
+}
+
 #[derive(Parser, Debug, Clone, PartialEq, Eq)]
 pub struct DeriveKeyCommand {
     /// Extended private key to derive from.
     #[arg(env = "XPRV", short = 'x', long = "xprv")]
     xprv: Xpriv,
     /// Path to use to derive extended public key from extended private key.
-    #[arg(env = "DERIVATION_PATH", short = 'p', long = "derivation_path")]
+    #[arg(
+        env = "DERIVATION_PATH",
+        short = 'p',
+        long = "derivation_path",
+        visible_alias = "path"
+    )]
     path: DerivationPath,
 }
 
@@ -91,16 +268,17 @@ impl AppCommand<AppContext<Init>> for DeriveKeyCommand {
     fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
         let secp = Secp256k1::new();
 
-        let derived_xprv = &self.xprv.derive_priv(&secp, &self.path)?;
-
-        let origin: KeySource = (self.xprv.fingerprint(&secp), self.path.clone());
-
         if self.xprv.network != ctx.network.into() {
-            return Err(Error::Generic(
+            return Err(Error::coded(
+                ErrorCode::NetworkMismatch,
                 "Extended key network does not match current network".to_string(),
             ));
         }
 
+        let derived_xprv = &self.xprv.derive_priv(&secp, &self.path)?;
+        let fingerprint = self.xprv.fingerprint(&secp);
+        let origin: KeySource = (fingerprint, self.path.clone());
+
         let derived_xprv_desc_key: DescriptorKey<Segwitv0> =
             derived_xprv.into_descriptor_key(Some(origin), DerivationPath::default())?;
 
@@ -108,13 +286,19 @@ impl AppCommand<AppContext<Init>> for DeriveKeyCommand {
             let desc_pubkey = desc_seckey.to_public(&secp)?;
 
             Ok(KeyResult {
-                xprv: desc_seckey.to_string(),
-                xpub: Some(desc_pubkey.to_string()),
+                xprv: derived_xprv.to_string(),
+                xpub: Some(Xpub::from_priv(&secp, derived_xprv).to_string()),
                 mnemonic: None,
-                fingerprint: None,
+                language: None,
+                shares: None,
+                fingerprint: Some(fingerprint.to_string()),
+                descriptor_key: Some(desc_pubkey.to_string()),
+                descriptor_secret_key: Some(desc_seckey.to_string()),
+                descriptors: None,
             })
         } else {
-            Err(Error::Generic(
+            Err(Error::coded(
+                ErrorCode::InvalidKey,
                 "Derived key is not a secret key".to_string(),
             ))
         }
@@ -124,11 +308,25 @@ impl AppCommand<AppContext<Init>> for DeriveKeyCommand {
 #[derive(Parser, Debug, Clone, PartialEq, Eq)]
 pub struct RestoreKeyCommand {
     /// Seed mnemonic words, must be quoted (eg. "word1 word2 ...").
-    #[arg(env = "MNEMONIC", short = 'm', long = "mnemonic")]
-    mnemonic: String,
-    /// Seed password.
-    #[arg(env = "PASSWORD", short = 'p', long = "password")]
-    password: Option<String>,
+    #[arg(
+        env = "MNEMONIC",
+        short = 'm',
+        long = "mnemonic",
+        required_unless_present = "slip39"
+    )]
+    mnemonic: Option<String>,
+    #[command(flatten)]
+    passphrase: PassphraseOpts,
+    /// Restores from SLIP-39 Shamir backup shares, each quoted, instead of a BIP39 mnemonic.
+    #[arg(long = "slip39", num_args = 1.., conflicts_with = "mnemonic")]
+    slip39: Vec<String>,
+    /// Wordlist of the mnemonic, detected from its words by default.
+    #[arg(long = "language", value_enum, conflicts_with = "slip39")]
+    language: Option<MnemonicLanguage>,
+    /// Also outputs the external and internal descriptors of this script type, on its BIP-44,
+    /// 49, 84 or 86 account path.
+    #[arg(long = "type", short = 't', value_parser = ["pkh", "wpkh", "sh", "wsh", "tr"])]
+    desc_type: Option<String>,
 }
 
 impl AppCommand<AppContext<Init>> for RestoreKeyCommand {
@@ -136,9 +334,29 @@ impl AppCommand<AppContext<Init>> for RestoreKeyCommand {
 
     fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
         let secp = Secp256k1::new();
+        let passphrase = read_passphrase(&self.passphrase, false)?;
 
-        let mnemonic = Mnemonic::parse_in(Language::English, &self.mnemonic)?;
-        let xkey: ExtendedKey = (mnemonic.clone(), &self.password).0.into_extended_key()?;
+        let Some(mnemonic) = &self.mnemonic else {
+            let master_secret =
+                slip39::combine(&self.slip39, passphrase.as_deref().unwrap_or_default())?;
+            let xprv = Xpriv::new_master(ctx.network, &master_secret)?;
+            return Ok(KeyResult {
+                xprv: xprv.to_string(),
+                mnemonic: None,
+                language: None,
+                shares: None,
+                fingerprint: Some(xprv.fingerprint(&secp).to_string()),
+                descriptor_key: None,
+                descriptor_secret_key: None,
+                descriptors: key_descriptors(self.desc_type.as_deref(), &xprv, ctx.network.into())?,
+                xpub: None,
+            });
+        };
+        let mnemonic = match self.language {
+            Some(language) => Mnemonic::parse_in(language.into(), mnemonic.as_str())?,
+            None => Mnemonic::parse(mnemonic.as_str())?,
+        };
+        let xkey: ExtendedKey = (mnemonic.clone(), passphrase).into_extended_key()?;
         let xprv = xkey.into_xprv(ctx.network.into()).ok_or_else(|| {
             Error::Generic("Privatekey info not found (should not happen)".to_string())
         })?;
@@ -147,8 +365,504 @@ impl AppCommand<AppContext<Init>> for RestoreKeyCommand {
         Ok(KeyResult {
             xprv: xprv.to_string(),
             mnemonic: Some(mnemonic.to_string()),
+            language: Some(MnemonicLanguage::from(mnemonic.language()).to_string()),
+            shares: None,
             fingerprint: Some(fingerprint.to_string()),
+            descriptor_key: None,
+            descriptor_secret_key: None,
+            descriptors: key_descriptors(self.desc_type.as_deref(), &xprv, ctx.network.into())?,
             xpub: None,
         })
     }
 }
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumCommand {
+    /// Output descriptor, with or without its checksum.
+    #[arg(env = "DESCRIPTOR")]
+    descriptor: String,
+}
+
+impl AppCommand<AppContext<Init>> for ChecksumCommand {
+    type Output = DescriptorCheck;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let input: String = self.descriptor.split_whitespace().collect();
+        let (body, given_checksum) = match input.split_once('#') {
+            Some((body, checksum)) => (body, Some(checksum)),
+            None => (input.as_str(), None),
+        };
+        let checksum = desc_checksum(body).map_err(|e| {
+            Error::coded(
+                ErrorCode::InvalidDescriptor,
+                format!("Invalid descriptor: {e}"),
+            )
+        })?;
+        if let Some(given_checksum) = given_checksum
+            && given_checksum != checksum
+        {
+            return Err(Error::coded(
+                ErrorCode::ChecksumMismatch,
+                format!(
+                    "Checksum mismatch: the descriptor ends with #{given_checksum} but its checksum \
+                 is #{checksum}. Check the descriptor for typos, or drop the checksum to \
+                 recompute it."
+                ),
+            ));
+        }
+        check_key_expressions(body)?;
+
+        let secp = Secp256k1::new();
+        let (descriptor, keymap) = Descriptor::parse_descriptor(&secp, body).map_err(|e| {
+            Error::coded(
+                ErrorCode::InvalidDescriptor,
+                format!("Invalid descriptor: {e}"),
+            )
+        })?;
+
+        let network = NetworkKind::from(ctx.network);
+        let mut warnings = Vec::new();
+        descriptor.for_each_key(|key| {
+            let (xkey, origin, wildcard) = match key {
+                DescriptorPublicKey::Single(_) => return true,
+                DescriptorPublicKey::XPub(xkey) => (xkey.xkey, &xkey.origin, xkey.wildcard),
+                DescriptorPublicKey::MultiXPub(xkey) => (xkey.xkey, &xkey.origin, xkey.wildcard),
+            };
+            if xkey.network != network {
+                warnings.push(format!(
+                    "Key {xkey} is for {}, not {}",
+                    network_name(xkey.network),
+                    network_name(network)
+                ));
+            }
+            if origin.is_none() {
+                warnings.push(format!(
+                    "Key {xkey} has no origin: add [fingerprint/path] so signers can recognise it"
+                ));
+            }
+            if wildcard == Wildcard::None {
+                warnings.push(format!(
+                    "Key {xkey} has no wildcard: the descriptor derives a single address"
+                ));
+            }
+            true
+        });
+        if !keymap.is_empty() {
+            warnings.push(
+                "The descriptor contains private keys: only share its public version".to_string(),
+            );
+        }
+
+        Ok(DescriptorCheck {
+            descriptor: format!("{body}#{checksum}"),
+            checksum,
+            canonical: if keymap.is_empty() {
+                descriptor.to_string()
+            } else {
+                descriptor.to_string_with_secret(&keymap)
+            },
+            script_type: script_type(descriptor.desc_type()).to_string(),
+            warnings,
+        })
+    }
+}
+
+/// Catch key mistakes the descriptor parser reports with obscure errors.
+fn check_key_expressions(descriptor: &str) -> Result<(), Error> {
+    for expression in descriptor.split(['(', ')', ',', '{', '}']) {
+        let key = match expression.split_once(']') {
+            Some((_, key)) => key,
+            None => expression,
+        };
+        let mut steps = key.split('/');
+        let xkey = steps.next().unwrap_or_default();
+        if [
+            "ypub", "zpub", "upub", "vpub", "Ypub", "Zpub", "Upub", "Vpub",
+        ]
+        .iter()
+        .any(|prefix| xkey.starts_with(prefix))
+        {
+            return Err(Error::coded(
+                ErrorCode::InvalidKey,
+                format!(
+                    "Key {xkey} uses a SLIP-132 prefix: convert it to an xpub or tpub, the script \
+                 type is given by the descriptor"
+                ),
+            ));
+        }
+        if (xkey.starts_with("xpub") || xkey.starts_with("tpub"))
+            && let Some(step) = steps.find(|step| step.ends_with(['h', 'H', '\'']))
+        {
+            return Err(Error::coded(
+                ErrorCode::InvalidKey,
+                format!(
+                    "Key {xkey} is followed by the hardened step {step}: hardened derivation needs \
+                 the private key. Derive the xpub at the hardened path with `key derive` and \
+                 put the path in the key origin instead"
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct InfoCommand {
+    /// Extended key (xprv or xpub, with or without origin) or output descriptor.
+    #[arg(env = "KEY")]
+    key: String,
+}
+
+impl AppCommand<AppContext<Init>> for InfoCommand {
+    type Output = KeyInfoResult;
+
+    fn execute(&self, _ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let input: String = self.key.split_whitespace().collect();
+        let secp = Secp256k1::new();
+
+        if !input.contains('(') {
+            let (key, is_private) = match DescriptorSecretKey::from_str(&input) {
+                Ok(secret_key) => (secret_key.to_public(&secp)?, true),
+                Err(_) => (
+                    DescriptorPublicKey::from_str(&input).map_err(|e| {
+                        Error::coded(ErrorCode::InvalidKey, format!("Invalid key: {e}"))
+                    })?,
+                    false,
+                ),
+            };
+            return Ok(KeyInfoResult {
+                descriptor: None,
+                keys: vec![key_info(&key, is_private)],
+            });
+        }
+
+        let (descriptor, keymap) = Descriptor::parse_descriptor(&secp, &input).map_err(|e| {
+            Error::coded(
+                ErrorCode::InvalidDescriptor,
+                format!("Invalid descriptor: {e}"),
+            )
+        })?;
+        let mut keys = Vec::new();
+        descriptor.for_each_key(|key| {
+            keys.push(key_info(key, keymap.contains_key(key)));
+            true
+        });
+        Ok(KeyInfoResult {
+            descriptor: Some(descriptor.to_string()),
+            keys,
+        })
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct TemplateCommand {
+    /// Master extended private key, or account extended public key with its origin
+    /// (eg. "[fingerprint/84'/0'/0']xpub...").
+    #[arg(env = "KEY")]
+    key: String,
+    /// Template to follow: 44 (pkh), 49 (sh-wpkh), 84 (wpkh) or 86 (tr).
+    #[arg(short = 'b', long = "bip", value_parser = ["44", "49", "84", "86"], default_value = "84")]
+    bip: String,
+    /// Account number.
+    #[arg(short = 'a', long = "account", default_value_t = 0)]
+    account: u32,
+    /// Fingerprint of the master key, for an account extended public key given without origin.
+    #[arg(short = 'f', long = "fingerprint")]
+    fingerprint: Option<Fingerprint>,
+}
+
+impl AppCommand<AppContext<Init>> for TemplateCommand {
+    type Output = DescriptorResult;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let bip = self.bip.parse().map_err(|_| {
+            Error::coded(
+                ErrorCode::InvalidArgument,
+                format!("Invalid template BIP-{}", self.bip),
+            )
+        })?;
+        template_descriptors(
+            bip,
+            self.account,
+            self.key.trim(),
+            self.fingerprint,
+            ctx.network.into(),
+        )
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct Bip85Command {
+    /// Master extended private key to derive from.
+    #[arg(env = "XPRV", short = 'x', long = "xprv")]
+    xprv: Xpriv,
+    /// Index of the child, each index giving unrelated entropy.
+    #[arg(short = 'i', long = "index", default_value_t = 0)]
+    index: u32,
+    /// What the entropy is derived for.
+    #[arg(short = 'a', long = "application", value_enum, default_value = "bip39")]
+    application: Application,
+    /// Number of words of the child mnemonic: 12, 15, 18, 21 or 24.
+    #[arg(long = "words", default_value_t = 12)]
+    words: usize,
+    /// Wordlist of the child mnemonic.
+    #[arg(long = "language", value_enum, default_value = "english")]
+    language: MnemonicLanguage,
+    /// Number of bytes of hex entropy, from 16 to 64.
+    #[arg(long = "length", default_value_t = 32)]
+    length: usize,
+}
+
+impl AppCommand<AppContext<Init>> for Bip85Command {
+    type Output = Bip85Result;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        if self.xprv.network != ctx.network.into() {
+            return Err(Error::coded(
+                ErrorCode::NetworkMismatch,
+                "Extended key network does not match current network".to_string(),
+            ));
+        }
+
+        let mut result = Bip85Result {
+            fingerprint: self.xprv.fingerprint(&Secp256k1::new()).to_string(),
+            path: String::new(),
+            mnemonic: None,
+            wif: None,
+            hex: None,
+        };
+        let path = match self.application {
+            Application::Bip39 => {
+                let (path, mnemonic) =
+                    bip85::mnemonic(&self.xprv, self.language.into(), self.words, self.index)?;
+                result.mnemonic = Some(mnemonic.to_string());
+                path
+            }
+            Application::Wif => {
+                let (path, key) = bip85::wif(&self.xprv, self.index)?;
+                result.wif = Some(key.to_wif());
+                path
+            }
+            Application::Hex => {
+                let (path, entropy) = bip85::hex(&self.xprv, self.length, self.index)?;
+                result.hex = Some(entropy.to_lower_hex_string());
+                path
+            }
+        };
+        result.path = full_path(&path, "");
+        Ok(result)
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct EncryptKeyCommand {
+    /// Wallet whose private keys are encrypted.
+    #[arg(env = "WALLET_NAME", short = 'w', long = "wallet")]
+    wallet: String,
+}
+
+impl AppCommand<AppContext<Init>> for EncryptKeyCommand {
+    type Output = StatusResult;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let mut config = WalletConfig::load(&ctx.datadir)?.ok_or_else(|| {
+            Error::coded(
+                ErrorCode::NoWalletConfig,
+                "No wallets configured yet.".to_string(),
+            )
+        })?;
+        let wallet_config = config
+            .wallets
+            .get_mut(&self.wallet)
+            .ok_or_else(|| Error::WalletNotFound(self.wallet.clone()))?;
+        let descriptors = WalletDescriptors {
+            ext_descriptor: wallet_config.ext_descriptor.clone(),
+            int_descriptor: wallet_config.int_descriptor.clone(),
+        };
+        let public = keystore::public_descriptors(&descriptors)?.ok_or_else(|| {
+            Error::coded(
+                ErrorCode::InvalidKey,
+                format!(
+                    "The descriptors of wallet '{}' hold no private key",
+                    self.wallet
+                ),
+            )
+        })?;
+
+        let password = read_key_password(true)?;
+        let path = keystore::save(&ctx.datadir, &self.wallet, &descriptors, &password)?;
+        wallet_config.ext_descriptor = public.ext_descriptor;
+        wallet_config.int_descriptor = public.int_descriptor;
+        config.save(&ctx.datadir)?;
+
+        Ok(StatusResult {
+            message: format!(
+                "Private keys of wallet '{}' encrypted in {path:?}",
+                self.wallet
+            ),
+        })
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct DecryptKeyCommand {
+    /// Wallet whose private keys are decrypted.
+    #[arg(env = "WALLET_NAME", short = 'w', long = "wallet")]
+    wallet: String,
+}
+
+impl AppCommand<AppContext<Init>> for DecryptKeyCommand {
+    type Output = StatusResult;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let mut config = WalletConfig::load(&ctx.datadir)?.ok_or_else(|| {
+            Error::coded(
+                ErrorCode::NoWalletConfig,
+                "No wallets configured yet.".to_string(),
+            )
+        })?;
+        let wallet_config = config
+            .wallets
+            .get_mut(&self.wallet)
+            .ok_or_else(|| Error::WalletNotFound(self.wallet.clone()))?;
+        let path = keystore::keystore_path(&ctx.datadir, &self.wallet);
+        if !path.exists() {
+            return Err(Error::coded(
+                ErrorCode::Keystore,
+                format!("Wallet '{}' has no keystore", self.wallet),
+            ));
+        }
+
+        let password = read_key_password(false)?;
+        let descriptors =
+            keystore::load(&ctx.datadir, &self.wallet, &password)?.ok_or_else(|| {
+                Error::coded(
+                    ErrorCode::Keystore,
+                    format!("Wallet '{}' has no keystore", self.wallet),
+                )
+            })?;
+        log::warn!("Private keys will be saved in PLAINTEXT in the config file.");
+        wallet_config.ext_descriptor = descriptors.ext_descriptor;
+        wallet_config.int_descriptor = descriptors.int_descriptor;
+        config.save(&ctx.datadir)?;
+        std::fs::remove_file(&path).map_err(|e| {
+            Error::coded(
+                ErrorCode::Keystore,
+                format!("Failed to remove keystore {path:?}: {e}"),
+            )
+        })?;
+
+        Ok(StatusResult {
+            message: format!(
+                "Private keys of wallet '{}' restored in {:?}",
+                self.wallet,
+                ctx.datadir.join("config.toml")
+            ),
+        })
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct HwiListCommand {
+    /// Path of the HWI executable.
+    #[arg(env = "HWI_PATH", long = "hwi_path", default_value = hwi::DEFAULT_HWI_PATH)]
+    hwi_path: String,
+}
+
+impl AppCommand<AppContext<Init>> for HwiListCommand {
+    type Output = ListResult<HwiDevice>;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        Ok(ListResult::new(
+            Hwi::new(&self.hwi_path, ctx.network).enumerate()?,
+        ))
+    }
+}
+
+fn key_info(key: &DescriptorPublicKey, is_private: bool) -> KeyInfo {
+    let (xkey, origin, derivation_paths, wildcard) = match key {
+        DescriptorPublicKey::Single(single) => {
+            return KeyInfo {
+                key: key.to_string(),
+                is_private,
+                master_fingerprint: single.origin.as_ref().map(|(fp, _)| fp.to_string()),
+                origin_path: single.origin.as_ref().map(|(_, path)| full_path(path, "")),
+                fingerprint: None,
+                depth: None,
+                parent_fingerprint: None,
+                child_number: None,
+                network: None,
+                derivation_paths: Vec::new(),
+            };
+        }
+        DescriptorPublicKey::XPub(xkey) => (
+            xkey.xkey,
+            &xkey.origin,
+            vec![&xkey.derivation_path],
+            xkey.wildcard,
+        ),
+        DescriptorPublicKey::MultiXPub(xkey) => (
+            xkey.xkey,
+            &xkey.origin,
+            xkey.derivation_paths.paths().iter().collect(),
+            xkey.wildcard,
+        ),
+    };
+
+    // Without an origin, only a depth 0 key is known to be the master key.
+    let master_fingerprint = match origin {
+        Some((fingerprint, _)) => Some(*fingerprint),
+        None if xkey.depth == 0 => Some(xkey.fingerprint()),
+        None => None,
+    };
+    let wildcard = match wildcard {
+        Wildcard::None => "",
+        Wildcard::Unhardened => "/*",
+        Wildcard::Hardened => "/*'",
+    };
+    KeyInfo {
+        key: key.to_string(),
+        is_private,
+        master_fingerprint: master_fingerprint.map(|fp| fp.to_string()),
+        origin_path: origin.as_ref().map(|(_, path)| full_path(path, "")),
+        fingerprint: Some(xkey.fingerprint().to_string()),
+        depth: Some(xkey.depth),
+        parent_fingerprint: Some(xkey.parent_fingerprint.to_string()),
+        child_number: Some(xkey.child_number.to_string()),
+        network: Some(network_name(xkey.network).to_string()),
+        derivation_paths: derivation_paths
+            .into_iter()
+            .map(|path| full_path(path, wildcard))
+            .collect(),
+    }
+}
+
+fn full_path(path: &DerivationPath, wildcard: &str) -> String {
+    match path.is_empty() {
+        true => format!("m{wildcard}"),
+        false => format!("m/{path}{wildcard}"),
+    }
+}
+
+fn script_type(desc_type: DescriptorType) -> &'static str {
+    match desc_type {
+        DescriptorType::Bare => "bare",
+        DescriptorType::Sh => "sh",
+        DescriptorType::Pkh => "pkh",
+        DescriptorType::Wpkh => "wpkh",
+        DescriptorType::Wsh => "wsh",
+        DescriptorType::ShWsh => "sh(wsh)",
+        DescriptorType::ShWpkh => "sh(wpkh)",
+        DescriptorType::ShSortedMulti => "sh(sortedmulti)",
+        DescriptorType::WshSortedMulti => "wsh(sortedmulti)",
+        DescriptorType::ShWshSortedMulti => "sh(wsh(sortedmulti))",
+        DescriptorType::Tr => "tr",
+    }
+}
+
+fn network_name(network: NetworkKind) -> &'static str {
+    match network {
+        NetworkKind::Main => "mainnet",
+        NetworkKind::Test => "test networks",
+    }
+}
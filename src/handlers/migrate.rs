@@ -0,0 +1,306 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Migration of bdk-cli 0.x wallet databases
+//!
+//! bdk-cli 0.x kept each wallet in a bdk 0.x sqlite or sled database. `wallet migrate` reads the
+//! revealed indices and the transactions of such a database into the database of a wallet
+//! configured with the same descriptors, so that a `sync` of the revealed addresses replaces a
+//! full scan. The old databases hold no block hashes, so the transactions stay unconfirmed until
+//! that `sync`, nor a network, which the checksums of their descriptors stand for.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::handlers::{AppCommand, AppContext, Init};
+use crate::persister::{Persister, new_persisted_wallet};
+use crate::utils::runtime::WalletRuntime;
+use crate::utils::snapshot::auto_snapshot;
+use crate::utils::types::{KeychainPair, MigrateResult};
+use bdk_wallet::KeychainKind;
+use bdk_wallet::bitcoin::{ScriptBuf, Transaction, consensus::deserialize};
+use bdk_wallet::descriptor::calc_checksum;
+use bdk_wallet::rusqlite::{Connection, OpenFlags};
+use clap::Args;
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct MigrateCommand {
+    /// The bdk-cli 0.x data directory, the directory of the wallet in it, or its sqlite file.
+    #[arg(long = "from", value_name = "DATADIR")]
+    pub(crate) from: PathBuf,
+
+    /// The name of the wallet in the old data directory. Defaults to `--wallet`.
+    #[arg(long = "from-wallet", value_name = "NAME")]
+    pub(crate) from_wallet: Option<String>,
+
+    /// The configured wallet receiving the migrated state, set from `--wallet`.
+    #[arg(skip)]
+    pub(crate) wallet: String,
+}
+
+/// What a bdk 0.x database holds of a wallet.
+#[derive(Default)]
+struct LegacyWallet {
+    last_index: BTreeMap<KeychainKind, u32>,
+    /// The script of index 0 of each keychain, to check the descriptors.
+    first_scripts: BTreeMap<KeychainKind, ScriptBuf>,
+    /// The checksum of the public descriptor of each keychain, whose keys tell the network.
+    checksums: BTreeMap<KeychainKind, String>,
+    txs: Vec<Transaction>,
+}
+
+impl AppCommand<AppContext<Init>> for MigrateCommand {
+    type Output = MigrateResult;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let runtime = WalletRuntime::load(&ctx.datadir, &self.wallet)?;
+        let from_wallet = self.from_wallet.as_deref().unwrap_or(&self.wallet);
+        let (source, legacy) = read_legacy(&self.from, from_wallet)?;
+
+        // A bdk-cli 0.x sqlite database may sit where the new one goes.
+        let db_path = runtime.database_path.join("wallet.sqlite");
+        if db_path.exists() && source.canonicalize().ok() == db_path.canonicalize().ok() {
+            let moved = db_path.with_extension("sqlite.v0");
+            fs::rename(&db_path, &moved).map_err(|e| {
+                Error::coded(
+                    ErrorCode::Io,
+                    format!("Cannot move the old database {db_path:?}: {e}"),
+                )
+            })?;
+            log::info!("Moved the bdk-cli 0.x database to {}", moved.display());
+        } else {
+            auto_snapshot(&runtime.database_path, "migrate")?;
+        }
+
+        let mut persister = Persister::open(
+            &runtime.wallet_opts,
+            &runtime.home_dir,
+            &runtime.database_path,
+            &runtime.wallet_name,
+        )?;
+        let mut wallet =
+            new_persisted_wallet(runtime.network, &mut persister, &runtime.wallet_opts)?;
+        for (keychain, script) in &legacy.first_scripts {
+            if wallet.peek_address(*keychain, 0).script_pubkey() != *script {
+                return Err(Error::coded(
+                    ErrorCode::InvalidDescriptor,
+                    format!(
+                        "The {keychain:?} addresses of {} differ from the ones of wallet '{}', \
+                     configure the wallet with the descriptors of the old wallet",
+                        source.display(),
+                        self.wallet
+                    ),
+                ));
+            }
+        }
+        // The scripts are the same on every network, the keys of the descriptors are not.
+        for (keychain, checksum) in &legacy.checksums {
+            let descriptor = wallet.public_descriptor(*keychain).to_string();
+            if !legacy_checksums(&descriptor).contains(checksum) {
+                return Err(Error::coded(
+                    ErrorCode::NetworkMismatch,
+                    format!(
+                        "The {keychain:?} descriptor of {} is not the one of wallet '{}' on {}, \
+                     the old wallet is on another network or has other descriptors",
+                        source.display(),
+                        self.wallet,
+                        runtime.network
+                    ),
+                ));
+            }
+        }
+        for (keychain, index) in &legacy.last_index {
+            let _ = wallet.reveal_addresses_to(*keychain, *index);
+        }
+        for tx in &legacy.txs {
+            wallet.insert_tx(tx.clone());
+        }
+        wallet.persist(&mut persister)?;
+
+        Ok(MigrateResult {
+            wallet: self.wallet.clone(),
+            from: source.display().to_string(),
+            last_revealed: KeychainPair {
+                external: legacy.last_index.get(&KeychainKind::External).copied(),
+                internal: legacy.last_index.get(&KeychainKind::Internal).copied(),
+            },
+            transactions: legacy.txs.len(),
+            message: "Run `sync` to confirm the migrated transactions".to_string(),
+        })
+    }
+}
+
+/// Find and read the bdk 0.x database of `wallet` at `from`.
+fn read_legacy(from: &Path, wallet: &str) -> Result<(PathBuf, LegacyWallet), Error> {
+    if from.is_file() {
+        return Ok((from.to_path_buf(), read_sqlite(from)?));
+    }
+    let dir = match from.join(wallet) {
+        dir if dir.is_dir() => dir,
+        _ => from.to_path_buf(),
+    };
+    let sqlite = dir.join("wallet.sqlite");
+    if sqlite.is_file() {
+        return Ok((sqlite.clone(), read_sqlite(&sqlite)?));
+    }
+    // sled databases are directories holding a `conf` and a `db` file.
+    if dir.join("conf").is_file() && dir.join("db").is_file() {
+        #[cfg(feature = "legacy-sled")]
+        return Ok((dir.clone(), read_sled(&dir, wallet)?));
+        #[cfg(not(feature = "legacy-sled"))]
+        return Err(Error::coded(
+            ErrorCode::Database,
+            format!(
+                "{} is a sled database, which needs a build with the legacy-sled feature",
+                dir.display()
+            ),
+        ));
+    }
+    Err(Error::coded(
+        ErrorCode::WalletNotFound,
+        format!(
+            "No bdk-cli 0.x database of wallet '{wallet}' found in {}",
+            from.display()
+        ),
+    ))
+}
+
+/// The keychain of a bdk 0.x sqlite row, stored as the JSON string `"External"` or `"Internal"`.
+fn legacy_keychain(keychain: &str) -> Option<KeychainKind> {
+    match keychain.trim_matches('"') {
+        "External" => Some(KeychainKind::External),
+        "Internal" => Some(KeychainKind::Internal),
+        _ => None,
+    }
+}
+
+/// The checksums `desc` may have had in bdk 0.x, which wrote its hardened steps with `'` or `h`.
+fn legacy_checksums(desc: &str) -> Vec<String> {
+    let desc = desc.split('#').next().unwrap_or(desc);
+    // A `h` after a digit and before the end of a step is a hardened step, not a key character.
+    let chars: Vec<char> = desc.chars().collect();
+    let apostrophes: String = chars
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let hardened = *c == 'h'
+                && i > 0
+                && chars[i - 1].is_ascii_digit()
+                && chars
+                    .get(i + 1)
+                    .is_none_or(|next| matches!(next, '/' | ']' | ')' | ','));
+            if hardened { '\'' } else { *c }
+        })
+        .collect();
+    [desc.to_string(), desc.replace('\'', "h"), apostrophes]
+        .iter()
+        .filter_map(|desc| calc_checksum(desc).ok())
+        .collect()
+}
+
+fn read_sqlite(path: &Path) -> Result<LegacyWallet, Error> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let is_legacy = connection
+        .prepare(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'last_derivation_indices'",
+        )?
+        .exists([])?;
+    if !is_legacy {
+        return Err(Error::coded(
+            ErrorCode::Database,
+            format!("{} is not a bdk-cli 0.x wallet database", path.display()),
+        ));
+    }
+
+    let mut legacy = LegacyWallet::default();
+    let mut statement =
+        connection.prepare("SELECT keychain, value FROM last_derivation_indices")?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        if let Some(keychain) = legacy_keychain(&row.get::<_, String>(0)?) {
+            legacy.last_index.insert(keychain, row.get(1)?);
+        }
+    }
+    let mut statement =
+        connection.prepare("SELECT keychain, script FROM script_pubkeys WHERE child = 0")?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        if let Some(keychain) = legacy_keychain(&row.get::<_, String>(0)?) {
+            let script = ScriptBuf::from_bytes(row.get(1)?);
+            legacy.first_scripts.insert(keychain, script);
+        }
+    }
+    let has_checksums = connection
+        .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'checksums'")?
+        .exists([])?;
+    if has_checksums {
+        let mut statement = connection.prepare("SELECT keychain, checksum FROM checksums")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            if let Some(keychain) = legacy_keychain(&row.get::<_, String>(0)?) {
+                let checksum = String::from_utf8_lossy(&row.get::<_, Vec<u8>>(1)?).into_owned();
+                legacy.checksums.insert(keychain, checksum);
+            }
+        }
+    }
+    // bdk 0.x left out the raw transactions of the wallets syncing without them, which the
+    // `sync` finds again.
+    let mut statement = connection.prepare("SELECT raw_tx FROM transactions")?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        if let Some(raw_tx) = row.get::<_, Option<Vec<u8>>>(0)? {
+            legacy.txs.push(deserialize(&raw_tx)?);
+        }
+    }
+    Ok(legacy)
+}
+
+/// Read the tree of `wallet` in a bdk 0.x sled database. Its keys are a prefix byte, `c` for the
+/// last index, `p` for the script of a derivation path, `d` for the descriptor checksum and `r`
+/// for a raw transaction, followed by `e` or `i` for the keychain and the big endian index, or by
+/// the txid.
+#[cfg(feature = "legacy-sled")]
+fn read_sled(path: &Path, wallet: &str) -> Result<LegacyWallet, Error> {
+    let sled_error =
+        |e: sled::Error| Error::coded(ErrorCode::Database, format!("Cannot read {path:?}: {e}"));
+    let db = sled::open(path).map_err(sled_error)?;
+    let tree = db.open_tree(wallet).map_err(sled_error)?;
+
+    let mut legacy = LegacyWallet::default();
+    for (keychain, byte) in [
+        (KeychainKind::External, b'e'),
+        (KeychainKind::Internal, b'i'),
+    ] {
+        if let Some(value) = tree.get([b'c', byte]).map_err(sled_error)? {
+            let index = <[u8; 4]>::try_from(value.as_ref()).map_err(|_| {
+                Error::coded(
+                    ErrorCode::Database,
+                    format!("Invalid last index in {path:?}"),
+                )
+            })?;
+            legacy
+                .last_index
+                .insert(keychain, u32::from_be_bytes(index));
+        }
+        let first_path = [[b'p', byte].as_slice(), &0u32.to_be_bytes()].concat();
+        if let Some(script) = tree.get(first_path).map_err(sled_error)? {
+            legacy.first_scripts.insert(keychain, deserialize(&script)?);
+        }
+        if let Some(checksum) = tree.get([b'd', byte]).map_err(sled_error)? {
+            let checksum = String::from_utf8_lossy(&checksum).into_owned();
+            legacy.checksums.insert(keychain, checksum);
+        }
+    }
+    for entry in tree.scan_prefix(b"r") {
+        let (_, raw_tx) = entry.map_err(sled_error)?;
+        legacy.txs.push(deserialize(&raw_tx)?);
+    }
+    Ok(legacy)
+}
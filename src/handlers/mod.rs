@@ -1,8 +1,36 @@
+#[cfg(any(feature = "electrum", feature = "esplora"))]
+pub mod backend;
+pub mod backup;
 pub mod config;
+pub mod contacts;
+#[cfg(all(
+    feature = "server",
+    any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    )
+))]
+pub mod daemon;
+#[cfg(feature = "sqlite")]
+pub mod database;
+#[cfg(any(feature = "sqlite", feature = "redb"))]
+pub mod debug;
 pub mod descriptor;
 #[cfg(feature = "dns_payment")]
 pub mod dns;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod init;
 pub mod key;
+#[cfg(feature = "sqlite")]
+pub mod migrate;
+pub mod multisig;
+#[cfg(feature = "rpc")]
+pub mod node;
+#[cfg(feature = "nwc")]
+pub mod nwc;
 pub mod offline;
 pub mod online;
 #[cfg(any(
@@ -12,7 +40,11 @@ pub mod online;
     feature = "rpc"
 ))]
 pub mod payjoin;
+pub mod psbt;
 pub mod repl;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod snapshot;
 
 #[cfg(any(
     feature = "electrum",
@@ -20,7 +52,7 @@ pub mod repl;
     feature = "rpc",
     feature = "cbf"
 ))]
-use crate::client::BlockchainClient;
+use crate::{client::BlockchainClient, persister::Persister, utils::EventFormat};
 use std::path::PathBuf;
 
 use crate::{error::BDKCliError as Error, utils::output::FormatOutput};
@@ -33,6 +65,8 @@ pub struct Init;
 /// Requires only a wallet.
 pub struct OfflineOperations<'a> {
     pub wallet: &'a mut Wallet,
+    pub wallet_name: String,
+    pub stop_gap: usize,
 }
 
 #[cfg(any(
@@ -47,6 +81,36 @@ pub struct OnlineOperations<'a> {
     pub wallet: &'a mut Wallet,
     pub client: &'a BlockchainClient,
     pub wallet_name: String,
+    pub stop_gap: usize,
+    /// How the commands syncing the wallet report its events.
+    pub events: EventFormat,
+    /// The persister of the wallet, for the commands persisting it as they go.
+    persister: Option<&'a mut Persister>,
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+impl OnlineOperations<'_> {
+    /// Persists the changes of the wallet so far, when the context was given its persister, as
+    /// the commands running until stopped do after each sync. The other changes are persisted
+    /// once the command is done.
+    pub(crate) fn persist(&mut self) -> Result<(), Error> {
+        let Some(persister) = self.persister.as_deref_mut() else {
+            return Ok(());
+        };
+        if self.wallet.staged().is_none() {
+            return Ok(());
+        }
+        if crate::utils::is_read_only() {
+            return Err(crate::utils::read_only_error());
+        }
+        let changeset = self.wallet.take_staged().unwrap_or_default();
+        bdk_wallet::WalletPersister::persist(persister, &changeset)
+    }
 }
 
 /// The generic context
@@ -68,11 +132,21 @@ impl AppContext<Init> {
 }
 
 impl<'a> AppContext<OfflineOperations<'a>> {
-    pub fn new_offline_wallet(network: Network, datadir: PathBuf, wallet: &'a mut Wallet) -> Self {
+    pub fn new_offline_wallet(
+        network: Network,
+        datadir: PathBuf,
+        wallet: &'a mut Wallet,
+        wallet_name: String,
+        stop_gap: usize,
+    ) -> Self {
         Self {
             network,
             datadir,
-            state: OfflineOperations { wallet },
+            state: OfflineOperations {
+                wallet,
+                wallet_name,
+                stop_gap,
+            },
         }
     }
 }
@@ -90,6 +164,7 @@ impl<'a> AppContext<OnlineOperations<'a>> {
         wallet: &'a mut Wallet,
         client: &'a BlockchainClient,
         wallet_name: String,
+        stop_gap: usize,
     ) -> Self {
         Self {
             network,
@@ -98,9 +173,18 @@ impl<'a> AppContext<OnlineOperations<'a>> {
                 wallet,
                 client,
                 wallet_name,
+                stop_gap,
+                events: EventFormat::default(),
+                persister: None,
             },
         }
     }
+
+    /// Gives the commands the persister of the wallet, see [`OnlineOperations::persist`].
+    pub(crate) fn with_persister(mut self, persister: Option<&'a mut Persister>) -> Self {
+        self.state.persister = persister;
+        self
+    }
 }
 
 pub trait AppCommand<C> {
@@ -0,0 +1,159 @@
+use crate::commands::{MultisigSubCommand, PsbtInputArgs};
+use crate::error::BDKCliError as Error;
+use crate::handlers::psbt::finalize_inputs;
+use crate::handlers::{AppCommand, AppContext, Init};
+use crate::multisig::{MultisigSession, cosigners};
+use crate::utils::{
+    output::FormatOutput,
+    types::{CosignerStatus, FinalizedPsbtResult, MultisigStatus, PsbtResult},
+    write_psbt_file,
+};
+use clap::Parser;
+use std::path::PathBuf;
+
+impl MultisigSubCommand {
+    pub fn execute(&self, ctx: &mut AppContext<Init>) -> Result<(), Error> {
+        match self {
+            MultisigSubCommand::Create(create_command) => {
+                create_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            MultisigSubCommand::Add(add_command) => {
+                add_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            MultisigSubCommand::Status(status_command) => {
+                status_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            MultisigSubCommand::Finalize(finalize_command) => {
+                finalize_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+        }
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct CreateSessionCommand {
+    /// Name of the session.
+    #[arg(env = "SESSION")]
+    pub session: String,
+
+    #[command(flatten)]
+    pub psbt: PsbtInputArgs,
+}
+
+impl AppCommand<AppContext<Init>> for CreateSessionCommand {
+    type Output = MultisigStatus;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let (psbt, version) = self.psbt.parse()?;
+        let session = MultisigSession::create(&ctx.datadir, &self.session, &psbt, version)?;
+        session.save()?;
+        session_status(&session)
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct AddSignedPsbtCommand {
+    /// Name of the session.
+    #[arg(env = "SESSION")]
+    pub session: String,
+
+    #[command(flatten)]
+    pub psbt: PsbtInputArgs,
+}
+
+impl AppCommand<AppContext<Init>> for AddSignedPsbtCommand {
+    type Output = MultisigStatus;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let (psbt, version) = self.psbt.parse()?;
+        let mut session = MultisigSession::load(&ctx.datadir, &self.session)?;
+        let signers = session.add(&psbt, version)?;
+        session.save()?;
+
+        log::info!(
+            "Added signatures of {} to session '{}'",
+            signers
+                .iter()
+                .map(|fingerprint| fingerprint.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.session
+        );
+        session_status(&session)
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct SessionStatusCommand {
+    /// Name of the session.
+    #[arg(env = "SESSION")]
+    pub session: String,
+}
+
+impl AppCommand<AppContext<Init>> for SessionStatusCommand {
+    type Output = MultisigStatus;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        session_status(&MultisigSession::load(&ctx.datadir, &self.session)?)
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct FinalizeSessionCommand {
+    /// Name of the session.
+    #[arg(env = "SESSION")]
+    pub session: String,
+
+    /// Also writes the finalized PSBT to a raw binary file.
+    #[arg(env = "PSBT_OUT_FILE", long = "out-file")]
+    pub out_file: Option<PathBuf>,
+}
+
+impl AppCommand<AppContext<Init>> for FinalizeSessionCommand {
+    type Output = FinalizedPsbtResult;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let mut session = MultisigSession::load(&ctx.datadir, &self.session)?;
+        let (mut psbt, version) = session.merged()?;
+        let inputs = finalize_inputs(&mut psbt);
+
+        // The session keeps the unfinalized PSBT until every input can be finalized.
+        let finalized = inputs.iter().all(|input| input.is_finalized);
+        if finalized {
+            session.set_merged(&psbt, version);
+            session.is_finalized = true;
+            session.save()?;
+            if let Some(path) = &self.out_file {
+                write_psbt_file(path, &psbt, version)?;
+            }
+        }
+
+        Ok(FinalizedPsbtResult {
+            psbt: PsbtResult::with_version(&psbt, Some(finalized), version),
+            inputs,
+        })
+    }
+}
+
+fn session_status(session: &MultisigSession) -> Result<MultisigStatus, Error> {
+    let (psbt, version) = session.merged()?;
+    let ready = finalize_inputs(&mut psbt.clone())
+        .iter()
+        .all(|input| input.is_finalized);
+
+    Ok(MultisigStatus {
+        session: session.name.clone(),
+        txid: session.txid.to_string(),
+        cosigners: cosigners(&psbt)
+            .into_iter()
+            .map(|(fingerprint, signed)| CosignerStatus {
+                fingerprint: fingerprint.to_string(),
+                signed,
+            })
+            .collect(),
+        contributions: session.contributions.len(),
+        ready,
+        is_finalized: session.is_finalized,
+        psbt: PsbtResult::with_version(&psbt, None, version).psbt,
+    })
+}
@@ -0,0 +1,700 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Regtest node
+//!
+//! `node` drives the regtest Bitcoin Core a wallet syncs from, or the one of `--node`: `mine`
+//! generates blocks, `fund` sends coins of the node wallet and `reorg` replaces the last blocks
+//! with a competing chain, each syncing the wallet after, which saves the test scripts the
+//! `bitcoin-cli` calls around each step. A wallet on a local electrum or esplora server following
+//! the node is synced once the server indexed the change.
+//!
+//! `wallet generate-test` configures a wallet of a known mnemonic on the node and funds it with
+//! the UTXOs of a preset, for the tests and tutorials that need a funded wallet to start with.
+
+use crate::client::{BlockchainClient, ClientType, rpc_endpoint};
+use crate::commands::{NodeSubCommand, WalletOpts};
+use crate::config::WalletConfigInner;
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::handlers::config::SaveConfigCommand;
+use crate::handlers::online::SyncCommand;
+use crate::handlers::{AppCommand, AppContext, AsyncAppCommand};
+use crate::utils::amount::parse_amount;
+use crate::utils::bitcoind::{node_rpc, parse_node};
+use crate::utils::descriptors::generate_descriptor_from_mnemonic;
+use crate::utils::output::FormatOutput;
+use crate::utils::parse_proxy_auth;
+use crate::utils::runtime::{RuntimeWallet, WalletRuntime};
+use crate::utils::types::{
+    GenerateTestResult, NodeFundResult, NodeMineResult, NodeReorgResult, ReorgedTransaction,
+};
+use bdk_bitcoind_rpc::bitcoincore_rpc::{Auth, Client, RpcApi};
+use bdk_wallet::bitcoin::{Address, Amount, Network, NetworkKind, Txid};
+use bdk_wallet::chain::ChainPosition;
+use bdk_wallet::{KeychainKind, Wallet};
+use clap::builder::RangedU64ValueParser;
+use clap::{Args, Parser, ValueEnum};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Name of the node wallet holding the mined coins, loaded or created when missing.
+const DEFAULT_NODE_WALLET: &str = "bdk-cli";
+
+/// Longest wait for the backend of the wallet to index the changes of the node.
+const BACKEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Interval between two checks of the backend of the wallet.
+const BACKEND_POLL: Duration = Duration::from_millis(200);
+
+/// Mnemonic of the `generate-test` wallets, the 12 word test vector of BIP-39.
+const TEST_MNEMONIC: &str =
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+/// Values of the UTXOs of the `generate-test` wallets, cycled through from dust-sized to large.
+const TEST_UTXO_VALUES: [u64; 6] = [1_000, 10_000, 50_000, 100_000, 1_000_000, 10_000_000];
+
+/// Fee rate in sat/vbyte of the transactions funding the `generate-test` wallets.
+const TEST_FEE_RATE: f32 = 2.0;
+
+/// Options selecting the regtest node.
+#[derive(Debug, Args, Clone, PartialEq, Eq)]
+pub struct NodeOpts {
+    /// Drives the Bitcoin Core of this data directory or `bitcoin.conf` file, the default data
+    /// directory when empty, instead of the node the wallet syncs from with the `rpc` backend.
+    #[arg(
+        env = "BITCOIN_NODE",
+        long = "node",
+        value_name = "DATADIR",
+        num_args = 0..=1,
+        default_missing_value = "",
+        value_parser = parse_node
+    )]
+    pub node: Option<PathBuf>,
+    /// Drives the Bitcoin Core rpc at this address, such as `127.0.0.1:18443`, with the
+    /// credentials of `--node-auth`.
+    #[arg(
+        env = "NODE_URL",
+        long = "node-url",
+        conflicts_with = "node",
+        requires = "node_auth"
+    )]
+    pub node_url: Option<String>,
+    /// Rpc credentials of `--node-url`.
+    #[arg(
+        env = "NODE_AUTH",
+        long = "node-auth",
+        value_name = "USER:PASSWD",
+        value_parser = parse_proxy_auth
+    )]
+    pub node_auth: Option<(String, String)>,
+    /// Node wallet paying `fund` and receiving the blocks mined without an address.
+    #[arg(
+        env = "NODE_WALLET",
+        long = "node-wallet",
+        default_value = DEFAULT_NODE_WALLET
+    )]
+    pub node_wallet: String,
+}
+
+impl NodeSubCommand {
+    pub async fn execute(
+        &self,
+        datadir: &Path,
+        wallet_name: &str,
+        opts: &NodeOpts,
+    ) -> Result<(), Error> {
+        let runtime = WalletRuntime::load(datadir, wallet_name)?;
+        if runtime.network != Network::Regtest {
+            return Err(Error::coded(
+                ErrorCode::NetworkMismatch,
+                format!(
+                    "The node commands only run on regtest, wallet '{wallet_name}' is on {}",
+                    runtime.network
+                ),
+            ));
+        }
+        let node = Node::connect(&runtime, opts)?;
+        let mut wallet = runtime.build_wallet(true)?;
+        match self {
+            NodeSubCommand::Mine(mine) => {
+                let mut result = mine.run(&node)?;
+                result.synced_height = sync(&runtime, &mut wallet, result.height, None).await?;
+                result.write_out(std::io::stdout())
+            }
+            NodeSubCommand::Fund(fund) => {
+                let (mut result, txid) = fund.run(&node)?;
+                // A confirmed transaction is found with its block.
+                let unconfirmed = (!result.confirmed).then_some(txid);
+                let height = node.client.get_block_count()?;
+                result.synced_height = sync(&runtime, &mut wallet, height, unconfirmed).await?;
+                result.write_out(std::io::stdout())
+            }
+            NodeSubCommand::Reorg(reorg) => {
+                // Synced first, the wallet knows the confirmations the reorg takes back.
+                let tip = node.client.get_block_count()?;
+                sync(&runtime, &mut wallet, tip, None).await?;
+                let before = confirmation_heights(&wallet);
+                let mut result = reorg.run(&node)?;
+                result.synced_height = sync(&runtime, &mut wallet, result.height, None).await?;
+                result.transactions = reorged_transactions(&before, &wallet, result.fork_height);
+                result.write_out(std::io::stdout())
+            }
+        }
+    }
+}
+
+/// A regtest Bitcoin Core, and its wallet holding the mined coins.
+pub(crate) struct Node {
+    pub client: Client,
+    pub wallet: Client,
+}
+
+impl Node {
+    /// Connects to the node of `opts`, or else to the node the wallet of `runtime` syncs from, and
+    /// loads the node wallet, creating it on first use.
+    pub(crate) fn connect(runtime: &WalletRuntime, opts: &NodeOpts) -> Result<Self, Error> {
+        let (url, auth) = match (&opts.node, &opts.node_url, &opts.node_auth) {
+            (Some(node), _, _) => {
+                let rpc = node_rpc(node, Network::Regtest)?;
+                (rpc.url, rpc.auth)
+            }
+            (None, Some(url), Some((user, password))) => {
+                (url.clone(), Auth::UserPass(user.clone(), password.clone()))
+            }
+            _ if runtime.wallet_opts.client_type == ClientType::Rpc => {
+                let rpc = rpc_endpoint(&runtime.wallet_opts, runtime.network)?;
+                (rpc.url, rpc.auth)
+            }
+            _ => {
+                return Err(Error::coded(
+                    ErrorCode::Config,
+                    format!(
+                        "Wallet '{}' does not sync from Bitcoin Core, pass the node with --node or --node-url",
+                        runtime.wallet_name
+                    ),
+                ));
+            }
+        };
+        let client = Client::new(&url, auth.clone())?;
+        if !client.list_wallets()?.contains(&opts.node_wallet) {
+            // A node wallet left unloaded by a restart of the node is loaded again.
+            if client.load_wallet(&opts.node_wallet).is_err() {
+                client.create_wallet(&opts.node_wallet, None, None, None, None)?;
+            }
+        }
+        let wallet = Client::new(&format!("{url}/wallet/{}", opts.node_wallet), auth)?;
+        Ok(Self { client, wallet })
+    }
+}
+
+/// Parses a regtest address.
+fn regtest_address(s: &str) -> Result<Address, Error> {
+    Ok(Address::from_str(s)?.require_network(Network::Regtest)?)
+}
+
+/// Waits until the backend of the wallet indexed the blocks of the node up to `height` and the
+/// transaction `txid`, as an electrum or esplora server lags behind the node it follows.
+async fn wait_for_backend(
+    client: &BlockchainClient,
+    height: u64,
+    txid: Option<Txid>,
+) -> Result<(), Error> {
+    let started = Instant::now();
+    loop {
+        let indexed = client
+            .tip_height()
+            .await?
+            .is_none_or(|tip| u64::from(tip) >= height);
+        let seen = match txid {
+            Some(txid) => client.has_tx(txid).await?.unwrap_or(true),
+            None => true,
+        };
+        if indexed && seen {
+            return Ok(());
+        }
+        if started.elapsed() > BACKEND_TIMEOUT {
+            log::warn!(
+                "The backend did not catch up with the node after {}s, syncing anyway.",
+                BACKEND_TIMEOUT.as_secs()
+            );
+            return Ok(());
+        }
+        tokio::time::sleep(BACKEND_POLL).await;
+    }
+}
+
+/// Syncs the wallet of `runtime` once its backend caught up with the node at `height` and saw
+/// `txid`, and persists it, returning its new height.
+pub(crate) async fn sync(
+    runtime: &WalletRuntime,
+    wallet: &mut RuntimeWallet,
+    height: u64,
+    txid: Option<Txid>,
+) -> Result<u32, Error> {
+    let client = runtime.build_client(wallet)?;
+    wait_for_backend(&client, height, txid).await?;
+    {
+        let mut ctx = AppContext::new_online_wallet(
+            runtime.network,
+            runtime.home_dir.clone(),
+            wallet,
+            &client,
+            runtime.wallet_name.clone(),
+            runtime.wallet_opts.stop_gap,
+        );
+        SyncCommand::quiet().execute(&mut ctx).await?;
+    }
+    wallet.persist()?;
+    Ok(wallet.latest_checkpoint().height())
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct NodeMineCommand {
+    /// Number of blocks to mine. The coinbase outputs can be spent after 100 more blocks.
+    #[arg(value_parser = RangedU64ValueParser::<u64>::new().range(1..))]
+    blocks: u64,
+    /// Address paid by the coinbase outputs, a new address of the node wallet when omitted.
+    address: Option<String>,
+}
+
+impl NodeMineCommand {
+    fn run(&self, node: &Node) -> Result<NodeMineResult, Error> {
+        let address = match &self.address {
+            Some(address) => regtest_address(address)?,
+            None => node
+                .wallet
+                .get_new_address(None, None)?
+                .require_network(Network::Regtest)?,
+        };
+        let block_hashes = node.client.generate_to_address(self.blocks, &address)?;
+        Ok(NodeMineResult {
+            address: address.to_string(),
+            block_hashes: block_hashes.iter().map(ToString::to_string).collect(),
+            height: node.client.get_block_count()?,
+            synced_height: 0,
+        })
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq)]
+pub struct NodeFundCommand {
+    /// Address to send the coins to.
+    address: String,
+    /// Amount to send, such as `150000sat` or `0.0015btc`.
+    #[arg(value_parser = parse_amount)]
+    amount: u64,
+    /// Fee rate to use in sat/vbyte, set so the node needs no fee estimates.
+    #[arg(
+        env = "SATS_VBYTE",
+        short = 'f',
+        long = "fee_rate",
+        default_value = "2.0"
+    )]
+    fee_rate: f32,
+    /// Mines a block after sending, confirming the transaction.
+    #[arg(long = "confirm")]
+    confirm: bool,
+}
+
+impl NodeFundCommand {
+    fn run(&self, node: &Node) -> Result<(NodeFundResult, Txid), Error> {
+        let address = regtest_address(&self.address)?;
+        let amount = Amount::from_sat(self.amount);
+        // Named `fee_rate`, positional after the 7 optional arguments before it.
+        let txid: Txid = node
+            .wallet
+            .call(
+                "sendtoaddress",
+                &[
+                    json!(address.to_string()),
+                    json!(amount.to_btc()),
+                    json!(null),
+                    json!(null),
+                    json!(null),
+                    json!(null),
+                    json!(null),
+                    json!(null),
+                    json!(null),
+                    json!(self.fee_rate),
+                ],
+            )
+            .map_err(|e| match e.to_string().contains("Insufficient funds") {
+                true => Error::coded(ErrorCode::InsufficientFunds, format!(
+                    "The node wallet cannot send {amount}, mine spendable coins to it first with `node mine 101`"
+                )),
+                false => e.into(),
+            })?;
+        let mut confirmed = false;
+        if self.confirm {
+            let address = node
+                .wallet
+                .get_new_address(None, None)?
+                .require_network(Network::Regtest)?;
+            node.client.generate_to_address(1, &address)?;
+            confirmed = true;
+        }
+        let result = NodeFundResult {
+            txid: txid.to_string(),
+            address: address.to_string(),
+            amount: self.amount,
+            confirmed,
+            synced_height: 0,
+        };
+        Ok((result, txid))
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct NodeReorgCommand {
+    /// Number of blocks invalidated at the tip of the node.
+    #[arg(
+        long = "depth",
+        value_parser = RangedU64ValueParser::<u64>::new().range(1..)
+    )]
+    depth: u64,
+    /// Number of blocks of the competing chain, one more than `--depth` by default so that it is
+    /// longer than the invalidated one.
+    #[arg(long = "blocks")]
+    blocks: Option<u64>,
+    /// Mines the competing chain without the transactions of the invalidated blocks, leaving them
+    /// unconfirmed in the mempool of the node.
+    #[arg(long = "empty")]
+    empty: bool,
+}
+
+impl NodeReorgCommand {
+    fn run(&self, node: &Node) -> Result<NodeReorgResult, Error> {
+        let blocks = self.blocks.unwrap_or(self.depth + 1);
+        if blocks <= self.depth {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "The competing chain must be longer than the {} invalidated blocks",
+                    self.depth
+                ),
+            ));
+        }
+        let tip = node.client.get_block_count()?;
+        if self.depth > tip {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "Cannot invalidate {} blocks of a chain of height {tip}",
+                    self.depth
+                ),
+            ));
+        }
+        let fork_height = tip - self.depth + 1;
+        let invalidated = (fork_height..=tip)
+            .map(|height| node.client.get_block_hash(height))
+            .collect::<Result<Vec<_>, _>>()?;
+        node.client.invalidate_block(&invalidated[0])?;
+        let block_hashes = match self.mine_competing_chain(node, blocks) {
+            Ok(block_hashes) => block_hashes,
+            Err(e) => {
+                // Back on the invalidated chain rather than left without it.
+                let _ = node.client.reconsider_block(&invalidated[0]);
+                return Err(e);
+            }
+        };
+        Ok(NodeReorgResult {
+            invalidated: invalidated.iter().map(ToString::to_string).collect(),
+            block_hashes,
+            fork_height: fork_height as u32,
+            height: node.client.get_block_count()?,
+            synced_height: 0,
+            transactions: Vec::new(),
+        })
+    }
+
+    /// Mines the `blocks` blocks of the competing chain, returning their hashes.
+    fn mine_competing_chain(&self, node: &Node, blocks: u64) -> Result<Vec<String>, Error> {
+        let address = node
+            .wallet
+            .get_new_address(None, None)?
+            .require_network(Network::Regtest)?;
+        if !self.empty {
+            return Ok(node
+                .client
+                .generate_to_address(blocks, &address)?
+                .iter()
+                .map(ToString::to_string)
+                .collect());
+        }
+        (0..blocks)
+            .map(|_| {
+                let block: Value = node
+                    .client
+                    .call("generateblock", &[json!(address.to_string()), json!([])])?;
+                block["hash"].as_str().map(str::to_string).ok_or_else(|| {
+                    Error::Generic(format!("The node mined a block without a hash: {block}"))
+                })
+            })
+            .collect()
+    }
+}
+
+/// The confirmation height of the transactions of the wallet, `None` when unconfirmed.
+fn confirmation_heights(wallet: &Wallet) -> BTreeMap<Txid, Option<u32>> {
+    wallet
+        .transactions()
+        .map(|tx| {
+            let height = match &tx.chain_position {
+                ChainPosition::Confirmed { anchor, .. } => Some(anchor.block_id.height),
+                ChainPosition::Unconfirmed { .. } => None,
+            };
+            (tx.tx_node.txid, height)
+        })
+        .collect()
+}
+
+/// What became of the transactions of the wallet confirmed at or above `fork_height` before a
+/// reorg: confirmed again or back to unconfirmed. The wallet keeps the transactions it no longer
+/// sees in a block as unconfirmed ones.
+fn reorged_transactions(
+    before: &BTreeMap<Txid, Option<u32>>,
+    wallet: &Wallet,
+    fork_height: u32,
+) -> Vec<ReorgedTransaction> {
+    let after = confirmation_heights(wallet);
+    before
+        .iter()
+        .filter_map(|(txid, height)| {
+            let height_before = height.filter(|height| *height >= fork_height)?;
+            let height_after = after.get(txid).copied().flatten();
+            let status = match height_after {
+                Some(_) => "reconfirmed",
+                None => "unconfirmed",
+            };
+            Some(ReorgedTransaction {
+                txid: txid.to_string(),
+                status: status.to_string(),
+                height_before,
+                height_after,
+            })
+        })
+        .collect()
+}
+
+/// UTXO layouts of `generate-test`.
+#[derive(Clone, Copy, Debug, ValueEnum, PartialEq, Eq)]
+pub enum TestPreset {
+    /// 5 UTXOs confirmed in 5 blocks, and 1 more unconfirmed.
+    Small,
+    /// 200 UTXOs confirmed in 20 blocks, and 10 more unconfirmed.
+    Large,
+}
+
+impl TestPreset {
+    /// The confirmed UTXOs, the blocks confirming them and the unconfirmed UTXOs.
+    fn layout(self) -> (usize, usize, usize) {
+        match self {
+            TestPreset::Small => (5, 5, 1),
+            TestPreset::Large => (200, 20, 10),
+        }
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq)]
+pub struct GenerateTestCommand {
+    /// Number and spread of the UTXOs of the wallet.
+    #[arg(long = "preset", value_enum, default_value_t = TestPreset::Small)]
+    preset: TestPreset,
+    /// Number of confirmed UTXOs, overriding the one of the preset.
+    #[arg(
+        long = "utxos",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    utxos: Option<usize>,
+    /// Mnemonic of the wallet, the `abandon ... about` test mnemonic by default.
+    #[arg(long = "mnemonic", default_value = TEST_MNEMONIC)]
+    mnemonic: String,
+    /// Descriptor type (script type)
+    #[arg(
+        long = "type",
+        short = 't',
+        value_parser = ["pkh", "wpkh", "sh", "wsh", "tr"],
+        default_value = "tr"
+    )]
+    desc_type: String,
+    /// Overwrites the config of an existing wallet of the same name, which has no database yet.
+    #[arg(short = 'f', long = "force")]
+    force: bool,
+    #[command(flatten)]
+    node: NodeOpts,
+}
+
+impl GenerateTestCommand {
+    pub async fn execute(
+        &self,
+        datadir: &Path,
+        wallet_name: &str,
+        network: Network,
+    ) -> Result<GenerateTestResult, Error> {
+        if network != Network::Regtest {
+            return Err(Error::coded(
+                ErrorCode::NetworkMismatch,
+                "generate-test only creates regtest wallets, pass --network regtest".to_string(),
+            ));
+        }
+        self.save_config(datadir, wallet_name, network)?;
+        let runtime = WalletRuntime::load(datadir, wallet_name)?;
+        let node = Node::connect(&runtime, &self.node)?;
+        let mut wallet = runtime.build_wallet(true)?;
+
+        let (preset_utxos, blocks, unconfirmed) = self.preset.layout();
+        let confirmed = self.utxos.unwrap_or(preset_utxos);
+        let outputs: Vec<(Address, u64)> = (0..confirmed + unconfirmed)
+            .map(|i| {
+                let address = wallet.reveal_next_address(KeychainKind::External).address;
+                (address, TEST_UTXO_VALUES[i % TEST_UTXO_VALUES.len()])
+            })
+            .collect();
+        wallet.persist()?;
+        let total: u64 = outputs.iter().map(|(_, value)| value).sum();
+
+        // The node wallet spends matured coinbase outputs, mined first when it has too few.
+        let miner = node
+            .wallet
+            .get_new_address(None, None)?
+            .require_network(Network::Regtest)?;
+        let needed = Amount::from_sat(total) + Amount::ONE_BTC;
+        if node.wallet.get_balance(None, None)? < needed {
+            node.client.generate_to_address(101, &miner)?;
+        }
+        while node.wallet.get_balance(None, None)? < needed {
+            node.client.generate_to_address(1, &miner)?;
+        }
+
+        let (confirmed_outputs, unconfirmed_outputs) = outputs.split_at(confirmed);
+        let mut txids = Vec::new();
+        for chunk in confirmed_outputs.chunks(confirmed.div_ceil(blocks)) {
+            txids.push(send_many(&node, chunk)?);
+            node.client.generate_to_address(1, &miner)?;
+        }
+        let mut last_unconfirmed = None;
+        if !unconfirmed_outputs.is_empty() {
+            let txid = send_many(&node, unconfirmed_outputs)?;
+            txids.push(txid);
+            last_unconfirmed = Some(txid);
+        }
+
+        let height = node.client.get_block_count()?;
+        let synced_height = sync(&runtime, &mut wallet, height, last_unconfirmed).await?;
+        Ok(GenerateTestResult {
+            wallet: wallet_name.to_string(),
+            mnemonic: self.mnemonic.clone(),
+            utxos: outputs.len(),
+            unconfirmed: unconfirmed_outputs.len(),
+            txids: txids.iter().map(ToString::to_string).collect(),
+            total,
+            synced_height,
+        })
+    }
+
+    /// Saves the config of the wallet, with the descriptors of the mnemonic and the node of
+    /// `--node` or `--node-url` as its rpc backend.
+    fn save_config(
+        &self,
+        datadir: &Path,
+        wallet_name: &str,
+        network: Network,
+    ) -> Result<(), Error> {
+        let descriptors = generate_descriptor_from_mnemonic(
+            &self.mnemonic,
+            NetworkKind::Test,
+            &self.desc_type,
+            "",
+        )?;
+        let private = descriptors
+            .private_descriptors
+            .ok_or_else(|| Error::Generic("No descriptors for the mnemonic".to_string()))?;
+        let mut wallet_config = WalletConfigInner {
+            wallet: wallet_name.to_string(),
+            network: network.to_string(),
+            ext_descriptor: private.external,
+            int_descriptor: Some(private.internal),
+            #[cfg(feature = "sqlite")]
+            database_type: "sqlite".to_string(),
+            #[cfg(all(feature = "redb", not(feature = "sqlite")))]
+            database_type: "redb".to_string(),
+            client_type: Some("rpc".to_string()),
+            server_url: Some(String::new()),
+            ..Default::default()
+        };
+        match (&self.node.node, &self.node.node_url, &self.node.node_auth) {
+            (Some(node), _, _) => wallet_config.node = Some(node.display().to_string()),
+            (None, Some(url), Some((user, password))) => {
+                wallet_config.server_url = Some(url.clone());
+                wallet_config.rpc_user = Some(user.clone());
+                wallet_config.rpc_password = Some(password.clone());
+            }
+            _ => {
+                return Err(Error::coded(
+                    ErrorCode::InvalidArgument,
+                    "Pass the regtest node with --node, or with --node-url and --node-auth"
+                        .to_string(),
+                ));
+            }
+        }
+        let wallet_opts = WalletOpts::try_from(&wallet_config)?;
+
+        // The database of an earlier wallet of the same name would be funded again from its
+        // last revealed address.
+        #[cfg(any(feature = "sqlite", feature = "redb"))]
+        if crate::persister::has_wallet(&wallet_opts, datadir, wallet_name)? {
+            return Err(Error::coded(
+                ErrorCode::WalletExists,
+                format!(
+                    "Wallet '{wallet_name}' already has a database, remove it or pick another name"
+                ),
+            ));
+        }
+
+        let config = SaveConfigCommand {
+            force: self.force,
+            encrypt: false,
+            offline: false,
+            explorer_url: None,
+            unit: None,
+            wallet_opts,
+        };
+        let status = config.execute(&mut AppContext::new(network, datadir.to_path_buf()))?;
+        log::info!("{}", status.message);
+        Ok(())
+    }
+}
+
+/// Sends the `outputs` from the node wallet in one transaction.
+fn send_many(node: &Node, outputs: &[(Address, u64)]) -> Result<Txid, Error> {
+    let amounts: serde_json::Map<String, Value> = outputs
+        .iter()
+        .map(|(address, value)| {
+            let btc = Amount::from_sat(*value).to_btc();
+            (address.to_string(), json!(btc))
+        })
+        .collect();
+    // `fee_rate` comes after the 6 optional arguments of `sendmany` before it.
+    Ok(node.wallet.call(
+        "sendmany",
+        &[
+            json!(""),
+            Value::Object(amounts),
+            json!(null),
+            json!(null),
+            json!(null),
+            json!(null),
+            json!(null),
+            json!(null),
+            json!(TEST_FEE_RATE),
+        ],
+    )?)
+}
@@ -0,0 +1,1023 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Nostr Wallet Connect
+//!
+//! `wallet nwc connect` creates a [NIP-47] connection to the wallet, a `nostr+walletconnect://`
+//! URI for a Nostr app holding the secret key of the app, and `daemon --nwc` answers the
+//! requests of the apps, sent to the relay of their connection as encrypted direct messages.
+//!
+//! This is the on-chain variant of NIP-47: `make_invoice` answers with a [BIP-21] URI of a new
+//! address, `pay_invoice` pays a BIP-21 URI or an address, answering with the txid in place of a
+//! preimage, and `get_balance` is the confirmed and trusted pending balance. The amounts of the
+//! requests and replies are in millisatoshis, as NIP-47 requires. The payments of a connection,
+//! their on-chain fees included, are limited by its budget, which resets every period.
+//!
+//! The connections are stored in `nwc.json` in the directory of the wallet, along with the
+//! secret key the wallet answers with. Its changes are made under the lock of `nwc.lock`, and a
+//! payment is charged to its budget before it is signed, so that the payments made at once, or a
+//! connection revoked meanwhile, never exceed the budget.
+//!
+//! [NIP-47]: https://github.com/nostr-protocol/nips/blob/master/47.md
+//! [BIP-21]: https://github.com/bitcoin/bips/blob/master/bip-0021.mediawiki
+
+use crate::commands::WalletNwcSubCommand;
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::handlers::contacts::parse_contact_name;
+use crate::handlers::{AppCommand, AppContext, OfflineOperations};
+use crate::utils::amount;
+use crate::utils::output::{FormatOutput, ListResult};
+use crate::utils::types::{NwcConnectionDetails, NwcConnectionResult};
+use clap::{Parser, ValueEnum};
+use nostr_sdk::{Keys, ToBech32};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+use {
+    crate::commands::{OfflineWalletSubCommand, WalletSubCommand},
+    crate::handlers::offline::{BalanceCommand, NewAddressCommand, SignCommand},
+    crate::handlers::server::{Runner, broadcast_command, create_tx_command, offline_command},
+    crate::utils::auth::Scope,
+    crate::utils::parse_psbt_with_version,
+    nostr_sdk::nips::nip04,
+    nostr_sdk::{Client, Event, EventBuilder, Filter, Kind, RelayPoolNotification, Tag, Timestamp},
+    serde_json::{Value, json},
+    std::sync::Arc,
+    tokio::sync::broadcast::error::RecvError,
+};
+
+/// The file of the connections, in the directory of the wallet.
+const NWC_FILE: &str = "nwc.json";
+
+/// The lock of the changes of [`NWC_FILE`], next to it.
+const NWC_LOCK_FILE: &str = "nwc.lock";
+
+/// The NIP-47 methods answered.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+const METHODS: [&str; 4] = ["get_info", "get_balance", "make_invoice", "pay_invoice"];
+
+impl WalletNwcSubCommand {
+    pub fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<(), Error> {
+        match self {
+            Self::Connect(connect_command) => {
+                connect_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            Self::List(list_command) => list_command.execute(ctx)?.write_out(std::io::stdout()),
+            Self::Revoke(revoke_command) => {
+                revoke_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+        }
+    }
+}
+
+/// How often the budget of a connection resets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetPeriod {
+    #[default]
+    Daily,
+    Weekly,
+    Monthly,
+    /// Never, the budget being the total of the payments.
+    Total,
+}
+
+impl BudgetPeriod {
+    fn seconds(self) -> Option<u64> {
+        match self {
+            BudgetPeriod::Daily => Some(24 * 3600),
+            BudgetPeriod::Weekly => Some(7 * 24 * 3600),
+            BudgetPeriod::Monthly => Some(30 * 24 * 3600),
+            BudgetPeriod::Total => None,
+        }
+    }
+}
+
+/// A connection of a Nostr app.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct NwcConnection {
+    name: String,
+    /// Public key of the app, the author of its requests.
+    client_pubkey: String,
+    relay: String,
+    /// Satoshis the app may pay each period.
+    budget_sat: u64,
+    period: BudgetPeriod,
+    /// Satoshis paid since `period_start`.
+    spent_sat: u64,
+    period_start: u64,
+}
+
+impl NwcConnection {
+    /// The satoshis left to pay at `now`, the budget being reset when its period ended.
+    #[cfg_attr(
+        not(any(
+            feature = "electrum",
+            feature = "esplora",
+            feature = "rpc",
+            feature = "cbf"
+        )),
+        allow(dead_code)
+    )]
+    fn remaining(&mut self, now: u64) -> u64 {
+        if let Some(seconds) = self.period.seconds()
+            && now.saturating_sub(self.period_start) >= seconds
+        {
+            self.spent_sat = 0;
+            self.period_start = now;
+        }
+        self.budget_sat.saturating_sub(self.spent_sat)
+    }
+
+    /// Fails when paying `sats` at `now`, the fee included, would exceed the budget.
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    fn check_budget(&mut self, sats: u64, now: u64) -> Result<(), NwcError> {
+        if sats > self.remaining(now) {
+            return Err(NwcError::new(
+                "QUOTA_EXCEEDED",
+                "The payment and its fee exceed the budget of the connection",
+            ));
+        }
+        Ok(())
+    }
+
+    fn details(&self) -> NwcConnectionDetails {
+        NwcConnectionDetails {
+            name: self.name.clone(),
+            relay: self.relay.clone(),
+            budget: self.budget_sat,
+            period: format!("{:?}", self.period).to_lowercase(),
+            spent: self.spent_sat,
+        }
+    }
+}
+
+/// The `nwc.json` of a wallet.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct NwcStore {
+    /// Secret key the wallet answers with, in hex, generated with the first connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    service_secret: Option<String>,
+    #[serde(default)]
+    connections: Vec<NwcConnection>,
+}
+
+fn nwc_path(datadir: &Path, wallet: &str) -> PathBuf {
+    datadir.join(wallet).join(NWC_FILE)
+}
+
+impl NwcStore {
+    fn load(datadir: &Path, wallet: &str) -> Result<Self, Error> {
+        let path = nwc_path(datadir, wallet);
+        if !path.exists() {
+            return Ok(NwcStore::default());
+        }
+        let content = fs::read_to_string(&path).map_err(|e| {
+            Error::coded(
+                ErrorCode::Io,
+                format!("Cannot read {}: {e}", path.display()),
+            )
+        })?;
+        serde_json::from_str(&content).map_err(|e| {
+            Error::coded(
+                ErrorCode::Config,
+                format!("Invalid {}: {e}", path.display()),
+            )
+        })
+    }
+
+    /// Writes the store, readable by its owner only as it holds a secret key.
+    fn save(&self, datadir: &Path, wallet: &str) -> Result<(), Error> {
+        let path = nwc_path(datadir, wallet);
+        let temp = path.with_extension("json.tmp");
+        let write = || -> std::io::Result<()> {
+            fs::create_dir_all(datadir.join(wallet))?;
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            let mut file = options.open(&temp)?;
+            file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+            file.sync_all()?;
+            fs::rename(&temp, &path)
+        };
+        write().map_err(|e| {
+            Error::coded(
+                ErrorCode::Io,
+                format!("Cannot write {}: {e}", path.display()),
+            )
+        })
+    }
+
+    /// Applies `change` to the store of `wallet` and saves it, the store being locked from its
+    /// load to its save so that the changes of the commands and of the daemon are never lost.
+    /// Nothing is saved when `change` fails.
+    fn update<T, E: From<Error>>(
+        datadir: &Path,
+        wallet: &str,
+        change: impl FnOnce(&mut NwcStore) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let path = datadir.join(wallet).join(NWC_LOCK_FILE);
+        let _lock = fs::create_dir_all(datadir.join(wallet))
+            .and_then(|_| {
+                fs::OpenOptions::new()
+                    .create(true)
+                    .truncate(false)
+                    .write(true)
+                    .open(&path)
+            })
+            .and_then(|file| file.lock().map(|_| file))
+            .map_err(|e| {
+                Error::coded(
+                    ErrorCode::Io,
+                    format!("Cannot lock {}: {e}", path.display()),
+                )
+            })?;
+        let mut store = Self::load(datadir, wallet)?;
+        let result = change(&mut store)?;
+        store.save(datadir, wallet)?;
+        Ok(result)
+    }
+
+    fn service_keys(&mut self) -> Result<Keys, Error> {
+        match &self.service_secret {
+            Some(secret) => Keys::parse(secret).map_err(nostr_error),
+            None => {
+                let keys = Keys::generate();
+                self.service_secret = Some(keys.secret_key().to_secret_hex());
+                Ok(keys)
+            }
+        }
+    }
+
+    fn list(&self) -> ListResult<NwcConnectionDetails> {
+        ListResult::new(
+            self.connections
+                .iter()
+                .map(NwcConnection::details)
+                .collect(),
+        )
+    }
+}
+
+fn nostr_error(e: impl std::fmt::Display) -> Error {
+    Error::Generic(format!("Nostr error: {e}"))
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Percent-encodes `s` for a query parameter of a URI.
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[derive(Parser, Debug, Clone, PartialEq)]
+pub struct NwcConnectCommand {
+    /// Name of the connection, such as the app it is for.
+    #[arg(long = "name", value_parser = parse_contact_name)]
+    pub name: String,
+    /// Relay the app sends its requests to, such as `wss://relay.damus.io`.
+    #[arg(env = "NWC_RELAY", long = "relay")]
+    pub relay: String,
+    /// Amount the app may pay each period, such as `50000sat`. Without it, the app can only read
+    /// the balance and request addresses.
+    #[arg(long = "budget", value_parser = amount::parse_amount)]
+    pub budget: Option<u64>,
+    /// How often the budget resets.
+    #[arg(long = "budget-period", value_enum, default_value_t = BudgetPeriod::Daily)]
+    pub budget_period: BudgetPeriod,
+}
+
+impl AppCommand<AppContext<OfflineOperations<'_>>> for NwcConnectCommand {
+    type Output = NwcConnectionResult;
+
+    fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        if !self.relay.starts_with("wss://") && !self.relay.starts_with("ws://") {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                format!("Invalid relay '{}', expected a wss:// url", self.relay),
+            ));
+        }
+        let client = Keys::generate();
+        let service = NwcStore::update(&ctx.datadir, &ctx.state.wallet_name, |store| {
+            if store.connections.iter().any(|c| c.name == self.name) {
+                return Err(Error::coded(
+                    ErrorCode::InvalidArgument,
+                    format!(
+                        "The connection '{}' already exists, revoke it first",
+                        self.name
+                    ),
+                ));
+            }
+            let service = store.service_keys()?;
+            store.connections.push(NwcConnection {
+                name: self.name.clone(),
+                client_pubkey: client.public_key().to_hex(),
+                relay: self.relay.clone(),
+                budget_sat: self.budget.unwrap_or(0),
+                period: self.budget_period,
+                spent_sat: 0,
+                period_start: unix_now(),
+            });
+            Ok(service)
+        })?;
+
+        let uri = format!(
+            "nostr+walletconnect://{}?relay={}&secret={}",
+            service.public_key().to_hex(),
+            percent_encode(&self.relay),
+            client.secret_key().to_secret_hex(),
+        );
+        Ok(NwcConnectionResult {
+            name: self.name.clone(),
+            uri,
+            wallet_pubkey: service.public_key().to_bech32().map_err(nostr_error)?,
+        })
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq)]
+pub struct NwcListCommand;
+
+impl AppCommand<AppContext<OfflineOperations<'_>>> for NwcListCommand {
+    type Output = ListResult<NwcConnectionDetails>;
+
+    fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        Ok(NwcStore::load(&ctx.datadir, &ctx.state.wallet_name)?.list())
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq)]
+pub struct NwcRevokeCommand {
+    /// Name of the connection to revoke. Its app can no longer reach the wallet.
+    #[arg(long = "name", value_parser = parse_contact_name)]
+    pub name: String,
+}
+
+impl AppCommand<AppContext<OfflineOperations<'_>>> for NwcRevokeCommand {
+    type Output = ListResult<NwcConnectionDetails>;
+
+    fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        NwcStore::update(&ctx.datadir, &ctx.state.wallet_name, |store| {
+            let count = store.connections.len();
+            store.connections.retain(|c| c.name != self.name);
+            if store.connections.len() == count {
+                return Err(Error::coded(
+                    ErrorCode::InvalidArgument,
+                    format!("Unknown connection '{}'", self.name),
+                ));
+            }
+            Ok(store.list())
+        })
+    }
+}
+
+/// A NIP-47 error, with its code.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+struct NwcError {
+    code: &'static str,
+    message: String,
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+impl NwcError {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        NwcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+impl From<Error> for NwcError {
+    fn from(e: Error) -> Self {
+        NwcError::new("INTERNAL", e.to_string())
+    }
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+#[derive(Deserialize)]
+struct NwcRequest {
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// The address and the amount in satoshis of a BIP-21 URI, or of a bare address.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+fn parse_invoice(invoice: &str) -> Result<(String, Option<u64>), String> {
+    let invoice = invoice.trim();
+    let uri = match invoice.split_once(':') {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("bitcoin") => rest,
+        Some(_) => return Err(format!("Unsupported invoice '{invoice}'")),
+        None => invoice,
+    };
+    let (address, query) = uri.split_once('?').unwrap_or((uri, ""));
+    if address.is_empty() {
+        return Err("The invoice has no address".to_string());
+    }
+    let mut amount = None;
+    for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+        if key == "amount" {
+            let sats = amount::parse_amount(&format!("{value}btc"))?;
+            amount = Some(sats);
+        }
+    }
+    Ok((address.to_string(), amount))
+}
+
+/// A sat amount of a command output, a number of satoshis or a string of bitcoins with
+/// `--unit btc`.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+fn sats(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(number) => number.as_u64(),
+        Value::String(btc) => amount::parse_amount(&format!("{btc}btc")).ok(),
+        _ => None,
+    }
+}
+
+/// Runs the `wallet` subcommand `command` on `wallet`.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+async fn run(
+    runner: &Arc<Runner>,
+    wallet: &str,
+    command: WalletSubCommand,
+) -> Result<Value, NwcError> {
+    runner
+        .clone()
+        .run(
+            wallet.to_string(),
+            command,
+            Scope::Spend,
+            #[cfg(feature = "grpc")]
+            None,
+        )
+        .await
+        .map_err(|e| match e.error.code() {
+            ErrorCode::InsufficientFunds => {
+                NwcError::new("INSUFFICIENT_BALANCE", e.error.to_string())
+            }
+            _ => NwcError::new("INTERNAL", e.error.to_string()),
+        })
+}
+
+/// Charges `sats` to the budget of the connection of `client_pubkey`, failing when they exceed
+/// it or when the connection was revoked.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+fn reserve(datadir: &Path, wallet: &str, client_pubkey: &str, sats: u64) -> Result<(), NwcError> {
+    NwcStore::update(datadir, wallet, |store| {
+        let connection = store
+            .connections
+            .iter_mut()
+            .find(|connection| connection.client_pubkey == client_pubkey)
+            .ok_or_else(|| NwcError::new("UNAUTHORIZED", "The connection was revoked"))?;
+        connection.check_budget(sats, unix_now())?;
+        connection.spent_sat += sats;
+        Ok(())
+    })
+}
+
+/// Gives back to the connection of `client_pubkey` the `sats` of [`reserve`], for a payment that
+/// was not made.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+fn refund(datadir: &Path, wallet: &str, client_pubkey: &str, sats: u64) {
+    let refunded = NwcStore::update(datadir, wallet, |store| {
+        let connection = store
+            .connections
+            .iter_mut()
+            .find(|connection| connection.client_pubkey == client_pubkey);
+        if let Some(connection) = connection {
+            connection.spent_sat = connection.spent_sat.saturating_sub(sats);
+        }
+        Ok::<_, Error>(())
+    });
+    if let Err(e) = refunded {
+        log::warn!("{wallet}: cannot give back {sats} sat to a Nostr Wallet Connect budget: {e}");
+    }
+}
+
+/// Signs and broadcasts the payment `psbt`, answering with its txid.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+async fn pay(runner: &Arc<Runner>, wallet: &str, psbt: String) -> Result<Value, NwcError> {
+    let sign = OfflineWalletSubCommand::Sign(SignCommand::of(psbt));
+    let signed = run(runner, wallet, offline_command(sign)).await?;
+    if signed["is_finalized"] != Value::Bool(true) {
+        return Err(NwcError::new(
+            "RESTRICTED",
+            "The wallet cannot sign the payment alone",
+        ));
+    }
+    let psbt = signed["psbt"].as_str().unwrap_or_default().to_string();
+    // The spend scope of the connection stands for the confirmation of the broadcast.
+    let broadcast = broadcast_command(Some(psbt), None)
+        .map_err(|e| NwcError::new("INTERNAL", e.error.to_string()))?;
+    let sent = run(runner, wallet, broadcast).await?;
+    Ok(sent["txid"].clone())
+}
+
+/// Answers `request` of `connection`, a copy of the connection in the store of `wallet`.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+async fn handle(
+    runner: &Arc<Runner>,
+    datadir: &Path,
+    wallet: &str,
+    connection: &mut NwcConnection,
+    request: &NwcRequest,
+) -> Result<Value, NwcError> {
+    let msat = |field: &str| request.params.get(field).and_then(Value::as_u64);
+    match request.method.as_str() {
+        "get_info" => Ok(json!({"alias": "bdk-cli", "methods": METHODS})),
+        "get_balance" => {
+            let command = OfflineWalletSubCommand::Balance(BalanceCommand::default());
+            let balance = run(runner, wallet, offline_command(command)).await?;
+            let confirmed = balance.get("confirmed").and_then(sats);
+            let pending = balance.get("trusted_pending").and_then(sats);
+            let (Some(confirmed), Some(pending)) = (confirmed, pending) else {
+                return Err(NwcError::new("INTERNAL", "Unexpected balance output"));
+            };
+            Ok(json!({"balance": (confirmed + pending) * 1000}))
+        }
+        "make_invoice" => {
+            let amount = msat("amount")
+                .ok_or_else(|| NwcError::new("OTHER", "Missing amount"))?
+                .div_ceil(1000);
+            let command = OfflineWalletSubCommand::NewAddress(NewAddressCommand);
+            let output = run(runner, wallet, offline_command(command)).await?;
+            let address = output["address"]
+                .as_str()
+                .ok_or_else(|| NwcError::new("INTERNAL", "Unexpected address output"))?;
+            let btc = bdk_wallet::bitcoin::Amount::from_sat(amount).to_btc();
+            let mut invoice = format!("bitcoin:{address}?amount={btc}");
+            let description = request.params.get("description").and_then(Value::as_str);
+            if let Some(description) = description {
+                invoice.push_str(&format!("&message={}", percent_encode(description)));
+            }
+            Ok(json!({
+                "type": "incoming",
+                "invoice": invoice,
+                "description": description,
+                "amount": amount * 1000,
+                "created_at": unix_now(),
+            }))
+        }
+        "pay_invoice" => {
+            let invoice = request
+                .params
+                .get("invoice")
+                .and_then(Value::as_str)
+                .ok_or_else(|| NwcError::new("OTHER", "Missing invoice"))?;
+            let (address, invoice_amount) =
+                parse_invoice(invoice).map_err(|e| NwcError::new("OTHER", e))?;
+            let amount = msat("amount")
+                .map(|msat| msat.div_ceil(1000))
+                .or(invoice_amount)
+                .ok_or_else(|| NwcError::new("OTHER", "The invoice has no amount"))?;
+            connection.check_budget(amount, unix_now())?;
+
+            let create = create_tx_command([(address, amount)], None, false)
+                .map_err(|e| NwcError::new("OTHER", e.error.to_string()))?;
+            let psbt = run(runner, wallet, create).await?;
+            let psbt = psbt["psbt"].as_str().unwrap_or_default().to_string();
+            // The budget pays the on-chain fee of the payment too.
+            let fee = parse_psbt_with_version(&psbt)
+                .ok()
+                .and_then(|(psbt, _)| psbt.fee().ok())
+                .ok_or_else(|| NwcError::new("INTERNAL", "Cannot compute the fee of the payment"))?
+                .to_sat();
+            // Charged before the payment is signed, so that the payments made meanwhile see it.
+            reserve(datadir, wallet, &connection.client_pubkey, amount + fee)?;
+            let paid = pay(runner, wallet, psbt).await;
+            if paid.is_err() {
+                refund(datadir, wallet, &connection.client_pubkey, amount + fee);
+            }
+            let txid = paid?;
+            Ok(json!({"preimage": txid, "txid": txid, "fees_paid": fee * 1000}))
+        }
+        _ => Err(NwcError::new(
+            "NOT_IMPLEMENTED",
+            format!("Unsupported method '{}'", request.method),
+        )),
+    }
+}
+
+/// The response to `event`, a request of one of the connections of `wallet`, `None` for the
+/// requests of unknown or revoked apps.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+async fn respond(
+    runner: &Arc<Runner>,
+    datadir: &Path,
+    wallet: &str,
+    keys: &Keys,
+    event: &Event,
+) -> Result<Option<EventBuilder>, Error> {
+    // Reloaded for each request, for the connections made or revoked while the daemon runs. The
+    // store is only changed by the payments, under its lock.
+    let store = NwcStore::load(datadir, wallet)?;
+    let client = event.pubkey.to_hex();
+    let Some(mut connection) = store
+        .connections
+        .into_iter()
+        .find(|connection| connection.client_pubkey == client)
+    else {
+        return Ok(None);
+    };
+    let content =
+        nip04::decrypt(keys.secret_key(), &event.pubkey, &event.content).map_err(nostr_error)?;
+    let (method, result) = match serde_json::from_str::<NwcRequest>(&content) {
+        Ok(request) => {
+            let result = handle(runner, datadir, wallet, &mut connection, &request).await;
+            (request.method, result)
+        }
+        Err(e) => (
+            String::new(),
+            Err(NwcError::new("OTHER", format!("Invalid request: {e}"))),
+        ),
+    };
+    let name = connection.name;
+
+    let reply = match result {
+        Ok(result) => json!({"result_type": method, "error": null, "result": result}),
+        Err(e) => {
+            log::warn!("{wallet}: {method} of {name} failed: {}", e.message);
+            json!({
+                "result_type": method,
+                "error": {"code": e.code, "message": e.message},
+                "result": null,
+            })
+        }
+    };
+    let encrypted =
+        nip04::encrypt(keys.secret_key(), &event.pubkey, reply.to_string()).map_err(nostr_error)?;
+    Ok(Some(
+        EventBuilder::new(Kind::WalletConnectResponse, encrypted)
+            .tag(Tag::public_key(event.pubkey))
+            .tag(Tag::event(event.id)),
+    ))
+}
+
+/// Answers the requests of the connections of `wallet` until the daemon stops.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub(crate) async fn provide(runner: Arc<Runner>, datadir: PathBuf, wallet: String) {
+    if let Err(e) = serve_connections(&runner, &datadir, &wallet).await {
+        log::error!("{wallet}: Nostr Wallet Connect stopped: {e}");
+    }
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+async fn serve_connections(
+    runner: &Arc<Runner>,
+    datadir: &Path,
+    wallet: &str,
+) -> Result<(), Error> {
+    let mut store = NwcStore::load(datadir, wallet)?;
+    if store.connections.is_empty() {
+        return Ok(());
+    }
+    let keys = store.service_keys()?;
+    let mut relays: Vec<&str> = store.connections.iter().map(|c| c.relay.as_str()).collect();
+    relays.sort();
+    relays.dedup();
+
+    let client = Client::new(keys.clone());
+    for relay in &relays {
+        client.add_relay(*relay).await.map_err(nostr_error)?;
+    }
+    client.connect().await;
+    let info = EventBuilder::new(Kind::WalletConnectInfo, METHODS.join(" "));
+    client.send_event_builder(info).await.map_err(nostr_error)?;
+    let requests = Filter::new()
+        .kind(Kind::WalletConnectRequest)
+        .pubkey(keys.public_key())
+        .since(Timestamp::now());
+    let mut notifications = client.notifications();
+    client
+        .subscribe(requests, None)
+        .await
+        .map_err(nostr_error)?;
+    log::info!("{wallet}: Nostr Wallet Connect on {}", relays.join(", "));
+
+    loop {
+        let event = match notifications.recv().await {
+            Ok(RelayPoolNotification::Event { event, .. }) => event,
+            Ok(_) | Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return Ok(()),
+        };
+        if event.kind != Kind::WalletConnectRequest {
+            continue;
+        }
+        match respond(runner, datadir, wallet, &keys, &event).await {
+            Ok(Some(response)) => {
+                if let Err(e) = client.send_event_builder(response).await {
+                    log::warn!("{wallet}: cannot send the Nostr Wallet Connect response: {e}");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("{wallet}: Nostr Wallet Connect request failed: {e}"),
+        }
+    }
+}
+
+/// Whether `wallet` has Nostr Wallet Connect connections.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub(crate) fn has_connections(datadir: &Path, wallet: &str) -> bool {
+    NwcStore::load(datadir, wallet).is_ok_and(|store| !store.connections.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    fn test_parse_invoice() {
+        let (address, amount) = parse_invoice("bitcoin:bc1qxyz?amount=0.0005&label=tip").unwrap();
+        assert_eq!(address, "bc1qxyz");
+        assert_eq!(amount, Some(50_000));
+        assert_eq!(
+            parse_invoice("bc1qxyz").unwrap(),
+            ("bc1qxyz".to_string(), None)
+        );
+        assert!(parse_invoice("lnbc1:xyz").is_err());
+        assert!(parse_invoice("bitcoin:?amount=1").is_err());
+    }
+
+    #[test]
+    fn test_budget_reset() {
+        let mut connection = NwcConnection {
+            name: "app".to_string(),
+            client_pubkey: String::new(),
+            relay: "wss://relay.example".to_string(),
+            budget_sat: 10_000,
+            period: BudgetPeriod::Daily,
+            spent_sat: 8_000,
+            period_start: 1_000,
+        };
+        assert_eq!(connection.remaining(2_000), 2_000);
+        assert_eq!(connection.remaining(1_000 + 24 * 3600), 10_000);
+        assert_eq!(connection.spent_sat, 0);
+    }
+
+    #[test]
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    fn test_budget_counts_the_fee() {
+        let mut connection = NwcConnection {
+            name: "app".to_string(),
+            client_pubkey: String::new(),
+            relay: "wss://relay.example".to_string(),
+            budget_sat: 10_000,
+            period: BudgetPeriod::Daily,
+            spent_sat: 8_000,
+            period_start: 1_000,
+        };
+        assert!(connection.check_budget(1_800, 2_000).is_ok());
+        // The amount fits the budget, but not with the fee of the transaction.
+        let error = connection.check_budget(1_800 + 300, 2_000).unwrap_err();
+        assert_eq!(error.code, "QUOTA_EXCEEDED");
+    }
+
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    fn connect(datadir: &Path, budget_sat: u64) -> (Keys, Keys) {
+        let app = Keys::generate();
+        let service = NwcStore::update(datadir, "wallet", |store| {
+            let service = store.service_keys()?;
+            store.connections.push(NwcConnection {
+                name: "app".to_string(),
+                client_pubkey: app.public_key().to_hex(),
+                relay: "wss://relay.example".to_string(),
+                budget_sat,
+                period: BudgetPeriod::Total,
+                spent_sat: 0,
+                period_start: 0,
+            });
+            Ok::<_, Error>(service)
+        })
+        .unwrap();
+        (service, app)
+    }
+
+    #[test]
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    fn test_reserve_the_budget() {
+        let datadir = tempfile::tempdir().unwrap();
+        let (_, app) = connect(datadir.path(), 5_000);
+        let client_pubkey = app.public_key().to_hex();
+        let payments: Vec<_> = (0..10)
+            .map(|_| {
+                let datadir = datadir.path().to_path_buf();
+                let client_pubkey = client_pubkey.clone();
+                std::thread::spawn(move || reserve(&datadir, "wallet", &client_pubkey, 1_000))
+            })
+            .collect();
+        let paid = payments
+            .into_iter()
+            .filter(|payment| matches!(payment.join(), Ok(Ok(()))))
+            .count();
+        // The payments made at once never exceed the budget.
+        assert_eq!(paid, 5);
+        let spent =
+            |datadir: &Path| NwcStore::load(datadir, "wallet").unwrap().connections[0].spent_sat;
+        assert_eq!(spent(datadir.path()), 5_000);
+
+        refund(datadir.path(), "wallet", &client_pubkey, 1_000);
+        assert_eq!(spent(datadir.path()), 4_000);
+        NwcStore::update(datadir.path(), "wallet", |store| {
+            store.connections.clear();
+            Ok::<_, Error>(())
+        })
+        .unwrap();
+        let error = reserve(datadir.path(), "wallet", &client_pubkey, 1_000).unwrap_err();
+        assert_eq!(error.code, "UNAUTHORIZED");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    async fn test_respond() {
+        let datadir = tempfile::tempdir().unwrap();
+        let (service, app) = connect(datadir.path(), 0);
+        let runner = Runner::new(
+            datadir.path().to_path_buf(),
+            true,
+            false,
+            Default::default(),
+        );
+        let request = |keys: &Keys, request: Value| {
+            let content = nip04::encrypt(
+                keys.secret_key(),
+                &service.public_key(),
+                request.to_string(),
+            )
+            .unwrap();
+            EventBuilder::new(Kind::WalletConnectRequest, content)
+                .tag(Tag::public_key(service.public_key()))
+                .sign_with_keys(keys)
+                .unwrap()
+        };
+        let reply = |event: &Event| {
+            let response = respond(&runner, datadir.path(), "wallet", &service, event);
+            let (service, app) = (&service, &app);
+            async move {
+                let response = response.await.unwrap()?.sign_with_keys(service).unwrap();
+                let content =
+                    nip04::decrypt(app.secret_key(), &service.public_key(), &response.content)
+                        .unwrap();
+                Some(serde_json::from_str::<Value>(&content).unwrap())
+            }
+        };
+
+        let info = reply(&request(&app, json!({"method": "get_info"})))
+            .await
+            .unwrap();
+        assert_eq!(info["result_type"], "get_info");
+        assert_eq!(info["result"]["methods"], json!(METHODS));
+
+        // A connection without a budget cannot pay.
+        let invoice = "bitcoin:bcrt1qxyz?amount=0.0001";
+        let pay = json!({"method": "pay_invoice", "params": {"invoice": invoice}});
+        let paid = reply(&request(&app, pay)).await.unwrap();
+        assert_eq!(paid["error"]["code"], "QUOTA_EXCEEDED");
+        assert_eq!(paid["result"], Value::Null);
+
+        let unknown = reply(&request(&Keys::generate(), json!({"method": "get_info"}))).await;
+        assert!(unknown.is_none());
+        let method = reply(&request(&app, json!({"method": "lookup_invoice"})))
+            .await
+            .unwrap();
+        assert_eq!(method["error"]["code"], "NOT_IMPLEMENTED");
+    }
+}
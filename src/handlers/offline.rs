@@ -1,24 +1,56 @@
-use crate::commands::OfflineWalletSubCommand;
-use crate::error::BDKCliError as Error;
+use crate::commands::{OfflineWalletSubCommand, PsbtInputArgs, WalletHwiSubCommand};
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::handlers::contacts::resolve_recipients;
 use crate::handlers::{AppCommand, AppContext, OfflineOperations};
+use crate::labels::{Label, LabelManager};
+use crate::utils::descriptors::join_multipath;
+use crate::utils::hwi::{self, Hwi};
+use crate::utils::keystore;
 use crate::utils::output::{FormatOutput, ListResult};
 use crate::utils::parse_address;
+use crate::utils::price::fiat_value;
+use crate::utils::psbt::{MAGIC, PsbtVersion, Sighash, deserialize_psbt, parse_psbt_version};
+use crate::utils::stats::{self, Phase};
 use crate::utils::types::{
-    AddressResult, BalanceResult, KeychainPair, PsbtResult, RawPsbt, TransactionDetails,
-    UnspentDetails,
+    AddressResult, BalanceResult, CombinedPsbtResult, GapDetails, KeychainPair, PsbtConflict,
+    PsbtResult, PublicDescriptorResult, RawPsbt, TimelockDetails, TimelockStatus,
+    TransactionDetails, UnspentDetails, UtxoReport, UtxoReportEntry, UtxoTimelockStatus,
+    VanityResult,
 };
-use crate::utils::{parse_outpoint, parse_recipient};
+use crate::utils::{
+    Payee, parse_outpoint, parse_payee_recipient, parse_psbt_with_version, run_external_signer,
+    write_psbt_file,
+};
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+use crate::utils::{fees::mempool_fee_rate, load_wallet_config};
 use bdk_wallet::bitcoin::base64::Engine;
 use bdk_wallet::bitcoin::base64::prelude::BASE64_STANDARD;
+use bdk_wallet::bitcoin::bip32::Fingerprint;
+use bdk_wallet::bitcoin::hashes::Hash;
+use bdk_wallet::bitcoin::psbt;
 use bdk_wallet::bitcoin::script::PushBytesBuf;
-use bdk_wallet::bitcoin::{Address, Amount, FeeRate, OutPoint, Psbt, ScriptBuf, Sequence, Txid};
-use bdk_wallet::{KeychainKind, SignOptions};
-use clap::Parser;
+use bdk_wallet::bitcoin::{
+    Address, Amount, FeeRate, OutPoint, Psbt, ScriptBuf, Sequence, Transaction, TxIn, Txid, Weight,
+    absolute, relative,
+};
+use bdk_wallet::chain::ChainPosition;
+use bdk_wallet::descriptor::Policy;
+use bdk_wallet::descriptor::policy::SatisfiableItem;
+use bdk_wallet::{KeychainKind, SignOptions, TxOrdering, Wallet};
+use clap::{Parser, ValueEnum};
 use serde_json::json;
 use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
 #[cfg(feature = "silent-payments")]
 use {
     crate::utils::common::parse_sp_code_value_pairs,
+    crate::utils::parse_recipient,
     bdk_sp::{
         bitcoin::{PrivateKey, PublicKey},
         encoding::SilentPaymentCode,
@@ -39,19 +71,40 @@ impl OfflineWalletSubCommand {
     pub fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<(), Error> {
         match self {
             Self::NewAddress(new_address) => new_address.execute(ctx)?.write_out(std::io::stdout()),
-            Self::Balance(balance) => balance.execute(ctx)?.write_out(std::io::stdout()),
+            Self::Balance(balance) => {
+                balance
+                    .execute(ctx)?
+                    .write_table_out(std::io::stdout(), &balance.columns, &[])
+            }
+            Self::Gap(gap) => gap.execute(ctx)?.write_out(std::io::stdout()),
             Self::UnusedAddress(unused_address_command) => unused_address_command
                 .execute(ctx)?
                 .write_out(std::io::stdout()),
-            Self::Unspent(unspent_command) => {
-                unspent_command.execute(ctx)?.write_out(std::io::stdout())
+            Self::Vanity(vanity_command) => {
+                vanity_command.execute(ctx)?.write_out(std::io::stdout())
             }
-            Self::Transactions(transactions_command) => transactions_command
+            Self::Unspent(unspent_command) => unspent_command.execute(ctx)?.write_table_out(
+                std::io::stdout(),
+                &unspent_command.columns,
+                UNSPENT_COLUMNS,
+            ),
+            Self::UtxoReport(utxo_report_command) => utxo_report_command
                 .execute(ctx)?
                 .write_out(std::io::stdout()),
+            Self::Transactions(transactions_command) => {
+                transactions_command.execute(ctx)?.write_table_out(
+                    std::io::stdout(),
+                    &transactions_command.columns,
+                    TRANSACTIONS_COLUMNS,
+                )
+            }
             Self::CreateTx(createtx_command) => {
                 createtx_command.execute(ctx)?.write_out(std::io::stdout())
             }
+            Self::Label(label_command) => label_command.execute(ctx)?.write_out(std::io::stdout()),
+            Self::Labels(labels_command) => {
+                labels_command.execute(ctx)?.write_out(std::io::stdout())
+            }
             #[cfg(feature = "silent-payments")]
             Self::CreateSpTx(cmd) => cmd.execute(ctx)?.write_out(std::io::stdout()),
             Self::BumpFee(bumpfee_command) => {
@@ -60,6 +113,9 @@ impl OfflineWalletSubCommand {
             Self::Policies(policies_command) => {
                 policies_command.execute(ctx)?.write_out(std::io::stdout())
             }
+            Self::Timelocks(timelocks_command) => {
+                timelocks_command.execute(ctx)?.write_out(std::io::stdout())
+            }
             Self::PublicDescriptor(public_descriptor_command) => public_descriptor_command
                 .execute(ctx)?
                 .write_out(std::io::stdout()),
@@ -90,6 +146,20 @@ impl OfflineWalletSubCommand {
             Self::CreateDnsTx(_) => Err(Error::Generic(
                 "CreateDnsTx is dispatched asynchronously through main".to_string(),
             )),
+            Self::Contacts { subcommand } => subcommand.execute(ctx),
+            Self::Hwi { subcommand } => subcommand.execute(ctx),
+            #[cfg(feature = "nwc")]
+            Self::Nwc { subcommand } => subcommand.execute(ctx),
+        }
+    }
+}
+
+impl WalletHwiSubCommand {
+    pub fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<(), Error> {
+        match self {
+            Self::DisplayAddress(display_address_command) => display_address_command
+                .execute(ctx)?
+                .write_out(std::io::stdout()),
         }
     }
 }
@@ -121,7 +191,91 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for UnusedAddressCommand {
 }
 
 #[derive(Parser, Debug, PartialEq, Clone)]
-pub struct UnspentCommand;
+pub struct VanityCommand {
+    /// Prefix the address must start with, network prefix included (e.g. `bcrt1qcafe`).
+    #[arg(env = "PATTERN", long = "pattern")]
+    pub pattern: String,
+
+    /// Highest derivation index to try.
+    #[arg(env = "MAX_INDEX", long = "max-index", default_value_t = 100_000)]
+    pub max_index: u32,
+
+    /// Reveals the addresses up to the match so that `new_address` does not hand it out.
+    #[arg(long = "reserve")]
+    pub reserve: bool,
+
+    /// Label to set on the reserved address.
+    #[arg(env = "LABEL", long = "label", requires = "reserve")]
+    pub label: Option<String>,
+}
+
+impl AppCommand<AppContext<OfflineOperations<'_>>> for VanityCommand {
+    type Output = VanityResult;
+
+    fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        let wallet = &mut ctx.state.wallet;
+        let keychain = KeychainKind::External;
+        if !wallet.public_descriptor(keychain).has_wildcard() {
+            return Err(Error::coded(
+                ErrorCode::InvalidDescriptor,
+                "The external descriptor has no wildcard, it derives a single address".to_string(),
+            ));
+        }
+
+        // Only unrevealed addresses are searched, revealed ones may already be handed out.
+        let start = wallet.next_derivation_index(keychain);
+        let address_info = (start..=self.max_index)
+            .map(|index| wallet.peek_address(keychain, index))
+            .find(|info| info.address.to_string().starts_with(&self.pattern))
+            .ok_or_else(|| {
+                Error::coded(
+                    ErrorCode::InvalidArgument,
+                    format!(
+                        "No external address from index {start} to {} starts with {}",
+                        self.max_index, self.pattern
+                    ),
+                )
+            })?;
+        let index = address_info.index;
+
+        if self.reserve {
+            let _ = wallet.reveal_addresses_to(keychain, index);
+            if let Some(label) = &self.label {
+                let mut labels = LabelManager::load(&ctx.datadir.join(&ctx.state.wallet_name))?;
+                labels.set(Label::new(
+                    &address_info.address.to_string(),
+                    label.clone(),
+                )?);
+                labels.save()?;
+            }
+        }
+
+        Ok(VanityResult {
+            address: address_info.address.to_string(),
+            index,
+            tried: index - start + 1,
+            reserved: self.reserve,
+            label: self.label.clone(),
+        })
+    }
+}
+
+/// The columns of the `unspent` table by default.
+const UNSPENT_COLUMNS: &[&str] = &[
+    "outpoint",
+    "txout.value",
+    "keychain",
+    "derivation_index",
+    "is_locked",
+];
+
+#[derive(Parser, Debug, Default, PartialEq, Clone)]
+pub struct UnspentCommand {
+    /// Comma separated columns of the table and CSV outputs, such as
+    /// `outpoint,txout.value,keychain,derivation_index,is_locked` which the table shows by default.
+    #[arg(long = "columns", value_delimiter = ',', value_name = "COLUMNS")]
+    pub columns: Vec<String>,
+}
 
 impl AppCommand<AppContext<OfflineOperations<'_>>> for UnspentCommand {
     type Output = ListResult<UnspentDetails>;
@@ -142,15 +296,106 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for UnspentCommand {
 }
 
 #[derive(Parser, Debug, PartialEq, Clone)]
-pub struct TransactionsCommand;
+pub struct UtxoReportCommand {
+    /// Fee rate in sat/vbyte used to estimate the cost of spending each UTXO.
+    #[arg(
+        env = "SATS_VBYTE",
+        short = 'f',
+        long = "fee_rate",
+        default_value = "10.0"
+    )]
+    pub fee_rate: f32,
+}
+
+/// Weight of an unsigned input: outpoint, sequence and the empty script_sig length.
+const TXIN_BASE_WEIGHT: Weight = Weight::from_wu(164);
+
+impl AppCommand<AppContext<OfflineOperations<'_>>> for UtxoReportCommand {
+    type Output = UtxoReport;
+
+    fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        let wallet = &ctx.state.wallet;
+        let tip_height = wallet.latest_checkpoint().height();
+        let fee_rate = FeeRate::from_sat_per_kwu((self.fee_rate * 250.0) as u64);
+
+        let mut utxos = Vec::new();
+        for utxo in wallet.list_unspent() {
+            let satisfaction_weight = wallet
+                .public_descriptor(utxo.keychain)
+                .max_weight_to_satisfy()?;
+            let spend_cost = fee_rate
+                .fee_wu(TXIN_BASE_WEIGHT + satisfaction_weight)
+                .unwrap_or(Amount::MAX_MONEY);
+            let value = utxo.txout.value;
+
+            let confirmations = match utxo.chain_position {
+                ChainPosition::Confirmed { anchor, .. } => {
+                    tip_height.saturating_sub(anchor.block_id.height) + 1
+                }
+                ChainPosition::Unconfirmed { .. } => 0,
+            };
+            let age = match confirmations {
+                0 => "unconfirmed",
+                1..=5 => "fresh",
+                6..=4_319 => "recent",
+                _ => "aged",
+            };
+
+            utxos.push(UtxoReportEntry {
+                outpoint: utxo.outpoint.to_string(),
+                keychain: format!("{:?}", utxo.keychain),
+                value: value.to_sat(),
+                spend_cost: spend_cost.to_sat(),
+                is_dust: value <= spend_cost,
+                confirmations,
+                age: age.to_string(),
+            });
+        }
+        utxos.sort_by_key(|utxo| utxo.value);
+
+        let (dust, economical): (Vec<_>, Vec<_>) = utxos.iter().partition(|utxo| utxo.is_dust);
+        Ok(UtxoReport {
+            fee_rate: self.fee_rate,
+            dust_count: dust.len(),
+            dust_value: dust.iter().map(|utxo| utxo.value).sum(),
+            economical_value: economical.iter().map(|utxo| utxo.value).sum(),
+            utxos,
+        })
+    }
+}
+
+/// The satoshis `tx` moves into the wallet, negative when it spends from it.
+fn net_amount(wallet: &Wallet, tx: &Transaction) -> i64 {
+    let (sent, received) = wallet.sent_and_received(tx);
+    received.to_sat() as i64 - sent.to_sat() as i64
+}
+
+/// The columns of the `transactions` table by default.
+const TRANSACTIONS_COLUMNS: &[&str] = &[
+    "txid",
+    "net",
+    "confirmed",
+    "version",
+    "is_rbf",
+    "is_coinbase",
+];
+
+#[derive(Parser, Debug, Default, PartialEq, Clone)]
+pub struct TransactionsCommand {
+    /// Comma separated columns of the table and CSV outputs, such as
+    /// `txid,net,confirmed,version,is_rbf,is_coinbase` which the table shows by default.
+    #[arg(long = "columns", value_delimiter = ',', value_name = "COLUMNS")]
+    pub columns: Vec<String>,
+}
 
 impl AppCommand<AppContext<OfflineOperations<'_>>> for TransactionsCommand {
     type Output = ListResult<TransactionDetails>;
 
     fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
-        let transactions = &mut ctx.state.wallet.transactions();
+        let wallet = &ctx.state.wallet;
 
-        let txns: Vec<TransactionDetails> = transactions
+        let txns: Vec<TransactionDetails> = wallet
+            .transactions()
             .map(|tx| {
                 let total_value = tx
                     .tx_node
@@ -158,6 +403,7 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for TransactionsCommand {
                     .iter()
                     .map(|output| output.value.to_sat())
                     .sum::<u64>();
+                let net = net_amount(wallet, &tx.tx_node);
 
                 TransactionDetails {
                     txid: tx.tx_node.txid.to_string(),
@@ -166,11 +412,14 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for TransactionsCommand {
                     version: serde_json::to_value(tx.tx_node.version).unwrap_or(json!(1)),
                     version_display: tx.tx_node.version.to_string(),
                     is_rbf: tx.tx_node.is_explicitly_rbf(),
+                    net,
+                    confirmed: tx.chain_position.is_confirmed(),
                     inputs: serde_json::to_value(&tx.tx_node.input).unwrap_or_default(),
                     outputs: serde_json::to_value(&tx.tx_node.output).unwrap_or_default(),
                     input_count: tx.tx_node.input.len(),
                     output_count: tx.tx_node.output.len(),
                     total_value,
+                    fiat: fiat_value(net),
                 }
             })
             .collect();
@@ -179,8 +428,13 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for TransactionsCommand {
     }
 }
 
-#[derive(Parser, Debug, PartialEq, Clone)]
-pub struct BalanceCommand;
+#[derive(Parser, Debug, Default, PartialEq, Clone)]
+pub struct BalanceCommand {
+    /// Comma separated fields of the table and CSV outputs, among `total`,
+    /// `trusted_pending`, `untrusted_pending`, `immature` and `confirmed`.
+    #[arg(long = "columns", value_delimiter = ',', value_name = "COLUMNS")]
+    pub columns: Vec<String>,
+}
 
 impl AppCommand<AppContext<OfflineOperations<'_>>> for BalanceCommand {
     type Output = BalanceResult;
@@ -190,11 +444,114 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for BalanceCommand {
         Ok(BalanceResult::from(balance))
     }
 }
+
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct GapCommand;
+
+impl AppCommand<AppContext<OfflineOperations<'_>>> for GapCommand {
+    type Output = ListResult<GapDetails>;
+
+    fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        let wallet = &ctx.state.wallet;
+        let stop_gap = ctx.state.stop_gap;
+        let index = wallet.spk_index();
+
+        let gaps = wallet
+            .keychains()
+            .map(|(keychain, _)| {
+                let last_revealed_index = index.last_revealed_index(keychain);
+                let last_used_index = index.last_used_index(keychain);
+
+                // Walk the revealed range and track the longest run of unused addresses.
+                let mut largest_unused_gap = 0;
+                let mut run = 0;
+                for i in last_revealed_index.map_or(0..0, |last| 0..last + 1) {
+                    if index.is_used(keychain, i) {
+                        run = 0;
+                    } else {
+                        run += 1;
+                        largest_unused_gap = largest_unused_gap.max(run);
+                    }
+                }
+
+                // Warn once three quarters of the stop gap is consumed, a full scan stops
+                // looking for funds as soon as the whole stop gap is reached.
+                let warning = if largest_unused_gap as usize >= stop_gap {
+                    Some(format!(
+                        "{largest_unused_gap} consecutive unused {keychain:?} addresses reach the stop gap of {stop_gap}, a full scan may miss funds"
+                    ))
+                } else if largest_unused_gap as usize * 4 >= stop_gap * 3 {
+                    Some(format!(
+                        "{largest_unused_gap} consecutive unused {keychain:?} addresses are nearing the stop gap of {stop_gap}"
+                    ))
+                } else {
+                    None
+                };
+                if let Some(warning) = &warning {
+                    log::warn!("{warning}");
+                }
+
+                GapDetails {
+                    keychain: format!("{keychain:?}"),
+                    last_revealed_index,
+                    last_used_index,
+                    largest_unused_gap,
+                    trailing_unused_gap: match (last_revealed_index, last_used_index) {
+                        (Some(revealed), Some(used)) => revealed.saturating_sub(used),
+                        (Some(revealed), None) => revealed + 1,
+                        (None, _) => 0,
+                    },
+                    stop_gap,
+                    warning,
+                }
+            })
+            .collect();
+
+        Ok(ListResult::new(gaps))
+    }
+}
+
+/// Order of the inputs and outputs of a created transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TxOrder {
+    /// Randomized order.
+    Shuffle,
+    /// Lexicographic order of BIP-69.
+    Bip69,
+    /// Recipients and manually selected utxos in the order given.
+    Untouched,
+}
+
+impl From<TxOrder> for TxOrdering {
+    fn from(order: TxOrder) -> Self {
+        match order {
+            TxOrder::Shuffle => TxOrdering::Shuffle,
+            TxOrder::Untouched => TxOrdering::Untouched,
+            // Previous txids are compared in their displayed, byte reversed, order.
+            TxOrder::Bip69 => TxOrdering::Custom {
+                input_sort: Arc::new(|a, b| {
+                    let key = |input: &TxIn| {
+                        let mut txid = input.previous_output.txid.to_byte_array();
+                        txid.reverse();
+                        (txid, input.previous_output.vout)
+                    };
+                    key(a).cmp(&key(b))
+                }),
+                output_sort: Arc::new(|a, b| {
+                    (a.value, a.script_pubkey.as_bytes())
+                        .cmp(&(b.value, b.script_pubkey.as_bytes()))
+                }),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Parser, Clone, PartialEq)]
 pub struct CreateTxCommand {
-    /// Adds a recipient to the transaction.
-    #[arg(env = "ADDRESS:SAT", long = "to", required = true, value_parser = parse_recipient)]
-    pub recipients: Vec<(ScriptBuf, u64)>,
+    /// Adds a recipient to the transaction, as `<address>:<amount>` or `@<contact>:<amount>`
+    /// for a contact of `wallet contacts`, whose address is confirmed first.
+    #[arg(env = "ADDRESS:SAT", long = "to", required = true, value_parser = parse_payee_recipient)]
+    pub recipients: Vec<(Payee, u64)>,
 
     /// Sends all the funds (or all the selected utxos). Requires only one recipient with value 0.
     #[arg(long = "send_all", short = 'a')]
@@ -216,10 +573,43 @@ pub struct CreateTxCommand {
     #[arg(env = "CANT_SPEND_TXID:VOUT", long = "unspendable", value_parser = parse_outpoint)]
     pub unspendable: Option<Vec<OutPoint>>,
 
+    /// Only spends utxos whose outpoint or address carries this label.
+    #[arg(env = "FROM_LABEL", long = "from-label")]
+    pub from_label: Option<String>,
+
+    /// Never spends utxos whose outpoint or address carries this label.
+    #[arg(env = "EXCLUDE_LABEL", long = "exclude-label")]
+    pub exclude_label: Option<String>,
+
     /// Fee rate to use in sat/vbyte.
     #[arg(env = "SATS_VBYTE", short = 'f', long = "fee_rate")]
     pub fee_rate: Option<f32>,
 
+    /// Uses the fee rate mempool.space recommends to confirm within this number of blocks.
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    #[arg(
+        env = "TARGET_BLOCKS",
+        long = "target-blocks",
+        conflicts_with = "fee_rate"
+    )]
+    pub target_blocks: Option<u16>,
+
+    /// Base URL of the mempool.space API, for self-hosted instances. Defaults to the public
+    /// instance of the wallet network.
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    #[arg(env = "MEMPOOL_URL", long = "mempool-url", requires = "target_blocks")]
+    pub mempool_url: Option<String>,
+
     /// Selects which policy should be used to satisfy the external descriptor.
     #[arg(env = "EXT_POLICY", long = "external_policy")]
     pub external_policy: Option<String>,
@@ -245,28 +635,121 @@ pub struct CreateTxCommand {
         conflicts_with = "add_string"
     )]
     pub add_data: Option<String>,
+
+    /// Order of the inputs and outputs.
+    #[arg(env = "ORDERING", long = "ordering", value_enum, default_value_t = TxOrder::Shuffle)]
+    pub ordering: TxOrder,
+
+    /// PSBT version of the result, 0 (BIP-174) or 2 (BIP-370).
+    #[arg(env = "PSBT_VERSION", long = "psbt-version", default_value = "0", value_parser = parse_psbt_version)]
+    pub psbt_version: PsbtVersion,
+
+    /// Also writes the PSBT to a raw binary file.
+    #[arg(env = "PSBT_OUT_FILE", long = "out-file")]
+    pub out_file: Option<PathBuf>,
+}
+
+impl CreateTxCommand {
+    /// A `create_tx` paying `recipients` with the default options, as the requests of the
+    /// servers build it.
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    pub(crate) fn paying(
+        recipients: Vec<(Payee, u64)>,
+        fee_rate: Option<f32>,
+        send_all: bool,
+    ) -> Self {
+        Self {
+            recipients,
+            send_all,
+            enable_rbf: true,
+            offline_signer: false,
+            utxos: None,
+            unspendable: None,
+            from_label: None,
+            exclude_label: None,
+            fee_rate,
+            #[cfg(any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf"
+            ))]
+            target_blocks: None,
+            #[cfg(any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf"
+            ))]
+            mempool_url: None,
+            external_policy: None,
+            internal_policy: None,
+            add_string: None,
+            add_data: None,
+            ordering: TxOrder::Shuffle,
+            psbt_version: PsbtVersion::V0,
+            out_file: None,
+        }
+    }
+
+    /// Utxos ruled out by `--from-label` and `--exclude-label`.
+    fn label_filtered_utxos(
+        &self,
+        ctx: &AppContext<OfflineOperations<'_>>,
+    ) -> Result<Vec<OutPoint>, Error> {
+        let labels = LabelManager::load(&ctx.datadir.join(&ctx.state.wallet_name))?;
+        let wallet = &ctx.state.wallet;
+        let network = wallet.network();
+
+        let mut filtered = Vec::new();
+        let mut matched = 0;
+        for utxo in wallet.list_unspent() {
+            let address = Address::from_script(&utxo.txout.script_pubkey, network).ok();
+            let has_label =
+                |label: &str| labels.output_has_label(&utxo.outpoint, address.as_ref(), label);
+
+            let excluded = self.exclude_label.as_deref().is_some_and(has_label);
+            let selected = self.from_label.as_deref().is_none_or(has_label);
+            if selected && !excluded {
+                matched += 1;
+            } else {
+                filtered.push(utxo.outpoint);
+            }
+        }
+
+        if matched == 0 {
+            return Err(Error::coded(
+                ErrorCode::NoSpendableUtxos,
+                "No spendable utxos left after applying the label filters".to_string(),
+            ));
+        }
+        Ok(filtered)
+    }
 }
 
 impl AppCommand<AppContext<OfflineOperations<'_>>> for CreateTxCommand {
     type Output = PsbtResult;
 
     fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        let mut unspendable = self.unspendable.clone().unwrap_or_default();
+        if self.from_label.is_some() || self.exclude_label.is_some() {
+            unspendable.extend(self.label_filtered_utxos(ctx)?);
+        }
+        let recipients = resolve_recipients(ctx, &self.recipients)?;
+
         let mut tx_builder = ctx.state.wallet.build_tx();
 
         if self.send_all {
-            if self.recipients.len() == 1 {
-                tx_builder
-                    .drain_wallet()
-                    .drain_to(self.recipients[0].0.clone());
+            if recipients.len() == 1 {
+                tx_builder.drain_wallet().drain_to(recipients[0].0.clone());
             } else {
-                return Err(Error::Generic(
+                return Err(Error::coded(
+                    ErrorCode::TxCreate,
                     "Wallet can only be drained to a single output".to_string(),
                 ));
             }
         } else {
-            let recipients = self
-                .recipients
-                .clone()
+            let recipients = recipients
                 .into_iter()
                 .map(|(script, amount)| (script, Amount::from_sat(amount)))
                 .collect();
@@ -281,18 +764,40 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for CreateTxCommand {
             tx_builder.add_global_xpubs();
         }
 
+        tx_builder.ordering(self.ordering.into());
+
         if let Some(fee_rate) = self.fee_rate
             && let Some(fee_rate) = FeeRate::from_sat_per_vb(fee_rate as u64)
         {
             tx_builder.fee_rate(fee_rate);
         }
 
+        #[cfg(any(
+            feature = "electrum",
+            feature = "esplora",
+            feature = "rpc",
+            feature = "cbf"
+        ))]
+        if let Some(target_blocks) = self.target_blocks {
+            let (wallet_opts, _) = load_wallet_config(&ctx.datadir, &ctx.state.wallet_name)?;
+            // The wallet commands run inside the tokio runtime of `main`.
+            let sat_per_vb = tokio::task::block_in_place(|| {
+                tokio::runtime::Handle::current().block_on(mempool_fee_rate(
+                    self.mempool_url.as_deref(),
+                    ctx.network,
+                    target_blocks,
+                    &wallet_opts,
+                ))
+            })?;
+            tx_builder.fee_rate(FeeRate::from_sat_per_kwu((sat_per_vb * 250.0).ceil() as u64));
+        }
+
         if let Some(utxos) = &self.utxos {
             tx_builder.add_utxos(&utxos[..]).unwrap();
         }
 
-        if let Some(unspendable) = &self.unspendable {
-            tx_builder.unspendable(unspendable.to_vec());
+        if !unspendable.is_empty() {
+            tx_builder.unspendable(unspendable);
         }
 
         if let Some(base64_data) = &self.add_data {
@@ -317,11 +822,19 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for CreateTxCommand {
             tx_builder.policy_path(policy, keychain);
         }
 
-        let psbt = tx_builder.finish()?;
+        let psbt = {
+            let _timer = stats::timer(Phase::CoinSelection);
+            tx_builder.finish()?
+        };
+        if let Some(path) = &self.out_file {
+            write_psbt_file(path, &psbt, self.psbt_version)?;
+        }
 
         // let psbt_base64 = BASE64_STANDARD.encode(psbt.serialize());
 
-        Ok(PsbtResult::new(&psbt, Some(false)))
+        let mut result = PsbtResult::with_version(&psbt, Some(false), self.psbt_version);
+        result.fiat = fiat_value(-net_amount(&ctx.state.wallet, &psbt.unsigned_tx));
+        Ok(result)
     }
 }
 
@@ -399,12 +912,14 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for CreateSpTxCommand {
                 if recipients.len() == 1 {
                     tx_builder.drain_wallet().drain_to(recipients[0].0.clone());
                 } else {
-                    return Err(Error::Generic(
+                    return Err(Error::coded(
+                        ErrorCode::TxCreate,
                         "Wallet can only be drain to a single output".to_string(),
                     ));
                 }
             } else {
-                return Err(Error::Generic(
+                return Err(Error::coded(
+                    ErrorCode::TxCreate,
                     "Wallet can only be drain to a single output".to_string(),
                 ));
             }
@@ -455,14 +970,14 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for CreateSpTxCommand {
         if let Some(base64_data) = &self.add_data {
             let op_return_data = BASE64_STANDARD
                 .decode(base64_data)
-                .map_err(|e| Error::Generic(e.to_string()))?;
+                .map_err(|e| Error::coded(ErrorCode::InvalidBase64, e.to_string()))?;
             tx_builder.add_data(
                 &PushBytesBuf::try_from(op_return_data)
-                    .map_err(|e| Error::Generic(e.to_string()))?,
+                    .map_err(|e| Error::coded(ErrorCode::TxCreate, e.to_string()))?,
             );
         } else if let Some(string_data) = &self.add_string {
             let data = PushBytesBuf::try_from(string_data.as_bytes().to_vec())
-                .map_err(|e| Error::Generic(e.to_string()))?;
+                .map_err(|e| Error::coded(ErrorCode::TxCreate, e.to_string()))?;
             tx_builder.add_data(&data);
         }
 
@@ -480,14 +995,21 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for CreateSpTxCommand {
             tx_builder.policy_path(policy, keychain);
         }
 
-        let mut psbt = tx_builder.finish()?;
+        let mut psbt = {
+            let _timer = stats::timer(Phase::CoinSelection);
+            tx_builder.finish()?
+        };
 
         let unsigned_psbt = psbt.clone();
 
-        let finalized = ctx.state.wallet.sign(&mut psbt, SignOptions::default())?;
+        let finalized = {
+            let _timer = stats::timer(Phase::Signing);
+            ctx.state.wallet.sign(&mut psbt, SignOptions::default())?
+        };
 
         if !finalized {
-            return Err(Error::Generic(
+            return Err(Error::coded(
+                ErrorCode::TxCreate,
                 "Cannot produce silent payment outputs without intermediate signing phase."
                     .to_string(),
             ));
@@ -538,7 +1060,10 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for CreateSpTxCommand {
             psbt_input.final_script_witness = None;
         }
 
-        let _resigned = ctx.state.wallet.sign(&mut psbt, SignOptions::default())?;
+        let _resigned = {
+            let _timer = stats::timer(Phase::Signing);
+            ctx.state.wallet.sign(&mut psbt, SignOptions::default())?
+        };
 
         let raw_tx = psbt.extract_tx()?;
 
@@ -576,6 +1101,10 @@ pub struct BumpFeeCommand {
         default_value = "1.0"
     )]
     pub fee_rate: f32,
+
+    /// Also writes the PSBT to a raw binary file.
+    #[arg(env = "PSBT_OUT_FILE", long = "out-file")]
+    pub out_file: Option<PathBuf>,
 }
 
 impl AppCommand<AppContext<OfflineOperations<'_>>> for BumpFeeCommand {
@@ -606,7 +1135,13 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for BumpFeeCommand {
             tx_builder.unspendable(unspendable.to_vec());
         }
 
-        let psbt = tx_builder.finish()?;
+        let psbt = {
+            let _timer = stats::timer(Phase::CoinSelection);
+            tx_builder.finish()?
+        };
+        if let Some(path) = &self.out_file {
+            write_psbt_file(path, &psbt, PsbtVersion::V0)?;
+        }
 
         // let psbt_base64 = BASE64_STANDARD.encode(psbt.serialize());
 
@@ -632,26 +1167,180 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for PoliciesCommand {
     }
 }
 
+#[derive(Parser, Debug, PartialEq, Clone)]
+pub struct TimelocksCommand;
+
+impl AppCommand<AppContext<OfflineOperations<'_>>> for TimelocksCommand {
+    type Output = ListResult<TimelockDetails>;
+
+    fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        let wallet = &ctx.state.wallet;
+        let tip_height = wallet.latest_checkpoint().height();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| Error::Generic(e.to_string()))?
+            .as_secs();
+
+        let mut timelocks = Vec::new();
+        for (keychain, _) in wallet.keychains() {
+            let Some(policy) = wallet.policies(keychain)? else {
+                continue;
+            };
+
+            let mut items = Vec::new();
+            collect_timelocks(&policy, &mut items);
+
+            for (policy_id, item) in items {
+                let details = match item {
+                    SatisfiableItem::AbsoluteTimelock { value } => TimelockDetails {
+                        keychain: format!("{keychain:?}"),
+                        policy_id,
+                        kind: "absolute".to_string(),
+                        value: value.to_consensus_u32(),
+                        status: Some(absolute_timelock_status(value, tip_height, now)),
+                        utxos: Vec::new(),
+                    },
+                    SatisfiableItem::RelativeTimelock { value } => TimelockDetails {
+                        keychain: format!("{keychain:?}"),
+                        policy_id,
+                        kind: "relative".to_string(),
+                        value: value.to_consensus_u32(),
+                        status: None,
+                        utxos: wallet
+                            .list_unspent()
+                            .filter(|utxo| utxo.keychain == keychain)
+                            .map(|utxo| {
+                                let confirmation = match utxo.chain_position {
+                                    ChainPosition::Confirmed { anchor, .. } => {
+                                        Some((anchor.block_id.height, anchor.confirmation_time))
+                                    }
+                                    ChainPosition::Unconfirmed { .. } => None,
+                                };
+                                UtxoTimelockStatus {
+                                    outpoint: utxo.outpoint.to_string(),
+                                    confirmation_height: confirmation.map(|(height, _)| height),
+                                    status: relative_timelock_status(
+                                        value,
+                                        confirmation,
+                                        tip_height,
+                                        now,
+                                    ),
+                                }
+                            })
+                            .collect(),
+                    },
+                    _ => continue,
+                };
+                timelocks.push(details);
+            }
+        }
+
+        Ok(ListResult::new(timelocks))
+    }
+}
+
+/// Average block interval used to estimate when a height based timelock unlocks.
+const AVERAGE_BLOCK_SECONDS: u64 = 600;
+
+/// Collect every CLTV/CSV node of a policy tree along with its policy id.
+fn collect_timelocks(policy: &Policy, items: &mut Vec<(String, SatisfiableItem)>) {
+    match &policy.item {
+        SatisfiableItem::AbsoluteTimelock { .. } | SatisfiableItem::RelativeTimelock { .. } => {
+            items.push((policy.id.clone(), policy.item.clone()))
+        }
+        SatisfiableItem::Thresh {
+            items: children, ..
+        } => {
+            for child in children {
+                collect_timelocks(child, items);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Status of an absolute timelock, a spend can be mined in the block after the tip.
+///
+/// Time based locks are compared against the local clock rather than the median time past.
+fn absolute_timelock_status(lock: absolute::LockTime, tip_height: u32, now: u64) -> TimelockStatus {
+    match lock {
+        absolute::LockTime::Blocks(height) => {
+            height_timelock_status(height.to_consensus_u32() + 1, tip_height, now)
+        }
+        absolute::LockTime::Seconds(time) => {
+            time_timelock_status(u64::from(time.to_consensus_u32()), now)
+        }
+    }
+}
+
+/// Status of a relative timelock for an output confirmed at `confirmation`.
+fn relative_timelock_status(
+    lock: relative::LockTime,
+    confirmation: Option<(u32, u64)>,
+    tip_height: u32,
+    now: u64,
+) -> TimelockStatus {
+    match (lock, confirmation) {
+        (relative::LockTime::Blocks(blocks), Some((height, _))) => {
+            height_timelock_status(height + u32::from(blocks.value()), tip_height, now)
+        }
+        (relative::LockTime::Time(time), Some((_, confirmation_time))) => {
+            time_timelock_status(confirmation_time + u64::from(time.value()) * 512, now)
+        }
+        // The relative lock only starts counting once the output confirms.
+        (_, None) => TimelockStatus {
+            satisfiable: false,
+            unlock_height: None,
+            unlock_time: None,
+            blocks_remaining: None,
+            estimated_unlock_time: None,
+        },
+    }
+}
+
+fn height_timelock_status(unlock_height: u32, tip_height: u32, now: u64) -> TimelockStatus {
+    let blocks_remaining = unlock_height.saturating_sub(tip_height + 1);
+    TimelockStatus {
+        satisfiable: blocks_remaining == 0,
+        unlock_height: Some(unlock_height),
+        unlock_time: None,
+        blocks_remaining: Some(blocks_remaining),
+        estimated_unlock_time: Some(now + u64::from(blocks_remaining) * AVERAGE_BLOCK_SECONDS),
+    }
+}
+
+fn time_timelock_status(unlock_time: u64, now: u64) -> TimelockStatus {
+    TimelockStatus {
+        satisfiable: now >= unlock_time,
+        unlock_height: None,
+        unlock_time: Some(unlock_time),
+        blocks_remaining: None,
+        estimated_unlock_time: Some(unlock_time.max(now)),
+    }
+}
+
 #[derive(Parser, Debug, PartialEq, Clone)]
 pub struct PublicDescriptorCommand;
 
 impl AppCommand<AppContext<OfflineOperations<'_>>> for PublicDescriptorCommand {
-    type Output = KeychainPair<String>;
+    type Output = PublicDescriptorResult;
 
     fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
         let wallet = &mut ctx.state.wallet;
-        Ok(KeychainPair {
-            external: wallet.public_descriptor(KeychainKind::External).to_string(),
-            internal: wallet.public_descriptor(KeychainKind::Internal).to_string(),
+        let external = wallet.public_descriptor(KeychainKind::External).to_string();
+        let internal = wallet.public_descriptor(KeychainKind::Internal).to_string();
+        Ok(PublicDescriptorResult {
+            multipath: join_multipath(&external, &internal),
+            descriptors: KeychainPair { external, internal },
         })
     }
 }
 
 #[derive(Debug, Parser, Clone, PartialEq)]
+#[command(mut_arg("psbt", |arg| arg.env("BASE64_PSBT")))]
 pub struct SignCommand {
-    /// Sets the PSBT to sign.
-    #[arg(env = "BASE64_PSBT")]
-    pub psbt: String,
+    #[command(flatten)]
+    pub psbt: PsbtInputArgs,
 
     /// Assume the blockchain has reached a specific height. This affects the transaction finalization, if there are timelocks in the descriptor.
     #[arg(env = "HEIGHT", long = "assume_height")]
@@ -660,6 +1349,67 @@ pub struct SignCommand {
     /// Whether the signer should trust the witness_utxo, if the non_witness_utxo hasn’t been provided.
     #[arg(env = "WITNESS", long = "trust_witness_utxo")]
     pub trust_witness_utxo: Option<bool>,
+
+    /// Sighash type to sign every input with.
+    #[arg(env = "SIGHASH", long = "sighash", value_enum)]
+    pub sighash: Option<Sighash>,
+
+    /// Signs with a hardware wallet through HWI instead of the wallet keys.
+    #[arg(long = "hwi")]
+    pub hwi: bool,
+
+    /// Fingerprint of the hardware wallet to sign with, required when several are connected.
+    #[arg(env = "HWI_DEVICE", long = "device", requires = "hwi")]
+    pub device: Option<Fingerprint>,
+
+    /// Path of the HWI executable.
+    #[arg(env = "HWI_PATH", long = "hwi_path", default_value = hwi::DEFAULT_HWI_PATH, requires = "hwi")]
+    pub hwi_path: String,
+
+    /// Signs with an external command instead of the wallet keys. The command is run through the
+    /// shell, `sh` or `cmd` on Windows, receives the base64 PSBT on stdin and prints the signed
+    /// PSBT on stdout.
+    #[arg(
+        env = "EXTERNAL_SIGNER",
+        long = "external_signer",
+        visible_alias = "external-signer",
+        conflicts_with = "hwi"
+    )]
+    pub external_signer: Option<String>,
+
+    /// Also writes the signed PSBT to a raw binary file.
+    #[arg(env = "PSBT_OUT_FILE", long = "out-file")]
+    pub out_file: Option<PathBuf>,
+}
+
+impl SignCommand {
+    /// A `sign` of the base64 `psbt` with the wallet keys, as the requests of `daemon --nwc`
+    /// build it.
+    #[cfg_attr(
+        not(all(
+            feature = "nwc",
+            any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf"
+            )
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn of(psbt: String) -> Self {
+        Self {
+            psbt: PsbtInputArgs::of(psbt),
+            assume_height: None,
+            trust_witness_utxo: None,
+            sighash: None,
+            hwi: false,
+            device: None,
+            hwi_path: hwi::DEFAULT_HWI_PATH.to_string(),
+            external_signer: None,
+            out_file: None,
+        }
+    }
 }
 
 impl AppCommand<AppContext<OfflineOperations<'_>>> for SignCommand {
@@ -667,34 +1417,104 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for SignCommand {
 
     fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
         let wallet = &mut ctx.state.wallet;
-        let psbt_bytes = BASE64_STANDARD
-            .decode(&self.psbt)
-            .map_err(|e| Error::Generic(e.to_string()))?;
-        let mut psbt = Psbt::deserialize(&psbt_bytes).map_err(|e| Error::Generic(e.to_string()))?;
+        let (mut psbt, version) = self.psbt.parse()?;
+
+        if let Some(sighash) = self.sighash {
+            sighash.apply(&mut psbt);
+        }
 
         let signopt = SignOptions {
             assume_height: self.assume_height,
             trust_witness_utxo: self.trust_witness_utxo.unwrap_or(false),
+            allow_all_sighashes: self.sighash.is_some_and(|sighash| !sighash.is_standard()),
             ..Default::default()
         };
-        let finalized = wallet.sign(&mut psbt, signopt)?;
-        Ok(PsbtResult::new(&psbt, Some(finalized)))
+        let finalized = if self.hwi {
+            let _timer = stats::timer(Phase::Signing);
+            let hwi = Hwi::new(&self.hwi_path, ctx.network);
+            let device = hwi.find_device(self.device)?;
+            psbt.combine(hwi.sign_tx(device, &psbt)?)?;
+            wallet.finalize_psbt(&mut psbt, signopt)?
+        } else if let Some(command) = &self.external_signer {
+            let _timer = stats::timer(Phase::Signing);
+            psbt.combine(run_external_signer(command, &psbt, version)?)?;
+            wallet.finalize_psbt(&mut psbt, signopt)?
+        } else {
+            keystore::unlock(wallet, &ctx.datadir, &ctx.state.wallet_name)?;
+            let _timer = stats::timer(Phase::Signing);
+            wallet.sign(&mut psbt, signopt)?
+        };
+        if let Some(path) = &self.out_file {
+            write_psbt_file(path, &psbt, version)?;
+        }
+        Ok(PsbtResult::with_version(&psbt, Some(finalized), version))
+    }
+}
+
+#[derive(Debug, Parser, Clone, PartialEq)]
+pub struct DisplayAddressCommand {
+    /// Derivation index of the address.
+    #[arg(long = "index")]
+    pub index: u32,
+
+    /// Shows a change address instead of a receive address.
+    #[arg(long = "change")]
+    pub change: bool,
+
+    /// Fingerprint of the hardware wallet, required when several are connected.
+    #[arg(env = "HWI_DEVICE", long = "device")]
+    pub device: Option<Fingerprint>,
+
+    /// Path of the HWI executable.
+    #[arg(env = "HWI_PATH", long = "hwi_path", default_value = hwi::DEFAULT_HWI_PATH)]
+    pub hwi_path: String,
+}
+
+impl AppCommand<AppContext<OfflineOperations<'_>>> for DisplayAddressCommand {
+    type Output = AddressResult;
+
+    fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        let wallet = &ctx.state.wallet;
+        let keychain = match self.change {
+            true => KeychainKind::Internal,
+            false => KeychainKind::External,
+        };
+        let address = wallet.peek_address(keychain, self.index);
+        let descriptor = wallet
+            .public_descriptor(keychain)
+            .at_derivation_index(self.index)
+            .map_err(|e| Error::coded(ErrorCode::InvalidDescriptor, e.to_string()))?;
+
+        let hwi = Hwi::new(&self.hwi_path, ctx.network);
+        let device = hwi.find_device(self.device)?;
+        let shown = hwi.display_address(device, &descriptor.to_string())?;
+        if shown != address.address.to_string() {
+            return Err(Error::coded(
+                ErrorCode::HardwareWallet,
+                format!(
+                    "Hardware wallet {device} shows {shown} but the wallet derives {} at index {}: \
+                 do not use this address",
+                    address.address, self.index
+                ),
+            ));
+        }
+        log::info!("Hardware wallet {device} displays the address, confirm it on its screen");
+        Ok(AddressResult::from(address))
     }
 }
 
 #[derive(Debug, Parser, Clone, PartialEq)]
+#[command(mut_arg("psbt", |arg| arg.env("BASE64_PSBT")))]
 pub struct ExtractPsbtCommand {
-    /// Sets the PSBT to extract
-    #[arg(env = "BASE64_PSBT")]
-    pub psbt: String,
+    #[command(flatten)]
+    pub psbt: PsbtInputArgs,
 }
 
 impl AppCommand<AppContext<OfflineOperations<'_>>> for ExtractPsbtCommand {
     type Output = RawPsbt;
 
     fn execute(&self, _ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
-        let psbt_serialized = BASE64_STANDARD.decode(self.psbt.clone())?;
-        let psbt = Psbt::deserialize(&psbt_serialized)?;
+        let (psbt, _) = self.psbt.parse()?;
         let raw_tx = psbt.extract_tx()?;
 
         Ok(RawPsbt::new(&raw_tx))
@@ -702,10 +1522,10 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for ExtractPsbtCommand {
 }
 
 #[derive(Debug, Parser, Clone, PartialEq)]
+#[command(mut_arg("psbt", |arg| arg.env("BASE64_PSBT")))]
 pub struct FinalizePsbtCommand {
-    /// Sets the PSBT to finalize.
-    #[arg(env = "BASE64_PSBT")]
-    pub psbt: String,
+    #[command(flatten)]
+    pub psbt: PsbtInputArgs,
 
     /// Assume the blockchain has reached a specific height.
     #[arg(env = "HEIGHT", long = "assume_height")]
@@ -714,6 +1534,10 @@ pub struct FinalizePsbtCommand {
     /// Whether the signer should trust the witness_utxo, if the non_witness_utxo hasn’t been provided.
     #[arg(env = "WITNESS", long = "trust_witness_utxo")]
     pub trust_witness_utxo: Option<bool>,
+
+    /// Also writes the finalized PSBT to a raw binary file.
+    #[arg(env = "PSBT_OUT_FILE", long = "out-file")]
+    pub out_file: Option<PathBuf>,
 }
 
 impl AppCommand<AppContext<OfflineOperations<'_>>> for FinalizePsbtCommand {
@@ -721,10 +1545,7 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for FinalizePsbtCommand {
 
     fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
         let wallet = &mut ctx.state.wallet;
-        let psbt_bytes = BASE64_STANDARD
-            .decode(&self.psbt)
-            .map_err(|e| Error::Generic(e.to_string()))?;
-        let mut psbt = Psbt::deserialize(&psbt_bytes).map_err(|e| Error::Generic(e.to_string()))?;
+        let (mut psbt, version) = self.psbt.parse()?;
 
         let signopt = SignOptions {
             assume_height: self.assume_height,
@@ -733,44 +1554,176 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for FinalizePsbtCommand {
         };
 
         let finalized = wallet.finalize_psbt(&mut psbt, signopt)?;
+        if let Some(path) = &self.out_file {
+            write_psbt_file(path, &psbt, version)?;
+        }
 
-        Ok(PsbtResult::new(&psbt, Some(finalized)))
+        Ok(PsbtResult::with_version(&psbt, Some(finalized), version))
     }
 }
 
 #[derive(Debug, Parser, Clone, PartialEq)]
 pub struct CombinePsbtCommand {
     /// Add one PSBT to combine. This option can be repeated multiple times, one for each PSBT.
-    #[arg(env = "BASE64_PSBT", required = true)]
+    #[arg(env = "BASE64_PSBT", required_unless_present_any = ["files", "stdin"])]
     pub psbt: Vec<String>,
+
+    /// Reads PSBTs from a file, one per line, or a single raw binary PSBT. Can be repeated.
+    #[arg(env = "PSBT_FILE", long = "file", value_name = "PATH")]
+    pub files: Vec<PathBuf>,
+
+    /// Reads PSBTs from stdin, one per line.
+    #[arg(long = "stdin")]
+    pub stdin: bool,
+}
+
+impl CombinePsbtCommand {
+    /// Collect the PSBTs from the arguments, the files and stdin, in that order.
+    fn read_psbts(&self) -> Result<Vec<(Psbt, PsbtVersion)>, Error> {
+        let mut psbts = Vec::new();
+        let encoded = |s: &str| (!s.trim().is_empty()).then(|| parse_psbt_with_version(s));
+
+        for s in &self.psbt {
+            psbts.extend(encoded(s).transpose()?);
+        }
+        for path in &self.files {
+            let content = std::fs::read(path).map_err(|e| {
+                Error::coded(
+                    ErrorCode::Io,
+                    format!("Failed to read PSBT file {path:?}: {e}"),
+                )
+            })?;
+            if content.starts_with(MAGIC) {
+                psbts.push(deserialize_psbt(&content)?);
+            } else {
+                for line in String::from_utf8_lossy(&content).lines() {
+                    psbts.extend(encoded(line).transpose()?);
+                }
+            }
+        }
+        if self.stdin {
+            for line in std::io::stdin().lines() {
+                let line = line.map_err(|e| Error::coded(ErrorCode::Io, e.to_string()))?;
+                psbts.extend(encoded(&line).transpose()?);
+            }
+        }
+        Ok(psbts)
+    }
 }
 
 impl AppCommand<AppContext<OfflineOperations<'_>>> for CombinePsbtCommand {
-    type Output = PsbtResult;
+    type Output = CombinedPsbtResult;
 
     fn execute(&self, _ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
-        let mut psbts = self
-            .psbt
-            .iter()
-            .map(|s| {
-                let psbt = BASE64_STANDARD.decode(s)?;
-                Ok(Psbt::deserialize(&psbt)?)
-            })
-            .collect::<Result<Vec<_>, Error>>()?;
+        let mut psbts = self.read_psbts()?.into_iter();
+        let (mut final_psbt, version) = psbts.next().ok_or_else(|| {
+            Error::coded(
+                ErrorCode::InvalidPsbt,
+                "Invalid PSBT input: no PSBT given".to_string(),
+            )
+        })?;
+
+        let mut conflicts = Vec::new();
+        for (index, (psbt, _)) in psbts.enumerate() {
+            for (input, (base, other)) in final_psbt.inputs.iter().zip(&psbt.inputs).enumerate() {
+                conflicts.extend(input_conflicts(base, other).into_iter().map(|field| {
+                    PsbtConflict {
+                        input,
+                        field,
+                        psbt: index + 1,
+                    }
+                }));
+            }
+            final_psbt.combine(psbt).map_err(|e| {
+                Error::coded(
+                    ErrorCode::InvalidPsbt,
+                    format!("Cannot combine PSBT #{}: {e}", index + 1),
+                )
+            })?;
+        }
 
-        let init_psbt = psbts
-            .pop()
-            .ok_or_else(|| Error::Generic("Invalid PSBT input".to_string()))?;
-        let final_psbt =
-            psbts
-                .into_iter()
-                .try_fold::<_, _, Result<Psbt, Error>>(init_psbt, |mut acc, x| {
-                    let _ = acc.combine(x);
-                    Ok(acc)
-                })?;
+        for conflict in &conflicts {
+            log::warn!(
+                "PSBT #{} has a conflicting {} for input {}",
+                conflict.psbt,
+                conflict.field,
+                conflict.input
+            );
+        }
+
+        Ok(CombinedPsbtResult {
+            psbt: PsbtResult::with_version(&final_psbt, Some(false), version),
+            conflicts,
+        })
+    }
+}
 
-        Ok(PsbtResult::new(&final_psbt, Some(false)))
+/// Fields both inputs set, but to different values.
+fn input_conflicts(base: &psbt::Input, other: &psbt::Input) -> Vec<String> {
+    fn differs<T: PartialEq>(a: &Option<T>, b: &Option<T>) -> bool {
+        matches!((a, b), (Some(a), Some(b)) if a != b)
     }
+
+    let mut fields: Vec<String> = [
+        (
+            "non_witness_utxo",
+            differs(&base.non_witness_utxo, &other.non_witness_utxo),
+        ),
+        (
+            "witness_utxo",
+            differs(&base.witness_utxo, &other.witness_utxo),
+        ),
+        (
+            "sighash_type",
+            differs(&base.sighash_type, &other.sighash_type),
+        ),
+        (
+            "redeem_script",
+            differs(&base.redeem_script, &other.redeem_script),
+        ),
+        (
+            "witness_script",
+            differs(&base.witness_script, &other.witness_script),
+        ),
+        (
+            "final_script_sig",
+            differs(&base.final_script_sig, &other.final_script_sig),
+        ),
+        (
+            "final_script_witness",
+            differs(&base.final_script_witness, &other.final_script_witness),
+        ),
+        (
+            "tap_key_sig",
+            differs(&base.tap_key_sig, &other.tap_key_sig),
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, differs)| *differs)
+    .map(|(field, _)| field.to_string())
+    .collect();
+
+    for (pubkey, sig) in &other.partial_sigs {
+        if base.partial_sigs.get(pubkey).is_some_and(|s| s != sig) {
+            fields.push(format!("partial_sig for {pubkey}"));
+        }
+    }
+    for (key, sig) in &other.tap_script_sigs {
+        if base.tap_script_sigs.get(key).is_some_and(|s| s != sig) {
+            fields.push(format!("tap_script_sig for {}", key.0));
+        }
+    }
+    for (pubkey, origin) in &other.bip32_derivation {
+        if base
+            .bip32_derivation
+            .get(pubkey)
+            .is_some_and(|o| o != origin)
+        {
+            fields.push(format!("bip32_derivation for {pubkey}"));
+        }
+    }
+
+    fields
 }
 
 #[cfg(feature = "bip322")]
@@ -803,10 +1756,10 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for SignMessageCommand {
         let signature_format = parse_signature_format(&self.signature_type)?;
 
         if !wallet.is_mine(address.script_pubkey()) {
-            return Err(Error::Generic(format!(
-                "Address {} does not belong to this wallet.",
-                address
-            )));
+            return Err(Error::coded(
+                ErrorCode::InvalidAddress,
+                format!("Address {} does not belong to this wallet.", address),
+            ));
         }
 
         let proof = wallet.sign_message(
@@ -848,8 +1801,12 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for VerifyMessageCommand {
 
         let address: Address = parse_address(&self.address)?;
 
-        let parsed_proof = MessageProof::from_base64(&self.proof)
-            .map_err(|e| Error::Generic(format!("Invalid proof format: {e}")))?;
+        let parsed_proof = MessageProof::from_base64(&self.proof).map_err(|e| {
+            Error::coded(
+                ErrorCode::InvalidArgument,
+                format!("Invalid proof format: {e}"),
+            )
+        })?;
 
         let is_valid = wallet.verify_message(&parsed_proof, &self.message, &address)?;
 
@@ -874,7 +1831,7 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for LockUtxoCommand {
         let wallet = &mut ctx.state.wallet;
         for out_point in &self.utxos {
             if wallet.get_utxo(*out_point).is_none() {
-                eprintln!("warning: {out_point} is not a known wallet UTXO; skipping");
+                log::warn!("{out_point} is not a known wallet UTXO; skipping");
                 continue;
             }
             wallet.lock_outpoint(*out_point);
@@ -923,3 +1880,40 @@ impl AppCommand<AppContext<OfflineOperations<'_>>> for LockedUtxosCommand {
         Ok(ListResult::new(locked))
     }
 }
+
+#[derive(Parser, Debug, Clone, PartialEq)]
+pub struct LabelCommand {
+    /// Address, txid or outpoint (TXID:VOUT) to label.
+    #[arg(env = "REF", long = "ref")]
+    pub reference: String,
+
+    /// Label to set. Removes the existing label when omitted.
+    #[arg(env = "LABEL", long = "label")]
+    pub label: Option<String>,
+}
+
+impl AppCommand<AppContext<OfflineOperations<'_>>> for LabelCommand {
+    type Output = ListResult<Label>;
+    fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        let mut labels = LabelManager::load(&ctx.datadir.join(&ctx.state.wallet_name))?;
+        let label = Label::new(&self.reference, self.label.clone().unwrap_or_default())?;
+        if self.label.is_some() {
+            labels.set(label);
+        } else if !labels.remove(&label.reference) {
+            log::warn!("{} has no label; nothing removed", label.reference);
+        }
+        labels.save()?;
+        Ok(ListResult::new(labels.labels().to_vec()))
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq)]
+pub struct LabelsCommand;
+
+impl AppCommand<AppContext<OfflineOperations<'_>>> for LabelsCommand {
+    type Output = ListResult<Label>;
+    fn execute(&self, ctx: &mut AppContext<OfflineOperations<'_>>) -> Result<Self::Output, Error> {
+        let labels = LabelManager::load(&ctx.datadir.join(&ctx.state.wallet_name))?;
+        Ok(ListResult::new(labels.labels().to_vec()))
+    }
+}
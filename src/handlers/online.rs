@@ -1,22 +1,45 @@
+use bdk_wallet::bitcoin::Txid;
 use clap::Parser;
+use clap::builder::RangedU64ValueParser;
+use std::path::PathBuf;
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+use crate::utils::progress::ProgressFormat;
 
 #[cfg(feature = "electrum")]
 use crate::client::BlockchainClient::Electrum;
 #[cfg(feature = "cbf")]
 use crate::client::{BlockchainClient::KyotoClient, sync_kyoto_client};
 #[cfg(feature = "esplora")]
-use {crate::client::BlockchainClient::Esplora, bdk_esplora::EsploraAsyncExt};
+use {
+    crate::client::BlockchainClient::Esplora, crate::utils::esplora::with_backoff,
+    bdk_esplora::EsploraAsyncExt,
+};
 #[cfg(feature = "rpc")]
 use {
     crate::client::BlockchainClient::RpcClient,
+    crate::utils::bitcoind::node_zmq,
+    crate::utils::progress::Progress,
+    crate::utils::zmq::{self, Notification},
     bdk_bitcoind_rpc::{Emitter, NO_EXPECTED_MEMPOOL_TXS, bitcoincore_rpc::RpcApi},
-    bdk_wallet::chain::{BlockId, CanonicalizationParams, CheckPoint},
+    bdk_wallet::chain::CanonicalizationParams,
+    tokio::sync::mpsc::UnboundedReceiver,
 };
 #[cfg(any(feature = "electrum", feature = "esplora"))]
-use {bdk_wallet::KeychainKind, std::collections::HashSet, std::io::Write};
+use {
+    crate::client::{new_url_client, server_urls},
+    crate::utils::types::{SyncDiscrepancy, SyncVerification},
+    bdk_wallet::bitcoin::OutPoint,
+    bdk_wallet::chain::BlockId,
+    bdk_wallet::chain::spk_client::SyncItem,
+    std::collections::BTreeMap,
+};
 
-#[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
-use crate::utils::print_wallet_events;
 #[cfg(any(
     feature = "electrum",
     feature = "esplora",
@@ -25,18 +48,29 @@ use crate::utils::print_wallet_events;
 ))]
 use {
     crate::commands::OnlineWalletSubCommand,
-    crate::error::BDKCliError as Error,
+    crate::error::{BDKCliError as Error, ErrorCode},
     crate::handlers::{AppContext, AsyncAppCommand, OnlineOperations, payjoin::PayjoinManager},
     crate::utils::{
-        is_final,
+        EventFormat, amount, confirm,
+        faucet::{default_faucet_url, request_coins},
+        fees::{FeeProvider, mempool_fee_rate},
+        is_final, load_wallet_config,
         output::FormatOutput,
-        types::{StatusResult, TransactionResult},
-    },
-    bdk_wallet::bitcoin::{
-        Psbt, Transaction, Txid, base64::Engine, base64::prelude::BASE64_STANDARD,
-        consensus::Decodable, hex::FromHex,
+        parse_psbt_input, parse_raw_tx, print_ndjson,
+        stats::{self, Phase},
+        types::{
+            BroadcastEndpoint, FaucetResult, FeeEstimateResult, RebroadcastResult,
+            RebroadcastStatus, RebroadcastedTx, StatusResult, SyncResult, TransactionResult,
+        },
     },
+    bdk_wallet::bitcoin::Transaction,
+    bdk_wallet::chain::ChainPosition,
+    std::collections::HashSet,
+    std::sync::Arc,
+    std::time::Duration,
 };
+#[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
+use {crate::utils::print_wallet_events, bdk_wallet::chain::CheckPoint};
 #[cfg(any(
     feature = "electrum",
     feature = "esplora",
@@ -50,14 +84,39 @@ impl OnlineWalletSubCommand {
                 let response: StatusResult = full_scan_command.execute(ctx).await?;
                 response.write_out(std::io::stdout())
             }
+            OnlineWalletSubCommand::Rescan(rescan_command) => {
+                let response: StatusResult = rescan_command.execute(ctx).await?;
+                response.write_out(std::io::stdout())
+            }
             OnlineWalletSubCommand::Sync(sync_command) => {
-                let response: StatusResult = sync_command.execute(ctx).await?;
+                let response: SyncResult = sync_command.execute(ctx).await?;
                 response.write_out(std::io::stdout())
             }
             OnlineWalletSubCommand::Broadcast(broadcast_command) => {
                 let response: TransactionResult = broadcast_command.execute(ctx).await?;
                 response.write_out(std::io::stdout())
             }
+            OnlineWalletSubCommand::Rebroadcast(rebroadcast_command) => {
+                let response: RebroadcastResult = rebroadcast_command.execute(ctx).await?;
+                response.write_out(std::io::stdout())
+            }
+            OnlineWalletSubCommand::Watch(watch_command) => {
+                let response: StatusResult = watch_command.execute(ctx).await?;
+                if watch_command.ndjson {
+                    // Stdout only carries the events.
+                    log::info!("{}", response.message);
+                    return Ok(());
+                }
+                response.write_out(std::io::stdout())
+            }
+            OnlineWalletSubCommand::EstimateFee(estimate_fee_command) => {
+                let response: FeeEstimateResult = estimate_fee_command.execute(ctx).await?;
+                response.write_out(std::io::stdout())
+            }
+            OnlineWalletSubCommand::Faucet(faucet_command) => {
+                let response: FaucetResult = faucet_command.execute(ctx).await?;
+                response.write_out(std::io::stdout())
+            }
             OnlineWalletSubCommand::ReceivePayjoin(receive_payjoin_command) => {
                 let response: StatusResult = receive_payjoin_command.execute(ctx).await?;
                 response.write_out(std::io::stdout())
@@ -81,10 +140,21 @@ impl OnlineWalletSubCommand {
 #[derive(Parser, Debug, PartialEq, Clone, Eq)]
 pub struct FullScanCommand {
     /// Stop searching addresses for transactions after finding an unused gap of this length.
-    #[arg(env = "STOP_GAP", long = "scan-stop-gap", default_value = "20")]
-    stop_gap: usize,
+    /// Defaults to the stop gap saved in the wallet configuration.
+    #[arg(env = "SCAN_STOP_GAP", long = "scan-stop-gap")]
+    stop_gap: Option<usize>,
     #[clap(long, default_value = "5")]
     pub parallel_request: usize,
+    /// Reports the progress on stderr as a bar, one JSON event per line or not at all. Defaults
+    /// to a bar when stderr is a terminal.
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    #[arg(long = "progress", value_enum)]
+    progress: Option<ProgressFormat>,
 }
 
 #[cfg(any(
@@ -100,104 +170,221 @@ impl AsyncAppCommand<AppContext<OnlineOperations<'_>>> for FullScanCommand {
         &self,
         ctx: &mut AppContext<OnlineOperations<'_>>,
     ) -> Result<Self::Output, Error> {
-        let wallet = &mut ctx.state.wallet;
-        let client = ctx.state.client;
+        scan_wallet(ctx, self.stop_gap, self.progress, None).await?;
+        Ok(StatusResult {
+            message: "Full scan completed successfully.".to_string(),
+        })
+    }
+}
 
-        #[cfg(any(feature = "electrum", feature = "esplora"))]
-        let request = wallet.start_full_scan().inspect({
-            let mut stderr = std::io::stderr();
-            let mut once = HashSet::<KeychainKind>::new();
-            move |k, spk_i, _| {
-                if once.insert(k) {
-                    eprint!("\nScanning keychain [{k:?}]");
-                }
-                eprint!(" {spk_i:<3}");
-                stderr.flush().expect("must flush");
-            }
-        });
+/// Scans the scripts of every keychain up to the stop gap, or the blocks of the node with the
+/// `rpc` backend, for the transactions of the wallet.
+///
+/// With `from_height`, the chain of the wallet is rewound to its block below that height, which
+/// the scan reconnects the chain of the backend to, and the `rpc` backend scans the blocks from
+/// that height. Without it, the chain of the backend is connected to the tip of the wallet, and
+/// the `rpc` backend scans from the genesis block.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+pub(crate) async fn scan_wallet(
+    ctx: &mut AppContext<OnlineOperations<'_>>,
+    stop_gap: Option<usize>,
+    progress: Option<ProgressFormat>,
+    from_height: Option<u32>,
+) -> Result<(), Error> {
+    let _timer = stats::timer(Phase::NetworkSync);
+    let format = ctx.state.events;
+    let wallet = &mut ctx.state.wallet;
+    let client = ctx.state.client;
+    client.check_network(ctx.network).await?;
+    let progress = client.progress(progress);
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    let stop_gap = stop_gap.unwrap_or(ctx.state.stop_gap);
+    #[cfg(not(any(feature = "electrum", feature = "esplora")))]
+    let _ = stop_gap;
+    // The genesis block is always in the chain, so there is a block at or below any height.
+    #[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
+    let rewound = |height: u32| -> CheckPoint {
+        wallet
+            .latest_checkpoint()
+            .floor_at(height.saturating_sub(1))
+            .expect("the chain of the wallet starts at the genesis block")
+    };
 
-        match client {
-            #[cfg(feature = "electrum")]
-            Electrum { client, batch_size } => {
-                client.populate_tx_cache(wallet.tx_graph().full_txs().map(|tx_node| tx_node.tx));
-                let update = client.full_scan(request, self.stop_gap, *batch_size, false)?;
-                let events = wallet.apply_update_events(update)?;
-                print_wallet_events(&events);
-            }
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    let request = match from_height {
+        Some(height) => wallet.start_full_scan().chain_tip(rewound(height)),
+        None => wallet.start_full_scan(),
+    }
+    .inspect({
+        let progress = progress.clone();
+        move |keychain, index, _| progress.script(keychain, index)
+    });
 
-            #[cfg(feature = "esplora")]
-            Esplora {
-                client,
-                parallel_requests,
-            } => {
-                let update = client
-                    .full_scan(request, self.stop_gap, *parallel_requests)
-                    .await
-                    .map_err(|e| *e)?;
-                let events = wallet.apply_update_events(update)?;
-                print_wallet_events(&events);
+    match client {
+        #[cfg(feature = "electrum")]
+        Electrum { client, batch_size } => {
+            // Populate the electrum client's transaction cache so it doesn't re-download
+            // transaction we already have.
+            client.populate_tx_cache(wallet.tx_graph().full_txs().map(|tx_node| tx_node.tx));
+            let update = client.full_scan(request, stop_gap, *batch_size, false)?;
+            let events = wallet.apply_update_events(update)?;
+            print_wallet_events(&events, format);
+        }
+        #[cfg(feature = "esplora")]
+        Esplora {
+            client,
+            parallel_requests,
+            backoff,
+        } => {
+            let update = with_backoff(
+                *backoff,
+                client.full_scan(request, stop_gap, *parallel_requests),
+            )
+            .await
+            .map_err(|e| *e)?;
+            let events = wallet.apply_update_events(update)?;
+            print_wallet_events(&events, format);
+        }
+        #[cfg(feature = "rpc")]
+        RpcClient { client, .. } => {
+            let from_height = from_height.unwrap_or_default();
+            let tip = client.get_block_count()?;
+            if u64::from(from_height) > tip {
+                return Err(Error::coded(
+                    ErrorCode::InvalidArgument,
+                    format!("Rescan height {from_height} is above the current chain tip {tip}"),
+                ));
             }
-            #[cfg(feature = "rpc")]
-            RpcClient { client } => {
-                let blockchain_info = client.get_blockchain_info()?;
+            let mut emitter = Emitter::new(
+                client.as_ref(),
+                rewound(from_height),
+                from_height,
+                NO_EXPECTED_MEMPOOL_TXS,
+            );
+            emit_rpc_updates(wallet, client, &mut emitter, &progress, format)?;
+        }
+        #[cfg(feature = "cbf")]
+        KyotoClient { client } => {
+            if from_height.is_some() {
+                return Err(Error::coded(
+                    ErrorCode::Config,
+                    "Rescan is not supported by the cbf backend".to_string(),
+                ));
+            }
+            sync_kyoto_client(wallet, client, format).await?;
+        }
+    }
 
-                let genesis_block = bdk_wallet::bitcoin::constants::genesis_block(wallet.network());
-                let genesis_cp = CheckPoint::new(BlockId {
-                    height: 0,
-                    hash: genesis_block.block_hash(),
-                });
-                let mut emitter = Emitter::new(
-                    client.as_ref(),
-                    genesis_cp.clone(),
-                    genesis_cp.height(),
-                    NO_EXPECTED_MEMPOOL_TXS,
-                );
+    progress.finish(ctx.state.wallet.latest_checkpoint().height());
+    Ok(())
+}
 
-                let block_events = wallet.events_helper(|w| {
-                    while let Some(block_event) = emitter.next_block()? {
-                        if block_event.block_height() % 10_000 == 0 {
-                            let percent_done = f64::from(block_event.block_height())
-                                / f64::from(blockchain_info.headers as u32)
-                                * 100f64;
-                            println!(
-                                "Applying block at height: {}, {:.2}% done.",
-                                block_event.block_height(),
-                                percent_done
-                            );
-                        }
+#[derive(Parser, Debug, PartialEq, Clone, Eq)]
+pub struct RescanCommand {
+    /// Block height to start rescanning from. The chain of the wallet is rewound below it; the
+    /// electrum and esplora backends look up the whole history of each script, and the rpc
+    /// backend scans the blocks from this height.
+    #[arg(
+        env = "RESCAN_FROM_HEIGHT",
+        long = "from-height",
+        required_unless_present = "all",
+        conflicts_with = "all"
+    )]
+    from_height: Option<u32>,
+    /// Rescan the whole chain, starting from the genesis block.
+    #[arg(long = "all", required_unless_present = "from_height")]
+    all: bool,
+    /// Stop searching addresses for transactions after finding an unused gap of this length.
+    /// Defaults to the stop gap saved in the wallet configuration.
+    #[arg(env = "SCAN_STOP_GAP", long = "scan-stop-gap")]
+    stop_gap: Option<usize>,
+    /// Reports the progress on stderr as a bar, one JSON event per line or not at all. Defaults
+    /// to a bar when stderr is a terminal.
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    #[arg(long = "progress", value_enum)]
+    progress: Option<ProgressFormat>,
+}
 
-                        w.apply_block_connected_to(
-                            &block_event.block,
-                            block_event.block_height(),
-                            block_event.connected_to(),
-                        )?;
-                    }
-                    Ok::<_, Error>(())
-                })?;
-                print_wallet_events(&block_events);
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+impl AsyncAppCommand<AppContext<OnlineOperations<'_>>> for RescanCommand {
+    type Output = StatusResult;
 
-                let mempool_txs = emitter.mempool()?;
-                let mempool_events = wallet.apply_unconfirmed_txs_events(mempool_txs.update);
-                print_wallet_events(&mempool_events);
+    async fn execute(
+        &self,
+        ctx: &mut AppContext<OnlineOperations<'_>>,
+    ) -> Result<Self::Output, Error> {
+        let from_height = if self.all {
+            0
+        } else {
+            self.from_height.unwrap_or_default()
+        };
+        scan_wallet(ctx, self.stop_gap, self.progress, Some(from_height)).await?;
 
-                let evicted_events = wallet.apply_evicted_txs_events(mempool_txs.evicted);
-                print_wallet_events(&evicted_events);
-            }
+        let message = if self.all {
+            "Rescan of the whole chain completed successfully.".to_string()
+        } else {
+            format!("Rescan from height {from_height} completed successfully.")
+        };
+        Ok(StatusResult { message })
+    }
+}
 
-            #[cfg(feature = "cbf")]
-            KyotoClient { client } => {
-                sync_kyoto_client(wallet, client).await?;
-            }
+#[derive(Parser, Debug, Default, PartialEq, Eq, Clone)]
+pub struct SyncCommand {
+    /// Cross-checks the synced transactions and UTXOs with an independent electrum (`tcp://`,
+    /// `ssl://`) or esplora (`http://`, `https://`) server and reports where they disagree.
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    #[arg(long = "verify-with", value_name = "URL")]
+    verify_with: Option<String>,
+    /// Only syncs the revealed scripts, even when the wallet was never synced and would be
+    /// scanned instead.
+    #[arg(long = "revealed-only")]
+    revealed_only: bool,
+    /// Reports the progress on stderr as a bar, one JSON event per line or not at all. Defaults
+    /// to a bar when stderr is a terminal.
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    #[arg(long = "progress", value_enum)]
+    progress: Option<ProgressFormat>,
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+impl SyncCommand {
+    /// A `sync` without progress report, as run by `watch` and `node`.
+    pub(crate) fn quiet() -> Self {
+        Self {
+            #[cfg(any(feature = "electrum", feature = "esplora"))]
+            verify_with: None,
+            revealed_only: false,
+            progress: Some(ProgressFormat::None),
         }
-        Ok(StatusResult {
-            message: "Full scan completed successfully.".to_string(),
-        })
     }
 }
 
-#[derive(Parser, Debug, PartialEq, Eq, Clone)]
-pub struct SyncCommand;
-
 #[cfg(any(
     feature = "electrum",
     feature = "esplora",
@@ -205,104 +392,292 @@ pub struct SyncCommand;
     feature = "rpc"
 ))]
 impl AsyncAppCommand<AppContext<OnlineOperations<'_>>> for SyncCommand {
-    type Output = StatusResult;
+    type Output = SyncResult;
 
     async fn execute(
         &self,
         ctx: &mut AppContext<OnlineOperations<'_>>,
     ) -> Result<Self::Output, Error> {
-        let wallet = &mut ctx.state.wallet;
-        let client = ctx.state.client;
+        let _timer = stats::timer(Phase::NetworkSync);
+        // Connect first, so an unreachable verifier fails before the sync.
         #[cfg(any(feature = "electrum", feature = "esplora"))]
-        let request = wallet
-            .start_sync_with_revealed_spks()
-            .inspect(|item, progress| {
-                let pc = (100 * progress.consumed()) as f32 / progress.total() as f32;
-                eprintln!("[ SCANNING {pc:03.0}% ] {item}");
+        let verifier = match &self.verify_with {
+            Some(url) => {
+                let (wallet_opts, _) = load_wallet_config(&ctx.datadir, &ctx.state.wallet_name)?;
+                let datadir = ctx.datadir.join(&ctx.state.wallet_name);
+                Some((
+                    url,
+                    new_url_client(&wallet_opts, url, ctx.state.wallet, datadir).map_err(|e| {
+                        Error::coded(ErrorCode::Backend, format!("Cannot verify with {url}: {e}"))
+                    })?,
+                ))
+            }
+            None => None,
+        };
+
+        // A wallet that was never synced has no revealed scripts with history to sync, so its
+        // descriptors are scanned instead.
+        let scan = !self.revealed_only && ctx.state.wallet.latest_checkpoint().height() == 0;
+        if scan {
+            log::info!(
+                "The wallet was never synced, running a full scan instead (use --revealed-only to skip it)."
+            );
+            let full_scan = FullScanCommand {
+                stop_gap: None,
+                parallel_request: 5,
+                progress: self.progress,
+            };
+            full_scan.execute(ctx).await?;
+        } else {
+            let format = ctx.state.events;
+            let wallet = &mut ctx.state.wallet;
+            let client = ctx.state.client;
+            client.check_network(ctx.network).await?;
+            let progress = client.progress(self.progress);
+            #[cfg(any(feature = "electrum", feature = "esplora"))]
+            let request = wallet.start_sync_with_revealed_spks().inspect({
+                let progress = progress.clone();
+                move |item, sync| {
+                    let script = match item {
+                        SyncItem::Spk(index, _) => Some(index),
+                        _ => None,
+                    };
+                    progress.sync_item(script, sync.consumed(), sync.total());
+                }
             });
 
-        match client {
-            #[cfg(feature = "electrum")]
-            Electrum { client, batch_size } => {
-                // Populate the electrum client's transaction cache so it doesn't re-download transaction we
-                // already have.
-                client.populate_tx_cache(wallet.tx_graph().full_txs().map(|tx_node| tx_node.tx));
+            match client {
+                #[cfg(feature = "electrum")]
+                Electrum { client, batch_size } => {
+                    // Populate the electrum client's transaction cache so it doesn't re-download transaction we
+                    // already have.
+                    client
+                        .populate_tx_cache(wallet.tx_graph().full_txs().map(|tx_node| tx_node.tx));
 
-                let update = client.sync(request, *batch_size, false)?;
-                let events = wallet.apply_update_events(update)?;
-                print_wallet_events(&events);
-            }
-            #[cfg(feature = "esplora")]
-            Esplora {
-                client,
-                parallel_requests,
-            } => {
-                let update = client
-                    .sync(request, *parallel_requests)
+                    let update = client.sync(request, *batch_size, false)?;
+                    let events = wallet.apply_update_events(update)?;
+                    print_wallet_events(&events, format);
+                }
+                #[cfg(feature = "esplora")]
+                Esplora {
+                    client,
+                    parallel_requests,
+                    backoff,
+                } => {
+                    let update = with_backoff(*backoff, client.sync(request, *parallel_requests))
+                        .await
+                        .map_err(|e| *e)?;
+                    let events = wallet.apply_update_events(update)?;
+                    print_wallet_events(&events, format);
+                }
+                #[cfg(feature = "rpc")]
+                RpcClient { client, .. } => {
+                    let mut emitter = rpc_emitter(wallet, client);
+                    emit_rpc_updates(wallet, client, &mut emitter, &progress, format)?;
+                }
+                #[cfg(feature = "cbf")]
+                KyotoClient { client } => sync_kyoto_client(wallet, client, format)
                     .await
-                    .map_err(|e| *e)?;
-                let events = wallet.apply_update_events(update)?;
-                print_wallet_events(&events);
+                    .map_err(|e| Error::Generic(e.to_string()))?,
             }
-            #[cfg(feature = "rpc")]
-            RpcClient { client } => {
-                let blockchain_info = client.get_blockchain_info()?;
-                let wallet_cp = wallet.latest_checkpoint();
-
-                let emitter_height = wallet_cp.height().saturating_sub(200);
-                let mut emitter = Emitter::new(
-                    client.as_ref(),
-                    wallet_cp,
-                    emitter_height,
-                    wallet
-                        .tx_graph()
-                        .list_canonical_txs(
-                            wallet.local_chain(),
-                            wallet.local_chain().tip().block_id(),
-                            CanonicalizationParams::default(),
-                        )
-                        .filter(|tx| tx.chain_position.is_unconfirmed()),
-                );
 
-                let block_events = wallet.events_helper(|w| {
-                    while let Some(block_event) = emitter.next_block()? {
-                        if block_event.block_height() % 10_000 == 0 {
-                            let percent_done = f64::from(block_event.block_height())
-                                / f64::from(blockchain_info.headers as u32)
-                                * 100f64;
-                            println!(
-                                "Applying block at height: {}, {:.2}% done.",
-                                block_event.block_height(),
-                                percent_done
-                            );
-                        }
+            progress.finish(ctx.state.wallet.latest_checkpoint().height());
+        }
+        let synced = if scan {
+            "Wallet scanned"
+        } else {
+            "Wallet synced"
+        };
 
-                        w.apply_block_connected_to(
-                            &block_event.block,
-                            block_event.block_height(),
-                            block_event.connected_to(),
-                        )?;
-                    }
-                    Ok::<_, Error>(())
-                })?;
-                print_wallet_events(&block_events);
+        #[cfg(any(feature = "electrum", feature = "esplora"))]
+        if let Some((url, verifier)) = verifier {
+            let verification = verify_sync(ctx.state.wallet, url, &verifier).await?;
+            let message = if verification.discrepancies.is_empty() {
+                format!(
+                    "{synced} successfully, {url} agrees on {} transactions and {} UTXOs.",
+                    verification.transactions, verification.utxos
+                )
+            } else {
+                format!(
+                    "{synced}, {url} disagrees on {} transactions or UTXOs.",
+                    verification.discrepancies.len()
+                )
+            };
+            return Ok(SyncResult {
+                message,
+                verification: Some(verification),
+            });
+        }
 
-                let mempool_txs = emitter.mempool()?;
-                let mempool_events = wallet.apply_unconfirmed_txs_events(mempool_txs.update);
-                print_wallet_events(&mempool_events);
+        Ok(SyncResult {
+            message: format!("{synced} successfully."),
+            #[cfg(any(feature = "electrum", feature = "esplora"))]
+            verification: None,
+        })
+    }
+}
 
-                let evicted_events = wallet.apply_evicted_txs_events(mempool_txs.evicted);
-                print_wallet_events(&evicted_events);
-            }
-            #[cfg(feature = "cbf")]
-            KyotoClient { client } => sync_kyoto_client(wallet, client)
+/// An emitter of the blocks from a little before the wallet tip, and of the mempool transactions
+/// that replace or evict the unconfirmed ones of the wallet.
+#[cfg(feature = "rpc")]
+fn rpc_emitter<'c>(
+    wallet: &bdk_wallet::Wallet,
+    client: &'c bdk_bitcoind_rpc::bitcoincore_rpc::Client,
+) -> Emitter<&'c bdk_bitcoind_rpc::bitcoincore_rpc::Client> {
+    let wallet_cp = wallet.latest_checkpoint();
+    let emitter_height = wallet_cp.height().saturating_sub(200);
+    Emitter::new(
+        client,
+        wallet_cp,
+        emitter_height,
+        wallet
+            .tx_graph()
+            .list_canonical_txs(
+                wallet.local_chain(),
+                wallet.local_chain().tip().block_id(),
+                CanonicalizationParams::default(),
+            )
+            .filter(|tx| tx.chain_position.is_unconfirmed()),
+    )
+}
+
+/// Applies the blocks and mempool transactions the `emitter` has not emitted yet.
+#[cfg(feature = "rpc")]
+fn emit_rpc_updates(
+    wallet: &mut bdk_wallet::Wallet,
+    client: &bdk_bitcoind_rpc::bitcoincore_rpc::Client,
+    emitter: &mut Emitter<&bdk_bitcoind_rpc::bitcoincore_rpc::Client>,
+    progress: &Progress,
+    format: EventFormat,
+) -> Result<(), Error> {
+    let blockchain_info = client.get_blockchain_info()?;
+    let block_events = wallet.events_helper(|w| {
+        while let Some(block_event) = emitter.next_block()? {
+            progress.block(block_event.block_height(), blockchain_info.headers as u32);
+
+            w.apply_block_connected_to(
+                &block_event.block,
+                block_event.block_height(),
+                block_event.connected_to(),
+            )?;
+        }
+        Ok::<_, Error>(())
+    })?;
+    print_wallet_events(&block_events, format);
+
+    let mempool_txs = emitter.mempool()?;
+    let mempool_events = wallet.apply_unconfirmed_txs_events(mempool_txs.update);
+    print_wallet_events(&mempool_events, format);
+
+    let evicted_events = wallet.apply_evicted_txs_events(mempool_txs.evicted);
+    print_wallet_events(&evicted_events, format);
+    Ok(())
+}
+
+/// Syncs the revealed scripts of the wallet with the `verifier` without applying the update, and
+/// compares the transactions and UTXOs it finds with the wallet's.
+#[cfg(any(feature = "electrum", feature = "esplora"))]
+async fn verify_sync(
+    wallet: &bdk_wallet::Wallet,
+    url: &str,
+    verifier: &crate::client::BlockchainClient,
+) -> Result<SyncVerification, Error> {
+    let request = wallet.start_sync_with_revealed_spks();
+    let tx_update = match verifier {
+        #[cfg(feature = "electrum")]
+        Electrum { client, batch_size } => client.sync(request, *batch_size, false)?.tx_update,
+        #[cfg(feature = "esplora")]
+        Esplora {
+            client,
+            parallel_requests,
+            backoff,
+        } => {
+            with_backoff(*backoff, client.sync(request, *parallel_requests))
                 .await
-                .map_err(|e| Error::Generic(e.to_string()))?,
+                .map_err(|e| *e)?
+                .tx_update
+        }
+        #[allow(unreachable_patterns)]
+        _ => {
+            return Err(Error::coded(
+                ErrorCode::Config,
+                "Only electrum and esplora servers verify a sync".to_string(),
+            ));
+        }
+    };
+
+    let verifier_txs: BTreeMap<Txid, _> = tx_update
+        .txs
+        .iter()
+        .map(|tx| (tx.compute_txid(), tx))
+        .collect();
+    // Anchors are ordered by height, so the last one of a transaction is its best confirmation.
+    let anchors: BTreeMap<Txid, BlockId> = tx_update
+        .anchors
+        .iter()
+        .map(|(anchor, txid)| (*txid, anchor.block_id))
+        .collect();
+    let status = |block: Option<&BlockId>| match block {
+        Some(block) => format!("confirmed at {} ({})", block.height, block.hash),
+        None => "unconfirmed".to_string(),
+    };
+    let verifier_status = |txid: &Txid| match verifier_txs.contains_key(txid) {
+        true => status(anchors.get(txid)),
+        false => "missing".to_string(),
+    };
+
+    let mut discrepancies = Vec::new();
+    let mut wallet_txids = HashSet::new();
+    for tx in wallet.transactions() {
+        let txid = tx.tx_node.txid;
+        wallet_txids.insert(txid);
+        let block = match &tx.chain_position {
+            ChainPosition::Confirmed { anchor, .. } => Some(anchor.block_id),
+            ChainPosition::Unconfirmed { .. } => None,
+        };
+        if !verifier_txs.contains_key(&txid) || anchors.get(&txid) != block.as_ref() {
+            discrepancies.push(SyncDiscrepancy {
+                txid: txid.to_string(),
+                vout: None,
+                primary: status(block.as_ref()),
+                verifier: verifier_status(&txid),
+            });
+        }
+    }
+    for txid in verifier_txs
+        .keys()
+        .filter(|txid| !wallet_txids.contains(*txid))
+    {
+        discrepancies.push(SyncDiscrepancy {
+            txid: txid.to_string(),
+            vout: None,
+            primary: "missing".to_string(),
+            verifier: verifier_status(txid),
+        });
+    }
+
+    let spenders: BTreeMap<OutPoint, Txid> = verifier_txs
+        .iter()
+        .flat_map(|(txid, tx)| tx.input.iter().map(|txin| (txin.previous_output, *txid)))
+        .collect();
+    let utxos: Vec<_> = wallet.list_unspent().collect();
+    for utxo in &utxos {
+        if let Some(spender) = spenders.get(&utxo.outpoint) {
+            discrepancies.push(SyncDiscrepancy {
+                txid: utxo.outpoint.txid.to_string(),
+                vout: Some(utxo.outpoint.vout),
+                primary: "unspent".to_string(),
+                verifier: format!("spent by {spender}"),
+            });
         }
-        Ok(StatusResult {
-            message: "Wallet synced successfully.".to_string(),
-        })
     }
+
+    Ok(SyncVerification {
+        server: url.to_string(),
+        transactions: wallet_txids.len(),
+        utxos: utxos.len(),
+        discrepancies,
+    })
 }
 
 #[derive(Parser, Debug, Clone, PartialEq, Eq)]
@@ -311,18 +686,57 @@ pub struct BroadcastCommand {
     #[arg(
         env = "BASE64_PSBT",
         long = "psbt",
-        required_unless_present = "tx",
-        conflicts_with = "tx"
+        required_unless_present_any = ["tx", "psbt_file"],
+        conflicts_with_all = ["tx", "psbt_file"]
     )]
     psbt: Option<String>,
-    /// Sets the raw transaction to broadcast.
+    /// Reads the PSBT from a file, raw binary or base64 encoded.
+    #[arg(
+        env = "PSBT_FILE",
+        long = "psbt-file",
+        required_unless_present_any = ["tx", "psbt"],
+        conflicts_with = "tx"
+    )]
+    psbt_file: Option<PathBuf>,
+    /// Sets the raw transaction to broadcast, hex or BBQr encoded.
     #[arg(
         env = "RAWTX",
         long = "tx",
-        required_unless_present = "psbt",
+        required_unless_present_any = ["psbt", "psbt_file"],
         conflicts_with = "psbt"
     )]
     tx: Option<String>,
+    /// Broadcasts through every configured server of the wallet, each electrum or esplora url on
+    /// its own connection, and reports which ones accepted the transaction.
+    #[arg(long = "all-backends")]
+    all_backends: bool,
+    /// Also broadcasts through this electrum (tcp://, ssl://) or esplora (http://, https://)
+    /// server and reports whether it accepted the transaction. Can be repeated or comma separated.
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    #[arg(long = "server", value_delimiter = ',')]
+    servers: Vec<String>,
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+impl BroadcastCommand {
+    /// A `broadcast` of either a finalized base64 `psbt` or a raw hex `tx` through the backend of
+    /// the wallet, as the requests of the servers build it.
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    pub(crate) fn of(psbt: Option<String>, tx: Option<String>) -> Self {
+        Self {
+            psbt,
+            psbt_file: None,
+            tx,
+            all_backends: false,
+            #[cfg(any(feature = "electrum", feature = "esplora"))]
+            servers: Vec::new(),
+        }
+    }
 }
 
 #[cfg(any(
@@ -340,43 +754,690 @@ impl AsyncAppCommand<AppContext<OnlineOperations<'_>>> for BroadcastCommand {
     ) -> Result<Self::Output, Error> {
         let client = ctx.state.client;
 
-        let tx = match (&self.psbt, &self.tx) {
-            (Some(psbt), None) => {
-                let psbt = BASE64_STANDARD
-                    .decode(psbt)
-                    .map_err(|e| Error::Generic(e.to_string()))?;
-                let psbt: Psbt = Psbt::deserialize(&psbt)?;
+        let tx = match (&self.psbt, &self.psbt_file, &self.tx) {
+            (None, None, Some(tx)) => parse_raw_tx(tx)?,
+            (psbt, psbt_file, None) if psbt.is_some() || psbt_file.is_some() => {
+                let (psbt, _) = parse_psbt_input(psbt.as_deref(), psbt_file.as_deref(), false)?;
                 is_final(&psbt)?;
                 psbt.extract_tx()?
             }
-            (None, Some(tx)) => {
-                let tx_bytes = Vec::<u8>::from_hex(tx)?;
-                Transaction::consensus_decode(&mut tx_bytes.as_slice())?
-            }
-            (Some(_), Some(_)) => {
-                return Err(Error::Generic(
+            (_, _, Some(_)) => {
+                return Err(Error::coded(
+                    ErrorCode::InvalidArgument,
                     "Both `psbt` and `tx` options are not allowed".into(),
                 ));
             }
-            (None, None) => {
-                return Err(Error::Generic(
+            _ => {
+                return Err(Error::coded(
+                    ErrorCode::InvalidArgument,
                     "Must provide either a `psbt` or `tx` to broadcast".into(),
                 ));
             }
         };
+        confirm(
+            "the broadcast",
+            &broadcast_summary(&tx, ctx.state.wallet, ctx.network),
+        )?;
+
+        #[cfg(any(feature = "electrum", feature = "esplora"))]
+        let multiple = self.all_backends || !self.servers.is_empty();
+        #[cfg(not(any(feature = "electrum", feature = "esplora")))]
+        let multiple = self.all_backends;
+        if !multiple {
+            client.check_network(ctx.network).await?;
+            let txid: Txid = client.broadcast(tx).await?;
+            return Ok(TransactionResult {
+                txid: txid.to_string(),
+                endpoints: Vec::new(),
+            });
+        }
+
+        let (wallet_opts, _) = load_wallet_config(&ctx.datadir, &ctx.state.wallet_name)?;
+        #[cfg(any(feature = "electrum", feature = "esplora"))]
+        let configured = match self.all_backends {
+            true => server_urls(&wallet_opts),
+            false => Vec::new(),
+        };
+        #[cfg(not(any(feature = "electrum", feature = "esplora")))]
+        let configured: Vec<String> = Vec::new();
+
+        let mut endpoints = Vec::new();
+        // The client of the command, unless replaced by each configured server on its own. rpc and
+        // cbf wallets have it as their single backend.
+        let own_backend = configured.is_empty();
+        if own_backend {
+            let server = format!("{:?}", wallet_opts.client_type).to_lowercase();
+            #[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
+            let server = match wallet_opts.url.is_empty() {
+                true => server,
+                false => wallet_opts.url.join(","),
+            };
+            let result = match client.check_network(ctx.network).await {
+                Ok(()) => client.broadcast(tx.clone()).await,
+                Err(e) => Err(e),
+            };
+            endpoints.push(broadcast_endpoint(server, result));
+        }
+        #[cfg(any(feature = "electrum", feature = "esplora"))]
+        {
+            let mut urls = configured;
+            for url in &self.servers {
+                let own_url = own_backend && wallet_opts.url.contains(url);
+                if !own_url && !urls.contains(url) {
+                    urls.push(url.clone());
+                }
+            }
+            let datadir = ctx.datadir.join(&ctx.state.wallet_name);
+            for url in urls {
+                let result =
+                    match new_url_client(&wallet_opts, &url, ctx.state.wallet, datadir.clone()) {
+                        Ok(client) => match client.check_network(ctx.network).await {
+                            Ok(()) => client.broadcast(tx.clone()).await,
+                            Err(e) => Err(e),
+                        },
+                        Err(e) => Err(e),
+                    };
+                endpoints.push(broadcast_endpoint(url, result));
+            }
+        }
 
-        let txid: Txid = client.broadcast(tx).await?;
+        if !endpoints.iter().any(|endpoint| endpoint.accepted) {
+            let errors: Vec<String> = endpoints
+                .iter()
+                .map(|endpoint| {
+                    format!(
+                        "{}: {}",
+                        endpoint.server,
+                        endpoint.error.as_deref().unwrap_or_default()
+                    )
+                })
+                .collect();
+            return Err(Error::coded(
+                ErrorCode::Broadcast,
+                format!("No backend accepted the transaction: {}", errors.join("; ")),
+            ));
+        }
 
         Ok(TransactionResult {
-            txid: txid.to_string(),
+            txid: tx.compute_txid().to_string(),
+            endpoints,
+        })
+    }
+}
+
+/// The outputs and the fee of `tx`, shown before broadcasting it.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+pub(crate) fn broadcast_summary(
+    tx: &Transaction,
+    wallet: &bdk_wallet::Wallet,
+    network: bdk_wallet::bitcoin::Network,
+) -> String {
+    let mut lines = vec![format!("Broadcasting transaction {}:", tx.compute_txid())];
+    for output in &tx.output {
+        let recipient = bdk_wallet::bitcoin::Address::from_script(&output.script_pubkey, network)
+            .map(|address| address.to_string())
+            .unwrap_or_else(|_| output.script_pubkey.to_hex_string());
+        let owner = match wallet.is_mine(output.script_pubkey.clone()) {
+            true => " (this wallet)",
+            false => "",
+        };
+        lines.push(format!(
+            "  {} to {recipient}{owner}",
+            amount::display(output.value.to_sat())
+        ));
+    }
+    if let Ok(fee) = wallet.calculate_fee(tx) {
+        lines.push(format!("  fee {}", amount::display(fee.to_sat())));
+    }
+    lines.join("\n")
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct RebroadcastCommand {
+    /// Re-submits only this unconfirmed wallet transaction.
+    #[arg(env = "TXID", long = "txid")]
+    txid: Option<Txid>,
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+impl AsyncAppCommand<AppContext<OnlineOperations<'_>>> for RebroadcastCommand {
+    type Output = RebroadcastResult;
+
+    async fn execute(
+        &self,
+        ctx: &mut AppContext<OnlineOperations<'_>>,
+    ) -> Result<Self::Output, Error> {
+        let wallet = &ctx.state.wallet;
+        let mut pending: Vec<Arc<Transaction>> = match &self.txid {
+            Some(txid) => {
+                let tx = wallet.get_tx(*txid).ok_or_else(|| {
+                    Error::coded(
+                        ErrorCode::TxNotFound,
+                        format!("Transaction {txid} is not in the wallet"),
+                    )
+                })?;
+                if tx.chain_position.is_confirmed() {
+                    return Err(Error::coded(
+                        ErrorCode::TxAlreadyConfirmed,
+                        format!("Transaction {txid} is already confirmed"),
+                    ));
+                }
+                vec![tx.tx_node.tx.clone()]
+            }
+            None => wallet
+                .transactions()
+                .filter(|tx| tx.chain_position.is_unconfirmed())
+                .map(|tx| tx.tx_node.tx.clone())
+                .collect(),
+        };
+
+        // A child reaching the backend before its parent would be rejected for missing inputs.
+        let mut ordered = Vec::with_capacity(pending.len());
+        while !pending.is_empty() {
+            let txids: HashSet<Txid> = pending.iter().map(|tx| tx.compute_txid()).collect();
+            let (parents, children) = pending.into_iter().partition::<Vec<_>, _>(|tx| {
+                !tx.input
+                    .iter()
+                    .any(|input| txids.contains(&input.previous_output.txid))
+            });
+            ordered.extend(parents);
+            pending = children;
+        }
+
+        if !ordered.is_empty() {
+            ctx.state.client.check_network(ctx.network).await?;
+        }
+        let mut transactions = Vec::with_capacity(ordered.len());
+        for tx in ordered {
+            let txid = tx.compute_txid();
+            let (status, error) = match ctx.state.client.broadcast((*tx).clone()).await {
+                Ok(_) => (RebroadcastStatus::Accepted, None),
+                Err(e) if already_known(&e.to_string()) => (RebroadcastStatus::AlreadyKnown, None),
+                Err(e) => (RebroadcastStatus::Rejected, Some(e.to_string())),
+            };
+            transactions.push(RebroadcastedTx {
+                txid: txid.to_string(),
+                status,
+                error,
+            });
+        }
+
+        Ok(RebroadcastResult { transactions })
+    }
+}
+
+/// Whether a broadcast error means the backend already has the transaction, in its mempool or in
+/// a block. Electrum and esplora servers pass on the errors of Bitcoin Core.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+fn already_known(error: &str) -> bool {
+    let error = error.to_lowercase();
+    [
+        "txn-already-known",
+        "txn-already-in-mempool",
+        "already in block chain",
+        "outputs already in utxo set",
+    ]
+    .iter()
+    .any(|known| error.contains(known))
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+fn broadcast_endpoint(server: String, result: Result<Txid, Error>) -> BroadcastEndpoint {
+    BroadcastEndpoint {
+        server,
+        accepted: result.is_ok(),
+        error: result.err().map(|e| e.to_string()),
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct WatchCommand {
+    /// Seconds between two syncs. With ZMQ notifications, the syncs only catch up with what the
+    /// notifications missed.
+    #[arg(
+        env = "WATCH_INTERVAL",
+        long = "interval",
+        default_value_t = 30,
+        value_parser = RangedU64ValueParser::<u64>::new().range(1..)
+    )]
+    interval: u64,
+    /// ZMQ endpoint where Bitcoin Core publishes the raw blocks (`zmqpubrawblock`). Defaults to
+    /// the one of the `bitcoin.conf` of `--node`.
+    #[cfg(feature = "rpc")]
+    #[arg(env = "ZMQ_BLOCK", long = "zmq-block", value_name = "ENDPOINT")]
+    zmq_block: Option<String>,
+    /// ZMQ endpoint where Bitcoin Core publishes the raw transactions (`zmqpubrawtx`). Defaults
+    /// to the one of the `bitcoin.conf` of `--node`.
+    #[cfg(feature = "rpc")]
+    #[arg(env = "ZMQ_TX", long = "zmq-tx", value_name = "ENDPOINT")]
+    zmq_tx: Option<String>,
+    /// Prints each wallet event on stdout as a line of JSON, rather than its summary on stderr.
+    /// The confirmation counts of the transactions are also reported on each new block, until
+    /// they reach six.
+    #[arg(long = "ndjson")]
+    pub ndjson: bool,
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+impl AsyncAppCommand<AppContext<OnlineOperations<'_>>> for WatchCommand {
+    type Output = StatusResult;
+
+    async fn execute(
+        &self,
+        ctx: &mut AppContext<OnlineOperations<'_>>,
+    ) -> Result<Self::Output, Error> {
+        // Listen for Ctrl-C right away, so it also stops a watch busy syncing.
+        let (stop_sender, mut stop) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                let _ = stop_sender.send(());
+            }
+        });
+        let interval = Duration::from_secs(self.interval);
+        ctx.state.client.check_network(ctx.network).await?;
+        if self.ndjson {
+            ctx.state.events = EventFormat::Ndjson;
+        }
+        let mut tip = ctx.state.wallet.latest_checkpoint().height();
+
+        // Copied out of the context, borrowed mutably to persist the wallet after each sync.
+        let backend = ctx.state.client;
+        match backend {
+            #[cfg(feature = "rpc")]
+            RpcClient { client, .. } => {
+                let (mut zmq_block, mut zmq_tx) = (self.zmq_block.clone(), self.zmq_tx.clone());
+                if zmq_block.is_none() && zmq_tx.is_none() {
+                    let (wallet_opts, _) =
+                        load_wallet_config(&ctx.datadir, &ctx.state.wallet_name)?;
+                    if let Some(node) = &wallet_opts.node {
+                        (zmq_block, zmq_tx) = node_zmq(node, ctx.network)?;
+                    }
+                }
+                let mut notifications = match (&zmq_block, &zmq_tx) {
+                    (None, None) => None,
+                    (block, tx) => Some(zmq::subscribe(block.as_deref(), tx.as_deref()).await?),
+                };
+                match &notifications {
+                    Some(_) => log::info!(
+                        "Watching the wallet with the ZMQ notifications of the node, syncing every {}s. Press Ctrl-C to stop.",
+                        self.interval
+                    ),
+                    None => log::info!(
+                        "Watching the wallet, syncing every {}s. Press Ctrl-C to stop.",
+                        self.interval
+                    ),
+                }
+
+                let progress = Progress::new(Some(ProgressFormat::None));
+                let format = ctx.state.events;
+                let mut emitter = rpc_emitter(ctx.state.wallet, client);
+                'watch: loop {
+                    let wallet = &mut *ctx.state.wallet;
+                    if let Err(e) =
+                        emit_rpc_updates(wallet, client, &mut emitter, &progress, format)
+                    {
+                        log::warn!("Sync failed: {e}");
+                    }
+                    if self.ndjson {
+                        print_confirmations(wallet, &mut tip);
+                    }
+                    persist_watched(ctx);
+                    loop {
+                        tokio::select! {
+                            _ = &mut stop => break 'watch,
+                            _ = tokio::time::sleep(interval) => break,
+                            notification = next_notification(&mut notifications) => match notification {
+                                Some(Ok(Notification::Block)) => break,
+                                Some(Ok(Notification::Tx(tx))) => {
+                                    let wallet = &mut *ctx.state.wallet;
+                                    if is_relevant(wallet, &tx) {
+                                        let events =
+                                            wallet.apply_unconfirmed_txs_events([(tx, unix_now())]);
+                                        print_wallet_events(&events, format);
+                                        persist_watched(ctx);
+                                    }
+                                }
+                                Some(Err(e)) => {
+                                    log::warn!("{e}, syncing every {}s instead.", self.interval);
+                                    notifications = None;
+                                }
+                                None => notifications = None,
+                            },
+                        }
+                    }
+                }
+            }
+            #[allow(unreachable_patterns)]
+            _ => {
+                log::info!(
+                    "Watching the wallet, syncing every {}s. Press Ctrl-C to stop.",
+                    self.interval
+                );
+                let sync = SyncCommand::quiet();
+                loop {
+                    if let Err(e) = sync.execute(ctx).await {
+                        log::warn!("Sync failed: {e}");
+                    }
+                    if self.ndjson {
+                        print_confirmations(&ctx.state.wallet, &mut tip);
+                    }
+                    persist_watched(ctx);
+                    tokio::select! {
+                        _ = &mut stop => break,
+                        _ = tokio::time::sleep(interval) => {}
+                    }
+                }
+            }
+        }
+
+        Ok(StatusResult {
+            message: format!(
+                "Stopped watching at height {}.",
+                ctx.state.wallet.latest_checkpoint().height()
+            ),
+        })
+    }
+}
+
+/// Persists the changes of a watched wallet, so that they survive the watch being killed.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+fn persist_watched(ctx: &mut AppContext<OnlineOperations<'_>>) {
+    if let Err(e) = ctx.state.persist() {
+        log::warn!("Cannot persist the wallet: {e}");
+    }
+}
+
+/// Confirmations of a transaction past which `watch --ndjson` stops reporting them.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+const REPORTED_CONFIRMATIONS: u32 = 6;
+
+/// Prints the confirmation counts of the recently confirmed transactions when the tip moved past
+/// `tip`, as lines of JSON.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+fn print_confirmations(wallet: &bdk_wallet::Wallet, tip: &mut u32) {
+    let height = wallet.latest_checkpoint().height();
+    if height == *tip {
+        return;
+    }
+    *tip = height;
+    for tx in wallet.transactions() {
+        let ChainPosition::Confirmed { anchor, .. } = tx.chain_position else {
+            continue;
+        };
+        let confirmations = height.saturating_sub(anchor.block_id.height) + 1;
+        if confirmations <= REPORTED_CONFIRMATIONS {
+            print_ndjson(&serde_json::json!({
+                "event": "confirmations",
+                "txid": tx.tx_node.txid,
+                "height": anchor.block_id.height,
+                "confirmations": confirmations,
+            }));
+        }
+    }
+}
+
+/// The next ZMQ notification, or never when not subscribed.
+#[cfg(feature = "rpc")]
+pub(crate) async fn next_notification(
+    notifications: &mut Option<UnboundedReceiver<Result<Notification, Error>>>,
+) -> Option<Result<Notification, Error>> {
+    match notifications {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Whether `tx` pays to or spends from the wallet.
+#[cfg(feature = "rpc")]
+fn is_relevant(wallet: &bdk_wallet::Wallet, tx: &Transaction) -> bool {
+    tx.output
+        .iter()
+        .any(|output| wallet.is_mine(output.script_pubkey.clone()))
+        || tx.input.iter().any(|input| {
+            wallet
+                .tx_graph()
+                .get_txout(input.previous_output)
+                .is_some_and(|txout| wallet.is_mine(txout.script_pubkey.clone()))
+        })
+}
+
+#[cfg(feature = "rpc")]
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct EstimateFeeCommand {
+    /// Number of blocks the transaction should confirm within.
+    #[arg(env = "TARGET_BLOCKS", long = "target-blocks", default_value_t = 6)]
+    target_blocks: u16,
+    /// Estimates with the blockchain backend of the wallet or a mempool.space instance.
+    #[arg(long = "provider", value_enum, default_value_t)]
+    provider: FeeProvider,
+    /// Base URL of the mempool.space API, for self-hosted instances. Defaults to the public
+    /// instance of the wallet network.
+    #[arg(env = "MEMPOOL_URL", long = "mempool-url")]
+    mempool_url: Option<String>,
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+impl AsyncAppCommand<AppContext<OnlineOperations<'_>>> for EstimateFeeCommand {
+    type Output = FeeEstimateResult;
+
+    async fn execute(
+        &self,
+        ctx: &mut AppContext<OnlineOperations<'_>>,
+    ) -> Result<Self::Output, Error> {
+        let fee_rate = match self.provider {
+            FeeProvider::Backend => ctx.state.client.estimate_fee(self.target_blocks).await?,
+            FeeProvider::Mempool => {
+                let (wallet_opts, _) = load_wallet_config(&ctx.datadir, &ctx.state.wallet_name)?;
+                mempool_fee_rate(
+                    self.mempool_url.as_deref(),
+                    ctx.network,
+                    self.target_blocks,
+                    &wallet_opts,
+                )
+                .await?
+            }
+        };
+
+        Ok(FeeEstimateResult {
+            provider: self.provider.to_string(),
+            target_blocks: self.target_blocks,
+            fee_rate,
+        })
+    }
+}
+
+#[derive(Parser, Debug, PartialEq, Clone, Eq)]
+pub struct FaucetCommand {
+    /// URL of the faucet API, taking a `POST` of `{"address": .., "sats": ..}`. Defaults to the
+    /// faucet of Mutinynet on its signet.
+    #[arg(env = "FAUCET_URL", long = "faucet-url")]
+    faucet_url: Option<String>,
+    /// Bearer token of the faucet API, for faucets behind a login.
+    #[arg(env = "FAUCET_TOKEN", long = "faucet-token", hide_env_values = true)]
+    faucet_token: Option<String>,
+    /// Amount to request, such as `100000sat` or `0.001btc`, in the unit of `--unit` when bare.
+    #[arg(
+        long = "amount",
+        default_value = "100000sat",
+        value_parser = crate::utils::amount::parse_amount
+    )]
+    amount: u64,
+    /// Seconds to wait for the faucet transaction to reach the wallet.
+    #[arg(long = "timeout", default_value_t = 300)]
+    timeout: u64,
+    /// Returns once the faucet answered, without waiting for its transaction.
+    #[arg(long = "no-wait")]
+    no_wait: bool,
+}
+
+/// Seconds between two syncs of `faucet` waiting for its transaction.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+const FAUCET_POLL_SECS: u64 = 5;
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+impl AsyncAppCommand<AppContext<OnlineOperations<'_>>> for FaucetCommand {
+    type Output = FaucetResult;
+
+    async fn execute(
+        &self,
+        ctx: &mut AppContext<OnlineOperations<'_>>,
+    ) -> Result<Self::Output, Error> {
+        let (wallet_opts, _) = load_wallet_config(&ctx.datadir, &ctx.state.wallet_name)?;
+        let url = match &self.faucet_url {
+            Some(_) if ctx.network == bdk_wallet::bitcoin::Network::Bitcoin => {
+                return Err(Error::coded(
+                    ErrorCode::NetworkMismatch,
+                    "There are no faucets on mainnet".to_string(),
+                ));
+            }
+            Some(url) => url.as_str(),
+            None => default_faucet_url(ctx.network, wallet_opts.signet_challenge.as_deref())?,
+        };
+
+        let known: HashSet<Txid> = ctx
+            .state
+            .wallet
+            .transactions()
+            .map(|tx| tx.tx_node.txid)
+            .collect();
+        let address = ctx
+            .state
+            .wallet
+            .next_unused_address(bdk_wallet::KeychainKind::External)
+            .address;
+        let txid = request_coins(
+            url,
+            &address.to_string(),
+            self.amount,
+            self.faucet_token.as_deref(),
+            &wallet_opts,
+        )
+        .await?;
+        log::info!(
+            "The faucet sends {} to {address}.",
+            amount::display(self.amount)
+        );
+
+        let mut received = false;
+        if !self.no_wait {
+            let script = address.script_pubkey();
+            let deadline = tokio::time::Instant::now() + Duration::from_secs(self.timeout);
+            let sync = SyncCommand::quiet();
+            loop {
+                sync.execute(ctx).await?;
+                // Without a txid from the faucet, any new transaction paying the address is its.
+                received = ctx.state.wallet.transactions().any(|tx| match txid {
+                    Some(txid) => tx.tx_node.txid == txid,
+                    None => {
+                        !known.contains(&tx.tx_node.txid)
+                            && tx.tx_node.output.iter().any(|o| o.script_pubkey == script)
+                    }
+                });
+                if received {
+                    break;
+                }
+                if tokio::time::Instant::now() >= deadline {
+                    return Err(Error::coded(
+                        ErrorCode::Backend,
+                        format!(
+                            "The faucet transaction{} did not reach the wallet within {}s",
+                            txid.map(|txid| format!(" {txid}")).unwrap_or_default(),
+                            self.timeout
+                        ),
+                    ));
+                }
+                tokio::time::sleep(Duration::from_secs(FAUCET_POLL_SECS)).await;
+            }
+        }
+
+        Ok(FaucetResult {
+            faucet: url.to_string(),
+            address: address.to_string(),
+            amount: self.amount,
+            txid: txid.map(|txid| txid.to_string()),
+            received,
         })
     }
 }
 
 #[derive(Parser, Debug, Clone, PartialEq, Eq)]
 pub struct ReceivePayjoinCommand {
-    /// Amount to be received in sats.
-    #[arg(env = "PAYJOIN_AMOUNT", long = "amount", required = true)]
+    /// Amount to be received, such as `150000sat` or `0.0015btc`, in the unit of `--unit` when
+    /// bare.
+    #[arg(
+        env = "PAYJOIN_AMOUNT",
+        long = "amount",
+        required = true,
+        value_parser = crate::utils::amount::parse_amount
+    )]
     amount: u64,
     /// Payjoin directory which will be used to store the PSBTs which are pending action
     /// from one of the parties.
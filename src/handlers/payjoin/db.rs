@@ -1,5 +1,5 @@
 use std::fmt;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex, MutexGuard};
 
 use bdk_wallet::rusqlite::{Connection, ToSql, params, types::ToSqlOutput};
@@ -10,7 +10,9 @@ use payjoin::persist::SessionPersister;
 use payjoin::receive::v2::SessionEvent as ReceiverSessionEvent;
 use payjoin::send::v2::SessionEvent as SenderSessionEvent;
 
-use crate::error::BDKCliError;
+use crate::config::WalletConfig;
+use crate::error::{BDKCliError, ErrorCode};
+use crate::persister::open_sqlite;
 use crate::utils::prepare_home_dir;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -66,9 +68,16 @@ pub fn open_payjoin_db(
     datadir: Option<PathBuf>,
     wallet_name: &str,
 ) -> std::result::Result<Arc<Database>, BDKCliError> {
-    let wallet_dir = prepare_home_dir(datadir)?.join(wallet_name);
-    std::fs::create_dir_all(&wallet_dir).map_err(|e| BDKCliError::Generic(e.to_string()))?;
-    let db = Arc::new(Database::create(wallet_dir.join(DB_FILENAME))?);
+    let home_dir = prepare_home_dir(datadir)?;
+    let wallet_dir = home_dir.join(wallet_name);
+    std::fs::create_dir_all(&wallet_dir)
+        .map_err(|e| BDKCliError::coded(ErrorCode::Io, e.to_string()))?;
+    // The sessions are as private as the wallet history, so they share its encryption.
+    let encrypted = WalletConfig::load(&home_dir)?
+        .and_then(|config| config.wallets.get(wallet_name)?.encrypt_db)
+        .unwrap_or(false);
+    let conn = open_sqlite(&wallet_dir.join(DB_FILENAME), encrypted)?;
+    let db = Arc::new(Database::new(conn)?);
     db.prune_expired_sessions()?;
     Ok(db)
 }
@@ -87,8 +96,7 @@ pub struct Database {
 }
 
 impl Database {
-    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
-        let conn = Connection::open(path.as_ref())?;
+    pub fn new(conn: Connection) -> Result<Self> {
         Self::init_schema(&conn)?;
         Ok(Self {
             conn: Mutex::new(conn),
@@ -557,7 +565,8 @@ mod tests {
 
     #[test]
     fn insert_input_seen_before_reports_replays() {
-        let db = Database::create(":memory:").expect("in-memory database should open");
+        let db = Database::new(Connection::open_in_memory().unwrap())
+            .expect("in-memory database should open");
         let input = OutPoint::null();
 
         assert!(
@@ -574,7 +583,10 @@ mod tests {
 
     #[test]
     fn persisters_round_trip_events_and_transition_sessions() {
-        let db = Arc::new(Database::create(":memory:").expect("in-memory database should open"));
+        let db = Arc::new(
+            Database::new(Connection::open_in_memory().unwrap())
+                .expect("in-memory database should open"),
+        );
 
         let sender = SenderPersister::new(db.clone(), sample_receiver_pubkey())
             .expect("sender session should be created");
@@ -656,7 +668,8 @@ mod tests {
 
     #[test]
     fn prune_expired_sessions_drops_stale_send_and_receive_rows() {
-        let db = Database::create(":memory:").expect("in-memory database should open");
+        let db = Database::new(Connection::open_in_memory().unwrap())
+            .expect("in-memory database should open");
         let stale_timestamp = now() - SESSION_RETENTION_SECS - 1;
         let fresh_timestamp = now();
         let receiver_pubkey = sample_receiver_pubkey();
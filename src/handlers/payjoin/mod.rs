@@ -1,5 +1,5 @@
 use crate::client::BlockchainClient;
-use crate::error::BDKCliError as Error;
+use crate::error::{BDKCliError as Error, ErrorCode};
 use bdk_wallet::{
     SignOptions, Wallet,
     bitcoin::{FeeRate, Psbt, Txid, consensus::encode::serialize_hex},
@@ -131,7 +131,12 @@ impl<'a> PayjoinManager<'a> {
             .into_iter()
             .map(|s| url::Url::parse(&s))
             .collect::<Result<_, _>>()
-            .map_err(|e| Error::Generic(format!("Failed to parse one or more OHTTP URLs: {e}")))?;
+            .map_err(|e| {
+                Error::coded(
+                    ErrorCode::PayjoinUrl,
+                    format!("Failed to parse one or more OHTTP URLs: {e}"),
+                )
+            })?;
 
         self.relay_manager.configure(ohttp_relays)?;
         let ohttp_keys = self.relay_manager.fetch_ohttp_keys(&directory).await?;
@@ -149,14 +154,14 @@ impl<'a> PayjoinManager<'a> {
                 .build()
                 .save(&persister)
                 .map_err(|e| {
-                    Error::Generic(format!(
-                        "Failed to persister the receiver after initialization: {e}"
-                    ))
+                    Error::coded(
+                        ErrorCode::PayjoinDatabase,
+                        format!("Failed to persister the receiver after initialization: {e}"),
+                    )
                 })?;
 
         let pj_uri = receiver.pj_uri();
-        println!("Request Payjoin by sharing this Payjoin Uri:");
-        println!("{pj_uri}");
+        log::info!("Request Payjoin by sharing this Payjoin Uri: {pj_uri}");
 
         self.proceed_receiver_session(
             ReceiveSession::Initialized(receiver.clone()),
@@ -176,18 +181,31 @@ impl<'a> PayjoinManager<'a> {
         ohttp_relays: Vec<String>,
         blockchain_client: &BlockchainClient,
     ) -> Result<String, Error> {
-        let uri = payjoin::Uri::try_from(uri)
-            .map_err(|e| Error::Generic(format!("Failed parsing to Payjoin URI: {}", e)))?;
+        let uri = payjoin::Uri::try_from(uri).map_err(|e| {
+            Error::coded(
+                ErrorCode::PayjoinUrl,
+                format!("Failed parsing to Payjoin URI: {}", e),
+            )
+        })?;
         let uri = uri.require_network(self.wallet.network()).map_err(|e| {
-            Error::Generic(format!("Failed setting the right network for the URI: {e}"))
+            Error::coded(
+                ErrorCode::NetworkMismatch,
+                format!("Failed setting the right network for the URI: {e}"),
+            )
+        })?;
+        let uri = uri.check_pj_supported().map_err(|e| {
+            Error::coded(
+                ErrorCode::PayjoinUrl,
+                format!("URI does not support Payjoin: {}", e),
+            )
         })?;
-        let uri = uri
-            .check_pj_supported()
-            .map_err(|e| Error::Generic(format!("URI does not support Payjoin: {}", e)))?;
 
-        let sats = uri
-            .amount
-            .ok_or_else(|| Error::Generic("Amount is not specified in the URI.".to_string()))?;
+        let sats = uri.amount.ok_or_else(|| {
+            Error::coded(
+                ErrorCode::PayjoinUrl,
+                "Amount is not specified in the URI.".to_string(),
+            )
+        })?;
 
         let fee_rate = FeeRate::from_sat_per_vb(fee_rate).expect("Provided fee rate is not valid.");
 
@@ -204,7 +222,8 @@ impl<'a> PayjoinManager<'a> {
             .wallet
             .sign(&mut original_psbt, SignOptions::default())?
         {
-            return Err(Error::Generic(
+            return Err(Error::coded(
+                ErrorCode::SigningFailed,
                 "Failed to sign and finalize the original PSBT.".to_string(),
             ));
         }
@@ -227,7 +246,10 @@ impl<'a> PayjoinManager<'a> {
                     .map(|s| url::Url::parse(&s))
                     .collect::<Result<_, _>>()
                     .map_err(|e| {
-                        Error::Generic(format!("Failed to parse one or more OHTTP URLs: {e}"))
+                        Error::coded(
+                            ErrorCode::PayjoinUrl,
+                            format!("Failed to parse one or more OHTTP URLs: {e}"),
+                        )
                     })?;
 
                 self.relay_manager.configure(ohttp_relays)?;
@@ -253,9 +275,12 @@ impl<'a> PayjoinManager<'a> {
                     let sender_persister = SenderPersister::from_id(self.db.clone(), session_id);
                     let (send_session, _) =
                         replay_sender_event_log(&sender_persister).map_err(|e| {
-                            Error::Generic(format!("Failed to replay sender event log: {e:?}"))
+                            Error::coded(
+                                ErrorCode::PayjoinDatabase,
+                                format!("Failed to replay sender event log: {e:?}"),
+                            )
                         })?;
-                    println!("Resuming existing sender session");
+                    log::info!("Resuming existing sender session");
                     (send_session, sender_persister)
                 } else {
                     let persister = {
@@ -267,9 +292,12 @@ impl<'a> PayjoinManager<'a> {
                         .build_recommended(fee_rate)?
                         .save(&persister)
                         .map_err(|e| {
-                            Error::Generic(format!(
-                                "Failed to save the Payjoin v2 sender in the persister: {e}"
-                            ))
+                            Error::coded(
+                                ErrorCode::PayjoinDatabase,
+                                format!(
+                                    "Failed to save the Payjoin v2 sender in the persister: {e}"
+                                ),
+                            )
                         })?;
 
                     (SendSession::WithReplyKey(sender), persister)
@@ -344,7 +372,10 @@ impl<'a> PayjoinManager<'a> {
                     .await
             }
             ReceiveSession::HasReplyableError(error) => self.handle_error(error, persister).await,
-            ReceiveSession::Closed(_) => Err(Error::Generic("Session closed".to_string())),
+            ReceiveSession::Closed(_) => Err(Error::coded(
+                ErrorCode::Payjoin,
+                "Session closed".to_string(),
+            )),
         }
     }
 
@@ -365,7 +396,9 @@ impl<'a> PayjoinManager<'a> {
                 .save(persister);
             match state_transition {
                 Ok(OptionalTransitionOutcome::Progress(next_state)) => {
-                    println!("Got a request from the sender. Responding with a Payjoin proposal.");
+                    log::info!(
+                        "Got a request from the sender. Responding with a Payjoin proposal."
+                    );
                     break next_state;
                 }
                 Ok(OptionalTransitionOutcome::Stasis(current_state)) => {
@@ -373,9 +406,12 @@ impl<'a> PayjoinManager<'a> {
                     continue;
                 }
                 Err(e) => {
-                    return Err(Error::Generic(format!(
-                        "Error occurred when polling for Payjoin proposal from the directory: {e}"
-                    )));
+                    return Err(Error::coded(
+                        ErrorCode::Payjoin,
+                        format!(
+                            "Error occurred when polling for Payjoin proposal from the directory: {e}"
+                        ),
+                    ));
                 }
             }
         };
@@ -399,16 +435,13 @@ impl<'a> PayjoinManager<'a> {
             .assume_interactive_receiver()
             .save(persister)
             .map_err(|e| {
-                Error::Generic(format!(
+                Error::coded(ErrorCode::PayjoinDatabase, format!(
                     "Error occurred when saving after assuming interactive receiver and not checking proposal broadcastability: {e}"
                 ))
             })?;
 
-        println!(
-            "Checking whether the original proposal can be broadcasted itself is not supported. If the Payjoin fails, manually fall back to the transaction below."
-        );
-        println!(
-            "{}",
+        log::warn!(
+            "Checking whether the original proposal can be broadcasted itself is not supported. If the Payjoin fails, manually fall back to this transaction: {}",
             serialize_hex(&next_receiver_typestate.extract_tx_to_schedule_broadcast())
         );
 
@@ -495,7 +528,7 @@ impl<'a> PayjoinManager<'a> {
         // to do that. This is a very simple implementation of Payjoin so we are just going
         // to commit to the existing outputs which the sender included in the original proposal.
         let next_receiver_typestate = receiver.commit_outputs().save(persister).map_err(|e| {
-            Error::Generic(format!(
+            Error::coded(ErrorCode::PayjoinDatabase, format!(
                 "Error occurred when saving after committing to the outputs in the proposal: {e}"
             ))
         })?;
@@ -539,7 +572,7 @@ impl<'a> PayjoinManager<'a> {
         let next_receiver_typestate = receiver.contribute_inputs(vec![selected_input])?
             .commit_inputs().save(persister)
             .map_err(|e| {
-                Error::Generic(format!("Error occurred when saving after committing to the inputs after receiver contribution: {e}"))
+                Error::coded(ErrorCode::PayjoinDatabase, format!("Error occurred when saving after committing to the inputs after receiver contribution: {e}"))
             })?;
 
         self.apply_fee_range(
@@ -607,7 +640,7 @@ impl<'a> PayjoinManager<'a> {
         let next_receiver_typestate = receiver
             .process_response(&res.bytes().await?, ctx)
             .save(persister)?;
-        println!(
+        log::info!(
             "Response successful. TXID: {}",
             payjoin_psbt.extract_tx_unchecked_fee_rate().compute_txid()
         );
@@ -632,7 +665,7 @@ impl<'a> PayjoinManager<'a> {
         let sync_interval = tokio::time::Duration::from_secs(3);
         let timeout_duration = tokio::time::Duration::from_secs(15);
 
-        println!(
+        log::info!(
             "Polling for Payjoin transaction broadcast. This may take up to {} seconds...",
             timeout_duration.as_secs()
         );
@@ -665,7 +698,7 @@ impl<'a> PayjoinManager<'a> {
                             .save(persister);
 
                         if let Ok(OptionalTransitionOutcome::Progress(_)) = check_result {
-                            println!("Payjoin transaction detected in the mempool!");
+                            log::info!("Payjoin transaction detected in the mempool!");
                             return Ok(());
                         }
                         // For Stasis or Err, continue polling (implicit - falls through to next loop iteration)
@@ -681,10 +714,13 @@ impl<'a> PayjoinManager<'a> {
 
         match result {
             Ok(ok) => ok,
-            Err(_) => Err(Error::Generic(format!(
-                "Timeout waiting for Payjoin transaction broadcast after {:?}. Check the state of the transaction manually after running the sync command.",
-                timeout_duration
-            ))),
+            Err(_) => Err(Error::coded(
+                ErrorCode::Payjoin,
+                format!(
+                    "Timeout waiting for Payjoin transaction broadcast after {:?}. Check the state of the transaction manually after running the sync command.",
+                    timeout_duration
+                ),
+            )),
         }
     }
 
@@ -700,10 +736,10 @@ impl<'a> PayjoinManager<'a> {
         let err_bytes = match err_response.bytes().await {
             Ok(bytes) => bytes,
             Err(e) => {
-                return Err(Error::Generic(format!(
-                    "Failed to get error response bytes: {}",
-                    e
-                )));
+                return Err(Error::coded(
+                    ErrorCode::Payjoin,
+                    format!("Failed to get error response bytes: {}", e),
+                ));
             }
         };
 
@@ -711,10 +747,10 @@ impl<'a> PayjoinManager<'a> {
             .process_error_response(&err_bytes, err_ctx)
             .save(persister)
         {
-            return Err(Error::Generic(format!(
-                "Failed to process error response: {}",
-                e
-            )));
+            return Err(Error::coded(
+                ErrorCode::Payjoin,
+                format!("Failed to process error response: {}", e),
+            ));
         }
 
         Ok(())
@@ -774,18 +810,19 @@ impl<'a> PayjoinManager<'a> {
                 .save(persister);
             match processed_response {
                 Ok(OptionalTransitionOutcome::Progress(psbt)) => {
-                    println!("Proposal received. Processing...");
+                    log::info!("Proposal received. Processing...");
                     return self.process_payjoin_proposal(psbt, blockchain_client).await;
                 }
                 Ok(OptionalTransitionOutcome::Stasis(current_state)) => {
-                    println!("No response yet. Continuing polling...");
+                    log::info!("No response yet. Continuing polling...");
                     sender = current_state;
                     continue;
                 }
                 Err(e) => {
-                    break Err(Error::Generic(format!(
-                        "Error occurred when polling for Payjoin v2 proposal: {e}"
-                    )));
+                    break Err(Error::coded(
+                        ErrorCode::Payjoin,
+                        format!("Error occurred when polling for Payjoin v2 proposal: {e}"),
+                    ));
                 }
             }
         }
@@ -797,14 +834,18 @@ impl<'a> PayjoinManager<'a> {
         blockchain_client: &BlockchainClient,
     ) -> Result<Txid, Error> {
         if !self.wallet.sign(&mut psbt, SignOptions::default())? {
-            return Err(Error::Generic(
+            return Err(Error::coded(
+                ErrorCode::SigningFailed,
                 "Failed to sign and finalize the Payjoin proposal PSBT.".to_string(),
             ));
         }
 
-        blockchain_client
-            .broadcast(psbt.extract_tx_fee_rate_limit()?)
-            .await
+        let tx = psbt.extract_tx_fee_rate_limit()?;
+        crate::utils::confirm(
+            "the broadcast",
+            &crate::handlers::online::broadcast_summary(&tx, self.wallet, self.wallet.network()),
+        )?;
+        blockchain_client.broadcast(tx).await
     }
 
     async fn send_payjoin_post_request(
@@ -830,8 +871,12 @@ impl<'a> PayjoinManager<'a> {
             let relay = self.relay_manager.choose_relay()?;
             // Build a fresh request for each attempt. Reusing an OHTTP
             // ciphertext would let relays correlate retransmissions.
-            let (req, context) = build(relay.as_str())
-                .map_err(|e| Error::Generic(format!("Failed to create OHTTP request: {e}")))?;
+            let (req, context) = build(relay.as_str()).map_err(|e| {
+                Error::coded(
+                    ErrorCode::Payjoin,
+                    format!("Failed to create OHTTP request: {e}"),
+                )
+            })?;
 
             match self.send_payjoin_post_request(req).await {
                 Ok(response) => return Ok((response, context)),
@@ -876,7 +921,12 @@ impl<'a> PayjoinManager<'a> {
             .into_iter()
             .map(|s| url::Url::parse(&s))
             .collect::<Result<_, _>>()
-            .map_err(|e| Error::Generic(format!("Failed to parse OHTTP URLs: {e}")))?;
+            .map_err(|e| {
+                Error::coded(
+                    ErrorCode::PayjoinUrl,
+                    format!("Failed to parse OHTTP URLs: {e}"),
+                )
+            })?;
         self.relay_manager.configure(ohttp_relays)?;
 
         let max_fee_rate = FeeRate::BROADCAST_MIN;
@@ -885,14 +935,14 @@ impl<'a> PayjoinManager<'a> {
         let mut timed_out = 0usize;
         let mut failed = 0usize;
 
-        println!("Resuming {} payjoin session(s)...\n", total_sessions);
+        log::info!("Resuming {total_sessions} payjoin session(s)...");
 
         // Resume receiver sessions
         for session_id in recv_session_ids {
             let persister = ReceiverPersister::from_id(db.clone(), session_id.clone());
             match replay_receiver_event_log(&persister) {
                 Ok((receiver_state, _)) => {
-                    println!("Resuming receiver session {}", session_id);
+                    log::info!("Resuming receiver session {session_id}");
                     match tokio::time::timeout(
                         std::time::Duration::from_secs(30),
                         self.proceed_receiver_session(
@@ -909,17 +959,17 @@ impl<'a> PayjoinManager<'a> {
                         }
                         Ok(Err(e)) => {
                             failed += 1;
-                            println!("Receiver session {} failed: {}", session_id, e);
+                            log::warn!("Receiver session {session_id} failed: {e}");
                         }
                         Err(_) => {
                             timed_out += 1;
-                            println!("Receiver session {} timed out", session_id);
+                            log::warn!("Receiver session {session_id} timed out");
                         }
                     }
                 }
                 Err(e) => {
                     failed += 1;
-                    println!("Failed to replay receiver session {}: {:?}", session_id, e);
+                    log::warn!("Failed to replay receiver session {session_id}: {e}");
                 }
             }
         }
@@ -929,7 +979,7 @@ impl<'a> PayjoinManager<'a> {
             let persister = SenderPersister::from_id(db.clone(), session_id.clone());
             match replay_sender_event_log(&persister) {
                 Ok((sender_state, _)) => {
-                    println!("Resuming sender session {}", session_id);
+                    log::info!("Resuming sender session {session_id}");
                     match tokio::time::timeout(
                         std::time::Duration::from_secs(30),
                         self.proceed_sender_session(sender_state, &persister, blockchain_client),
@@ -941,17 +991,17 @@ impl<'a> PayjoinManager<'a> {
                         }
                         Ok(Err(e)) => {
                             failed += 1;
-                            println!("Sender session {} failed: {}", session_id, e);
+                            log::warn!("Sender session {session_id} failed: {e}");
                         }
                         Err(_) => {
                             timed_out += 1;
-                            println!("Sender session {} timed out", session_id);
+                            log::warn!("Sender session {session_id} timed out");
                         }
                     }
                 }
                 Err(e) => {
                     failed += 1;
-                    println!("Failed to replay sender session {}: {:?}", session_id, e);
+                    log::warn!("Failed to replay sender session {session_id}: {e}");
                 }
             }
         }
@@ -977,7 +1027,7 @@ impl<'a> PayjoinManager<'a> {
         // Active send sessions
         for session_id in db
             .get_send_session_ids()
-            .map_err(|e| Error::Generic(format!("{e}")))?
+            .map_err(|e| Error::coded(ErrorCode::PayjoinDatabase, format!("{e}")))?
         {
             let persister = SenderPersister::from_id(db.clone(), session_id.clone());
             let status = match replay_sender_event_log(&persister) {
@@ -995,7 +1045,7 @@ impl<'a> PayjoinManager<'a> {
         // Active receive sessions
         for session_id in db
             .get_recv_session_ids()
-            .map_err(|e| Error::Generic(format!("{e}")))?
+            .map_err(|e| Error::coded(ErrorCode::PayjoinDatabase, format!("{e}")))?
         {
             let persister = ReceiverPersister::from_id(db.clone(), session_id.clone());
             let status = match replay_receiver_event_log(&persister) {
@@ -1013,7 +1063,7 @@ impl<'a> PayjoinManager<'a> {
         // Completed send sessions
         for (session_id, completed_at) in db
             .get_inactive_send_session_ids()
-            .map_err(|e| Error::Generic(format!("{e}")))?
+            .map_err(|e| Error::coded(ErrorCode::PayjoinDatabase, format!("{e}")))?
         {
             let persister = SenderPersister::from_id(db.clone(), session_id.clone());
             let status = match replay_sender_event_log(&persister) {
@@ -1022,7 +1072,7 @@ impl<'a> PayjoinManager<'a> {
             };
             let completed_at = db
                 .format_unix_timestamp(completed_at)
-                .map_err(|e| Error::Generic(format!("{e}")))?;
+                .map_err(|e| Error::coded(ErrorCode::PayjoinDatabase, format!("{e}")))?;
             send_rows.push(SessionHistoryRow {
                 id: session_id.to_string(),
                 role: "Sender",
@@ -1034,7 +1084,7 @@ impl<'a> PayjoinManager<'a> {
         // Completed receive sessions
         for (session_id, completed_at) in db
             .get_inactive_recv_session_ids()
-            .map_err(|e| Error::Generic(format!("{e}")))?
+            .map_err(|e| Error::coded(ErrorCode::PayjoinDatabase, format!("{e}")))?
         {
             let persister = ReceiverPersister::from_id(db.clone(), session_id.clone());
             let status = match replay_receiver_event_log(&persister) {
@@ -1043,7 +1093,7 @@ impl<'a> PayjoinManager<'a> {
             };
             let completed_at = db
                 .format_unix_timestamp(completed_at)
-                .map_err(|e| Error::Generic(format!("{e}")))?;
+                .map_err(|e| Error::coded(ErrorCode::PayjoinDatabase, format!("{e}")))?;
             recv_rows.push(SessionHistoryRow {
                 id: session_id.to_string(),
                 role: "Receiver",
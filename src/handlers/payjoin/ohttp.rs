@@ -1,4 +1,4 @@
-use crate::error::BDKCliError as Error;
+use crate::error::{BDKCliError as Error, ErrorCode};
 use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone)]
@@ -17,7 +17,8 @@ impl RelayManager {
 
     pub(crate) fn configure(&mut self, relays: Vec<url::Url>) -> Result<(), Error> {
         if relays.is_empty() {
-            return Err(Error::Generic(
+            return Err(Error::coded(
+                ErrorCode::PayjoinUrl,
                 "At least one valid OHTTP relay must be provided.".into(),
             ));
         }
@@ -57,7 +58,12 @@ impl RelayManager {
         remaining_relays
             .choose(&mut payjoin::bitcoin::key::rand::thread_rng())
             .cloned()
-            .ok_or_else(|| Error::Generic("No valid OHTTP relays available".to_string()))
+            .ok_or_else(|| {
+                Error::coded(
+                    ErrorCode::Payjoin,
+                    "No valid OHTTP relays available".to_string(),
+                )
+            })
     }
 
     pub(crate) async fn fetch_ohttp_keys(
@@ -73,9 +79,10 @@ impl RelayManager {
             match ohttp_keys {
                 Ok(keys) => return Ok(keys),
                 Err(payjoin::io::Error::UnexpectedStatusCode(e)) => {
-                    return Err(Error::Generic(format!(
-                        "Unexpected error occurred when fetching OHTTP keys: {e}"
-                    )));
+                    return Err(Error::coded(
+                        ErrorCode::Payjoin,
+                        format!("Unexpected error occurred when fetching OHTTP keys: {e}"),
+                    ));
                 }
                 Err(e) => {
                     tracing::debug!(
@@ -0,0 +1,894 @@
+use crate::commands::{PsbtInputArgs, PsbtSubCommand};
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::handlers::{AppCommand, AppContext, Init};
+use crate::multisig;
+use crate::utils::output::ListResult;
+use crate::utils::psbt::{Map, MapKind, deserialize_psbt, field_name, raw_maps, serialize_psbt};
+use crate::utils::runtime::WalletRuntime;
+use crate::utils::{bbqr, qr, ur};
+use crate::utils::{is_final, parse_psbt_with_version};
+use crate::utils::{
+    output::FormatOutput,
+    types::{
+        Change, Cosigner, DecodedPsbt, DecodedPsbtInput, DecodedPsbtOutput, EntryDiff,
+        ExtractedTxResult, FieldDiff, FinalizedPsbtResult, InputAnalysis, InputFinalization,
+        InputSigners, KeyOrigin, MissingSignature, PsbtAnalysis, PsbtDiff, PsbtResult, PsbtSigners,
+    },
+};
+use bdk_wallet::KeychainKind;
+use bdk_wallet::bitcoin::bip32::{ChildNumber, DerivationPath, Fingerprint};
+use bdk_wallet::bitcoin::consensus::encode::serialize_hex;
+use bdk_wallet::bitcoin::hex::DisplayHex;
+use bdk_wallet::bitcoin::key::Secp256k1;
+use bdk_wallet::bitcoin::{Address, Network, Psbt, PublicKey, ScriptBuf, TxIn, Weight, psbt};
+use bdk_wallet::descriptor::ExtendedDescriptor;
+use bdk_wallet::miniscript::ForEachKey;
+use bdk_wallet::miniscript::psbt::PsbtExt;
+use clap::Parser;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+impl PsbtSubCommand {
+    pub fn execute(&self, ctx: &mut AppContext<Init>) -> Result<(), Error> {
+        match self {
+            PsbtSubCommand::Decode(decode_psbt_command) => decode_psbt_command
+                .execute(ctx)?
+                .write_out(std::io::stdout()),
+            PsbtSubCommand::Finalize(finalize_psbt_command) => finalize_psbt_command
+                .execute(ctx)?
+                .write_out(std::io::stdout()),
+            PsbtSubCommand::Extract(extract_psbt_command) => extract_psbt_command
+                .execute(ctx)?
+                .write_out(std::io::stdout()),
+            PsbtSubCommand::Analyze(analyze_psbt_command) => analyze_psbt_command
+                .execute(ctx)?
+                .write_out(std::io::stdout()),
+            PsbtSubCommand::Signers(signers_command) => {
+                signers_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            PsbtSubCommand::Diff(diff_command) => {
+                diff_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            PsbtSubCommand::UrEncode(ur_encode_command) if ur_encode_command.qr => {
+                ur_encode_command.show_qr()
+            }
+            PsbtSubCommand::UrEncode(ur_encode_command) => {
+                ur_encode_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            PsbtSubCommand::UrDecode(ur_decode_command) => {
+                ur_decode_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+            PsbtSubCommand::BbqrSplit(bbqr_split_command) => bbqr_split_command
+                .execute(ctx)?
+                .write_out(std::io::stdout()),
+            PsbtSubCommand::BbqrJoin(bbqr_join_command) => {
+                bbqr_join_command.execute(ctx)?.write_out(std::io::stdout())
+            }
+        }
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct DecodePsbtCommand {
+    #[command(flatten)]
+    pub psbt: PsbtInputArgs,
+}
+
+impl AppCommand<AppContext<Init>> for DecodePsbtCommand {
+    type Output = DecodedPsbt;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let (psbt, version) = self.psbt.parse()?;
+        let tx = &psbt.unsigned_tx;
+
+        let inputs: Vec<DecodedPsbtInput> = tx
+            .input
+            .iter()
+            .zip(&psbt.inputs)
+            .enumerate()
+            .map(|(index, (txin, input))| {
+                let prevout = input.witness_utxo.clone().or_else(|| {
+                    input.non_witness_utxo.as_ref().and_then(|prev_tx| {
+                        prev_tx
+                            .output
+                            .get(txin.previous_output.vout as usize)
+                            .cloned()
+                    })
+                });
+                let mut partial_signatures: Vec<String> =
+                    input.partial_sigs.keys().map(|pk| pk.to_string()).collect();
+                partial_signatures
+                    .extend(input.tap_script_sigs.keys().map(|(pk, _)| pk.to_string()));
+
+                DecodedPsbtInput {
+                    index,
+                    outpoint: txin.previous_output.to_string(),
+                    value: prevout.as_ref().map(|txout| txout.value.to_sat()),
+                    address: prevout
+                        .as_ref()
+                        .and_then(|txout| address(&txout.script_pubkey, ctx.network)),
+                    sequence: txin.sequence.to_consensus_u32(),
+                    sighash_type: input.sighash_type.map(|sighash| sighash.to_string()),
+                    key_origins: key_origins(
+                        input
+                            .bip32_derivation
+                            .iter()
+                            .map(|(pk, o)| (pk.to_string(), o)),
+                        input
+                            .tap_key_origins
+                            .iter()
+                            .map(|(pk, (_, o))| (pk.to_string(), o)),
+                    ),
+                    partial_signatures,
+                    has_taproot_key_signature: input.tap_key_sig.is_some(),
+                    is_finalized: input.final_script_sig.is_some()
+                        || input.final_script_witness.is_some(),
+                }
+            })
+            .collect();
+
+        let outputs = tx
+            .output
+            .iter()
+            .zip(&psbt.outputs)
+            .enumerate()
+            .map(|(index, (txout, output))| DecodedPsbtOutput {
+                index,
+                value: txout.value.to_sat(),
+                address: address(&txout.script_pubkey, ctx.network),
+                script_pubkey: txout.script_pubkey.to_hex_string(),
+                key_origins: key_origins(
+                    output
+                        .bip32_derivation
+                        .iter()
+                        .map(|(pk, o)| (pk.to_string(), o)),
+                    output
+                        .tap_key_origins
+                        .iter()
+                        .map(|(pk, (_, o))| (pk.to_string(), o)),
+                ),
+            })
+            .collect();
+
+        let total_input = inputs.iter().map(|input| input.value).sum::<Option<u64>>();
+        let total_output = tx.output.iter().map(|txout| txout.value.to_sat()).sum();
+
+        Ok(DecodedPsbt {
+            psbt_version: version as u32,
+            txid: tx.compute_txid().to_string(),
+            version: tx.version.0,
+            lock_time: tx.lock_time.to_consensus_u32(),
+            total_input,
+            total_output,
+            fee: psbt.fee().ok().map(|fee| fee.to_sat()),
+            is_finalized: inputs.iter().all(|input| input.is_finalized),
+            inputs,
+            outputs,
+        })
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct FinalizePsbtCommand {
+    #[command(flatten)]
+    pub psbt: PsbtInputArgs,
+}
+
+impl AppCommand<AppContext<Init>> for FinalizePsbtCommand {
+    type Output = FinalizedPsbtResult;
+
+    fn execute(&self, _ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let (mut psbt, version) = self.psbt.parse()?;
+        let inputs = finalize_inputs(&mut psbt);
+
+        let finalized = inputs.iter().all(|input| input.is_finalized);
+        Ok(FinalizedPsbtResult {
+            psbt: PsbtResult::with_version(&psbt, Some(finalized), version),
+            inputs,
+        })
+    }
+}
+
+/// Finalize every input the PSBT carries enough data for, reporting why the others failed.
+pub(crate) fn finalize_inputs(psbt: &mut Psbt) -> Vec<InputFinalization> {
+    let secp = Secp256k1::verification_only();
+    (0..psbt.inputs.len())
+        .map(|index| {
+            let input = &psbt.inputs[index];
+            if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+                return InputFinalization {
+                    index,
+                    is_finalized: true,
+                    error: None,
+                };
+            }
+            let result = psbt.finalize_inp_mut(&secp, index);
+            InputFinalization {
+                index,
+                is_finalized: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            }
+        })
+        .collect()
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct ExtractPsbtCommand {
+    #[command(flatten)]
+    pub psbt: PsbtInputArgs,
+
+    /// Also writes the hex encoded transaction to this file, e.g. `signed.tx`.
+    #[arg(env = "TX_OUT_FILE", long = "out-file")]
+    pub out_file: Option<PathBuf>,
+}
+
+impl AppCommand<AppContext<Init>> for ExtractPsbtCommand {
+    type Output = ExtractedTxResult;
+
+    fn execute(&self, _ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let (psbt, _) = self.psbt.parse()?;
+        is_final(&psbt)?;
+        let tx = psbt.extract_tx()?;
+        let raw_tx = serialize_hex(&tx);
+
+        if let Some(path) = &self.out_file {
+            fs::write(path, format!("{raw_tx}\n")).map_err(|e| {
+                Error::coded(
+                    ErrorCode::Io,
+                    format!("Failed to write transaction to {path:?}: {e}"),
+                )
+            })?;
+        }
+
+        Ok(ExtractedTxResult {
+            txid: tx.compute_txid().to_string(),
+            raw_tx,
+            file: self
+                .out_file
+                .as_ref()
+                .map(|path| path.display().to_string()),
+        })
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct AnalyzePsbtCommand {
+    #[command(flatten)]
+    pub psbt: PsbtInputArgs,
+
+    /// Saved wallet to match keys against and to estimate the weight of unsigned inputs.
+    #[arg(env = "WALLET_NAME", short = 'w', long = "wallet")]
+    pub wallet: Option<String>,
+}
+
+/// The next BIP-174 role an input or PSBT needs, in processing order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Role {
+    Updater,
+    Signer,
+    Finalizer,
+    Extractor,
+}
+
+impl Role {
+    fn as_str(self) -> &'static str {
+        match self {
+            Role::Updater => "updater",
+            Role::Signer => "signer",
+            Role::Finalizer => "finalizer",
+            Role::Extractor => "extractor",
+        }
+    }
+}
+
+impl AppCommand<AppContext<Init>> for AnalyzePsbtCommand {
+    type Output = PsbtAnalysis;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let (psbt, _) = self.psbt.parse()?;
+        let secp = Secp256k1::verification_only();
+
+        let (descriptors, wallet_fingerprints) =
+            wallet_descriptors(&ctx.datadir, self.wallet.as_deref())?;
+
+        // Finalize what can be finalized so the final weight of those inputs is exact.
+        let mut finalized = psbt.clone();
+        let mut inputs = Vec::with_capacity(psbt.inputs.len());
+        let mut satisfaction_weights = Vec::with_capacity(psbt.inputs.len());
+        let mut next = Role::Extractor;
+        for (index, input) in psbt.inputs.iter().enumerate() {
+            let is_final = input.final_script_sig.is_some() || input.final_script_witness.is_some();
+            let prevout = input.witness_utxo.clone().or_else(|| {
+                input.non_witness_utxo.as_ref().and_then(|prev_tx| {
+                    let vout = psbt.unsigned_tx.input[index].previous_output.vout;
+                    prev_tx.output.get(vout as usize).cloned()
+                })
+            });
+
+            let role = if is_final {
+                Role::Extractor
+            } else if prevout.is_none() {
+                Role::Updater
+            } else if finalized.finalize_inp_mut(&secp, index).is_ok() {
+                Role::Finalizer
+            } else {
+                Role::Signer
+            };
+            next = next.min(role);
+
+            let missing_signatures = if role == Role::Signer {
+                missing_signatures(input)
+                    .into_iter()
+                    .map(|(pubkey, (fingerprint, path))| MissingSignature {
+                        pubkey,
+                        fingerprint: fingerprint.to_string(),
+                        path: path.to_string(),
+                        is_ours: self
+                            .wallet
+                            .as_ref()
+                            .map(|_| wallet_fingerprints.contains(&fingerprint)),
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            let satisfaction_weight = match &finalized.inputs[index] {
+                final_input
+                    if final_input.final_script_sig.is_some()
+                        || final_input.final_script_witness.is_some() =>
+                {
+                    let bare = TxIn::default();
+                    let satisfied = TxIn {
+                        script_sig: final_input.final_script_sig.clone().unwrap_or_default(),
+                        witness: final_input.final_script_witness.clone().unwrap_or_default(),
+                        ..Default::default()
+                    };
+                    Some(satisfied.segwit_weight() - bare.segwit_weight())
+                }
+                _ => prevout.as_ref().and_then(|prevout| {
+                    wallet_satisfaction_weight(&descriptors, input, &prevout.script_pubkey)
+                }),
+            };
+            satisfaction_weights.push(satisfaction_weight);
+
+            inputs.push(InputAnalysis {
+                index,
+                has_utxo: prevout.is_some(),
+                is_final,
+                next: role.as_str().to_string(),
+                missing_signatures,
+            });
+        }
+
+        let fee = psbt.fee().ok();
+        let estimated_weight = satisfaction_weights
+            .into_iter()
+            .sum::<Option<Weight>>()
+            .map(|satisfaction_weight| {
+                let is_segwit = psbt.inputs.iter().any(|input| input.witness_utxo.is_some());
+                // Marker, flag and one witness item count per input.
+                let witness_overhead = if is_segwit {
+                    Weight::from_wu(2 + psbt.inputs.len() as u64)
+                } else {
+                    Weight::ZERO
+                };
+                psbt.unsigned_tx.weight() + witness_overhead + satisfaction_weight
+            });
+        let estimated_vsize = estimated_weight.map(|weight| weight.to_vbytes_ceil());
+
+        Ok(PsbtAnalysis {
+            next: next.as_str().to_string(),
+            fee: fee.map(|fee| fee.to_sat()),
+            estimated_vsize,
+            estimated_fee_rate: fee
+                .zip(estimated_vsize)
+                .map(|(fee, vsize)| fee.to_sat() as f64 / vsize as f64),
+            inputs,
+        })
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct UrEncodePsbtCommand {
+    #[command(flatten)]
+    pub psbt: PsbtInputArgs,
+
+    /// Maximum number of bytes carried by each part.
+    #[arg(long = "max-fragment-len", default_value_t = ur::DEFAULT_MAX_FRAGMENT_LEN)]
+    pub max_fragment_len: usize,
+
+    /// Shows the parts as QR codes instead of listing them. On a terminal, several parts are
+    /// animated until interrupted, looping over mixed parts once the pure ones were shown.
+    #[arg(long = "qr")]
+    pub qr: bool,
+
+    /// Milliseconds each frame of an animated QR code stays on screen.
+    #[arg(long = "frame-ms", default_value_t = 500, requires = "qr")]
+    pub frame_ms: u64,
+
+    /// Also writes the parts as PNG QR codes, `part-1.png`, `part-2.png`..., in this directory.
+    #[arg(env = "QR_DIR", long = "qr-dir")]
+    pub qr_dir: Option<PathBuf>,
+}
+
+impl UrEncodePsbtCommand {
+    /// The encoder of the PSBT and its pure parts, written as PNG files to `--qr-dir`.
+    fn encode(&self) -> Result<(ur::Encoder, Vec<String>), Error> {
+        if self.max_fragment_len < 10 {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                "The maximum fragment length must be at least 10 bytes".to_string(),
+            ));
+        }
+        let (psbt, version) = self.psbt.parse()?;
+        let encoder = ur::Encoder::psbt(&serialize_psbt(&psbt, version), self.max_fragment_len);
+        let parts: Vec<String> = (1..=encoder.fragment_count() as u32)
+            .map(|seq_num| encoder.part(seq_num))
+            .collect();
+
+        if let Some(dir) = &self.qr_dir {
+            let write_error = |e: std::io::Error| {
+                Error::coded(
+                    ErrorCode::Io,
+                    format!("Failed to write QR codes to {dir:?}: {e}"),
+                )
+            };
+            fs::create_dir_all(dir).map_err(write_error)?;
+            for (index, part) in parts.iter().enumerate() {
+                fs::write(dir.join(format!("part-{}.png", index + 1)), qr::png(part)?)
+                    .map_err(write_error)?;
+            }
+        }
+        Ok((encoder, parts))
+    }
+
+    /// Prints the parts as QR codes. On a terminal a multi-part PSBT is an animation of the
+    /// pure parts followed by endless mixed parts, otherwise the pure parts are printed in turn.
+    pub fn show_qr(&self) -> Result<(), Error> {
+        let (encoder, parts) = self.encode()?;
+        let mut stdout = std::io::stdout();
+        let io_error = |e: std::io::Error| Error::Generic(e.to_string());
+
+        if parts.len() == 1 || !stdout.is_terminal() {
+            for part in &parts {
+                writeln!(stdout, "{}", qr::terminal(part)?).map_err(io_error)?;
+            }
+            return Ok(());
+        }
+
+        for seq_num in 1..=u32::MAX {
+            let frame = qr::terminal(&encoder.part(seq_num))?;
+            // Clears the screen so every frame is drawn at the same place.
+            write!(
+                stdout,
+                "\x1b[2J\x1b[H{frame}\npart {seq_num}, Ctrl-C to stop\n"
+            )
+            .and_then(|_| stdout.flush())
+            .map_err(io_error)?;
+            std::thread::sleep(Duration::from_millis(self.frame_ms));
+        }
+        Ok(())
+    }
+}
+
+impl AppCommand<AppContext<Init>> for UrEncodePsbtCommand {
+    type Output = ListResult<String>;
+
+    fn execute(&self, _ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        Ok(ListResult::new(self.encode()?.1))
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct UrDecodePsbtCommand {
+    /// The scanned parts, pure or mixed, in any order.
+    #[arg(env = "UR_PART", required_unless_present = "stdin")]
+    pub parts: Vec<String>,
+
+    /// Reads the parts from stdin, one per line.
+    #[arg(long = "stdin")]
+    pub stdin: bool,
+}
+
+impl AppCommand<AppContext<Init>> for UrDecodePsbtCommand {
+    type Output = PsbtResult;
+
+    fn execute(&self, _ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let parts = read_parts(&self.parts, self.stdin)?;
+        let (psbt, version) = deserialize_psbt(&ur::decode_psbt(&parts)?)?;
+        Ok(PsbtResult::with_version(&psbt, None, version))
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct BbqrSplitPsbtCommand {
+    #[command(flatten)]
+    pub psbt: PsbtInputArgs,
+
+    /// Encoding of the parts.
+    #[arg(long = "encoding", value_enum, default_value_t = bbqr::Encoding::Base32)]
+    pub encoding: bbqr::Encoding,
+
+    /// Maximum number of characters carried by each part, after its header.
+    #[arg(long = "max-part-len", default_value_t = bbqr::DEFAULT_MAX_PART_LEN)]
+    pub max_part_len: usize,
+}
+
+impl AppCommand<AppContext<Init>> for BbqrSplitPsbtCommand {
+    type Output = ListResult<String>;
+
+    fn execute(&self, _ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let (psbt, version) = self.psbt.parse()?;
+        let parts = bbqr::split(
+            &serialize_psbt(&psbt, version),
+            bbqr::FileType::Psbt,
+            self.encoding,
+            self.max_part_len,
+        )?;
+        Ok(ListResult::new(parts))
+    }
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct BbqrJoinPsbtCommand {
+    /// The scanned parts, in any order.
+    #[arg(env = "BBQR_PART", required_unless_present = "stdin")]
+    pub parts: Vec<String>,
+
+    /// Reads the parts from stdin, one per line.
+    #[arg(long = "stdin")]
+    pub stdin: bool,
+}
+
+impl AppCommand<AppContext<Init>> for BbqrJoinPsbtCommand {
+    type Output = PsbtResult;
+
+    fn execute(&self, _ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let parts = read_parts(&self.parts, self.stdin)?;
+        let (psbt, version) = parse_psbt_with_version(&parts.join(" "))?;
+        Ok(PsbtResult::with_version(&psbt, None, version))
+    }
+}
+
+/// The parts given as arguments followed by the non-empty lines of stdin, if requested.
+fn read_parts(parts: &[String], stdin: bool) -> Result<Vec<String>, Error> {
+    let mut parts = parts.to_vec();
+    if stdin {
+        for line in std::io::stdin().lines() {
+            parts.push(line.map_err(|e| Error::Generic(e.to_string()))?);
+        }
+    }
+    parts.retain(|part| !part.trim().is_empty());
+    Ok(parts)
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct SignersPsbtCommand {
+    #[command(flatten)]
+    pub psbt: PsbtInputArgs,
+
+    /// Saved wallet whose descriptor keys are all expected to sign its inputs.
+    #[arg(env = "WALLET_NAME", short = 'w', long = "wallet")]
+    pub wallet: Option<String>,
+
+    /// Names a cosigner, as <FINGERPRINT>=<NAME>. Can be repeated.
+    #[arg(long = "alias", value_parser = parse_alias)]
+    pub aliases: Vec<(Fingerprint, String)>,
+}
+
+impl AppCommand<AppContext<Init>> for SignersPsbtCommand {
+    type Output = PsbtSigners;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let (psbt, _) = self.psbt.parse()?;
+        let (_, wallet_fingerprints) = wallet_descriptors(&ctx.datadir, self.wallet.as_deref())?;
+        let aliases: BTreeMap<Fingerprint, String> = self.aliases.iter().cloned().collect();
+        let cosigner = |fingerprint: Fingerprint| Cosigner {
+            fingerprint: fingerprint.to_string(),
+            alias: aliases.get(&fingerprint).cloned(),
+        };
+
+        let inputs = psbt
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                let is_finalized =
+                    input.final_script_sig.is_some() || input.final_script_witness.is_some();
+                let signed = multisig::signed_by(input);
+                let threshold = multisig::threshold(input);
+                // Once the threshold is met, no other cosigner has to sign.
+                let complete = is_finalized || threshold.is_some_and(|k| signed.len() >= k);
+                let mut expected = multisig::key_fingerprints(input);
+                if expected
+                    .iter()
+                    .any(|fingerprint| wallet_fingerprints.contains(fingerprint))
+                {
+                    expected.extend(wallet_fingerprints.iter().copied());
+                }
+
+                InputSigners {
+                    index,
+                    is_finalized,
+                    threshold,
+                    signed: signed.iter().copied().map(cosigner).collect(),
+                    outstanding: if complete {
+                        Vec::new()
+                    } else {
+                        expected
+                            .difference(&signed)
+                            .copied()
+                            .map(cosigner)
+                            .collect()
+                    },
+                }
+            })
+            .collect();
+
+        Ok(PsbtSigners { inputs })
+    }
+}
+
+fn parse_alias(s: &str) -> Result<(Fingerprint, String), Error> {
+    let (fingerprint, name) = s.split_once('=').ok_or_else(|| {
+        Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("Invalid alias '{s}', use <FINGERPRINT>=<NAME>"),
+        )
+    })?;
+    let fingerprint = Fingerprint::from_str(fingerprint).map_err(|e| {
+        Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("Invalid fingerprint in alias '{s}': {e}"),
+        )
+    })?;
+    Ok((fingerprint, name.to_string()))
+}
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct DiffPsbtCommand {
+    /// The reference PSBT, base64 or hex encoded.
+    pub first: String,
+
+    /// The PSBT to compare with it, base64 or hex encoded.
+    pub second: String,
+}
+
+impl AppCommand<AppContext<Init>> for DiffPsbtCommand {
+    type Output = PsbtDiff;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let (first, _) = parse_psbt_with_version(&self.first)?;
+        let (second, _) = parse_psbt_with_version(&self.second)?;
+        let (first_global, first_inputs, first_outputs) = raw_maps(&first);
+        let (second_global, second_inputs, second_outputs) = raw_maps(&second);
+        let (first_tx, second_tx) = (&first.unsigned_tx, &second.unsigned_tx);
+
+        // The unsigned transaction is compared through its inputs and outputs.
+        let without_tx =
+            |map: Map| -> Map { map.into_iter().filter(|(key, _)| key != &[0x00]).collect() };
+        let mut global = diff_maps(
+            MapKind::Global,
+            &without_tx(first_global),
+            &without_tx(second_global),
+        );
+        if first_tx.version != second_tx.version {
+            global.push(changed_field("tx_version"));
+        }
+        if first_tx.lock_time != second_tx.lock_time {
+            global.push(changed_field("lock_time"));
+        }
+
+        let inputs = diff_entries(
+            &first_tx.input,
+            &second_tx.input,
+            |a, b| a.previous_output == b.previous_output,
+            |txin| txin.previous_output.to_string(),
+            |a, b| {
+                let mut fields = diff_maps(MapKind::Input, &first_inputs[a], &second_inputs[b]);
+                if first_tx.input[a].sequence != second_tx.input[b].sequence {
+                    fields.push(changed_field("sequence"));
+                }
+                fields
+            },
+        );
+        let outputs = diff_entries(
+            &first_tx.output,
+            &second_tx.output,
+            |a, b| a == b,
+            |txout| {
+                address(&txout.script_pubkey, ctx.network)
+                    .unwrap_or_else(|| txout.script_pubkey.to_hex_string())
+            },
+            |a, b| diff_maps(MapKind::Output, &first_outputs[a], &second_outputs[b]),
+        );
+
+        Ok(PsbtDiff {
+            identical: global.is_empty() && inputs.is_empty() && outputs.is_empty(),
+            global,
+            inputs,
+            outputs,
+        })
+    }
+}
+
+/// Match the entries of two lists, returning the ones that were removed, added, moved or whose
+/// PSBT fields differ.
+fn diff_entries<T>(
+    first: &[T],
+    second: &[T],
+    same: impl Fn(&T, &T) -> bool,
+    describe: impl Fn(&T) -> String,
+    fields: impl Fn(usize, usize) -> Vec<FieldDiff>,
+) -> Vec<EntryDiff> {
+    let mut unmatched: Vec<usize> = (0..second.len()).collect();
+    let mut entries = Vec::new();
+    for (index, entry) in first.iter().enumerate() {
+        let Some(position) = unmatched
+            .iter()
+            .position(|other| same(entry, &second[*other]))
+        else {
+            entries.push(EntryDiff {
+                first: Some(index),
+                second: None,
+                entry: describe(entry),
+                change: Change::Removed,
+                fields: Vec::new(),
+            });
+            continue;
+        };
+        let other = unmatched.remove(position);
+        let fields = fields(index, other);
+        if index != other || !fields.is_empty() {
+            entries.push(EntryDiff {
+                first: Some(index),
+                second: Some(other),
+                entry: describe(entry),
+                change: Change::Changed,
+                fields,
+            });
+        }
+    }
+    entries.extend(unmatched.into_iter().map(|index| EntryDiff {
+        first: None,
+        second: Some(index),
+        entry: describe(&second[index]),
+        change: Change::Added,
+        fields: Vec::new(),
+    }));
+    entries
+}
+
+fn diff_maps(kind: MapKind, first: &Map, second: &Map) -> Vec<FieldDiff> {
+    let first: BTreeMap<&[u8], &[u8]> = first
+        .iter()
+        .map(|(key, value)| (key.as_slice(), value.as_slice()))
+        .collect();
+    let second: BTreeMap<&[u8], &[u8]> = second
+        .iter()
+        .map(|(key, value)| (key.as_slice(), value.as_slice()))
+        .collect();
+    let keys: BTreeSet<&[u8]> = first.keys().chain(second.keys()).copied().collect();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let change = match (first.get(key), second.get(key)) {
+                (Some(_), None) => Change::Removed,
+                (None, Some(_)) => Change::Added,
+                (Some(a), Some(b)) if a != b => Change::Changed,
+                _ => return None,
+            };
+            let (key_type, key_data) = key.split_first()?;
+            Some(FieldDiff {
+                field: field_name(kind, *key_type),
+                key: (!key_data.is_empty()).then(|| key_data.to_lower_hex_string()),
+                change,
+            })
+        })
+        .collect()
+}
+
+fn changed_field(field: &str) -> FieldDiff {
+    FieldDiff {
+        field: field.to_string(),
+        key: None,
+        change: Change::Changed,
+    }
+}
+
+/// The public descriptors of a saved wallet and the fingerprints of their keys.
+fn wallet_descriptors(
+    datadir: &Path,
+    wallet: Option<&str>,
+) -> Result<(Vec<ExtendedDescriptor>, BTreeSet<Fingerprint>), Error> {
+    let Some(name) = wallet else {
+        return Ok((Vec::new(), BTreeSet::new()));
+    };
+    let wallet = WalletRuntime::load(datadir, name)?.build_wallet(false)?;
+    let descriptors: Vec<ExtendedDescriptor> = [KeychainKind::External, KeychainKind::Internal]
+        .map(|keychain| wallet.public_descriptor(keychain).clone())
+        .into();
+    let mut fingerprints = BTreeSet::new();
+    for descriptor in &descriptors {
+        descriptor.for_each_key(|key| {
+            fingerprints.insert(key.master_fingerprint());
+            true
+        });
+    }
+    Ok((descriptors, fingerprints))
+}
+
+/// Keys with a known origin that have not signed the input yet.
+fn missing_signatures(input: &psbt::Input) -> Vec<(String, (Fingerprint, DerivationPath))> {
+    let ecdsa = input
+        .bip32_derivation
+        .iter()
+        .filter(|(pubkey, _)| !input.partial_sigs.contains_key(&PublicKey::new(**pubkey)))
+        .map(|(pubkey, origin)| (pubkey.to_string(), origin.clone()));
+    let taproot = input
+        .tap_key_origins
+        .iter()
+        .filter(|(pubkey, (leaf_hashes, _))| {
+            if leaf_hashes.is_empty() {
+                input.tap_key_sig.is_none()
+            } else {
+                !leaf_hashes
+                    .iter()
+                    .any(|leaf_hash| input.tap_script_sigs.contains_key(&(**pubkey, *leaf_hash)))
+            }
+        })
+        .map(|(pubkey, (_, origin))| (pubkey.to_string(), origin.clone()));
+    ecdsa.chain(taproot).collect()
+}
+
+/// Maximum satisfaction weight of an input paying to one of the wallet descriptors.
+fn wallet_satisfaction_weight(
+    descriptors: &[ExtendedDescriptor],
+    input: &psbt::Input,
+    script_pubkey: &ScriptBuf,
+) -> Option<Weight> {
+    let indexes = input
+        .bip32_derivation
+        .values()
+        .chain(input.tap_key_origins.values().map(|(_, origin)| origin))
+        .filter_map(|(_, path)| match path.into_iter().last() {
+            Some(ChildNumber::Normal { index }) => Some(*index),
+            _ => None,
+        });
+
+    for index in indexes {
+        for descriptor in descriptors {
+            if let Ok(derived) = descriptor.at_derivation_index(index)
+                && derived.script_pubkey() == *script_pubkey
+            {
+                return derived.max_weight_to_satisfy().ok();
+            }
+        }
+    }
+    None
+}
+
+fn address(script: &ScriptBuf, network: Network) -> Option<String> {
+    Address::from_script(script, network)
+        .ok()
+        .map(|address| address.to_string())
+}
+
+fn key_origins<'a>(
+    ecdsa: impl Iterator<Item = (String, &'a (Fingerprint, DerivationPath))>,
+    taproot: impl Iterator<Item = (String, &'a (Fingerprint, DerivationPath))>,
+) -> Vec<KeyOrigin> {
+    ecdsa
+        .chain(taproot)
+        .map(|(pubkey, (fingerprint, path))| KeyOrigin {
+            pubkey,
+            fingerprint: fingerprint.to_string(),
+            path: path.to_string(),
+        })
+        .collect()
+}
@@ -2,9 +2,20 @@
 use {
     crate::commands::ReplSubCommand,
     crate::handlers::{AppCommand, AppContext},
-    crate::utils::output::FormatOutput,
+    crate::utils::output::{FormatOutput, ListResult, start_capture, take_captured},
+    crate::utils::types::{AliasDetails, BatchResult},
     bdk_wallet::{Wallet, bitcoin::Network},
-    clap::Parser,
+    clap::{Args, CommandFactory, Parser, error::ErrorKind},
+    rustyline::completion::Completer,
+    rustyline::error::ReadlineError,
+    rustyline::highlight::Highlighter,
+    rustyline::hint::Hinter,
+    rustyline::history::DefaultHistory,
+    rustyline::validate::Validator,
+    rustyline::{CompletionType, Config, Context, Editor, Helper},
+    serde_json::Value,
+    std::collections::BTreeMap,
+    std::path::{Path, PathBuf},
 };
 
 #[cfg(all(
@@ -17,10 +28,111 @@ use {
     )
 ))]
 use crate::client::BlockchainClient;
+#[cfg(all(feature = "repl", any(feature = "sqlite", feature = "redb")))]
+use crate::commands::WalletDebugSubCommand;
 #[cfg(feature = "repl")]
-use {crate::commands::WalletSubCommand, crate::error::BDKCliError as Error, std::io::Write};
+use {
+    crate::commands::WalletSubCommand,
+    crate::error::{BDKCliError as Error, ErrorCode},
+    crate::utils::{
+        is_read_only, read_only_error, wallet_command_mutates_wallet,
+        wallet_command_requires_network,
+    },
+    std::io::Write,
+};
+
+/// The wallet of `use`, and the network it must be on when given.
+#[cfg(any(feature = "repl", target_arch = "wasm32"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletTarget {
+    pub wallet: String,
+    pub network: Option<bdk_wallet::bitcoin::Network>,
+}
+
+/// Parse `<wallet>` or `<wallet>@<network>`.
+#[cfg(any(feature = "repl", target_arch = "wasm32"))]
+pub(crate) fn parse_wallet_target(s: &str) -> Result<WalletTarget, String> {
+    let (wallet, network) = match s.rsplit_once('@') {
+        Some((wallet, network)) => {
+            let network = network
+                .parse()
+                .map_err(|e| format!("Invalid network '{network}': {e}"))?;
+            (wallet, Some(network))
+        }
+        None => (s, None),
+    };
+    if wallet.is_empty() {
+        return Err(format!("Missing the wallet name in '{s}'"));
+    }
+    Ok(WalletTarget {
+        wallet: wallet.to_string(),
+        network,
+    })
+}
+
+/// What the REPL does once a command is done.
+#[cfg(feature = "repl")]
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ReplAction {
+    Continue,
+    Exit,
+    /// Switch to another wallet, which only the REPL loop can load.
+    Use(WalletTarget),
+}
+
+/// The variables and aliases defined during a REPL session.
+#[cfg(feature = "repl")]
+#[derive(Debug, Default)]
+pub(crate) struct Session {
+    pub(crate) variables: Variables,
+    aliases: BTreeMap<String, Vec<String>>,
+}
 
 #[cfg(feature = "repl")]
+impl Session {
+    /// `args` with an alias as first word replaced by its command.
+    fn expand_alias(&self, args: Vec<String>) -> Vec<String> {
+        match args.first().and_then(|first| self.aliases.get(first)) {
+            Some(command) => command
+                .iter()
+                .cloned()
+                .chain(args.into_iter().skip(1))
+                .collect(),
+            None => args,
+        }
+    }
+
+    /// Defines the alias `name`, the `=` before its command being optional.
+    fn define_alias(&mut self, name: String, command: Vec<String>) -> Result<(), String> {
+        let command: Vec<String> = command.into_iter().skip_while(|arg| arg == "=").collect();
+        if ReplSubCommand::command().find_subcommand(&name).is_some() {
+            return Err(format!(
+                "error: `{name}` is a REPL command and cannot be an alias"
+            ));
+        }
+        if command.is_empty() {
+            return Err(format!("error: Expected a command after `alias {name} =`"));
+        }
+        self.aliases.insert(name, command);
+        Ok(())
+    }
+
+    fn alias_list(&self) -> ListResult<AliasDetails> {
+        let aliases = self
+            .aliases
+            .iter()
+            .map(|(name, command)| AliasDetails {
+                name: name.clone(),
+                command: shlex::try_join(command.iter().map(String::as_str))
+                    .unwrap_or_else(|_| command.join(" ")),
+            })
+            .collect();
+        ListResult::new(aliases)
+    }
+}
+
+#[cfg(feature = "repl")]
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn respond(
     network: Network,
     wallet: &mut Wallet,
@@ -30,31 +142,49 @@ pub(crate) async fn respond(
         feature = "rpc",
         feature = "cbf"
     ))]
-    client: Option<&BlockchainClient>,
+    client: Result<&BlockchainClient, &str>,
     line: &str,
+    session: &mut Session,
     datadir: std::path::PathBuf,
-    #[cfg(any(
-        feature = "electrum",
-        feature = "esplora",
-        feature = "rpc",
-        feature = "cbf"
-    ))]
+    stop_gap: usize,
     wallet_name: &str,
-) -> Result<bool, String> {
+    offline: bool,
+) -> Result<ReplAction, String> {
     let args = shlex::split(line).ok_or("error: Invalid quoting".to_string())?;
+    let args = session
+        .expand_alias(args)
+        .iter()
+        .map(|arg| session.variables.expand(arg))
+        .collect::<Result<Vec<_>, _>>()?;
 
     let repl_subcommand = match ReplSubCommand::try_parse_from(&args) {
         Ok(cmd) => cmd,
         Err(e) => {
             writeln!(std::io::stdout(), "{}", e).map_err(|e| e.to_string())?;
-            return Ok(false);
+            return Ok(ReplAction::Continue);
         }
     };
 
     let response = match repl_subcommand {
+        ReplSubCommand::Wallet { subcommand }
+            if offline && wallet_command_requires_network(&subcommand) =>
+        {
+            return Err(Error::Offline.to_string());
+        }
+        ReplSubCommand::Wallet { subcommand }
+            if is_read_only() && wallet_command_mutates_wallet(&subcommand) =>
+        {
+            return Err(read_only_error().to_string());
+        }
         ReplSubCommand::Wallet { subcommand } => match subcommand {
             WalletSubCommand::OfflineWalletSubCommand(cmd) => {
-                let mut ctx = AppContext::new_offline_wallet(network, datadir, wallet);
+                let mut ctx = AppContext::new_offline_wallet(
+                    network,
+                    datadir,
+                    wallet,
+                    wallet_name.to_string(),
+                    stop_gap,
+                );
                 cmd.execute(&mut ctx).map_err(|e| e.to_string())?;
                 Some(())
             }
@@ -65,13 +195,15 @@ pub(crate) async fn respond(
                 feature = "rpc"
             ))]
             WalletSubCommand::OnlineWalletSubCommand(cmd) => {
-                let client_ref = client.ok_or("Online commands require a client.".to_string())?;
+                let client_ref =
+                    client.map_err(|e| format!("Online commands require a backend: {e}"))?;
                 let mut ctx = AppContext::new_online_wallet(
                     network,
                     datadir,
                     wallet,
                     client_ref,
                     wallet_name.to_string(),
+                    stop_gap,
                 );
 
                 cmd.execute(&mut ctx).await.map_err(|e| e.to_string())?;
@@ -79,13 +211,91 @@ pub(crate) async fn respond(
             }
             WalletSubCommand::Config(_) => {
                 writeln!(
-                    std::io::stdout(),
+                    std::io::stderr(),
                     "`config` is not available in REPL mode — the wallet for this session \
          is already loaded. Exit and run `bdk-cli wallet --wallet <name> config ...`."
                 )
                 .map_err(|e| e.to_string())?;
                 Some(())
             }
+            WalletSubCommand::Backup(mut cmd) => {
+                cmd.wallet = wallet_name.to_string();
+                let mut ctx = AppContext::new(network, datadir);
+                cmd.execute(&mut ctx)
+                    .map_err(|e| e.to_string())?
+                    .write_out(std::io::stdout())
+                    .map_err(|e| e.to_string())?;
+                Some(())
+            }
+            #[cfg(any(feature = "sqlite", feature = "redb"))]
+            WalletSubCommand::Debug {
+                subcommand: WalletDebugSubCommand::Changesets(mut cmd),
+            } => {
+                cmd.wallet = wallet_name.to_string();
+                let mut ctx = AppContext::new(network, datadir);
+                cmd.execute(&mut ctx)
+                    .map_err(|e| e.to_string())?
+                    .write_out(std::io::stdout())
+                    .map_err(|e| e.to_string())?;
+                Some(())
+            }
+            #[cfg(feature = "sqlite")]
+            WalletSubCommand::Db { .. } => {
+                writeln!(
+                    std::io::stderr(),
+                    "`db` is not available in REPL mode — the wallet database is in use. \
+         Exit and run `bdk-cli wallet --wallet <name> db ...`."
+                )
+                .map_err(|e| e.to_string())?;
+                Some(())
+            }
+            #[cfg(feature = "sqlite")]
+            WalletSubCommand::Migrate(_) => {
+                writeln!(
+                    std::io::stderr(),
+                    "`migrate` is not available in REPL mode — the wallet database is in use. \
+         Exit and run `bdk-cli wallet --wallet <name> migrate --from <datadir>`."
+                )
+                .map_err(|e| e.to_string())?;
+                Some(())
+            }
+            WalletSubCommand::Snapshot { .. } => {
+                writeln!(
+                    std::io::stderr(),
+                    "`snapshot` is not available in REPL mode — the wallet database is in use. \
+         Exit and run `bdk-cli wallet --wallet <name> snapshot ...`."
+                )
+                .map_err(|e| e.to_string())?;
+                Some(())
+            }
+            WalletSubCommand::Rollback(_) => {
+                writeln!(
+                    std::io::stderr(),
+                    "`rollback` is not available in REPL mode — the wallet database is in use. \
+         Exit and run `bdk-cli wallet --wallet <name> rollback`."
+                )
+                .map_err(|e| e.to_string())?;
+                Some(())
+            }
+            WalletSubCommand::Restore(_) => {
+                writeln!(
+                    std::io::stderr(),
+                    "`restore` is not available in REPL mode — it creates a new wallet. \
+         Exit and run `bdk-cli wallet --wallet <name> restore <file>`."
+                )
+                .map_err(|e| e.to_string())?;
+                Some(())
+            }
+            #[cfg(feature = "rpc")]
+            WalletSubCommand::GenerateTest(_) => {
+                writeln!(
+                    std::io::stderr(),
+                    "`generate-test` is not available in REPL mode — it creates a new wallet. \
+         Exit and run `bdk-cli --network regtest wallet --wallet <name> generate-test --node`."
+                )
+                .map_err(|e| e.to_string())?;
+                Some(())
+            }
         },
 
         ReplSubCommand::Descriptor(cmd) => {
@@ -103,28 +313,671 @@ pub(crate) async fn respond(
             Some(())
         }
 
+        ReplSubCommand::Psbt { subcommand } => {
+            let mut ctx = AppContext::new(network, datadir);
+            subcommand.execute(&mut ctx).map_err(|e| e.to_string())?;
+            Some(())
+        }
+
+        ReplSubCommand::Multisig { subcommand } => {
+            let mut ctx = AppContext::new(network, datadir);
+            subcommand.execute(&mut ctx).map_err(|e| e.to_string())?;
+            Some(())
+        }
+
+        ReplSubCommand::Alias { name, command } => {
+            if let Some(name) = name {
+                session.define_alias(name, command)?;
+            }
+            session
+                .alias_list()
+                .write_out(std::io::stdout())
+                .map_err(|e| e.to_string())?;
+            Some(())
+        }
+
+        ReplSubCommand::Use { target } => return Ok(ReplAction::Use(target)),
+
         ReplSubCommand::Exit => None,
     };
 
     if response.is_some() {
         std::io::stdout().flush().map_err(|e| e.to_string())?;
-        Ok(false)
+        Ok(ReplAction::Continue)
     } else {
-        writeln!(std::io::stdout(), "Exiting...").map_err(|e| e.to_string())?;
+        writeln!(std::io::stderr(), "Exiting...").map_err(|e| e.to_string())?;
         std::io::stdout().flush().map_err(|e| e.to_string())?;
-        Ok(true)
+        Ok(ReplAction::Exit)
+    }
+}
+
+#[cfg(feature = "repl")]
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct RunCommand {
+    /// Script of REPL commands, one per line, `-` for stdin. `#` starts a comment line.
+    #[arg(
+        value_name = "SCRIPT",
+        required_unless_present = "commands",
+        conflicts_with = "commands"
+    )]
+    pub(crate) script: Option<PathBuf>,
+
+    /// REPL commands separated by `;`, or `-` to read a script from stdin.
+    #[arg(long = "commands", value_name = "COMMANDS")]
+    pub(crate) commands: Option<String>,
+
+    /// Runs the commands after a failed one, rather than stopping at it.
+    #[arg(long = "keep-going")]
+    pub(crate) keep_going: bool,
+}
+
+#[cfg(feature = "repl")]
+impl RunCommand {
+    /// The commands of the script, checked to parse before any runs.
+    pub(crate) fn lines(&self) -> Result<Vec<String>, Error> {
+        let stdin =
+            self.commands.as_deref() == Some("-") || self.script.as_deref() == Some(Path::new("-"));
+        let text = if stdin {
+            std::io::read_to_string(std::io::stdin()).map_err(|e| {
+                Error::coded(ErrorCode::Io, format!("Failed to read the script: {e}"))
+            })?
+        } else if let Some(commands) = &self.commands {
+            commands.clone()
+        } else if let Some(script) = &self.script {
+            std::fs::read_to_string(script).map_err(|e| {
+                Error::coded(
+                    ErrorCode::Io,
+                    format!("Failed to read {}: {e}", script.display()),
+                )
+            })?
+        } else {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                "No script to run".to_string(),
+            ));
+        };
+        let text = match self.commands.is_some() && !stdin {
+            true => split_commands(&text),
+            false => text.lines().map(str::to_string).collect(),
+        };
+
+        let lines: Vec<String> = text
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect();
+        // The aliases and variables defined by the script, for the lines using them to be checked.
+        let mut session = Session::default();
+        let mut variables = Vec::new();
+        for (number, line) in lines.iter().enumerate() {
+            let invalid = |e: String| {
+                Error::coded(
+                    ErrorCode::InvalidArgument,
+                    format!("Invalid command {} `{line}`: {e}", number + 1),
+                )
+            };
+            let repl_line = ReplLine::parse(line).map_err(invalid)?;
+            let args = shlex::split(repl_line.command).ok_or_else(|| {
+                Error::coded(
+                    ErrorCode::InvalidArgument,
+                    format!("Invalid quoting in `{line}`"),
+                )
+            })?;
+            let args = session.expand_alias(args);
+            let mut referencing = false;
+            for (_, reference) in args.iter().flat_map(|arg| references(arg)) {
+                let name = reference.split('.').next().unwrap_or_default();
+                if !variables.contains(&name) {
+                    return Err(invalid(format!(
+                        "Unknown variable `${name}`, set it with `let {name} = <command>` on an \
+                         earlier line"
+                    )));
+                }
+                referencing = true;
+            }
+            // The values of the variables are only known when running, so a line referencing
+            // them may only fail on its values.
+            let parsed = match ReplSubCommand::try_parse_from(&args) {
+                Ok(parsed) => Some(parsed),
+                Err(e)
+                    if referencing
+                        && matches!(
+                            e.kind(),
+                            ErrorKind::ValueValidation | ErrorKind::InvalidValue
+                        ) =>
+                {
+                    None
+                }
+                Err(e) => return Err(invalid(e.to_string())),
+            };
+            if let Some(ReplSubCommand::Alias {
+                name: Some(name),
+                command,
+            }) = parsed
+            {
+                session.define_alias(name, command).map_err(invalid)?;
+            }
+            variables.extend(repl_line.variable);
+        }
+        Ok(lines)
+    }
+}
+
+/// The client of the wallet of a REPL or `run` session, or why there is none, reported by the
+/// online commands.
+#[cfg(all(
+    feature = "repl",
+    any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    )
+))]
+pub(crate) fn session_client(
+    runtime: &crate::utils::runtime::WalletRuntime,
+    wallet: &Wallet,
+    offline: bool,
+) -> Result<BlockchainClient, String> {
+    match offline {
+        true => Err(Error::Offline.to_string()),
+        false => runtime.build_client(wallet).map_err(|e| e.to_string()),
+    }
+}
+
+/// The commands of `--commands`, split on the `;` outside the quotes and the escapes of the
+/// arguments.
+#[cfg(feature = "repl")]
+fn split_commands(commands: &str) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    let mut quote = None;
+    let mut escaped = false;
+    for c in commands.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && quote != Some('\'') {
+            escaped = true;
+        } else if quote == Some(c) {
+            quote = None;
+        } else if quote.is_none() && (c == '"' || c == '\'') {
+            quote = Some(c);
+        } else if quote.is_none() && c == ';' {
+            lines.push(String::new());
+            continue;
+        }
+        lines.last_mut().expect("there is a line").push(c);
+    }
+    lines
+}
+
+/// Run the REPL commands `lines` on the loaded wallet, collecting the outputs of each. Stops at
+/// the first failed command unless `keep_going`, returning its error with the results. The
+/// outputs kept with `let` and the aliases are used by the next commands, as in the REPL.
+#[cfg(feature = "repl")]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn run_batch(
+    network: Network,
+    wallet: &mut Wallet,
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    client: Result<&BlockchainClient, &str>,
+    lines: Vec<String>,
+    datadir: std::path::PathBuf,
+    stop_gap: usize,
+    wallet_name: &str,
+    offline: bool,
+    keep_going: bool,
+) -> (Vec<BatchResult>, Option<String>) {
+    let mut results = Vec::new();
+    let mut failure = None;
+    let mut session = Session::default();
+    for line in lines {
+        start_capture();
+        let repl_line = ReplLine::parse(&line);
+        let response = match &repl_line {
+            Ok(repl_line) => {
+                respond(
+                    network,
+                    wallet,
+                    #[cfg(any(
+                        feature = "electrum",
+                        feature = "esplora",
+                        feature = "rpc",
+                        feature = "cbf"
+                    ))]
+                    client,
+                    repl_line.command,
+                    &mut session,
+                    datadir.clone(),
+                    stop_gap,
+                    wallet_name,
+                    offline,
+                )
+                .await
+            }
+            Err(e) => Err(e.clone()),
+        };
+        let response = match response {
+            Ok(ReplAction::Use(_)) => {
+                Err("error: `use` is only available in the REPL, `run` runs on one wallet".into())
+            }
+            response => response,
+        };
+        let output = take_captured();
+        if let (
+            Ok(_),
+            Ok(ReplLine {
+                variable: Some(name),
+                ..
+            }),
+        ) = (&response, &repl_line)
+        {
+            session.variables.set(name, &output);
+        }
+        match response {
+            Ok(action) => {
+                results.push(BatchResult {
+                    command: line,
+                    output,
+                    error: None,
+                });
+                if action == ReplAction::Exit {
+                    break;
+                }
+            }
+            Err(e) => {
+                let stop = !keep_going;
+                if stop {
+                    failure = Some(format!("`{line}` failed: {e}"));
+                }
+                results.push(BatchResult {
+                    command: line,
+                    output,
+                    error: Some(e),
+                });
+                if stop {
+                    break;
+                }
+            }
+        }
+    }
+    (results, failure)
+}
+
+/// A REPL line, `let <name> = <command>` keeping the output of the command in the variable
+/// `name`, or a plain command.
+#[cfg(feature = "repl")]
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ReplLine<'a> {
+    pub(crate) variable: Option<&'a str>,
+    pub(crate) command: &'a str,
+}
+
+#[cfg(feature = "repl")]
+impl<'a> ReplLine<'a> {
+    pub(crate) fn parse(line: &'a str) -> Result<Self, String> {
+        let line = line.trim();
+        let Some(assignment) = line.strip_prefix("let ") else {
+            return Ok(Self {
+                variable: None,
+                command: line,
+            });
+        };
+        let (name, command) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("error: Expected `let <name> = <command>`, found `{line}`"))?;
+        let (name, command) = (name.trim(), command.trim());
+        if !is_variable_name(name) {
+            return Err(format!(
+                "error: Invalid variable name `{name}`, use letters, digits and `_`"
+            ));
+        }
+        if command.is_empty() {
+            return Err(format!("error: Expected a command after `let {name} =`"));
+        }
+        Ok(Self {
+            variable: Some(name),
+            command,
+        })
+    }
+}
+
+#[cfg(feature = "repl")]
+fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The outputs kept with `let`, referenced in the next commands as `$name`, or `$name.field` for
+/// a field of the output, such as `$psbt.psbt` or `$txs.items.0.txid`.
+#[cfg(feature = "repl")]
+#[derive(Debug, Default)]
+pub(crate) struct Variables(BTreeMap<String, Value>);
+
+#[cfg(feature = "repl")]
+impl Variables {
+    /// Keeps the last of the `outputs` of a command in the variable `name`.
+    pub(crate) fn set(&mut self, name: &str, outputs: &[Value]) {
+        let output = outputs.last().cloned().unwrap_or(Value::Null);
+        self.0.insert(name.to_string(), output);
+    }
+
+    /// `arg` with its variable references replaced by their values, the strings unquoted and the
+    /// other values as JSON. A `$` not starting a variable name is kept.
+    pub(crate) fn expand(&self, arg: &str) -> Result<String, String> {
+        let mut expanded = String::new();
+        let mut last = 0;
+        for (range, reference) in references(arg) {
+            expanded.push_str(&arg[last..range.start]);
+            expanded.push_str(&self.value(reference)?);
+            last = range.end;
+        }
+        expanded.push_str(&arg[last..]);
+        Ok(expanded)
+    }
+
+    fn value(&self, reference: &str) -> Result<String, String> {
+        let mut path = reference.split('.');
+        let name = path.next().unwrap_or_default();
+        let mut value = self.0.get(name).ok_or_else(|| {
+            format!("error: Unknown variable `${name}`, set it with `let {name} = <command>`")
+        })?;
+        for field in path {
+            value = match value {
+                Value::Array(items) => field.parse::<usize>().ok().and_then(|i| items.get(i)),
+                _ => value.get(field),
+            }
+            .ok_or_else(|| format!("error: `${reference}` has no `{field}`"))?;
+        }
+        Ok(match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+    }
+}
+
+/// The variable references of `arg`, such as `psbt.psbt` for `$psbt.psbt`, with the ranges of
+/// `arg` they take.
+#[cfg(feature = "repl")]
+fn references(arg: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut references = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = arg[offset..].find('$').map(|start| offset + start) {
+        let after = &arg[start + 1..];
+        let end = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(after.len());
+        let reference = after[..end].trim_end_matches('.');
+        offset = start + 1;
+        if reference.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            offset += reference.len();
+            references.push((start..offset, reference));
+        }
     }
+    references
 }
 
+/// The REPL history, in the data directory.
 #[cfg(feature = "repl")]
-pub(crate) fn readline() -> Result<String, Error> {
-    write!(std::io::stdout(), "> ").map_err(|e| Error::Generic(e.to_string()))?;
-    std::io::stdout()
-        .flush()
-        .map_err(|e| Error::Generic(e.to_string()))?;
-    let mut buffer = String::new();
-    std::io::stdin()
-        .read_line(&mut buffer)
-        .map_err(|e| Error::Generic(e.to_string()))?;
-    Ok(buffer)
+const HISTORY_FILE: &str = "repl_history";
+
+/// Prints the `error` of a REPL line, which leaves the REPL running.
+#[cfg(feature = "repl")]
+pub(crate) fn print_error(error: &str) {
+    match error.starts_with("error:") {
+        true => eprintln!("{error}"),
+        false => eprintln!("error: {error}"),
+    }
+}
+
+/// Commands whose lines are kept out of the history, as they may hold keys or mnemonics.
+#[cfg(feature = "repl")]
+const UNRECORDED_COMMANDS: [&str; 2] = ["key", "descriptor"];
+
+/// The line editor of the REPL, completing its commands and flags with tab and keeping the
+/// history of the data directory, searched backwards with Ctrl-R.
+#[cfg(feature = "repl")]
+pub(crate) struct LineEditor {
+    editor: Editor<ReplHelper, DefaultHistory>,
+    history: PathBuf,
+}
+
+#[cfg(feature = "repl")]
+impl LineEditor {
+    pub(crate) fn new(datadir: &Path) -> Result<Self, Error> {
+        let config = Config::builder()
+            .completion_type(CompletionType::List)
+            .history_ignore_space(true)
+            .build();
+        let mut editor = Editor::with_config(config).map_err(|e| Error::Generic(e.to_string()))?;
+        editor.set_helper(Some(ReplHelper));
+        let history = datadir.join(HISTORY_FILE);
+        // Created readable by its owner only before rustyline saves to it, as the lines may
+        // hold addresses and amounts.
+        let mut options = std::fs::OpenOptions::new();
+        options.append(true).create(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(&history)
+            .and_then(|_| editor.load_history(&history).map_err(std::io::Error::other))
+            .map_err(|e| {
+                Error::coded(
+                    ErrorCode::Io,
+                    format!("Failed to read {}: {e}", history.display()),
+                )
+            })?;
+        Ok(Self { editor, history })
+    }
+
+    /// Read the next line, `None` once the input ends with Ctrl-D.
+    pub(crate) fn readline(&mut self) -> Result<Option<String>, Error> {
+        let line = match self.editor.readline("> ") {
+            Ok(line) => line,
+            // Ctrl-C drops the line being edited.
+            Err(ReadlineError::Interrupted) => return Ok(Some(String::new())),
+            Err(ReadlineError::Eof) => return Ok(None),
+            Err(e) => return Err(Error::Generic(e.to_string())),
+        };
+        let command = ReplLine::parse(&line).map_or(line.as_str(), |line| line.command);
+        let command = command.split_whitespace().next().unwrap_or_default();
+        if !UNRECORDED_COMMANDS.contains(&command) {
+            self.editor
+                .add_history_entry(line.as_str())
+                .map_err(|e| Error::Generic(e.to_string()))?;
+            if let Err(e) = self.editor.save_history(&self.history) {
+                log::warn!("Failed to save the REPL history: {e}");
+            }
+        }
+        Ok(Some(line))
+    }
+}
+
+/// Completes the REPL commands, their subcommands and their long flags.
+#[cfg(feature = "repl")]
+struct ReplHelper;
+
+#[cfg(feature = "repl")]
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let (typed, word) = line.split_at(start);
+        Ok((start, completions(typed, word)))
+    }
+}
+
+#[cfg(feature = "repl")]
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+#[cfg(feature = "repl")]
+impl Highlighter for ReplHelper {}
+
+#[cfg(feature = "repl")]
+impl Validator for ReplHelper {}
+
+#[cfg(feature = "repl")]
+impl Helper for ReplHelper {}
+
+/// The subcommands, or the long flags when `word` starts with `-`, of the command `typed` so far
+/// that start with `word`.
+#[cfg(feature = "repl")]
+fn completions(typed: &str, word: &str) -> Vec<String> {
+    let mut command = ReplSubCommand::command();
+    for name in typed.split_whitespace() {
+        if let Some(subcommand) = command.find_subcommand(name) {
+            command = subcommand.clone();
+        }
+    }
+    let mut candidates: Vec<String> = if word.starts_with('-') {
+        command
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .filter_map(|arg| arg.get_long())
+            .map(|long| format!("--{long}"))
+            .collect()
+    } else {
+        command
+            .get_subcommands()
+            .filter(|subcommand| !subcommand.is_hide_set())
+            .map(|subcommand| subcommand.get_name().to_string())
+            .collect()
+    };
+    candidates.retain(|candidate| candidate.starts_with(word));
+    candidates.sort();
+    candidates
+}
+
+#[cfg(all(test, feature = "repl"))]
+mod tests {
+    use super::{
+        HISTORY_FILE, LineEditor, ReplLine, Session, Variables, WalletTarget, completions,
+        parse_wallet_target,
+    };
+    use bdk_wallet::bitcoin::Network;
+    use serde_json::json;
+
+    #[test]
+    fn test_completions() {
+        assert_eq!(completions("", "wal"), vec!["wallet"]);
+        assert!(completions("wallet ", "").contains(&"balance".to_string()));
+        assert_eq!(completions("wallet ", "new_"), vec!["new_address"]);
+        assert!(completions("wallet create_tx ", "--fee").contains(&"--fee_rate".to_string()));
+        assert!(completions("wallet nothing ", "zzz").is_empty());
+    }
+
+    #[test]
+    fn test_repl_line() {
+        assert_eq!(
+            ReplLine::parse(" wallet balance "),
+            Ok(ReplLine {
+                variable: None,
+                command: "wallet balance"
+            })
+        );
+        assert_eq!(
+            ReplLine::parse("let psbt = wallet create_tx --to @alice:1000"),
+            Ok(ReplLine {
+                variable: Some("psbt"),
+                command: "wallet create_tx --to @alice:1000"
+            })
+        );
+        assert!(ReplLine::parse("let psbt wallet create_tx").is_err());
+        assert!(ReplLine::parse("let 2psbt = wallet create_tx").is_err());
+        assert!(ReplLine::parse("let psbt =").is_err());
+    }
+
+    #[test]
+    fn test_variable_references() {
+        let mut variables = Variables::default();
+        variables.set(
+            "psbt",
+            &[json!({"psbt": "cHNidP8B", "is_finalized": false})],
+        );
+        variables.set("txs", &[json!({"count": 1, "items": [{"txid": "00ff"}]})]);
+
+        assert_eq!(variables.expand("$psbt.psbt"), Ok("cHNidP8B".to_string()));
+        assert_eq!(
+            variables.expand("$psbt.is_finalized."),
+            Ok("false.".to_string())
+        );
+        assert_eq!(
+            variables.expand("$txs.items.0.txid"),
+            Ok("00ff".to_string())
+        );
+        assert_eq!(
+            variables.expand("--utxos=$txs.items.0.txid:0"),
+            Ok("--utxos=00ff:0".to_string())
+        );
+        assert_eq!(
+            variables.expand("price in $5"),
+            Ok("price in $5".to_string())
+        );
+        assert!(variables.expand("$psbt.fee").is_err());
+        assert!(variables.expand("$tx.txid").is_err());
+    }
+
+    #[test]
+    fn test_wallet_target() {
+        assert_eq!(
+            parse_wallet_target("cold"),
+            Ok(WalletTarget {
+                wallet: "cold".to_string(),
+                network: None
+            })
+        );
+        assert_eq!(
+            parse_wallet_target("cold@signet"),
+            Ok(WalletTarget {
+                wallet: "cold".to_string(),
+                network: Some(Network::Signet)
+            })
+        );
+        assert!(parse_wallet_target("cold@moon").is_err());
+        assert!(parse_wallet_target("@regtest").is_err());
+    }
+
+    #[test]
+    fn test_alias_expansion() {
+        let mut session = Session::default();
+        session.aliases.insert(
+            "bal".to_string(),
+            vec!["wallet".to_string(), "balance".to_string()],
+        );
+        let args = |line: &str| shlex::split(line).unwrap();
+
+        assert_eq!(
+            session.expand_alias(args("bal --sat")),
+            args("wallet balance --sat")
+        );
+        assert_eq!(session.expand_alias(args("wallet bal")), args("wallet bal"));
+    }
+
+    #[test]
+    fn test_history_file_is_private() {
+        let datadir = tempfile::tempdir().unwrap();
+        LineEditor::new(datadir.path()).unwrap();
+        let history = datadir.path().join(HISTORY_FILE);
+        assert!(history.exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&history).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
 }
@@ -0,0 +1,983 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! REST server
+//!
+//! `serve` answers HTTP requests on the wallets of the data directory. Each endpoint stands for a
+//! `wallet` command, run on the wallet named in the path, and answers with the JSON output of the
+//! command, or with the `{"error": ...}` output of `--output json` on failure. The commands of a
+//! wallet run one at a time, each loading the wallet and persisting it once done, while the ones
+//! of different wallets run at once. Under `daemon` the wallets stay loaded between two commands,
+//! and are persisted on shutdown.
+//!
+//! The OpenAPI description of the endpoints is built from the same route table and from the
+//! output types of the commands, served at `/openapi.json` and printed by `serve --openapi`.
+//!
+//! The requests authenticate as described in [`crate::utils::auth`], building and broadcasting
+//! transactions needing a credential with the `spend` scope.
+
+use crate::commands::{OfflineWalletSubCommand, WalletSubCommand};
+use crate::error::{BDKCliError as Error, ErrorCategory, ErrorCode};
+use crate::handlers::offline::{
+    BalanceCommand, CreateTxCommand, NewAddressCommand, TransactionsCommand, UnspentCommand,
+};
+use crate::handlers::{AppCommand, AppContext, OfflineOperations};
+use crate::utils::auth::{Auth, Scope, ServerSecurity, TlsIdentity};
+#[cfg(feature = "grpc")]
+use crate::utils::events::{EventSender, forward_events};
+use crate::utils::output::{start_capture, take_captured};
+use crate::utils::runtime::{RuntimeWallet, WalletRuntime};
+use crate::utils::{
+    assume_yes_on_thread, is_read_only, parse_payee, read_only_error,
+    wallet_command_mutates_wallet, wallet_command_requires_network,
+};
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode, header::AUTHORIZATION};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{MethodRouter, get, post};
+use axum_server::tls_rustls::RustlsConfig;
+use clap::Parser;
+use schemars::JsonSchema;
+use schemars::generate::SchemaSettings;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+use {
+    crate::client::BlockchainClient,
+    crate::commands::OnlineWalletSubCommand,
+    crate::handlers::online::{BroadcastCommand, SyncCommand},
+    crate::handlers::{AsyncAppCommand, OnlineOperations},
+};
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+pub struct ServeCommand {
+    /// Address and port the server listens on.
+    #[arg(env = "LISTEN", long = "listen", default_value = "127.0.0.1:3000")]
+    pub listen: SocketAddr,
+    /// Prints the OpenAPI description of the endpoints rather than serving them.
+    #[arg(long = "openapi")]
+    pub openapi: bool,
+    /// Also serves the wallet operations over gRPC, on this address and port.
+    #[cfg(feature = "grpc")]
+    #[arg(env = "GRPC_LISTEN", long = "grpc", value_name = "ADDRESS")]
+    pub grpc: Option<SocketAddr>,
+    #[command(flatten)]
+    pub security: ServerSecurity,
+}
+
+type OfflineContext = AppContext<OfflineOperations<'static>>;
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+type OnlineContext = AppContext<OnlineOperations<'static>>;
+
+#[derive(Clone, Copy, Debug)]
+enum Method {
+    Get,
+    Post,
+}
+
+impl Method {
+    fn name(self) -> &'static str {
+        match self {
+            Method::Get => "get",
+            Method::Post => "post",
+        }
+    }
+}
+
+/// An endpoint and the `wallet` command it runs.
+struct Route {
+    method: Method,
+    path: &'static str,
+    summary: &'static str,
+    /// The name of the `wallet` subcommand.
+    command: &'static str,
+    body: Option<Body>,
+    /// Builds the command from the request body, empty without a body.
+    build: fn(&[u8]) -> Result<WalletSubCommand, ApiError>,
+    /// The name and the schema of the output type of the command, the response.
+    output: fn() -> (String, Value),
+}
+
+static ROUTES: &[Route] = &[
+    Route {
+        method: Method::Get,
+        path: "/wallets/{name}/balance",
+        summary: "Balance of the wallet",
+        command: "balance",
+        body: None,
+        build: |_| {
+            Ok(offline_command(OfflineWalletSubCommand::Balance(
+                BalanceCommand::default(),
+            )))
+        },
+        output: output_schema::<<BalanceCommand as AppCommand<OfflineContext>>::Output>,
+    },
+    Route {
+        method: Method::Get,
+        path: "/wallets/{name}/utxos",
+        summary: "Unspent outputs of the wallet",
+        command: "unspent",
+        body: None,
+        build: |_| {
+            Ok(offline_command(OfflineWalletSubCommand::Unspent(
+                UnspentCommand::default(),
+            )))
+        },
+        output: output_schema::<<UnspentCommand as AppCommand<OfflineContext>>::Output>,
+    },
+    Route {
+        method: Method::Get,
+        path: "/wallets/{name}/transactions",
+        summary: "Transactions of the wallet",
+        command: "transactions",
+        body: None,
+        build: |_| {
+            Ok(offline_command(OfflineWalletSubCommand::Transactions(
+                TransactionsCommand::default(),
+            )))
+        },
+        output: output_schema::<<TransactionsCommand as AppCommand<OfflineContext>>::Output>,
+    },
+    Route {
+        method: Method::Post,
+        path: "/wallets/{name}/addresses",
+        summary: "Reveal a new receive address",
+        command: "new_address",
+        body: None,
+        build: |_| {
+            Ok(offline_command(OfflineWalletSubCommand::NewAddress(
+                NewAddressCommand,
+            )))
+        },
+        output: output_schema::<<NewAddressCommand as AppCommand<OfflineContext>>::Output>,
+    },
+    Route {
+        method: Method::Post,
+        path: "/wallets/{name}/psbts",
+        summary: "Create an unsigned PSBT paying the recipients",
+        command: "create_tx",
+        body: Some(Body::Psbt),
+        build: psbt_request_command,
+        output: output_schema::<<CreateTxCommand as AppCommand<OfflineContext>>::Output>,
+    },
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    Route {
+        method: Method::Post,
+        path: "/wallets/{name}/sync",
+        summary: "Sync the wallet with its backend",
+        command: "sync",
+        body: None,
+        build: |_| {
+            Ok(online_command(OnlineWalletSubCommand::Sync(
+                SyncCommand::default(),
+            )))
+        },
+        output: output_schema::<<SyncCommand as AsyncAppCommand<OnlineContext>>::Output>,
+    },
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    Route {
+        method: Method::Post,
+        path: "/wallets/{name}/broadcasts",
+        summary: "Broadcast a finalized PSBT or a raw transaction",
+        command: "broadcast",
+        body: Some(Body::Broadcast),
+        build: broadcast_request_command,
+        output: output_schema::<<BroadcastCommand as AsyncAppCommand<OnlineContext>>::Output>,
+    },
+];
+
+/// The request bodies, each described by its schema.
+#[derive(Clone, Copy, Debug)]
+enum Body {
+    Psbt,
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    Broadcast,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PsbtRequest {
+    recipients: Vec<RecipientRequest>,
+    #[serde(default)]
+    fee_rate: Option<f32>,
+    #[serde(default)]
+    send_all: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecipientRequest {
+    /// An address or a `@contact`.
+    address: String,
+    /// The amount in satoshis.
+    amount: u64,
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BroadcastRequest {
+    #[serde(default)]
+    psbt: Option<String>,
+    #[serde(default)]
+    tx: Option<String>,
+}
+
+impl Body {
+    fn schema(self) -> Value {
+        match self {
+            Body::Psbt => json!({
+                "type": "object",
+                "required": ["recipients"],
+                "properties": {
+                    "recipients": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "required": ["address", "amount"],
+                            "properties": {
+                                "address": {"type": "string", "description": "An address or a @contact."},
+                                "amount": {"type": "integer", "format": "int64", "description": "Amount in satoshis."},
+                            },
+                        },
+                    },
+                    "fee_rate": {"type": "number", "description": "Fee rate in sat/vbyte."},
+                    "send_all": {"type": "boolean", "description": "Sends all the funds, the amount of the single recipient being ignored."},
+                },
+            }),
+            #[cfg(any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf"
+            ))]
+            Body::Broadcast => json!({
+                "type": "object",
+                "properties": {
+                    "psbt": {"type": "string", "description": "A finalized PSBT, base64 encoded."},
+                    "tx": {"type": "string", "description": "A raw transaction, hex encoded."},
+                },
+            }),
+        }
+    }
+}
+
+/// The `create_tx` of a `POST /psbts` body.
+fn psbt_request_command(body: &[u8]) -> Result<WalletSubCommand, ApiError> {
+    let request: PsbtRequest = parse_body(body)?;
+    let recipients = request
+        .recipients
+        .into_iter()
+        .map(|recipient| (recipient.address, recipient.amount));
+    create_tx_command(recipients, request.fee_rate, request.send_all)
+}
+
+/// The `broadcast` of a `POST /broadcasts` body.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+fn broadcast_request_command(body: &[u8]) -> Result<WalletSubCommand, ApiError> {
+    let request: BroadcastRequest = parse_body(body)?;
+    broadcast_command(request.psbt, request.tx)
+}
+
+pub(crate) fn offline_command(command: OfflineWalletSubCommand) -> WalletSubCommand {
+    WalletSubCommand::OfflineWalletSubCommand(command)
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub(crate) fn online_command(command: OnlineWalletSubCommand) -> WalletSubCommand {
+    WalletSubCommand::OnlineWalletSubCommand(command)
+}
+
+/// The `create_tx` paying the `(address or @contact, satoshis)` recipients. The amounts are
+/// satoshis whatever the `--unit` of the server.
+pub(crate) fn create_tx_command(
+    recipients: impl IntoIterator<Item = (String, u64)>,
+    fee_rate: Option<f32>,
+    send_all: bool,
+) -> Result<WalletSubCommand, ApiError> {
+    let recipients = recipients
+        .into_iter()
+        .map(|(address, amount)| {
+            let payee = parse_payee(&address)
+                .map_err(|e| ApiError::bad_request(&format!("Invalid recipient {address}: {e}")))?;
+            Ok((payee, amount))
+        })
+        .collect::<Result<Vec<_>, ApiError>>()?;
+    if recipients.is_empty() {
+        return Err(ApiError::bad_request("Expected at least one recipient"));
+    }
+    Ok(offline_command(OfflineWalletSubCommand::CreateTx(
+        CreateTxCommand::paying(recipients, fee_rate, send_all),
+    )))
+}
+
+/// The `broadcast` of either a finalized `psbt` or a raw `tx`.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub(crate) fn broadcast_command(
+    psbt: Option<String>,
+    tx: Option<String>,
+) -> Result<WalletSubCommand, ApiError> {
+    if psbt.is_some() == tx.is_some() {
+        return Err(ApiError::bad_request(
+            "Expected either a `psbt` or a `tx` to broadcast",
+        ));
+    }
+    Ok(online_command(OnlineWalletSubCommand::Broadcast(
+        BroadcastCommand::of(psbt, tx),
+    )))
+}
+
+fn parse_body<T: for<'de> Deserialize<'de>>(body: &[u8]) -> Result<T, ApiError> {
+    serde_json::from_slice(body)
+        .map_err(|e| ApiError::bad_request(&format!("Invalid request body: {e}")))
+}
+
+/// The name of the schema of `T`, `ListResult<UnspentDetails>` being `ListResult.UnspentDetails`.
+fn schema_name<T>() -> String {
+    std::any::type_name::<T>()
+        .split(['<', '>', ','])
+        .filter_map(|part| part.trim().rsplit("::").next())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// The name and the schema of the output `T` of a command, its nested types inlined.
+fn output_schema<T: JsonSchema>() -> (String, Value) {
+    let mut settings = SchemaSettings::openapi3();
+    settings.inline_subschemas = true;
+    let schema = settings.into_generator().subschema_for::<T>();
+    (
+        schema_name::<T>(),
+        serde_json::to_value(schema).unwrap_or_default(),
+    )
+}
+
+/// The OpenAPI description of the endpoints.
+pub fn openapi() -> Value {
+    let mut paths = Map::new();
+    let mut schemas = Map::new();
+    for route in ROUTES {
+        let (output, mut schema) = (route.output)();
+        schema["description"] = json!(format!(
+            "The JSON output of `bdk-cli wallet {}`.",
+            route.command
+        ));
+        schemas.insert(output.clone(), schema);
+        let mut operation = json!({
+            "summary": route.summary,
+            "description": format!("Runs `wallet {}` on the wallet.", route.command),
+            "operationId": route.command,
+            "parameters": [{
+                "name": "name",
+                "in": "path",
+                "required": true,
+                "description": "Name of a wallet of the data directory.",
+                "schema": {"type": "string"},
+            }],
+            "responses": {
+                "200": {
+                    "description": "The output of the command.",
+                    "content": {"application/json": {"schema": {"$ref": format!("#/components/schemas/{output}")}}},
+                },
+                "default": {
+                    "description": "The failure of the command.",
+                    "content": {"application/json": {"schema": {"$ref": "#/components/schemas/Error"}}},
+                },
+            },
+        });
+        if let Some(body) = route.body {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": {"application/json": {"schema": body.schema()}},
+            });
+        }
+        let path = paths
+            .entry(route.path)
+            .or_insert_with(|| Value::Object(Map::new()));
+        path[route.method.name()] = operation;
+    }
+    schemas.insert(
+        "Error".to_string(),
+        json!({
+            "type": "object",
+            "properties": {
+                "error": {
+                    "type": "object",
+                    "properties": {
+                        "code": {"type": "string"},
+                        "category": {"type": "string"},
+                        "message": {"type": "string"},
+                    },
+                },
+            },
+        }),
+    );
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "bdk-cli",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "bearer": {"type": "http", "scheme": "bearer"},
+                "basic": {"type": "http", "scheme": "basic"},
+            },
+        },
+        "security": [{"bearer": []}, {"basic": []}],
+    })
+}
+
+/// A failed request, answered with the JSON error of the command.
+pub(crate) struct ApiError {
+    pub(crate) status: StatusCode,
+    pub(crate) error: Error,
+}
+
+impl ApiError {
+    pub(crate) fn bad_request(message: &str) -> Self {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            error: Error::coded(ErrorCode::InvalidArgument, message.to_string()),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        let status = match (&error, error.category()) {
+            (Error::NoConfig | Error::WalletNotFound(_), _) => StatusCode::NOT_FOUND,
+            (Error::Unauthorized(_), _) => StatusCode::UNAUTHORIZED,
+            (_, ErrorCategory::InvalidInput) => StatusCode::BAD_REQUEST,
+            (_, ErrorCategory::Transaction) => StatusCode::UNPROCESSABLE_ENTITY,
+            (_, ErrorCategory::Network) => StatusCode::BAD_GATEWAY,
+            (_, ErrorCategory::Refused) => StatusCode::FORBIDDEN,
+            (_, ErrorCategory::Wallet | ErrorCategory::Internal) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        ApiError { status, error }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, axum::Json(self.error.to_json())).into_response()
+    }
+}
+
+/// Answers the request of `route` on `wallet`.
+async fn answer(
+    runner: Arc<Runner>,
+    route: &'static Route,
+    wallet: String,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let authorization = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok());
+    let request = match runner.authorize(authorization) {
+        Ok(scope) => (route.build)(&body).map(|command| (scope, command)),
+        Err(e) => Err(e),
+    };
+    let output = match request {
+        Ok((scope, command)) => {
+            runner
+                .run(
+                    wallet,
+                    command,
+                    scope,
+                    #[cfg(feature = "grpc")]
+                    None,
+                )
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    match output {
+        Ok(output) => axum::Json(output).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// A loaded wallet, with the client of its backend once an online command needed it.
+struct OpenWallet {
+    runtime: WalletRuntime,
+    wallet: RuntimeWallet,
+    #[cfg(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    ))]
+    client: Option<BlockchainClient>,
+}
+
+impl OpenWallet {
+    fn load(datadir: &std::path::Path, wallet_name: &str) -> Result<Self, Error> {
+        let runtime = WalletRuntime::load(datadir, wallet_name)?;
+        let wallet = runtime.build_wallet(true)?;
+        Ok(OpenWallet {
+            runtime,
+            wallet,
+            #[cfg(any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf"
+            ))]
+            client: None,
+        })
+    }
+
+    /// Runs `command`, returning its last output.
+    async fn execute(&mut self, command: WalletSubCommand) -> Result<Value, Error> {
+        let runtime = &self.runtime;
+        start_capture();
+        let result = match &command {
+            WalletSubCommand::OfflineWalletSubCommand(cmd) => {
+                let mut ctx = AppContext::new_offline_wallet(
+                    runtime.network,
+                    runtime.home_dir.clone(),
+                    &mut self.wallet,
+                    runtime.wallet_name.clone(),
+                    runtime.wallet_opts.stop_gap,
+                );
+                cmd.execute(&mut ctx)
+            }
+            #[cfg(any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf"
+            ))]
+            WalletSubCommand::OnlineWalletSubCommand(cmd) => {
+                let client = match self.client.take() {
+                    Some(client) => Ok(client),
+                    None => runtime.build_client(&self.wallet),
+                };
+                match client {
+                    Ok(client) => {
+                        let mut ctx = AppContext::new_online_wallet(
+                            runtime.network,
+                            runtime.home_dir.clone(),
+                            &mut self.wallet,
+                            &client,
+                            runtime.wallet_name.clone(),
+                            runtime.wallet_opts.stop_gap,
+                        );
+                        let result = cmd.execute(&mut ctx).await;
+                        self.client = Some(client);
+                        result
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            _ => Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                "Only the wallet operations are served".to_string(),
+            )),
+        };
+        let outputs = take_captured();
+        result?;
+        self.wallet.persist()?;
+        Ok(outputs.into_iter().next_back().unwrap_or(Value::Null))
+    }
+}
+
+/// A wallet of the [`Runner`], empty until a command loads it when the wallets are kept loaded.
+type WalletSlot = Arc<tokio::sync::Mutex<Option<OpenWallet>>>;
+
+/// Runs the `wallet` commands of the requests, of the REST and gRPC servers alike.
+pub(crate) struct Runner {
+    datadir: PathBuf,
+    offline: bool,
+    auth: Auth,
+    /// Whether the wallets stay loaded between two commands, as in the daemon.
+    keep_open: bool,
+    /// The wallets of the requests, each locked while one of its commands runs and holding the
+    /// wallet kept loaded, if any. The map itself is only locked to look a wallet up.
+    wallets: tokio::sync::Mutex<BTreeMap<String, WalletSlot>>,
+}
+
+impl Runner {
+    pub(crate) fn new(datadir: PathBuf, offline: bool, keep_open: bool, auth: Auth) -> Arc<Self> {
+        Arc::new(Runner {
+            datadir,
+            offline,
+            auth,
+            keep_open,
+            wallets: tokio::sync::Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// The scope of a request with the `authorization` header.
+    pub(crate) fn authorize(&self, authorization: Option<&str>) -> Result<Scope, ApiError> {
+        Ok(self.auth.scope(authorization)?)
+    }
+
+    /// Runs the `wallet` subcommand `command` on `wallet` for a request of `scope`, returning its
+    /// JSON output. The progress and wallet events of the command go to `events` when given.
+    pub(crate) async fn run(
+        self: Arc<Self>,
+        wallet: String,
+        command: WalletSubCommand,
+        scope: Scope,
+        #[cfg(feature = "grpc")] events: Option<EventSender>,
+    ) -> Result<Value, ApiError> {
+        if required_scope(&command) > scope {
+            return Err(ApiError {
+                status: StatusCode::FORBIDDEN,
+                error: Error::Unauthorized(
+                    "This request needs a credential with the spend scope".to_string(),
+                ),
+            });
+        }
+        if self.offline && wallet_command_requires_network(&command) {
+            return Err(Error::Offline.into());
+        }
+        if is_read_only() && wallet_command_mutates_wallet(&command) {
+            return Err(read_only_error().into());
+        }
+
+        let slot = self
+            .wallets
+            .lock()
+            .await
+            .entry(wallet.clone())
+            .or_default()
+            .clone();
+        let mut slot = slot.lock_owned().await;
+        let open = slot.take();
+        let datadir = self.datadir.clone();
+        // The commands hold the wallet across their awaits, so they run on a thread of their own.
+        let (open, output) = tokio::task::spawn_blocking(move || {
+            let mut open = match open {
+                Some(open) => open,
+                None => OpenWallet::load(&datadir, &wallet)?,
+            };
+            let _reset = CommandThreadGuard;
+            #[cfg(feature = "grpc")]
+            forward_events(events);
+            // No one is at the terminal of the server, a credential allowed to spend stands for
+            // the confirmation of the broadcasts.
+            assume_yes_on_thread(scope == Scope::Spend);
+            let output = tokio::runtime::Handle::current().block_on(open.execute(command));
+            Ok::<_, Error>((open, output))
+        })
+        .await
+        .map_err(|e| Error::Generic(format!("The command failed: {e}")))??;
+        if self.keep_open {
+            *slot = Some(open);
+        }
+        output.map_err(ApiError::from)
+    }
+
+    /// Persists the wallets kept loaded and closes them.
+    pub(crate) async fn close(&self) -> Result<(), Error> {
+        let slots: Vec<WalletSlot> = self.wallets.lock().await.values().cloned().collect();
+        for slot in slots {
+            if let Some(mut open) = slot.lock().await.take() {
+                open.wallet.persist()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resets the confirmation and the event forwarding of the blocking thread of a command when
+/// dropped, so that a panicking command leaves none of them to the next one of the thread.
+struct CommandThreadGuard;
+
+impl Drop for CommandThreadGuard {
+    fn drop(&mut self) {
+        assume_yes_on_thread(false);
+        #[cfg(feature = "grpc")]
+        forward_events(None);
+    }
+}
+
+/// The scope a command needs, the ones building or broadcasting a transaction needing `spend`.
+fn required_scope(command: &WalletSubCommand) -> Scope {
+    match command {
+        WalletSubCommand::OfflineWalletSubCommand(
+            OfflineWalletSubCommand::CreateTx(_)
+            | OfflineWalletSubCommand::BumpFee(_)
+            | OfflineWalletSubCommand::Sign(_),
+        ) => Scope::Spend,
+        #[cfg(any(
+            feature = "electrum",
+            feature = "esplora",
+            feature = "rpc",
+            feature = "cbf"
+        ))]
+        WalletSubCommand::OnlineWalletSubCommand(OnlineWalletSubCommand::Broadcast(_)) => {
+            Scope::Spend
+        }
+        _ => Scope::Read,
+    }
+}
+
+/// Serves the wallets of `runner` over REST on `rest` and over gRPC on `grpc` until Ctrl-C or
+/// SIGTERM, over TLS with `tls`.
+pub(crate) async fn serve(
+    runner: Arc<Runner>,
+    rest: Option<SocketAddr>,
+    #[cfg(feature = "grpc")] grpc: Option<SocketAddr>,
+    tls: Option<TlsIdentity>,
+) -> Result<(), Error> {
+    let tls = tls.as_ref();
+    if runner.auth.is_open() {
+        #[cfg_attr(not(feature = "grpc"), allow(unused_mut))]
+        let mut addresses = vec![rest];
+        #[cfg(feature = "grpc")]
+        addresses.push(grpc);
+        if let Some(address) = addresses
+            .into_iter()
+            .flatten()
+            .find(|address| !address.ip().is_loopback())
+        {
+            return Err(Error::coded(
+                ErrorCode::Config,
+                format!(
+                    "Refusing to serve the wallets on {address} without credentials: pass --token, \
+                 --basic-auth or --auth-file, or listen on a loopback address"
+                ),
+            ));
+        }
+        log::warn!("No credentials given: only the requests that do not spend are answered.");
+    }
+    let rest = async {
+        match rest {
+            Some(address) => serve_rest(runner.clone(), address, tls).await,
+            None => Ok(()),
+        }
+    };
+    #[cfg(feature = "grpc")]
+    let rest = async {
+        let grpc = async {
+            match grpc {
+                Some(address) => crate::handlers::grpc::serve(runner.clone(), address, tls).await,
+                None => Ok(()),
+            }
+        };
+        tokio::try_join!(rest, grpc).map(|_| ())
+    };
+    rest.await
+}
+
+async fn serve_rest(
+    runner: Arc<Runner>,
+    address: SocketAddr,
+    tls: Option<&TlsIdentity>,
+) -> Result<(), Error> {
+    let mut router = Router::new().route("/openapi.json", get(|| async { axum::Json(openapi()) }));
+    for route in ROUTES {
+        let handler =
+            move |State(runner): State<Arc<Runner>>,
+                  Path(wallet): Path<String>,
+                  headers: HeaderMap,
+                  body: Bytes| answer(runner, route, wallet, headers, body);
+        let method: MethodRouter<Arc<Runner>> = match route.method {
+            Method::Get => get(handler),
+            Method::Post => post(handler),
+        };
+        router = router.route(route.path, method);
+    }
+
+    let router = router.with_state(runner);
+
+    if let Some(tls) = tls {
+        let config = RustlsConfig::from_pem(tls.cert.clone(), tls.key.clone())
+            .await
+            .map_err(|e| {
+                Error::coded(
+                    ErrorCode::Config,
+                    format!("Invalid TLS certificate or key: {e}"),
+                )
+            })?;
+        let handle = axum_server::Handle::new();
+        let shutdown = handle.clone();
+        tokio::spawn(async move {
+            crate::utils::shutdown_signal().await;
+            shutdown.graceful_shutdown(None);
+        });
+        log::info!("REST API on https://{address}");
+        return axum_server::bind_rustls(address, config)
+            .handle(handle)
+            .serve(router.into_make_service())
+            .await
+            .map_err(|e| Error::coded(ErrorCode::Io, format!("The server failed: {e}")));
+    }
+
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(|e| Error::coded(ErrorCode::Io, format!("Cannot listen on {address}: {e}")))?;
+    log::info!("REST API on http://{address}");
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async {
+            crate::utils::shutdown_signal().await;
+        })
+        .await
+        .map_err(|e| Error::coded(ErrorCode::Io, format!("The server failed: {e}")))
+}
+
+impl ServeCommand {
+    /// Serves the wallets of `datadir` until Ctrl-C or SIGTERM.
+    pub async fn serve(&self, datadir: PathBuf, offline: bool) -> Result<(), Error> {
+        log::info!("Serving the wallets of {}", datadir.display());
+        let tls = self.security.tls(&datadir)?;
+        let runner = Runner::new(datadir, offline, false, self.security.auth()?);
+        serve(
+            runner,
+            Some(self.listen),
+            #[cfg(feature = "grpc")]
+            self.grpc,
+            tls,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Payee;
+
+    #[test]
+    fn test_schema_name() {
+        assert_eq!(
+            schema_name::<<UnspentCommand as AppCommand<OfflineContext>>::Output>(),
+            "ListResult.UnspentDetails"
+        );
+        assert_eq!(
+            schema_name::<<BalanceCommand as AppCommand<OfflineContext>>::Output>(),
+            "BalanceResult"
+        );
+    }
+
+    #[test]
+    fn test_psbt_request_command() {
+        let body = br#"{"recipients": [{"address": "@alice", "amount": 1000}], "fee_rate": 2.5}"#;
+        let command = psbt_request_command(body).ok().unwrap();
+        let recipients = vec![(Payee::Contact("alice".to_string()), 1000)];
+        assert_eq!(
+            command,
+            offline_command(OfflineWalletSubCommand::CreateTx(CreateTxCommand::paying(
+                recipients,
+                Some(2.5),
+                false
+            )))
+        );
+        assert!(psbt_request_command(br#"{"to": []}"#).is_err());
+        assert!(psbt_request_command(br#"{"recipients": []}"#).is_err());
+        let body = br#"{"recipients": [{"address": "not an address", "amount": 1000}]}"#;
+        assert!(psbt_request_command(body).is_err());
+    }
+
+    #[test]
+    fn test_required_scope() {
+        let scope = |command| required_scope(&offline_command(command));
+        let balance = OfflineWalletSubCommand::Balance(BalanceCommand::default());
+        assert_eq!(scope(balance), Scope::Read);
+        let new_address = OfflineWalletSubCommand::NewAddress(NewAddressCommand);
+        assert_eq!(scope(new_address), Scope::Read);
+        let create_tx = create_tx_command([("@alice".to_string(), 1000)], None, false);
+        assert_eq!(required_scope(&create_tx.ok().unwrap()), Scope::Spend);
+    }
+
+    #[test]
+    fn test_openapi_paths() {
+        let doc = openapi();
+        let utxos = &doc["paths"]["/wallets/{name}/utxos"]["get"];
+        assert_eq!(utxos["operationId"], "unspent");
+        assert_eq!(
+            utxos["responses"]["200"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/ListResult.UnspentDetails"
+        );
+        let psbts = &doc["paths"]["/wallets/{name}/psbts"]["post"];
+        assert!(psbts["requestBody"].is_object());
+        assert!(doc["components"]["schemas"]["PsbtResult"].is_object());
+    }
+
+    #[test]
+    fn test_openapi_output_schemas() {
+        let doc = openapi();
+        let schemas = &doc["components"]["schemas"];
+        let balance = &schemas["BalanceResult"];
+        // The amounts are strings of bitcoins with `--unit btc`.
+        let confirmed = &balance["properties"]["confirmed"]["oneOf"];
+        assert_eq!(confirmed[0]["type"], "integer");
+        assert_eq!(confirmed[1]["type"], "string");
+        assert!(
+            balance["required"]
+                .as_array()
+                .unwrap()
+                .contains(&json!("confirmed"))
+        );
+        let psbt = &schemas["PsbtResult"]["properties"];
+        assert_eq!(psbt["psbt"]["type"], "string");
+        // The items of the lists are inlined.
+        let utxo = &schemas["ListResult.UnspentDetails"]["properties"]["items"]["items"];
+        assert_eq!(utxo["properties"]["outpoint"]["type"], "string");
+        assert_eq!(utxo["properties"]["is_locked"]["type"], "boolean");
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Wallet snapshot commands
+
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::commands::WalletSnapshotSubCommand;
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::handlers::{AppCommand, AppContext, Init};
+use crate::utils::confirm;
+use crate::utils::output::{FormatOutput, ListResult};
+use crate::utils::snapshot::{self, BACKUPS_DIR, SNAPSHOT_FILES};
+use crate::utils::types::{SnapshotEntry, SnapshotResult};
+use clap::Args;
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct RollbackCommand {
+    /// The wallet to roll back, set from `--wallet`.
+    #[arg(skip)]
+    pub(crate) wallet: String,
+}
+
+impl AppCommand<AppContext<Init>> for RollbackCommand {
+    type Output = SnapshotResult;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let wallet_dir = ctx.datadir.join(&self.wallet);
+        snapshot::check_database(&wallet_dir)?;
+        let dir = snapshot::automatic_snapshots(&wallet_dir)?
+            .pop()
+            .ok_or_else(|| {
+                Error::coded(
+                    ErrorCode::InvalidArgument,
+                    format!(
+                        "Wallet '{}' has no automatic snapshot to roll back to",
+                        self.wallet
+                    ),
+                )
+            })?;
+        let name = dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        confirm(
+            "the rollback",
+            &format!(
+                "Rolling wallet '{}' back to the snapshot {name}, replacing its database and labels",
+                self.wallet
+            ),
+        )?;
+        snapshot::restore(&wallet_dir, &dir)?;
+        // The next rollback goes one snapshot further back.
+        fs::remove_dir_all(&dir).map_err(|e| {
+            Error::coded(
+                ErrorCode::Io,
+                format!("Failed to remove {}: {e}", dir.display()),
+            )
+        })?;
+
+        Ok(SnapshotResult {
+            wallet: self.wallet.clone(),
+            snapshot: name,
+            files: files(&wallet_dir),
+        })
+    }
+}
+
+impl WalletSnapshotSubCommand {
+    pub fn execute(self, ctx: &mut AppContext<Init>, wallet: &str) -> Result<(), Error> {
+        let out = std::io::stdout();
+        match self {
+            Self::Create(mut cmd) => {
+                cmd.wallet = wallet.to_string();
+                cmd.execute(ctx)?.write_out(out)
+            }
+            Self::List(mut cmd) => {
+                cmd.wallet = wallet.to_string();
+                cmd.execute(ctx)?.write_out(out)
+            }
+            Self::Restore(mut cmd) => {
+                cmd.wallet = wallet.to_string();
+                cmd.execute(ctx)?.write_out(out)
+            }
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotCreateCommand {
+    /// Name of the snapshot, made of letters, digits, '-', '_' and '.'.
+    pub(crate) name: String,
+
+    /// The wallet to snapshot, set from `--wallet`.
+    #[arg(skip)]
+    pub(crate) wallet: String,
+}
+
+impl AppCommand<AppContext<Init>> for SnapshotCreateCommand {
+    type Output = SnapshotResult;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        snapshot::check_name(&self.name)?;
+        let wallet_dir = ctx.datadir.join(&self.wallet);
+        snapshot::check_database(&wallet_dir)?;
+        let dir = snapshot::create(&wallet_dir, &self.name)?;
+        Ok(SnapshotResult {
+            wallet: self.wallet.clone(),
+            snapshot: self.name.clone(),
+            files: files(&dir),
+        })
+    }
+}
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotListCommand {
+    /// The wallet whose snapshots are listed, set from `--wallet`.
+    #[arg(skip)]
+    pub(crate) wallet: String,
+}
+
+impl AppCommand<AppContext<Init>> for SnapshotListCommand {
+    type Output = ListResult<SnapshotEntry>;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let wallet_dir = ctx.datadir.join(&self.wallet);
+        let entries = snapshot::snapshots(&wallet_dir)?
+            .into_iter()
+            .map(|dir| SnapshotEntry {
+                name: dir
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                automatic: snapshot::is_automatic(&dir),
+                created_at: snapshot::created_at(&dir)
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_secs())
+                    .unwrap_or_default(),
+                files: files(&dir),
+            })
+            .collect();
+        Ok(ListResult::new(entries))
+    }
+}
+
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotRestoreCommand {
+    /// Name of the snapshot to restore, automatic snapshots included.
+    pub(crate) name: String,
+
+    /// The wallet to restore, set from `--wallet`.
+    #[arg(skip)]
+    pub(crate) wallet: String,
+}
+
+impl AppCommand<AppContext<Init>> for SnapshotRestoreCommand {
+    type Output = SnapshotResult;
+
+    fn execute(&self, ctx: &mut AppContext<Init>) -> Result<Self::Output, Error> {
+        let wallet_dir = ctx.datadir.join(&self.wallet);
+        snapshot::check_database(&wallet_dir)?;
+        let dir = wallet_dir.join(BACKUPS_DIR).join(&self.name);
+        if self.name.contains(['/', '\\']) || !dir.is_dir() {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                format!("Wallet '{}' has no snapshot '{}'", self.wallet, self.name),
+            ));
+        }
+        confirm(
+            "the restore",
+            &format!(
+                "Restoring the snapshot '{}' over wallet '{}', replacing its database and labels",
+                self.name, self.wallet
+            ),
+        )?;
+        // The current state stays one `rollback` away.
+        snapshot::auto_snapshot(&wallet_dir, "restore")?;
+        snapshot::restore(&wallet_dir, &dir)?;
+        Ok(SnapshotResult {
+            wallet: self.wallet.clone(),
+            snapshot: self.name.clone(),
+            files: files(&wallet_dir),
+        })
+    }
+}
+
+/// The wallet files held by the wallet or snapshot directory `dir`.
+fn files(dir: &Path) -> Vec<String> {
+    SNAPSHOT_FILES
+        .iter()
+        .filter(|file| dir.join(file).exists())
+        .map(|file| file.to_string())
+        .collect()
+}
@@ -0,0 +1,200 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Wallet labels
+//!
+//! Labels are stored per wallet in the [BIP-329] JSON lines format, which keeps them readable and
+//! lets them be exchanged with other wallets.
+//!
+//! [BIP-329]: https://github.com/bitcoin/bips/blob/master/bip-0329.mediawiki
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::utils::{is_read_only, read_only_error};
+use bdk_wallet::bitcoin::{Address, OutPoint, Txid};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The labels file of each wallet directory.
+pub const LABELS_FILE: &str = "labels.jsonl";
+
+/// The kind of object a label refers to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelType {
+    Tx,
+    Addr,
+    Pubkey,
+    Input,
+    Output,
+    Xpub,
+}
+
+/// A single BIP-329 label record.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Label {
+    #[serde(rename = "type")]
+    pub label_type: LabelType,
+    #[serde(rename = "ref")]
+    pub reference: String,
+    pub label: String,
+}
+
+impl Label {
+    /// Build a label, inferring its type from the reference: an outpoint, a txid or an address.
+    pub fn new(reference: &str, label: String) -> Result<Self, Error> {
+        let (label_type, reference) = if let Ok(outpoint) = OutPoint::from_str(reference) {
+            (LabelType::Output, outpoint.to_string())
+        } else if let Ok(txid) = Txid::from_str(reference) {
+            (LabelType::Tx, txid.to_string())
+        } else if let Ok(address) = Address::from_str(reference) {
+            (LabelType::Addr, address.assume_checked().to_string())
+        } else {
+            return Err(Error::coded(
+                ErrorCode::InvalidLabel,
+                format!(
+                    "Cannot label '{reference}': expected an address, a txid or a txid:vout outpoint"
+                ),
+            ));
+        };
+
+        Ok(Self {
+            label_type,
+            reference,
+            label,
+        })
+    }
+}
+
+/// Loads, queries and saves the labels of a wallet.
+#[derive(Debug)]
+pub struct LabelManager {
+    path: PathBuf,
+    labels: Vec<Label>,
+}
+
+impl LabelManager {
+    /// Load the labels stored in the wallet directory, if any.
+    pub fn load(wallet_dir: &Path) -> Result<Self, Error> {
+        let path = wallet_dir.join(LABELS_FILE);
+        let mut labels = Vec::new();
+
+        if path.exists() {
+            let content = fs::read_to_string(&path)
+                .map_err(|e| labels_error(format!("Failed to read labels file: {e}")))?;
+            for line in content.lines().filter(|line| !line.trim().is_empty()) {
+                let label: Label = serde_json::from_str(line)
+                    .map_err(|e| labels_error(format!("Failed to parse label '{line}': {e}")))?;
+                labels.push(label);
+            }
+        }
+
+        Ok(Self { path, labels })
+    }
+
+    /// Write the labels back to the wallet directory.
+    pub fn save(&self) -> Result<(), Error> {
+        if is_read_only() {
+            return Err(read_only_error());
+        }
+        let mut content = String::new();
+        for label in &self.labels {
+            content.push_str(&serde_json::to_string(label)?);
+            content.push('\n');
+        }
+        fs::write(&self.path, content).map_err(|e| {
+            labels_error(format!("Failed to write labels file {:?}: {e}", self.path))
+        })?;
+        log::debug!("Saved labels to {:?}", self.path);
+        Ok(())
+    }
+
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    /// Add a label, replacing any previous label of the same reference.
+    pub fn set(&mut self, label: Label) {
+        self.remove(&label.reference);
+        self.labels.push(label);
+    }
+
+    /// Remove the label of a reference, returning whether one was present.
+    pub fn remove(&mut self, reference: &str) -> bool {
+        let len = self.labels.len();
+        self.labels.retain(|label| label.reference != reference);
+        self.labels.len() != len
+    }
+
+    /// Get the label of a reference.
+    pub fn get(&self, label_type: LabelType, reference: &str) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|label| label.label_type == label_type && label.reference == reference)
+            .map(|label| label.label.as_str())
+    }
+
+    /// Whether an output, or the address it pays to, carries the given label.
+    pub fn output_has_label(
+        &self,
+        outpoint: &OutPoint,
+        address: Option<&Address>,
+        label: &str,
+    ) -> bool {
+        self.get(LabelType::Output, &outpoint.to_string()) == Some(label)
+            || address.is_some_and(|address| {
+                self.get(LabelType::Addr, &address.to_string()) == Some(label)
+            })
+    }
+}
+
+/// A failure reading or writing the labels file of a wallet.
+fn labels_error(message: String) -> Error {
+    Error::coded(ErrorCode::LabelsFile, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "tb1p4tp4l6glyr2gs94neqcpr5gha7344nfyznfkc8szkreflscsdkgqsdent4";
+    const OUTPOINT: &str = "0000000000000000000000000000000000000000000000000000000000000001:0";
+
+    #[test]
+    fn test_label_type_is_inferred_from_reference() {
+        let label = Label::new(OUTPOINT, "donation".to_string()).unwrap();
+        assert_eq!(label.label_type, LabelType::Output);
+        let label = Label::new(&OUTPOINT[..64], "donation".to_string()).unwrap();
+        assert_eq!(label.label_type, LabelType::Tx);
+        let label = Label::new(ADDRESS, "donation".to_string()).unwrap();
+        assert_eq!(label.label_type, LabelType::Addr);
+        assert!(Label::new("not a reference", "donation".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_labels_round_trip_as_bip329() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = LabelManager::load(dir.path()).unwrap();
+        manager.set(Label::new(ADDRESS, "old".to_string()).unwrap());
+        manager.set(Label::new(ADDRESS, "donation".to_string()).unwrap());
+        manager.save().unwrap();
+
+        let content = fs::read_to_string(dir.path().join(LABELS_FILE)).unwrap();
+        assert_eq!(
+            content,
+            format!("{{\"type\":\"addr\",\"ref\":\"{ADDRESS}\",\"label\":\"donation\"}}\n")
+        );
+
+        let manager = LabelManager::load(dir.path()).unwrap();
+        let address = Address::from_str(ADDRESS).unwrap().assume_checked();
+        let outpoint = OutPoint::from_str(OUTPOINT).unwrap();
+        assert!(manager.output_has_label(&outpoint, Some(&address), "donation"));
+        assert!(!manager.output_has_label(&outpoint, None, "donation"));
+    }
+}
@@ -15,26 +15,83 @@ mod commands;
 mod config;
 mod error;
 mod handlers;
+mod labels;
+mod multisig;
 mod persister;
 mod utils;
 
+use std::ffi::OsString;
+
 use bdk_wallet::bitcoin::Network;
 use log::{debug, warn};
 
+#[cfg(feature = "sqlite")]
+use crate::commands::WalletDbSubCommand;
+#[cfg(any(feature = "sqlite", feature = "redb"))]
+use crate::commands::WalletDebugSubCommand;
 use crate::commands::{CliOpts, CliSubCommand, WalletSubCommand};
 use crate::error::BDKCliError as Error;
 #[cfg(feature = "dns_payment")]
 use crate::handlers::AsyncAppCommand;
+#[cfg(feature = "repl")]
+use crate::handlers::repl::{ReplAction, ReplLine, Session};
 use crate::handlers::{AppCommand, AppContext};
-use crate::utils::output::FormatOutput;
+use crate::utils::amount::{set_unit, unit_from_globals};
+use crate::utils::logging::init_logger;
+use crate::utils::network::{network_preset, with_network_defaults};
+use crate::utils::output::{
+    FormatOutput, OutputFormat, disable_colors, set_explorer, set_output_format,
+};
+#[cfg(feature = "repl")]
+use crate::utils::output::{start_capture, take_captured};
+use crate::utils::price::{load_quote, set_quote};
 use crate::utils::runtime::WalletRuntime;
-use crate::utils::{command_requires_db, prepare_home_dir};
-use clap::{CommandFactory, Parser};
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+use crate::utils::snapshot::auto_snapshot;
+use crate::utils::stats;
+use crate::utils::{
+    check_network_flag, command_mutates_wallet, command_requires_db, command_requires_network,
+    command_uses_datadir, default_home_dir, explorer_url, is_offline, load_wallet_config,
+    prepare_home_dir, read_only_error, set_assume_yes, set_read_only,
+};
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches};
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
-    let cli_opts: CliOpts = CliOpts::parse();
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let globals = CliOpts::globals(&args);
+    set_unit(unit_from_globals(&globals));
+    let mut command = CliOpts::command();
+    if let Some(preset) = network_preset(&globals) {
+        command = with_network_defaults(command, preset);
+    }
+    let matches = command.get_matches_from(args);
+    let mut cli_opts = CliOpts::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if cli_opts.no_color {
+        disable_colors();
+    }
+    #[cfg(feature = "clipboard")]
+    if cli_opts.copy {
+        crate::utils::clipboard::enable_copy();
+    }
+    if let Err(e) = init_logger(
+        cli_opts.log_format,
+        cli_opts.log_file.as_deref(),
+        cli_opts.no_color,
+    ) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    cli_opts.network_given = matches.value_source("network") != Some(ValueSource::DefaultValue);
+    cli_opts.network_preset = network_preset(&matches);
+    cli_opts.output_given = matches.value_source("output") != Some(ValueSource::DefaultValue);
+    cli_opts.unit_given = matches.value_source("unit") != Some(ValueSource::DefaultValue);
 
     let network = &cli_opts.network;
     debug!("network: {network:?}");
@@ -44,15 +101,87 @@ async fn main() {
         )
     }
 
-    if let Err(e) = run(cli_opts).await {
+    // Scripts asking for JSON also get the failures as JSON, with their stable code.
+    let json_errors = cli_opts.output_given && cli_opts.output == OutputFormat::Json;
+    if cli_opts.stats {
+        stats::enable(json_errors);
+    }
+    let result = run(cli_opts).await;
+    stats::report();
+    if let Err(e) = result {
+        if json_errors {
+            println!("{:#}", e.to_json());
+        }
         eprintln!("Error: {}", e);
-        std::process::exit(1);
+        std::process::exit(e.category().exit_code());
     }
 }
 
 async fn run(cli_opts: CliOpts) -> Result<(), Error> {
-    let datadir = cli_opts.datadir.clone();
-    let home_dir = prepare_home_dir(datadir)?;
+    let uses_datadir = command_uses_datadir(&cli_opts.subcommand);
+    // The key tooling never reads the data directory, which is resolved but left uncreated.
+    let home_dir = match (uses_datadir, cli_opts.datadir.clone()) {
+        (true, datadir) => prepare_home_dir(datadir)?,
+        (false, Some(datadir)) => datadir,
+        (false, None) => default_home_dir()?.0,
+    };
+
+    if command_requires_network(&cli_opts.subcommand) && is_offline(cli_opts.offline, &home_dir)? {
+        return Err(Error::Offline);
+    }
+    if cli_opts.read_only {
+        if command_mutates_wallet(&cli_opts.subcommand) {
+            return Err(read_only_error());
+        }
+        set_read_only();
+    }
+    if cli_opts.yes {
+        set_assume_yes();
+    }
+    if cli_opts.network_given {
+        check_network_flag(
+            cli_opts.network,
+            cli_opts.network_preset,
+            &home_dir,
+            &cli_opts.subcommand,
+        )?;
+    }
+    if cli_opts.output_given {
+        set_output_format(cli_opts.output);
+    }
+    if cli_opts.links {
+        let explorer = explorer_url(
+            cli_opts.explorer_url.as_deref(),
+            &home_dir,
+            &cli_opts.subcommand,
+            cli_opts.network,
+        )?;
+        set_explorer(&explorer);
+    }
+    // Without a wallet, there are no amounts to value.
+    if let Some(currency) = &cli_opts.fiat
+        && uses_datadir
+    {
+        let offline = is_offline(cli_opts.offline, &home_dir)?;
+        // The prices are asked through the proxy of the wallet, if it has one.
+        let wallet_opts = match &cli_opts.subcommand {
+            CliSubCommand::Wallet { wallet, .. } => load_wallet_config(&home_dir, wallet)
+                .ok()
+                .map(|(wallet_opts, _)| wallet_opts),
+            _ => None,
+        };
+        if let Some(quote) = load_quote(
+            currency,
+            cli_opts.price_url.as_deref(),
+            &home_dir,
+            offline,
+            wallet_opts.as_ref(),
+        )
+        .await
+        {
+            set_quote(quote);
+        }
+    }
 
     match cli_opts.subcommand.clone() {
         CliSubCommand::Wallet {
@@ -67,16 +196,22 @@ async fn run(cli_opts: CliOpts) -> Result<(), Error> {
             ))]
             WalletSubCommand::OnlineWalletSubCommand(cmd) => {
                 let runtime = WalletRuntime::load(&home_dir, &wallet_name)?;
+                if matches!(cmd, commands::OnlineWalletSubCommand::Rescan(_)) {
+                    auto_snapshot(&runtime.database_path, "rescan")?;
+                }
                 let mut wallet = runtime.build_wallet(true)?;
                 let client = runtime.build_client(&wallet)?;
                 {
+                    let (wallet, persister) = wallet.parts();
                     let mut ctx = AppContext::new_online_wallet(
                         runtime.network,
                         runtime.home_dir.clone(),
-                        &mut wallet,
+                        wallet,
                         &client,
                         runtime.wallet_name.clone(),
-                    );
+                        runtime.wallet_opts.stop_gap,
+                    )
+                    .with_persister(persister);
 
                     cmd.execute(&mut ctx).await?;
                 }
@@ -92,6 +227,8 @@ async fn run(cli_opts: CliOpts) -> Result<(), Error> {
                         runtime.network,
                         runtime.home_dir.clone(),
                         &mut wallet,
+                        runtime.wallet_name.clone(),
+                        runtime.wallet_opts.stop_gap,
                     );
 
                     match cmd {
@@ -110,11 +247,85 @@ async fn run(cli_opts: CliOpts) -> Result<(), Error> {
 
             WalletSubCommand::Config(mut config_cmd) => {
                 config_cmd.wallet_opts.wallet = Some(wallet_name);
+                config_cmd.offline = cli_opts.offline;
+                config_cmd.explorer_url = cli_opts.explorer_url.clone();
+                config_cmd.unit = cli_opts.unit_given.then_some(cli_opts.unit);
 
                 let mut ctx = AppContext::new(cli_opts.network, home_dir);
 
                 config_cmd.execute(&mut ctx)?.write_out(std::io::stdout())?;
             }
+
+            WalletSubCommand::Backup(mut backup_cmd) => {
+                backup_cmd.wallet = wallet_name;
+                let mut ctx = AppContext::new(cli_opts.network, home_dir);
+
+                backup_cmd.execute(&mut ctx)?.write_out(std::io::stdout())?;
+            }
+
+            #[cfg(any(feature = "sqlite", feature = "redb"))]
+            WalletSubCommand::Debug {
+                subcommand: WalletDebugSubCommand::Changesets(mut changesets_cmd),
+            } => {
+                changesets_cmd.wallet = wallet_name;
+                let mut ctx = AppContext::new(cli_opts.network, home_dir);
+
+                changesets_cmd
+                    .execute(&mut ctx)?
+                    .write_out(std::io::stdout())?;
+            }
+
+            #[cfg(feature = "sqlite")]
+            WalletSubCommand::Db {
+                subcommand: WalletDbSubCommand::Vacuum(mut vacuum_cmd),
+            } => {
+                vacuum_cmd.wallet = wallet_name;
+                let mut ctx = AppContext::new(cli_opts.network, home_dir);
+
+                vacuum_cmd.execute(&mut ctx)?.write_out(std::io::stdout())?;
+            }
+
+            WalletSubCommand::Snapshot { subcommand } => {
+                let mut ctx = AppContext::new(cli_opts.network, home_dir);
+
+                subcommand.execute(&mut ctx, &wallet_name)?;
+            }
+
+            WalletSubCommand::Rollback(mut rollback_cmd) => {
+                rollback_cmd.wallet = wallet_name;
+                let mut ctx = AppContext::new(cli_opts.network, home_dir);
+
+                rollback_cmd
+                    .execute(&mut ctx)?
+                    .write_out(std::io::stdout())?;
+            }
+
+            #[cfg(feature = "sqlite")]
+            WalletSubCommand::Migrate(mut migrate_cmd) => {
+                migrate_cmd.wallet = wallet_name;
+                let mut ctx = AppContext::new(cli_opts.network, home_dir);
+
+                migrate_cmd
+                    .execute(&mut ctx)?
+                    .write_out(std::io::stdout())?;
+            }
+
+            WalletSubCommand::Restore(mut restore_cmd) => {
+                restore_cmd.wallet = wallet_name;
+                let mut ctx = AppContext::new(cli_opts.network, home_dir);
+
+                restore_cmd
+                    .execute(&mut ctx)?
+                    .write_out(std::io::stdout())?;
+            }
+
+            #[cfg(feature = "rpc")]
+            WalletSubCommand::GenerateTest(generate_cmd) => {
+                generate_cmd
+                    .execute(&home_dir, &wallet_name, cli_opts.network)
+                    .await?
+                    .write_out(std::io::stdout())?;
+            }
         },
 
         CliSubCommand::Key { subcommand } => {
@@ -123,25 +334,101 @@ async fn run(cli_opts: CliOpts) -> Result<(), Error> {
             subcommand.execute(&mut ctx)?;
         }
 
+        CliSubCommand::Psbt { subcommand } => {
+            let mut ctx = AppContext::new(cli_opts.network, home_dir);
+
+            subcommand.execute(&mut ctx)?;
+        }
+
+        CliSubCommand::Multisig { subcommand } => {
+            let mut ctx = AppContext::new(cli_opts.network, home_dir);
+
+            subcommand.execute(&mut ctx)?;
+        }
+
+        #[cfg(any(feature = "electrum", feature = "esplora"))]
+        CliSubCommand::Backend {
+            wallet: wallet_name,
+            subcommand,
+        } => {
+            let runtime = WalletRuntime::load(&home_dir, &wallet_name)?;
+            let mut wallet = runtime.build_wallet(false)?;
+            let mut ctx = AppContext::new_offline_wallet(
+                runtime.network,
+                runtime.home_dir.clone(),
+                &mut wallet,
+                runtime.wallet_name.clone(),
+                runtime.wallet_opts.stop_gap,
+            );
+
+            subcommand.execute(&mut ctx).await?;
+        }
+
+        #[cfg(feature = "rpc")]
+        CliSubCommand::Node {
+            wallet: wallet_name,
+            node,
+            subcommand,
+        } => subcommand.execute(&home_dir, &wallet_name, &node).await?,
+
         CliSubCommand::Descriptor(cmd) => {
             let mut ctx = AppContext::new(cli_opts.network, home_dir);
 
             cmd.execute(&mut ctx)?.write_out(std::io::stdout())?;
         }
 
+        CliSubCommand::Init(mut init_cmd) => {
+            if cli_opts.network_given {
+                init_cmd.network = Some(cli_opts.network);
+            }
+            init_cmd.offline = cli_opts.offline;
+            init_cmd.no_scan |= is_offline(cli_opts.offline, &home_dir)?;
+            let mut ctx = AppContext::new(cli_opts.network, home_dir.clone());
+
+            let result = init_cmd.execute(&mut ctx)?;
+            #[cfg(any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf"
+            ))]
+            let result = match result.scan {
+                true => {
+                    handlers::init::first_scan(&home_dir, &result.wallet).await?;
+                    crate::utils::types::InitResult {
+                        scanned: true,
+                        ..result
+                    }
+                }
+                false => result,
+            };
+            result.write_out(std::io::stdout())?;
+        }
+
         CliSubCommand::Wallets(cmd) => {
             let mut ctx = AppContext::new(cli_opts.network, home_dir);
 
             cmd.execute(&mut ctx)?.write_out(std::io::stdout())?;
         }
 
+        CliSubCommand::Paths(mut cmd) => {
+            cmd.source = match cli_opts.datadir {
+                Some(_) => "--datadir".to_string(),
+                None => default_home_dir()?.1.to_string(),
+            };
+            let mut ctx = AppContext::new(cli_opts.network, home_dir);
+
+            cmd.execute(&mut ctx)?.write_out(std::io::stdout())?;
+        }
+
         #[cfg(feature = "repl")]
         CliSubCommand::Repl {
-            wallet: wallet_name,
+            wallet: mut wallet_name,
         } => {
-            let runtime = WalletRuntime::load(&home_dir, &wallet_name)?;
+            let mut runtime = WalletRuntime::load(&home_dir, &wallet_name)?;
 
             let mut wallet = runtime.build_wallet(true)?;
+            let offline = is_offline(cli_opts.offline, &home_dir)?;
 
             #[cfg(any(
                 feature = "electrum",
@@ -149,7 +436,7 @@ async fn run(cli_opts: CliOpts) -> Result<(), Error> {
                 feature = "rpc",
                 feature = "cbf"
             ))]
-            let client = runtime.build_client(&wallet).ok();
+            let mut client = crate::handlers::repl::session_client(&runtime, &wallet, offline);
 
             println!(
                 "Entering REPL mode for wallet '{}'. \
@@ -157,14 +444,28 @@ async fn run(cli_opts: CliOpts) -> Result<(), Error> {
                 wallet_name
             );
 
+            let mut editor = crate::handlers::repl::LineEditor::new(&runtime.home_dir)?;
+            let mut session = Session::default();
             loop {
-                let line = crate::handlers::repl::readline()?;
+                let Some(line) = editor.readline()? else {
+                    break;
+                };
 
                 if line.trim().is_empty() {
                     continue;
                 }
 
-                let should_exit = crate::handlers::repl::respond(
+                let repl_line = match ReplLine::parse(&line) {
+                    Ok(repl_line) => repl_line,
+                    Err(e) => {
+                        crate::handlers::repl::print_error(&e);
+                        continue;
+                    }
+                };
+                if repl_line.variable.is_some() {
+                    start_capture();
+                }
+                let response = crate::handlers::repl::respond(
                     runtime.network,
                     &mut wallet,
                     #[cfg(any(
@@ -173,26 +474,144 @@ async fn run(cli_opts: CliOpts) -> Result<(), Error> {
                         feature = "rpc",
                         feature = "cbf"
                     ))]
-                    client.as_ref(),
-                    &line,
+                    client.as_ref().map_err(String::as_str),
+                    repl_line.command,
+                    &mut session,
                     runtime.home_dir.clone(),
-                    #[cfg(any(
-                        feature = "electrum",
-                        feature = "esplora",
-                        feature = "rpc",
-                        feature = "cbf"
-                    ))]
+                    runtime.wallet_opts.stop_gap,
                     &wallet_name,
+                    offline,
                 )
-                .await
-                .map_err(Error::Generic)?;
+                .await;
+                if let Some(name) = repl_line.variable {
+                    let outputs = take_captured();
+                    for output in &outputs {
+                        output.write_out(std::io::stdout())?;
+                    }
+                    if response.is_ok() {
+                        session.variables.set(name, &outputs);
+                    }
+                }
 
-                if should_exit {
-                    break;
+                match response {
+                    Err(e) => crate::handlers::repl::print_error(&e),
+                    Ok(ReplAction::Continue) => {}
+                    Ok(ReplAction::Exit) => break,
+                    Ok(ReplAction::Use(target)) => {
+                        let next = match WalletRuntime::load(&home_dir, &target.wallet) {
+                            Ok(next) => next,
+                            Err(e) => {
+                                eprintln!("error: {e}");
+                                continue;
+                            }
+                        };
+                        if let Some(network) = target.network
+                            && network != next.network
+                        {
+                            eprintln!(
+                                "error: Wallet '{}' is on {}, not {network}",
+                                target.wallet, next.network
+                            );
+                            continue;
+                        }
+                        if next.wallet_name == wallet_name {
+                            println!("Already on wallet '{wallet_name}'.");
+                            continue;
+                        }
+                        // The wallet being left stays locked until replaced.
+                        let next_wallet = match next.build_wallet(true) {
+                            Ok(next_wallet) => next_wallet,
+                            Err(e) => {
+                                eprintln!("error: {e}");
+                                continue;
+                            }
+                        };
+                        wallet.persist()?;
+                        wallet = next_wallet;
+                        #[cfg(any(
+                            feature = "electrum",
+                            feature = "esplora",
+                            feature = "rpc",
+                            feature = "cbf"
+                        ))]
+                        {
+                            client = crate::handlers::repl::session_client(&next, &wallet, offline);
+                        }
+                        wallet_name = next.wallet_name.clone();
+                        runtime = next;
+                        println!("Switched to wallet '{wallet_name}' on {}.", runtime.network);
+                    }
                 }
+                stats::report();
             }
+            // Each line was reported on its own.
+            stats::disable();
         }
 
+        #[cfg(feature = "repl")]
+        CliSubCommand::Run {
+            wallet: wallet_name,
+            script,
+        } => {
+            let lines = script.lines()?;
+            let runtime = WalletRuntime::load(&home_dir, &wallet_name)?;
+            let mut wallet = runtime.build_wallet(true)?;
+            let offline = is_offline(cli_opts.offline, &home_dir)?;
+
+            #[cfg(any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf"
+            ))]
+            let client = crate::handlers::repl::session_client(&runtime, &wallet, offline);
+
+            let (results, failure) = crate::handlers::repl::run_batch(
+                runtime.network,
+                &mut wallet,
+                #[cfg(any(
+                    feature = "electrum",
+                    feature = "esplora",
+                    feature = "rpc",
+                    feature = "cbf"
+                ))]
+                client.as_ref().map_err(String::as_str),
+                lines,
+                runtime.home_dir.clone(),
+                runtime.wallet_opts.stop_gap,
+                &wallet_name,
+                offline,
+                script.keep_going,
+            )
+            .await;
+            wallet.persist()?;
+            results.write_out(std::io::stdout())?;
+            if let Some(failure) = failure {
+                return Err(Error::Generic(failure));
+            }
+        }
+
+        #[cfg(feature = "server")]
+        CliSubCommand::Serve(serve_cmd) => {
+            if serve_cmd.openapi {
+                let openapi = crate::handlers::server::openapi();
+                println!("{}", serde_json::to_string_pretty(&openapi)?);
+            } else {
+                let offline = is_offline(cli_opts.offline, &home_dir)?;
+                serve_cmd.serve(home_dir, offline).await?;
+            }
+        }
+        #[cfg(all(
+            feature = "server",
+            any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf"
+            )
+        ))]
+        CliSubCommand::Daemon(daemon_cmd) => daemon_cmd.run(home_dir).await?,
+
         #[cfg(feature = "compiler")]
         CliSubCommand::Compile(cmd) => {
             let mut ctx = AppContext::new(cli_opts.network, home_dir);
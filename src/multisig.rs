@@ -0,0 +1,266 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Multisig coordination sessions
+//!
+//! A session follows one spend from the unsigned PSBT to the finalized transaction. The signed
+//! PSBT returned by each cosigner is kept next to the merged PSBT, so the state of the quorum can
+//! be inspected at any time. Sessions are stored as JSON files in `<datadir>/multisig`.
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::utils::parse_psbt_with_version;
+use crate::utils::psbt::{PsbtVersion, serialize_psbt};
+use bdk_wallet::bitcoin::base64::{Engine, prelude::BASE64_STANDARD};
+use bdk_wallet::bitcoin::bip32::Fingerprint;
+use bdk_wallet::bitcoin::{Psbt, PublicKey, Script, Txid, XOnlyPublicKey, psbt::Input};
+use bdk_wallet::miniscript::{Legacy, Miniscript, ScriptContext, Segwitv0, Tap, Terminal};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SESSIONS_DIR: &str = "multisig";
+
+/// A signed PSBT returned by one or more cosigners.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Contribution {
+    pub signers: Vec<Fingerprint>,
+    pub psbt: String,
+}
+
+/// The state of a multisig spend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultisigSession {
+    pub name: String,
+    pub txid: Txid,
+    pub original: String,
+    pub contributions: Vec<Contribution>,
+    pub merged: String,
+    pub is_finalized: bool,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl MultisigSession {
+    /// Start a session for an unsigned PSBT. Fails if the session already exists.
+    pub fn create(
+        datadir: &Path,
+        name: &str,
+        psbt: &Psbt,
+        version: PsbtVersion,
+    ) -> Result<Self, Error> {
+        let path = session_path(datadir, name)?;
+        if path.exists() {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                format!("Multisig session '{name}' already exists"),
+            ));
+        }
+
+        let encoded = encode(psbt, version);
+        Ok(Self {
+            name: name.to_string(),
+            txid: psbt.unsigned_tx.compute_txid(),
+            original: encoded.clone(),
+            contributions: Vec::new(),
+            merged: encoded,
+            is_finalized: false,
+            path,
+        })
+    }
+
+    /// Load an existing session.
+    pub fn load(datadir: &Path, name: &str) -> Result<Self, Error> {
+        let path = session_path(datadir, name)?;
+        let content = fs::read_to_string(&path).map_err(|e| {
+            Error::coded(
+                ErrorCode::Io,
+                format!("Cannot read multisig session '{name}': {e}"),
+            )
+        })?;
+        let mut session: Self = serde_json::from_str(&content)?;
+        session.path = path;
+        Ok(session)
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| {
+                Error::coded(
+                    ErrorCode::Io,
+                    format!("Failed to create multisig directory {dir:?}: {e}"),
+                )
+            })?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(self)?).map_err(|e| {
+            Error::coded(
+                ErrorCode::Io,
+                format!("Failed to write multisig session {:?}: {e}", self.path),
+            )
+        })?;
+        log::debug!("Saved multisig session to {:?}", self.path);
+        Ok(())
+    }
+
+    /// The PSBT combining every contribution so far.
+    pub fn merged(&self) -> Result<(Psbt, PsbtVersion), Error> {
+        parse_psbt_with_version(&self.merged)
+    }
+
+    pub fn set_merged(&mut self, psbt: &Psbt, version: PsbtVersion) {
+        self.merged = encode(psbt, version);
+    }
+
+    /// Merge a signed PSBT, returning the fingerprints of the cosigners that signed it.
+    pub fn add(&mut self, psbt: &Psbt, version: PsbtVersion) -> Result<Vec<Fingerprint>, Error> {
+        if self.is_finalized {
+            return Err(Error::coded(
+                ErrorCode::InvalidPsbt,
+                format!("Multisig session '{}' is already finalized", self.name),
+            ));
+        }
+        let txid = psbt.unsigned_tx.compute_txid();
+        if txid != self.txid {
+            return Err(Error::coded(
+                ErrorCode::InvalidPsbt,
+                format!(
+                    "The PSBT spends transaction {txid}, the session is for {}",
+                    self.txid
+                ),
+            ));
+        }
+
+        let signers: Vec<Fingerprint> = psbt
+            .inputs
+            .iter()
+            .flat_map(signed_by)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        if signers.is_empty() {
+            return Err(Error::coded(
+                ErrorCode::InvalidPsbt,
+                "The PSBT carries no signature from a key with a known origin".to_string(),
+            ));
+        }
+
+        let (mut merged, merged_version) = self.merged()?;
+        merged.combine(psbt.clone())?;
+        self.set_merged(&merged, merged_version);
+        self.contributions.push(Contribution {
+            signers: signers.clone(),
+            psbt: encode(psbt, version),
+        });
+        Ok(signers)
+    }
+}
+
+/// Every cosigner with a key in the PSBT, and whether it signed all the inputs it has a key in.
+/// Finalized inputs count as signed by all their cosigners.
+pub fn cosigners(psbt: &Psbt) -> BTreeMap<Fingerprint, bool> {
+    let mut cosigners = BTreeMap::new();
+    for input in &psbt.inputs {
+        let is_final = input.final_script_sig.is_some() || input.final_script_witness.is_some();
+        let signed = signed_by(input);
+        for fingerprint in key_fingerprints(input) {
+            let input_signed = is_final || signed.contains(&fingerprint);
+            cosigners
+                .entry(fingerprint)
+                .and_modify(|all_signed| *all_signed &= input_signed)
+                .or_insert(input_signed);
+        }
+    }
+    cosigners
+}
+
+/// Fingerprints of the keys with a known origin in an input.
+pub fn key_fingerprints(input: &Input) -> BTreeSet<Fingerprint> {
+    input
+        .bip32_derivation
+        .values()
+        .map(|(fingerprint, _)| *fingerprint)
+        .chain(
+            input
+                .tap_key_origins
+                .values()
+                .map(|(_, (fingerprint, _))| *fingerprint),
+        )
+        .collect()
+}
+
+/// Signatures needed by the multisig script of an input: the `multi` of its witness or redeem
+/// script, or the smallest `multi_a` of its taproot leaves.
+pub fn threshold(input: &Input) -> Option<usize> {
+    let script_threshold = match (&input.witness_script, &input.redeem_script) {
+        (Some(script), _) => multi_threshold::<Segwitv0>(script),
+        (None, Some(script)) => multi_threshold::<Legacy>(script),
+        (None, None) => None,
+    };
+    script_threshold.or_else(|| {
+        input
+            .tap_scripts
+            .values()
+            .filter_map(|(script, _)| {
+                let ms = Miniscript::<XOnlyPublicKey, Tap>::parse_insane(script).ok()?;
+                ms.iter().find_map(|node| match &node.node {
+                    Terminal::MultiA(thresh) => Some(thresh.k()),
+                    _ => None,
+                })
+            })
+            .min()
+    })
+}
+
+fn multi_threshold<Ctx: ScriptContext>(script: &Script) -> Option<usize> {
+    let ms = Miniscript::<PublicKey, Ctx>::parse_insane(script).ok()?;
+    ms.iter().find_map(|node| match &node.node {
+        Terminal::Multi(thresh) => Some(thresh.k()),
+        _ => None,
+    })
+}
+
+/// Fingerprints of the keys that signed an input.
+pub fn signed_by(input: &Input) -> BTreeSet<Fingerprint> {
+    let ecdsa = input
+        .bip32_derivation
+        .iter()
+        .filter(|(pubkey, _)| input.partial_sigs.contains_key(&PublicKey::new(**pubkey)))
+        .map(|(_, (fingerprint, _))| *fingerprint);
+    let taproot = input
+        .tap_key_origins
+        .iter()
+        .filter(|(pubkey, (leaf_hashes, _))| {
+            if leaf_hashes.is_empty() {
+                input.tap_key_sig.is_some()
+            } else {
+                leaf_hashes
+                    .iter()
+                    .any(|leaf_hash| input.tap_script_sigs.contains_key(&(**pubkey, *leaf_hash)))
+            }
+        })
+        .map(|(_, (_, (fingerprint, _)))| *fingerprint);
+    ecdsa.chain(taproot).collect()
+}
+
+fn encode(psbt: &Psbt, version: PsbtVersion) -> String {
+    BASE64_STANDARD.encode(serialize_psbt(psbt, version))
+}
+
+fn session_path(datadir: &Path, name: &str) -> Result<PathBuf, Error> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("Invalid multisig session name '{name}': use letters, digits, '-' and '_'"),
+        ));
+    }
+    Ok(datadir.join(SESSIONS_DIR).join(format!("{name}.json")))
+}
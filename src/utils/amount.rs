@@ -0,0 +1,235 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Amounts in satoshis or bitcoins
+//!
+//! Amounts are taken as `150000sat` or `0.0015btc`, and bare numbers are in the unit of
+//! `--unit`, satoshis by default. With `--unit btc`, the amounts of the outputs are written as
+//! strings with the 8 decimals of a bitcoin amount, so that no float rounding creeps in.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use bdk_wallet::bitcoin::{Amount, Denomination};
+use clap::{ArgMatches, ValueEnum, parser::ValueSource};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::config::WalletConfig;
+use crate::utils::default_home_dir;
+
+/// Set by `--unit btc`.
+static BTC: AtomicBool = AtomicBool::new(false);
+
+/// The units of the amounts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    /// Satoshis, written as integers.
+    #[default]
+    Sat,
+    /// Bitcoins, written with 8 decimals.
+    Btc,
+}
+
+/// Takes and writes the amounts in `unit`.
+pub fn set_unit(unit: Unit) {
+    BTC.store(unit == Unit::Btc, Ordering::Relaxed);
+}
+
+fn unit() -> Unit {
+    match BTC.load(Ordering::Relaxed) {
+        true => Unit::Btc,
+        false => Unit::Sat,
+    }
+}
+
+/// The `--unit` of the global options `globals`, or of `AMOUNT_UNIT`, else the `unit` saved in
+/// the `config.toml` of the data directory. Clap parses the amounts of the arguments along with
+/// the command line, so their unit is needed before it runs.
+pub fn unit_from_globals(globals: &ArgMatches) -> Unit {
+    let given = globals
+        .try_get_one::<Unit>("unit")
+        .ok()
+        .flatten()
+        .filter(|_| globals.value_source("unit") != Some(ValueSource::DefaultValue));
+    if let Some(unit) = given {
+        return *unit;
+    }
+    let datadir = match globals.try_get_one::<PathBuf>("datadir").ok().flatten() {
+        Some(datadir) => datadir.clone(),
+        None => match default_home_dir() {
+            Ok((datadir, _)) => datadir,
+            Err(_) => return Unit::default(),
+        },
+    };
+    // A broken config file is reported by the command.
+    WalletConfig::load(&datadir)
+        .ok()
+        .flatten()
+        .and_then(|config| config.unit)
+        .unwrap_or_default()
+}
+
+/// Parse an amount such as `150000sat`, `150000sats`, `0.0015btc` or a bare number in the unit of
+/// `--unit`, into satoshis.
+pub(crate) fn parse_amount(s: &str) -> Result<u64, String> {
+    let s = s.trim().to_lowercase();
+    let (number, denomination) = if let Some(number) = s.strip_suffix("btc") {
+        (number, Denomination::Bitcoin)
+    } else if let Some(number) = s.strip_suffix("sats").or_else(|| s.strip_suffix("sat")) {
+        (number, Denomination::Satoshi)
+    } else {
+        match unit() {
+            Unit::Sat => (s.as_str(), Denomination::Satoshi),
+            Unit::Btc => (s.as_str(), Denomination::Bitcoin),
+        }
+    };
+    Amount::from_str_in(number.trim(), denomination)
+        .map(|amount| amount.to_sat())
+        .map_err(|e| format!("Invalid amount '{s}': {e}"))
+}
+
+/// `sats` in the unit of `--unit`, a number of satoshis or a string of bitcoins.
+pub(crate) fn to_value(sats: u64) -> Value {
+    match unit() {
+        Unit::Sat => Value::from(sats),
+        Unit::Btc => Value::String(btc(sats)),
+    }
+}
+
+/// `sats` in the unit of `--unit` like [`to_value`], negative for the amounts leaving the wallet.
+pub(crate) fn to_signed_value(sats: i64) -> Value {
+    match (unit(), sats < 0) {
+        (Unit::Sat, _) => Value::from(sats),
+        (Unit::Btc, false) => Value::String(btc(sats.unsigned_abs())),
+        (Unit::Btc, true) => Value::String(format!("-{}", btc(sats.unsigned_abs()))),
+    }
+}
+
+/// `sats` with its unit, for the summaries written to the terminal.
+pub(crate) fn display(sats: u64) -> String {
+    match unit() {
+        Unit::Sat => format!("{sats} sat"),
+        Unit::Btc => format!("{} BTC", btc(sats)),
+    }
+}
+
+fn btc(sats: u64) -> String {
+    format!("{}.{:08}", sats / 100_000_000, sats % 100_000_000)
+}
+
+/// Serializes an amount of satoshis in the unit of `--unit`, for `#[serde(serialize_with)]`.
+pub(crate) fn serialize<S: Serializer>(sats: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    to_value(*sats).serialize(serializer)
+}
+
+/// Serializes a signed amount of satoshis in the unit of `--unit`.
+pub(crate) fn serialize_signed<S: Serializer>(
+    sats: &i64,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    to_signed_value(*sats).serialize(serializer)
+}
+
+/// Serializes an optional amount of satoshis in the unit of `--unit`.
+pub(crate) fn serialize_option<S: Serializer>(
+    sats: &Option<u64>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match sats {
+        Some(sats) => serialize(sats, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// The JSON schema of the amounts of [`serialize`], for `#[schemars(with)]`: an integer of
+/// satoshis, or a string of bitcoins with `--unit btc`.
+#[cfg(feature = "server")]
+pub(crate) struct AmountSchema;
+
+#[cfg(feature = "server")]
+impl schemars::JsonSchema for AmountSchema {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Amount".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "Satoshis, or a string of bitcoins with 8 decimals with `--unit btc`.",
+            "oneOf": [
+                { "type": "integer", "minimum": 0 },
+                { "type": "string", "pattern": "^[0-9]+\\.[0-9]{8}$" }
+            ]
+        })
+    }
+}
+
+/// The JSON schema of the amounts of [`serialize_signed`].
+#[cfg(feature = "server")]
+pub(crate) struct SignedAmountSchema;
+
+#[cfg(feature = "server")]
+impl schemars::JsonSchema for SignedAmountSchema {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "SignedAmount".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "Satoshis, or a string of bitcoins with 8 decimals with `--unit btc`, \
+                negative when leaving the wallet.",
+            "oneOf": [
+                { "type": "integer" },
+                { "type": "string", "pattern": "^-?[0-9]+\\.[0-9]{8}$" }
+            ]
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("150000sat"), Ok(150_000));
+        assert_eq!(parse_amount("150000 sats"), Ok(150_000));
+        assert_eq!(parse_amount("0.015BTC"), Ok(1_500_000));
+        assert_eq!(parse_amount("2100"), Ok(2100));
+        assert!(parse_amount("0.000000001btc").is_err());
+        assert!(parse_amount("1.5sat").is_err());
+        assert!(parse_amount("-1").is_err());
+        assert_eq!(btc(1_500_000), "0.01500000");
+        assert_eq!(btc(2_100_000_000_000_000), "21000000.00000000");
+    }
+
+    #[test]
+    fn test_unit_from_globals() {
+        use crate::commands::CliOpts;
+
+        let datadir = tempfile::tempdir().unwrap();
+        let unit = |args: &[&str]| {
+            let mut full_args = vec!["bdk-cli", "--datadir", datadir.path().to_str().unwrap()];
+            full_args.extend_from_slice(args);
+            unit_from_globals(&CliOpts::globals(full_args))
+        };
+        assert_eq!(unit(&["--unit", "btc", "wallet"]), Unit::Btc);
+        assert_eq!(unit(&["--unit=btc", "wallet"]), Unit::Btc);
+        assert_eq!(unit(&["wallet", "--", "--unit", "btc"]), Unit::Sat);
+
+        // The unit saved in the config file, unless `--unit` is given.
+        std::fs::write(
+            datadir.path().join("config.toml"),
+            "unit = \"btc\"\nwallets = {}\n",
+        )
+        .unwrap();
+        assert_eq!(unit(&["wallets"]), Unit::Btc);
+        assert_eq!(unit(&["--unit", "sat", "wallets"]), Unit::Sat);
+    }
+}
@@ -0,0 +1,373 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Server authentication and TLS
+//!
+//! The requests of `serve` and `daemon` authenticate with a bearer token or with HTTP basic
+//! auth, in the `authorization` header of REST requests and in the metadata of gRPC calls. Each
+//! credential has a scope: `read` allows every request but the ones building or broadcasting a
+//! transaction, which need `spend`. A server without credentials only answers the `read`
+//! requests, so that no spending endpoint is ever served unauthenticated.
+//!
+//! The credentials can be kept off the command line, in the environment or in the file of
+//! `--auth-file`. A server without credentials only listens on loopback addresses.
+//!
+//! With a certificate and its key, or a self-signed certificate generated at the first startup
+//! and kept in the data directory, the servers only accept TLS connections.
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use bdk_wallet::bitcoin::base64::{Engine, prelude::BASE64_STANDARD};
+use clap::Args;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The self-signed certificate of `--tls-self-signed`, in the data directory.
+const SELF_SIGNED_CERT: &str = "server-cert.pem";
+/// The private key of [`SELF_SIGNED_CERT`], next to it.
+const SELF_SIGNED_KEY: &str = "server-key.pem";
+
+/// What a credential allows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Scope {
+    /// Reading the wallets, syncing them and revealing addresses.
+    Read,
+    /// Also building and broadcasting transactions.
+    Spend,
+}
+
+/// Credentials and TLS of the servers.
+#[derive(Args, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerSecurity {
+    /// Bearer token allowed every request, spending ones included. Can be repeated.
+    #[arg(
+        env = "API_TOKENS",
+        long = "token",
+        value_name = "TOKEN",
+        value_delimiter = ','
+    )]
+    pub tokens: Vec<String>,
+    /// Bearer token only allowed the requests that do not spend. Can be repeated.
+    #[arg(
+        env = "API_READ_TOKENS",
+        long = "read-token",
+        value_name = "TOKEN",
+        value_delimiter = ','
+    )]
+    pub read_tokens: Vec<String>,
+    /// User and password of HTTP basic auth allowed every request. Can be repeated.
+    #[arg(
+        env = "API_BASIC_AUTH",
+        long = "basic-auth",
+        value_name = "USER:PASSWORD",
+        value_parser = parse_user,
+        value_delimiter = ','
+    )]
+    pub users: Vec<(String, String)>,
+    /// User and password of HTTP basic auth only allowed the requests that do not spend. Can be
+    /// repeated.
+    #[arg(
+        env = "API_READ_BASIC_AUTH",
+        long = "read-basic-auth",
+        value_name = "USER:PASSWORD",
+        value_parser = parse_user,
+        value_delimiter = ','
+    )]
+    pub read_users: Vec<(String, String)>,
+    /// File of credentials, one per line: `spend` or `read`, then `token TOKEN` or
+    /// `basic USER:PASSWORD`. Lines starting with `#` are comments.
+    #[arg(env = "API_AUTH_FILE", long = "auth-file", value_name = "PATH")]
+    pub auth_file: Option<PathBuf>,
+    /// PEM certificate chain the servers present, only accepting TLS connections.
+    #[arg(env = "TLS_CERT", long = "tls-cert", requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key of `--tls-cert`.
+    #[arg(env = "TLS_KEY", long = "tls-key", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+    /// Serves over TLS with a certificate for `localhost` generated at the first startup, written
+    /// to `server-cert.pem` in the data directory for the clients to trust, and kept with its key
+    /// in `server-key.pem` for the next startups.
+    #[arg(long = "tls-self-signed", conflicts_with = "tls_cert")]
+    pub tls_self_signed: bool,
+}
+
+fn parse_user(s: &str) -> Result<(String, String), String> {
+    match s.split_once(':') {
+        Some((user, password)) if !user.is_empty() && !password.is_empty() => {
+            Ok((user.to_string(), password.to_string()))
+        }
+        _ => Err("Expected USER:PASSWORD".to_string()),
+    }
+}
+
+/// Compares two secrets in a time that does not depend on where they differ.
+fn same_secret(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The credentials the servers accept.
+#[derive(Debug, Default)]
+pub(crate) struct Auth {
+    tokens: Vec<(String, Scope)>,
+    users: Vec<(String, String, Scope)>,
+}
+
+impl Auth {
+    /// Whether requests are served without credentials, only in the `read` scope.
+    pub(crate) fn is_open(&self) -> bool {
+        self.tokens.is_empty() && self.users.is_empty()
+    }
+
+    /// The scope of a request with the `authorization` header, failing on missing or unknown
+    /// credentials.
+    pub(crate) fn scope(&self, authorization: Option<&str>) -> Result<Scope, Error> {
+        if self.is_open() {
+            return Ok(Scope::Read);
+        }
+        let unauthorized = || Error::Unauthorized("Missing or invalid credentials".to_string());
+        let (scheme, credentials) = authorization
+            .and_then(|header| header.trim().split_once(' '))
+            .ok_or_else(unauthorized)?;
+        let credentials = credentials.trim();
+        let scope = if scheme.eq_ignore_ascii_case("bearer") {
+            self.tokens
+                .iter()
+                .filter(|(token, _)| same_secret(token.as_bytes(), credentials.as_bytes()))
+                .map(|(_, scope)| *scope)
+                .max()
+        } else if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = BASE64_STANDARD
+                .decode(credentials)
+                .map_err(|_| unauthorized())?;
+            let decoded = String::from_utf8(decoded).map_err(|_| unauthorized())?;
+            let (user, password) = decoded.split_once(':').ok_or_else(unauthorized)?;
+            self.users
+                .iter()
+                .filter(|(u, p, _)| {
+                    same_secret(u.as_bytes(), user.as_bytes())
+                        & same_secret(p.as_bytes(), password.as_bytes())
+                })
+                .map(|(_, _, scope)| *scope)
+                .max()
+        } else {
+            None
+        };
+        scope.ok_or_else(unauthorized)
+    }
+}
+
+/// A certificate chain and its private key, PEM encoded.
+pub(crate) struct TlsIdentity {
+    pub(crate) cert: Vec<u8>,
+    pub(crate) key: Vec<u8>,
+}
+
+impl ServerSecurity {
+    /// The credentials of the options and of `--auth-file`.
+    pub(crate) fn auth(&self) -> Result<Auth, Error> {
+        let tokens = self.tokens.iter().map(|token| (token, Scope::Spend));
+        let read_tokens = self.read_tokens.iter().map(|token| (token, Scope::Read));
+        let users = self.users.iter().map(|user| (user, Scope::Spend));
+        let read_users = self.read_users.iter().map(|user| (user, Scope::Read));
+        let mut auth = Auth {
+            tokens: tokens
+                .chain(read_tokens)
+                .map(|(token, scope)| (token.clone(), scope))
+                .collect(),
+            users: users
+                .chain(read_users)
+                .map(|((user, password), scope)| (user.clone(), password.clone(), scope))
+                .collect(),
+        };
+        let Some(path) = &self.auth_file else {
+            return Ok(auth);
+        };
+        let content = fs::read_to_string(path).map_err(|e| {
+            Error::coded(
+                ErrorCode::Io,
+                format!("Cannot read {}: {e}", path.display()),
+            )
+        })?;
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || {
+                Error::coded(
+                    ErrorCode::Config,
+                    format!(
+                        "Invalid line {} of {}, expected `spend|read token TOKEN` or \
+                     `spend|read basic USER:PASSWORD`",
+                        number + 1,
+                        path.display()
+                    ),
+                )
+            };
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let &[scope, kind, credential] = &words[..] else {
+                return Err(invalid());
+            };
+            let scope = match scope {
+                "spend" => Scope::Spend,
+                "read" => Scope::Read,
+                _ => return Err(invalid()),
+            };
+            match kind {
+                "token" => auth.tokens.push((credential.to_string(), scope)),
+                "basic" => {
+                    let (user, password) = parse_user(credential).map_err(|_| invalid())?;
+                    auth.users.push((user, password, scope));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(auth)
+    }
+
+    /// The TLS identity of the servers, `None` to serve plain HTTP.
+    pub(crate) fn tls(&self, datadir: &Path) -> Result<Option<TlsIdentity>, Error> {
+        let read = |path: &Path| {
+            fs::read(path).map_err(|e| {
+                Error::coded(
+                    ErrorCode::Io,
+                    format!("Cannot read {}: {e}", path.display()),
+                )
+            })
+        };
+        if let (Some(cert), Some(key)) = (&self.tls_cert, &self.tls_key) {
+            return Ok(Some(TlsIdentity {
+                cert: read(cert)?,
+                key: read(key)?,
+            }));
+        }
+        if !self.tls_self_signed {
+            return Ok(None);
+        }
+        // Kept, so that the clients trusting the certificate keep trusting the server.
+        let (cert_path, key_path) = (
+            datadir.join(SELF_SIGNED_CERT),
+            datadir.join(SELF_SIGNED_KEY),
+        );
+        if cert_path.exists() && key_path.exists() {
+            log::info!("Self-signed certificate of {}", cert_path.display());
+            return Ok(Some(TlsIdentity {
+                cert: read(&cert_path)?,
+                key: read(&key_path)?,
+            }));
+        }
+        let names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+        let generated = rcgen::generate_simple_self_signed(names).map_err(|e| {
+            Error::coded(
+                ErrorCode::Config,
+                format!("Cannot generate a certificate: {e}"),
+            )
+        })?;
+        let identity = TlsIdentity {
+            cert: generated.cert.pem().into_bytes(),
+            key: generated.key_pair.serialize_pem().into_bytes(),
+        };
+        // The key is readable by its owner only.
+        let write = |path: &Path, content: &[u8], private: bool| {
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            #[cfg(unix)]
+            if private {
+                std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            }
+            #[cfg(not(unix))]
+            let _ = private;
+            options
+                .open(path)
+                .and_then(|mut file| file.write_all(content))
+                .map_err(|e| {
+                    Error::coded(
+                        ErrorCode::Io,
+                        format!("Cannot write {}: {e}", path.display()),
+                    )
+                })
+        };
+        write(&key_path, &identity.key, true)?;
+        write(&cert_path, &identity.cert, false)?;
+        log::info!("Self-signed certificate written to {}", cert_path.display());
+        Ok(Some(identity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth() -> Auth {
+        ServerSecurity {
+            tokens: vec!["spender".to_string()],
+            read_tokens: vec!["reader".to_string()],
+            read_users: vec![("alice".to_string(), "secret".to_string())],
+            ..Default::default()
+        }
+        .auth()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_scope() {
+        let auth = auth();
+        assert_eq!(auth.scope(Some("Bearer spender")).unwrap(), Scope::Spend);
+        assert_eq!(auth.scope(Some("bearer reader")).unwrap(), Scope::Read);
+        let basic = format!("Basic {}", BASE64_STANDARD.encode("alice:secret"));
+        assert_eq!(auth.scope(Some(&basic)).unwrap(), Scope::Read);
+        let wrong = format!("Basic {}", BASE64_STANDARD.encode("alice:guess"));
+        assert!(auth.scope(Some(&wrong)).is_err());
+        assert!(auth.scope(Some("Bearer spend")).is_err());
+        assert!(auth.scope(Some("spender")).is_err());
+        assert!(auth.scope(None).is_err());
+        assert_eq!(Auth::default().scope(None).unwrap(), Scope::Read);
+    }
+
+    #[test]
+    fn test_auth_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials");
+        fs::write(
+            &path,
+            "# the dashboard\nread token reader\n\nspend basic alice:secret\n",
+        )
+        .unwrap();
+        let security = ServerSecurity {
+            auth_file: Some(path.clone()),
+            ..Default::default()
+        };
+        let auth = security.auth().unwrap();
+        assert_eq!(auth.scope(Some("Bearer reader")).unwrap(), Scope::Read);
+        let basic = format!("Basic {}", BASE64_STANDARD.encode("alice:secret"));
+        assert_eq!(auth.scope(Some(&basic)).unwrap(), Scope::Spend);
+
+        fs::write(&path, "spend token\n").unwrap();
+        let error = security.auth().err().unwrap();
+        assert!(error.to_string().contains("Invalid line 1"));
+    }
+
+    #[test]
+    fn test_self_signed_certificate_is_kept() {
+        let datadir = tempfile::tempdir().unwrap();
+        let security = ServerSecurity {
+            tls_self_signed: true,
+            ..Default::default()
+        };
+        let first = security.tls(datadir.path()).unwrap().unwrap();
+        let second = security.tls(datadir.path()).unwrap().unwrap();
+        assert_eq!(first.cert, second.cert);
+        assert_eq!(first.key, second.key);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let key = fs::metadata(datadir.path().join(SELF_SIGNED_KEY)).unwrap();
+            assert_eq!(key.permissions().mode() & 0o777, 0o600);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Better Bitcoin QR
+//!
+//! Splits files into [BBQr] parts, the format Coldcard Q and other signers use to move PSBTs and
+//! transactions through a series of QR codes, and joins scanned parts back together.
+//!
+//! Parts are written hex or base32 encoded. Zlib compressed parts are only read: the deflate
+//! stream they carry must use a 1 KiB window, which the available compressor cannot restrict.
+//!
+//! [BBQr]: https://github.com/coinkite/BBQr/blob/master/BBQr.md
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use clap::ValueEnum;
+
+/// Prefix of every part.
+pub const PREFIX: &str = "B$";
+
+/// Default maximum number of payload characters of each part.
+pub const DEFAULT_MAX_PART_LEN: usize = 1000;
+
+const HEADER_LEN: usize = 8;
+const MAX_PARTS: usize = 36 * 36 - 1;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const BASE36_ALPHABET: &[u8; 36] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// How the payload of the parts is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Encoding {
+    Hex,
+    Base32,
+    /// Zlib compressed, then base32 encoded.
+    #[value(skip)]
+    Zlib,
+}
+
+impl Encoding {
+    fn code(self) -> char {
+        match self {
+            Encoding::Hex => 'H',
+            Encoding::Base32 => '2',
+            Encoding::Zlib => 'Z',
+        }
+    }
+
+    fn from_code(code: char) -> Result<Self, Error> {
+        match code {
+            'H' => Ok(Encoding::Hex),
+            '2' => Ok(Encoding::Base32),
+            'Z' => Ok(Encoding::Zlib),
+            _ => Err(invalid(&format!("unknown encoding '{code}'"))),
+        }
+    }
+
+    /// Number of characters the part payloads, except the last one, are a multiple of.
+    fn unit(self) -> usize {
+        match self {
+            Encoding::Hex => 2,
+            Encoding::Base32 | Encoding::Zlib => 8,
+        }
+    }
+}
+
+/// The kind of file carried by the parts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Psbt,
+    Transaction,
+}
+
+impl FileType {
+    fn code(self) -> char {
+        match self {
+            FileType::Psbt => 'P',
+            FileType::Transaction => 'T',
+        }
+    }
+
+    fn from_code(code: char) -> Result<Self, Error> {
+        match code {
+            'P' => Ok(FileType::Psbt),
+            'T' => Ok(FileType::Transaction),
+            _ => Err(invalid(&format!("unsupported file type '{code}'"))),
+        }
+    }
+}
+
+/// Split a file into parts of at most `max_part_len` payload characters.
+pub fn split(
+    data: &[u8],
+    file_type: FileType,
+    encoding: Encoding,
+    max_part_len: usize,
+) -> Result<Vec<String>, Error> {
+    let payload = match encoding {
+        Encoding::Hex => data.iter().map(|byte| format!("{byte:02X}")).collect(),
+        Encoding::Base32 => base32_encode(data),
+        Encoding::Zlib => {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                "Writing zlib compressed BBQr parts is not supported".to_string(),
+            ));
+        }
+    };
+
+    let unit = encoding.unit();
+    if max_part_len < unit {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("The maximum part length must be at least {unit} characters"),
+        ));
+    }
+    let max_units = max_part_len / unit;
+    let units = payload.len().div_ceil(unit).max(1);
+    let part_len = units.div_ceil(units.div_ceil(max_units)) * unit;
+
+    let chunks: Vec<&[u8]> = if payload.is_empty() {
+        vec![&[]]
+    } else {
+        payload.as_bytes().chunks(part_len).collect()
+    };
+    if chunks.len() > MAX_PARTS {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!(
+                "The data needs {} parts, BBQr allows at most {MAX_PARTS}",
+                chunks.len()
+            ),
+        ));
+    }
+
+    let total = base36(chunks.len());
+    Ok(chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            format!(
+                "{PREFIX}{}{}{total}{}{}",
+                encoding.code(),
+                file_type.code(),
+                base36(index),
+                String::from_utf8_lossy(chunk)
+            )
+        })
+        .collect())
+}
+
+/// Join scanned parts, given in any order, back into the file they carry.
+pub fn join(parts: &[String]) -> Result<(FileType, Vec<u8>), Error> {
+    let mut header: Option<(Encoding, FileType, usize)> = None;
+    let mut payloads: Vec<Option<String>> = Vec::new();
+
+    for part in parts {
+        let part = part.trim();
+        if !part.starts_with(PREFIX) || part.len() < HEADER_LEN || !part.is_ascii() {
+            return Err(invalid(&format!("expected a part starting with {PREFIX}")));
+        }
+        let mut codes = part[PREFIX.len()..].chars();
+        let encoding = Encoding::from_code(codes.next().unwrap_or_default())?;
+        let file_type = FileType::from_code(codes.next().unwrap_or_default())?;
+        let total = from_base36(&part[4..6])?;
+        let index = from_base36(&part[6..8])?;
+
+        match header {
+            None => {
+                if total == 0 {
+                    return Err(invalid("the number of parts is zero"));
+                }
+                header = Some((encoding, file_type, total));
+                payloads = vec![None; total];
+            }
+            Some(expected) if expected != (encoding, file_type, total) => {
+                return Err(invalid("parts belong to different files"));
+            }
+            Some(_) => {}
+        }
+        if index >= total {
+            return Err(invalid(&format!("part {index} is out of range")));
+        }
+        payloads[index] = Some(part[HEADER_LEN..].to_string());
+    }
+
+    let (encoding, file_type, _) = header.ok_or_else(|| invalid("no part given"))?;
+    let missing: Vec<String> = payloads
+        .iter()
+        .enumerate()
+        .filter(|(_, payload)| payload.is_none())
+        .map(|(index, _)| index.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(invalid(&format!("missing parts {}", missing.join(", "))));
+    }
+
+    let payload: String = payloads.into_iter().flatten().collect();
+    let data = match encoding {
+        Encoding::Hex => {
+            use bdk_wallet::bitcoin::hex::FromHex;
+            Vec::<u8>::from_hex(&payload).map_err(|e| invalid(&e.to_string()))?
+        }
+        Encoding::Base32 => base32_decode(&payload)?,
+        Encoding::Zlib => miniz_oxide::inflate::decompress_to_vec(&base32_decode(&payload)?)
+            .map_err(|e| invalid(&format!("cannot decompress: {e}")))?,
+    };
+    Ok((file_type, data))
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::coded(ErrorCode::InvalidBbqr, format!("Invalid BBQr: {reason}"))
+}
+
+fn base36(value: usize) -> String {
+    [value / 36, value % 36]
+        .iter()
+        .map(|digit| BASE36_ALPHABET[*digit] as char)
+        .collect()
+}
+
+fn from_base36(s: &str) -> Result<usize, Error> {
+    s.bytes().try_fold(0, |value, byte| {
+        BASE36_ALPHABET
+            .iter()
+            .position(|digit| *digit == byte)
+            .map(|digit| value * 36 + digit)
+            .ok_or_else(|| invalid(&format!("invalid base36 number '{s}'")))
+    })
+}
+
+/// RFC 4648 base32, without padding.
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[(buffer >> bits) as usize & 0x1f] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[(buffer << (5 - bits)) as usize & 0x1f] as char);
+    }
+    out
+}
+
+fn base32_decode(s: &str) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for letter in s.bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|candidate| *candidate == letter)
+            .ok_or_else(|| invalid("invalid base32 character"))?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base32_vectors() {
+        // RFC 4648 test vectors, without padding.
+        for (data, encoded) in [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ] {
+            assert_eq!(base32_encode(data.as_bytes()), encoded);
+            assert_eq!(base32_decode(encoded).unwrap(), data.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_split_and_join_round_trip() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        for encoding in [Encoding::Hex, Encoding::Base32] {
+            let mut parts = split(&data, FileType::Psbt, encoding, 300).unwrap();
+            assert!(parts.len() > 1);
+            assert!(parts[0].starts_with(&format!("B${}P", encoding.code())));
+            let unit = encoding.unit();
+            assert!(
+                parts[..parts.len() - 1]
+                    .iter()
+                    .all(|part| (part.len() - HEADER_LEN).is_multiple_of(unit)
+                        && part.len() - HEADER_LEN <= 300)
+            );
+
+            parts.reverse();
+            assert_eq!(join(&parts).unwrap(), (FileType::Psbt, data.clone()));
+
+            parts.remove(0);
+            let err = join(&parts).unwrap_err().to_string();
+            assert!(err.contains("missing parts"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_join_zlib_part() {
+        // "Hello" compressed as a raw deflate stream.
+        let compressed = miniz_oxide::deflate::compress_to_vec(b"Hello", 6);
+        let part = format!("B$ZT0100{}", base32_encode(&compressed));
+        assert_eq!(
+            join(&[part]).unwrap(),
+            (FileType::Transaction, b"Hello".to_vec())
+        );
+    }
+}
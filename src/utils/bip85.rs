@@ -0,0 +1,206 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! BIP-85 deterministic entropy
+//!
+//! Derives child entropy from a master key, as described in [BIP-85], so that child seeds, keys
+//! and passwords can all be recovered from the single backup of the master key. The entropy is
+//! the HMAC-SHA512 of the private key found at `m/83696968'/<application>'/...`.
+//!
+//! [BIP-85]: https://github.com/bitcoin/bips/blob/master/bip-0085.mediawiki
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use bdk_wallet::bip39::{Language, Mnemonic};
+use bdk_wallet::bitcoin::PrivateKey;
+use bdk_wallet::bitcoin::bip32::{ChildNumber, DerivationPath, Xpriv};
+use bdk_wallet::bitcoin::hashes::{Hash, HashEngine, Hmac, HmacEngine, sha512};
+use bdk_wallet::bitcoin::secp256k1::{Secp256k1, SecretKey};
+use clap::ValueEnum;
+
+/// Purpose of the BIP-85 derivation paths, "DRNG" on a phone keypad.
+const PURPOSE: u32 = 83696968;
+
+/// HMAC key turning a derived private key into entropy.
+const HMAC_KEY: &[u8] = b"bip-entropy-from-k";
+
+/// The application the child entropy is derived for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Application {
+    /// A BIP39 mnemonic.
+    Bip39,
+    /// A compressed private key in wallet import format.
+    Wif,
+    /// Raw hex encoded entropy.
+    Hex,
+}
+
+/// The derivation path of an application, `parameters` being the hardened indexes between the
+/// application number and the child index.
+pub fn path(
+    application: Application,
+    parameters: &[u32],
+    index: u32,
+) -> Result<DerivationPath, Error> {
+    let application = match application {
+        Application::Bip39 => 39,
+        Application::Wif => 2,
+        Application::Hex => 128169,
+    };
+    [PURPOSE, application]
+        .iter()
+        .chain(parameters)
+        .chain(&[index])
+        .map(|&index| ChildNumber::from_hardened_idx(index))
+        .collect::<Result<Vec<_>, _>>()
+        .map(DerivationPath::from)
+        .map_err(|e| {
+            Error::coded(
+                ErrorCode::InvalidArgument,
+                format!("Invalid BIP-85 index: {e}"),
+            )
+        })
+}
+
+/// The 64 bytes of entropy derived from a master key at a BIP-85 path.
+pub fn entropy(master: &Xpriv, path: &DerivationPath) -> Result<[u8; 64], Error> {
+    if master.depth != 0 {
+        return Err(Error::coded(
+            ErrorCode::InvalidKey,
+            format!(
+                "BIP-85 derives from a master key, not from a key at depth {}",
+                master.depth
+            ),
+        ));
+    }
+    let derived = master.derive_priv(&Secp256k1::new(), path)?;
+    let mut engine = HmacEngine::<sha512::Hash>::new(HMAC_KEY);
+    engine.input(&derived.private_key.secret_bytes());
+    Ok(Hmac::from_engine(engine).to_byte_array())
+}
+
+/// A child BIP39 mnemonic of `words` words.
+pub fn mnemonic(
+    master: &Xpriv,
+    language: Language,
+    words: usize,
+    index: u32,
+) -> Result<(DerivationPath, Mnemonic), Error> {
+    if ![12, 15, 18, 21, 24].contains(&words) {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("Invalid word count {words}, use 12, 15, 18, 21 or 24"),
+        ));
+    }
+    let language_code = match language {
+        Language::English => 0,
+        Language::Japanese => 1,
+        Language::Korean => 2,
+        Language::Spanish => 3,
+        Language::SimplifiedChinese => 4,
+        Language::TraditionalChinese => 5,
+        Language::French => 6,
+        Language::Italian => 7,
+        Language::Czech => 8,
+        Language::Portuguese => 9,
+    };
+    let path = path(Application::Bip39, &[language_code, words as u32], index)?;
+    let entropy = entropy(master, &path)?;
+    let mnemonic = Mnemonic::from_entropy_in(language, &entropy[..words * 4 / 3])?;
+    Ok((path, mnemonic))
+}
+
+/// A child private key for a wallet import format.
+pub fn wif(master: &Xpriv, index: u32) -> Result<(DerivationPath, PrivateKey), Error> {
+    let path = path(Application::Wif, &[], index)?;
+    let entropy = entropy(master, &path)?;
+    let secret_key = SecretKey::from_slice(&entropy[..32]).map_err(|e| {
+        Error::coded(
+            ErrorCode::InvalidKey,
+            format!("Invalid derived private key: {e}"),
+        )
+    })?;
+    Ok((path, PrivateKey::new(secret_key, master.network)))
+}
+
+/// `length` bytes of child entropy, from 16 to 64.
+pub fn hex(master: &Xpriv, length: usize, index: u32) -> Result<(DerivationPath, Vec<u8>), Error> {
+    if !(16..=64).contains(&length) {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("Invalid entropy length {length}, use 16 to 64 bytes"),
+        ));
+    }
+    let path = path(Application::Hex, &[length as u32], index)?;
+    let entropy = entropy(master, &path)?;
+    Ok((path, entropy[..length].to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk_wallet::bitcoin::hex::DisplayHex;
+    use std::str::FromStr;
+
+    // The master key of the BIP-85 test vectors.
+    const MASTER: &str = "xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb";
+
+    #[test]
+    fn test_bip85_vectors() {
+        let master = Xpriv::from_str(MASTER).unwrap();
+
+        let path = DerivationPath::from_str("m/83696968'/0'/0'").unwrap();
+        assert_eq!(
+            entropy(&master, &path).unwrap().to_lower_hex_string(),
+            "efecfbccffea313214232d29e71563d941229afb4338c21f9517c41aaa0d16f0\
+             0b83d2a09ef747e7a64e8e2bd5a14869e693da66ce94ac2da570ab7ee48618f7"
+        );
+
+        let (path, mnemonic) = super::mnemonic(&master, Language::English, 12, 0).unwrap();
+        assert_eq!(path.to_string(), "83696968'/39'/0'/12'/0'");
+        assert_eq!(
+            mnemonic.to_string(),
+            "girl mad pet galaxy egg matter matrix prison refuse sense ordinary nose"
+        );
+        let (_, mnemonic) = super::mnemonic(&master, Language::English, 24, 0).unwrap();
+        assert_eq!(
+            mnemonic.to_string(),
+            "puppy ocean match cereal symbol another shed magic wrap hammer bulb intact gadget \
+             divorce twin tonight reason outdoor destroy simple truth cigar social volcano"
+        );
+
+        let (path, key) = wif(&master, 0).unwrap();
+        assert_eq!(path.to_string(), "83696968'/2'/0'");
+        assert_eq!(
+            key.to_wif(),
+            "Kzyv4uF39d4Jrw2W7UryTHwZr1zQVNk4dAFyqE6BuMrMh1Za7uhp"
+        );
+
+        let (_, entropy) = hex(&master, 64, 0).unwrap();
+        assert_eq!(
+            entropy.to_lower_hex_string(),
+            "492db4698cf3b73a5a24998aa3e9d7fa96275d85724a91e71aa2d645442f8785\
+             55d078fd1f1f67e368976f04137b1f7a0d19232136ca50c44614af72b5582a5c"
+        );
+    }
+
+    #[test]
+    fn test_bip85_rejects_invalid_parameters() {
+        let master = Xpriv::from_str(MASTER).unwrap();
+        assert!(super::mnemonic(&master, Language::English, 13, 0).is_err());
+        assert!(hex(&master, 15, 0).is_err());
+        assert!(wif(&master, 1 << 31).is_err());
+
+        let child = master
+            .derive_priv(
+                &Secp256k1::new(),
+                &DerivationPath::from_str("m/0'").unwrap(),
+            )
+            .unwrap();
+        assert!(wif(&child, 0).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Local Bitcoin Core node
+//!
+//! Reads the `bitcoin.conf` of a Bitcoin Core data directory the way `bitcoind` does, so the rpc
+//! client finds the chain, rpc address and credentials of the node without setting them by hand.
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::utils::signet::parse_signet_challenge;
+use bdk_bitcoind_rpc::bitcoincore_rpc::Auth;
+use bdk_wallet::bitcoin::{Network, ScriptBuf};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Options of the default section that `bitcoind` applies to mainnet only, the other chains
+/// taking them from their own section.
+const NETWORK_ONLY_OPTIONS: [&str; 1] = ["rpcport"];
+
+/// The rpc endpoint of a local Bitcoin Core node.
+#[derive(Debug, Clone)]
+pub struct NodeRpc {
+    pub url: String,
+    pub auth: Auth,
+    /// The `rpcwallet` the calls of `bitcoin-cli` go to.
+    pub wallet: Option<String>,
+}
+
+/// Parse the `--node` argument, a Bitcoin Core data directory or `bitcoin.conf` file, the
+/// default data directory of the platform when empty.
+pub(crate) fn parse_node(s: &str) -> Result<PathBuf, Error> {
+    if !s.is_empty() {
+        return Ok(PathBuf::from(s));
+    }
+    let datadir = if cfg!(any(target_os = "macos", windows)) {
+        dirs::data_dir().map(|dir| dir.join("Bitcoin"))
+    } else {
+        dirs::home_dir().map(|dir| dir.join(".bitcoin"))
+    };
+    datadir.ok_or_else(|| {
+        Error::coded(
+            ErrorCode::Config,
+            "Cannot find the Bitcoin Core data directory, pass it to --node".into(),
+        )
+    })
+}
+
+/// The settings of a `bitcoin.conf`, by section.
+#[derive(Debug, Default)]
+struct BitcoinConf {
+    datadir: PathBuf,
+    default: HashMap<String, String>,
+    sections: HashMap<String, HashMap<String, String>>,
+}
+
+impl BitcoinConf {
+    /// Loads the `bitcoin.conf` of `node`, a data directory or the file itself. A data directory
+    /// without `bitcoin.conf` runs on the defaults.
+    fn load(node: &Path) -> Result<Self, Error> {
+        let (datadir, conf_path) = if node.is_file() {
+            let datadir = node.parent().unwrap_or(Path::new(".")).to_path_buf();
+            (datadir, node.to_path_buf())
+        } else if node.is_dir() {
+            (node.to_path_buf(), node.join("bitcoin.conf"))
+        } else {
+            return Err(Error::coded(
+                ErrorCode::Config,
+                format!("Bitcoin Core data directory {} not found", node.display()),
+            ));
+        };
+
+        let mut conf = BitcoinConf {
+            datadir,
+            ..Default::default()
+        };
+        if !conf_path.exists() {
+            return Ok(conf);
+        }
+        let content = std::fs::read_to_string(&conf_path).map_err(|e| {
+            Error::coded(
+                ErrorCode::Config,
+                format!("Cannot read {}: {e}", conf_path.display()),
+            )
+        })?;
+
+        let mut section = None;
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = Some(name.trim().to_string());
+                continue;
+            }
+            let (key, value) = line.split_once('=').unwrap_or((line, "1"));
+            // `main.rpcport=8332` sets an option of a section from anywhere in the file.
+            let (section, key) = match key.trim().split_once('.') {
+                Some((section, key)) => (Some(section.to_string()), key),
+                None => (section.clone(), key.trim()),
+            };
+            let options = match section {
+                Some(section) => conf.sections.entry(section).or_default(),
+                None => &mut conf.default,
+            };
+            // The first value of an option wins, as with `bitcoind`.
+            options
+                .entry(key.to_string())
+                .or_insert_with(|| value.trim().to_string());
+        }
+        Ok(conf)
+    }
+
+    /// The chain selected in the default section, if any.
+    fn network(&self) -> Result<Option<Network>, Error> {
+        let enabled = |option| self.default.get(option).is_some_and(|value| value != "0");
+        let chain = match self.default.get("chain") {
+            Some(chain) => chain.as_str(),
+            None if enabled("regtest") => "regtest",
+            None if enabled("signet") => "signet",
+            None if enabled("testnet4") => "testnet4",
+            None if enabled("testnet") => "test",
+            None => return Ok(None),
+        };
+        match chain {
+            "main" => Ok(Some(Network::Bitcoin)),
+            "test" => Ok(Some(Network::Testnet)),
+            "testnet4" => Ok(Some(Network::Testnet4)),
+            "signet" => Ok(Some(Network::Signet)),
+            "regtest" => Ok(Some(Network::Regtest)),
+            chain => Err(Error::coded(
+                ErrorCode::Config,
+                format!("Unknown chain {chain} in bitcoin.conf"),
+            )),
+        }
+    }
+
+    /// The value of an option for `network`, its section overriding the default section.
+    fn get(&self, network: Network, option: &str) -> Option<&str> {
+        let section = self.sections.get(section_name(network));
+        section
+            .and_then(|options| options.get(option))
+            .or_else(|| {
+                (network == Network::Bitcoin || !NETWORK_ONLY_OPTIONS.contains(&option))
+                    .then(|| self.default.get(option))
+                    .flatten()
+            })
+            .map(String::as_str)
+    }
+}
+
+/// The `bitcoin.conf` section of the network.
+fn section_name(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "main",
+        Network::Testnet => "test",
+        Network::Testnet4 => "testnet4",
+        Network::Signet => "signet",
+        Network::Regtest => "regtest",
+    }
+}
+
+/// The chain the Bitcoin Core `node` is configured for, `None` when `bitcoin.conf` does not set
+/// one and the chain is left to the command line of `bitcoind`.
+pub fn node_network(node: &Path) -> Result<Option<Network>, Error> {
+    BitcoinConf::load(node)?.network()
+}
+
+/// The challenge of the custom signet the Bitcoin Core `node` is configured for, from the
+/// `signetchallenge` of its `bitcoin.conf`.
+pub fn node_signet_challenge(node: &Path) -> Result<Option<ScriptBuf>, Error> {
+    BitcoinConf::load(node)?
+        .get(Network::Signet, "signetchallenge")
+        .map(parse_signet_challenge)
+        .transpose()
+}
+
+/// The rpc url and credentials of the Bitcoin Core `node` running on `network`, with the
+/// `rpcuser`/`rpcpassword` of `bitcoin.conf` or else its cookie file.
+pub fn node_rpc(node: &Path, network: Network) -> Result<NodeRpc, Error> {
+    let conf = BitcoinConf::load(node)?;
+    if let Some(chain) = conf.network()?
+        && chain != network
+    {
+        return Err(Error::coded(
+            ErrorCode::NetworkMismatch,
+            format!(
+                "Bitcoin Core at {} runs on {chain}, not on the {network} network of the wallet",
+                node.display()
+            ),
+        ));
+    }
+
+    let host = conf.get(network, "rpcconnect").unwrap_or("127.0.0.1");
+    let port = match conf.get(network, "rpcport") {
+        Some(port) => port.parse::<u16>().map_err(|e| {
+            Error::coded(
+                ErrorCode::Config,
+                format!("Invalid rpcport {port} in bitcoin.conf: {e}"),
+            )
+        })?,
+        None => default_rpc_port(network),
+    };
+
+    let auth = match (
+        conf.get(network, "rpcuser"),
+        conf.get(network, "rpcpassword"),
+    ) {
+        (Some(user), Some(password)) => Auth::UserPass(user.to_string(), password.to_string()),
+        _ => {
+            let chain_dir = match network {
+                Network::Bitcoin => conf.datadir.clone(),
+                Network::Testnet => conf.datadir.join("testnet3"),
+                network => conf.datadir.join(section_name(network)),
+            };
+            let cookie = conf
+                .get(network, "rpccookiefile")
+                .map_or(chain_dir.join(".cookie"), |file| chain_dir.join(file));
+            if !cookie.exists() {
+                return Err(Error::coded(
+                    ErrorCode::Config,
+                    format!(
+                        "Cookie file {} not found, is Bitcoin Core running?",
+                        cookie.display()
+                    ),
+                ));
+            }
+            Auth::CookieFile(cookie)
+        }
+    };
+
+    Ok(NodeRpc {
+        url: format!("{host}:{port}"),
+        auth,
+        wallet: conf.get(network, "rpcwallet").map(String::from),
+    })
+}
+
+/// The ZMQ endpoints where the Bitcoin Core `node` running on `network` publishes its raw blocks
+/// and raw transactions, when `bitcoin.conf` enables them.
+pub fn node_zmq(node: &Path, network: Network) -> Result<(Option<String>, Option<String>), Error> {
+    let conf = BitcoinConf::load(node)?;
+    Ok((
+        conf.get(network, "zmqpubrawblock").map(String::from),
+        conf.get(network, "zmqpubrawtx").map(String::from),
+    ))
+}
+
+/// The rpc port Bitcoin Core listens on by default for `network`.
+fn default_rpc_port(network: Network) -> u16 {
+    match network {
+        Network::Bitcoin => 8332,
+        Network::Testnet => 18332,
+        Network::Testnet4 => 48332,
+        Network::Signet => 38332,
+        Network::Regtest => 18443,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_rpc_reads_bitcoin_conf() {
+        let datadir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            datadir.path().join("bitcoin.conf"),
+            "# local node\nregtest=1\nrpcport=9999\nrpcuser=alice\n\n[regtest]\nrpcport=18500\nrpcpassword=secret\nrpcwallet=watchonly\nzmqpubrawtx=tcp://127.0.0.1:28333\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            node_network(datadir.path()).unwrap(),
+            Some(Network::Regtest)
+        );
+        let rpc = node_rpc(datadir.path(), Network::Regtest).unwrap();
+        assert_eq!(rpc.url, "127.0.0.1:18500");
+        assert_eq!(rpc.wallet.as_deref(), Some("watchonly"));
+        assert_eq!(
+            node_zmq(datadir.path(), Network::Regtest).unwrap(),
+            (None, Some("tcp://127.0.0.1:28333".to_string()))
+        );
+        assert_eq!(rpc.auth, Auth::UserPass("alice".into(), "secret".into()));
+        assert!(node_rpc(datadir.path(), Network::Signet).is_err());
+
+        std::fs::write(datadir.path().join("bitcoin.conf"), "signet=1\n").unwrap();
+        assert!(node_rpc(datadir.path(), Network::Signet).is_err());
+        std::fs::create_dir(datadir.path().join("signet")).unwrap();
+        std::fs::write(datadir.path().join("signet/.cookie"), "__cookie__:x").unwrap();
+        let rpc = node_rpc(datadir.path(), Network::Signet).unwrap();
+        assert_eq!(rpc.url, "127.0.0.1:38332");
+        assert_eq!(
+            rpc.auth,
+            Auth::CookieFile(datadir.path().join("signet/.cookie"))
+        );
+    }
+}
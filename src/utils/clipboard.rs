@@ -0,0 +1,93 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! System clipboard
+//!
+//! With the `clipboard` feature, `--copy` puts the PSBT, txid or address of a command output on
+//! the system clipboard, and the commands taking a PSBT read it from the clipboard with `--paste`
+//! rather than from the command line or a `--psbt-file`. On Linux the copied value outlives
+//! `bdk-cli` when a clipboard manager takes it over.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use serde::Serialize;
+use serde_json::Value;
+
+/// The fields of an output copied by `--copy`, the first one found being copied.
+const COPIED_FIELDS: [&str; 3] = ["psbt", "txid", "address"];
+
+/// Set by `--copy`.
+static COPY: AtomicBool = AtomicBool::new(false);
+
+/// Copies the PSBT, txid or address of the outputs written from now on.
+pub fn enable_copy() {
+    COPY.store(true, Ordering::Relaxed);
+}
+
+fn clipboard_error(e: arboard::Error) -> Error {
+    Error::coded(ErrorCode::Io, format!("Cannot use the clipboard: {e}"))
+}
+
+/// The field of `output` copied by `--copy`, and its value.
+fn copied_field(output: &Value) -> Option<(&'static str, &str)> {
+    COPIED_FIELDS
+        .into_iter()
+        .find_map(|field| Some((field, output.get(field)?.as_str()?)))
+}
+
+/// Puts the PSBT, txid or address of `output` on the clipboard with `--copy`.
+pub(crate) fn copy_output<T: Serialize + ?Sized>(output: &T) -> Result<(), Error> {
+    if !COPY.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let output = serde_json::to_value(output)
+        .map_err(|e| Error::Generic(format!("JSON serialization failed: {e}")))?;
+    let Some((field, value)) = copied_field(&output) else {
+        log::warn!("the output has no PSBT, txid or address to copy");
+        return Ok(());
+    };
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(value))
+        .map_err(clipboard_error)?;
+    log::info!("Copied the {field} to the clipboard.");
+    Ok(())
+}
+
+/// The text on the clipboard, such as a PSBT copied from another wallet.
+pub(crate) fn paste() -> Result<String, Error> {
+    let text = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(clipboard_error)?;
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            "The clipboard is empty".to_string(),
+        ));
+    }
+    Ok(text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_copied_field() {
+        let psbt = json!({"psbt": "cHNidP8B", "is_finalized": false, "txid": "00ff"});
+        assert_eq!(copied_field(&psbt), Some(("psbt", "cHNidP8B")));
+        let broadcast = json!({"txid": "00ff"});
+        assert_eq!(copied_field(&broadcast), Some(("txid", "00ff")));
+        let address = json!({"address": "bcrt1q", "index": 0});
+        assert_eq!(copied_field(&address), Some(("address", "bcrt1q")));
+        assert_eq!(copied_field(&json!({"satoshi": 1000})), None);
+        assert_eq!(copied_field(&json!({"psbt": 2})), None);
+    }
+}
@@ -1,39 +1,80 @@
-use crate::{commands::WalletOpts, config::WalletConfig, error::BDKCliError as Error};
-#[cfg(feature = "cbf")]
-use bdk_kyoto::{Info, Receiver, UnboundedReceiver, Warning};
+use crate::{
+    commands::{PassphraseOpts, WalletOpts},
+    config::WalletConfig,
+    error::{BDKCliError as Error, ErrorCode},
+};
 #[cfg(feature = "bip322")]
 use bdk_message_signer::SignatureFormat;
 #[cfg(feature = "silent-payments")]
 use bdk_sp::encoding::SilentPaymentCode;
-use bdk_wallet::bitcoin::{Address, Network, OutPoint, ScriptBuf};
+use bdk_wallet::bitcoin::base64::{Engine, prelude::BASE64_STANDARD};
+use bdk_wallet::bitcoin::hex::FromHex;
+use bdk_wallet::bitcoin::{Address, Network, OutPoint, Psbt, ScriptBuf};
 #[cfg(any(
     feature = "electrum",
     feature = "esplora",
     feature = "cbf",
     feature = "rpc"
 ))]
-use bdk_wallet::{WalletEvent, bitcoin::Psbt};
-
-use crate::commands::OfflineWalletSubCommand;
-use std::{
-    path::{Path, PathBuf},
-    str::FromStr,
+use bdk_wallet::{WalletEvent, bitcoin::Transaction};
+#[cfg(feature = "cbf")]
+use {
+    crate::utils::progress::Progress,
+    bdk_kyoto::{Info, Receiver, TrustedPeer, UnboundedReceiver, Warning},
 };
 
-/// Determine if PSBT has final script sigs or witnesses for all unsigned tx inputs.
 #[cfg(any(
     feature = "electrum",
     feature = "esplora",
     feature = "cbf",
     feature = "rpc"
 ))]
+use crate::commands::OnlineWalletSubCommand;
+#[cfg(feature = "nwc")]
+use crate::commands::WalletNwcSubCommand;
+use crate::commands::{
+    CliSubCommand, KeySubCommand, OfflineWalletSubCommand, WalletContactsSubCommand,
+    WalletSnapshotSubCommand, WalletSubCommand,
+};
+use crate::handlers::contacts::parse_contact_name;
+use crate::utils::amount::parse_amount;
+use crate::utils::network::MUTINYNET;
+use crate::utils::psbt::{self, PsbtVersion, deserialize_psbt, serialize_psbt};
+use crate::utils::signet::MUTINYNET_CHALLENGE;
+use crate::utils::{bbqr, keystore, ur};
+use std::{
+    cell::Cell,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use zeroize::Zeroizing;
+
+/// Set by `--read-only`, for the wallet stores to open without write access.
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--yes`, for the dangerous operations to run without asking.
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Set while this thread runs the command of a server request authenticated to spend, the
+    /// credential standing for the confirmation.
+    static CONFIRMED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Determine if PSBT has final script sigs or witnesses for all unsigned tx inputs.
 pub(crate) fn is_final(psbt: &Psbt) -> Result<(), Error> {
     let unsigned_tx_inputs = psbt.unsigned_tx.input.len();
     let psbt_inputs = psbt.inputs.len();
     if unsigned_tx_inputs != psbt_inputs {
-        return Err(Error::Generic(format!(
-            "Malformed PSBT, {unsigned_tx_inputs} unsigned tx inputs and {psbt_inputs} psbt inputs."
-        )));
+        return Err(Error::coded(
+            ErrorCode::InvalidPsbt,
+            format!(
+                "Malformed PSBT, {unsigned_tx_inputs} unsigned tx inputs and {psbt_inputs} psbt inputs."
+            ),
+        ));
     }
     let sig_count = psbt.inputs.iter().fold(0, |count, input| {
         if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
@@ -43,14 +84,16 @@ pub(crate) fn is_final(psbt: &Psbt) -> Result<(), Error> {
         }
     });
     if unsigned_tx_inputs > sig_count {
-        return Err(Error::Generic(
+        return Err(Error::coded(
+            ErrorCode::InvalidPsbt,
             "The PSBT is not finalized, inputs are are not fully signed.".to_string(),
         ));
     }
     Ok(())
 }
 
-/// Parse the recipient (Address,Amount) argument from cli input.
+/// Parse the recipient (Address,Amount) argument from cli input, the amount as in
+/// [`parse_amount`].
 pub(crate) fn parse_recipient(s: &str) -> Result<(ScriptBuf, u64), String> {
     let parts: Vec<_> = s.split(':').collect();
     if parts.len() != 2 {
@@ -59,17 +102,47 @@ pub(crate) fn parse_recipient(s: &str) -> Result<(ScriptBuf, u64), String> {
     let addr = Address::from_str(parts[0])
         .map_err(|e| e.to_string())?
         .assume_checked();
-    let val = u64::from_str(parts[1]).map_err(|e| e.to_string())?;
+    let val = parse_amount(parts[1])?;
 
     Ok((addr.script_pubkey(), val))
 }
 
+/// Who a `--to` recipient pays: an address, or a contact of `wallet contacts` by its name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Payee {
+    Script(ScriptBuf),
+    Contact(String),
+}
+
+/// Parse a recipient like [`parse_recipient`], or a contact recipient such as `@alice:10000`.
+pub(crate) fn parse_payee_recipient(s: &str) -> Result<(Payee, u64), String> {
+    if !s.starts_with('@') {
+        return parse_recipient(s).map(|(script, amount)| (Payee::Script(script), amount));
+    }
+    let (name, amount) = s.split_once(':').ok_or("Invalid format")?;
+    Ok((parse_payee(name)?, parse_amount(amount)?))
+}
+
+/// Parse the payee of a recipient, an address or a contact such as `@alice`.
+pub(crate) fn parse_payee(s: &str) -> Result<Payee, String> {
+    if s.starts_with('@') {
+        return Ok(Payee::Contact(parse_contact_name(s)?));
+    }
+    let address = Address::from_str(s)
+        .map_err(|e| e.to_string())?
+        .assume_checked();
+    Ok(Payee::Script(address.script_pubkey()))
+}
+
 #[cfg(any(feature = "electrum", feature = "esplora", feature = "rpc"))]
 /// Parse the proxy (Socket:Port) argument from the cli input.
 pub(crate) fn parse_proxy_auth(s: &str) -> Result<(String, String), Error> {
     let parts: Vec<_> = s.split(':').collect();
     if parts.len() != 2 {
-        return Err(Error::Generic("Invalid format".to_string()));
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            "Invalid format".to_string(),
+        ));
     }
 
     let user = parts[0].to_string();
@@ -78,40 +151,291 @@ pub(crate) fn parse_proxy_auth(s: &str) -> Result<(String, String), Error> {
     Ok((user, passwd))
 }
 
+#[cfg(any(feature = "electrum", feature = "esplora"))]
+/// Parse the SOCKS5 proxy (`[socks5://]host:port`) argument from the cli input into `host:port`.
+pub(crate) fn parse_proxy(s: &str) -> Result<String, Error> {
+    let address = match s.split_once("://") {
+        None => s,
+        Some(("socks5" | "socks5h", address)) => address,
+        Some((scheme, _)) => {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                format!("Unsupported proxy scheme {scheme}, only SOCKS5 proxies are supported"),
+            ));
+        }
+    };
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+            Ok(address.to_string())
+        }
+        _ => Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("Invalid proxy address {address}, expected host:port"),
+        )),
+    }
+}
+
+#[cfg(feature = "esplora")]
+/// Parse an HTTP header (`Name: value`) argument from the cli input.
+pub(crate) fn parse_http_header(s: &str) -> Result<(String, String), Error> {
+    match s.split_once(':') {
+        Some((name, value))
+            if !name.is_empty()
+                && name
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)) =>
+        {
+            Ok((name.to_string(), value.trim().to_string()))
+        }
+        _ => Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("Invalid HTTP header {s}, expected Name: value"),
+        )),
+    }
+}
+
+#[cfg(feature = "cbf")]
+/// Parse a compact block filter peer (`ip`, `ip:port` or `host:port`) argument from the cli
+/// input, an IP address without a port connecting to the default port of the network.
+pub(crate) fn parse_cbf_peer(s: &str) -> Result<TrustedPeer, Error> {
+    if let Ok(socket_addr) = s.parse::<std::net::SocketAddr>() {
+        return Ok(TrustedPeer::from_socket_addr(socket_addr));
+    }
+    if let Ok(ip) = s.parse::<std::net::IpAddr>() {
+        return Ok(TrustedPeer::from_ip(ip));
+    }
+    match s.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && !host.contains(':') => {
+            let port = port.parse().map_err(|e| {
+                Error::coded(
+                    ErrorCode::InvalidArgument,
+                    format!("Invalid port of peer {s}: {e}"),
+                )
+            })?;
+            Ok(TrustedPeer::from_hostname(host, port))
+        }
+        _ => Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("Invalid peer {s}, expected an IP address or host:port"),
+        )),
+    }
+}
+
 /// Parse a outpoint (Txid:Vout) argument from cli input.
 pub(crate) fn parse_outpoint(s: &str) -> Result<OutPoint, Error> {
     Ok(OutPoint::from_str(s)?)
 }
 
+/// Parse a base64, hex, single-part `ur:crypto-psbt` or BBQr encoded PSBT of either version,
+/// returning it as version 0. BBQr parts are separated by whitespace.
+pub(crate) fn parse_psbt_with_version(s: &str) -> Result<(Psbt, PsbtVersion), Error> {
+    let s = s.trim();
+    let bytes = if s.starts_with(bbqr::PREFIX) {
+        bbqr_file(s, bbqr::FileType::Psbt)?
+    } else if s.to_lowercase().starts_with("ur:") {
+        ur::decode_psbt(&[s.to_string()])?
+    } else {
+        match Vec::<u8>::from_hex(s) {
+            Ok(bytes) => bytes,
+            Err(_) => BASE64_STANDARD.decode(s).map_err(|e| {
+                Error::coded(
+                    ErrorCode::InvalidPsbt,
+                    format!("PSBT is neither hex nor base64: {e}"),
+                )
+            })?,
+        }
+    };
+    deserialize_psbt(&bytes)
+}
+
+/// Read a PSBT file, either raw binary or holding an encoded PSBT as text.
+pub(crate) fn read_psbt_file(path: &Path) -> Result<(Psbt, PsbtVersion), Error> {
+    let bytes = std::fs::read(path).map_err(|e| {
+        Error::coded(
+            ErrorCode::Io,
+            format!("Failed to read PSBT file {path:?}: {e}"),
+        )
+    })?;
+    if bytes.starts_with(psbt::MAGIC) {
+        deserialize_psbt(&bytes)
+    } else {
+        parse_psbt_with_version(&String::from_utf8_lossy(&bytes))
+    }
+}
+
+/// Write a PSBT as a raw binary file.
+pub(crate) fn write_psbt_file(path: &Path, psbt: &Psbt, version: PsbtVersion) -> Result<(), Error> {
+    std::fs::write(path, serialize_psbt(psbt, version)).map_err(|e| {
+        Error::coded(
+            ErrorCode::Io,
+            format!("Failed to write PSBT file {path:?}: {e}"),
+        )
+    })
+}
+
+/// Have an external signer command sign a PSBT. The command runs through the shell of the
+/// system, receives the base64 encoded PSBT on stdin and must print the signed PSBT on stdout.
+pub(crate) fn run_external_signer(
+    command: &str,
+    psbt: &Psbt,
+    version: PsbtVersion,
+) -> Result<Psbt, Error> {
+    let mut child = shell_command(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            Error::coded(
+                ErrorCode::ExternalSigner,
+                format!("Failed to run external signer '{command}': {e}"),
+            )
+        })?;
+    // Written from its own thread, as a signer printing before it has read the whole PSBT would
+    // otherwise block on a full stdout pipe while we block on its stdin.
+    let input = BASE64_STANDARD.encode(serialize_psbt(psbt, version));
+    let writer = child
+        .stdin
+        .take()
+        .map(|mut stdin| std::thread::spawn(move || stdin.write_all(input.as_bytes())));
+    let output = child.wait_with_output().map_err(|e| {
+        Error::coded(
+            ErrorCode::ExternalSigner,
+            format!("External signer '{command}' failed: {e}"),
+        )
+    })?;
+    if !output.status.success() {
+        return Err(Error::coded(
+            ErrorCode::ExternalSigner,
+            format!("External signer '{command}' failed with {}", output.status),
+        ));
+    }
+    if let Some(writer) = writer {
+        writer
+            .join()
+            .expect("the PSBT writer does not panic")
+            .map_err(|e| {
+                Error::coded(
+                    ErrorCode::ExternalSigner,
+                    format!("Failed to send the PSBT to the external signer: {e}"),
+                )
+            })?;
+    }
+
+    let (signed, _) = parse_psbt_with_version(&String::from_utf8_lossy(&output.stdout))?;
+    if signed.unsigned_tx.compute_txid() != psbt.unsigned_tx.compute_txid() {
+        return Err(Error::coded(
+            ErrorCode::ExternalSigner,
+            "The external signer returned a PSBT for another transaction".to_string(),
+        ));
+    }
+    Ok(signed)
+}
+
+/// `command` run by the shell of the system, `sh` or `cmd` on Windows.
+fn shell_command(command: &str) -> Command {
+    #[cfg(windows)]
+    let (shell, flag) = ("cmd", "/C");
+    #[cfg(not(windows))]
+    let (shell, flag) = ("sh", "-c");
+    let mut cmd = Command::new(shell);
+    cmd.arg(flag).arg(command);
+    cmd
+}
+
+/// Parse the PSBT given either on the command line or as a file, or else on the clipboard when
+/// `paste`.
+pub(crate) fn parse_psbt_input(
+    psbt: Option<&str>,
+    psbt_file: Option<&Path>,
+    paste: bool,
+) -> Result<(Psbt, PsbtVersion), Error> {
+    match (psbt, psbt_file, paste) {
+        (Some(psbt), None, false) => parse_psbt_with_version(psbt),
+        (None, Some(path), false) => read_psbt_file(path),
+        #[cfg(feature = "clipboard")]
+        (None, None, true) => parse_psbt_with_version(&crate::utils::clipboard::paste()?),
+        _ => Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            "Provide one of a PSBT, a `--psbt-file` or `--paste`".to_string(),
+        )),
+    }
+}
+
+/// Parse a hex or BBQr encoded transaction. BBQr parts are separated by whitespace.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+pub(crate) fn parse_raw_tx(s: &str) -> Result<Transaction, Error> {
+    let s = s.trim();
+    let bytes = if s.starts_with(bbqr::PREFIX) {
+        bbqr_file(s, bbqr::FileType::Transaction)?
+    } else {
+        Vec::<u8>::from_hex(s)?
+    };
+    Ok(bdk_wallet::bitcoin::consensus::deserialize(&bytes)?)
+}
+
+fn bbqr_file(s: &str, expected: bbqr::FileType) -> Result<Vec<u8>, Error> {
+    let parts: Vec<String> = s.split_whitespace().map(str::to_string).collect();
+    let (file_type, data) = bbqr::join(&parts)?;
+    if file_type != expected {
+        return Err(Error::coded(
+            ErrorCode::InvalidBbqr,
+            format!("Expected a BBQr {expected:?}, found a {file_type:?}"),
+        ));
+    }
+    Ok(data)
+}
+
 /// Parse an address string into `Address<NetworkChecked>`.
 pub(crate) fn parse_address(address_str: &str) -> Result<Address, Error> {
     let unchecked_address = Address::from_str(address_str)?;
     Ok(unchecked_address.assume_checked())
 }
 
+/// Environment variable of the data directory, below `--datadir`.
+pub const DATADIR_ENV: &str = "BDK_CLI_DATADIR";
+
 /// Prepare bdk-cli home directory
 ///
 /// This function is called to check if [`crate::CliOpts`] datadir is set.
-/// If not the default home directory is created at `~/.bdk-bitcoin`.
+/// If not the default home directory of [`default_home_dir`] is created.
 #[allow(dead_code)]
 pub(crate) fn prepare_home_dir(home_path: Option<PathBuf>) -> Result<PathBuf, Error> {
     let dir = match home_path {
         Some(dir) => dir,
-        None => {
-            let mut dir =
-                dirs::home_dir().ok_or_else(|| Error::Generic("Home dir not found".to_string()))?;
-            dir.push(".bdk-bitcoin");
-            dir
-        }
+        None => default_home_dir()?.0,
     };
 
     if !dir.exists() {
-        std::fs::create_dir_all(&dir).map_err(|e| Error::Generic(e.to_string()))?;
+        std::fs::create_dir_all(&dir).map_err(|e| Error::coded(ErrorCode::Io, e.to_string()))?;
     }
 
     Ok(dir)
 }
 
+/// The data directory when `--datadir` is not given, and where it comes from: `BDK_CLI_DATADIR`,
+/// the `~/.bdk-bitcoin` of earlier versions when it exists, or `bdk-cli` in the data directory of
+/// the system, the XDG one on Linux.
+pub(crate) fn default_home_dir() -> Result<(PathBuf, &'static str), Error> {
+    if let Some(dir) = std::env::var_os(DATADIR_ENV).filter(|dir| !dir.is_empty()) {
+        return Ok((PathBuf::from(dir), DATADIR_ENV));
+    }
+    let legacy = dirs::home_dir().map(|home| home.join(".bdk-bitcoin"));
+    match (legacy, dirs::data_dir()) {
+        (Some(legacy), _) if legacy.exists() => Ok((legacy, "~/.bdk-bitcoin")),
+        (_, Some(data_dir)) => Ok((data_dir.join("bdk-cli"), "system data directory")),
+        (Some(legacy), None) => Ok((legacy, "~/.bdk-bitcoin")),
+        (None, None) => Err(Error::coded(
+            ErrorCode::Io,
+            "Home dir not found".to_string(),
+        )),
+    }
+}
+
 /// Prepare wallet database directory.
 #[allow(dead_code)]
 pub(crate) fn prepare_wallet_db_dir(
@@ -122,7 +446,7 @@ pub(crate) fn prepare_wallet_db_dir(
     dir.push(wallet_name);
 
     if !dir.exists() {
-        std::fs::create_dir(&dir).map_err(|e| Error::Generic(e.to_string()))?;
+        std::fs::create_dir(&dir).map_err(|e| Error::coded(ErrorCode::Io, e.to_string()))?;
     }
 
     Ok(dir)
@@ -133,16 +457,119 @@ pub fn is_mnemonic(s: &str) -> bool {
     (12..=24).contains(&word_count) && s.chars().all(|c| c.is_alphanumeric() || c.is_whitespace())
 }
 
+/// The BIP39 passphrase given on the command line, or asked for on the terminal. Asking for a
+/// new passphrase asks twice, to catch typos.
+pub(crate) fn read_passphrase(
+    opts: &PassphraseOpts,
+    confirm: bool,
+) -> Result<Option<String>, Error> {
+    if !opts.ask_passphrase {
+        // `PASSWORD` is the variable of the `--password` of earlier versions.
+        return Ok(opts
+            .passphrase
+            .clone()
+            .or_else(|| std::env::var("PASSWORD").ok()));
+    }
+    let passphrase = read_hidden_line("BIP39 passphrase: ")?;
+    if confirm && read_hidden_line("Repeat the passphrase: ")? != passphrase {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            "The passphrases do not match".to_string(),
+        ));
+    }
+    Ok(Some(passphrase))
+}
+
+/// The keystore password from `BDK_CLI_KEY_PASSWORD`, or asked for on the terminal. Asking for
+/// a new password asks twice, to catch typos.
+pub(crate) fn read_key_password(confirm: bool) -> Result<Zeroizing<String>, Error> {
+    read_password(keystore::PASSWORD_ENV, "keystore", confirm)
+}
+
+/// The password of encrypted backup bundles from `BDK_CLI_BACKUP_PASSWORD`, or asked for on the
+/// terminal. Asking for a new password asks twice, to catch typos.
+pub(crate) fn read_backup_password(confirm: bool) -> Result<Zeroizing<String>, Error> {
+    read_password(crate::handlers::backup::PASSWORD_ENV, "backup", confirm)
+}
+
+/// The wallet database password from `BDK_CLI_DB_PASSWORD`, or asked for on the terminal once
+/// per run, as the wallet and payjoin databases share it.
+#[cfg(feature = "sqlcipher")]
+pub(crate) fn read_db_password(confirm: bool) -> Result<&'static str, Error> {
+    static PASSWORD: std::sync::OnceLock<Zeroizing<String>> = std::sync::OnceLock::new();
+    if let Some(password) = PASSWORD.get() {
+        return Ok(password.as_str());
+    }
+    let password = read_password(crate::persister::DB_PASSWORD_ENV, "database", confirm)?;
+    Ok(PASSWORD.get_or_init(|| password).as_str())
+}
+
+fn read_password(env: &str, name: &str, confirm: bool) -> Result<Zeroizing<String>, Error> {
+    if let Ok(password) = std::env::var(env) {
+        return Ok(Zeroizing::new(password));
+    }
+    let mut prompt = name.to_string();
+    prompt[..1].make_ascii_uppercase();
+    let password = Zeroizing::new(read_hidden_line(&format!("{prompt} password: "))?);
+    if password.is_empty() {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("The {name} password is empty"),
+        ));
+    }
+    if confirm && *Zeroizing::new(read_hidden_line("Repeat the password: ")?) != *password {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            "The passwords do not match".to_string(),
+        ));
+    }
+    Ok(password)
+}
+
+/// Read a line from stdin. When stdin is a terminal, the prompt is shown on stderr and the
+/// typed characters are not echoed.
+pub(crate) fn read_hidden_line(prompt: &str) -> Result<String, Error> {
+    let stdin = std::io::stdin();
+    let is_terminal = stdin.is_terminal();
+    if is_terminal {
+        eprint!("{prompt}");
+        set_echo(false);
+    }
+    let mut line = String::new();
+    let read = stdin.read_line(&mut line);
+    if is_terminal {
+        set_echo(true);
+        eprintln!();
+    }
+    read.map_err(|e| Error::coded(ErrorCode::Io, format!("Cannot read the passphrase: {e}")))?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Turn the terminal echo on or off through `stty`, where it is available.
+fn set_echo(on: bool) {
+    if cfg!(unix) {
+        let _ = Command::new("stty")
+            .arg(if on { "echo" } else { "-echo" })
+            .status();
+    }
+}
+
 #[cfg(feature = "cbf")]
 pub async fn trace_logger(
     mut info_subcriber: Receiver<Info>,
     mut warning_subscriber: UnboundedReceiver<Warning>,
+    progress: Progress,
 ) {
     loop {
         tokio::select! {
             info = info_subcriber.recv() => {
-                if let Some(info) = info {
-                    tracing::info!("{info}")
+                match info {
+                    Some(Info::Progress(filters)) => progress.filters(
+                        f64::from(filters.percentage_complete()),
+                        filters.chain_height(),
+                    ),
+                    Some(info) => tracing::info!("{info}"),
+                    None => {}
                 }
             }
             warn = warning_subscriber.recv() => {
@@ -158,20 +585,19 @@ pub fn load_wallet_config(
     home_dir: &Path,
     wallet_name: &str,
 ) -> Result<(WalletOpts, Network), Error> {
-    let config = WalletConfig::load(home_dir)?.ok_or(Error::Generic(format!(
-        "No config found for wallet {wallet_name}",
-    )))?;
+    let config = WalletConfig::load(home_dir)?.ok_or(Error::coded(
+        ErrorCode::NoWalletConfig,
+        format!("No config found for wallet {wallet_name}",),
+    ))?;
 
     let wallet_opts = config.get_wallet_opts(wallet_name)?;
     let wallet_config = config
         .wallets
         .get(wallet_name)
-        .ok_or(Error::Generic(format!(
-            "Wallet '{wallet_name}' not found in config"
-        )))?;
+        .ok_or_else(|| Error::WalletNotFound(wallet_name.to_string()))?;
 
     let network = Network::from_str(&wallet_config.network)
-        .map_err(|_| Error::Generic("Invalid network in config".to_string()))?;
+        .map_err(|_| Error::coded(ErrorCode::Config, "Invalid network in config".to_string()))?;
 
     Ok((wallet_opts, network))
 }
@@ -180,19 +606,17 @@ pub fn load_wallet_config(
 pub(crate) fn parse_sp_code_value_pairs(s: &str) -> Result<(SilentPaymentCode, u64), Error> {
     let parts: Vec<&str> = s.split(':').collect();
     if parts.len() != 2 {
-        return Err(Error::Generic(format!(
-            "Invalid format '{}'. Expected 'key:value'",
-            s
-        )));
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("Invalid format '{}'. Expected 'key:value'", s),
+        ));
     }
 
     let value_0 = parts[0].trim();
     let key = SilentPaymentCode::try_from(value_0)?;
 
-    let value = parts[1]
-        .trim()
-        .parse::<u64>()
-        .map_err(|_| Error::Generic(format!("Invalid number '{}' for key '{}'", parts[1], key)))?;
+    let value = parse_amount(parts[1])
+        .map_err(|e| Error::coded(ErrorCode::InvalidArgument, format!("{e} for key '{key}'")))?;
 
     Ok((key, value))
 }
@@ -205,7 +629,8 @@ pub(crate) fn parse_signature_format(format_str: &str) -> Result<SignatureFormat
         "simple" => Ok(SignatureFormat::Simple),
         "full" => Ok(SignatureFormat::Full),
         "fullproofoffunds" => Ok(SignatureFormat::FullProofOfFunds),
-        _ => Err(Error::Generic(
+        _ => Err(Error::coded(
+            ErrorCode::InvalidArgument,
             "Invalid signature format. Use 'legacy', 'simple', 'full', or 'fullproofoffunds'"
                 .to_string(),
         )),
@@ -216,21 +641,33 @@ pub fn command_requires_db(command: &OfflineWalletSubCommand) -> bool {
     match command {
         OfflineWalletSubCommand::Balance(_)
         | OfflineWalletSubCommand::Unspent(_)
+        | OfflineWalletSubCommand::UtxoReport(_)
         | OfflineWalletSubCommand::Transactions(_)
+        | OfflineWalletSubCommand::Gap(_)
+        | OfflineWalletSubCommand::Timelocks(_)
         | OfflineWalletSubCommand::BumpFee(_)
         | OfflineWalletSubCommand::NewAddress(_)
         | OfflineWalletSubCommand::UnusedAddress(_)
+        | OfflineWalletSubCommand::Vanity(_)
         | OfflineWalletSubCommand::CreateTx(_)
         | OfflineWalletSubCommand::LockUtxo(_)
         | OfflineWalletSubCommand::UnlockUtxo(_)
         | OfflineWalletSubCommand::LockedUtxos(_) => true,
 
+        // Contacts are told apart from the addresses of the wallet.
+        OfflineWalletSubCommand::Contacts { .. } => true,
+
+        OfflineWalletSubCommand::Label(_) | OfflineWalletSubCommand::Labels(_) => false,
+
         OfflineWalletSubCommand::Policies(_)
         | OfflineWalletSubCommand::PublicDescriptor(_)
         | OfflineWalletSubCommand::Sign(_)
         | OfflineWalletSubCommand::ExtractPsbt(_)
         | OfflineWalletSubCommand::FinalizePsbt(_)
-        | OfflineWalletSubCommand::CombinePsbt(_) => false,
+        | OfflineWalletSubCommand::CombinePsbt(_)
+        | OfflineWalletSubCommand::Hwi { .. } => false,
+        #[cfg(feature = "nwc")]
+        OfflineWalletSubCommand::Nwc { .. } => false,
 
         #[cfg(feature = "bip322")]
         OfflineWalletSubCommand::SignMessage(_) => true,
@@ -244,22 +681,525 @@ pub fn command_requires_db(command: &OfflineWalletSubCommand) -> bool {
     }
 }
 
+/// Whether network access is disabled, by the `--offline` flag or by `offline = true` in the
+/// `config.toml` of `datadir`.
+pub fn is_offline(offline_flag: bool, datadir: &Path) -> Result<bool, Error> {
+    Ok(offline_flag || WalletConfig::load(datadir)?.is_some_and(|config| config.offline))
+}
+
+/// Fails when `--network` is given for a wallet configured on another network, since wallet
+/// commands run on the network of the wallet, or when its `mutinynet` preset is given for a
+/// wallet on another signet.
+pub fn check_network_flag(
+    network: Network,
+    preset: Option<&str>,
+    datadir: &Path,
+    command: &CliSubCommand,
+) -> Result<(), Error> {
+    let wallet = match command {
+        // Creates a regtest wallet, refusing the other networks itself.
+        #[cfg(feature = "rpc")]
+        CliSubCommand::Wallet {
+            subcommand: WalletSubCommand::GenerateTest(_),
+            ..
+        } => return Ok(()),
+        CliSubCommand::Wallet { wallet, subcommand }
+            if !matches!(
+                subcommand,
+                WalletSubCommand::Config(_) | WalletSubCommand::Restore(_)
+            ) =>
+        {
+            wallet
+        }
+        #[cfg(any(feature = "electrum", feature = "esplora"))]
+        CliSubCommand::Backend { wallet, .. } => wallet,
+        #[cfg(feature = "rpc")]
+        CliSubCommand::Node { wallet, .. } => wallet,
+        #[cfg(feature = "repl")]
+        CliSubCommand::Repl { wallet } | CliSubCommand::Run { wallet, .. } => wallet,
+        _ => return Ok(()),
+    };
+    let Some(config) = WalletConfig::load(datadir)? else {
+        return Ok(());
+    };
+    let Some(wallet_config) = config.wallets.get(wallet) else {
+        return Ok(());
+    };
+    if let Ok(wallet_network) = Network::from_str(&wallet_config.network)
+        && wallet_network != network
+    {
+        return Err(Error::coded(
+            ErrorCode::NetworkMismatch,
+            format!(
+                "Network mismatch: wallet '{wallet}' is configured for {wallet_network}, but --network is {network}"
+            ),
+        ));
+    }
+    // Mutinynet is the signet of its own challenge.
+    if preset == Some(MUTINYNET)
+        && wallet_config.signet_challenge.as_deref() != Some(MUTINYNET_CHALLENGE)
+    {
+        return Err(Error::coded(
+            ErrorCode::NetworkMismatch,
+            format!(
+                "Network mismatch: wallet '{wallet}' is not on Mutinynet, but --network is mutinynet"
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// The URL template of the block explorer of `--links`: `--explorer-url`, the one saved in the
+/// `config.toml` of `datadir` for the network of `command`, or mempool.space. Regtest has no
+/// default explorer.
+pub fn explorer_url(
+    url_flag: Option<&str>,
+    datadir: &Path,
+    command: &CliSubCommand,
+    network: Network,
+) -> Result<String, Error> {
+    if let Some(url) = url_flag {
+        return check_explorer_template(url);
+    }
+    let config = match command_uses_datadir(command) {
+        true => WalletConfig::load(datadir)?,
+        false => None,
+    };
+    // Wallet commands run on the network of the wallet, whatever the `--network`.
+    let network = match (command, &config) {
+        (CliSubCommand::Wallet { wallet, .. }, Some(config)) => config
+            .wallets
+            .get(wallet)
+            .and_then(|wallet| Network::from_str(&wallet.network).ok())
+            .unwrap_or(network),
+        _ => network,
+    };
+    if let Some(url) =
+        config.and_then(|config| config.explorer_urls.get(&network.to_string()).cloned())
+    {
+        return check_explorer_template(&url);
+    }
+    match network {
+        Network::Bitcoin => Ok("https://mempool.space/{kind}/{id}".to_string()),
+        Network::Testnet => Ok("https://mempool.space/testnet/{kind}/{id}".to_string()),
+        Network::Testnet4 => Ok("https://mempool.space/testnet4/{kind}/{id}".to_string()),
+        Network::Signet => Ok("https://mempool.space/signet/{kind}/{id}".to_string()),
+        _ => Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("No block explorer for {network}, set one with --explorer-url to use --links"),
+        )),
+    }
+}
+
+/// `template` when it has the `{id}` placeholder of the txid or address to link.
+fn check_explorer_template(template: &str) -> Result<String, Error> {
+    if !template.contains("{id}") {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("Block explorer template '{template}' has no {{id}} placeholder"),
+        ));
+    }
+    Ok(template.to_string())
+}
+
+/// Opens the wallet databases and labels of this run without write access.
+pub fn set_read_only() {
+    READ_ONLY.store(true, Ordering::Relaxed);
+}
+
+/// Whether `--read-only` was given.
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+/// Runs the dangerous operations of this run without asking for a confirmation.
+pub fn set_assume_yes() {
+    ASSUME_YES.store(true, Ordering::Relaxed);
+}
+
+/// Runs the dangerous operations of the commands of this thread without asking from now on, or
+/// asks again when `false`.
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
+pub(crate) fn assume_yes_on_thread(yes: bool) {
+    CONFIRMED.set(yes);
+}
+
+/// Shows `summary` on stderr and asks whether to go on with `action`, such as "the broadcast".
+/// Without `--yes`, fails when the answer is not yes or when stdin is not a terminal to ask on.
+pub(crate) fn confirm(action: &str, summary: &str) -> Result<(), Error> {
+    if ASSUME_YES.load(Ordering::Relaxed) || CONFIRMED.get() {
+        return Ok(());
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(Error::ConfirmationRequired(action.to_string()));
+    }
+    let answer = read_answer(&format!("{summary}\nProceed? [y/N] "))?;
+    match answer.unwrap_or_default().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(Error::Cancelled(action.to_string())),
+    }
+}
+
+/// Shows `prompt` on stderr and reads the answer from stdin, trimmed, or `None` at the end of
+/// the input.
+pub(crate) fn read_answer(prompt: &str) -> Result<Option<String>, Error> {
+    eprint!("{prompt}");
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    let read = std::io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| Error::coded(ErrorCode::Io, format!("Cannot read the answer: {e}")))?;
+    Ok((read > 0).then(|| answer.trim().to_string()))
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM on unix, as sent by service managers and `kill` to stop
+/// the long-running commands.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf",
+    feature = "server"
+))]
+pub(crate) async fn shutdown_signal() {
+    #[cfg(unix)]
+    if let Ok(mut terminate) =
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+    {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+        return;
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// The error of a command refused in read-only mode.
+pub fn read_only_error() -> Error {
+    Error::ReadOnly(
+        "This command changes the wallet database or labels, which --read-only forbids".to_string(),
+    )
+}
+
+/// Whether `command` reads the data directory, for its config, wallets or caches. The key
+/// tooling and the policy compiler run without one, on machines with no wallet at all, only the
+/// keystore commands touching the config.
+pub fn command_uses_datadir(command: &CliSubCommand) -> bool {
+    match command {
+        CliSubCommand::Key { subcommand } => matches!(
+            subcommand,
+            KeySubCommand::Encrypt(_) | KeySubCommand::Decrypt(_)
+        ),
+        #[cfg(feature = "compiler")]
+        CliSubCommand::Compile(_) => false,
+        _ => true,
+    }
+}
+
+/// Whether `command` writes to the database, the labels, the snapshots or the config of a
+/// wallet.
+pub fn command_mutates_wallet(command: &CliSubCommand) -> bool {
+    match command {
+        CliSubCommand::Wallet { subcommand, .. } => wallet_command_mutates_wallet(subcommand),
+
+        // The REPL and its scripts refuse their mutating commands themselves.
+        #[cfg(feature = "repl")]
+        CliSubCommand::Repl { .. } | CliSubCommand::Run { .. } => false,
+        // So does the server, for each request.
+        #[cfg(feature = "server")]
+        CliSubCommand::Serve(_) => false,
+        // The daemon persists the syncs of its wallet.
+        #[cfg(all(
+            feature = "server",
+            any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf"
+            )
+        ))]
+        CliSubCommand::Daemon(_) => true,
+        #[cfg(any(feature = "electrum", feature = "esplora"))]
+        CliSubCommand::Backend { .. } => false,
+        // The node commands sync the wallet after.
+        #[cfg(feature = "rpc")]
+        CliSubCommand::Node { .. } => true,
+        #[cfg(feature = "compiler")]
+        CliSubCommand::Compile(_) => false,
+        #[cfg(feature = "silent-payments")]
+        CliSubCommand::SilentPaymentCode(_) => false,
+        #[cfg(feature = "dns_payment")]
+        CliSubCommand::ResolveDnsRecipient(_) => false,
+        CliSubCommand::Key { .. }
+        | CliSubCommand::Psbt { .. }
+        | CliSubCommand::Multisig { .. }
+        | CliSubCommand::Descriptor(_)
+        | CliSubCommand::Wallets(_)
+        | CliSubCommand::Paths(_)
+        | CliSubCommand::Completions { .. } => false,
+        CliSubCommand::Init(_) => true,
+    }
+}
+
+/// Whether the wallet `command` writes to the database, the labels, the snapshots or the config
+/// of the wallet.
+pub fn wallet_command_mutates_wallet(command: &WalletSubCommand) -> bool {
+    let command = match command {
+        WalletSubCommand::Config(_)
+        | WalletSubCommand::Restore(_)
+        | WalletSubCommand::Rollback(_) => return true,
+        #[cfg(feature = "rpc")]
+        WalletSubCommand::GenerateTest(_) => return true,
+        WalletSubCommand::Snapshot { subcommand } => {
+            return !matches!(subcommand, WalletSnapshotSubCommand::List(_));
+        }
+        #[cfg(feature = "sqlite")]
+        WalletSubCommand::Db { .. } | WalletSubCommand::Migrate(_) => return true,
+        WalletSubCommand::Backup(_) => return false,
+        #[cfg(any(feature = "sqlite", feature = "redb"))]
+        WalletSubCommand::Debug { .. } => return false,
+        #[cfg(any(
+            feature = "electrum",
+            feature = "esplora",
+            feature = "cbf",
+            feature = "rpc"
+        ))]
+        WalletSubCommand::OnlineWalletSubCommand(command) => {
+            return !matches!(
+                command,
+                OnlineWalletSubCommand::EstimateFee(_) | OnlineWalletSubCommand::PayjoinHistory(_)
+            );
+        }
+        WalletSubCommand::OfflineWalletSubCommand(command) => command,
+    };
+    match command {
+        OfflineWalletSubCommand::Vanity(vanity) => vanity.reserve,
+        OfflineWalletSubCommand::Contacts { subcommand } => {
+            !matches!(subcommand, WalletContactsSubCommand::List(_))
+        }
+        #[cfg(feature = "nwc")]
+        OfflineWalletSubCommand::Nwc { subcommand } => {
+            !matches!(subcommand, WalletNwcSubCommand::List(_))
+        }
+        // Transactions reveal their change address.
+        OfflineWalletSubCommand::NewAddress(_)
+        | OfflineWalletSubCommand::UnusedAddress(_)
+        | OfflineWalletSubCommand::CreateTx(_)
+        | OfflineWalletSubCommand::BumpFee(_)
+        | OfflineWalletSubCommand::Label(_)
+        | OfflineWalletSubCommand::LockUtxo(_)
+        | OfflineWalletSubCommand::UnlockUtxo(_) => true,
+        #[cfg(feature = "silent-payments")]
+        OfflineWalletSubCommand::CreateSpTx(_) => true,
+        #[cfg(feature = "dns_payment")]
+        OfflineWalletSubCommand::CreateDnsTx(_) => true,
+
+        #[cfg(feature = "bip322")]
+        OfflineWalletSubCommand::SignMessage(_) | OfflineWalletSubCommand::VerifyMessage(_) => {
+            false
+        }
+        OfflineWalletSubCommand::Unspent(_)
+        | OfflineWalletSubCommand::UtxoReport(_)
+        | OfflineWalletSubCommand::Transactions(_)
+        | OfflineWalletSubCommand::Balance(_)
+        | OfflineWalletSubCommand::Gap(_)
+        | OfflineWalletSubCommand::Labels(_)
+        | OfflineWalletSubCommand::Policies(_)
+        | OfflineWalletSubCommand::Timelocks(_)
+        | OfflineWalletSubCommand::PublicDescriptor(_)
+        | OfflineWalletSubCommand::Sign(_)
+        | OfflineWalletSubCommand::ExtractPsbt(_)
+        | OfflineWalletSubCommand::FinalizePsbt(_)
+        | OfflineWalletSubCommand::CombinePsbt(_)
+        | OfflineWalletSubCommand::LockedUtxos(_)
+        | OfflineWalletSubCommand::Hwi { .. } => false,
+    }
+}
+
+/// Whether `command` reaches out to the network: a blockchain backend, mempool.space or a DNS
+/// resolver.
+pub fn command_requires_network(command: &CliSubCommand) -> bool {
+    match command {
+        CliSubCommand::Wallet { subcommand, .. } => wallet_command_requires_network(subcommand),
+        #[cfg(any(feature = "electrum", feature = "esplora"))]
+        CliSubCommand::Backend { .. } => true,
+        #[cfg(feature = "rpc")]
+        CliSubCommand::Node { .. } => true,
+        #[cfg(feature = "dns_payment")]
+        CliSubCommand::ResolveDnsRecipient(_) => true,
+        // The daemon syncs the wallet, its served commands being checked for each request.
+        #[cfg(all(
+            feature = "server",
+            any(
+                feature = "electrum",
+                feature = "esplora",
+                feature = "rpc",
+                feature = "cbf"
+            )
+        ))]
+        CliSubCommand::Daemon(_) => true,
+
+        // The REPL and its scripts refuse their online commands themselves.
+        #[cfg(feature = "repl")]
+        CliSubCommand::Repl { .. } | CliSubCommand::Run { .. } => false,
+        // So does the server, for each request.
+        #[cfg(feature = "server")]
+        CliSubCommand::Serve(_) => false,
+        #[cfg(feature = "compiler")]
+        CliSubCommand::Compile(_) => false,
+        #[cfg(feature = "silent-payments")]
+        CliSubCommand::SilentPaymentCode(_) => false,
+        CliSubCommand::Key { .. }
+        | CliSubCommand::Psbt { .. }
+        | CliSubCommand::Multisig { .. }
+        | CliSubCommand::Descriptor(_)
+        | CliSubCommand::Wallets(_)
+        | CliSubCommand::Paths(_)
+        | CliSubCommand::Completions { .. } => false,
+        // The first scan is skipped offline.
+        CliSubCommand::Init(_) => false,
+    }
+}
+
+/// Whether the wallet `command` reaches out to the network.
+pub fn wallet_command_requires_network(command: &WalletSubCommand) -> bool {
+    let command = match command {
+        WalletSubCommand::Config(_)
+        | WalletSubCommand::Backup(_)
+        | WalletSubCommand::Restore(_)
+        | WalletSubCommand::Rollback(_)
+        | WalletSubCommand::Snapshot { .. } => return false,
+        #[cfg(feature = "rpc")]
+        WalletSubCommand::GenerateTest(_) => return true,
+        #[cfg(any(feature = "sqlite", feature = "redb"))]
+        WalletSubCommand::Debug { .. } => return false,
+        #[cfg(feature = "sqlite")]
+        WalletSubCommand::Db { .. } => return false,
+        #[cfg(feature = "sqlite")]
+        WalletSubCommand::Migrate(_) => return false,
+        #[cfg(any(
+            feature = "electrum",
+            feature = "esplora",
+            feature = "cbf",
+            feature = "rpc"
+        ))]
+        WalletSubCommand::OnlineWalletSubCommand(_) => return true,
+        WalletSubCommand::OfflineWalletSubCommand(command) => command,
+    };
+    match command {
+        // `--target-blocks` fetches the fee rate from mempool.space.
+        #[cfg(any(
+            feature = "electrum",
+            feature = "esplora",
+            feature = "cbf",
+            feature = "rpc"
+        ))]
+        OfflineWalletSubCommand::CreateTx(create_tx) => create_tx.target_blocks.is_some(),
+        #[cfg(feature = "dns_payment")]
+        OfflineWalletSubCommand::CreateDnsTx(_) => true,
+
+        #[cfg(not(any(
+            feature = "electrum",
+            feature = "esplora",
+            feature = "cbf",
+            feature = "rpc"
+        )))]
+        OfflineWalletSubCommand::CreateTx(_) => false,
+        #[cfg(feature = "bip322")]
+        OfflineWalletSubCommand::SignMessage(_) | OfflineWalletSubCommand::VerifyMessage(_) => {
+            false
+        }
+        #[cfg(feature = "silent-payments")]
+        OfflineWalletSubCommand::CreateSpTx(_) => false,
+        OfflineWalletSubCommand::NewAddress(_)
+        | OfflineWalletSubCommand::UnusedAddress(_)
+        | OfflineWalletSubCommand::Vanity(_)
+        | OfflineWalletSubCommand::Unspent(_)
+        | OfflineWalletSubCommand::UtxoReport(_)
+        | OfflineWalletSubCommand::Transactions(_)
+        | OfflineWalletSubCommand::Balance(_)
+        | OfflineWalletSubCommand::Gap(_)
+        | OfflineWalletSubCommand::Label(_)
+        | OfflineWalletSubCommand::Labels(_)
+        | OfflineWalletSubCommand::Contacts { .. }
+        | OfflineWalletSubCommand::BumpFee(_)
+        | OfflineWalletSubCommand::Policies(_)
+        | OfflineWalletSubCommand::Timelocks(_)
+        | OfflineWalletSubCommand::PublicDescriptor(_)
+        | OfflineWalletSubCommand::Sign(_)
+        | OfflineWalletSubCommand::ExtractPsbt(_)
+        | OfflineWalletSubCommand::FinalizePsbt(_)
+        | OfflineWalletSubCommand::CombinePsbt(_)
+        | OfflineWalletSubCommand::LockUtxo(_)
+        | OfflineWalletSubCommand::UnlockUtxo(_)
+        | OfflineWalletSubCommand::LockedUtxos(_)
+        | OfflineWalletSubCommand::Hwi { .. } => false,
+        // The connections are answered by `daemon --nwc`.
+        #[cfg(feature = "nwc")]
+        OfflineWalletSubCommand::Nwc { .. } => false,
+    }
+}
+
+/// How the wallet events of a sync or scan are reported.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum EventFormat {
+    /// A human-readable summary on stderr.
+    #[default]
+    Summary,
+    /// One JSON event per line on stdout, as `watch --ndjson` prints them.
+    Ndjson,
+}
+
+/// Prints `json` on stdout as one line, flushed right away for the programs reading it.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub(crate) fn print_ndjson(json: &serde_json::Value) {
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{json}");
+    let _ = stdout.flush();
+}
+
 /// Print a human-readable summary of the wallet events produced by a sync or scan.
 ///
-/// Emitted to stderr so it does not pollute the JSON result on stdout.
+/// Emitted to stderr so it does not pollute the JSON result on stdout, unless `format` asks for
+/// lines of JSON on stdout.
 #[cfg(any(
     feature = "electrum",
     feature = "esplora",
     feature = "rpc",
     feature = "cbf"
 ))]
-pub fn print_wallet_events(events: &[WalletEvent]) {
+pub fn print_wallet_events(events: &[WalletEvent], format: EventFormat) {
     for event in events {
+        if format == EventFormat::Ndjson {
+            if let Some(json) = wallet_event_json(event) {
+                print_ndjson(&json);
+            }
+            continue;
+        }
+        #[cfg(feature = "grpc")]
+        if let Some(json) = wallet_event_json(event)
+            && crate::utils::events::forward(crate::utils::events::Event::Wallet(json)).is_none()
+        {
+            continue;
+        }
         match event {
             WalletEvent::ChainTipChanged { old_tip, new_tip } => {
-                eprintln!(
+                log::info!(
                     "Chain tip advanced from height {} to {}",
-                    old_tip.height, new_tip.height
+                    old_tip.height,
+                    new_tip.height
                 );
             }
             WalletEvent::TxConfirmed {
@@ -268,11 +1208,12 @@ pub fn print_wallet_events(events: &[WalletEvent]) {
                 old_block_time,
                 ..
             } => match old_block_time {
-                Some(old) => eprintln!(
+                Some(old) => log::info!(
                     "Transaction {txid} re-confirmed at height {} (was height {}, likely a reorg)",
-                    block_time.block_id.height, old.block_id.height
+                    block_time.block_id.height,
+                    old.block_id.height
                 ),
-                None => eprintln!(
+                None => log::info!(
                     "Transaction {txid} confirmed at height {}",
                     block_time.block_id.height
                 ),
@@ -282,29 +1223,80 @@ pub fn print_wallet_events(events: &[WalletEvent]) {
                 old_block_time,
                 ..
             } => match old_block_time {
-                Some(old) => eprintln!(
+                Some(old) => log::info!(
                     "Transaction {txid} became unconfirmed (was confirmed at height {}, likely a reorg)",
                     old.block_id.height
                 ),
-                None => eprintln!("Transaction {txid} seen in mempool"),
+                None => log::info!("Transaction {txid} seen in mempool"),
             },
             WalletEvent::TxReplaced {
                 txid, conflicts, ..
             } => {
                 let ids: Vec<String> = conflicts.iter().map(|(_, c)| c.to_string()).collect();
-                eprintln!(
+                log::info!(
                     "Transaction {txid} was replaced (conflicts with: {})",
                     ids.join(", ")
                 );
             }
             WalletEvent::TxDropped { txid, .. } => {
-                eprintln!("Transaction {txid} dropped from the mempool");
+                log::info!("Transaction {txid} dropped from the mempool");
             }
             _ => {}
         }
     }
 }
 
+/// A wallet event as JSON, `None` for the events left unreported. `reorg` tells the transactions
+/// a reorg confirmed again or sent back to the mempool.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+fn wallet_event_json(event: &WalletEvent) -> Option<serde_json::Value> {
+    use serde_json::json;
+    Some(match event {
+        WalletEvent::ChainTipChanged { old_tip, new_tip } => json!({
+            "event": "chain_tip_changed",
+            "old_height": old_tip.height,
+            "height": new_tip.height,
+            "hash": new_tip.hash,
+        }),
+        WalletEvent::TxConfirmed {
+            txid,
+            block_time,
+            old_block_time,
+            ..
+        } => json!({
+            "event": "tx_confirmed",
+            "txid": txid,
+            "height": block_time.block_id.height,
+            "old_height": old_block_time.as_ref().map(|old| old.block_id.height),
+            "reorg": old_block_time.is_some(),
+        }),
+        WalletEvent::TxUnconfirmed {
+            txid,
+            old_block_time,
+            ..
+        } => json!({
+            "event": "tx_unconfirmed",
+            "txid": txid,
+            "old_height": old_block_time.as_ref().map(|old| old.block_id.height),
+            "reorg": old_block_time.is_some(),
+        }),
+        WalletEvent::TxReplaced {
+            txid, conflicts, ..
+        } => json!({
+            "event": "tx_replaced",
+            "txid": txid,
+            "conflicts": conflicts.iter().map(|(_, c)| c.to_string()).collect::<Vec<_>>(),
+        }),
+        WalletEvent::TxDropped { txid, .. } => json!({ "event": "tx_dropped", "txid": txid }),
+        _ => return None,
+    })
+}
+
 #[cfg(feature = "dns_payment")]
 /// Parse dns recipients in the form "test@me.com:10000" from cli input
 pub(crate) fn parse_dns_recipient(s: &str) -> Result<(String, u64), String> {
@@ -312,6 +1304,6 @@ pub(crate) fn parse_dns_recipient(s: &str) -> Result<(String, u64), String> {
     if parts.len() != 2 {
         return Err("Invalid format".to_string());
     }
-    let sending_amount = u64::from_str(parts[1]).map_err(|e| e.to_string())?;
+    let sending_amount = parse_amount(parts[1])?;
     Ok((parts[0].to_string(), sending_amount))
 }
@@ -1,24 +1,24 @@
 use bdk_wallet::keys::GeneratableKey;
 use std::{str::FromStr, sync::Arc};
 
-use bdk_wallet::keys::DescriptorPublicKey;
+use bdk_wallet::keys::{DescriptorPublicKey, DescriptorSecretKey};
 use bdk_wallet::{
     KeychainKind,
     bip39::{Language, Mnemonic},
     bitcoin::{
-        NetworkKind,
-        bip32::{DerivationPath, Xpriv, Xpub},
+        Network, NetworkKind,
+        bip32::{ChildNumber, DerivationPath, Fingerprint, Xpriv, Xpub},
         secp256k1::Secp256k1,
     },
     keys::{GeneratedKey, bip39::WordCount},
     miniscript::{
-        Descriptor, Miniscript, Segwitv0, Terminal,
-        descriptor::{DescriptorXKey, Wildcard},
+        Descriptor, ForEachKey, Miniscript, Segwitv0, Terminal,
+        descriptor::{DescriptorXKey, KeyMap, Wildcard, checksum::desc_checksum},
     },
     template::DescriptorTemplate,
 };
 
-use crate::error::BDKCliError as Error;
+use crate::error::{BDKCliError as Error, ErrorCode};
 use crate::utils::types::DescriptorResult;
 use crate::utils::types::KeychainPair;
 
@@ -66,9 +66,10 @@ fn generate_private_descriptors(
         "wpkh" | "wsh" => Bip84(xprv, KeychainKind::External).build(network)?,
         "tr" => Bip86(xprv, KeychainKind::External).build(network)?,
         _ => {
-            return Err(Error::Generic(format!(
-                "Unsupported descriptor type: {desc_type}"
-            )));
+            return Err(Error::coded(
+                ErrorCode::InvalidDescriptor,
+                format!("Unsupported descriptor type: {desc_type}"),
+            ));
         }
     };
 
@@ -78,9 +79,10 @@ fn generate_private_descriptors(
         "wpkh" | "wsh" => Bip84(xprv, KeychainKind::Internal).build(network)?,
         "tr" => Bip86(xprv, KeychainKind::Internal).build(network)?,
         _ => {
-            return Err(Error::Generic(format!(
-                "Unsupported descriptor type: {desc_type}"
-            )));
+            return Err(Error::coded(
+                ErrorCode::InvalidDescriptor,
+                format!("Unsupported descriptor type: {desc_type}"),
+            ));
         }
     };
 
@@ -91,7 +93,7 @@ fn generate_private_descriptors(
 
     Ok(DescriptorResult {
         descriptor: None,
-        multipath_descriptor: None,
+        multipath_descriptor: join_multipath(&external_pub, &internal_pub),
         public_descriptors: Some(KeychainPair {
             external: external_pub,
             internal: internal_pub,
@@ -103,6 +105,7 @@ fn generate_private_descriptors(
         mnemonic: None,
         fingerprint: Some(fingerprint.to_string()),
         r: None,
+        spending_paths: None,
     })
 }
 
@@ -132,7 +135,7 @@ pub fn generate_public_descriptors(
     let internal_pub = build_descriptor("1")?;
     Ok(DescriptorResult {
         descriptor: None,
-        multipath_descriptor: None,
+        multipath_descriptor: join_multipath(&external_pub, &internal_pub),
         public_descriptors: Some(KeychainPair {
             external: external_pub,
             internal: internal_pub,
@@ -141,6 +144,157 @@ pub fn generate_public_descriptors(
         mnemonic: None,
         fingerprint: Some(fingerprint.to_string()),
         r: None,
+        spending_paths: None,
+    })
+}
+
+/// Build the standard BIP-44, 49, 84 or 86 external and internal descriptors of an account.
+///
+/// The key is either a master private key, or the public key of the account with its origin,
+/// given as `[fingerprint/path]xpub` or with the master `fingerprint`.
+pub fn template_descriptors(
+    bip: u32,
+    account: u32,
+    key: &str,
+    fingerprint: Option<Fingerprint>,
+    network: NetworkKind,
+) -> Result<DescriptorResult, Error> {
+    let desc_type = match bip {
+        44 => "pkh",
+        49 => "sh",
+        84 => "wpkh",
+        86 => "tr",
+        _ => {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                format!("Unsupported template BIP-{bip}, use 44, 49, 84 or 86"),
+            ));
+        }
+    };
+    let coin_type = match network {
+        NetworkKind::Main => 0,
+        NetworkKind::Test => 1,
+    };
+    let path: DerivationPath = [bip, coin_type, account]
+        .into_iter()
+        .map(ChildNumber::from_hardened_idx)
+        .collect::<Result<Vec<_>, _>>()?
+        .into();
+
+    let secp = Secp256k1::new();
+    let (master_fingerprint, account_xprv, account_xpub) = match Xpriv::from_str(key) {
+        Ok(xprv) => {
+            if xprv.depth != 0 {
+                return Err(Error::coded(
+                    ErrorCode::InvalidKey,
+                    format!(
+                        "Give the master private key, not a key at depth {}",
+                        xprv.depth
+                    ),
+                ));
+            }
+            if xprv.network != network {
+                return Err(Error::coded(
+                    ErrorCode::NetworkMismatch,
+                    "Extended key network does not match current network".to_string(),
+                ));
+            }
+            let account_xprv = xprv.derive_priv(&secp, &path)?;
+            let account_xpub = Xpub::from_priv(&secp, &account_xprv);
+            (xprv.fingerprint(&secp), Some(account_xprv), account_xpub)
+        }
+        Err(_) => {
+            let (origin, xpub) = match DescriptorPublicKey::from_str(key)? {
+                DescriptorPublicKey::XPub(xkey)
+                    if xkey.derivation_path.is_empty() && xkey.wildcard == Wildcard::None =>
+                {
+                    (xkey.origin, xkey.xkey)
+                }
+                _ => {
+                    return Err(Error::coded(
+                        ErrorCode::InvalidKey,
+                        "Give a master private key or an account extended public key".to_string(),
+                    ));
+                }
+            };
+            if xpub.network != network {
+                return Err(Error::coded(
+                    ErrorCode::NetworkMismatch,
+                    "Extended key network does not match current network".to_string(),
+                ));
+            }
+            let master_fingerprint = match (origin, fingerprint) {
+                (Some((origin_fingerprint, origin_path)), _) => {
+                    if origin_path != path {
+                        return Err(Error::coded(
+                            ErrorCode::InvalidKey,
+                            format!(
+                                "The key origin m/{origin_path} is not the BIP-{bip} account path m/{path}"
+                            ),
+                        ));
+                    }
+                    origin_fingerprint
+                }
+                (None, Some(fingerprint)) => {
+                    if xpub.depth != 3 || xpub.child_number != path[2] {
+                        return Err(Error::coded(
+                            ErrorCode::InvalidKey,
+                            format!("The extended public key is not the key of account {account}"),
+                        ));
+                    }
+                    fingerprint
+                }
+                (None, None) => {
+                    return Err(Error::coded(
+                        ErrorCode::InvalidKey,
+                        "Give the origin of the account key as [fingerprint/path]xpub, or the \
+                         master fingerprint"
+                            .to_string(),
+                    ));
+                }
+            };
+            (master_fingerprint, None, xpub)
+        }
+    };
+
+    let build_descriptors = |branch: u32| -> Result<(String, Option<String>), Error> {
+        let origin = Some((master_fingerprint, path.clone()));
+        let derivation_path = DerivationPath::from(vec![ChildNumber::from_normal_idx(branch)?]);
+        let public_key = DescriptorPublicKey::XPub(DescriptorXKey {
+            origin: origin.clone(),
+            xkey: account_xpub,
+            derivation_path: derivation_path.clone(),
+            wildcard: Wildcard::Unhardened,
+        });
+        let descriptor = build_public_descriptor(desc_type, public_key.clone())?;
+        let private = account_xprv.map(|xkey| {
+            let secret_key = DescriptorSecretKey::XPrv(DescriptorXKey {
+                origin,
+                xkey,
+                derivation_path,
+                wildcard: Wildcard::Unhardened,
+            });
+            descriptor.to_string_with_secret(&KeyMap::from([(public_key, secret_key)]))
+        });
+        Ok((descriptor.to_string(), private))
+    };
+    let (external_pub, external_priv) = build_descriptors(0)?;
+    let (internal_pub, internal_priv) = build_descriptors(1)?;
+
+    Ok(DescriptorResult {
+        descriptor: None,
+        multipath_descriptor: join_multipath(&external_pub, &internal_pub),
+        public_descriptors: Some(KeychainPair {
+            external: external_pub,
+            internal: internal_pub,
+        }),
+        private_descriptors: external_priv
+            .zip(internal_priv)
+            .map(|(external, internal)| KeychainPair { external, internal }),
+        mnemonic: None,
+        fingerprint: Some(master_fingerprint.to_string()),
+        r: None,
+        spending_paths: None,
     })
 }
 
@@ -160,9 +314,10 @@ pub fn build_public_descriptor(
             Descriptor::new_wsh(pk_ms).map_err(Error::from)
         }
         "tr" => Descriptor::new_tr(key, None).map_err(Error::from),
-        _ => Err(Error::Generic(format!(
-            "Unsupported descriptor type: {desc_type}"
-        ))),
+        _ => Err(Error::coded(
+            ErrorCode::InvalidDescriptor,
+            format!("Unsupported descriptor type: {desc_type}"),
+        )),
     }
 }
 
@@ -170,11 +325,12 @@ pub fn build_public_descriptor(
 pub fn generate_descriptor_with_mnemonic(
     network: NetworkKind,
     desc_type: &str,
+    passphrase: &str,
 ) -> Result<DescriptorResult, Error> {
     let mnemonic: GeneratedKey<Mnemonic, Segwitv0> =
         Mnemonic::generate((WordCount::Words12, Language::English)).map_err(Error::BIP39Error)?;
 
-    let seed = mnemonic.to_seed("");
+    let seed = mnemonic.to_seed(passphrase);
     let xprv = Xpriv::new_master(network, &seed)?;
 
     let mut result = generate_descriptors(desc_type, &xprv.to_string(), network)?;
@@ -187,12 +343,137 @@ pub fn generate_descriptor_from_mnemonic(
     mnemonic_str: &str,
     network: NetworkKind,
     desc_type: &str,
+    passphrase: &str,
 ) -> Result<DescriptorResult, Error> {
-    let mnemonic = Mnemonic::parse_in(Language::English, mnemonic_str)?;
-    let seed = mnemonic.to_seed("");
+    let mnemonic = Mnemonic::parse(mnemonic_str)?;
+    let seed = mnemonic.to_seed(passphrase);
     let xprv = Xpriv::new_master(network, &seed)?;
 
     let mut result = generate_descriptors(desc_type, &xprv.to_string(), network)?;
     result.mnemonic = Some(mnemonic_str.to_string());
     Ok(result)
 }
+
+/// Fails when the extended or WIF keys of `descriptor` belong to another kind of network than
+/// `network`, such as tpubs on mainnet, which would otherwise sync to an empty wallet.
+pub fn check_descriptor_network(descriptor: &str, network: Network) -> Result<(), Error> {
+    // A descriptor that does not parse is reported as such when the wallet is created.
+    let Ok((descriptor, keymap)) =
+        Descriptor::<DescriptorPublicKey>::parse_descriptor(&Secp256k1::new(), descriptor)
+    else {
+        return Ok(());
+    };
+    let mut kinds = Vec::new();
+    descriptor.for_each_key(|key| {
+        match key {
+            DescriptorPublicKey::XPub(xpub) => kinds.push(xpub.xkey.network),
+            DescriptorPublicKey::MultiXPub(xpub) => kinds.push(xpub.xkey.network),
+            DescriptorPublicKey::Single(_) => {}
+        }
+        true
+    });
+    kinds.extend(keymap.values().map(|key| match key {
+        DescriptorSecretKey::Single(single) => single.key.network,
+        DescriptorSecretKey::XPrv(xprv) => xprv.xkey.network,
+        DescriptorSecretKey::MultiXPrv(xprv) => xprv.xkey.network,
+    }));
+
+    let expected = NetworkKind::from(network);
+    let keys = |kind| match kind {
+        NetworkKind::Main => "mainnet keys (xpub, xprv)",
+        NetworkKind::Test => "test network keys (tpub, tprv)",
+    };
+    match kinds.into_iter().find(|kind| *kind != expected) {
+        None => Ok(()),
+        Some(kind) => Err(Error::coded(
+            ErrorCode::NetworkMismatch,
+            format!(
+                "Network mismatch: the descriptor has {}, but the network is {network}, which takes {}",
+                keys(kind),
+                keys(expected)
+            ),
+        )),
+    }
+}
+
+/// Split a BIP-389 multipath descriptor into its external and internal descriptors.
+///
+/// Each `<a;b>` step must have exactly two paths, the first for the external keychain and the
+/// second for the internal one. Returns `None` for a single path descriptor.
+pub fn split_multipath(descriptor: &str) -> Result<Option<KeychainPair<String>>, Error> {
+    let (body, checksum) = match descriptor.split_once('#') {
+        Some((body, checksum)) => (body, Some(checksum)),
+        None => (descriptor, None),
+    };
+    if !body.contains('<') {
+        return Ok(None);
+    }
+    if let Some(checksum) = checksum {
+        let expected = desc_checksum(body)
+            .map_err(|e| Error::coded(ErrorCode::InvalidDescriptor, e.to_string()))?;
+        if checksum != expected {
+            return Err(Error::coded(
+                ErrorCode::ChecksumMismatch,
+                format!("Checksum mismatch: the descriptor has #{checksum}, expected #{expected}"),
+            ));
+        }
+    }
+
+    let mut paths = [String::new(), String::new()];
+    let mut rest = body;
+    while let Some(start) = rest.find('<') {
+        let end = rest[start..]
+            .find('>')
+            .map(|end| start + end)
+            .ok_or_else(|| {
+                Error::coded(
+                    ErrorCode::InvalidDescriptor,
+                    "Unclosed multipath step in descriptor".to_string(),
+                )
+            })?;
+        let steps: Vec<&str> = rest[start + 1..end].split(';').collect();
+        if steps.len() != 2 {
+            return Err(Error::coded(
+                ErrorCode::InvalidDescriptor,
+                format!(
+                    "Multipath descriptors need exactly two paths, one per keychain, found {}",
+                    steps.len()
+                ),
+            ));
+        }
+        for (path, step) in paths.iter_mut().zip(steps) {
+            path.push_str(&rest[..start]);
+            path.push_str(step);
+        }
+        rest = &rest[end + 1..];
+    }
+    let [external, internal] = paths.map(|path| path + rest);
+    Ok(Some(KeychainPair { external, internal }))
+}
+
+/// Join an external and an internal descriptor into a BIP-389 multipath descriptor, when they
+/// only differ by derivation steps.
+pub fn join_multipath(external: &str, internal: &str) -> Option<String> {
+    let external = external.split('#').next().unwrap_or_default();
+    let internal = internal.split('#').next().unwrap_or_default();
+    let external_steps: Vec<&str> = external.split('/').collect();
+    let internal_steps: Vec<&str> = internal.split('/').collect();
+    if external == internal || external_steps.len() != internal_steps.len() {
+        return None;
+    }
+
+    let mut steps = Vec::with_capacity(external_steps.len());
+    for (external, internal) in external_steps.into_iter().zip(internal_steps) {
+        if external == internal {
+            steps.push(external.to_string());
+        } else if ChildNumber::from_str(external).is_ok() && ChildNumber::from_str(internal).is_ok()
+        {
+            steps.push(format!("<{external};{internal}>"));
+        } else {
+            return None;
+        }
+    }
+    let body = steps.join("/");
+    let checksum = desc_checksum(&body).ok()?;
+    Some(format!("{body}#{checksum}"))
+}
@@ -0,0 +1,1062 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Electrum TLS verification
+//!
+//! `electrum_client` verifies TLS servers against the public web roots only. This module opens
+//! the TLS connections that trust a pinned server certificate, typically a self-signed one, or
+//! the CA certificates of a PEM file instead, and puts them behind the same [`ElectrumApi`] as
+//! the regular client.
+//!
+//! The client also splits the batched requests in batches of at most the batch size, and halves
+//! the batch size to retry when a server rejects a batch as too large.
+//!
+//! The headers the client fetches for blocks buried deep under the tip of the server are kept in
+//! the `headers.json` of the wallet, so that the next runs, short-lived commands over Tor in
+//! particular, do not fetch them again. A cached header is only answered for a block the chain of
+//! the wallet already has at its height, and the file is written once per batch of headers and
+//! when the client is dropped.
+
+use crate::commands::ElectrumTlsOpts;
+use crate::error::{BDKCliError, ErrorCode};
+use bdk_electrum::electrum_client::bitcoin::{Script, Txid};
+use bdk_electrum::electrum_client::raw_client::{ElectrumSslStream, RawClient};
+use bdk_electrum::electrum_client::socks::Socks5Stream;
+use bdk_electrum::electrum_client::{
+    Batch, BroadcastPackageRes, Client, Config, ElectrumApi, Error, EstimationMode, GetBalanceRes,
+    GetHeadersRes, GetHistoryRes, GetMerkleRes, ListUnspentRes, MempoolInfoRes, Param,
+    RawHeaderNotification, ScriptStatus, ServerFeaturesRes, TxidFromPosRes,
+};
+use bdk_wallet::bitcoin::Network;
+use bdk_wallet::bitcoin::block::Header;
+use bdk_wallet::bitcoin::consensus::{deserialize, serialize};
+use bdk_wallet::bitcoin::hashes::{Hash, sha256};
+use bdk_wallet::bitcoin::hex::{DisplayHex, FromHex};
+use bdk_wallet::bitcoin::params::Params;
+use bdk_wallet::chain::CheckPoint;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, aws_lc_rs, verify_tls12_signature, verify_tls13_signature};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{
+    ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    StreamOwned,
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::Duration;
+
+/// File of the wallet data directory caching the block headers fetched from Electrum.
+pub const HEADER_CACHE_FILE: &str = "headers.json";
+
+/// Depth under the tip of the server from which a block header is cached, deep enough that no
+/// reorg replaces it.
+const CACHED_HEADER_DEPTH: u32 = 100;
+
+/// Parse a SHA256 certificate fingerprint, hex encoded with optional colons as printed by
+/// `openssl x509 -fingerprint -sha256`, into lowercase hex.
+pub(crate) fn parse_cert_fingerprint(s: &str) -> Result<String, BDKCliError> {
+    let fingerprint = <[u8; 32]>::from_hex(&s.replace(':', "")).map_err(|e| {
+        BDKCliError::coded(
+            ErrorCode::InvalidArgument,
+            format!("Invalid SHA256 certificate fingerprint {s}: {e}"),
+        )
+    })?;
+    Ok(fingerprint.to_lower_hex_string())
+}
+
+/// The TLS configuration of the [`ElectrumTlsOpts`], `None` when the server certificates are
+/// verified against the public web roots.
+pub fn tls_config(opts: &ElectrumTlsOpts) -> Result<Option<Arc<ClientConfig>>, BDKCliError> {
+    let provider = Arc::new(aws_lc_rs::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| {
+            BDKCliError::coded(ErrorCode::Config, format!("Invalid TLS configuration: {e}"))
+        })?;
+
+    let config = if let Some(fingerprint) = &opts.cert_fingerprint {
+        let fingerprint = <[u8; 32]>::from_hex(&parse_cert_fingerprint(fingerprint)?)?;
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(PinnedCertificate {
+                fingerprint,
+                provider,
+            }))
+            .with_no_client_auth()
+    } else if let Some(ca_file) = &opts.ca_file {
+        let read_error = |e| {
+            BDKCliError::coded(
+                ErrorCode::Io,
+                format!("Failed to read CA certificates {ca_file:?}: {e}"),
+            )
+        };
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_file_iter(ca_file).map_err(read_error)? {
+            roots.add(cert.map_err(read_error)?).map_err(|e| {
+                BDKCliError::coded(ErrorCode::Config, format!("Invalid CA certificate: {e}"))
+            })?;
+        }
+        if roots.is_empty() {
+            return Err(BDKCliError::coded(
+                ErrorCode::Config,
+                format!("No CA certificate found in {ca_file:?}"),
+            ));
+        }
+        builder.with_root_certificates(roots).with_no_client_auth()
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(Arc::new(config)))
+}
+
+/// Accepts the server certificate with a SHA256 fingerprint, whoever issued it.
+#[derive(Debug)]
+struct PinnedCertificate {
+    fingerprint: [u8; 32],
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let fingerprint = sha256::Hash::hash(end_entity).to_byte_array();
+        if fingerprint != self.fingerprint {
+            return Err(rustls::Error::General(format!(
+                "server certificate fingerprint {} is not the pinned {}",
+                fingerprint.to_lower_hex_string(),
+                self.fingerprint.to_lower_hex_string()
+            )));
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// The headers of `headers.json`, by height.
+#[derive(Default, Deserialize, Serialize)]
+struct CachedHeaders {
+    network: String,
+    /// Raw headers in hex.
+    headers: BTreeMap<u32, String>,
+}
+
+/// Block headers buried deep enough to outlive any reorg, persisted in the wallet data directory.
+/// The genesis header is never cached, as it tells the network of the server.
+///
+/// A cached header is only answered for a block of the chain of the wallet, so a header a server
+/// made up is never taken for one the wallet verified, by later runs and other servers alike.
+pub struct HeaderCache {
+    path: PathBuf,
+    network: Network,
+    headers: Mutex<BTreeMap<u32, Header>>,
+    /// Tip of the chain of the wallet, whose blocks the cached headers must be.
+    chain: CheckPoint,
+    /// Whether headers were cached since the file was last saved.
+    dirty: AtomicBool,
+    /// Height of the tip of the server, once it told it.
+    tip: AtomicU32,
+}
+
+impl HeaderCache {
+    /// The cache of the wallet data directory `datadir`, keeping the headers of `network` that
+    /// meet their proof of work and answering those of the blocks of `chain`. Regtest chains are
+    /// reset and reorged at will, and never cached.
+    pub fn open(datadir: &Path, network: Network, chain: CheckPoint) -> Option<Self> {
+        if network == Network::Regtest {
+            return None;
+        }
+        let path = datadir.join(HEADER_CACHE_FILE);
+        let cached = std::fs::read(&path)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<CachedHeaders>(&json).ok())
+            .filter(|cached| cached.network == network.to_string())
+            .unwrap_or_default();
+        let headers = cached
+            .headers
+            .into_iter()
+            .filter(|(height, _)| *height > 0)
+            .filter_map(|(height, hex)| {
+                let raw = Vec::<u8>::from_hex(&hex).ok()?;
+                Some((height, valid_header(&raw, network)?))
+            })
+            .collect();
+        Some(Self {
+            path,
+            network,
+            headers: Mutex::new(headers),
+            chain,
+            dirty: AtomicBool::new(false),
+            tip: AtomicU32::new(0),
+        })
+    }
+
+    /// The cached header at `height`, when it is the block of the chain of the wallet there.
+    fn get(&self, height: u32) -> Option<Vec<u8>> {
+        let block = self.chain.get(height)?;
+        self.headers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&height)
+            .filter(|header| header.block_hash() == block.hash())
+            .map(serialize)
+    }
+
+    fn set_tip(&self, height: u32) {
+        self.tip.fetch_max(height, Ordering::Relaxed);
+    }
+
+    /// Caches the `fetched` headers buried deep enough under the tip that meet the proof of work
+    /// of the network, in place of the headers cached at their heights. The cache is saved by
+    /// [`HeaderCache::save`].
+    fn insert(&self, fetched: impl IntoIterator<Item = (u32, Vec<u8>)>) {
+        let tip = self.tip.load(Ordering::Relaxed);
+        let mut headers = self.headers.lock().unwrap_or_else(PoisonError::into_inner);
+        for (height, raw) in fetched {
+            if height == 0 || height.saturating_add(CACHED_HEADER_DEPTH) > tip {
+                continue;
+            }
+            let Some(header) = valid_header(&raw, self.network) else {
+                log::debug!("Not caching the invalid block header at height {height}");
+                continue;
+            };
+            if headers.insert(height, header) != Some(header) {
+                self.dirty.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Writes the cache to its file when headers were cached since it was last written. Failing
+    /// to save only costs fetching them again.
+    fn save(&self) {
+        let headers = self.headers.lock().unwrap_or_else(PoisonError::into_inner);
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let cached = CachedHeaders {
+            network: self.network.to_string(),
+            headers: headers
+                .iter()
+                .map(|(height, header)| (*height, serialize(header).to_lower_hex_string()))
+                .collect(),
+        };
+        if let Err(e) = serde_json::to_vec(&cached)
+            .map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(&self.path, json))
+        {
+            log::debug!("Cannot cache the block headers in {:?}: {e}", self.path);
+        }
+    }
+}
+
+impl Drop for HeaderCache {
+    fn drop(&mut self) {
+        self.save();
+    }
+}
+
+/// The block header `raw`, when its target is within the proof of work limit of `network` and
+/// its hash meets that target.
+fn valid_header(raw: &[u8], network: Network) -> Option<Header> {
+    let header = deserialize::<Header>(raw).ok()?;
+    let target = header.target();
+    (target <= Params::new(network).max_attainable_target && header.validate_pow(target).is_ok())
+        .then_some(header)
+}
+
+/// An Electrum client over either kind of connection.
+pub struct ElectrumClient {
+    connection: Connection,
+    /// Largest number of requests sent in one batch, lowered when the server rejects a batch.
+    batch_size: AtomicUsize,
+    headers: Option<HeaderCache>,
+}
+
+enum Connection {
+    /// Connection of `electrum_client`, re-established on failures.
+    Managed(Client),
+    /// TLS connection verified with a custom [`tls_config`].
+    Tls(TlsConnection),
+}
+
+/// A TLS connection of its own, re-established on failures like the managed ones.
+struct TlsConnection {
+    server: String,
+    config: Config,
+    tls: Arc<ClientConfig>,
+    client: RwLock<RawClient<ElectrumSslStream>>,
+}
+
+impl TlsConnection {
+    /// Sends a request with `call`. A request failing other than with an error response of the
+    /// server is sent again on a new connection, up to the retries of the config.
+    fn call<R>(
+        &self,
+        call: impl Fn(&RawClient<ElectrumSslStream>) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        let mut errors = Vec::new();
+        loop {
+            match call(&self.client.read().unwrap_or_else(PoisonError::into_inner)) {
+                Ok(result) => return Ok(result),
+                Err(e @ (Error::Protocol(_) | Error::AlreadySubscribed(_))) => return Err(e),
+                Err(e) => errors.push(e),
+            }
+            loop {
+                if errors.len() > self.config.retry() as usize {
+                    return Err(Error::AllAttemptsErrored(errors));
+                }
+                log::debug!("Reconnecting to Electrum server {}", self.server);
+                match open_tls(&self.server, &self.config, &self.tls) {
+                    Ok(client) => {
+                        *self.client.write().unwrap_or_else(PoisonError::into_inner) = client;
+                        break;
+                    }
+                    Err(e) => errors.push(e),
+                }
+            }
+        }
+    }
+}
+
+impl ElectrumClient {
+    /// Connects to `server` with the timeout and proxy of `config`, over a TLS connection verified
+    /// with `tls` when given, to send batches of at most `batch_size` requests.
+    pub fn connect(
+        server: &str,
+        config: &Config,
+        tls: Option<&Arc<ClientConfig>>,
+        batch_size: usize,
+    ) -> Result<Self, Error> {
+        Ok(ElectrumClient {
+            connection: Connection::open(server, config, tls)?,
+            batch_size: AtomicUsize::new(batch_size.max(1)),
+            headers: None,
+        })
+    }
+
+    /// Answers the block headers of the requests from `headers` when it has them.
+    pub fn with_header_cache(mut self, headers: Option<HeaderCache>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Requests `items` in batches of at most the batch size. A batch the server rejects is
+    /// retried in batches half its size, down to single requests.
+    fn batched<T, R>(
+        &self,
+        items: &[T],
+        call: impl Fn(&[T]) -> Result<Vec<R>, Error>,
+    ) -> Result<Vec<R>, Error> {
+        let mut results = Vec::with_capacity(items.len());
+        let mut rest = items;
+        while !rest.is_empty() {
+            let size = self.batch_size.load(Ordering::Relaxed).min(rest.len());
+            match call(&rest[..size]) {
+                Ok(batch) => {
+                    results.extend(batch);
+                    rest = &rest[size..];
+                }
+                Err(e) if size > 1 && rejects_batch(&e) => {
+                    let smaller = size / 2;
+                    self.batch_size.fetch_min(smaller, Ordering::Relaxed);
+                    log::warn!(
+                        "Electrum server rejected a batch of {size} requests ({e}), retrying in batches of {smaller}. Use --batch-size {smaller} to skip the retries."
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// Whether `error` is how servers turn down a batch over their limit: an error response about the
+/// cost of the session, the `excessive resource usage` of ElectrumX and Fulcrum, or about the
+/// size of the batch. Lost connections and other errors fail the request as they are.
+fn rejects_batch(error: &Error) -> bool {
+    match error {
+        Error::Protocol(response) => {
+            let message = response.to_string().to_lowercase();
+            message.contains("excessive resource usage")
+                || (message.contains("batch") && message.contains("too large"))
+        }
+        Error::AllAttemptsErrored(errors) => !errors.is_empty() && errors.iter().all(rejects_batch),
+        _ => false,
+    }
+}
+
+/// Whether `error` is how servers answer a request for a transaction they do not know.
+pub(crate) fn is_unknown_tx(error: &Error) -> bool {
+    match error {
+        Error::Protocol(response) => {
+            let message = response.to_string().to_lowercase();
+            ["not found", "no such"]
+                .iter()
+                .any(|reason| message.contains(reason))
+        }
+        Error::AllAttemptsErrored(errors) => errors.iter().all(is_unknown_tx),
+        _ => false,
+    }
+}
+
+impl Connection {
+    fn open(server: &str, config: &Config, tls: Option<&Arc<ClientConfig>>) -> Result<Self, Error> {
+        let Some(tls) = tls else {
+            return Client::from_config(server, config.clone()).map(Self::Managed);
+        };
+        Ok(Self::Tls(TlsConnection {
+            client: RwLock::new(open_tls(server, config, tls)?),
+            server: server.to_string(),
+            config: config.clone(),
+            tls: tls.clone(),
+        }))
+    }
+}
+
+/// Opens a connection to the `ssl://` url `server`, verified with `tls`.
+fn open_tls(
+    server: &str,
+    config: &Config,
+    tls: &Arc<ClientConfig>,
+) -> Result<RawClient<ElectrumSslStream>, Error> {
+    let address = server.trim_start_matches("ssl://");
+    let host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let server_name = ServerName::try_from(host.to_string())
+        .map_err(|_| Error::InvalidDNSNameError(host.to_string()))?;
+
+    let timeout = config.timeout();
+    let stream = match config.socks5() {
+        Some(proxy) => match &proxy.credentials {
+            Some(credentials) => Socks5Stream::connect_with_password(
+                &proxy.addr,
+                address,
+                &credentials.username,
+                &credentials.password,
+                timeout,
+            ),
+            None => Socks5Stream::connect(&proxy.addr, address, timeout),
+        }?
+        .into_inner(),
+        None => connect_tcp(address, timeout)?,
+    };
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)?;
+
+    let connection =
+        ClientConnection::new(tls.clone(), server_name).map_err(Error::CouldNotCreateConnection)?;
+    let client = RawClient::from(StreamOwned::new(connection, stream));
+    // The raw client parses the responses of protocol 1.4 unless it negotiated the version
+    // itself, which it only does for the connections it opens.
+    client.raw_call(
+        "server.version",
+        [
+            Param::String(String::new()),
+            Param::StringVec(vec!["1.4".to_string(), "1.4".to_string()]),
+        ],
+    )?;
+    Ok(client)
+}
+
+fn connect_tcp(address: &str, timeout: Option<Duration>) -> std::io::Result<TcpStream> {
+    let Some(timeout) = timeout else {
+        return TcpStream::connect(address);
+    };
+    let mut last_error = None;
+    for socket_addr in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&socket_addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{address} resolves to no address"),
+        )
+    }))
+}
+
+/// Sends a request on the connection, counting it, a batch being one request.
+macro_rules! delegate {
+    ($self:ident.$method:ident($($arg:expr),*)) => {{
+        crate::utils::stats::add_requests(1);
+        match &$self.connection {
+            Connection::Managed(client) => client.$method($($arg),*),
+            Connection::Tls(connection) => connection.call(|client| client.$method($($arg),*)),
+        }
+    }};
+}
+
+impl ElectrumApi for ElectrumClient {
+    fn raw_call(
+        &self,
+        method_name: &str,
+        params: impl IntoIterator<Item = Param>,
+    ) -> Result<serde_json::Value, Error> {
+        // Collected to be sent again on a new connection.
+        let params: Vec<Param> = params.into_iter().collect();
+        delegate!(self.raw_call(method_name, params.clone()))
+    }
+
+    fn batch_call(&self, batch: &Batch) -> Result<Vec<serde_json::Value>, Error> {
+        delegate!(self.batch_call(batch))
+    }
+
+    fn block_headers_subscribe_raw(&self) -> Result<RawHeaderNotification, Error> {
+        let notification = delegate!(self.block_headers_subscribe_raw())?;
+        if let Some(headers) = &self.headers {
+            headers.set_tip(notification.height as u32);
+        }
+        Ok(notification)
+    }
+
+    fn block_headers_pop_raw(&self) -> Result<Option<RawHeaderNotification>, Error> {
+        let notification = delegate!(self.block_headers_pop_raw())?;
+        if let (Some(headers), Some(notification)) = (&self.headers, &notification) {
+            headers.set_tip(notification.height as u32);
+        }
+        Ok(notification)
+    }
+
+    fn block_header_raw(&self, height: usize) -> Result<Vec<u8>, Error> {
+        let Some(headers) = &self.headers else {
+            return delegate!(self.block_header_raw(height));
+        };
+        if let Some(raw) = headers.get(height as u32) {
+            return Ok(raw);
+        }
+        let raw = delegate!(self.block_header_raw(height))?;
+        headers.insert([(height as u32, raw.clone())]);
+        Ok(raw)
+    }
+
+    fn block_headers(&self, start_height: usize, count: usize) -> Result<GetHeadersRes, Error> {
+        delegate!(self.block_headers(start_height, count))
+    }
+
+    fn estimate_fee(&self, number: usize, mode: Option<EstimationMode>) -> Result<f64, Error> {
+        delegate!(self.estimate_fee(number, mode))
+    }
+
+    fn relay_fee(&self) -> Result<f64, Error> {
+        delegate!(self.relay_fee())
+    }
+
+    fn script_subscribe(&self, script: &Script) -> Result<Option<ScriptStatus>, Error> {
+        delegate!(self.script_subscribe(script))
+    }
+
+    fn batch_script_subscribe<'s, I>(&self, scripts: I) -> Result<Vec<Option<ScriptStatus>>, Error>
+    where
+        I: IntoIterator + Clone,
+        I::Item: Borrow<&'s Script>,
+    {
+        let scripts: Vec<&Script> = scripts.into_iter().map(|script| *script.borrow()).collect();
+        self.batched(&scripts, |batch| {
+            delegate!(self.batch_script_subscribe(batch))
+        })
+    }
+
+    fn script_unsubscribe(&self, script: &Script) -> Result<bool, Error> {
+        delegate!(self.script_unsubscribe(script))
+    }
+
+    fn script_pop(&self, script: &Script) -> Result<Option<ScriptStatus>, Error> {
+        delegate!(self.script_pop(script))
+    }
+
+    fn script_get_balance(&self, script: &Script) -> Result<GetBalanceRes, Error> {
+        delegate!(self.script_get_balance(script))
+    }
+
+    fn batch_script_get_balance<'s, I>(&self, scripts: I) -> Result<Vec<GetBalanceRes>, Error>
+    where
+        I: IntoIterator + Clone,
+        I::Item: Borrow<&'s Script>,
+    {
+        let scripts: Vec<&Script> = scripts.into_iter().map(|script| *script.borrow()).collect();
+        self.batched(&scripts, |batch| {
+            delegate!(self.batch_script_get_balance(batch))
+        })
+    }
+
+    fn script_get_history(&self, script: &Script) -> Result<Vec<GetHistoryRes>, Error> {
+        delegate!(self.script_get_history(script))
+    }
+
+    fn batch_script_get_history<'s, I>(&self, scripts: I) -> Result<Vec<Vec<GetHistoryRes>>, Error>
+    where
+        I: IntoIterator + Clone,
+        I::Item: Borrow<&'s Script>,
+    {
+        let scripts: Vec<&Script> = scripts.into_iter().map(|script| *script.borrow()).collect();
+        self.batched(&scripts, |batch| {
+            delegate!(self.batch_script_get_history(batch))
+        })
+    }
+
+    fn script_list_unspent(&self, script: &Script) -> Result<Vec<ListUnspentRes>, Error> {
+        delegate!(self.script_list_unspent(script))
+    }
+
+    fn batch_script_list_unspent<'s, I>(
+        &self,
+        scripts: I,
+    ) -> Result<Vec<Vec<ListUnspentRes>>, Error>
+    where
+        I: IntoIterator + Clone,
+        I::Item: Borrow<&'s Script>,
+    {
+        let scripts: Vec<&Script> = scripts.into_iter().map(|script| *script.borrow()).collect();
+        self.batched(&scripts, |batch| {
+            delegate!(self.batch_script_list_unspent(batch))
+        })
+    }
+
+    fn transaction_get_raw(&self, txid: &Txid) -> Result<Vec<u8>, Error> {
+        delegate!(self.transaction_get_raw(txid))
+    }
+
+    fn batch_transaction_get_raw<'t, I>(&self, txids: I) -> Result<Vec<Vec<u8>>, Error>
+    where
+        I: IntoIterator + Clone,
+        I::Item: Borrow<&'t Txid>,
+    {
+        let txids: Vec<&Txid> = txids.into_iter().map(|txid| *txid.borrow()).collect();
+        self.batched(&txids, |batch| {
+            delegate!(self.batch_transaction_get_raw(batch))
+        })
+    }
+
+    fn batch_block_header_raw<I>(&self, heights: I) -> Result<Vec<Vec<u8>>, Error>
+    where
+        I: IntoIterator + Clone,
+        I::Item: Borrow<u32>,
+    {
+        let heights: Vec<u32> = heights.into_iter().map(|height| *height.borrow()).collect();
+        let cached: Vec<Option<Vec<u8>>> = heights
+            .iter()
+            .map(|height| {
+                self.headers
+                    .as_ref()
+                    .and_then(|headers| headers.get(*height))
+            })
+            .collect();
+        let missing: Vec<u32> = heights
+            .iter()
+            .zip(&cached)
+            .filter(|(_, raw)| raw.is_none())
+            .map(|(height, _)| *height)
+            .collect();
+        let fetched = self.batched(&missing, |batch| {
+            delegate!(self.batch_block_header_raw(batch))
+        })?;
+        if let Some(headers) = &self.headers {
+            headers.insert(missing.iter().copied().zip(fetched.iter().cloned()));
+            headers.save();
+        }
+        let mut fetched = fetched.into_iter();
+        Ok(cached
+            .into_iter()
+            .filter_map(|raw| raw.or_else(|| fetched.next()))
+            .collect())
+    }
+
+    fn batch_estimate_fee<I>(&self, numbers: I) -> Result<Vec<f64>, Error>
+    where
+        I: IntoIterator + Clone,
+        I::Item: Borrow<usize>,
+    {
+        let numbers: Vec<usize> = numbers.into_iter().map(|number| *number.borrow()).collect();
+        self.batched(&numbers, |batch| delegate!(self.batch_estimate_fee(batch)))
+    }
+
+    fn transaction_broadcast_raw(&self, raw_tx: &[u8]) -> Result<Txid, Error> {
+        delegate!(self.transaction_broadcast_raw(raw_tx))
+    }
+
+    fn transaction_broadcast_package_raw<T: AsRef<[u8]>>(
+        &self,
+        raw_txs: &[T],
+    ) -> Result<BroadcastPackageRes, Error> {
+        delegate!(self.transaction_broadcast_package_raw(raw_txs))
+    }
+
+    fn transaction_get_merkle(&self, txid: &Txid, height: usize) -> Result<GetMerkleRes, Error> {
+        delegate!(self.transaction_get_merkle(txid, height))
+    }
+
+    fn batch_transaction_get_merkle<I>(
+        &self,
+        txids_and_heights: I,
+    ) -> Result<Vec<GetMerkleRes>, Error>
+    where
+        I: IntoIterator + Clone,
+        I::Item: Borrow<(Txid, usize)>,
+    {
+        let txids_and_heights: Vec<(Txid, usize)> = txids_and_heights
+            .into_iter()
+            .map(|txid_and_height| *txid_and_height.borrow())
+            .collect();
+        self.batched(&txids_and_heights, |batch| {
+            delegate!(self.batch_transaction_get_merkle(batch))
+        })
+    }
+
+    fn txid_from_pos(&self, height: usize, tx_pos: usize) -> Result<Txid, Error> {
+        delegate!(self.txid_from_pos(height, tx_pos))
+    }
+
+    fn txid_from_pos_with_merkle(
+        &self,
+        height: usize,
+        tx_pos: usize,
+    ) -> Result<TxidFromPosRes, Error> {
+        delegate!(self.txid_from_pos_with_merkle(height, tx_pos))
+    }
+
+    fn server_features(&self) -> Result<ServerFeaturesRes, Error> {
+        delegate!(self.server_features())
+    }
+
+    fn mempool_get_info(&self) -> Result<MempoolInfoRes, Error> {
+        delegate!(self.mempool_get_info())
+    }
+
+    fn ping(&self) -> Result<(), Error> {
+        delegate!(self.ping())
+    }
+
+    fn calls_made(&self) -> Result<usize, Error> {
+        delegate!(self.calls_made())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cert_fingerprint() {
+        let hex = "5e8a2ba1d1f3c7e1a8b0f4f6c5d9e0b1a2c3d4e5f60718293a4b5c6d7e8f9012";
+        assert_eq!(parse_cert_fingerprint(hex).unwrap(), hex);
+
+        let openssl = hex
+            .to_uppercase()
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| std::str::from_utf8(pair).unwrap())
+            .collect::<Vec<_>>()
+            .join(":");
+        assert_eq!(parse_cert_fingerprint(&openssl).unwrap(), hex);
+
+        assert!(parse_cert_fingerprint(&hex[2..]).is_err());
+        assert!(parse_cert_fingerprint("not hex").is_err());
+    }
+
+    #[test]
+    fn test_header_cache_keeps_buried_headers() {
+        use bdk_wallet::bitcoin::CompactTarget;
+        use bdk_wallet::bitcoin::constants::genesis_block;
+        use bdk_wallet::chain::BlockId;
+
+        // The headers of the mainnet blocks 1 and 2.
+        let block_1 = Vec::<u8>::from_hex(
+            "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744\
+             bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299",
+        )
+        .unwrap();
+        let block_2 = Vec::<u8>::from_hex(
+            "010000004860eb18bf1b1620e37e9490fc8a427514416fd75159ab86688e9a8300000000d5fdcc541e25de1c\
+             7a5addedf24858b8bb665c9f36ef744ee42c316022c90f9bb0bc6649ffff001d08d2bd61",
+        )
+        .unwrap();
+        let block_id = |height, raw: &[u8]| BlockId {
+            height,
+            hash: deserialize::<Header>(raw).unwrap().block_hash(),
+        };
+        let mut header = genesis_block(Network::Bitcoin).header;
+        let genesis = serialize(&header);
+        header.nonce += 1;
+        let invalid = serialize(&header);
+        // A header meeting its own target, easier than the proof of work limit of mainnet.
+        header.bits = CompactTarget::from_consensus(0x207fffff);
+        while header.validate_pow(header.target()).is_err() {
+            header.nonce += 1;
+        }
+        let easy = serialize(&header);
+        // The chain of the wallet, with a made up block at height 3.
+        let chain = CheckPoint::new(block_id(0, &genesis))
+            .push(block_id(1, &block_1))
+            .unwrap()
+            .push(block_id(2, &block_2))
+            .unwrap()
+            .push(block_id(3, &easy))
+            .unwrap();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        assert!(HeaderCache::open(temp_dir.path(), Network::Regtest, chain.clone()).is_none());
+        let cache = HeaderCache::open(temp_dir.path(), Network::Bitcoin, chain.clone()).unwrap();
+        // Nothing is buried before the tip is known.
+        cache.insert([(1, block_1.clone())]);
+        assert!(cache.get(1).is_none());
+
+        cache.set_tip(200);
+        cache.insert([
+            (0, genesis.clone()),
+            (1, block_2.clone()),
+            (2, block_2.clone()),
+            (3, easy),
+            (4, invalid),
+            (5, block_1.clone()),
+            (150, block_1.clone()),
+        ]);
+        assert_eq!(cache.get(2), Some(block_2.clone()));
+        assert!(cache.get(0).is_none());
+        // Block 2 is not the block of the wallet at height 1, nor block 1 at height 5.
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(5).is_none());
+        // Headers easier than the proof of work limit are not cached, even in the chain.
+        assert!(cache.get(3).is_none());
+        assert!(cache.get(4).is_none());
+        assert!(cache.get(150).is_none());
+        // Fetching the header of the wallet replaces the header of another chain.
+        cache.insert([(1, block_1.clone())]);
+        assert_eq!(cache.get(1), Some(block_1.clone()));
+        assert!(!temp_dir.path().join(HEADER_CACHE_FILE).exists());
+        drop(cache);
+
+        let reopened = HeaderCache::open(temp_dir.path(), Network::Bitcoin, chain.clone()).unwrap();
+        assert_eq!(reopened.get(1), Some(block_1.clone()));
+        assert_eq!(reopened.get(2), Some(block_2));
+        // Another wallet, on a chain without block 2, is not answered its header.
+        let without_block_2 = CheckPoint::new(block_id(0, &genesis))
+            .push(block_id(1, &block_1))
+            .unwrap();
+        let other = HeaderCache::open(temp_dir.path(), Network::Bitcoin, without_block_2).unwrap();
+        assert_eq!(other.get(1), Some(block_1));
+        assert!(other.get(2).is_none());
+        let testnet = HeaderCache::open(temp_dir.path(), Network::Testnet, chain).unwrap();
+        assert!(testnet.get(1).is_none());
+    }
+
+    #[test]
+    fn test_batches_are_split_when_rejected() {
+        use bdk_electrum::electrum_client::ConfigBuilder;
+        use bdk_wallet::bitcoin::ScriptBuf;
+        use std::io::{BufRead, BufReader, Write};
+
+        // A server answering batches of at most 2 requests, and the others with an error for each
+        // request, like public servers limiting the cost of a session.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = format!("tcp://{}", listener.local_addr().unwrap());
+        let largest_batch = Arc::new(AtomicUsize::new(0));
+        std::thread::spawn({
+            let largest_batch = largest_batch.clone();
+            move || {
+                let (stream, _) = listener.accept().unwrap();
+                let mut writer = stream.try_clone().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut batch = Vec::new();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 {
+                    batch.push(serde_json::from_str::<serde_json::Value>(&line).unwrap());
+                    line.clear();
+                    // The requests of a batch are written at once, so they are buffered together.
+                    if !reader.buffer().is_empty() {
+                        continue;
+                    }
+                    let accepted = batch.len() <= 2;
+                    if accepted && batch[0]["method"] != "server.version" {
+                        largest_batch.fetch_max(batch.len(), Ordering::Relaxed);
+                    }
+                    for request in batch.drain(..) {
+                        let response = match request["method"].as_str() {
+                            Some("server.version") => {
+                                serde_json::json!({"result": ["fake", "1.4"]})
+                            }
+                            _ if accepted => serde_json::json!({"result": []}),
+                            _ => serde_json::json!({
+                                "error": {"code": -32600, "message": "excessive resource usage"}
+                            }),
+                        };
+                        let mut response = response.as_object().unwrap().clone();
+                        response.insert("jsonrpc".to_string(), "2.0".into());
+                        response.insert("id".to_string(), request["id"].clone());
+                        writeln!(writer, "{}", serde_json::Value::Object(response)).unwrap();
+                    }
+                }
+            }
+        });
+
+        let config = ConfigBuilder::new()
+            .timeout(Some(Duration::from_secs(5)))
+            .build();
+        let client = ElectrumClient::connect(&server, &config, None, 10).unwrap();
+        let scripts: Vec<ScriptBuf> = (0..5u8).map(|i| ScriptBuf::from_bytes(vec![i])).collect();
+        let histories = client
+            .batch_script_get_history(scripts.iter().map(ScriptBuf::as_script))
+            .unwrap();
+
+        assert_eq!(histories.len(), 5);
+        assert_eq!(client.batch_size.load(Ordering::Relaxed), 2);
+        assert_eq!(largest_batch.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_rejects_batch_only_on_rejections() {
+        let response = |message: &str| {
+            Error::Protocol(serde_json::json!({"code": -32600, "message": message}))
+        };
+        assert!(rejects_batch(&response("excessive resource usage")));
+        assert!(rejects_batch(&response("Batch too large")));
+        assert!(rejects_batch(&Error::AllAttemptsErrored(vec![response(
+            "excessive resource usage"
+        )])));
+        assert!(!rejects_batch(&response("daily limit reached")));
+        assert!(!rejects_batch(&response("too many connections")));
+        assert!(!rejects_batch(&Error::IOError(std::io::Error::from(
+            std::io::ErrorKind::ConnectionReset
+        ))));
+        assert!(!rejects_batch(&Error::AllAttemptsErrored(vec![
+            response("excessive resource usage"),
+            Error::IOError(std::io::Error::from(std::io::ErrorKind::ConnectionReset)),
+        ])));
+    }
+
+    #[test]
+    fn test_pinned_certificate_connections_reconnect() {
+        use crate::commands::ElectrumTlsOpts;
+        use bdk_electrum::electrum_client::ConfigBuilder;
+        use rustls::pki_types::PrivateKeyDer;
+        use rustls::{ServerConfig, ServerConnection};
+        use std::io::{BufRead, BufReader, Write};
+
+        // A TLS server with a self-signed certificate, dropping its first connection after the
+        // version negotiation.
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert = CertificateDer::from(certified.cert.der().to_vec());
+        let fingerprint = sha256::Hash::hash(&cert)
+            .to_byte_array()
+            .to_lower_hex_string();
+        let key = PrivateKeyDer::Pkcs8(certified.key_pair.serialize_der().into());
+        let server_config = Arc::new(
+            ServerConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(vec![cert], key)
+                .unwrap(),
+        );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = format!("ssl://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        std::thread::spawn({
+            let connections = connections.clone();
+            move || {
+                for stream in listener.incoming() {
+                    let connection = ServerConnection::new(server_config.clone()).unwrap();
+                    let mut stream = StreamOwned::new(connection, stream.unwrap());
+                    let first = connections.fetch_add(1, Ordering::Relaxed) == 0;
+                    let mut line = String::new();
+                    let mut answered = 0;
+                    while BufReader::new(&mut stream)
+                        .read_line(&mut line)
+                        .unwrap_or(0)
+                        > 0
+                    {
+                        let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+                        line.clear();
+                        let result = match request["method"].as_str() {
+                            Some("server.version") => serde_json::json!(["fake", "1.4"]),
+                            _ => serde_json::Value::Null,
+                        };
+                        let response = serde_json::json!({
+                            "jsonrpc": "2.0",
+                            "id": request["id"],
+                            "result": result,
+                        });
+                        writeln!(stream, "{response}").unwrap();
+                        stream.flush().unwrap();
+                        answered += 1;
+                        if first && answered == 1 {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        let config = ConfigBuilder::new()
+            .timeout(Some(Duration::from_secs(5)))
+            .retry(1)
+            .build();
+        let tls_opts = |fingerprint: &str| ElectrumTlsOpts {
+            require_tls: true,
+            cert_fingerprint: Some(fingerprint.to_string()),
+            ca_file: None,
+        };
+
+        // The ping fails on the dropped connection, and is sent again on a new one.
+        let pinned = tls_config(&tls_opts(&fingerprint)).unwrap().unwrap();
+        let client = ElectrumClient::connect(&server, &config, Some(&pinned), 10).unwrap();
+        client.ping().unwrap();
+        assert_eq!(connections.load(Ordering::Relaxed), 2);
+
+        let wrong = tls_config(&tls_opts(&"ab".repeat(32))).unwrap().unwrap();
+        let Err(e) = ElectrumClient::connect(&server, &config, Some(&wrong), 10) else {
+            panic!("connected to a server with another certificate");
+        };
+        assert!(e.to_string().contains("is not the pinned"), "{e}");
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Esplora rate limiting
+//!
+//! `esplora_client` retries the requests a server throttles (429) or fails (500, 503), sleeping
+//! through its [`Sleeper`] for a backoff that starts at 256ms and doubles on each retry. The
+//! [`BackoffSleeper`] scales that backoff to the `--esplora-backoff` of the client, whose requests
+//! run [`with_backoff`], and tells the user why the sync slows down.
+//!
+//! The sleeper of `esplora_client` has no state of its own, so the backoff of the client is kept
+//! by the task sending its requests, letting the servers run the clients of several wallets at
+//! once.
+
+use bdk_esplora::esplora_client::Sleeper;
+use std::future::Future;
+use std::time::Duration;
+
+/// First backoff of `esplora_client`, in milliseconds.
+pub const DEFAULT_BACKOFF_MILLIS: u64 = 256;
+
+tokio::task_local! {
+    /// First backoff of the client whose requests the task is sending, in milliseconds.
+    static BACKOFF_MILLIS: u64;
+}
+
+/// Sends the `requests` of a client with a first backoff of `millis`.
+pub async fn with_backoff<F: Future>(millis: u64, requests: F) -> F::Output {
+    BACKOFF_MILLIS.scope(millis, requests).await
+}
+
+/// Sleeps for the backoff of `esplora_client`, scaled to the configured first backoff.
+#[derive(Debug, Clone, Copy)]
+pub struct BackoffSleeper;
+
+impl Sleeper for BackoffSleeper {
+    type Sleep = tokio::time::Sleep;
+
+    fn sleep(dur: Duration) -> Self::Sleep {
+        let millis = BACKOFF_MILLIS
+            .try_with(|millis| *millis)
+            .unwrap_or(DEFAULT_BACKOFF_MILLIS);
+        let dur = dur.mul_f64(millis as f64 / DEFAULT_BACKOFF_MILLIS as f64);
+        log::warn!(
+            "Esplora server is rate limiting or unavailable, retrying in {}ms",
+            dur.as_millis()
+        );
+        tokio::time::sleep(dur)
+    }
+}
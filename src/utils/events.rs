@@ -0,0 +1,76 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Event forwarding
+//!
+//! While a gRPC stream runs a command, the progress events of its scan and the wallet events of
+//! its sync are sent to the stream as JSON rather than written on stderr. The sender is kept by
+//! the thread running the command, the server running the commands of several wallets at once.
+
+use serde_json::Value;
+use std::cell::RefCell;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// An event of the command being run.
+#[derive(Debug)]
+#[cfg_attr(
+    not(any(
+        feature = "electrum",
+        feature = "esplora",
+        feature = "rpc",
+        feature = "cbf"
+    )),
+    allow(dead_code)
+)]
+pub(crate) enum Event {
+    /// A progress event of a scan, as written by `--progress json`.
+    Progress(Value),
+    /// A transaction seen, confirmed, replaced or dropped, or a new chain tip.
+    Wallet(Value),
+}
+
+pub(crate) type EventSender = UnboundedSender<Event>;
+
+thread_local! {
+    static SENDER: RefCell<Option<EventSender>> = const { RefCell::new(None) };
+}
+
+/// Sends the events of the commands of this thread from now on to `sender`, or writes them again
+/// when `None`.
+pub(crate) fn forward_events(sender: Option<EventSender>) {
+    SENDER.set(sender);
+}
+
+/// Whether the events are forwarded.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub(crate) fn forwarding() -> bool {
+    SENDER.with_borrow(Option::is_some)
+}
+
+/// Sends `event` when forwarding, handing it back otherwise to be written.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub(crate) fn forward(event: Event) -> Option<Event> {
+    SENDER.with_borrow(|sender| match sender {
+        Some(sender) => {
+            // A stream closed by its client drops the events of the command still running.
+            let _ = sender.send(event);
+            None
+        }
+        None => Some(event),
+    })
+}
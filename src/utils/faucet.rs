@@ -0,0 +1,148 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Faucets
+//!
+//! `wallet faucet` asks the HTTP API of a faucet for coins with a `POST` of
+//! `{"address": .., "sats": ..}`, the request of the Mutinynet faucet, which self-hosted signet
+//! and testnet faucets can mirror. The `txid` of the reply, when the faucet gives one, is the
+//! transaction the wallet waits for.
+
+use crate::commands::WalletOpts;
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::utils::http::http_client;
+use crate::utils::signet::{MUTINYNET_CHALLENGE, challenge_hex};
+use bdk_wallet::bitcoin::{Network, Script, Txid};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// API of the Mutinynet faucet.
+pub const MUTINYNET_FAUCET_URL: &str = "https://faucet.mutinynet.com/api/onchain";
+
+#[derive(Serialize)]
+struct FaucetRequest<'a> {
+    address: &'a str,
+    sats: u64,
+}
+
+/// The faucet of `network`, Mutinynet being the signet of `signet_challenge`.
+pub fn default_faucet_url(
+    network: Network,
+    signet_challenge: Option<&Script>,
+) -> Result<&'static str, Error> {
+    match network {
+        Network::Signet
+            if signet_challenge.is_some_and(|c| challenge_hex(c) == MUTINYNET_CHALLENGE) =>
+        {
+            Ok(MUTINYNET_FAUCET_URL)
+        }
+        Network::Bitcoin => Err(Error::coded(
+            ErrorCode::NetworkMismatch,
+            "There are no faucets on mainnet".to_string(),
+        )),
+        network => Err(Error::coded(
+            ErrorCode::Config,
+            format!("There is no known faucet API for {network}, set --faucet-url"),
+        )),
+    }
+}
+
+/// The txid of the reply of a faucet, the `txid` of a JSON object or a bare txid, if any.
+fn reply_txid(body: &[u8]) -> Result<Option<Txid>, Error> {
+    let txid = match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(serde_json::Value::Object(reply)) => match reply.get("txid") {
+            Some(serde_json::Value::String(txid)) => Some(txid.clone()),
+            _ => None,
+        },
+        Ok(serde_json::Value::String(txid)) => Some(txid),
+        Ok(_) => None,
+        Err(_) => {
+            Some(String::from_utf8_lossy(body).trim().to_string()).filter(|reply| reply.len() == 64)
+        }
+    };
+    txid.map(|txid| Txid::from_str(&txid))
+        .transpose()
+        .map_err(Error::from)
+}
+
+/// Asks the faucet at `url` for `sats` to `address`, authenticated with `token` as a bearer
+/// token, through the proxy of the wallet of `wallet_opts`, returning the txid of its reply.
+pub async fn request_coins(
+    url: &str,
+    address: &str,
+    sats: u64,
+    token: Option<&str>,
+    wallet_opts: &WalletOpts,
+) -> Result<Option<Txid>, Error> {
+    let body = serde_json::to_vec(&FaucetRequest { address, sats })?;
+    let mut request = http_client(Some(wallet_opts))?
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await.map_err(|e| {
+        Error::coded(
+            ErrorCode::Http,
+            format!("Cannot reach the faucet at {url}: {e}"),
+        )
+    })?;
+    let status = response.status();
+    let reply = response.bytes().await?;
+    if !status.is_success() {
+        return Err(Error::coded(
+            ErrorCode::Http,
+            format!(
+                "The faucet at {url} refused the request ({status}): {}",
+                String::from_utf8_lossy(&reply).trim()
+            ),
+        ));
+    }
+    reply_txid(&reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::signet::{default_signet_challenge, parse_signet_challenge};
+
+    const TXID: &str = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+
+    #[test]
+    fn test_default_faucet_url() {
+        let mutinynet = parse_signet_challenge(MUTINYNET_CHALLENGE).unwrap();
+        assert_eq!(
+            default_faucet_url(Network::Signet, Some(mutinynet.as_script())).unwrap(),
+            MUTINYNET_FAUCET_URL
+        );
+        let signet = default_signet_challenge();
+        assert!(default_faucet_url(Network::Signet, Some(signet.as_script())).is_err());
+        assert!(default_faucet_url(Network::Signet, None).is_err());
+        assert!(default_faucet_url(Network::Testnet4, None).is_err());
+        assert!(default_faucet_url(Network::Bitcoin, None).is_err());
+    }
+
+    #[test]
+    fn test_reply_txid() {
+        let txid = Txid::from_str(TXID).unwrap();
+        let json = format!(r#"{{"txid":"{TXID}","address":"tb1q..."}}"#);
+        assert_eq!(reply_txid(json.as_bytes()).unwrap(), Some(txid));
+        assert_eq!(
+            reply_txid(format!("\"{TXID}\"").as_bytes()).unwrap(),
+            Some(txid)
+        );
+        assert_eq!(
+            reply_txid(format!("{TXID}\n").as_bytes()).unwrap(),
+            Some(txid)
+        );
+        assert_eq!(reply_txid(br#"{"status":"queued"}"#).unwrap(), None);
+        assert_eq!(reply_txid(b"Sent!").unwrap(), None);
+        assert!(reply_txid(br#"{"txid":"not a txid"}"#).is_err());
+    }
+}
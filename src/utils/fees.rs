@@ -0,0 +1,127 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Fee estimation
+//!
+//! Besides the estimates of the blockchain backend, fee rates can be taken from the
+//! `/v1/fees/recommended` endpoint of a [mempool.space](https://mempool.space) instance, public
+//! or self-hosted.
+
+use crate::commands::WalletOpts;
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::utils::http::http_client;
+use bdk_wallet::bitcoin::Network;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// Where fee rates are estimated.
+#[derive(Clone, Copy, ValueEnum, Debug, Default, Eq, PartialEq)]
+pub enum FeeProvider {
+    /// The blockchain backend of the wallet.
+    #[default]
+    Backend,
+    /// A mempool.space instance.
+    Mempool,
+}
+
+impl std::fmt::Display for FeeProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variant");
+        f.write_str(value.get_name())
+    }
+}
+
+/// The recommended fee rates of mempool.space, in sat/vB.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct RecommendedFees {
+    fastest_fee: f64,
+    half_hour_fee: f64,
+    hour_fee: f64,
+    economy_fee: f64,
+    minimum_fee: f64,
+}
+
+impl RecommendedFees {
+    /// The fee rate to confirm within `target_blocks`, mempool.space recommending rates for the
+    /// next block, half an hour (3 blocks) and an hour (6 blocks).
+    fn fee_rate(&self, target_blocks: u16) -> f64 {
+        let fee_rate = match target_blocks {
+            0..=1 => self.fastest_fee,
+            2..=3 => self.half_hour_fee,
+            4..=6 => self.hour_fee,
+            _ => self.economy_fee,
+        };
+        fee_rate.max(self.minimum_fee)
+    }
+}
+
+/// The API of the public mempool.space instance for `network`.
+pub fn default_mempool_url(network: Network) -> Result<&'static str, Error> {
+    match network {
+        Network::Bitcoin => Ok("https://mempool.space/api"),
+        Network::Testnet => Ok("https://mempool.space/testnet/api"),
+        Network::Testnet4 => Ok("https://mempool.space/testnet4/api"),
+        Network::Signet => Ok("https://mempool.space/signet/api"),
+        Network::Regtest => Err(Error::coded(
+            ErrorCode::Config,
+            "There is no public mempool.space instance for regtest, set --mempool-url".into(),
+        )),
+    }
+}
+
+/// Queries the fee rate, in sat/vB, to confirm within `target_blocks` from the mempool.space API
+/// at `base_url`, or the public instance for `network` when unset, through the proxy of the
+/// wallet of `wallet_opts`.
+pub async fn mempool_fee_rate(
+    base_url: Option<&str>,
+    network: Network,
+    target_blocks: u16,
+    wallet_opts: &WalletOpts,
+) -> Result<f64, Error> {
+    let base_url = match base_url {
+        Some(url) => url,
+        None => default_mempool_url(network)?,
+    };
+    let url = format!("{}/v1/fees/recommended", base_url.trim_end_matches('/'));
+    let response = http_client(Some(wallet_opts))?
+        .get(&url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| {
+            Error::coded(
+                ErrorCode::Http,
+                format!("Cannot get fee estimates from {url}: {e}"),
+            )
+        })?;
+    let fees: RecommendedFees = serde_json::from_slice(&response.bytes().await?).map_err(|e| {
+        Error::coded(
+            ErrorCode::Http,
+            format!("Invalid fee estimates from {url}: {e}"),
+        )
+    })?;
+    Ok(fees.fee_rate(target_blocks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recommended_fees_by_target() {
+        let fees: RecommendedFees = serde_json::from_str(
+            r#"{"fastestFee":25,"halfHourFee":20,"hourFee":12,"economyFee":3,"minimumFee":4}"#,
+        )
+        .unwrap();
+        assert_eq!(fees.fee_rate(1), 25.0);
+        assert_eq!(fees.fee_rate(3), 20.0);
+        assert_eq!(fees.fee_rate(6), 12.0);
+        assert_eq!(fees.fee_rate(144), 4.0);
+    }
+}
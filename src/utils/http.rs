@@ -0,0 +1,56 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! HTTP requests
+//!
+//! The web services besides the blockchain backend, the fiat prices, the mempool.space fee
+//! estimates and the faucets, are reached with the client of [`http_client`]: its requests time
+//! out, and go through the SOCKS5 proxy of the wallet when it has one, as its blockchain client
+//! does.
+
+use crate::commands::WalletOpts;
+use crate::error::BDKCliError as Error;
+use std::time::Duration;
+
+/// How long a request may take, unless the wallet sets its `--timeout`.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// The HTTP client of the requests made for the wallet of `wallet_opts`, or without a wallet.
+pub(crate) fn http_client(
+    #[cfg_attr(
+        not(any(feature = "electrum", feature = "esplora")),
+        allow(unused_variables)
+    )]
+    wallet_opts: Option<&WalletOpts>,
+) -> Result<reqwest::Client, Error> {
+    #[cfg_attr(not(any(feature = "electrum", feature = "esplora")), allow(unused_mut))]
+    let mut builder = reqwest::Client::builder().timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS));
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    if let Some(proxy_opts) = wallet_opts.map(|opts| &opts.proxy_opts) {
+        if let Some(timeout) = proxy_opts.timeout {
+            builder = builder.timeout(Duration::from_secs(timeout.into()));
+        }
+        if let Some(url) = proxy_url(proxy_opts) {
+            builder = builder.proxy(reqwest::Proxy::all(url)?);
+        }
+    }
+    Ok(builder.build()?)
+}
+
+/// The `socks5h://` url of the proxy of `proxy_opts`, which leaves the name resolution to the
+/// proxy, as onion urls require.
+#[cfg(any(feature = "electrum", feature = "esplora"))]
+pub(crate) fn proxy_url(proxy_opts: &crate::commands::ProxyOpts) -> Option<String> {
+    let proxy = proxy_opts.proxy.as_ref()?;
+    let credentials = proxy_opts
+        .proxy_auth
+        .as_ref()
+        .map(|(user, passwd)| format!("{user}:{passwd}@"))
+        .unwrap_or_default();
+    Some(format!("socks5h://{credentials}{proxy}"))
+}
@@ -0,0 +1,215 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Hardware wallet interface
+//!
+//! Drives the [HWI] command line tool to find connected hardware wallets and have them sign
+//! PSBTs. HWI answers every command with a JSON document on stdout.
+//!
+//! [HWI]: https://github.com/bitcoin-core/HWI
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use bdk_wallet::bitcoin::base64::{Engine, prelude::BASE64_STANDARD};
+use bdk_wallet::bitcoin::bip32::Fingerprint;
+use bdk_wallet::bitcoin::{Network, Psbt};
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::str::FromStr;
+
+/// Default name of the HWI executable.
+pub const DEFAULT_HWI_PATH: &str = "hwi";
+
+/// A hardware wallet as reported by `hwi enumerate`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HwiDevice {
+    #[serde(rename = "type")]
+    pub device_type: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    #[serde(default)]
+    pub needs_pin_sent: bool,
+    #[serde(default)]
+    pub needs_passphrase_sent: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SignTxResponse {
+    psbt: String,
+}
+
+#[derive(Deserialize)]
+struct DisplayAddressResponse {
+    address: String,
+}
+
+/// Runs the HWI executable for a network.
+pub struct Hwi<'a> {
+    path: &'a str,
+    network: Network,
+}
+
+impl<'a> Hwi<'a> {
+    pub fn new(path: &'a str, network: Network) -> Self {
+        Self { path, network }
+    }
+
+    /// List the connected hardware wallets.
+    pub fn enumerate(&self) -> Result<Vec<HwiDevice>, Error> {
+        self.run(&["enumerate"])
+    }
+
+    /// Pick the device to sign with: the one with the given fingerprint, or the only one connected.
+    pub fn find_device(&self, fingerprint: Option<Fingerprint>) -> Result<Fingerprint, Error> {
+        let devices = self.enumerate()?;
+        let mut candidates = Vec::new();
+        for device in &devices {
+            let Some(device_fingerprint) = &device.fingerprint else {
+                continue;
+            };
+            let device_fingerprint = Fingerprint::from_str(device_fingerprint).map_err(|e| {
+                Error::coded(
+                    ErrorCode::HardwareWallet,
+                    format!("HWI returned an invalid fingerprint: {e}"),
+                )
+            })?;
+            if fingerprint.is_none_or(|fingerprint| fingerprint == device_fingerprint) {
+                candidates.push((device_fingerprint, device));
+            }
+        }
+
+        let (device_fingerprint, device) = match (candidates.as_slice(), fingerprint) {
+            ([candidate], _) => *candidate,
+            ([], Some(fingerprint)) => {
+                return Err(Error::coded(
+                    ErrorCode::HardwareWallet,
+                    format!("No hardware wallet with fingerprint {fingerprint} is connected"),
+                ));
+            }
+            ([], None) => {
+                return Err(Error::coded(
+                    ErrorCode::HardwareWallet,
+                    "No unlocked hardware wallet is connected".to_string(),
+                ));
+            }
+            (_, _) => {
+                return Err(Error::coded(
+                    ErrorCode::HardwareWallet,
+                    format!(
+                        "Several hardware wallets are connected, select one with --device: {}",
+                        candidates
+                            .iter()
+                            .map(|(fingerprint, device)| format!(
+                                "{fingerprint} ({})",
+                                device.model
+                            ))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                ));
+            }
+        };
+
+        if let Some(error) = &device.error {
+            return Err(Error::coded(
+                ErrorCode::HardwareWallet,
+                format!("Hardware wallet {device_fingerprint} is not ready: {error}"),
+            ));
+        }
+        if device.needs_pin_sent || device.needs_passphrase_sent {
+            return Err(Error::coded(
+                ErrorCode::HardwareWallet,
+                format!(
+                    "Hardware wallet {device_fingerprint} ({}) must be unlocked first",
+                    device.device_type
+                ),
+            ));
+        }
+        Ok(device_fingerprint)
+    }
+
+    /// Have a device sign the PSBT, returning the PSBT it signed.
+    pub fn sign_tx(&self, fingerprint: Fingerprint, psbt: &Psbt) -> Result<Psbt, Error> {
+        let encoded = BASE64_STANDARD.encode(psbt.serialize());
+        let response: SignTxResponse = self.run(&[
+            "--fingerprint",
+            &fingerprint.to_string(),
+            "signtx",
+            &encoded,
+        ])?;
+        Ok(Psbt::deserialize(&BASE64_STANDARD.decode(response.psbt)?)?)
+    }
+
+    /// Have a device show the address of a descriptor without wildcard, returning the address
+    /// it shows.
+    pub fn display_address(
+        &self,
+        fingerprint: Fingerprint,
+        descriptor: &str,
+    ) -> Result<String, Error> {
+        let response: DisplayAddressResponse = self.run(&[
+            "--fingerprint",
+            &fingerprint.to_string(),
+            "displayaddress",
+            "--desc",
+            descriptor,
+        ])?;
+        Ok(response.address)
+    }
+
+    fn run<T: for<'de> Deserialize<'de>>(&self, args: &[&str]) -> Result<T, Error> {
+        let output = Command::new(self.path)
+            .arg("--chain")
+            .arg(chain(self.network))
+            .args(args)
+            .output()
+            .map_err(|e| {
+                Error::coded(
+                    ErrorCode::HardwareWallet,
+                    format!("Failed to run HWI '{}': {e}", self.path),
+                )
+            })?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if let Ok(serde_json::Value::Object(response)) = serde_json::from_str(&stdout)
+            && let Some(error) = response.get("error")
+        {
+            return Err(Error::coded(
+                ErrorCode::HardwareWallet,
+                format!("HWI error: {error}"),
+            ));
+        }
+        if !output.status.success() {
+            return Err(Error::coded(
+                ErrorCode::HardwareWallet,
+                format!(
+                    "HWI failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ));
+        }
+        serde_json::from_str(&stdout).map_err(|e| {
+            Error::coded(
+                ErrorCode::HardwareWallet,
+                format!("Unexpected HWI output '{stdout}': {e}"),
+            )
+        })
+    }
+}
+
+fn chain(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "main",
+        Network::Regtest => "regtest",
+        Network::Signet => "signet",
+        _ => "test",
+    }
+}
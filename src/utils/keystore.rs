@@ -0,0 +1,330 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Encrypted keystore
+//!
+//! Keeps the private descriptors of a wallet out of the config file. They are encrypted with
+//! ChaCha20-Poly1305 under a key derived from a password with [scrypt], and written to
+//! `<datadir>/<wallet>/keystore.json`. The config only holds the public descriptors, so the
+//! password is needed at signing time only. The password, the derived key and the decrypted
+//! descriptors are wiped from memory once used.
+//!
+//! [scrypt]: https://www.rfc-editor.org/rfc/rfc7914
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::utils::descriptors::split_multipath;
+use bdk_wallet::bitcoin::hex::{DisplayHex, FromHex};
+use bdk_wallet::bitcoin::secp256k1::Secp256k1;
+use bdk_wallet::bitcoin::secp256k1::rand::{RngCore, thread_rng};
+use bdk_wallet::keys::DescriptorPublicKey;
+use bdk_wallet::miniscript::Descriptor;
+use bdk_wallet::miniscript::descriptor::KeyMap;
+use bdk_wallet::{KeychainKind, Wallet};
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Name of the keystore file in the wallet directory.
+pub const KEYSTORE_FILE: &str = "keystore.json";
+
+/// Environment variable holding the keystore password, asked for on the terminal otherwise.
+pub const PASSWORD_ENV: &str = "BDK_CLI_KEY_PASSWORD";
+
+/// scrypt cost of new keystores: N = 2^15, r = 8, p = 1, using 32 MiB of memory.
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+/// Most memory the scrypt parameters of a keystore may take, 128 * r * N bytes: 1 GiB.
+const MAX_SCRYPT_MEMORY: u64 = 1 << 30;
+/// Most parallel passes of a keystore, each taking as long as the whole derivation of p = 1.
+const MAX_SCRYPT_P: u32 = 16;
+
+/// The descriptors of a wallet, as they are given to `wallet config`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletDescriptors {
+    pub ext_descriptor: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub int_descriptor: Option<String>,
+}
+
+/// Parameters of the scrypt key derivation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ScryptParams {
+    log_n: u8,
+    r: u32,
+    p: u32,
+    salt: String,
+}
+
+/// The keystore file, also the envelope of encrypted backup bundles.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Keystore {
+    version: u8,
+    scrypt: ScryptParams,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Path of the keystore of a wallet.
+pub fn keystore_path(datadir: &Path, wallet_name: &str) -> PathBuf {
+    datadir.join(wallet_name).join(KEYSTORE_FILE)
+}
+
+/// Encrypt the private descriptors of a wallet into its keystore. An existing keystore is
+/// never overwritten.
+pub fn save(
+    datadir: &Path,
+    wallet_name: &str,
+    descriptors: &WalletDescriptors,
+    password: &str,
+) -> Result<PathBuf, Error> {
+    let path = keystore_path(datadir, wallet_name);
+    if path.exists() {
+        return Err(keystore_error(format!(
+            "Wallet '{wallet_name}' already has a keystore in {path:?}"
+        )));
+    }
+
+    let keystore = seal(&Zeroizing::new(serde_json::to_vec(descriptors)?), password)?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| keystore_error(format!("Failed to create directory {dir:?}: {e}")))?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options
+        .open(&path)
+        .map_err(|e| keystore_error(format!("Failed to write keystore {path:?}: {e}")))?;
+    serde_json::to_writer_pretty(file, &keystore)?;
+    Ok(path)
+}
+
+/// Decrypt the keystore of a wallet, `None` when the wallet has no keystore.
+pub fn load(
+    datadir: &Path,
+    wallet_name: &str,
+    password: &str,
+) -> Result<Option<WalletDescriptors>, Error> {
+    let path = keystore_path(datadir, wallet_name);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path)
+        .map_err(|e| keystore_error(format!("Failed to read keystore {path:?}: {e}")))?;
+    let keystore: Keystore = serde_json::from_str(&content)
+        .map_err(|e| keystore_error(format!("Failed to parse keystore {path:?}: {e}")))?;
+    let plaintext = unseal(
+        &keystore,
+        password,
+        &format!("the keystore of wallet '{wallet_name}'"),
+    )?;
+    Ok(Some(serde_json::from_slice(&plaintext)?))
+}
+
+/// Encrypt `plaintext` under a password, into the JSON of a keystore file.
+pub fn encrypt(plaintext: &[u8], password: &str) -> Result<Vec<u8>, Error> {
+    Ok(serde_json::to_vec_pretty(&seal(plaintext, password)?)?)
+}
+
+/// Decrypt the JSON of a keystore file written by [`encrypt`]. `what` names the data in the
+/// error of a wrong password.
+pub fn decrypt(data: &[u8], password: &str, what: &str) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let keystore: Keystore = serde_json::from_slice(data)
+        .map_err(|e| keystore_error(format!("Cannot parse {what}: {e}")))?;
+    unseal(&keystore, password, what)
+}
+
+fn seal(plaintext: &[u8], password: &str) -> Result<Keystore, Error> {
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    thread_rng().fill_bytes(&mut salt);
+    thread_rng().fill_bytes(&mut nonce);
+    let scrypt = ScryptParams {
+        log_n: SCRYPT_LOG_N,
+        r: SCRYPT_R,
+        p: SCRYPT_P,
+        salt: salt.to_lower_hex_string(),
+    };
+    let ciphertext = cipher(password, &scrypt)?
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| keystore_error("Cannot encrypt the keystore".to_string()))?;
+    Ok(Keystore {
+        version: 1,
+        scrypt,
+        nonce: nonce.to_lower_hex_string(),
+        ciphertext: ciphertext.to_lower_hex_string(),
+    })
+}
+
+fn unseal(keystore: &Keystore, password: &str, what: &str) -> Result<Zeroizing<Vec<u8>>, Error> {
+    if keystore.version != 1 {
+        return Err(keystore_error(format!(
+            "Unsupported keystore version {}",
+            keystore.version
+        )));
+    }
+    let nonce = <[u8; 12]>::from_hex(&keystore.nonce)?;
+    let ciphertext = Vec::<u8>::from_hex(&keystore.ciphertext)?;
+    cipher(password, &keystore.scrypt)?
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map(Zeroizing::new)
+        .map_err(|_| Error::WrongPassword(what.to_string()))
+}
+
+/// Load the keys of the wallet keystore into the wallet signers. Returns `false` when the wallet
+/// has no keystore, without asking for the password.
+pub fn unlock(wallet: &mut Wallet, datadir: &Path, wallet_name: &str) -> Result<bool, Error> {
+    if !keystore_path(datadir, wallet_name).exists() {
+        return Ok(false);
+    }
+    let password = crate::utils::read_key_password(false)?;
+    let Some(descriptors) = load(datadir, wallet_name, &password)? else {
+        return Ok(false);
+    };
+
+    let WalletDescriptors {
+        ext_descriptor,
+        int_descriptor,
+    } = descriptors;
+    let ext_descriptor = Zeroizing::new(ext_descriptor);
+    let int_descriptor = int_descriptor.map(Zeroizing::new);
+    let (ext_descriptor, int_descriptor) = match split_multipath(&ext_descriptor)? {
+        Some(pair) => (
+            Zeroizing::new(pair.external),
+            Some(Zeroizing::new(pair.internal)),
+        ),
+        None => (ext_descriptor, int_descriptor),
+    };
+    let keychains = [KeychainKind::External, KeychainKind::Internal];
+    let mut keymaps = Vec::new();
+    for (keychain, descriptor) in keychains
+        .into_iter()
+        .zip([Some(ext_descriptor), int_descriptor])
+    {
+        let Some(descriptor) = descriptor else {
+            continue;
+        };
+        let (public, keymap) = parse(&descriptor)?;
+        if wallet.public_descriptor(keychain) != &public {
+            return Err(keystore_error(format!(
+                "The keystore of wallet '{wallet_name}' does not hold the keys of its descriptors"
+            )));
+        }
+        keymaps.push((keychain, keymap));
+    }
+    wallet.set_keymaps(keymaps);
+    Ok(true)
+}
+
+/// The public version of wallet descriptors, `None` when they hold no private key.
+pub fn public_descriptors(
+    descriptors: &WalletDescriptors,
+) -> Result<Option<WalletDescriptors>, Error> {
+    let (ext_descriptor, ext_keys) = parse(&descriptors.ext_descriptor)?;
+    let (int_descriptor, int_keys) = match &descriptors.int_descriptor {
+        Some(descriptor) => {
+            let (descriptor, keys) = parse(descriptor)?;
+            (Some(descriptor.to_string()), keys)
+        }
+        None => (None, KeyMap::new()),
+    };
+    if ext_keys.is_empty() && int_keys.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(WalletDescriptors {
+        ext_descriptor: ext_descriptor.to_string(),
+        int_descriptor,
+    }))
+}
+
+/// The public version of a descriptor and the private keys it holds.
+pub fn parse(descriptor: &str) -> Result<(Descriptor<DescriptorPublicKey>, KeyMap), Error> {
+    Descriptor::parse_descriptor(&Secp256k1::new(), descriptor)
+        .map_err(|e| keystore_error(format!("Invalid descriptor: {e}")))
+}
+
+fn cipher(password: &str, params: &ScryptParams) -> Result<ChaCha20Poly1305, Error> {
+    // Also bounds the memory and time a crafted keystore can make the key derivation use.
+    if params.log_n == 0
+        || params.log_n > 20
+        || params.r == 0
+        || (128 * u64::from(params.r)) << params.log_n > MAX_SCRYPT_MEMORY
+        || params.p > MAX_SCRYPT_P
+    {
+        return Err(keystore_error(
+            "Invalid scrypt parameters in the keystore".to_string(),
+        ));
+    }
+    let scrypt_params = scrypt::Params::new(params.log_n, params.r, params.p, 32)
+        .map_err(|e| keystore_error(format!("Invalid scrypt parameters in the keystore: {e}")))?;
+    let salt = Vec::<u8>::from_hex(&params.salt)?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    scrypt::scrypt(
+        password.as_bytes(),
+        &salt,
+        &scrypt_params,
+        key.as_mut_slice(),
+    )
+    .map_err(|e| keystore_error(format!("Cannot derive the keystore key: {e}")))?;
+    Ok(ChaCha20Poly1305::new_from_slice(key.as_slice()).expect("32 byte key"))
+}
+
+/// A keystore that cannot be written, read or opened, other than for a wrong password.
+fn keystore_error(message: String) -> Error {
+    Error::coded(ErrorCode::Keystore, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_invalid_scrypt_params() {
+        let mut keystore = seal(b"descriptors", "correct horse").unwrap();
+        for (log_n, r, p) in [
+            (0, 8, 1),
+            (21, 8, 1),
+            (15, 0, 1),
+            (15, 8, 0),
+            (20, 9, 1),
+            (15, u32::MAX, 1),
+            (15, 8, 17),
+        ] {
+            keystore.scrypt.log_n = log_n;
+            keystore.scrypt.r = r;
+            keystore.scrypt.p = p;
+            assert!(unseal(&keystore, "correct horse", "keystore").is_err());
+        }
+    }
+
+    #[test]
+    fn test_keystore_roundtrip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let datadir = temp_dir.path();
+        let descriptors = WalletDescriptors {
+            ext_descriptor: "wpkh(tprv8ZgxMBicQKsPd3krDUsBAmtnRsK3rb8u5yi1zhQgMhF1tR8MW7xfE4rnrbbsrbPR52e7rKapu6ztw1jXveJSCGHEriUGZV7mCe88duLp5pj/84'/1'/0'/0/*)".to_string(),
+            int_descriptor: None,
+        };
+
+        save(datadir, "wallet", &descriptors, "correct horse").unwrap();
+        assert!(save(datadir, "wallet", &descriptors, "correct horse").is_err());
+        assert_eq!(
+            load(datadir, "wallet", "correct horse").unwrap(),
+            Some(descriptors)
+        );
+        assert!(load(datadir, "wallet", "battery staple").is_err());
+        assert_eq!(load(datadir, "other", "correct horse").unwrap(), None);
+    }
+}
@@ -0,0 +1,88 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Log output
+//!
+//! The log lines are written to stderr as text, filtered by `RUST_LOG`. Without it, the notices
+//! and warnings of bdk-cli itself are written as plain lines, and the other crates only log their
+//! errors. `--log-format json` writes one JSON object per line instead, for journald or ELK when
+//! bdk-cli runs as a service, and `--log-file` appends the lines to a file so that they do not mix
+//! with the command output.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use clap::ValueEnum;
+use env_logger::{Builder, Env, Target, WriteStyle};
+use log::Level;
+use serde_json::json;
+
+/// The formats of the log lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// The level, target and message, colored in a terminal.
+    #[default]
+    Text,
+    /// A JSON object per line, with the `timestamp`, `level`, `target` and `message`.
+    Json,
+}
+
+/// The filter of the log lines when `RUST_LOG` is not set.
+const DEFAULT_FILTER: &str = "error,bdk_cli=info";
+
+/// Installs the logger writing the lines in `format` to `file`, or to stderr when `None`, without
+/// colors when `no_color`.
+pub fn init_logger(format: LogFormat, file: Option<&Path>, no_color: bool) -> Result<(), Error> {
+    let mut builder = Builder::from_env(Env::default().default_filter_or(DEFAULT_FILTER));
+    if no_color {
+        builder.write_style(WriteStyle::Never);
+    }
+    if let Some(path) = file {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                Error::coded(
+                    ErrorCode::Io,
+                    format!("Cannot open the log file {path:?}: {e}"),
+                )
+            })?;
+        builder
+            .target(Target::Pipe(Box::new(file)))
+            .write_style(WriteStyle::Never);
+    }
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let line = json!({
+                "timestamp": buf.timestamp_millis().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{line}")
+        });
+    } else if std::env::var_os("RUST_LOG").is_none() {
+        builder.format(|buf, record| match record.level() {
+            Level::Info => writeln!(buf, "{}", record.args()),
+            level => {
+                let style = buf.default_level_style(level);
+                let name = match level {
+                    Level::Warn => "warning".to_string(),
+                    level => level.as_str().to_lowercase(),
+                };
+                writeln!(buf, "{style}{name}{style:#}: {}", record.args())
+            }
+        });
+    }
+    builder
+        .try_init()
+        .map_err(|e| Error::Generic(format!("Cannot install the logger: {e}")))
+}
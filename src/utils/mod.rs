@@ -1,6 +1,68 @@
+pub mod amount;
+#[cfg(feature = "server")]
+pub mod auth;
+pub mod bbqr;
+pub mod bip85;
+#[cfg(feature = "rpc")]
+pub mod bitcoind;
+#[cfg(feature = "clipboard")]
+pub mod clipboard;
 pub mod common;
 pub mod descriptors;
+#[cfg(feature = "electrum")]
+pub mod electrum;
+#[cfg(feature = "esplora")]
+pub mod esplora;
+#[cfg(feature = "grpc")]
+pub mod events;
+#[cfg(test)]
+#[path = "../../tests/common/http.rs"]
+pub(crate) mod fake_http;
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub mod faucet;
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub mod fees;
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub mod http;
+pub mod hwi;
+pub mod keystore;
+pub mod logging;
+pub mod network;
 pub mod output;
+#[cfg(feature = "compiler")]
+pub mod policy;
+pub mod price;
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub mod progress;
+pub mod psbt;
+pub mod qr;
 pub use common::*;
 pub mod runtime;
+pub mod signet;
+pub mod slip39;
+pub mod snapshot;
+pub mod stats;
 pub mod types;
+pub mod ur;
+#[cfg(feature = "rpc")]
+pub mod zmq;
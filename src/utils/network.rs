@@ -0,0 +1,142 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Network presets
+//!
+//! `--network` takes the networks of rust-bitcoin, and `mutinynet` for the signet of
+//! [Mutinynet](https://mutinynet.com). The network of the global options, parsed ahead of the
+//! command, sets the defaults of `wallet config`: the public Electrum and Esplora servers of
+//! testnet4, or the challenge and Esplora API of Mutinynet, which `wallet config` still checks
+//! for a server.
+
+use crate::utils::signet::MUTINYNET_CHALLENGE;
+use bdk_wallet::bitcoin::Network;
+use clap::{ArgMatches, Command};
+use std::str::FromStr;
+
+/// The `--network` preset of Mutinynet.
+pub(crate) const MUTINYNET: &str = "mutinynet";
+
+/// The `--network` preset of testnet4.
+const TESTNET4: &str = "testnet4";
+
+/// Esplora API of Mutinynet.
+pub const MUTINYNET_ESPLORA_URL: &str = "https://mutinynet.com/api";
+
+/// Public Electrum server of testnet4.
+pub const TESTNET4_ELECTRUM_URL: &str = "ssl://mempool.space:40002";
+
+/// Public Esplora API of testnet4.
+pub const TESTNET4_ESPLORA_URL: &str = "https://mempool.space/testnet4/api";
+
+/// Parse the `--network` argument, `mutinynet` being on signet.
+pub(crate) fn parse_network(s: &str) -> Result<Network, String> {
+    match s {
+        MUTINYNET => Ok(Network::Signet),
+        s => Network::from_str(s).map_err(|e| e.to_string()),
+    }
+}
+
+/// The preset of the `--network` of the global options `globals`, `mutinynet` or `testnet4`,
+/// whose defaults are set on the command before clap parses it.
+pub fn network_preset(globals: &ArgMatches) -> Option<&'static str> {
+    let network = globals.try_get_raw("network").ok()??.next()?.to_str()?;
+    [MUTINYNET, TESTNET4]
+        .into_iter()
+        .find(|preset| *preset == network)
+}
+
+/// The public server of the `client_type` backend on `network`, if bdk-cli knows one.
+pub fn default_server_url(network: Network, client_type: &str) -> Option<&'static str> {
+    match (network, client_type) {
+        (Network::Testnet4, "electrum") => Some(TESTNET4_ELECTRUM_URL),
+        (Network::Testnet4, "esplora") => Some(TESTNET4_ESPLORA_URL),
+        _ => None,
+    }
+}
+
+/// `command` with the defaults of `preset` for `wallet config`, and for `init` on Mutinynet.
+pub fn with_network_defaults(command: Command, preset: &str) -> Command {
+    match preset {
+        MUTINYNET => command
+            .mut_subcommand("wallet", |wallet| {
+                wallet.mut_subcommand("config", mutinynet_defaults)
+            })
+            .mut_subcommand("init", mutinynet_defaults),
+        TESTNET4 => command.mut_subcommand("wallet", |wallet| {
+            wallet.mut_subcommand("config", testnet4_defaults)
+        }),
+        _ => command,
+    }
+}
+
+/// The challenge and, with the `esplora` feature, the Esplora API of Mutinynet.
+fn mutinynet_defaults(command: Command) -> Command {
+    let command = command.mut_arg("signet_challenge", |arg| {
+        arg.default_value(MUTINYNET_CHALLENGE)
+    });
+    #[cfg(feature = "esplora")]
+    let command = command
+        .mut_arg("client_type", |arg| {
+            arg.required(false).default_value("esplora")
+        })
+        .mut_arg("url", |arg| arg.default_value(MUTINYNET_ESPLORA_URL));
+    command
+}
+
+/// The public server of the electrum or esplora backend.
+fn testnet4_defaults(command: Command) -> Command {
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    let command = command.mut_arg("url", |arg| {
+        arg.default_value_ifs([
+            ("client_type", "electrum", Some(TESTNET4_ELECTRUM_URL)),
+            ("client_type", "esplora", Some(TESTNET4_ESPLORA_URL)),
+        ])
+    });
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::CliOpts;
+
+    #[test]
+    fn test_network_preset() {
+        let preset = |args: &[&str]| network_preset(&CliOpts::globals(args));
+        assert_eq!(
+            preset(&["bdk-cli", "--network", "mutinynet", "init"]),
+            Some(MUTINYNET)
+        );
+        assert_eq!(preset(&["bdk-cli", "-n=testnet4", "init"]), Some(TESTNET4));
+        assert_eq!(preset(&["bdk-cli", "--network=signet", "init"]), None);
+        // Only the `--network` option sets the preset, as clap reads it.
+        assert_eq!(
+            preset(&["bdk-cli", "--networkfoo", "mutinynet", "init"]),
+            None
+        );
+
+        assert_eq!(parse_network("mutinynet"), Ok(Network::Signet));
+        assert_eq!(parse_network("testnet4"), Ok(Network::Testnet4));
+        assert!(parse_network("mainnet4").is_err());
+    }
+
+    #[test]
+    fn test_default_server_url() {
+        assert_eq!(
+            default_server_url(Network::Testnet4, "esplora"),
+            Some(TESTNET4_ESPLORA_URL)
+        );
+        assert_eq!(
+            default_server_url(Network::Testnet4, "electrum"),
+            Some(TESTNET4_ELECTRUM_URL)
+        );
+        assert_eq!(default_server_url(Network::Testnet4, "rpc"), None);
+        assert_eq!(default_server_url(Network::Testnet, "esplora"), None);
+    }
+}
@@ -1,20 +1,184 @@
-use std::io::Write;
+use std::cell::RefCell;
+use std::io::{IsTerminal, Write};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::error::BDKCliError as Error;
+use crate::error::{BDKCliError as Error, ErrorCode};
+use clap::ValueEnum;
+use cli_table::format::Justify;
+use cli_table::{Cell, CellStruct, Color, ColorChoice, Style, Table};
 use serde::Serialize;
+use serde_json::Value;
+
+/// URL template of the block explorer of `--links`.
+static EXPLORER: OnceLock<String> = OnceLock::new();
+
+/// Format of `--output`.
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// The formats of the command outputs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Pretty printed JSON.
+    #[default]
+    Json,
+    /// Aligned columns, to read in a terminal.
+    Table,
+    /// Comma separated values, one line per item of the lists such as UTXOs and transactions.
+    Csv,
+}
+
+/// Writes the outputs in `format` rather than JSON.
+pub fn set_output_format(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+}
+
+/// Set by `--no-color`.
+static NO_COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Writes the tables and warnings without colors.
+pub fn disable_colors() {
+    NO_COLOR.store(true, Ordering::Relaxed);
+}
+
+/// Whether what is written to `stream` is colored: only in a terminal, and neither with
+/// `--no-color` nor with a non-empty `NO_COLOR` (<https://no-color.org>).
+pub(crate) fn colors_enabled(stream: &impl IsTerminal) -> bool {
+    !NO_COLOR.load(Ordering::Relaxed)
+        && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        && stream.is_terminal()
+}
+
+thread_local! {
+    /// Outputs of the commands of `run` or of a server request, collected while they run rather
+    /// than written. Kept per thread, so that the servers running the commands of several wallets
+    /// at once each get the outputs of their own command.
+    static CAPTURED: RefCell<Option<Vec<Value>>> = const { RefCell::new(None) };
+}
+
+/// Collects the outputs written from now on by this thread, until [`take_captured`].
+pub fn start_capture() {
+    CAPTURED.set(Some(Vec::new()));
+}
+
+/// Stops collecting the outputs of this thread, and returns the ones collected.
+pub fn take_captured() -> Vec<Value> {
+    CAPTURED.take().unwrap_or_default()
+}
+
+/// Links the txids and addresses of the outputs to the block explorer of the URL `template`, in
+/// which `{kind}` becomes `tx` or `address` and `{id}` the txid or the address.
+pub fn set_explorer(template: &str) {
+    let _ = EXPLORER.set(template.to_string());
+}
+
+/// Adds a `txid_link` next to every `txid` of `value`, and an `address_link` next to every
+/// `address`.
+fn add_links(value: &mut Value, explorer: &str) {
+    match value {
+        Value::Object(map) => {
+            let mut links = Vec::new();
+            for (key, value) in map.iter_mut() {
+                add_links(value, explorer);
+                let kind = match key.as_str() {
+                    "txid" => "tx",
+                    "address" => "address",
+                    _ => continue,
+                };
+                if let Value::String(id) = value {
+                    let link = explorer.replace("{kind}", kind).replace("{id}", id);
+                    links.push((format!("{key}_link"), Value::String(link)));
+                }
+            }
+            map.extend(links);
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| add_links(value, explorer)),
+        _ => {}
+    }
+}
+
+/// Collects `output` as JSON while `run` captures the outputs, returning whether it did.
+fn capture<T: Serialize + ?Sized>(output: &T) -> Result<bool, Error> {
+    if CAPTURED.with_borrow(Option::is_none) {
+        return Ok(false);
+    }
+    let mut value = serde_json::to_value(output)
+        .map_err(|e| Error::Generic(format!("JSON serialization failed: {e}")))?;
+    if let Some(explorer) = EXPLORER.get() {
+        add_links(&mut value, explorer);
+    }
+    CAPTURED.with_borrow_mut(|captured| captured.get_or_insert_default().push(value));
+    Ok(true)
+}
 
 /// A trait for types that can be presented to the user.
 pub trait FormatOutput: Serialize {
     fn format(&self) -> Result<String, Error> {
-        serde_json::to_string_pretty(self)
-            .map_err(|e| Error::Generic(format!("JSON serialization failed: {e}")))
+        self.format_columns(FORMAT.get().copied().unwrap_or_default(), &[])
+    }
+
+    /// The output in `format`, the tables and CSV keeping only `columns` when not empty.
+    fn format_columns(&self, format: OutputFormat, columns: &[String]) -> Result<String, Error> {
+        let json_error = |e| Error::Generic(format!("JSON serialization failed: {e}"));
+        if format == OutputFormat::Json && EXPLORER.get().is_none() {
+            return serde_json::to_string_pretty(self).map_err(json_error);
+        }
+        let mut value = serde_json::to_value(self).map_err(json_error)?;
+        if let Some(explorer) = EXPLORER.get() {
+            add_links(&mut value, explorer);
+        }
+        match format {
+            OutputFormat::Json => serde_json::to_string_pretty(&value).map_err(json_error),
+            OutputFormat::Table => table(&value, columns),
+            OutputFormat::Csv => csv(&value, columns),
+        }
     }
 
     fn write_out<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        if capture(self)? {
+            return Ok(());
+        }
+        #[cfg(feature = "clipboard")]
+        crate::utils::clipboard::copy_output(self)?;
         let output = self.format()?;
 
         writeln!(writer, "{}", output)
-            .map_err(|e| Error::Generic(format!("Failed to write output: {e}")))
+            .map_err(|e| Error::coded(ErrorCode::Io, format!("Failed to write output: {e}")))
+    }
+
+    /// Like [`FormatOutput::write_out`], but writes a table when no `--output` is given and
+    /// stdout is a terminal. Tables show `columns`, or `default_columns` when it is empty, and
+    /// CSV shows `columns`, or every column.
+    fn write_table_out<W: Write>(
+        &self,
+        mut writer: W,
+        columns: &[String],
+        default_columns: &[&str],
+    ) -> Result<(), Error> {
+        if capture(self)? {
+            return Ok(());
+        }
+        #[cfg(feature = "clipboard")]
+        crate::utils::clipboard::copy_output(self)?;
+        let format = match FORMAT.get() {
+            Some(format) => *format,
+            None if std::io::stdout().is_terminal() => OutputFormat::Table,
+            None => OutputFormat::Json,
+        };
+        let columns = if columns.is_empty() && format == OutputFormat::Table {
+            default_columns
+                .iter()
+                .map(|column| column.to_string())
+                .collect()
+        } else {
+            columns.to_vec()
+        };
+        let output = self.format_columns(format, &columns)?;
+
+        writeln!(writer, "{}", output)
+            .map_err(|e| Error::coded(ErrorCode::Io, format!("Failed to write output: {e}")))
     }
 }
 
@@ -22,6 +186,7 @@ impl<T: Serialize> FormatOutput for T {}
 
 /// A generic wrapper for commands that return a list of items.
 #[derive(Serialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct ListResult<T> {
     pub count: usize,
     pub items: Vec<T>,
@@ -35,3 +200,269 @@ impl<T> ListResult<T> {
         }
     }
 }
+
+/// The items of a [`ListResult`] or of an array, the value itself otherwise.
+fn rows(value: &Value) -> Option<&Vec<Value>> {
+    match value {
+        Value::Array(values) => Some(values),
+        Value::Object(map) if map.contains_key("count") => match map.get("items") {
+            Some(Value::Array(items)) => Some(items),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// `value` with the fields of its nested objects moved up as `parent.child` fields, such as the
+/// `txout.value` of a UTXO.
+fn flatten(value: &Value) -> Value {
+    let Value::Object(map) = value else {
+        return value.clone();
+    };
+    let mut flat = serde_json::Map::new();
+    for (key, value) in map {
+        match flatten(value) {
+            Value::Object(nested) if !nested.is_empty() => {
+                for (nested_key, nested_value) in nested {
+                    flat.insert(format!("{key}.{nested_key}"), nested_value);
+                }
+            }
+            other => {
+                flat.insert(key.clone(), other);
+            }
+        }
+    }
+    Value::Object(flat)
+}
+
+/// The text of a cell, nested values being written as JSON.
+fn cell_text(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(text)) => text.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// `selected` in their order, or `available` when `selected` is empty. Fails for the selected
+/// columns no row has.
+fn select_columns(available: Vec<String>, selected: &[String]) -> Result<Vec<String>, Error> {
+    if selected.is_empty() {
+        return Ok(available);
+    }
+    if let Some(unknown) = selected.iter().find(|column| !available.contains(column)) {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!(
+                "Unknown column '{unknown}', available columns: {}",
+                available.join(", ")
+            ),
+        ));
+    }
+    Ok(selected.to_vec())
+}
+
+/// The `selected` columns of `rows`, every key of the objects in order of appearance by default,
+/// and their cells.
+fn columns_and_cells(
+    rows: &[Value],
+    selected: &[String],
+) -> Result<(Vec<String>, Vec<Vec<Value>>), Error> {
+    // An empty list has no columns to check the selected ones against.
+    if rows.is_empty() && !selected.is_empty() {
+        return Ok((selected.to_vec(), Vec::new()));
+    }
+    let rows: Vec<Value> = rows.iter().map(flatten).collect();
+    let mut columns: Vec<String> = Vec::new();
+    for key in rows
+        .iter()
+        .filter_map(Value::as_object)
+        .flat_map(|map| map.keys())
+    {
+        if !columns.contains(key) {
+            columns.push(key.clone());
+        }
+    }
+    if columns.is_empty() {
+        let cells = rows.into_iter().map(|row| vec![row]).collect();
+        return Ok((vec!["value".to_string()], cells));
+    }
+    let columns = select_columns(columns, selected)?;
+    let cells = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|key| row.get(key).cloned().unwrap_or(Value::Null))
+                .collect()
+        })
+        .collect();
+    Ok((columns, cells))
+}
+
+/// The columns of signed amounts, such as the `net` amount of a transaction, green when they
+/// come into the wallet and red when they leave it.
+const SIGNED_COLUMNS: &[&str] = &["net", "fiat.value"];
+
+/// The flags that deserve attention when set, such as the UTXOs costing more to spend than
+/// they are worth.
+const WARNING_COLUMNS: &[&str] = &["is_dust"];
+
+/// Whether the flattened `row` is an unconfirmed transaction or UTXO.
+fn is_pending(row: &Value) -> bool {
+    let Value::Object(map) = row else {
+        return false;
+    };
+    map.get("confirmed") == Some(&Value::Bool(false))
+        || map.get("confirmations").and_then(Value::as_u64) == Some(0)
+        || map
+            .keys()
+            .any(|key| key.starts_with("chain_position.Unconfirmed"))
+}
+
+/// `cell` styled for the `value` of `column`, and dimmed when `pending`.
+fn style_cell(cell: CellStruct, column: &str, value: &Value, pending: bool) -> CellStruct {
+    let sign = match value {
+        Value::Number(number) => number.as_f64(),
+        // Bitcoin amounts of `--unit btc` are strings.
+        Value::String(text) => text.parse::<f64>().ok(),
+        _ => None,
+    };
+    let color = match (column, value) {
+        (column, _) if SIGNED_COLUMNS.contains(&column) => match sign {
+            Some(sign) if sign > 0.0 => Some(Color::Green),
+            Some(sign) if sign < 0.0 => Some(Color::Red),
+            _ => None,
+        },
+        (column, Value::Bool(true)) if WARNING_COLUMNS.contains(&column) => Some(Color::Yellow),
+        _ => None,
+    };
+    cell.foreground_color(color).dimmed(pending)
+}
+
+/// A table of the `columns` of the items of a list, or of the fields of any other output. In a
+/// terminal, the cells are styled by their meaning, see [`style_cell`].
+fn table(value: &Value, columns: &[String]) -> Result<String, Error> {
+    // Every cell with its column, and whether its row is pending.
+    let (titles, rows): (_, Vec<(Vec<(String, Value)>, bool)>) = match (rows(value), flatten(value))
+    {
+        (Some(rows), _) => {
+            let (titles, cells) = columns_and_cells(rows, columns)?;
+            let rows = cells
+                .into_iter()
+                .zip(rows)
+                .map(|(cells, row)| {
+                    let cells = titles.iter().cloned().zip(cells).collect();
+                    (cells, is_pending(&flatten(row)))
+                })
+                .collect();
+            (titles, rows)
+        }
+        (None, Value::Object(map)) => {
+            let fields = select_columns(map.keys().cloned().collect(), columns)?;
+            let rows = fields
+                .into_iter()
+                .map(|field| {
+                    // The pending amounts of a balance are dimmed like the pending transactions.
+                    let pending = field.contains("pending") || field == "immature";
+                    let value = map[&field].clone();
+                    let cells = vec![
+                        ("field".to_string(), Value::String(field.clone())),
+                        (field, value),
+                    ];
+                    (cells, pending)
+                })
+                .collect();
+            (vec!["field".to_string(), "value".to_string()], rows)
+        }
+        (None, other) => (
+            vec!["value".to_string()],
+            vec![(vec![("value".to_string(), other)], false)],
+        ),
+    };
+    let rows: Vec<Vec<CellStruct>> = rows
+        .into_iter()
+        .map(|(cells, pending)| {
+            cells
+                .into_iter()
+                .map(|(column, value)| {
+                    let cell = cell_text(Some(&value)).cell();
+                    // Amounts and other numbers line up on their last digit.
+                    let cell = match value {
+                        Value::Number(_) => cell.justify(Justify::Right),
+                        _ => cell,
+                    };
+                    style_cell(cell, &column, &value, pending)
+                })
+                .collect()
+        })
+        .collect();
+    let color_choice = match colors_enabled(&std::io::stdout()) {
+        true => ColorChoice::Always,
+        false => ColorChoice::Never,
+    };
+    let table = rows
+        .table()
+        .title(titles.into_iter().map(|column| column.cell().bold(true)))
+        .color_choice(color_choice)
+        .display()
+        .map_err(|e| Error::Generic(e.to_string()))?;
+    Ok(format!("{table}"))
+}
+
+/// The `columns` of the items of a list, or of any other output, as CSV with a header line.
+fn csv(value: &Value, columns: &[String]) -> Result<String, Error> {
+    let (titles, cells) = match rows(value) {
+        Some(rows) => columns_and_cells(rows, columns)?,
+        None => columns_and_cells(std::slice::from_ref(value), columns)?,
+    };
+    let cells = cells
+        .into_iter()
+        .map(|row| row.iter().map(|value| cell_text(Some(value))).collect());
+    Ok(std::iter::once(titles)
+        .chain(cells)
+        .map(|row: Vec<String>| {
+            row.iter()
+                .map(|text| csv_field(text))
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// `text` quoted when it holds a separator, a quote or a line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_capture_is_per_thread() {
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    start_capture();
+                    for _ in 0..100 {
+                        json!({ "thread": i }).write_out(std::io::sink()).unwrap();
+                    }
+                    take_captured()
+                })
+            })
+            .collect();
+        for (i, thread) in threads.into_iter().enumerate() {
+            let outputs = thread.join().unwrap();
+            assert_eq!(outputs.len(), 100);
+            assert!(outputs.iter().all(|output| output["thread"] == i));
+        }
+        assert!(take_captured().is_empty());
+    }
+}
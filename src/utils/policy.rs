@@ -0,0 +1,365 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Spending policy analysis
+//!
+//! Lists the ways a policy can be satisfied and what each one costs once the policy is compiled
+//! into a descriptor. Policies name their keys freely, so each key that is not a valid descriptor
+//! key is replaced by a placeholder key of the same size before planning the satisfactions.
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::utils::types::SpendingPathAnalysis;
+use bdk_wallet::bitcoin::hashes::{Hash, hash160, ripemd160, sha256};
+use bdk_wallet::bitcoin::secp256k1::{Secp256k1, SecretKey};
+use bdk_wallet::bitcoin::{PublicKey, VarInt, absolute, relative};
+use bdk_wallet::miniscript::descriptor::{
+    DefiniteDescriptorKey, DescriptorPublicKey, DescriptorType, SinglePub, SinglePubKey,
+};
+use bdk_wallet::miniscript::plan::Assets;
+use bdk_wallet::miniscript::policy::Concrete;
+use bdk_wallet::miniscript::{
+    AbsLockTime, Descriptor, RelLockTime, TranslatePk, Translator, hash256,
+};
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Maximum number of spending paths a policy may expand to.
+const MAX_PATHS: usize = 1000;
+
+/// Weight of an input without its satisfaction: outpoint and sequence.
+const TXIN_BASE_WEIGHT: usize = (32 + 4 + 4) * 4;
+
+/// A hash lock of a spending path.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Preimage {
+    Sha256(String),
+    Hash256(String),
+    Ripemd160(String),
+    Hash160(String),
+}
+
+/// One way to satisfy a policy: every key signs, every preimage is revealed and the timelocks
+/// have expired.
+#[derive(Debug, Clone, Default)]
+struct SpendingPath {
+    keys: BTreeSet<String>,
+    preimages: BTreeSet<Preimage>,
+    after: Option<AbsLockTime>,
+    older: Option<RelLockTime>,
+}
+
+impl SpendingPath {
+    /// The path satisfying both paths, if their timelocks can be satisfied together.
+    fn merge(&self, other: &Self) -> Option<Self> {
+        let after = match (self.after, other.after) {
+            (Some(a), Some(b)) => {
+                let (lock_a, lock_b) = (absolute::LockTime::from(a), absolute::LockTime::from(b));
+                if !lock_a.is_same_unit(lock_b) {
+                    return None;
+                }
+                Some(a.max(b))
+            }
+            (a, b) => a.or(b),
+        };
+        let older = match (self.older, other.older) {
+            (Some(a), Some(b)) => {
+                let (lock_a, lock_b) = (relative::LockTime::from(a), relative::LockTime::from(b));
+                if !lock_a.is_same_unit(lock_b) {
+                    return None;
+                }
+                Some(a.max(b))
+            }
+            (a, b) => a.or(b),
+        };
+        Some(Self {
+            keys: self.keys.union(&other.keys).cloned().collect(),
+            preimages: self.preimages.union(&other.preimages).cloned().collect(),
+            after,
+            older,
+        })
+    }
+}
+
+/// Report the signers, timelocks, satisfaction weight and fee of each spending path of a policy,
+/// as compiled into `descriptor`.
+pub fn analyze(
+    policy: &Concrete<String>,
+    descriptor: &Descriptor<String>,
+    fee_rate: f32,
+) -> Result<Vec<SpendingPathAnalysis>, Error> {
+    let mut keys = PlaceholderKeys::default();
+    let descriptor = descriptor.translate_pk(&mut keys).map_err(|e| match e {
+        bdk_wallet::miniscript::TranslateErr::TranslatorErr(e) => e,
+        bdk_wallet::miniscript::TranslateErr::OuterError(e) => Error::Generic(e.to_string()),
+    })?;
+
+    spending_paths(policy)?
+        .into_iter()
+        .map(|path| {
+            let mut assets = Assets::new();
+            for key in &path.keys {
+                assets = assets.add(keys.pk(key)?.as_descriptor_public_key().clone());
+            }
+            for preimage in &path.preimages {
+                assets = match preimage {
+                    Preimage::Sha256(hash) => assets.add(keys.sha256(hash)?),
+                    Preimage::Hash256(hash) => assets.add(keys.hash256(hash)?),
+                    Preimage::Ripemd160(hash) => assets.add(keys.ripemd160(hash)?),
+                    Preimage::Hash160(hash) => assets.add(keys.hash160(hash)?),
+                };
+            }
+            if let Some(after) = path.after {
+                assets = assets.after(after.into());
+            }
+            if let Some(older) = path.older {
+                assets = assets.older(older.into());
+            }
+
+            let plan = descriptor.clone().plan(&assets).map_err(|_| {
+                Error::coded(
+                    ErrorCode::InvalidDescriptor,
+                    format!(
+                        "Cannot plan the spending path signed by {}",
+                        path.keys.iter().cloned().collect::<Vec<_>>().join(", ")
+                    ),
+                )
+            })?;
+            let satisfaction_weight = plan.satisfaction_weight() + script_weight(&descriptor);
+            let input_vbytes = (TXIN_BASE_WEIGHT + satisfaction_weight).div_ceil(4) as u64;
+
+            Ok(SpendingPathAnalysis {
+                signers: path.keys.into_iter().collect(),
+                preimages: path
+                    .preimages
+                    .iter()
+                    .map(|preimage| match preimage {
+                        Preimage::Sha256(hash) => format!("sha256({hash})"),
+                        Preimage::Hash256(hash) => format!("hash256({hash})"),
+                        Preimage::Ripemd160(hash) => format!("ripemd160({hash})"),
+                        Preimage::Hash160(hash) => format!("hash160({hash})"),
+                    })
+                    .collect(),
+                after: path.after.map(|after| after.to_consensus_u32()),
+                older: path.older.map(|older| older.to_consensus_u32()),
+                satisfaction_weight,
+                input_vbytes,
+                fee: (input_vbytes as f64 * fee_rate as f64).ceil() as u64,
+            })
+        })
+        .collect()
+}
+
+/// Weight of the script pushed after the satisfaction of sh and wsh descriptors, which plans
+/// leave out.
+fn script_weight(descriptor: &Descriptor<DefiniteDescriptorKey>) -> usize {
+    let script_len = || {
+        descriptor
+            .explicit_script()
+            .map(|script| script.len())
+            .unwrap_or_default()
+    };
+    match descriptor.desc_type() {
+        DescriptorType::Wsh
+        | DescriptorType::WshSortedMulti
+        | DescriptorType::ShWsh
+        | DescriptorType::ShWshSortedMulti => {
+            let len = script_len();
+            VarInt(len as u64).size() + len
+        }
+        DescriptorType::Sh | DescriptorType::ShSortedMulti => {
+            let len = script_len();
+            let push_opcode_len = match len {
+                0..0x4c => 1,
+                0x4c..=0xff => 2,
+                _ => 3,
+            };
+            (push_opcode_len + len) * 4
+        }
+        _ => 0,
+    }
+}
+
+/// Expand a policy into the distinct ways to satisfy it.
+fn spending_paths(policy: &Concrete<String>) -> Result<Vec<SpendingPath>, Error> {
+    let paths = match policy {
+        Concrete::Unsatisfiable => Vec::new(),
+        Concrete::Trivial => vec![SpendingPath::default()],
+        Concrete::Key(key) => vec![SpendingPath {
+            keys: BTreeSet::from([key.clone()]),
+            ..Default::default()
+        }],
+        Concrete::After(after) => vec![SpendingPath {
+            after: Some(*after),
+            ..Default::default()
+        }],
+        Concrete::Older(older) => vec![SpendingPath {
+            older: Some(*older),
+            ..Default::default()
+        }],
+        Concrete::Sha256(hash) => preimage(Preimage::Sha256(hash.clone())),
+        Concrete::Hash256(hash) => preimage(Preimage::Hash256(hash.clone())),
+        Concrete::Ripemd160(hash) => preimage(Preimage::Ripemd160(hash.clone())),
+        Concrete::Hash160(hash) => preimage(Preimage::Hash160(hash.clone())),
+        Concrete::And(subs) => all_of(subs)?,
+        Concrete::Or(subs) => {
+            let mut paths = Vec::new();
+            for (_, sub) in subs {
+                paths.extend(spending_paths(sub)?);
+            }
+            paths
+        }
+        Concrete::Thresh(thresh) => {
+            let mut paths = Vec::new();
+            for subs in combinations(thresh.data(), thresh.k()) {
+                paths.extend(all_of(&subs)?);
+                check_path_count(paths.len())?;
+            }
+            paths
+        }
+    };
+    check_path_count(paths.len())?;
+    Ok(paths)
+}
+
+fn preimage(preimage: Preimage) -> Vec<SpendingPath> {
+    vec![SpendingPath {
+        preimages: BTreeSet::from([preimage]),
+        ..Default::default()
+    }]
+}
+
+/// The paths satisfying every sub-policy.
+fn all_of(subs: &[Arc<Concrete<String>>]) -> Result<Vec<SpendingPath>, Error> {
+    let mut paths = vec![SpendingPath::default()];
+    for sub in subs {
+        let sub_paths = spending_paths(sub)?;
+        paths = paths
+            .iter()
+            .flat_map(|path| sub_paths.iter().filter_map(|sub_path| path.merge(sub_path)))
+            .collect();
+        check_path_count(paths.len())?;
+    }
+    Ok(paths)
+}
+
+/// Every choice of `k` items, in order.
+fn combinations<T: Clone>(items: &[T], k: usize) -> Vec<Vec<T>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+    if items.len() < k {
+        return Vec::new();
+    }
+    let mut with_first = combinations(&items[1..], k - 1);
+    for combination in &mut with_first {
+        combination.insert(0, items[0].clone());
+    }
+    with_first.extend(combinations(&items[1..], k));
+    with_first
+}
+
+fn check_path_count(count: usize) -> Result<(), Error> {
+    if count > MAX_PATHS {
+        return Err(Error::coded(
+            ErrorCode::InvalidDescriptor,
+            format!("The policy has more than {MAX_PATHS} spending paths, too many to analyze"),
+        ));
+    }
+    Ok(())
+}
+
+/// Replaces policy key names with keys of the same size, and hash names with hashes.
+#[derive(Default)]
+struct PlaceholderKeys {
+    keys: HashMap<String, DefiniteDescriptorKey>,
+}
+
+impl Translator<String, DefiniteDescriptorKey, Error> for PlaceholderKeys {
+    fn pk(&mut self, pk: &String) -> Result<DefiniteDescriptorKey, Error> {
+        if let Some(key) = self.keys.get(pk) {
+            return Ok(key.clone());
+        }
+        let key = match DescriptorPublicKey::from_str(pk)
+            .ok()
+            .and_then(|key| key.at_derivation_index(0).ok())
+        {
+            Some(key) => key,
+            None => {
+                let secret = SecretKey::from_slice(sha256::Hash::hash(pk.as_bytes()).as_ref())
+                    .map_err(|e| Error::Generic(e.to_string()))?;
+                let key = PublicKey::new(secret.public_key(&Secp256k1::signing_only()));
+                DescriptorPublicKey::Single(SinglePub {
+                    origin: None,
+                    key: SinglePubKey::FullKey(key),
+                })
+                .at_derivation_index(0)
+                .map_err(|e| Error::Generic(e.to_string()))?
+            }
+        };
+        self.keys.insert(pk.clone(), key.clone());
+        Ok(key)
+    }
+
+    fn sha256(&mut self, hash: &String) -> Result<sha256::Hash, Error> {
+        Ok(sha256::Hash::from_str(hash).unwrap_or_else(|_| sha256::Hash::hash(hash.as_bytes())))
+    }
+
+    fn hash256(&mut self, hash: &String) -> Result<hash256::Hash, Error> {
+        Ok(hash256::Hash::from_str(hash).unwrap_or_else(|_| hash256::Hash::hash(hash.as_bytes())))
+    }
+
+    fn ripemd160(&mut self, hash: &String) -> Result<ripemd160::Hash, Error> {
+        Ok(ripemd160::Hash::from_str(hash)
+            .unwrap_or_else(|_| ripemd160::Hash::hash(hash.as_bytes())))
+    }
+
+    fn hash160(&mut self, hash: &String) -> Result<hash160::Hash, Error> {
+        Ok(hash160::Hash::from_str(hash).unwrap_or_else(|_| hash160::Hash::hash(hash.as_bytes())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(policy: &str) -> Vec<SpendingPath> {
+        spending_paths(&Concrete::from_str(policy).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_spending_paths() {
+        let paths = paths("or(pk(A),and(thresh(2,pk(B),pk(C),pk(D)),older(144)))");
+        let keys: Vec<Vec<&str>> = paths
+            .iter()
+            .map(|path| path.keys.iter().map(String::as_str).collect())
+            .collect();
+        assert_eq!(
+            keys,
+            [vec!["A"], vec!["B", "C"], vec!["B", "D"], vec!["C", "D"]]
+        );
+        assert!(paths[0].older.is_none());
+        assert!(
+            paths[1..]
+                .iter()
+                .all(|path| path.older.unwrap().to_consensus_u32() == 144)
+        );
+    }
+
+    #[test]
+    fn test_mixed_timelock_units_are_unsatisfiable() {
+        let after = |value| SpendingPath {
+            after: Some(AbsLockTime::from_consensus(value).unwrap()),
+            ..Default::default()
+        };
+        // 500_000_000 and above are timestamps, below are block heights.
+        assert!(after(100).merge(&after(500_000_001)).is_none());
+        let merged = after(100).merge(&after(200)).unwrap();
+        assert_eq!(merged.after.unwrap().to_consensus_u32(), 200);
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Fiat values
+//!
+//! With `--fiat USD`, the balance, the transactions and the `create_tx` previews show the
+//! approximate value of their amounts in that currency, at the price of the `/v1/prices` endpoint
+//! of a [mempool.space](https://mempool.space) instance. The quotes are cached in `prices.json`
+//! of the data directory for [`CACHE_TTL_SECS`], and offline the cached quote is used whatever
+//! its age. Without any quote, the outputs are written without their fiat values.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::commands::WalletOpts;
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+use crate::utils::http::http_client;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+/// The prices of the public mempool.space instance.
+pub const DEFAULT_PRICE_URL: &str = "https://mempool.space/api/v1/prices";
+
+/// How long a cached quote is used before asking the provider again.
+pub const CACHE_TTL_SECS: u64 = 600;
+
+const CACHE_FILE: &str = "prices.json";
+
+/// The quote of `--fiat`, when one could be had.
+static QUOTE: OnceLock<Quote> = OnceLock::new();
+
+/// The price of a bitcoin in a fiat currency.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quote {
+    pub currency: String,
+    pub price: f64,
+    /// When the provider quoted the price, in seconds since the epoch.
+    pub time: u64,
+}
+
+/// The prices of the provider at `url`, as cached in `prices.json`.
+#[derive(Debug, Deserialize, Serialize)]
+struct CachedPrices {
+    url: String,
+    /// When the prices were fetched, in seconds since the epoch.
+    fetched_at: u64,
+    /// When the provider quoted the prices.
+    time: u64,
+    prices: BTreeMap<String, f64>,
+}
+
+impl CachedPrices {
+    /// Reads the `{"time": .., "USD": .., "EUR": ..}` prices of mempool.space.
+    fn from_response(url: &str, body: &[u8]) -> Option<Self> {
+        let fields: BTreeMap<String, serde_json::Value> = serde_json::from_slice(body).ok()?;
+        let time = fields.get("time")?.as_u64()?;
+        let prices = fields
+            .iter()
+            .filter(|(currency, _)| currency.as_str() != "time")
+            .filter_map(|(currency, price)| Some((currency.clone(), price.as_f64()?)))
+            .collect();
+        Some(Self {
+            url: url.to_string(),
+            fetched_at: now(),
+            time,
+            prices,
+        })
+    }
+
+    fn quote(&self, currency: &str) -> Option<Quote> {
+        let price = *self.prices.get(currency)?;
+        (price > 0.0).then(|| Quote {
+            currency: currency.to_string(),
+            price,
+            time: self.time,
+        })
+    }
+}
+
+/// The value of an amount in the currency of `--fiat`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct FiatValue {
+    pub currency: String,
+    /// Rounded to the cent.
+    pub value: f64,
+    /// The price of a bitcoin it was valued at.
+    pub price: f64,
+    /// When the price was quoted, in seconds since the epoch.
+    pub quoted_at: u64,
+}
+
+/// Values the amounts of the outputs at `quote`.
+pub fn set_quote(quote: Quote) {
+    let _ = QUOTE.set(quote);
+}
+
+/// The value of `sats` at the quote of `--fiat`, negative for the amounts leaving the wallet, or
+/// `None` without `--fiat` or without a quote.
+pub(crate) fn fiat_value(sats: i64) -> Option<FiatValue> {
+    let quote = QUOTE.get()?;
+    Some(value_at(quote, sats))
+}
+
+fn value_at(quote: &Quote, sats: i64) -> FiatValue {
+    let value = sats as f64 / 100_000_000.0 * quote.price;
+    FiatValue {
+        currency: quote.currency.clone(),
+        value: (value * 100.0).round() / 100.0,
+        price: quote.price,
+        quoted_at: quote.time,
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+fn read_cache(datadir: &Path, url: &str) -> Option<CachedPrices> {
+    let cache = std::fs::read(datadir.join(CACHE_FILE)).ok()?;
+    let cache: CachedPrices = serde_json::from_slice(&cache).ok()?;
+    (cache.url == url).then_some(cache)
+}
+
+/// The price of a bitcoin in `currency`, from the provider at `url` or [`DEFAULT_PRICE_URL`],
+/// asked through the proxy of the wallet of `wallet_opts`. The cached quote is used while fresh,
+/// offline, or when the provider cannot be reached. Any failure is only logged, since the fiat
+/// values are a convenience.
+pub async fn load_quote(
+    currency: &str,
+    url: Option<&str>,
+    datadir: &Path,
+    offline: bool,
+    wallet_opts: Option<&WalletOpts>,
+) -> Option<Quote> {
+    let currency = currency.to_uppercase();
+    let url = url.unwrap_or(DEFAULT_PRICE_URL);
+    let cached = read_cache(datadir, url);
+    let fresh = cached
+        .as_ref()
+        .is_some_and(|cache| now().saturating_sub(cache.fetched_at) < CACHE_TTL_SECS);
+    if offline || fresh {
+        return cached.and_then(|cache| cache.quote(&currency));
+    }
+    match fetch_prices(url, wallet_opts).await {
+        Some(prices) => {
+            if let Err(e) = serde_json::to_vec(&prices)
+                .map_err(std::io::Error::other)
+                .and_then(|json| std::fs::write(datadir.join(CACHE_FILE), json))
+            {
+                debug!("Cannot cache the prices: {e}");
+            }
+            prices.quote(&currency)
+        }
+        None => cached.and_then(|cache| cache.quote(&currency)),
+    }
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+async fn fetch_prices(url: &str, wallet_opts: Option<&WalletOpts>) -> Option<CachedPrices> {
+    let client = match http_client(wallet_opts) {
+        Ok(client) => client,
+        Err(e) => {
+            debug!("Cannot get the prices from {url}: {e}");
+            return None;
+        }
+    };
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    let body = match response {
+        Ok(response) => response.bytes().await,
+        Err(e) => Err(e),
+    };
+    match body {
+        Ok(body) => {
+            let prices = CachedPrices::from_response(url, &body);
+            if prices.is_none() {
+                debug!("Invalid prices from {url}");
+            }
+            prices
+        }
+        Err(e) => {
+            debug!("Cannot get the prices from {url}: {e}");
+            None
+        }
+    }
+}
+
+/// Without a blockchain client feature there is no HTTP client, and only cached quotes are used.
+#[cfg(not(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+)))]
+async fn fetch_prices(_url: &str, _wallet_opts: Option<&WalletOpts>) -> Option<CachedPrices> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_from_mempool_prices() {
+        let prices = CachedPrices::from_response(
+            DEFAULT_PRICE_URL,
+            br#"{"time":1703252411,"USD":43753,"EUR":40545.5,"GBP":0}"#,
+        )
+        .unwrap();
+        let quote = prices.quote("USD").unwrap();
+        assert_eq!(quote.price, 43753.0);
+        assert_eq!(quote.time, 1703252411);
+        assert_eq!(prices.quote("EUR").unwrap().price, 40545.5);
+        assert!(prices.quote("GBP").is_none());
+        assert!(prices.quote("JPY").is_none());
+
+        let value = value_at(&quote, 150_000);
+        assert_eq!(value.value, 65.63);
+        assert_eq!(value_at(&quote, -150_000).value, -65.63);
+    }
+}
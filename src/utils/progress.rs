@@ -0,0 +1,295 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Sync progress
+//!
+//! `sync`, `full_scan` and `rescan` report the scripts checked per keychain and the block height
+//! reached, as a progress bar redrawn on the terminal or as newline delimited JSON events. Both
+//! go to stderr, leaving the result of the command alone on stdout.
+
+#[cfg(feature = "grpc")]
+use crate::utils::events::{Event, forward};
+use crate::utils::stats;
+use clap::ValueEnum;
+use serde_json::{Value, json};
+use std::io::{IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+#[cfg(any(feature = "electrum", feature = "esplora"))]
+use {bdk_wallet::KeychainKind, std::collections::BTreeMap};
+
+/// Width of the progress bar, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// Shortest interval between two redraws of the bar or two block events.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How the progress of a scan is reported.
+#[derive(Clone, Copy, ValueEnum, Debug, Eq, PartialEq)]
+pub enum ProgressFormat {
+    /// A progress bar redrawn in place.
+    Bar,
+    /// One JSON event per line.
+    Json,
+    /// No progress.
+    None,
+}
+
+#[derive(Debug)]
+struct State {
+    format: ProgressFormat,
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    scripts: BTreeMap<KeychainKind, u32>,
+    /// Whether each script or transaction checked is a request of its own, rather than one of a
+    /// batch the client counts.
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    item_requests: bool,
+    drawn: bool,
+    pending: Option<String>,
+    last_refresh: Option<Instant>,
+}
+
+/// Reports the progress of a scan, shared with the inspectors of the scan requests.
+#[derive(Debug, Clone)]
+pub struct Progress(Arc<Mutex<State>>);
+
+impl Progress {
+    /// Reports in `format`, a bar when stderr is a terminal and nothing otherwise when unset.
+    pub fn new(format: Option<ProgressFormat>) -> Self {
+        Progress(Arc::new(Mutex::new(State {
+            format: resolve(format),
+            #[cfg(any(feature = "electrum", feature = "esplora"))]
+            scripts: BTreeMap::new(),
+            #[cfg(any(feature = "electrum", feature = "esplora"))]
+            item_requests: true,
+            drawn: false,
+            pending: None,
+            last_refresh: None,
+        })))
+    }
+
+    /// Starts reporting a new scan in `format`.
+    #[cfg(feature = "cbf")]
+    pub fn reset(&self, format: Option<ProgressFormat>) {
+        let mut state = self.lock();
+        state.format = resolve(format);
+        #[cfg(any(feature = "electrum", feature = "esplora"))]
+        state.scripts.clear();
+        state.drawn = false;
+        state.pending = None;
+        state.last_refresh = None;
+    }
+
+    /// Leaves the count of the requests of the scan to the client, which sends the scripts and
+    /// the transactions in batches.
+    #[cfg(feature = "electrum")]
+    pub fn batched(self) -> Self {
+        self.lock().item_requests = false;
+        self
+    }
+
+    /// A full scan checked the script at `index` of `keychain`.
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    pub fn script(&self, keychain: KeychainKind, index: u32) {
+        stats::add_script();
+        let mut state = self.lock();
+        if state.item_requests {
+            stats::add_requests(1);
+        }
+        state.scripts.insert(keychain, index + 1);
+        match state.format {
+            ProgressFormat::Json => emit(json!({
+                "event": "script",
+                "keychain": keychain_name(keychain),
+                "index": index,
+                "scanned": state.scripts[&keychain],
+            })),
+            ProgressFormat::Bar => {
+                let line = format!("Scanning {}", state.scripts_per_keychain());
+                state.draw(line, false);
+            }
+            ProgressFormat::None => {}
+        }
+    }
+
+    /// A sync checked `checked` of its `total` items, the last being the script at `index` of
+    /// `keychain` when set.
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    pub fn sync_item(&self, script: Option<(KeychainKind, u32)>, checked: usize, total: usize) {
+        if script.is_some() {
+            stats::add_script();
+        }
+        let mut state = self.lock();
+        if state.item_requests {
+            stats::add_requests(1);
+        }
+        if let Some((keychain, _)) = script {
+            *state.scripts.entry(keychain).or_default() += 1;
+        }
+        match state.format {
+            ProgressFormat::Json => emit(json!({
+                "event": "sync",
+                "keychain": script.map(|(keychain, _)| keychain_name(keychain)),
+                "index": script.map(|(_, index)| index),
+                "checked": checked,
+                "total": total,
+            })),
+            ProgressFormat::Bar => {
+                let line = format!(
+                    "{} {checked}/{total} checked, {}",
+                    bar(checked as f64 / total.max(1) as f64),
+                    state.scripts_per_keychain()
+                );
+                state.draw(line, checked == total);
+            }
+            ProgressFormat::None => {}
+        }
+    }
+
+    /// A block scan reached `height` of a chain ending at `tip`.
+    #[cfg(feature = "rpc")]
+    pub fn block(&self, height: u32, tip: u32) {
+        stats::add_requests(1);
+        let mut state = self.lock();
+        let format = state.format;
+        match format {
+            ProgressFormat::Json if state.refresh(height >= tip) => emit(json!({
+                "event": "block",
+                "height": height,
+                "tip": tip,
+            })),
+            ProgressFormat::Bar => {
+                let line = format!(
+                    "{} height {height}/{tip}",
+                    bar(f64::from(height) / f64::from(tip.max(1)))
+                );
+                state.draw(line, height >= tip);
+            }
+            _ => {}
+        }
+    }
+
+    /// A compact block filter scan is `percent` complete, on a chain at `height`.
+    #[cfg(feature = "cbf")]
+    pub fn filters(&self, percent: f64, height: u32) {
+        let mut state = self.lock();
+        let format = state.format;
+        match format {
+            ProgressFormat::Json if state.refresh(percent >= 100.0) => emit(json!({
+                "event": "filters",
+                "percent": percent,
+                "height": height,
+            })),
+            ProgressFormat::Bar => {
+                let line = format!("{} chain height {height}", bar(percent / 100.0));
+                state.draw(line, percent >= 100.0);
+            }
+            _ => {}
+        }
+    }
+
+    /// The scan is over and the wallet is at `height`.
+    pub fn finish(&self, height: u32) {
+        let mut state = self.lock();
+        match state.format {
+            ProgressFormat::Json => emit(json!({ "event": "done", "height": height })),
+            ProgressFormat::Bar => {
+                if let Some(line) = state.pending.take() {
+                    state.draw(line, true);
+                }
+                if state.drawn {
+                    eprintln!();
+                    state.drawn = false;
+                }
+            }
+            ProgressFormat::None => {}
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl State {
+    /// Whether to report now, at most once per [`REFRESH_INTERVAL`] unless `last`.
+    fn refresh(&mut self, last: bool) -> bool {
+        let now = Instant::now();
+        let due = self
+            .last_refresh
+            .is_none_or(|at| now.duration_since(at) >= REFRESH_INTERVAL);
+        if due || last {
+            self.last_refresh = Some(now);
+        }
+        due || last
+    }
+
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    fn scripts_per_keychain(&self) -> String {
+        self.scripts
+            .iter()
+            .map(|(keychain, scripts)| format!("{} {scripts}", keychain_name(*keychain)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Redraws the bar line in place, or keeps it for the next redraw when too early.
+    fn draw(&mut self, line: String, last: bool) {
+        if !self.refresh(last) {
+            self.pending = Some(line);
+            return;
+        }
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r\x1b[2K{line}");
+        let _ = stderr.flush();
+        self.pending = None;
+        self.drawn = true;
+    }
+}
+
+fn resolve(format: Option<ProgressFormat>) -> ProgressFormat {
+    // A gRPC stream takes the events, whatever the format asked for.
+    #[cfg(feature = "grpc")]
+    if crate::utils::events::forwarding() {
+        return ProgressFormat::Json;
+    }
+    format.unwrap_or(if std::io::stderr().is_terminal() {
+        ProgressFormat::Bar
+    } else {
+        ProgressFormat::None
+    })
+}
+
+fn emit(event: Value) {
+    #[cfg(feature = "grpc")]
+    let Some(Event::Progress(event)) = forward(Event::Progress(event)) else {
+        return;
+    };
+    eprintln!("{event}");
+}
+
+#[cfg(any(feature = "electrum", feature = "esplora"))]
+fn keychain_name(keychain: KeychainKind) -> &'static str {
+    match keychain {
+        KeychainKind::External => "external",
+        KeychainKind::Internal => "internal",
+    }
+}
+
+fn bar(ratio: f64) -> String {
+    let ratio = ratio.clamp(0.0, 1.0);
+    let filled = (ratio * BAR_WIDTH as f64).round() as usize;
+    format!(
+        "[{}{}] {:>3.0}%",
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        ratio * 100.0
+    )
+}
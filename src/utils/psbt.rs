@@ -0,0 +1,663 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! PSBT version handling
+//!
+//! `rust-bitcoin` only models version 0 PSBTs, so [BIP-370] version 2 PSBTs are converted to and
+//! from version 0 at the key-value map level. Every field that is not specific to either version
+//! is carried over untouched. The version 2 fields a version 0 transaction cannot express, the
+//! per-input locktime requirements, the modifiable flags and a fallback locktime other than the
+//! transaction locktime, are kept as proprietary entries with the `bdk-cli` prefix so that they
+//! are written back when the PSBT is serialized as version 2 again.
+//!
+//! [BIP-370]: https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use bdk_wallet::bitcoin::absolute::LockTime;
+use bdk_wallet::bitcoin::consensus::encode::{deserialize, deserialize_partial, serialize};
+use bdk_wallet::bitcoin::transaction::Version;
+use bdk_wallet::bitcoin::{
+    Amount, EcdsaSighashType, OutPoint, Psbt, ScriptBuf, Sequence, TapSighashType, Transaction,
+    TxIn, TxOut, Txid, VarInt,
+};
+use clap::ValueEnum;
+
+pub const MAGIC: &[u8] = b"psbt\xff";
+
+const GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const GLOBAL_TX_VERSION: u8 = 0x02;
+const GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+const GLOBAL_INPUT_COUNT: u8 = 0x04;
+const GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+const GLOBAL_TX_MODIFIABLE: u8 = 0x06;
+const GLOBAL_VERSION: u8 = 0xfb;
+
+const IN_PREVIOUS_TXID: u8 = 0x0e;
+const IN_OUTPUT_INDEX: u8 = 0x0f;
+const IN_SEQUENCE: u8 = 0x10;
+const IN_REQUIRED_TIME_LOCKTIME: u8 = 0x11;
+const IN_REQUIRED_HEIGHT_LOCKTIME: u8 = 0x12;
+
+const OUT_AMOUNT: u8 = 0x03;
+const OUT_SCRIPT: u8 = 0x04;
+
+const PROPRIETARY: u8 = 0xfc;
+/// Prefix of the proprietary entries carrying version 2 fields, their subtype is the key type.
+const PROPRIETARY_PREFIX: &[u8] = b"bdk-cli";
+
+/// The PSBT serialization format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(u32)]
+pub enum PsbtVersion {
+    /// BIP-174, the unsigned transaction is stored in the global map.
+    #[default]
+    V0 = 0,
+    /// BIP-370, the transaction fields are spread over the input and output maps.
+    V2 = 2,
+}
+
+/// Parse the `--psbt-version` argument from cli input.
+pub(crate) fn parse_psbt_version(s: &str) -> Result<PsbtVersion, Error> {
+    match s {
+        "0" => Ok(PsbtVersion::V0),
+        "2" => Ok(PsbtVersion::V2),
+        _ => Err(Error::coded(
+            ErrorCode::InvalidPsbtVersion,
+            format!("Unsupported PSBT version {s}, use 0 or 2"),
+        )),
+    }
+}
+
+/// The sighash type requested when signing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Sighash {
+    /// SIGHASH_DEFAULT on taproot inputs, SIGHASH_ALL on the others.
+    Default,
+    All,
+    None,
+    Single,
+    AnyonecanpayAll,
+    AnyonecanpayNone,
+    AnyonecanpaySingle,
+}
+
+impl Sighash {
+    /// Whether the default signers accept it without `allow_all_sighashes`.
+    pub fn is_standard(self) -> bool {
+        matches!(self, Sighash::Default | Sighash::All)
+    }
+
+    fn ecdsa(self) -> EcdsaSighashType {
+        match self {
+            Sighash::Default | Sighash::All => EcdsaSighashType::All,
+            Sighash::None => EcdsaSighashType::None,
+            Sighash::Single => EcdsaSighashType::Single,
+            Sighash::AnyonecanpayAll => EcdsaSighashType::AllPlusAnyoneCanPay,
+            Sighash::AnyonecanpayNone => EcdsaSighashType::NonePlusAnyoneCanPay,
+            Sighash::AnyonecanpaySingle => EcdsaSighashType::SinglePlusAnyoneCanPay,
+        }
+    }
+
+    fn taproot(self) -> TapSighashType {
+        match self {
+            Sighash::Default => TapSighashType::Default,
+            Sighash::All => TapSighashType::All,
+            Sighash::None => TapSighashType::None,
+            Sighash::Single => TapSighashType::Single,
+            Sighash::AnyonecanpayAll => TapSighashType::AllPlusAnyoneCanPay,
+            Sighash::AnyonecanpayNone => TapSighashType::NonePlusAnyoneCanPay,
+            Sighash::AnyonecanpaySingle => TapSighashType::SinglePlusAnyoneCanPay,
+        }
+    }
+
+    /// Set the sighash type of every input, in the form its script type expects.
+    pub fn apply(self, psbt: &mut Psbt) {
+        for input in &mut psbt.inputs {
+            let is_taproot = input.tap_internal_key.is_some()
+                || input
+                    .witness_utxo
+                    .as_ref()
+                    .is_some_and(|utxo| utxo.script_pubkey.is_p2tr());
+            input.sighash_type = Some(if is_taproot {
+                self.taproot().into()
+            } else {
+                self.ecdsa().into()
+            });
+        }
+    }
+}
+
+pub(crate) type Map = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// The raw global, input and output key-value maps of a version 0 PSBT.
+pub(crate) fn raw_maps(psbt: &Psbt) -> (Map, Vec<Map>, Vec<Map>) {
+    let bytes = psbt.serialize();
+    let mut reader = Reader(&bytes[MAGIC.len()..]);
+    let mut map = || reader.map().expect("serialized PSBT is well formed");
+    let global = map();
+    let inputs = psbt.inputs.iter().map(|_| map()).collect();
+    let outputs = psbt.outputs.iter().map(|_| map()).collect();
+    (global, inputs, outputs)
+}
+
+/// The kind of key-value map a key belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MapKind {
+    Global,
+    Input,
+    Output,
+}
+
+/// BIP-174 name of a key type.
+pub(crate) fn field_name(kind: MapKind, key_type: u8) -> String {
+    let name = match (kind, key_type) {
+        (_, 0xfc) => "proprietary",
+        (MapKind::Global, 0x00) => "unsigned_tx",
+        (MapKind::Global, 0x01) => "xpub",
+        (MapKind::Global, 0xfb) => "version",
+        (MapKind::Input, 0x00) => "non_witness_utxo",
+        (MapKind::Input, 0x01) => "witness_utxo",
+        (MapKind::Input, 0x02) => "partial_sig",
+        (MapKind::Input, 0x03) => "sighash_type",
+        (MapKind::Input, 0x04) | (MapKind::Output, 0x00) => "redeem_script",
+        (MapKind::Input, 0x05) | (MapKind::Output, 0x01) => "witness_script",
+        (MapKind::Input, 0x06) | (MapKind::Output, 0x02) => "bip32_derivation",
+        (MapKind::Input, 0x07) => "final_script_sig",
+        (MapKind::Input, 0x08) => "final_script_witness",
+        (MapKind::Input, 0x0a) => "ripemd160",
+        (MapKind::Input, 0x0b) => "sha256",
+        (MapKind::Input, 0x0c) => "hash160",
+        (MapKind::Input, 0x0d) => "hash256",
+        (MapKind::Input, 0x13) => "tap_key_sig",
+        (MapKind::Input, 0x14) => "tap_script_sig",
+        (MapKind::Input, 0x15) => "tap_leaf_script",
+        (MapKind::Input, 0x16) | (MapKind::Output, 0x07) => "tap_bip32_derivation",
+        (MapKind::Input, 0x17) | (MapKind::Output, 0x05) => "tap_internal_key",
+        (MapKind::Input, 0x18) => "tap_merkle_root",
+        (MapKind::Output, 0x06) => "tap_tree",
+        _ => return format!("unknown_{key_type:#04x}"),
+    };
+    name.to_string()
+}
+
+/// Deserialize a PSBT of either version, returning it as a version 0 PSBT.
+pub fn deserialize_psbt(bytes: &[u8]) -> Result<(Psbt, PsbtVersion), Error> {
+    let mut reader = Reader(bytes.strip_prefix(MAGIC).unwrap_or_default());
+    match reader.map() {
+        Ok(global) if get_u32(&global, GLOBAL_VERSION)? == Some(2) => {
+            Ok((v2_to_v0(global, reader)?, PsbtVersion::V2))
+        }
+        _ => Ok((Psbt::deserialize(bytes)?, PsbtVersion::V0)),
+    }
+}
+
+/// Serialize a PSBT in the requested version.
+///
+/// The version 2 fields carried by a converted PSBT are restored, otherwise the locktime of the
+/// transaction is the fallback locktime.
+pub fn serialize_psbt(psbt: &Psbt, version: PsbtVersion) -> Vec<u8> {
+    let bytes = psbt.serialize();
+    if version == PsbtVersion::V0 {
+        return bytes;
+    }
+
+    let tx = &psbt.unsigned_tx;
+    let mut reader = Reader(&bytes[MAGIC.len()..]);
+    let mut global = reader.map().expect("serialized PSBT is well formed");
+    let inputs: Vec<Map> = (0..tx.input.len())
+        .map(|_| reader.map().expect("serialized PSBT is well formed"))
+        .collect();
+    let outputs: Vec<Map> = (0..tx.output.len())
+        .map(|_| reader.map().expect("serialized PSBT is well formed"))
+        .collect();
+
+    // An empty carried fallback locktime stands for a PSBT without one.
+    let fallback_locktime = match take_carried(&mut global, GLOBAL_FALLBACK_LOCKTIME) {
+        Some(value) => Some(value).filter(|value| !value.is_empty()),
+        None => Some(tx.lock_time.to_consensus_u32().to_le_bytes().to_vec()),
+    };
+    let tx_modifiable = take_carried(&mut global, GLOBAL_TX_MODIFIABLE);
+
+    let mut global_v2: Map = vec![(vec![GLOBAL_TX_VERSION], tx.version.0.to_le_bytes().to_vec())];
+    global_v2.extend(fallback_locktime.map(|value| (vec![GLOBAL_FALLBACK_LOCKTIME], value)));
+    global_v2.extend([
+        (
+            vec![GLOBAL_INPUT_COUNT],
+            serialize(&VarInt(tx.input.len() as u64)),
+        ),
+        (
+            vec![GLOBAL_OUTPUT_COUNT],
+            serialize(&VarInt(tx.output.len() as u64)),
+        ),
+    ]);
+    global_v2.extend(tx_modifiable.map(|value| (vec![GLOBAL_TX_MODIFIABLE], value)));
+    global_v2.push((vec![GLOBAL_VERSION], 2u32.to_le_bytes().to_vec()));
+    global_v2.extend(without(global, &[GLOBAL_UNSIGNED_TX, GLOBAL_VERSION]));
+
+    let inputs_v2 = tx.input.iter().zip(inputs).map(|(txin, mut map)| {
+        let required_locktimes = [IN_REQUIRED_TIME_LOCKTIME, IN_REQUIRED_HEIGHT_LOCKTIME]
+            .map(|key_type| take_carried(&mut map, key_type).map(|value| (vec![key_type], value)));
+        let mut map_v2: Map = vec![
+            (
+                vec![IN_PREVIOUS_TXID],
+                serialize(&txin.previous_output.txid),
+            ),
+            (
+                vec![IN_OUTPUT_INDEX],
+                txin.previous_output.vout.to_le_bytes().to_vec(),
+            ),
+            (
+                vec![IN_SEQUENCE],
+                txin.sequence.to_consensus_u32().to_le_bytes().to_vec(),
+            ),
+        ];
+        map_v2.extend(required_locktimes.into_iter().flatten());
+        map_v2.extend(map);
+        map_v2
+    });
+
+    let outputs_v2 = tx.output.iter().zip(outputs).map(|(txout, map)| {
+        let mut map_v2: Map = vec![
+            (
+                vec![OUT_AMOUNT],
+                txout.value.to_sat().to_le_bytes().to_vec(),
+            ),
+            (vec![OUT_SCRIPT], txout.script_pubkey.to_bytes()),
+        ];
+        map_v2.extend(map);
+        map_v2
+    });
+
+    write(global_v2, inputs_v2, outputs_v2)
+}
+
+/// Rebuild the unsigned transaction of a version 2 PSBT and store it as version 0.
+fn v2_to_v0(global: Map, mut reader: Reader) -> Result<Psbt, Error> {
+    let input_count = get_count(&global, GLOBAL_INPUT_COUNT)?;
+    let output_count = get_count(&global, GLOBAL_OUTPUT_COUNT)?;
+    let inputs = (0..input_count)
+        .map(|_| reader.map())
+        .collect::<Result<Vec<_>, _>>()?;
+    let outputs = (0..output_count)
+        .map(|_| reader.map())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let version = get_u32(&global, GLOBAL_TX_VERSION)?
+        .ok_or_else(|| malformed("missing transaction version"))?;
+    let fallback_locktime = get_u32(&global, GLOBAL_FALLBACK_LOCKTIME)?;
+
+    let mut txins = Vec::with_capacity(inputs.len());
+    let mut time_locks = Vec::new();
+    let mut height_locks = Vec::new();
+    for map in &inputs {
+        let txid = get(map, IN_PREVIOUS_TXID).ok_or_else(|| malformed("missing input txid"))?;
+        let txid: Txid = deserialize(txid).map_err(|_| malformed("invalid input txid"))?;
+        let vout = get_u32(map, IN_OUTPUT_INDEX)?
+            .ok_or_else(|| malformed("missing input output index"))?;
+        let sequence = get_u32(map, IN_SEQUENCE)?.unwrap_or(u32::MAX);
+
+        let time = get_u32(map, IN_REQUIRED_TIME_LOCKTIME)?;
+        let height = get_u32(map, IN_REQUIRED_HEIGHT_LOCKTIME)?;
+        if time.is_some() || height.is_some() {
+            time_locks.push(time);
+            height_locks.push(height);
+        }
+
+        txins.push(TxIn {
+            previous_output: OutPoint::new(txid, vout),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::from_consensus(sequence),
+            ..Default::default()
+        });
+    }
+
+    let mut txouts = Vec::with_capacity(outputs.len());
+    for map in &outputs {
+        let amount = get(map, OUT_AMOUNT)
+            .and_then(|value| <[u8; 8]>::try_from(value).ok())
+            .ok_or_else(|| malformed("missing or invalid output amount"))?;
+        let script = get(map, OUT_SCRIPT).ok_or_else(|| malformed("missing output script"))?;
+        txouts.push(TxOut {
+            value: Amount::from_sat(u64::from_le_bytes(amount)),
+            script_pubkey: ScriptBuf::from_bytes(script.to_vec()),
+        });
+    }
+
+    // BIP-370 locktime determination: prefer heights when every constrained input allows it.
+    let lock_time = if time_locks.is_empty() {
+        fallback_locktime.unwrap_or(0)
+    } else if height_locks.iter().all(Option::is_some) {
+        height_locks.into_iter().flatten().max().unwrap_or(0)
+    } else if time_locks.iter().all(Option::is_some) {
+        time_locks.into_iter().flatten().max().unwrap_or(0)
+    } else {
+        return Err(malformed(
+            "inputs require both height and time based locktimes",
+        ));
+    };
+
+    let tx = Transaction {
+        version: Version(version as i32),
+        lock_time: LockTime::from_consensus(lock_time),
+        input: txins,
+        output: txouts,
+    };
+
+    let mut carried = Vec::new();
+    if fallback_locktime != Some(lock_time) {
+        let value = get(&global, GLOBAL_FALLBACK_LOCKTIME).unwrap_or_default();
+        carried.push(carry(GLOBAL_FALLBACK_LOCKTIME, value));
+    }
+    if let Some(value) = get(&global, GLOBAL_TX_MODIFIABLE) {
+        carried.push(carry(GLOBAL_TX_MODIFIABLE, value));
+    }
+
+    let global_v0: Map = std::iter::once((vec![GLOBAL_UNSIGNED_TX], serialize(&tx)))
+        .chain(carried)
+        .chain(without(
+            global,
+            &[
+                GLOBAL_TX_VERSION,
+                GLOBAL_FALLBACK_LOCKTIME,
+                GLOBAL_INPUT_COUNT,
+                GLOBAL_OUTPUT_COUNT,
+                GLOBAL_TX_MODIFIABLE,
+                GLOBAL_VERSION,
+            ],
+        ))
+        .collect();
+    let inputs_v0 = inputs.into_iter().map(|map| {
+        let carried: Map = [IN_REQUIRED_TIME_LOCKTIME, IN_REQUIRED_HEIGHT_LOCKTIME]
+            .into_iter()
+            .filter_map(|key_type| get(&map, key_type).map(|value| carry(key_type, value)))
+            .collect();
+        carried.into_iter().chain(without(
+            map,
+            &[
+                IN_PREVIOUS_TXID,
+                IN_OUTPUT_INDEX,
+                IN_SEQUENCE,
+                IN_REQUIRED_TIME_LOCKTIME,
+                IN_REQUIRED_HEIGHT_LOCKTIME,
+            ],
+        ))
+    });
+    let outputs_v0 = outputs
+        .into_iter()
+        .map(|map| without(map, &[OUT_AMOUNT, OUT_SCRIPT]));
+
+    Ok(Psbt::deserialize(&write(global_v0, inputs_v0, outputs_v0))?)
+}
+
+fn malformed(reason: &str) -> Error {
+    Error::coded(
+        ErrorCode::InvalidPsbt,
+        format!("Malformed version 2 PSBT: {reason}"),
+    )
+}
+
+/// The value of a key without key data.
+fn get(map: &Map, key_type: u8) -> Option<&[u8]> {
+    map.iter()
+        .find(|(key, _)| key.as_slice() == [key_type])
+        .map(|(_, value)| value.as_slice())
+}
+
+fn get_u32(map: &Map, key_type: u8) -> Result<Option<u32>, Error> {
+    get(map, key_type)
+        .map(|value| {
+            <[u8; 4]>::try_from(value)
+                .map(u32::from_le_bytes)
+                .map_err(|_| malformed(&format!("invalid value for key type {key_type:#04x}")))
+        })
+        .transpose()
+}
+
+fn get_count(map: &Map, key_type: u8) -> Result<u64, Error> {
+    let value = get(map, key_type)
+        .ok_or_else(|| malformed(&format!("missing key type {key_type:#04x}")))?;
+    let count: VarInt = deserialize(value)
+        .map_err(|_| malformed(&format!("invalid value for key type {key_type:#04x}")))?;
+    Ok(count.0)
+}
+
+/// The proprietary key carrying the version 2 field of `key_type`.
+fn carried_key(key_type: u8) -> Vec<u8> {
+    let mut key = vec![PROPRIETARY];
+    key.extend(serialize(&VarInt(PROPRIETARY_PREFIX.len() as u64)));
+    key.extend(PROPRIETARY_PREFIX);
+    key.push(key_type);
+    key
+}
+
+fn carry(key_type: u8, value: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    (carried_key(key_type), value.to_vec())
+}
+
+/// Remove the carried version 2 field of `key_type` from `map`, returning its value.
+fn take_carried(map: &mut Map, key_type: u8) -> Option<Vec<u8>> {
+    let key = carried_key(key_type);
+    let position = map.iter().position(|(k, _)| *k == key)?;
+    Some(map.remove(position).1)
+}
+
+/// Drop the given keys without key data.
+fn without(map: Map, key_types: &[u8]) -> impl Iterator<Item = (Vec<u8>, Vec<u8>)> {
+    map.into_iter()
+        .filter(move |(key, _)| !(key.len() == 1 && key_types.contains(&key[0])))
+}
+
+fn write(
+    global: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+    inputs: impl Iterator<Item = impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>>,
+    outputs: impl Iterator<Item = impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>>,
+) -> Vec<u8> {
+    fn write_map(bytes: &mut Vec<u8>, map: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>) {
+        for (key, value) in map {
+            bytes.extend(serialize(&VarInt(key.len() as u64)));
+            bytes.extend(key);
+            bytes.extend(serialize(&VarInt(value.len() as u64)));
+            bytes.extend(value);
+        }
+        bytes.push(0x00);
+    }
+
+    let mut bytes = MAGIC.to_vec();
+    write_map(&mut bytes, global);
+    inputs.for_each(|map| write_map(&mut bytes, map));
+    outputs.for_each(|map| write_map(&mut bytes, map));
+    bytes
+}
+
+/// Reads raw key-value maps.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn map(&mut self) -> Result<Map, Error> {
+        let mut map = Vec::new();
+        loop {
+            let key_len = self.compact_size()?;
+            if key_len == 0 {
+                return Ok(map);
+            }
+            let key = self.take(key_len)?;
+            let value_len = self.compact_size()?;
+            let value = self.take(value_len)?;
+            map.push((key, value));
+        }
+    }
+
+    fn compact_size(&mut self) -> Result<u64, Error> {
+        let (value, consumed): (VarInt, usize) =
+            deserialize_partial(self.0).map_err(|_| malformed("truncated compact size"))?;
+        self.0 = &self.0[consumed..];
+        Ok(value.0)
+    }
+
+    fn take(&mut self, len: u64) -> Result<Vec<u8>, Error> {
+        let len = usize::try_from(len).map_err(|_| malformed("oversized field"))?;
+        if len > self.0.len() {
+            return Err(malformed("truncated field"));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn psbt() -> Psbt {
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::from_consensus(840_000),
+            input: vec![TxIn {
+                previous_output: OutPoint::from_str(
+                    "f61b1742ca13176464adb3cb66050c00787bb3a4eead37e985f2df1e37718126:1",
+                )
+                .unwrap(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(9_000),
+                script_pubkey: ScriptBuf::from_hex("0014d85c2b71d0060b09c9886aeb815e50991dda124d")
+                    .unwrap(),
+            }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new(),
+        });
+        psbt
+    }
+
+    #[test]
+    fn test_v2_round_trip() {
+        let psbt = psbt();
+        let v2 = serialize_psbt(&psbt, PsbtVersion::V2);
+        assert_ne!(v2, psbt.serialize());
+        assert!(Psbt::deserialize(&v2).is_err());
+
+        let (decoded, version) = deserialize_psbt(&v2).unwrap();
+        assert_eq!(version, PsbtVersion::V2);
+        assert_eq!(decoded, psbt);
+        assert_eq!(serialize_psbt(&decoded, PsbtVersion::V2), v2);
+    }
+
+    #[test]
+    fn test_v2_only_fields_survive_a_round_trip() {
+        let psbt = psbt();
+        let tx = &psbt.unsigned_tx;
+        let (_, inputs, outputs) = raw_maps(&psbt);
+        // No fallback locktime, the locktime comes from the required height of the input.
+        let global: Map = vec![
+            (vec![GLOBAL_TX_VERSION], 2i32.to_le_bytes().to_vec()),
+            (vec![GLOBAL_INPUT_COUNT], serialize(&VarInt(1))),
+            (vec![GLOBAL_OUTPUT_COUNT], serialize(&VarInt(1))),
+            (vec![GLOBAL_TX_MODIFIABLE], vec![0x03]),
+            (vec![GLOBAL_VERSION], 2u32.to_le_bytes().to_vec()),
+        ];
+        let mut input: Map = vec![
+            (
+                vec![IN_PREVIOUS_TXID],
+                serialize(&tx.input[0].previous_output.txid),
+            ),
+            (vec![IN_OUTPUT_INDEX], 1u32.to_le_bytes().to_vec()),
+            (
+                vec![IN_SEQUENCE],
+                tx.input[0]
+                    .sequence
+                    .to_consensus_u32()
+                    .to_le_bytes()
+                    .to_vec(),
+            ),
+            (
+                vec![IN_REQUIRED_HEIGHT_LOCKTIME],
+                850_000u32.to_le_bytes().to_vec(),
+            ),
+        ];
+        input.extend(inputs[0].clone());
+        let mut output: Map = vec![
+            (vec![OUT_AMOUNT], 9_000u64.to_le_bytes().to_vec()),
+            (vec![OUT_SCRIPT], tx.output[0].script_pubkey.to_bytes()),
+        ];
+        output.extend(outputs[0].clone());
+        let v2 = write(global, std::iter::once(input), std::iter::once(output));
+
+        let (decoded, version) = deserialize_psbt(&v2).unwrap();
+        assert_eq!(version, PsbtVersion::V2);
+        assert_eq!(decoded.unsigned_tx.lock_time.to_consensus_u32(), 850_000);
+        assert_eq!(decoded.proprietary.len(), 2);
+        assert_eq!(decoded.inputs[0].proprietary.len(), 1);
+
+        // Also through a version 0 serialization, as when the PSBT is passed around as base64.
+        let v0 = Psbt::deserialize(&decoded.serialize()).unwrap();
+        assert_eq!(serialize_psbt(&v0, PsbtVersion::V2), v2);
+    }
+
+    #[test]
+    fn test_malformed_v2_is_an_invalid_psbt() {
+        // A version 2 global map without the input count.
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend([0x01, GLOBAL_VERSION, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00]);
+        let error = deserialize_psbt(&bytes).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::InvalidPsbt);
+        assert_eq!(error.category(), crate::error::ErrorCategory::Transaction);
+        assert_eq!(
+            error.to_string(),
+            "Malformed version 2 PSBT: missing key type 0x04"
+        );
+
+        let error = parse_psbt_version("1").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::InvalidPsbtVersion);
+    }
+
+    #[test]
+    fn test_v0_is_left_untouched() {
+        let psbt = psbt();
+        let (decoded, version) = deserialize_psbt(&psbt.serialize()).unwrap();
+        assert_eq!(version, PsbtVersion::V0);
+        assert_eq!(decoded, psbt);
+    }
+
+    #[test]
+    fn test_sighash_matches_input_script_type() {
+        let mut psbt = psbt();
+        psbt.inputs.push(psbt.inputs[0].clone());
+        psbt.inputs[1].witness_utxo.as_mut().unwrap().script_pubkey = ScriptBuf::from_hex(
+            "51200f0c8db753acbd17343a39c2f3f4e35e4be6da749f9e35137ab220e7b238a667",
+        )
+        .unwrap();
+
+        Sighash::AnyonecanpaySingle.apply(&mut psbt);
+        assert_eq!(
+            psbt.inputs[0].sighash_type,
+            Some(EcdsaSighashType::SinglePlusAnyoneCanPay.into())
+        );
+        assert_eq!(
+            psbt.inputs[1].sighash_type,
+            Some(TapSighashType::SinglePlusAnyoneCanPay.into())
+        );
+
+        Sighash::Default.apply(&mut psbt);
+        assert_eq!(
+            psbt.inputs[0].sighash_type,
+            Some(EcdsaSighashType::All.into())
+        );
+        assert_eq!(
+            psbt.inputs[1].sighash_type,
+            Some(TapSighashType::Default.into())
+        );
+        assert!(Sighash::Default.is_standard() && !Sighash::None.is_standard());
+    }
+}
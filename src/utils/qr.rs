@@ -0,0 +1,109 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! QR codes
+//!
+//! Renders the parts of `ur:` resources as QR codes, in the terminal or as PNG images, for the
+//! cameras of air-gapped signers. Parts are uppercased first, so they are encoded in the
+//! alphanumeric mode which gives the smallest codes.
+
+use crate::error::BDKCliError as Error;
+use qrcode::render::unicode;
+use qrcode::{Color, QrCode};
+
+/// Width of the light border around a code, in modules.
+const QUIET_ZONE: usize = 4;
+
+/// Smallest width of a PNG image, in pixels.
+const MIN_PNG_WIDTH: usize = 400;
+
+fn code(part: &str) -> Result<QrCode, Error> {
+    QrCode::new(part.to_uppercase())
+        .map_err(|e| Error::Generic(format!("Cannot encode the part as a QR code: {e}")))
+}
+
+/// The QR code of `part` drawn with half blocks, light on dark so it scans on dark terminals.
+pub fn terminal(part: &str) -> Result<String, Error> {
+    Ok(code(part)?
+        .render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build())
+}
+
+/// The QR code of `part` as a grayscale PNG image, dark on light with a quiet zone.
+pub fn png(part: &str) -> Result<Vec<u8>, Error> {
+    let code = code(part)?;
+    let colors = code.to_colors();
+    let width = code.width();
+    let modules = width + 2 * QUIET_ZONE;
+    let scale = MIN_PNG_WIDTH.div_ceil(modules);
+    let pixels = modules * scale;
+    let dark = |x: usize, y: usize| {
+        let (x, y) = (x / scale, y / scale);
+        (QUIET_ZONE..QUIET_ZONE + width).contains(&x)
+            && (QUIET_ZONE..QUIET_ZONE + width).contains(&y)
+            && colors[(y - QUIET_ZONE) * width + x - QUIET_ZONE] == Color::Dark
+    };
+
+    // Each row starts with its filter type, none.
+    let mut rows = Vec::with_capacity((pixels + 1) * pixels);
+    for y in 0..pixels {
+        rows.push(0);
+        rows.extend((0..pixels).map(|x| if dark(x, y) { 0 } else { 255 }));
+    }
+    let size = (pixels as u32).to_be_bytes();
+    // 8-bit grayscale, without interlacing.
+    let header = [&size[..], &size[..], &[8, 0, 0, 0, 0]].concat();
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(
+        &mut png,
+        b"IDAT",
+        &miniz_oxide::deflate::compress_to_vec_zlib(&rows, 6),
+    );
+    png_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+/// Appends the chunk `kind` of `data` to `png`, with its length and checksum.
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(data);
+    png.extend(crc32([&kind[..], data].concat().as_slice()).to_be_bytes());
+}
+
+/// The CRC-32 of the PNG chunks.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_of_part() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let png = png("ur:crypto-psbt/hdcxlkahssqzwfvslofzoxwkrewngotktbmwjkwdcmnefsaaehrlolkskncnktlbaypkvoonhknt").unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR"));
+        let width = u32::from_be_bytes(png[16..20].try_into().unwrap()) as usize;
+        assert!(width >= MIN_PNG_WIDTH);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+    }
+}
@@ -1,5 +1,3 @@
-#[cfg(feature = "redb")]
-use bdk_redb::Store as RedbStore;
 use bdk_wallet::{Wallet, bitcoin::Network};
 use std::{
     ops::{Deref, DerefMut},
@@ -9,11 +7,15 @@ use std::{
 use crate::{
     error::BDKCliError as Error,
     persister::new_wallet,
-    utils::{load_wallet_config, prepare_wallet_db_dir},
+    utils::{
+        descriptors::check_descriptor_network,
+        load_wallet_config, prepare_wallet_db_dir,
+        stats::{self, Phase},
+    },
 };
 #[cfg(any(feature = "sqlite", feature = "redb"))]
 use {
-    crate::persister::{DatabaseType, Persister, new_persisted_wallet},
+    crate::persister::{Persister, new_persisted_wallet},
     bdk_wallet::PersistedWallet,
 };
 
@@ -53,11 +55,25 @@ impl DerefMut for RuntimeWallet {
 }
 
 impl RuntimeWallet {
+    /// The wallet and its persister, borrowed apart for the commands persisting the wallet as
+    /// they go.
+    #[cfg(any(feature = "sqlite", feature = "redb"))]
+    pub(crate) fn parts(&mut self) -> (&mut Wallet, Option<&mut Persister>) {
+        match self {
+            Self::Standard(wallet) => (&mut **wallet, None),
+            Self::Persisted(wallet, persister) => (&mut ***wallet, Some(&mut **persister)),
+        }
+    }
+
     pub fn persist(&mut self) -> Result<(), Error> {
+        let _timer = stats::timer(Phase::Persistence);
         match self {
             Self::Standard(_) => Ok(()),
             #[cfg(any(feature = "sqlite", feature = "redb"))]
             Self::Persisted(wallet, persister) => {
+                if crate::utils::is_read_only() && wallet.staged().is_some() {
+                    return Err(crate::utils::read_only_error());
+                }
                 wallet.persist(persister)?;
                 Ok(())
             }
@@ -77,6 +93,15 @@ pub struct WalletRuntime {
 impl WalletRuntime {
     pub fn load(home_dir: &Path, wallet_name: &str) -> Result<Self, Error> {
         let (wallet_opts, network) = load_wallet_config(home_dir, wallet_name)?;
+        for descriptor in [
+            Some(&wallet_opts.ext_descriptor),
+            wallet_opts.int_descriptor.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            check_descriptor_network(descriptor, network)?;
+        }
 
         let database_path = prepare_wallet_db_dir(home_dir, wallet_name)?;
 
@@ -97,6 +122,7 @@ impl WalletRuntime {
         )]
         require_db: bool,
     ) -> Result<RuntimeWallet, Error> {
+        let _timer = stats::timer(Phase::WalletLoad);
         #[cfg(any(feature = "sqlite", feature = "redb"))]
         if require_db {
             let mut persister = self.create_persister()?;
@@ -125,26 +151,11 @@ impl WalletRuntime {
 
     #[cfg(any(feature = "sqlite", feature = "redb"))]
     fn create_persister(&self) -> Result<Persister, Error> {
-        match &self.wallet_opts.database_type {
-            #[cfg(feature = "sqlite")]
-            DatabaseType::Sqlite => {
-                let db_file = self.database_path.join("wallet.sqlite");
-
-                let connection = bdk_wallet::rusqlite::Connection::open(db_file)?;
-
-                Ok(Persister::Connection(connection))
-            }
-
-            #[cfg(feature = "redb")]
-            DatabaseType::Redb => {
-                let db = std::sync::Arc::new(bdk_redb::redb::Database::create(
-                    self.home_dir.join("wallet.redb"),
-                )?);
-
-                let store = RedbStore::new(db, self.wallet_name.clone())?;
-
-                Ok(Persister::RedbStore(store))
-            }
-        }
+        Persister::open(
+            &self.wallet_opts,
+            &self.home_dir,
+            &self.database_path,
+            &self.wallet_name,
+        )
     }
 }
@@ -0,0 +1,105 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Custom signets
+//!
+//! A signet is told apart by its challenge, the script the signers of its blocks satisfy, and
+//! by the magic bytes of its peer messages derived from it (BIP-325). Every signet shares the
+//! genesis block and address prefix of the default signet, so a custom signet wallet runs on
+//! [`Network::Signet`] and only keeps its challenge to check the backend against.
+//!
+//! [`Network::Signet`]: bdk_wallet::bitcoin::Network::Signet
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use bdk_wallet::bitcoin::consensus::encode::serialize;
+use bdk_wallet::bitcoin::hashes::{Hash, sha256d};
+use bdk_wallet::bitcoin::hex::{DisplayHex, FromHex};
+use bdk_wallet::bitcoin::p2p::Magic;
+use bdk_wallet::bitcoin::{Script, ScriptBuf};
+
+/// Challenge of the default signet, a 1-of-2 multisig of its signers.
+pub const DEFAULT_SIGNET_CHALLENGE: &str = "512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d1e086be430210359ef5021964fe22d6f8e05b2463c9540ce96883fe3b278760f048f5189f2e6c452ae";
+
+/// Challenge of Mutinynet, the signet of 30 second blocks of Mutiny.
+pub const MUTINYNET_CHALLENGE: &str =
+    "512102f7561d208dd9ae99bf497273e16f389bdbd6c4742ddb8e6b216e64fa2928ad8f51ae";
+
+/// Parse the `--signet-challenge` argument, the hex of a script.
+pub(crate) fn parse_signet_challenge(s: &str) -> Result<ScriptBuf, Error> {
+    let bytes = Vec::<u8>::from_hex(s.trim()).map_err(|e| {
+        Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("Invalid signet challenge {s}: {e}"),
+        )
+    })?;
+    if bytes.is_empty() {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            "The signet challenge is empty".to_string(),
+        ));
+    }
+    Ok(ScriptBuf::from_bytes(bytes))
+}
+
+/// The challenge of the default signet.
+pub fn default_signet_challenge() -> ScriptBuf {
+    parse_signet_challenge(DEFAULT_SIGNET_CHALLENGE).expect("valid default signet challenge")
+}
+
+/// The magic bytes of the signet of `challenge`, the first 4 bytes of the double SHA-256 of the
+/// challenge serialized as a byte vector.
+pub fn signet_magic(challenge: &Script) -> Magic {
+    let hash = sha256d::Hash::hash(&serialize(&challenge.to_bytes()));
+    let mut magic = [0u8; 4];
+    magic.copy_from_slice(&hash.as_byte_array()[..4]);
+    Magic::from_bytes(magic)
+}
+
+/// Whether `challenge` is the one of a custom signet rather than the default signet.
+pub fn is_custom_signet(challenge: Option<&Script>) -> bool {
+    challenge.is_some_and(|challenge| challenge != default_signet_challenge().as_script())
+}
+
+/// The hex of `challenge`, as saved in `config.toml`.
+pub fn challenge_hex(challenge: &Script) -> String {
+    challenge.as_bytes().to_lower_hex_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signet_magic_of_the_default_challenge() {
+        let challenge = default_signet_challenge();
+        assert_eq!(signet_magic(&challenge), Magic::SIGNET);
+        assert!(!is_custom_signet(Some(challenge.as_script())));
+        assert!(!is_custom_signet(None));
+        assert_eq!(challenge_hex(&challenge), DEFAULT_SIGNET_CHALLENGE);
+    }
+
+    #[test]
+    fn test_mutinynet_challenge() {
+        let challenge = parse_signet_challenge(MUTINYNET_CHALLENGE).unwrap();
+        assert!(is_custom_signet(Some(challenge.as_script())));
+    }
+
+    #[test]
+    fn test_custom_signet_challenge() {
+        // A 1-of-1 multisig of an arbitrary key.
+        let challenge = parse_signet_challenge(
+            "512103abababababababababababababababababababababababababababababababab51ae",
+        )
+        .unwrap();
+        assert!(is_custom_signet(Some(challenge.as_script())));
+        assert_ne!(signet_magic(&challenge), Magic::SIGNET);
+
+        assert!(parse_signet_challenge("").is_err());
+        assert!(parse_signet_challenge("51zz").is_err());
+    }
+}
@@ -0,0 +1,808 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! SLIP-39 Shamir backups
+//!
+//! Splits a master secret into [SLIP-39] share mnemonics, organised in groups, and recovers it
+//! from a threshold of them. The master secret is encrypted with the passphrase before it is
+//! split, and is used directly as the BIP32 seed, as Trezor wallets do.
+//!
+//! [SLIP-39]: https://github.com/satoshilabs/slips/blob/master/slip-0039.md
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use bdk_wallet::bitcoin::hashes::{Hash, HashEngine, Hmac, HmacEngine, sha256};
+use bdk_wallet::bitcoin::secp256k1::rand::{RngCore, thread_rng};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Iteration exponent of the passphrase encryption of new shares.
+pub const DEFAULT_ITERATION_EXPONENT: u8 = 1;
+
+const MAX_SHARES: u8 = 16;
+const HEADER_WORDS: usize = 4;
+const CHECKSUM_WORDS: usize = 3;
+const MIN_SECRET_LEN: usize = 16;
+const DIGEST_LEN: usize = 4;
+const DIGEST_INDEX: u8 = 254;
+const SECRET_INDEX: u8 = 255;
+const BASE_ITERATION_COUNT: u32 = 10_000;
+const ROUND_COUNT: u8 = 4;
+
+/// Generator of the RS1024 checksum, GF(1024) being defined by x^10 + x^3 + 1.
+const RS1024_GEN: [u32; 10] = [
+    0xe0e040, 0x1c1c080, 0x3838100, 0x7070200, 0xe0e0009, 0x1c0c2412, 0x38086c24, 0x3090fc48,
+    0x21b1f890, 0x3f3f120,
+];
+
+/// A group of shares, `threshold` of the `count` shares being needed to recover it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupSpec {
+    pub threshold: u8,
+    pub count: u8,
+}
+
+impl FromStr for GroupSpec {
+    type Err = Error;
+
+    /// Parse a group given as `<THRESHOLD>-of-<COUNT>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            Error::coded(
+                ErrorCode::InvalidArgument,
+                format!("Invalid group '{s}', use <THRESHOLD>-of-<COUNT>"),
+            )
+        };
+        let (threshold, count) = s.split_once("-of-").ok_or_else(invalid)?;
+        Ok(Self {
+            threshold: threshold.parse().map_err(|_| invalid())?,
+            count: count.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// One share of a SLIP-39 backup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Share {
+    identifier: u16,
+    extendable: bool,
+    iteration_exponent: u8,
+    group_index: u8,
+    group_threshold: u8,
+    group_count: u8,
+    member_index: u8,
+    member_threshold: u8,
+    value: Vec<u8>,
+}
+
+impl Share {
+    fn parse(mnemonic: &str) -> Result<Self, Error> {
+        let words = mnemonic
+            .split_whitespace()
+            .map(|word| {
+                let word = word.to_lowercase();
+                WORDLIST
+                    .binary_search(&word.as_str())
+                    .map(|index| index as u32)
+                    .map_err(|_| invalid(&format!("unknown word '{word}'")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let min_words = HEADER_WORDS + (MIN_SECRET_LEN * 8).div_ceil(10) + CHECKSUM_WORDS;
+        if words.len() < min_words {
+            return Err(invalid(&format!(
+                "a share has at least {min_words} words, got {}",
+                words.len()
+            )));
+        }
+
+        let mut header = BitReader::new(&words[..HEADER_WORDS]);
+        let identifier = header.read(15) as u16;
+        let extendable = header.read(1) == 1;
+        if polymod(
+            customization(extendable)
+                .bytes()
+                .map(u32::from)
+                .chain(words.iter().copied()),
+        ) != 1
+        {
+            return Err(invalid("the checksum is wrong"));
+        }
+        let iteration_exponent = header.read(4) as u8;
+        let group_index = header.read(4) as u8;
+        let group_threshold = header.read(4) as u8 + 1;
+        let group_count = header.read(4) as u8 + 1;
+        let member_index = header.read(4) as u8;
+        let member_threshold = header.read(4) as u8 + 1;
+        if group_threshold > group_count {
+            return Err(invalid("the group threshold exceeds the number of groups"));
+        }
+
+        let value_words = &words[HEADER_WORDS..words.len() - CHECKSUM_WORDS];
+        let padding = value_words.len() * 10 % 16;
+        if padding > 8 {
+            return Err(invalid("the share value has a wrong length"));
+        }
+        let mut reader = BitReader::new(value_words);
+        if reader.read(padding) != 0 {
+            return Err(invalid("the share value padding is not zero"));
+        }
+        let value = (0..(value_words.len() * 10 - padding) / 8)
+            .map(|_| reader.read(8) as u8)
+            .collect();
+
+        Ok(Self {
+            identifier,
+            extendable,
+            iteration_exponent,
+            group_index,
+            group_threshold,
+            group_count,
+            member_index,
+            member_threshold,
+            value,
+        })
+    }
+
+    fn to_mnemonic(&self) -> String {
+        let mut writer = BitWriter::default();
+        writer.write(self.identifier as u32, 15);
+        writer.write(self.extendable as u32, 1);
+        writer.write(self.iteration_exponent as u32, 4);
+        writer.write(self.group_index as u32, 4);
+        writer.write(self.group_threshold as u32 - 1, 4);
+        writer.write(self.group_count as u32 - 1, 4);
+        writer.write(self.member_index as u32, 4);
+        writer.write(self.member_threshold as u32 - 1, 4);
+        writer.write(0, (10 - self.value.len() * 8 % 10) % 10);
+        for byte in &self.value {
+            writer.write(*byte as u32, 8);
+        }
+
+        let mut words = writer.words;
+        let checksum = polymod(
+            customization(self.extendable)
+                .bytes()
+                .map(u32::from)
+                .chain(words.iter().copied())
+                .chain([0; CHECKSUM_WORDS]),
+        ) ^ 1;
+        words.extend(
+            (0..CHECKSUM_WORDS)
+                .rev()
+                .map(|i| (checksum >> (10 * i)) & 1023),
+        );
+        words
+            .iter()
+            .map(|word| WORDLIST[*word as usize])
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Split a master secret into share mnemonics, one list per group. `group_threshold` of the
+/// groups are needed to recover the secret.
+pub fn generate(
+    master_secret: &[u8],
+    passphrase: &str,
+    group_threshold: u8,
+    groups: &[GroupSpec],
+) -> Result<Vec<Vec<String>>, Error> {
+    if master_secret.len() < MIN_SECRET_LEN || !master_secret.len().is_multiple_of(2) {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("The master secret must be an even number of bytes, at least {MIN_SECRET_LEN}"),
+        ));
+    }
+    if groups.is_empty() || groups.len() > MAX_SHARES as usize {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("Between 1 and {MAX_SHARES} groups are needed"),
+        ));
+    }
+    if group_threshold == 0 || group_threshold as usize > groups.len() {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!(
+                "The group threshold must be between 1 and the number of groups ({})",
+                groups.len()
+            ),
+        ));
+    }
+    for group in groups {
+        if group.threshold == 0 || group.threshold > group.count || group.count > MAX_SHARES {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "Invalid group {}-of-{}: the threshold must be between 1 and the number of \
+                 shares, at most {MAX_SHARES}",
+                    group.threshold, group.count
+                ),
+            ));
+        }
+        if group.threshold == 1 && group.count > 1 {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "Invalid group 1-of-{}: use a 1-of-1 group instead of copies of the same share",
+                    group.count
+                ),
+            ));
+        }
+    }
+
+    let identifier = (thread_rng().next_u32() & 0x7fff) as u16;
+    let extendable = true;
+    let encrypted = encrypt(
+        master_secret,
+        passphrase,
+        DEFAULT_ITERATION_EXPONENT,
+        identifier,
+        extendable,
+    );
+    let group_secrets = split_secret(group_threshold, groups.len() as u8, &encrypted);
+
+    Ok(groups
+        .iter()
+        .zip(group_secrets)
+        .map(|(group, (group_index, group_secret))| {
+            split_secret(group.threshold, group.count, &group_secret)
+                .into_iter()
+                .map(|(member_index, value)| {
+                    Share {
+                        identifier,
+                        extendable,
+                        iteration_exponent: DEFAULT_ITERATION_EXPONENT,
+                        group_index,
+                        group_threshold,
+                        group_count: groups.len() as u8,
+                        member_index,
+                        member_threshold: group.threshold,
+                        value,
+                    }
+                    .to_mnemonic()
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Recover the master secret from share mnemonics, given in any order.
+pub fn combine(mnemonics: &[String], passphrase: &str) -> Result<Vec<u8>, Error> {
+    let shares = mnemonics
+        .iter()
+        .map(|mnemonic| Share::parse(mnemonic))
+        .collect::<Result<Vec<_>, _>>()?;
+    let first = shares.first().ok_or_else(|| invalid("no share given"))?;
+    if shares.iter().any(|share| {
+        (
+            share.identifier,
+            share.extendable,
+            share.iteration_exponent,
+            share.group_threshold,
+            share.group_count,
+            share.value.len(),
+        ) != (
+            first.identifier,
+            first.extendable,
+            first.iteration_exponent,
+            first.group_threshold,
+            first.group_count,
+            first.value.len(),
+        )
+    }) {
+        return Err(invalid("the shares belong to different backups"));
+    }
+
+    let mut groups: BTreeMap<u8, BTreeMap<u8, &Share>> = BTreeMap::new();
+    for share in &shares {
+        let members = groups.entry(share.group_index).or_default();
+        if members
+            .values()
+            .any(|member| member.member_threshold != share.member_threshold)
+        {
+            return Err(invalid(&format!(
+                "the shares of group {} have different thresholds",
+                share.group_index
+            )));
+        }
+        members.insert(share.member_index, share);
+    }
+
+    let mut group_secrets = Vec::new();
+    let mut incomplete = Vec::new();
+    for (group_index, members) in &groups {
+        let threshold = members
+            .values()
+            .next()
+            .map_or(1, |share| share.member_threshold);
+        if members.len() < threshold as usize {
+            incomplete.push(format!(
+                "group {group_index} has {} of {threshold} shares",
+                members.len()
+            ));
+            continue;
+        }
+        let members: Vec<(u8, Vec<u8>)> = members
+            .values()
+            .take(threshold as usize)
+            .map(|share| (share.member_index, share.value.clone()))
+            .collect();
+        group_secrets.push((*group_index, recover_secret(threshold, &members)?));
+    }
+    if group_secrets.len() < first.group_threshold as usize {
+        let mut reason = format!(
+            "{} of {} groups are complete",
+            group_secrets.len(),
+            first.group_threshold
+        );
+        if !incomplete.is_empty() {
+            reason += &format!(" ({})", incomplete.join(", "));
+        }
+        return Err(invalid(&reason));
+    }
+    group_secrets.truncate(first.group_threshold as usize);
+
+    let encrypted = recover_secret(first.group_threshold, &group_secrets)?;
+    Ok(decrypt(
+        &encrypted,
+        passphrase,
+        first.iteration_exponent,
+        first.identifier,
+        first.extendable,
+    ))
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::coded(
+        ErrorCode::InvalidArgument,
+        format!("Invalid SLIP-39 share: {reason}"),
+    )
+}
+
+fn customization(extendable: bool) -> &'static str {
+    if extendable {
+        "shamir_extendable"
+    } else {
+        "shamir"
+    }
+}
+
+fn polymod(values: impl Iterator<Item = u32>) -> u32 {
+    let mut checksum = 1;
+    for value in values {
+        let top = checksum >> 20;
+        checksum = ((checksum & 0xfffff) << 10) ^ value;
+        for (i, generator) in RS1024_GEN.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+#[derive(Default)]
+struct BitWriter {
+    words: Vec<u32>,
+    buffer: u64,
+    bits: usize,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: usize) {
+        self.buffer = (self.buffer << bits) | value as u64;
+        self.bits += bits;
+        while self.bits >= 10 {
+            self.bits -= 10;
+            self.words.push((self.buffer >> self.bits) as u32 & 1023);
+        }
+    }
+}
+
+struct BitReader<'a> {
+    words: &'a [u32],
+    buffer: u64,
+    bits: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(words: &'a [u32]) -> Self {
+        Self {
+            words,
+            buffer: 0,
+            bits: 0,
+        }
+    }
+
+    fn read(&mut self, bits: usize) -> u32 {
+        while self.bits < bits {
+            let (word, rest) = self.words.split_first().unwrap_or((&0, &[]));
+            self.words = rest;
+            self.buffer = (self.buffer << 10) | *word as u64;
+            self.bits += 10;
+        }
+        self.bits -= bits;
+        ((self.buffer >> self.bits) & ((1 << bits) - 1)) as u32
+    }
+}
+
+/// Split a secret into `count` shares, `threshold` of them being needed to recover it.
+fn split_secret(threshold: u8, count: u8, secret: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    if threshold == 1 {
+        return (0..count).map(|index| (index, secret.to_vec())).collect();
+    }
+
+    let mut rng = thread_rng();
+    let mut random_bytes = |len: usize| {
+        let mut bytes = vec![0; len];
+        rng.fill_bytes(&mut bytes);
+        bytes
+    };
+    let random_shares = threshold - 2;
+    let mut shares: Vec<(u8, Vec<u8>)> = (0..random_shares)
+        .map(|index| (index, random_bytes(secret.len())))
+        .collect();
+    let random_part = random_bytes(secret.len() - DIGEST_LEN);
+    let mut digest = hmac_sha256(&random_part, secret)[..DIGEST_LEN].to_vec();
+    digest.extend(random_part);
+
+    let mut base_shares = shares.clone();
+    base_shares.push((DIGEST_INDEX, digest));
+    base_shares.push((SECRET_INDEX, secret.to_vec()));
+    shares.extend((random_shares..count).map(|index| (index, interpolate(&base_shares, index))));
+    shares
+}
+
+fn recover_secret(threshold: u8, shares: &[(u8, Vec<u8>)]) -> Result<Vec<u8>, Error> {
+    if threshold == 1 {
+        return Ok(shares[0].1.clone());
+    }
+    let secret = interpolate(shares, SECRET_INDEX);
+    let digest = interpolate(shares, DIGEST_INDEX);
+    if hmac_sha256(&digest[DIGEST_LEN..], &secret)[..DIGEST_LEN] != digest[..DIGEST_LEN] {
+        return Err(invalid("the digest of the shared secret does not match"));
+    }
+    Ok(secret)
+}
+
+/// Evaluate at `x` the polynomial going through the shares, over GF(256).
+fn interpolate(shares: &[(u8, Vec<u8>)], x: u8) -> Vec<u8> {
+    if let Some((_, value)) = shares.iter().find(|(index, _)| *index == x) {
+        return value.clone();
+    }
+    let (exp, log) = gf256_tables();
+    let log_product: usize = shares
+        .iter()
+        .map(|(index, _)| log[(index ^ x) as usize] as usize)
+        .sum();
+
+    let mut result = vec![0; shares[0].1.len()];
+    for (index, value) in shares {
+        let log_denominator: usize = shares
+            .iter()
+            .filter(|(other, _)| other != index)
+            .map(|(other, _)| log[(other ^ index) as usize] as usize)
+            .sum::<usize>()
+            + log[(index ^ x) as usize] as usize;
+        let log_basis = (log_product + 255 * shares.len() - log_denominator) % 255;
+        for (out, byte) in result.iter_mut().zip(value) {
+            if *byte != 0 {
+                *out ^= exp[(log[*byte as usize] as usize + log_basis) % 255];
+            }
+        }
+    }
+    result
+}
+
+/// Exponent and logarithm tables of GF(256) with the Rijndael polynomial, generated by 3.
+fn gf256_tables() -> ([u8; 255], [u8; 256]) {
+    let (mut exp, mut log) = ([0; 255], [0; 256]);
+    let mut value: u16 = 1;
+    for (i, entry) in exp.iter_mut().enumerate() {
+        *entry = value as u8;
+        log[value as usize] = i as u8;
+        value ^= value << 1;
+        if value & 0x100 != 0 {
+            value ^= 0x11b;
+        }
+    }
+    (exp, log)
+}
+
+fn encrypt(
+    master_secret: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+) -> Vec<u8> {
+    feistel(
+        master_secret,
+        passphrase,
+        iteration_exponent,
+        identifier,
+        extendable,
+        0..ROUND_COUNT,
+    )
+}
+
+fn decrypt(
+    encrypted: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+) -> Vec<u8> {
+    feistel(
+        encrypted,
+        passphrase,
+        iteration_exponent,
+        identifier,
+        extendable,
+        (0..ROUND_COUNT).rev(),
+    )
+}
+
+/// The four round Feistel network of SLIP-39, with PBKDF2-HMAC-SHA256 as round function.
+fn feistel(
+    data: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+    rounds: impl Iterator<Item = u8>,
+) -> Vec<u8> {
+    let mut salt_prefix = Vec::new();
+    if !extendable {
+        salt_prefix.extend(customization(extendable).as_bytes());
+        salt_prefix.extend(identifier.to_be_bytes());
+    }
+    let iterations = (BASE_ITERATION_COUNT << iteration_exponent) / ROUND_COUNT as u32;
+
+    let (left, right) = data.split_at(data.len() / 2);
+    let (mut left, mut right) = (left.to_vec(), right.to_vec());
+    for round in rounds {
+        let mut password = vec![round];
+        password.extend(passphrase.as_bytes());
+        let salt = [salt_prefix.as_slice(), &right].concat();
+        let key = pbkdf2_sha256(&password, &salt, iterations);
+        let mixed = left.iter().zip(key).map(|(byte, key)| byte ^ key).collect();
+        left = std::mem::replace(&mut right, mixed);
+    }
+    [right, left].concat()
+}
+
+/// The first block of PBKDF2-HMAC-SHA256, enough for secrets of up to 64 bytes.
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut block = hmac_sha256(password, &[salt, &1u32.to_be_bytes()].concat());
+    let mut result = block;
+    for _ in 1..iterations {
+        block = hmac_sha256(password, &block);
+        for (out, byte) in result.iter_mut().zip(block) {
+            *out ^= byte;
+        }
+    }
+    result
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut engine = HmacEngine::<sha256::Hash>::new(key);
+    engine.input(data);
+    Hmac::from_engine(engine).to_byte_array()
+}
+
+const WORDLIST: [&str; 1024] = [
+    "academic", "acid", "acne", "acquire", "acrobat", "activity", "actress", "adapt", "adequate",
+    "adjust", "admit", "adorn", "adult", "advance", "advocate", "afraid", "again", "agency",
+    "agree", "aide", "aircraft", "airline", "airport", "ajar", "alarm", "album", "alcohol",
+    "alien", "alive", "alpha", "already", "alto", "aluminum", "always", "amazing", "ambition",
+    "amount", "amuse", "analysis", "anatomy", "ancestor", "ancient", "angel", "angry", "animal",
+    "answer", "antenna", "anxiety", "apart", "aquatic", "arcade", "arena", "argue", "armed",
+    "artist", "artwork", "aspect", "auction", "august", "aunt", "average", "aviation", "avoid",
+    "award", "away", "axis", "axle", "beam", "beard", "beaver", "become", "bedroom", "behavior",
+    "being", "believe", "belong", "benefit", "best", "beyond", "bike", "biology", "birthday",
+    "bishop", "black", "blanket", "blessing", "blimp", "blind", "blue", "body", "bolt", "boring",
+    "born", "both", "boundary", "bracelet", "branch", "brave", "breathe", "briefing", "broken",
+    "brother", "browser", "bucket", "budget", "building", "bulb", "bulge", "bumpy", "bundle",
+    "burden", "burning", "busy", "buyer", "cage", "calcium", "camera", "campus", "canyon",
+    "capacity", "capital", "capture", "carbon", "cards", "careful", "cargo", "carpet", "carve",
+    "category", "cause", "ceiling", "center", "ceramic", "champion", "change", "charity", "check",
+    "chemical", "chest", "chew", "chubby", "cinema", "civil", "class", "clay", "cleanup", "client",
+    "climate", "clinic", "clock", "clogs", "closet", "clothes", "club", "cluster", "coal",
+    "coastal", "coding", "column", "company", "corner", "costume", "counter", "course", "cover",
+    "cowboy", "cradle", "craft", "crazy", "credit", "cricket", "criminal", "crisis", "critical",
+    "crowd", "crucial", "crunch", "crush", "crystal", "cubic", "cultural", "curious", "curly",
+    "custody", "cylinder", "daisy", "damage", "dance", "darkness", "database", "daughter",
+    "deadline", "deal", "debris", "debut", "decent", "decision", "declare", "decorate", "decrease",
+    "deliver", "demand", "density", "deny", "depart", "depend", "depict", "deploy", "describe",
+    "desert", "desire", "desktop", "destroy", "detailed", "detect", "device", "devote", "diagnose",
+    "dictate", "diet", "dilemma", "diminish", "dining", "diploma", "disaster", "discuss",
+    "disease", "dish", "dismiss", "display", "distance", "dive", "divorce", "document", "domain",
+    "domestic", "dominant", "dough", "downtown", "dragon", "dramatic", "dream", "dress", "drift",
+    "drink", "drove", "drug", "dryer", "duckling", "duke", "duration", "dwarf", "dynamic", "early",
+    "earth", "easel", "easy", "echo", "eclipse", "ecology", "edge", "editor", "educate", "either",
+    "elbow", "elder", "election", "elegant", "element", "elephant", "elevator", "elite", "else",
+    "email", "emerald", "emission", "emperor", "emphasis", "employer", "empty", "ending",
+    "endless", "endorse", "enemy", "energy", "enforce", "engage", "enjoy", "enlarge", "entrance",
+    "envelope", "envy", "epidemic", "episode", "equation", "equip", "eraser", "erode", "escape",
+    "estate", "estimate", "evaluate", "evening", "evidence", "evil", "evoke", "exact", "example",
+    "exceed", "exchange", "exclude", "excuse", "execute", "exercise", "exhaust", "exotic",
+    "expand", "expect", "explain", "express", "extend", "extra", "eyebrow", "facility", "fact",
+    "failure", "faint", "fake", "false", "family", "famous", "fancy", "fangs", "fantasy", "fatal",
+    "fatigue", "favorite", "fawn", "fiber", "fiction", "filter", "finance", "findings", "finger",
+    "firefly", "firm", "fiscal", "fishing", "fitness", "flame", "flash", "flavor", "flea",
+    "flexible", "flip", "float", "floral", "fluff", "focus", "forbid", "force", "forecast",
+    "forget", "formal", "fortune", "forward", "founder", "fraction", "fragment", "frequent",
+    "freshman", "friar", "fridge", "friendly", "frost", "froth", "frozen", "fumes", "funding",
+    "furl", "fused", "galaxy", "game", "garbage", "garden", "garlic", "gasoline", "gather",
+    "general", "genius", "genre", "genuine", "geology", "gesture", "glad", "glance", "glasses",
+    "glen", "glimpse", "goat", "golden", "graduate", "grant", "grasp", "gravity", "gray",
+    "greatest", "grief", "grill", "grin", "grocery", "gross", "group", "grownup", "grumpy",
+    "guard", "guest", "guilt", "guitar", "gums", "hairy", "hamster", "hand", "hanger", "harvest",
+    "have", "havoc", "hawk", "hazard", "headset", "health", "hearing", "heat", "helpful", "herald",
+    "herd", "hesitate", "hobo", "holiday", "holy", "home", "hormone", "hospital", "hour", "huge",
+    "human", "humidity", "hunting", "husband", "hush", "husky", "hybrid", "idea", "identify",
+    "idle", "image", "impact", "imply", "improve", "impulse", "include", "income", "increase",
+    "index", "indicate", "industry", "infant", "inform", "inherit", "injury", "inmate", "insect",
+    "inside", "install", "intend", "intimate", "invasion", "involve", "iris", "island", "isolate",
+    "item", "ivory", "jacket", "jerky", "jewelry", "join", "judicial", "juice", "jump", "junction",
+    "junior", "junk", "jury", "justice", "kernel", "keyboard", "kidney", "kind", "kitchen",
+    "knife", "knit", "laden", "ladle", "ladybug", "lair", "lamp", "language", "large", "laser",
+    "laundry", "lawsuit", "leader", "leaf", "learn", "leaves", "lecture", "legal", "legend",
+    "legs", "lend", "length", "level", "liberty", "library", "license", "lift", "likely", "lilac",
+    "lily", "lips", "liquid", "listen", "literary", "living", "lizard", "loan", "lobe", "location",
+    "losing", "loud", "loyalty", "luck", "lunar", "lunch", "lungs", "luxury", "lying", "lyrics",
+    "machine", "magazine", "maiden", "mailman", "main", "makeup", "making", "mama", "manager",
+    "mandate", "mansion", "manual", "marathon", "march", "market", "marvel", "mason", "material",
+    "math", "maximum", "mayor", "meaning", "medal", "medical", "member", "memory", "mental",
+    "merchant", "merit", "method", "metric", "midst", "mild", "military", "mineral", "minister",
+    "miracle", "mixed", "mixture", "mobile", "modern", "modify", "moisture", "moment", "morning",
+    "mortgage", "mother", "mountain", "mouse", "move", "much", "mule", "multiple", "muscle",
+    "museum", "music", "mustang", "nail", "national", "necklace", "negative", "nervous", "network",
+    "news", "nuclear", "numb", "numerous", "nylon", "oasis", "obesity", "object", "observe",
+    "obtain", "ocean", "often", "olympic", "omit", "oral", "orange", "orbit", "order", "ordinary",
+    "organize", "ounce", "oven", "overall", "owner", "paces", "pacific", "package", "paid",
+    "painting", "pajamas", "pancake", "pants", "papa", "paper", "parcel", "parking", "party",
+    "patent", "patrol", "payment", "payroll", "peaceful", "peanut", "peasant", "pecan", "penalty",
+    "pencil", "percent", "perfect", "permit", "petition", "phantom", "pharmacy", "photo", "phrase",
+    "physics", "pickup", "picture", "piece", "pile", "pink", "pipeline", "pistol", "pitch",
+    "plains", "plan", "plastic", "platform", "playoff", "pleasure", "plot", "plunge", "practice",
+    "prayer", "preach", "predator", "pregnant", "premium", "prepare", "presence", "prevent",
+    "priest", "primary", "priority", "prisoner", "privacy", "prize", "problem", "process",
+    "profile", "program", "promise", "prospect", "provide", "prune", "public", "pulse", "pumps",
+    "punish", "puny", "pupal", "purchase", "purple", "python", "quantity", "quarter", "quick",
+    "quiet", "race", "racism", "radar", "railroad", "rainbow", "raisin", "random", "ranked",
+    "rapids", "raspy", "reaction", "realize", "rebound", "rebuild", "recall", "receiver",
+    "recover", "regret", "regular", "reject", "relate", "remember", "remind", "remove", "render",
+    "repair", "repeat", "replace", "require", "rescue", "research", "resident", "response",
+    "result", "retailer", "retreat", "reunion", "revenue", "review", "reward", "rhyme", "rhythm",
+    "rich", "rival", "river", "robin", "rocky", "romantic", "romp", "roster", "round", "royal",
+    "ruin", "ruler", "rumor", "sack", "safari", "salary", "salon", "salt", "satisfy", "satoshi",
+    "saver", "says", "scandal", "scared", "scatter", "scene", "scholar", "science", "scout",
+    "scramble", "screw", "script", "scroll", "seafood", "season", "secret", "security", "segment",
+    "senior", "shadow", "shaft", "shame", "shaped", "sharp", "shelter", "sheriff", "short",
+    "should", "shrimp", "sidewalk", "silent", "silver", "similar", "simple", "single", "sister",
+    "skin", "skunk", "slap", "slavery", "sled", "slice", "slim", "slow", "slush", "smart", "smear",
+    "smell", "smirk", "smith", "smoking", "smug", "snake", "snapshot", "sniff", "society",
+    "software", "soldier", "solution", "soul", "source", "space", "spark", "speak", "species",
+    "spelling", "spend", "spew", "spider", "spill", "spine", "spirit", "spit", "spray", "sprinkle",
+    "square", "squeeze", "stadium", "staff", "standard", "starting", "station", "stay", "steady",
+    "step", "stick", "stilt", "story", "strategy", "strike", "style", "subject", "submit", "sugar",
+    "suitable", "sunlight", "superior", "surface", "surprise", "survive", "sweater", "swimming",
+    "swing", "switch", "symbolic", "sympathy", "syndrome", "system", "tackle", "tactics",
+    "tadpole", "talent", "task", "taste", "taught", "taxi", "teacher", "teammate", "teaspoon",
+    "temple", "tenant", "tendency", "tension", "terminal", "testify", "texture", "thank", "that",
+    "theater", "theory", "therapy", "thorn", "threaten", "thumb", "thunder", "ticket", "tidy",
+    "timber", "timely", "ting", "tofu", "together", "tolerate", "total", "toxic", "tracks",
+    "traffic", "training", "transfer", "trash", "traveler", "treat", "trend", "trial", "tricycle",
+    "trip", "triumph", "trouble", "true", "trust", "twice", "twin", "type", "typical", "ugly",
+    "ultimate", "umbrella", "uncover", "undergo", "unfair", "unfold", "unhappy", "union",
+    "universe", "unkind", "unknown", "unusual", "unwrap", "upgrade", "upstairs", "username",
+    "usher", "usual", "valid", "valuable", "vampire", "vanish", "various", "vegan", "velvet",
+    "venture", "verdict", "verify", "very", "veteran", "vexed", "victim", "video", "view",
+    "vintage", "violence", "viral", "visitor", "visual", "vitamins", "vocal", "voice", "volume",
+    "voter", "voting", "walnut", "warmth", "warn", "watch", "wavy", "wealthy", "weapon", "webcam",
+    "welcome", "welfare", "western", "width", "wildlife", "window", "wine", "wireless", "wisdom",
+    "withdraw", "wits", "wolf", "woman", "work", "worthy", "wrap", "wrist", "writing", "wrote",
+    "year", "yelp", "yield", "yoga", "zero",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk_wallet::bitcoin::hex::FromHex;
+
+    #[test]
+    fn test_combine_vectors() {
+        // SLIP-39 test vectors, encrypted with the "TREZOR" passphrase.
+        let single = ["duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision keyboard".to_string()];
+        assert_eq!(
+            combine(&single, "TREZOR").unwrap(),
+            Vec::<u8>::from_hex("bb54aac4b89dc868ba37d9cc21b2cece").unwrap()
+        );
+
+        let two_of_three = [
+            "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed".to_string(),
+            "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip twice unkind craft early superior advocate guest smoking".to_string(),
+        ];
+        assert_eq!(
+            combine(&two_of_three, "TREZOR").unwrap(),
+            Vec::<u8>::from_hex("b43ceb7e57a0ea8766221624d01b0864").unwrap()
+        );
+
+        let err = combine(&two_of_three[..1], "TREZOR")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("group 0 has 1 of 2 shares"), "{err}");
+
+        let wrong_checksum = ["duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision kidney".to_string()];
+        let err = combine(&wrong_checksum, "TREZOR").unwrap_err().to_string();
+        assert!(err.contains("checksum"), "{err}");
+    }
+
+    #[test]
+    fn test_generate_and_combine_round_trip() {
+        let secret: Vec<u8> = (0..32).collect();
+        let groups = [
+            GroupSpec {
+                threshold: 2,
+                count: 3,
+            },
+            GroupSpec {
+                threshold: 1,
+                count: 1,
+            },
+            GroupSpec {
+                threshold: 3,
+                count: 5,
+            },
+        ];
+        let shares = generate(&secret, "passphrase", 2, &groups).unwrap();
+        assert_eq!(shares.iter().map(Vec::len).collect::<Vec<_>>(), [3, 1, 5]);
+        assert!(
+            shares
+                .iter()
+                .flatten()
+                .all(|share| share.split(' ').count() == 33)
+        );
+
+        let chosen = [
+            shares[0][2].clone(),
+            shares[2][4].clone(),
+            shares[0][0].clone(),
+            shares[2][1].clone(),
+            shares[2][2].clone(),
+        ];
+        assert_eq!(combine(&chosen, "passphrase").unwrap(), secret);
+        assert_ne!(combine(&chosen, "").unwrap(), secret);
+        assert_eq!(
+            combine(
+                &[
+                    shares[1][0].clone(),
+                    shares[0][1].clone(),
+                    shares[0][0].clone()
+                ],
+                "passphrase"
+            )
+            .unwrap(),
+            secret
+        );
+        assert!(combine(&chosen[..4], "passphrase").is_err());
+    }
+
+    #[test]
+    fn test_generate_rejects_invalid_groups() {
+        let secret = [0; 16];
+        let group = |threshold, count| GroupSpec { threshold, count };
+        assert!(generate(&secret, "", 1, &[group(1, 3)]).is_err());
+        assert!(generate(&secret, "", 2, &[group(2, 3)]).is_err());
+        assert!(generate(&secret, "", 1, &[group(4, 3)]).is_err());
+        assert!(generate(&secret[..15], "", 1, &[group(2, 3)]).is_err());
+        assert_eq!("3-of-5".parse::<GroupSpec>().unwrap(), group(3, 5));
+        assert!("3/5".parse::<GroupSpec>().is_err());
+    }
+}
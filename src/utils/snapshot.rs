@@ -0,0 +1,232 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Wallet snapshots
+//!
+//! A snapshot copies the sqlite database and the labels of a wallet into a directory of its
+//! `backups/` directory. The redb and Postgres wallets, whose database is not in their directory,
+//! have no snapshots. Operations that rewrite the wallet history, such as `rescan`, first take
+//! an automatic snapshot, named after the time and the operation, and only the most recent ones
+//! are kept. `wallet rollback` puts the last automatic snapshot back, and `wallet snapshot`
+//! manages named ones.
+//!
+//! Snapshots are never written to once taken, so a file equal to the one of the previous snapshot
+//! is hard linked to it rather than copied. Other files are copied with `fs::copy`, which shares
+//! the blocks of the copy on copy-on-write filesystems such as btrfs and XFS.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use crate::labels::LABELS_FILE;
+
+/// The directory of the snapshots, in the wallet directory.
+pub const BACKUPS_DIR: &str = "backups";
+/// The files of the wallet directory a snapshot copies, when they exist.
+pub const SNAPSHOT_FILES: [&str; 2] = ["wallet.sqlite", LABELS_FILE];
+/// Automatic snapshots kept per wallet, the oldest being deleted first.
+const KEEP_AUTOMATIC: usize = 5;
+const AUTOMATIC_PREFIX: &str = "auto-";
+
+/// Copy the wallet files of `wallet_dir` into a new snapshot, skipped when the wallet has none
+/// yet. Only the last automatic snapshots are kept.
+pub(crate) fn auto_snapshot(wallet_dir: &Path, operation: &str) -> Result<(), Error> {
+    if let Err(e) = check_database(wallet_dir) {
+        log::warn!("No snapshot taken before the {operation}: {e}");
+        return Ok(());
+    }
+    if !SNAPSHOT_FILES
+        .iter()
+        .any(|file| wallet_dir.join(file).exists())
+    {
+        return Ok(());
+    }
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default();
+    let name = format!("{AUTOMATIC_PREFIX}{millis:013}-{operation}");
+    let dir = create(wallet_dir, &name)?;
+    log::info!("Saved a snapshot of the wallet in {}", dir.display());
+
+    let automatic = automatic_snapshots(wallet_dir)?;
+    for old in automatic.iter().rev().skip(KEEP_AUTOMATIC) {
+        fs::remove_dir_all(old).map_err(|e| {
+            Error::coded(
+                ErrorCode::Io,
+                format!("Failed to remove {}: {e}", old.display()),
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Fails for the wallets whose database a snapshot cannot hold, as it is not a file of their
+/// directory: the redb wallets share the `wallet.redb` of the data directory, and the Postgres
+/// ones are on a server.
+pub(crate) fn check_database(
+    #[cfg_attr(
+        not(any(feature = "sqlite", feature = "redb")),
+        allow(unused_variables)
+    )]
+    wallet_dir: &Path,
+) -> Result<(), Error> {
+    #[cfg(any(feature = "sqlite", feature = "redb"))]
+    if let (Some(datadir), Some(wallet)) = (wallet_dir.parent(), wallet_dir.file_name())
+        && let Ok((wallet_opts, _)) =
+            crate::utils::load_wallet_config(datadir, &wallet.to_string_lossy())
+    {
+        let database = match wallet_opts.database_type.to_string().as_str() {
+            "sqlite" => return Ok(()),
+            "redb" => "the wallet.redb shared by the wallets of the data directory".to_string(),
+            url => format!("the Postgres database {url}"),
+        };
+        return Err(Error::coded(
+            ErrorCode::Database,
+            format!(
+                "Snapshots only hold sqlite wallets, and wallet '{}' is in {database}; back up the \
+             database itself instead",
+                wallet.to_string_lossy()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Copy the wallet files of `wallet_dir` into the snapshot `name`, which must not exist yet.
+pub(crate) fn create(wallet_dir: &Path, name: &str) -> Result<PathBuf, Error> {
+    let dir = wallet_dir.join(BACKUPS_DIR).join(name);
+    if dir.exists() {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!("Snapshot '{name}' already exists in {}", dir.display()),
+        ));
+    }
+    let previous = snapshots(wallet_dir)?.pop();
+    fs::create_dir_all(&dir).map_err(|e| {
+        Error::coded(
+            ErrorCode::Io,
+            format!("Failed to create {}: {e}", dir.display()),
+        )
+    })?;
+    for file in SNAPSHOT_FILES {
+        let source = wallet_dir.join(file);
+        if !source.exists() {
+            continue;
+        }
+        let unchanged = previous
+            .as_ref()
+            .map(|previous| previous.join(file))
+            .filter(|previous| same_content(&source, previous));
+        let result = match unchanged {
+            Some(previous) => fs::hard_link(&previous, dir.join(file)),
+            None => fs::copy(&source, dir.join(file)).map(|_| ()),
+        };
+        result.map_err(|e| {
+            Error::coded(
+                ErrorCode::Io,
+                format!("Failed to copy {}: {e}", source.display()),
+            )
+        })?;
+    }
+    Ok(dir)
+}
+
+/// Whether the files `a` and `b` both exist with the same content.
+fn same_content(a: &Path, b: &Path) -> bool {
+    let len = |path: &Path| fs::metadata(path).map(|metadata| metadata.len()).ok();
+    len(a).is_some() && len(a) == len(b) && fs::read(a).ok() == fs::read(b).ok()
+}
+
+/// Put the files of the snapshot `dir` back into `wallet_dir`, removing the wallet files the
+/// snapshot does not hold.
+pub(crate) fn restore(wallet_dir: &Path, dir: &Path) -> Result<(), Error> {
+    for file in SNAPSHOT_FILES {
+        let (source, target) = (dir.join(file), wallet_dir.join(file));
+        let result = if source.exists() {
+            fs::copy(&source, &target).map(|_| ())
+        } else if target.exists() {
+            fs::remove_file(&target)
+        } else {
+            Ok(())
+        };
+        result.map_err(|e| {
+            Error::coded(
+                ErrorCode::Io,
+                format!("Failed to restore {}: {e}", target.display()),
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// The automatic snapshots of `wallet_dir`, oldest first.
+pub(crate) fn automatic_snapshots(wallet_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut snapshots: Vec<PathBuf> = snapshots(wallet_dir)?
+        .into_iter()
+        .filter(|path| is_automatic(path))
+        .collect();
+    // The zero padded times sort the names in creation order.
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// The snapshots of `wallet_dir`, automatic and named, oldest first.
+pub(crate) fn snapshots(wallet_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let backups = wallet_dir.join(BACKUPS_DIR);
+    if !backups.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = fs::read_dir(&backups).map_err(|e| {
+        Error::coded(
+            ErrorCode::Io,
+            format!("Failed to read {}: {e}", backups.display()),
+        )
+    })?;
+    let mut snapshots: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir())
+        .map(|path| (created_at(&path), path))
+        .collect();
+    snapshots.sort();
+    Ok(snapshots.into_iter().map(|(_, path)| path).collect())
+}
+
+/// When the snapshot `dir` was taken, the last change of its directory.
+pub(crate) fn created_at(dir: &Path) -> SystemTime {
+    fs::metadata(dir)
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or(UNIX_EPOCH)
+}
+
+/// Whether the snapshot `dir` was taken before an operation rather than by name.
+pub(crate) fn is_automatic(dir: &Path) -> bool {
+    dir.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with(AUTOMATIC_PREFIX))
+}
+
+/// Fails for the snapshot names that are not a plain directory name, or that automatic
+/// snapshots use.
+pub(crate) fn check_name(name: &str) -> Result<(), Error> {
+    let plain = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !name.starts_with('.');
+    if !plain || name.starts_with(AUTOMATIC_PREFIX) {
+        return Err(Error::coded(
+            ErrorCode::InvalidArgument,
+            format!(
+                "Invalid snapshot name '{name}': use letters, digits, '-', '_' and '.', \
+             without the '{AUTOMATIC_PREFIX}' prefix of automatic snapshots"
+            ),
+        ));
+    }
+    Ok(())
+}
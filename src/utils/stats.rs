@@ -0,0 +1,277 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Command statistics
+//!
+//! With `--stats`, the wall time of the phases of a command and the work asked of the backend are
+//! written on stderr once the command ends, to tell a slow backend from slow local processing.
+//! The phases are timed where the wallet is loaded, synced, funded, signed and persisted, and the
+//! time spent elsewhere is reported as `other`. Requests count the calls made to the backend: a
+//! batch of the Electrum client is one request whatever the number of scripts it looks up, while
+//! an Esplora server is asked for each script and each block is fetched from a node on its own.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Whether `--stats` was given.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the statistics are written as one JSON line rather than as a table.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+static STATS: Mutex<Stats> = Mutex::new(Stats::new());
+
+/// A timed phase of a command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    WalletLoad,
+    NetworkSync,
+    CoinSelection,
+    Signing,
+    Persistence,
+}
+
+impl Phase {
+    const ALL: [Phase; 5] = [
+        Phase::WalletLoad,
+        Phase::NetworkSync,
+        Phase::CoinSelection,
+        Phase::Signing,
+        Phase::Persistence,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Phase::WalletLoad => "wallet load",
+            Phase::NetworkSync => "network sync",
+            Phase::CoinSelection => "coin selection",
+            Phase::Signing => "signing",
+            Phase::Persistence => "persistence",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Stats {
+    started: Option<Instant>,
+    elapsed: [Duration; 5],
+    /// The running timers of each phase, only the outermost of nested timers being counted.
+    running: [usize; 5],
+    requests: u64,
+    scripts: u64,
+}
+
+impl Stats {
+    const fn new() -> Self {
+        Stats {
+            started: None,
+            elapsed: [Duration::ZERO; 5],
+            running: [0; 5],
+            requests: 0,
+            scripts: 0,
+        }
+    }
+}
+
+/// The statistics of a command, in milliseconds.
+#[derive(Debug, PartialEq, Serialize)]
+struct Report {
+    wallet_load_ms: f64,
+    network_sync_ms: f64,
+    coin_selection_ms: f64,
+    signing_ms: f64,
+    persistence_ms: f64,
+    other_ms: f64,
+    total_ms: f64,
+    requests: u64,
+    scripts_scanned: u64,
+}
+
+impl Report {
+    fn new(stats: &Stats, total: Duration) -> Self {
+        let ms = |duration: Duration| (duration.as_secs_f64() * 10_000.0).round() / 10.0;
+        let phases: Duration = stats.elapsed.iter().sum();
+        Report {
+            wallet_load_ms: ms(stats.elapsed[Phase::WalletLoad as usize]),
+            network_sync_ms: ms(stats.elapsed[Phase::NetworkSync as usize]),
+            coin_selection_ms: ms(stats.elapsed[Phase::CoinSelection as usize]),
+            signing_ms: ms(stats.elapsed[Phase::Signing as usize]),
+            persistence_ms: ms(stats.elapsed[Phase::Persistence as usize]),
+            other_ms: ms(total.saturating_sub(phases)),
+            total_ms: ms(total),
+            requests: stats.requests,
+            scripts_scanned: stats.scripts,
+        }
+    }
+
+    fn table(&self) -> String {
+        let times = [
+            self.wallet_load_ms,
+            self.network_sync_ms,
+            self.coin_selection_ms,
+            self.signing_ms,
+            self.persistence_ms,
+        ];
+        let mut lines = vec!["Statistics:".to_string()];
+        for (phase, ms) in Phase::ALL.iter().zip(times) {
+            lines.push(format!("  {:<16}{ms:>10.1} ms", phase.name()));
+        }
+        lines.push(format!("  {:<16}{:>10.1} ms", "other", self.other_ms));
+        lines.push(format!("  {:<16}{:>10.1} ms", "total", self.total_ms));
+        lines.push(format!("  {:<16}{:>10}", "requests", self.requests));
+        lines.push(format!(
+            "  {:<16}{:>10}",
+            "scripts scanned", self.scripts_scanned
+        ));
+        lines.join("\n")
+    }
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Stats> {
+    STATS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Collects the statistics of the commands from now on, written as JSON when `json`.
+pub fn enable(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+    ENABLED.store(true, Ordering::Relaxed);
+    lock().started = Some(Instant::now());
+}
+
+/// Stops collecting the statistics, once the REPL has reported the ones of its last line.
+#[cfg(feature = "repl")]
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Times a phase until dropped.
+#[must_use]
+pub(crate) struct Timer {
+    phase: Phase,
+    counted: bool,
+    started: Option<Instant>,
+}
+
+/// Starts timing `phase`, a timer nested in another one of the same phase adding no time.
+pub(crate) fn timer(phase: Phase) -> Timer {
+    if !enabled() {
+        return Timer {
+            phase,
+            counted: false,
+            started: None,
+        };
+    }
+    let mut stats = lock();
+    stats.running[phase as usize] += 1;
+    Timer {
+        phase,
+        counted: true,
+        started: (stats.running[phase as usize] == 1).then(Instant::now),
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        if !self.counted {
+            return;
+        }
+        let mut stats = lock();
+        stats.running[self.phase as usize] -= 1;
+        if let Some(started) = self.started {
+            stats.elapsed[self.phase as usize] += started.elapsed();
+        }
+    }
+}
+
+/// Counts `count` requests made to the backend.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub(crate) fn add_requests(count: u64) {
+    if enabled() {
+        lock().requests += count;
+    }
+}
+
+/// Counts a script looked up by a sync or a scan, its request being counted apart.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "rpc",
+    feature = "cbf"
+))]
+pub(crate) fn add_script() {
+    if enabled() {
+        lock().scripts += 1;
+    }
+}
+
+/// Writes the statistics of the command that just ended on stderr, and starts collecting the
+/// ones of the next command, such as the next line of the REPL.
+pub fn report() {
+    if !enabled() {
+        return;
+    }
+    let mut stats = lock();
+    let now = Instant::now();
+    let total = stats
+        .started
+        .map(|started| now.duration_since(started))
+        .unwrap_or_default();
+    let report = Report::new(&stats, total);
+    let running = stats.running;
+    *stats = Stats::new();
+    stats.started = Some(now);
+    stats.running = running;
+    drop(stats);
+
+    if JSON.load(Ordering::Relaxed) {
+        match serde_json::to_string(&report) {
+            Ok(json) => eprintln!("{json}"),
+            Err(e) => eprintln!("Cannot write the statistics: {e}"),
+        }
+    } else {
+        eprintln!("{}", report.table());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_of_phases() {
+        let mut stats = Stats::new();
+        stats.elapsed[Phase::NetworkSync as usize] = Duration::from_millis(1200);
+        stats.elapsed[Phase::Signing as usize] = Duration::from_micros(2560);
+        stats.requests = 41;
+        stats.scripts = 40;
+
+        let report = Report::new(&stats, Duration::from_millis(1250));
+        assert_eq!(report.network_sync_ms, 1200.0);
+        assert_eq!(report.signing_ms, 2.6);
+        assert_eq!(report.other_ms, 47.4);
+        assert_eq!(report.total_ms, 1250.0);
+
+        let table = report.table();
+        assert!(table.contains("  network sync        1200.0 ms"));
+        assert!(table.contains("  scripts scanned         40"));
+    }
+}
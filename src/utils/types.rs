@@ -1,6 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::config::WalletConfigInner;
+use crate::utils::amount;
+use crate::utils::price::{FiatValue, fiat_value};
+use crate::utils::psbt::{PsbtVersion, serialize_psbt};
 use bdk_wallet::Balance;
 use bdk_wallet::bitcoin::{
     Network, Psbt, Transaction, base64::Engine, consensus::encode::serialize_hex,
@@ -11,6 +14,7 @@ use serde_json::json;
 
 /// Represent address result
 #[derive(Serialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct AddressResult {
     pub address: String,
     pub index: u32,
@@ -25,15 +29,34 @@ impl From<AddressInfo> for AddressResult {
     }
 }
 
+/// External address found by a vanity search
+#[derive(Serialize)]
+pub struct VanityResult {
+    pub address: String,
+    pub index: u32,
+    /// Number of addresses derived before the match.
+    pub tried: u32,
+    /// Whether the addresses up to the match were revealed.
+    pub reserved: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
 #[allow(unused)]
 /// Represents the data for a single transaction
 #[derive(Serialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct TransactionDetails {
     pub txid: String,
     pub is_coinbase: bool,
     pub wtxid: String,
     pub version: serde_json::Value,
     pub is_rbf: bool,
+    /// The satoshis the transaction moves into the wallet, negative when it spends from it.
+    #[serde(serialize_with = "amount::serialize_signed")]
+    #[cfg_attr(feature = "server", schemars(with = "amount::SignedAmountSchema"))]
+    pub net: i64,
+    pub confirmed: bool,
     pub inputs: serde_json::Value,
     pub outputs: serde_json::Value,
     #[serde(skip)]
@@ -44,10 +67,14 @@ pub struct TransactionDetails {
     pub output_count: usize,
     #[serde(skip)]
     pub total_value: u64,
+    /// The value of the net amount the transaction moves in or out of the wallet, with `--fiat`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<FiatValue>,
 }
 
 /// single UTXO
 #[derive(Serialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct UnspentDetails {
     pub outpoint: String,
     pub txout: serde_json::Value,
@@ -61,10 +88,12 @@ pub struct UnspentDetails {
 impl UnspentDetails {
     pub fn from_local_output(utxo: &LocalOutput, _network: Network, is_locked: bool) -> Self {
         let outpoint_str = utxo.outpoint.to_string();
+        let mut txout = serde_json::to_value(&utxo.txout).unwrap_or(json!({}));
+        txout["value"] = amount::to_value(utxo.txout.value.to_sat());
 
         Self {
             outpoint: outpoint_str.clone(),
-            txout: serde_json::to_value(&utxo.txout).unwrap_or(json!({})),
+            txout,
             keychain: format!("{:?}", utxo.keychain),
             is_spent: utxo.is_spent,
             derivation_index: utxo.derivation_index,
@@ -74,23 +103,234 @@ impl UnspentDetails {
     }
 }
 
+/// Classification of the wallet UTXOs by spending cost and age
+#[derive(Serialize)]
+pub struct UtxoReport {
+    pub fee_rate: f32,
+    pub dust_count: usize,
+    #[serde(serialize_with = "amount::serialize")]
+    pub dust_value: u64,
+    #[serde(serialize_with = "amount::serialize")]
+    pub economical_value: u64,
+    pub utxos: Vec<UtxoReportEntry>,
+}
+
+#[derive(Serialize)]
+pub struct UtxoReportEntry {
+    pub outpoint: String,
+    pub keychain: String,
+    #[serde(serialize_with = "amount::serialize")]
+    pub value: u64,
+    /// Fee needed to spend this UTXO at the report fee rate.
+    #[serde(serialize_with = "amount::serialize")]
+    pub spend_cost: u64,
+    /// Whether spending this UTXO costs at least as much as it is worth.
+    pub is_dust: bool,
+    pub confirmations: u32,
+    /// One of `unconfirmed`, `fresh` (<6 confs), `recent` (<30 days) or `aged`.
+    pub age: String,
+}
+
+/// Unused address gap of a single keychain
+#[derive(Serialize)]
+pub struct GapDetails {
+    pub keychain: String,
+    pub last_revealed_index: Option<u32>,
+    pub last_used_index: Option<u32>,
+    pub largest_unused_gap: u32,
+    pub trailing_unused_gap: u32,
+    pub stop_gap: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+/// A CSV or CLTV spending condition of the wallet descriptor
+#[derive(Serialize)]
+pub struct TimelockDetails {
+    pub keychain: String,
+    pub policy_id: String,
+    /// Either `absolute` (CLTV) or `relative` (CSV).
+    pub kind: String,
+    /// Consensus encoded timelock value.
+    pub value: u32,
+    /// Status of an absolute timelock, which does not depend on the spent UTXO.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<TimelockStatus>,
+    /// Status of a relative timelock for each UTXO of the keychain.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub utxos: Vec<UtxoTimelockStatus>,
+}
+
 #[derive(Serialize)]
+pub struct UtxoTimelockStatus {
+    pub outpoint: String,
+    pub confirmation_height: Option<u32>,
+    #[serde(flatten)]
+    pub status: TimelockStatus,
+}
+
+#[derive(Serialize)]
+pub struct TimelockStatus {
+    pub satisfiable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unlock_height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unlock_time: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks_remaining: Option<u32>,
+    /// Unix timestamp of the expected unlock, assuming ten minute blocks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_unlock_time: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct PsbtResult {
     pub psbt: String,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_finalized: Option<bool>,
+
+    /// The value of the amount the transaction takes out of the wallet, fee included, with
+    /// `--fiat`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<FiatValue>,
 }
 
 impl PsbtResult {
     pub fn new(psbt: &Psbt, finalized: Option<bool>) -> Self {
+        Self::with_version(psbt, finalized, PsbtVersion::V0)
+    }
+
+    pub fn with_version(psbt: &Psbt, finalized: Option<bool>, version: PsbtVersion) -> Self {
         Self {
-            psbt: bdk_wallet::bitcoin::base64::prelude::BASE64_STANDARD.encode(psbt.serialize()),
+            psbt: bdk_wallet::bitcoin::base64::prelude::BASE64_STANDARD
+                .encode(serialize_psbt(psbt, version)),
             is_finalized: finalized,
+            fiat: None,
         }
     }
 }
 
+#[derive(Serialize)]
+pub struct CombinedPsbtResult {
+    #[serde(flatten)]
+    pub psbt: PsbtResult,
+    pub conflicts: Vec<PsbtConflict>,
+}
+
+#[derive(Serialize)]
+pub struct FinalizedPsbtResult {
+    #[serde(flatten)]
+    pub psbt: PsbtResult,
+    pub inputs: Vec<InputFinalization>,
+}
+
+#[derive(Serialize)]
+pub struct InputFinalization {
+    pub index: usize,
+    pub is_finalized: bool,
+    /// Why the input could not be finalized.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Who signed each input of a PSBT
+#[derive(Serialize)]
+pub struct PsbtSigners {
+    pub inputs: Vec<InputSigners>,
+}
+
+#[derive(Serialize)]
+pub struct InputSigners {
+    pub index: usize,
+    pub is_finalized: bool,
+    /// Signatures the multisig script of the input needs, when it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<usize>,
+    pub signed: Vec<Cosigner>,
+    /// Expected cosigners that have not signed yet, none once the threshold is met.
+    pub outstanding: Vec<Cosigner>,
+}
+
+#[derive(Serialize)]
+pub struct Cosigner {
+    pub fingerprint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+}
+
+/// Differences between two PSBTs
+#[derive(Serialize)]
+pub struct PsbtDiff {
+    pub identical: bool,
+    /// Changed global fields, including the transaction version and lock time.
+    pub global: Vec<FieldDiff>,
+    /// Inputs that differ, matched by the outpoint they spend.
+    pub inputs: Vec<EntryDiff>,
+    /// Outputs that differ, matched by script and amount.
+    pub outputs: Vec<EntryDiff>,
+}
+
+/// An input or output that differs between two PSBTs
+#[derive(Serialize)]
+pub struct EntryDiff {
+    /// Position in the first PSBT.
+    pub first: Option<usize>,
+    /// Position in the second PSBT.
+    pub second: Option<usize>,
+    /// The outpoint spent by an input, or the address or script of an output.
+    pub entry: String,
+    pub change: Change,
+    pub fields: Vec<FieldDiff>,
+}
+
+#[derive(Serialize)]
+pub struct FieldDiff {
+    pub field: String,
+    /// Key data following the key type, such as the public key of a signature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    pub change: Change,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// State of a multisig coordination session
+#[derive(Serialize)]
+pub struct MultisigStatus {
+    pub session: String,
+    pub txid: String,
+    pub cosigners: Vec<CosignerStatus>,
+    pub contributions: usize,
+    /// Whether enough signatures were collected to finalize every input.
+    pub ready: bool,
+    pub is_finalized: bool,
+    pub psbt: String,
+}
+
+#[derive(Serialize)]
+pub struct CosignerStatus {
+    pub fingerprint: String,
+    /// Whether the cosigner signed every input it has a key in.
+    pub signed: bool,
+}
+
+/// An input field on which the combined PSBTs disagree
+#[derive(Serialize)]
+pub struct PsbtConflict {
+    pub input: usize,
+    pub field: String,
+    /// Position of the PSBT that disagreed with the ones before it.
+    pub psbt: usize,
+}
+
 #[derive(Serialize)]
 pub struct RawPsbt {
     pub raw_tx: String,
@@ -104,12 +344,113 @@ impl RawPsbt {
     }
 }
 
+/// Human readable breakdown of a PSBT
+#[derive(Serialize)]
+pub struct DecodedPsbt {
+    pub psbt_version: u32,
+    pub txid: String,
+    pub version: i32,
+    pub lock_time: u32,
+    /// Sum of the input values, if every input carries its previous output.
+    #[serde(serialize_with = "amount::serialize_option")]
+    pub total_input: Option<u64>,
+    #[serde(serialize_with = "amount::serialize")]
+    pub total_output: u64,
+    #[serde(serialize_with = "amount::serialize_option")]
+    pub fee: Option<u64>,
+    pub is_finalized: bool,
+    pub inputs: Vec<DecodedPsbtInput>,
+    pub outputs: Vec<DecodedPsbtOutput>,
+}
+
+#[derive(Serialize)]
+pub struct DecodedPsbtInput {
+    pub index: usize,
+    pub outpoint: String,
+    #[serde(serialize_with = "amount::serialize_option")]
+    pub value: Option<u64>,
+    pub address: Option<String>,
+    pub sequence: u32,
+    pub sighash_type: Option<String>,
+    pub key_origins: Vec<KeyOrigin>,
+    /// Public keys that already provided a partial signature.
+    pub partial_signatures: Vec<String>,
+    pub has_taproot_key_signature: bool,
+    pub is_finalized: bool,
+}
+
+#[derive(Serialize)]
+pub struct DecodedPsbtOutput {
+    pub index: usize,
+    #[serde(serialize_with = "amount::serialize")]
+    pub value: u64,
+    pub address: Option<String>,
+    pub script_pubkey: String,
+    pub key_origins: Vec<KeyOrigin>,
+}
+
+#[derive(Serialize)]
+pub struct KeyOrigin {
+    pub pubkey: String,
+    pub fingerprint: String,
+    pub path: String,
+}
+
+/// Readiness report of a PSBT
+#[derive(Serialize)]
+pub struct PsbtAnalysis {
+    /// Role needed to move the PSBT forward: `updater`, `signer`, `finalizer` or `extractor`.
+    pub next: String,
+    #[serde(serialize_with = "amount::serialize_option")]
+    pub fee: Option<u64>,
+    /// Estimated vsize once every input is satisfied, if every input weight is known.
+    pub estimated_vsize: Option<u64>,
+    /// Estimated final fee rate in sat/vB.
+    pub estimated_fee_rate: Option<f64>,
+    pub inputs: Vec<InputAnalysis>,
+}
+
+#[derive(Serialize)]
+pub struct InputAnalysis {
+    pub index: usize,
+    pub has_utxo: bool,
+    pub is_final: bool,
+    pub next: String,
+    pub missing_signatures: Vec<MissingSignature>,
+}
+
+#[derive(Serialize)]
+pub struct MissingSignature {
+    pub pubkey: String,
+    pub fingerprint: String,
+    pub path: String,
+    /// Whether the key belongs to the wallet given with `--wallet`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_ours: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct ExtractedTxResult {
+    pub txid: String,
+    pub raw_tx: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
 #[derive(Serialize)]
 pub struct KeychainPair<T> {
     pub external: T,
     pub internal: T,
 }
 
+#[derive(Serialize)]
+pub struct PublicDescriptorResult {
+    #[serde(flatten)]
+    pub descriptors: KeychainPair<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multipath: Option<String>,
+}
+
 #[cfg(feature = "bip322")]
 #[derive(Serialize, Debug, Default)]
 pub struct MessageResult {
@@ -128,6 +469,137 @@ pub struct StatusResult {
     pub message: String,
 }
 
+/// Result of `sync`, with the cross-check of `--verify-with` when asked.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+#[derive(Serialize, Debug)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct SyncResult {
+    pub message: String,
+    #[cfg(any(feature = "electrum", feature = "esplora"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<SyncVerification>,
+}
+
+/// Comparison of the synced wallet with an independent server.
+#[cfg(any(feature = "electrum", feature = "esplora"))]
+#[derive(Serialize, Debug)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct SyncVerification {
+    pub server: String,
+    pub transactions: usize,
+    pub utxos: usize,
+    pub discrepancies: Vec<SyncDiscrepancy>,
+}
+
+/// A transaction, or a UTXO when `vout` is set, the wallet backend and the verifying server
+/// disagree on.
+#[cfg(any(feature = "electrum", feature = "esplora"))]
+#[derive(Serialize, Debug)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct SyncDiscrepancy {
+    pub txid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vout: Option<u32>,
+    pub primary: String,
+    pub verifier: String,
+}
+
+/// Result of `rebroadcast`, parents before the transactions spending them.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+#[derive(Serialize, Debug)]
+pub struct RebroadcastResult {
+    pub transactions: Vec<RebroadcastedTx>,
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+#[derive(Serialize, Debug)]
+pub struct RebroadcastedTx {
+    pub txid: String,
+    pub status: RebroadcastStatus,
+    /// Why the backend refused the transaction, such as an eviction for a low fee.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RebroadcastStatus {
+    Accepted,
+    AlreadyKnown,
+    Rejected,
+}
+
+/// Measurements of one electrum or esplora server by `backend bench`, in milliseconds, each unset
+/// when the server failed before it, and the broadcast also without `--tx`.
+#[cfg(any(feature = "electrum", feature = "esplora"))]
+#[derive(Serialize, Debug)]
+pub struct ServerBench {
+    pub url: String,
+    pub backend: String,
+    pub connect_ms: Option<f64>,
+    pub header_ms: Option<f64>,
+    pub scripts: usize,
+    pub scripts_per_sec: Option<f64>,
+    pub broadcast_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of `estimate_fee`, the fee rate in sat/vB.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+#[derive(Serialize, Debug)]
+pub struct FeeEstimateResult {
+    pub provider: String,
+    pub target_blocks: u16,
+    pub fee_rate: f64,
+}
+
+/// Result of `faucet`.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+#[derive(Serialize, Debug)]
+pub struct FaucetResult {
+    pub faucet: String,
+    pub address: String,
+    #[serde(serialize_with = "amount::serialize")]
+    pub amount: u64,
+    /// The transaction of the faucet, when its reply gives it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub txid: Option<String>,
+    /// Whether the transaction reached the wallet, always false with `--no-wait`.
+    pub received: bool,
+}
+
 #[cfg(any(
     feature = "electrum",
     feature = "esplora",
@@ -135,8 +607,27 @@ pub struct StatusResult {
     feature = "rpc"
 ))]
 #[derive(Serialize, Debug)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct TransactionResult {
     pub txid: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub endpoints: Vec<BroadcastEndpoint>,
+}
+
+/// Whether one backend of `broadcast --all-backends` or `--server` accepted the transaction.
+#[cfg(any(
+    feature = "electrum",
+    feature = "esplora",
+    feature = "cbf",
+    feature = "rpc"
+))]
+#[derive(Serialize, Debug)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct BroadcastEndpoint {
+    pub server: String,
+    pub accepted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 /// Return type definition
@@ -144,6 +635,141 @@ pub struct TransactionResult {
 #[serde(transparent)]
 pub struct WalletsListResult(pub HashMap<String, WalletConfigInner>);
 
+/// A command of a `run` script, with the outputs it wrote.
+#[derive(Serialize)]
+pub struct BatchResult {
+    pub command: String,
+    pub output: Vec<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Where `paths` finds the files of bdk-cli.
+#[derive(Serialize)]
+pub struct PathsResult {
+    pub datadir: String,
+    /// `--datadir`, `BDK_CLI_DATADIR`, `~/.bdk-bitcoin` or `system data directory`.
+    pub source: String,
+    pub config: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wallet: Option<WalletPaths>,
+}
+
+/// The files of a wallet, which may not exist yet.
+#[derive(Serialize)]
+pub struct WalletPaths {
+    pub name: String,
+    pub directory: String,
+    pub database: Option<String>,
+    pub labels: String,
+    pub keystore: String,
+    pub backups: String,
+}
+
+/// A backup bundle written by `wallet backup` or read by `wallet restore`.
+#[derive(Serialize)]
+pub struct BackupResult {
+    pub wallet: String,
+    pub network: String,
+    pub file: String,
+    pub encrypted: bool,
+    /// The files of the bundle.
+    pub contents: Vec<String>,
+}
+
+/// The changes persisted in a wallet database, from `wallet debug changesets`.
+#[derive(Serialize)]
+pub struct ChangesetDump {
+    pub network: Option<String>,
+    pub external_descriptor: Option<String>,
+    pub internal_descriptor: Option<String>,
+    /// Last revealed index by keychain, or by descriptor id for unknown descriptors.
+    pub last_revealed: BTreeMap<String, u32>,
+    pub blocks: Vec<ChangesetBlock>,
+    pub transactions: Vec<ChangesetTx>,
+    /// Outputs known without their transaction.
+    pub floating_txouts: Vec<ChangesetTxOut>,
+}
+
+#[derive(Serialize)]
+pub struct ChangesetBlock {
+    pub height: u32,
+    /// Missing for a block removed from the chain by a reorg.
+    pub hash: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ChangesetTx {
+    pub txid: String,
+    /// Whether the whole transaction is stored, rather than only its anchors or timestamps.
+    pub stored: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<usize>,
+    pub anchors: Vec<ChangesetAnchor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_evicted: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ChangesetAnchor {
+    pub height: u32,
+    pub block_hash: String,
+    pub confirmation_time: u64,
+}
+
+#[derive(Serialize)]
+pub struct ChangesetTxOut {
+    pub outpoint: String,
+    pub value: u64,
+    pub script_pubkey: String,
+}
+
+/// Result of `wallet db vacuum`, with the database sizes in bytes.
+#[derive(Serialize)]
+pub struct VacuumResult {
+    pub database: String,
+    pub size_before: u64,
+    pub size_after: u64,
+    pub reclaimed: u64,
+    /// The transactions deleted with `--prune-older-than`.
+    pub pruned: Vec<String>,
+}
+
+/// A wallet snapshot, with the wallet files it holds.
+#[derive(Serialize)]
+pub struct SnapshotResult {
+    pub wallet: String,
+    pub snapshot: String,
+    pub files: Vec<String>,
+}
+
+/// Result of `wallet migrate`.
+#[derive(Serialize)]
+pub struct MigrateResult {
+    pub wallet: String,
+    /// The bdk-cli 0.x database read.
+    pub from: String,
+    pub last_revealed: KeychainPair<Option<u32>>,
+    pub transactions: usize,
+    pub message: String,
+}
+
+/// A snapshot listed by `wallet snapshot list`.
+#[derive(Serialize)]
+pub struct SnapshotEntry {
+    pub name: String,
+    /// Whether the snapshot was taken before an operation rather than by name.
+    pub automatic: bool,
+    pub created_at: u64,
+    pub files: Vec<String>,
+}
+
 /// return type
 #[derive(Serialize)]
 pub struct DescriptorResult {
@@ -168,6 +794,91 @@ pub struct DescriptorResult {
     /// Randomness factor `r` used to derive the taproot unspendable internal key (H + rG).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub r: Option<String>,
+
+    /// Signers, timelocks and cost of each way to spend the compiled policy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spending_paths: Option<Vec<SpendingPathAnalysis>>,
+}
+
+/// A way to satisfy a spending policy
+#[derive(Serialize)]
+pub struct SpendingPathAnalysis {
+    pub signers: Vec<String>,
+    /// Hashes whose preimage must be revealed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub preimages: Vec<String>,
+    /// Consensus encoded absolute timelock (CLTV) of the path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<u32>,
+    /// Consensus encoded relative timelock (CSV) of the path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub older: Option<u32>,
+    /// Maximum weight of the scriptSig and witness, in weight units.
+    pub satisfaction_weight: usize,
+    /// Virtual size of an input spent through this path.
+    pub input_vbytes: u64,
+    /// Fee paid for the input at the requested fee rate, in satoshis.
+    pub fee: u64,
+}
+
+/// A validated descriptor
+#[derive(Serialize)]
+pub struct DescriptorCheck {
+    /// The descriptor as given, with its checksum.
+    pub descriptor: String,
+    pub checksum: String,
+    /// The descriptor as written by the parser, with its own checksum.
+    pub canonical: String,
+    pub script_type: String,
+    pub warnings: Vec<String>,
+}
+
+/// Extended keys and their origins
+#[derive(Serialize)]
+pub struct KeyInfoResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub descriptor: Option<String>,
+    pub keys: Vec<KeyInfo>,
+}
+
+#[derive(Serialize)]
+pub struct KeyInfo {
+    /// The public key expression.
+    pub key: String,
+    pub is_private: bool,
+    /// Fingerprint of the master key, from the key origin or of a depth 0 key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub master_fingerprint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin_path: Option<String>,
+    /// Fingerprint of the extended key itself.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_fingerprint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub child_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    /// Paths derived from the extended key, relative to it.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub derivation_paths: Vec<String>,
+}
+
+/// Child entropy derived from a master key with BIP-85
+#[derive(Serialize)]
+pub struct Bip85Result {
+    /// Fingerprint of the master key.
+    pub fingerprint: String,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mnemonic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wif: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hex: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -180,18 +891,183 @@ pub struct KeyResult {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mnemonic: Option<String>,
 
+    /// Wordlist of the mnemonic.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+
+    /// SLIP-39 share mnemonics, one list per group.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shares: Option<Vec<Vec<String>>>,
+
+    /// Fingerprint of the master key, or of the key a derived key comes from.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fingerprint: Option<String>,
+
+    /// The derived public key with its origin and a wildcard, as written in descriptors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub descriptor_key: Option<String>,
+
+    /// The derived private key with its origin and a wildcard, as written in descriptors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub descriptor_secret_key: Option<String>,
+
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub descriptors: Option<KeyDescriptors>,
+}
+
+/// Descriptors of a key for both keychains.
+#[derive(Serialize)]
+pub struct KeyDescriptors {
+    pub public_descriptors: KeychainPair<String>,
+    pub private_descriptors: KeychainPair<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multipath_descriptor: Option<String>,
+}
+
+/// Wallet set up by `init`
+#[derive(Serialize)]
+pub struct InitResult {
+    pub wallet: String,
+    pub network: String,
+    pub public_descriptors: KeychainPair<String>,
+    /// Whether the private keys are kept in a password encrypted keystore.
+    pub encrypted: bool,
+    /// Whether the first scan was asked for.
+    #[serde(skip)]
+    pub scan: bool,
+    pub scanned: bool,
+}
+
+/// An alias of the REPL
+#[derive(Serialize)]
+pub struct AliasDetails {
+    pub name: String,
+    pub command: String,
+}
+
+/// A contact of the wallet
+#[derive(Serialize)]
+pub struct ContactDetails {
+    pub name: String,
+    pub address: String,
+}
+
+/// A Nostr Wallet Connect connection of the wallet
+#[cfg(feature = "nwc")]
+#[derive(Serialize)]
+pub struct NwcConnectionDetails {
+    pub name: String,
+    pub relay: String,
+    /// Satoshis the app may pay each period.
+    pub budget: u64,
+    pub period: String,
+    /// Satoshis paid in the current period.
+    pub spent: u64,
+}
+
+/// A new Nostr Wallet Connect connection, and the URI the app connects with
+#[cfg(feature = "nwc")]
+#[derive(Serialize)]
+pub struct NwcConnectionResult {
+    pub name: String,
+    /// Holds the secret key of the app: shown once, and to be kept private.
+    pub uri: String,
+    pub wallet_pubkey: String,
+}
+
+/// Result of `wallet generate-test`
+#[cfg(feature = "rpc")]
+#[derive(Serialize)]
+pub struct GenerateTestResult {
+    pub wallet: String,
+    pub mnemonic: String,
+    /// UTXOs sent to the wallet, the unconfirmed ones included.
+    pub utxos: usize,
+    pub unconfirmed: usize,
+    pub txids: Vec<String>,
+    /// Sum of the UTXOs.
+    #[serde(serialize_with = "amount::serialize")]
+    pub total: u64,
+    /// Height of the wallet once synced.
+    pub synced_height: u32,
+}
+
+/// Result of `node mine`
+#[cfg(feature = "rpc")]
+#[derive(Serialize)]
+pub struct NodeMineResult {
+    pub address: String,
+    pub block_hashes: Vec<String>,
+    /// Height of the node after mining.
+    pub height: u64,
+    /// Height of the wallet once synced.
+    pub synced_height: u32,
+}
+
+/// Result of `node fund`
+#[cfg(feature = "rpc")]
+#[derive(Serialize)]
+pub struct NodeFundResult {
+    pub txid: String,
+    pub address: String,
+    #[serde(serialize_with = "amount::serialize")]
+    pub amount: u64,
+    /// Whether a block was mined to confirm the transaction.
+    pub confirmed: bool,
+    /// Height of the wallet once synced.
+    pub synced_height: u32,
+}
+
+/// Result of `node reorg`
+#[cfg(feature = "rpc")]
+#[derive(Serialize)]
+pub struct NodeReorgResult {
+    pub invalidated: Vec<String>,
+    pub block_hashes: Vec<String>,
+    /// Height of the first invalidated block.
+    pub fork_height: u32,
+    /// Height of the node after mining the competing chain.
+    pub height: u64,
+    /// Height of the wallet once synced.
+    pub synced_height: u32,
+    /// The transactions of the wallet confirmed in the invalidated blocks.
+    pub transactions: Vec<ReorgedTransaction>,
+}
+
+/// A transaction of the wallet confirmed in a block taken back by a reorg
+#[cfg(feature = "rpc")]
+#[derive(Serialize)]
+pub struct ReorgedTransaction {
+    pub txid: String,
+    /// `reconfirmed` or `unconfirmed`.
+    pub status: String,
+    pub height_before: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height_after: Option<u32>,
 }
 
 /// Balance representation
 #[derive(Serialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
 pub struct BalanceResult {
+    #[serde(serialize_with = "amount::serialize")]
+    #[cfg_attr(feature = "server", schemars(with = "amount::AmountSchema"))]
     pub total: u64,
+    #[serde(serialize_with = "amount::serialize")]
+    #[cfg_attr(feature = "server", schemars(with = "amount::AmountSchema"))]
     pub trusted_pending: u64,
+    #[serde(serialize_with = "amount::serialize")]
+    #[cfg_attr(feature = "server", schemars(with = "amount::AmountSchema"))]
     pub untrusted_pending: u64,
+    #[serde(serialize_with = "amount::serialize")]
+    #[cfg_attr(feature = "server", schemars(with = "amount::AmountSchema"))]
     pub immature: u64,
+    #[serde(serialize_with = "amount::serialize")]
+    #[cfg_attr(feature = "server", schemars(with = "amount::AmountSchema"))]
     pub confirmed: u64,
+    /// The value of the total, with `--fiat`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<FiatValue>,
 }
 
 impl From<Balance> for BalanceResult {
@@ -202,6 +1078,7 @@ impl From<Balance> for BalanceResult {
             trusted_pending: b.trusted_pending.to_sat(),
             untrusted_pending: b.untrusted_pending.to_sat(),
             immature: b.immature.to_sat(),
+            fiat: fiat_value(b.total().to_sat() as i64),
         }
     }
 }
@@ -0,0 +1,576 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Uniform Resources
+//!
+//! Encodes PSBTs as [BC-UR] `crypto-psbt` resources, the format air-gapped signers exchange
+//! through animated QR codes. Multi-part resources are split with the fountain code of the spec:
+//! the first parts are "pure", one fragment each, the following ones are "mixed", the XOR of
+//! fragments picked by a PRNG seeded with the sequence number. An animation can loop over mixed
+//! parts forever, and the decoder solves the fragments from any mix of pure and mixed parts.
+//!
+//! [BC-UR]: https://github.com/BlockchainCommons/Research/blob/master/papers/bcr-2020-005-ur.md
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use bdk_wallet::bitcoin::hashes::{Hash, sha256};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub const CRYPTO_PSBT: &str = "crypto-psbt";
+
+/// Default maximum fragment length, in bytes, of a multi-part resource.
+pub const DEFAULT_MAX_FRAGMENT_LEN: usize = 200;
+
+const BYTEWORDS: [&str; 256] = [
+    "able", "acid", "also", "apex", "aqua", "arch", "atom", "aunt", "away", "axis", "back", "bald",
+    "barn", "belt", "beta", "bias", "blue", "body", "brag", "brew", "bulb", "buzz", "calm", "cash",
+    "cats", "chef", "city", "claw", "code", "cola", "cook", "cost", "crux", "curl", "cusp", "cyan",
+    "dark", "data", "days", "deli", "dice", "diet", "door", "down", "draw", "drop", "drum", "dull",
+    "duty", "each", "easy", "echo", "edge", "epic", "even", "exam", "exit", "eyes", "fact", "fair",
+    "fern", "figs", "film", "fish", "fizz", "flap", "flew", "flux", "foxy", "free", "frog", "fuel",
+    "fund", "gala", "game", "gear", "gems", "gift", "girl", "glow", "good", "gray", "grim", "guru",
+    "gush", "gyro", "half", "hang", "hard", "hawk", "heat", "help", "high", "hill", "holy", "hope",
+    "horn", "huts", "iced", "idea", "idle", "inch", "inky", "into", "iris", "iron", "item", "jade",
+    "jazz", "join", "jolt", "jowl", "judo", "jugs", "jump", "junk", "jury", "keep", "keno", "kept",
+    "keys", "kick", "kiln", "king", "kite", "kiwi", "knob", "lamb", "lava", "lazy", "leaf", "legs",
+    "liar", "limp", "lion", "list", "logo", "loud", "love", "luau", "luck", "lung", "main", "many",
+    "math", "maze", "memo", "menu", "meow", "mild", "mint", "miss", "monk", "nail", "navy", "need",
+    "news", "next", "noon", "note", "numb", "obey", "oboe", "omit", "onyx", "open", "oval", "owls",
+    "paid", "part", "peck", "play", "plus", "poem", "pool", "pose", "puff", "puma", "purr", "quad",
+    "quiz", "race", "ramp", "real", "redo", "rich", "road", "rock", "roof", "ruby", "ruin", "runs",
+    "rust", "safe", "saga", "scar", "sets", "silk", "skew", "slot", "soap", "solo", "song", "stub",
+    "surf", "swan", "taco", "task", "taxi", "tent", "tied", "time", "tiny", "toil", "tomb", "toys",
+    "trip", "tuna", "twin", "ugly", "undo", "unit", "urge", "user", "vast", "very", "veto", "vial",
+    "vibe", "view", "visa", "void", "vows", "wall", "wand", "warm", "wasp", "wave", "waxy", "webs",
+    "what", "when", "whiz", "wolf", "work", "yank", "yawn", "yell", "yoga", "yurt", "zaps", "zero",
+    "zest", "zinc", "zone", "zoom",
+];
+
+/// Encode a PSBT as one or more `ur:crypto-psbt` parts, the pure parts of a multi-part resource.
+pub fn encode_psbt(psbt: &[u8], max_fragment_len: usize) -> Vec<String> {
+    let encoder = Encoder::psbt(psbt, max_fragment_len);
+    (1..=encoder.fragment_count() as u32)
+        .map(|seq_num| encoder.part(seq_num))
+        .collect()
+}
+
+/// Decode the PSBT carried by the `ur:crypto-psbt` parts.
+pub fn decode_psbt(parts: &[String]) -> Result<Vec<u8>, Error> {
+    let message = decode(CRYPTO_PSBT, parts)?;
+    let mut cbor = Cbor(&message);
+    let psbt = cbor.bytes()?;
+    if !cbor.0.is_empty() {
+        return Err(invalid("trailing data after the PSBT"));
+    }
+    Ok(psbt)
+}
+
+/// Splits a message into the parts of a `ur:` resource.
+pub struct Encoder {
+    ur_type: &'static str,
+    message_len: usize,
+    checksum: u32,
+    /// The fragments of the message, the last one padded with zeros, or the whole message when
+    /// it fits in a single part.
+    fragments: Vec<Vec<u8>>,
+}
+
+impl Encoder {
+    /// The encoder of a `ur:crypto-psbt` resource.
+    pub fn psbt(psbt: &[u8], max_fragment_len: usize) -> Self {
+        let mut message = Vec::new();
+        cbor_header(&mut message, 2, psbt.len() as u64);
+        message.extend_from_slice(psbt);
+        Self::new(CRYPTO_PSBT, message, max_fragment_len)
+    }
+
+    fn new(ur_type: &'static str, message: Vec<u8>, max_fragment_len: usize) -> Self {
+        let message_len = message.len();
+        let checksum = crc32(&message);
+        if message_len <= max_fragment_len {
+            return Self {
+                ur_type,
+                message_len,
+                checksum,
+                fragments: vec![message],
+            };
+        }
+
+        let fragment_len = message_len.div_ceil(message_len.div_ceil(max_fragment_len));
+        let fragments = message
+            .chunks(fragment_len)
+            .map(|chunk| {
+                let mut fragment = chunk.to_vec();
+                fragment.resize(fragment_len, 0);
+                fragment
+            })
+            .collect();
+        Self {
+            ur_type,
+            message_len,
+            checksum,
+            fragments,
+        }
+    }
+
+    /// Number of pure parts, each carrying one fragment.
+    pub fn fragment_count(&self) -> usize {
+        self.fragments.len()
+    }
+
+    /// The part of sequence number `seq_num`, counted from 1. Numbers past the fragment count
+    /// give mixed parts, a single-part resource is the same part whatever the number.
+    pub fn part(&self, seq_num: u32) -> String {
+        let ur_type = self.ur_type;
+        let seq_len = self.fragments.len();
+        if seq_len == 1 {
+            return format!("ur:{ur_type}/{}", bytewords(&self.fragments[0]));
+        }
+
+        let mut fragment = vec![0; self.fragments[0].len()];
+        for index in choose_fragments(seq_num, seq_len, self.checksum) {
+            xor(&mut fragment, &self.fragments[index]);
+        }
+
+        let mut part = Vec::new();
+        cbor_header(&mut part, 4, 5);
+        cbor_header(&mut part, 0, seq_num as u64);
+        cbor_header(&mut part, 0, seq_len as u64);
+        cbor_header(&mut part, 0, self.message_len as u64);
+        cbor_header(&mut part, 0, self.checksum as u64);
+        cbor_header(&mut part, 2, fragment.len() as u64);
+        part.extend(fragment);
+
+        format!("ur:{ur_type}/{seq_num}-{seq_len}/{}", bytewords(&part))
+    }
+}
+
+fn decode(ur_type: &str, parts: &[String]) -> Result<Vec<u8>, Error> {
+    let mut decoder = FountainDecoder::default();
+    let mut header: Option<(u64, u64, u32, usize)> = None;
+
+    for part in parts {
+        let part = part.trim().to_lowercase();
+        let rest = part
+            .strip_prefix("ur:")
+            .and_then(|rest| rest.strip_prefix(ur_type))
+            .and_then(|rest| rest.strip_prefix('/'))
+            .ok_or_else(|| invalid(&format!("expected a ur:{ur_type} part, found '{part}'")))?;
+
+        let Some((_, body)) = rest.split_once('/') else {
+            if parts.len() > 1 {
+                return Err(invalid("a single-part resource cannot be combined"));
+            }
+            return from_bytewords(rest);
+        };
+
+        let mut cbor_part = Cbor(&from_bytewords(body)?);
+        if cbor_part.header(4)? != 5 {
+            return Err(invalid("malformed multi-part header"));
+        }
+        let seq_num = cbor_part.header(0)?;
+        let seq_len = cbor_part.header(0)?;
+        let message_len = cbor_part.header(0)?;
+        let checksum = u32::try_from(cbor_part.header(0)?)
+            .map_err(|_| invalid("malformed multi-part checksum"))?;
+        let fragment = cbor_part.bytes()?;
+        let seq_num =
+            u32::try_from(seq_num).map_err(|_| invalid("malformed multi-part sequence number"))?;
+
+        match header {
+            None => {
+                if seq_len == 0 || seq_len > 10_000 {
+                    return Err(invalid("malformed multi-part sequence length"));
+                }
+                header = Some((seq_len, message_len, checksum, fragment.len()));
+            }
+            Some(expected) if expected != (seq_len, message_len, checksum, fragment.len()) => {
+                return Err(invalid("parts belong to different resources"));
+            }
+            Some(_) => {}
+        }
+        if seq_num == 0 {
+            return Err(invalid("malformed multi-part sequence number"));
+        }
+
+        decoder.receive(
+            choose_fragments(seq_num, seq_len as usize, checksum),
+            fragment,
+        );
+    }
+
+    let (seq_len, message_len, checksum, _) = header.ok_or_else(|| invalid("no part given"))?;
+    let missing: Vec<String> = (0..seq_len as usize)
+        .filter(|index| !decoder.solved.contains_key(index))
+        .map(|index| (index + 1).to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(invalid(&format!("missing parts {}", missing.join(", "))));
+    }
+
+    let mut message: Vec<u8> = decoder.solved.into_values().flatten().collect();
+    message.truncate(message_len as usize);
+    if message.len() as u64 != message_len || crc32(&message) != checksum {
+        return Err(invalid("message checksum mismatch"));
+    }
+    Ok(message)
+}
+
+/// Solves the fragments of a fountain code from its pure and mixed parts.
+#[derive(Default)]
+struct FountainDecoder {
+    solved: BTreeMap<usize, Vec<u8>>,
+    /// Mixed parts with more than one unsolved fragment.
+    mixed: Vec<(BTreeSet<usize>, Vec<u8>)>,
+}
+
+impl FountainDecoder {
+    fn receive(&mut self, indexes: BTreeSet<usize>, data: Vec<u8>) {
+        let mut queue = vec![(indexes, data)];
+        while let Some((mut indexes, mut data)) = queue.pop() {
+            // XOR out the fragments already solved.
+            for index in indexes.clone() {
+                if let Some(fragment) = self.solved.get(&index) {
+                    xor(&mut data, fragment);
+                    indexes.remove(&index);
+                }
+            }
+
+            match indexes.len() {
+                0 => {}
+                1 => {
+                    let index = indexes.into_iter().next().expect("one index");
+                    self.solved.insert(index, data);
+                    // The mixed parts with this fragment may now be solvable.
+                    let (reducible, mixed) = std::mem::take(&mut self.mixed)
+                        .into_iter()
+                        .partition(|(mixed, _)| mixed.contains(&index));
+                    self.mixed = mixed;
+                    queue.extend(reducible);
+                }
+                _ => {
+                    if !self.mixed.iter().any(|(mixed, _)| *mixed == indexes) {
+                        self.mixed.push((indexes, data));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Indexes of the fragments mixed into the part of sequence number `seq_num`.
+fn choose_fragments(seq_num: u32, seq_len: usize, checksum: u32) -> BTreeSet<usize> {
+    if seq_num as usize <= seq_len {
+        return BTreeSet::from([seq_num as usize - 1]);
+    }
+
+    let mut seed = seq_num.to_be_bytes().to_vec();
+    seed.extend(checksum.to_be_bytes());
+    let mut rng = Xoshiro256::new(&seed);
+    let degree = choose_degree(seq_len, &mut rng);
+    shuffled((0..seq_len).collect(), &mut rng)
+        .into_iter()
+        .take(degree)
+        .collect()
+}
+
+/// Number of fragments of a mixed part, from 1 to `seq_len` with probabilities proportional to
+/// `1 / degree`.
+fn choose_degree(seq_len: usize, rng: &mut Xoshiro256) -> usize {
+    let weights: Vec<f64> = (1..=seq_len).map(|degree| 1.0 / degree as f64).collect();
+    AliasSampler::new(&weights).next(rng) + 1
+}
+
+fn shuffled(mut items: Vec<usize>, rng: &mut Xoshiro256) -> Vec<usize> {
+    let mut result = Vec::with_capacity(items.len());
+    while !items.is_empty() {
+        let index = rng.next_int(0, items.len() - 1);
+        result.push(items.remove(index));
+    }
+    result
+}
+
+/// The xoshiro256** PRNG of the fountain code, seeded with the SHA-256 of a seed.
+struct Xoshiro256([u64; 4]);
+
+impl Xoshiro256 {
+    fn new(seed: &[u8]) -> Self {
+        let digest = sha256::Hash::hash(seed).to_byte_array();
+        Self(std::array::from_fn(|i| {
+            u64::from_be_bytes(digest[8 * i..8 * (i + 1)].try_into().expect("8 bytes"))
+        }))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.0;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    fn next_double(&mut self) -> f64 {
+        self.next_u64() as f64 / (u64::MAX as f64 + 1.0)
+    }
+
+    fn next_int(&mut self, low: usize, high: usize) -> usize {
+        (self.next_double() * (high - low + 1) as f64) as usize + low
+    }
+}
+
+/// Walker's alias method, as specified for the degrees of the fountain code.
+struct AliasSampler {
+    probs: Vec<f64>,
+    aliases: Vec<usize>,
+}
+
+impl AliasSampler {
+    fn new(weights: &[f64]) -> Self {
+        let count = weights.len();
+        let total: f64 = weights.iter().sum();
+        let mut scaled: Vec<f64> = weights
+            .iter()
+            .map(|weight| weight * count as f64 / total)
+            .collect();
+
+        let (mut small, mut large) = (Vec::new(), Vec::new());
+        for (index, prob) in scaled.iter().enumerate().rev() {
+            if *prob < 1.0 {
+                small.push(index);
+            } else {
+                large.push(index);
+            }
+        }
+
+        let mut probs = vec![0.0; count];
+        let mut aliases = vec![0; count];
+        while !small.is_empty() && !large.is_empty() {
+            let (less, more) = (small.pop().unwrap(), large.pop().unwrap());
+            probs[less] = scaled[less];
+            aliases[less] = more;
+            scaled[more] += scaled[less] - 1.0;
+            if scaled[more] < 1.0 {
+                small.push(more);
+            } else {
+                large.push(more);
+            }
+        }
+        for index in large.into_iter().chain(small) {
+            probs[index] = 1.0;
+        }
+        Self { probs, aliases }
+    }
+
+    fn next(&self, rng: &mut Xoshiro256) -> usize {
+        let (r1, r2) = (rng.next_double(), rng.next_double());
+        let index = (self.probs.len() as f64 * r1) as usize;
+        if r2 < self.probs[index] {
+            index
+        } else {
+            self.aliases[index]
+        }
+    }
+}
+
+fn xor(data: &mut [u8], other: &[u8]) {
+    data.iter_mut()
+        .zip(other)
+        .for_each(|(byte, other)| *byte ^= other);
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::coded(ErrorCode::InvalidUr, format!("Invalid UR: {reason}"))
+}
+
+/// Minimal bytewords of the data followed by its CRC32 checksum.
+fn bytewords(data: &[u8]) -> String {
+    data.iter()
+        .chain(&crc32(data).to_be_bytes())
+        .map(|byte| {
+            let word = BYTEWORDS[*byte as usize].as_bytes();
+            [word[0] as char, word[3] as char]
+        })
+        .flat_map(|pair| pair.into_iter())
+        .collect()
+}
+
+fn from_bytewords(s: &str) -> Result<Vec<u8>, Error> {
+    let letters = s.as_bytes();
+    if !letters.len().is_multiple_of(2) {
+        return Err(invalid("bytewords have an odd length"));
+    }
+    let mut data = letters
+        .chunks(2)
+        .map(|pair| {
+            BYTEWORDS
+                .iter()
+                .position(|word| {
+                    let word = word.as_bytes();
+                    word[0] == pair[0] && word[3] == pair[1]
+                })
+                .map(|byte| byte as u8)
+                .ok_or_else(|| invalid("unknown byteword"))
+        })
+        .collect::<Result<Vec<u8>, Error>>()?;
+
+    if data.len() < 4 {
+        return Err(invalid("bytewords are too short"));
+    }
+    let checksum = data.split_off(data.len() - 4);
+    if crc32(&data).to_be_bytes() != checksum[..] {
+        return Err(invalid("bytewords checksum mismatch"));
+    }
+    Ok(data)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn cbor_header(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xff => out.extend([major | 24, value as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend((value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend((value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend(value.to_be_bytes());
+        }
+    }
+}
+
+/// Reads the few CBOR items used by UR.
+struct Cbor<'a>(&'a [u8]);
+
+impl Cbor<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], Error> {
+        if len > self.0.len() {
+            return Err(invalid("truncated CBOR"));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn header(&mut self, major: u8) -> Result<u64, Error> {
+        let initial = self.take(1)?[0];
+        if initial >> 5 != major {
+            return Err(invalid("unexpected CBOR item"));
+        }
+        let len = match initial & 0x1f {
+            info @ 0..=23 => return Ok(info as u64),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err(invalid("unsupported CBOR item")),
+        };
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0, |value, byte| (value << 8) | *byte as u64))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let len = self.header(2)?;
+        let len = usize::try_from(len).map_err(|_| invalid("oversized CBOR byte string"))?;
+        Ok(self.take(len)?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytewords_vector() {
+        // BCR-2020-012 test vector.
+        assert_eq!(bytewords(&[0, 1, 2, 128, 255]), "aeadaolazmjendeoti");
+        assert_eq!(
+            from_bytewords("aeadaolazmjendeoti").unwrap(),
+            [0, 1, 2, 128, 255]
+        );
+        assert!(from_bytewords("aeadaolazmjendeota").is_err());
+    }
+
+    #[test]
+    fn test_single_and_multi_part_round_trip() {
+        let psbt: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        let single = encode_psbt(&psbt, 2000);
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].matches('/').count(), 1);
+        assert_eq!(decode_psbt(&single).unwrap(), psbt);
+
+        let mut parts = encode_psbt(&psbt, 100);
+        assert_eq!(parts.len(), 11);
+        assert!(parts[0].starts_with("ur:crypto-psbt/1-11/"));
+        parts.reverse();
+        assert_eq!(decode_psbt(&parts).unwrap(), psbt);
+
+        parts.remove(3);
+        let err = decode_psbt(&parts).unwrap_err().to_string();
+        assert!(err.contains("missing parts 8"), "{err}");
+    }
+
+    #[test]
+    fn test_fountain_prng_vectors() {
+        // BCR-2020-012 test vectors of the PRNG and of the shuffle.
+        let mut rng = Xoshiro256::new(b"Wolf");
+        let values: Vec<u64> = (0..10).map(|_| rng.next_u64() % 100).collect();
+        assert_eq!(values, [42, 81, 85, 8, 82, 84, 76, 73, 70, 88]);
+
+        let mut rng = Xoshiro256::new(b"Wolf");
+        let items = shuffled((1..=10).collect(), &mut rng);
+        assert_eq!(items, [6, 4, 9, 3, 10, 5, 7, 8, 1, 2]);
+    }
+
+    #[test]
+    fn test_mixed_parts_stand_in_for_missing_pure_parts() {
+        let psbt: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let encoder = Encoder::psbt(&psbt, 100);
+        assert_eq!(encoder.fragment_count(), 11);
+        assert!(encoder.part(12).starts_with("ur:crypto-psbt/12-11/"));
+
+        // Without parts 2 and 5, the mixed parts that follow the pure ones solve them.
+        let mut parts: Vec<String> = [1, 3, 4, 6, 7, 8, 9, 10, 11]
+            .into_iter()
+            .map(|seq_num| encoder.part(seq_num))
+            .collect();
+        let mut seq_num = 12;
+        while decode_psbt(&parts).is_err() {
+            assert!(seq_num < 100, "mixed parts never solved the fragments");
+            parts.push(encoder.part(seq_num));
+            seq_num += 1;
+        }
+        assert_eq!(decode_psbt(&parts).unwrap(), psbt);
+    }
+}
@@ -0,0 +1,191 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! ZMQ notifications of Bitcoin Core
+//!
+//! The `rawblock` and `rawtx` notifications Bitcoin Core publishes with `zmqpubrawblock` and
+//! `zmqpubrawtx`, received with the SUB sockets of the `zeromq` crate, which speaks ZMTP without
+//! linking libzmq. Each endpoint is read by its own task and the notifications are forwarded to a
+//! channel.
+
+use crate::error::{BDKCliError as Error, ErrorCode};
+use bdk_wallet::bitcoin::{Transaction, consensus::deserialize};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use zeromq::{Socket, SocketRecv, SubSocket, ZmqMessage};
+
+/// A notification of Bitcoin Core.
+#[derive(Debug)]
+pub enum Notification {
+    /// A block was connected, the wallet has to catch up with the chain.
+    Block,
+    /// A transaction entered the mempool or a block.
+    Tx(Transaction),
+}
+
+/// Subscribes to the raw blocks published at `block_endpoint` and the raw transactions published
+/// at `tx_endpoint`, over a single connection when both are the same. A connection that fails
+/// sends its error and stops.
+pub async fn subscribe(
+    block_endpoint: Option<&str>,
+    tx_endpoint: Option<&str>,
+) -> Result<UnboundedReceiver<Result<Notification, Error>>, Error> {
+    let mut endpoints: Vec<(&str, Vec<&str>)> = Vec::new();
+    for (endpoint, topic) in [(block_endpoint, "rawblock"), (tx_endpoint, "rawtx")] {
+        let Some(endpoint) = endpoint else { continue };
+        match endpoints.iter_mut().find(|(e, _)| *e == endpoint) {
+            Some((_, topics)) => topics.push(topic),
+            None => endpoints.push((endpoint, vec![topic])),
+        }
+    }
+
+    let (sender, receiver) = unbounded_channel();
+    for (endpoint, topics) in endpoints {
+        if !endpoint.starts_with("tcp://") {
+            return Err(Error::coded(
+                ErrorCode::InvalidArgument,
+                format!("Unsupported ZMQ endpoint {endpoint}, expected tcp://host:port"),
+            ));
+        }
+        let mut socket = SubSocket::new();
+        socket.connect(endpoint).await.map_err(|e| {
+            Error::coded(
+                ErrorCode::Backend,
+                format!("Cannot connect to ZMQ endpoint {endpoint}: {e}"),
+            )
+        })?;
+        for topic in topics {
+            socket.subscribe(topic).await.map_err(|e| {
+                Error::coded(
+                    ErrorCode::Backend,
+                    format!("Cannot subscribe to {topic} at {endpoint}: {e}"),
+                )
+            })?;
+        }
+        tokio::spawn(forward(socket, endpoint.to_string(), sender.clone()));
+    }
+    Ok(receiver)
+}
+
+async fn forward(
+    mut socket: SubSocket,
+    endpoint: String,
+    sender: UnboundedSender<Result<Notification, Error>>,
+) {
+    loop {
+        let notification = match socket.recv().await {
+            Ok(message) => match notification(&message, &endpoint) {
+                Some(notification) => notification,
+                None => continue,
+            },
+            Err(e) => Err(Error::coded(
+                ErrorCode::Backend,
+                format!("ZMQ connection to {endpoint} lost: {e}"),
+            )),
+        };
+        let failed = notification.is_err();
+        if sender.send(notification).is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// The notification of a message of Bitcoin Core, made of a topic, a body and a sequence number,
+/// `None` for the other topics.
+fn notification(message: &ZmqMessage, endpoint: &str) -> Option<Result<Notification, Error>> {
+    match message.get(0).map(|topic| &topic[..]) {
+        Some(b"rawblock") => Some(Ok(Notification::Block)),
+        Some(b"rawtx") => Some(match message.get(1).map(|body| deserialize(body)) {
+            Some(Ok(tx)) => Ok(Notification::Tx(tx)),
+            _ => Err(Error::coded(
+                ErrorCode::Backend,
+                format!("Invalid rawtx notification from {endpoint}"),
+            )),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk_wallet::bitcoin::consensus::serialize;
+    use bdk_wallet::bitcoin::{
+        Amount, ScriptBuf, TxIn, TxOut, absolute::LockTime, transaction::Version,
+    };
+    use std::time::Duration;
+    use zeromq::{PubSocket, SocketSend};
+
+    /// A message the way Bitcoin Core publishes it: topic, body and sequence number.
+    fn message(topic: &str, body: Vec<u8>) -> ZmqMessage {
+        let mut message = ZmqMessage::from(topic.to_string());
+        message.push_back(body.into());
+        message.push_back(0u32.to_le_bytes().to_vec().into());
+        message
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_receives_core_notifications() {
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value: Amount::from_sat(1000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        };
+        let mut publisher = PubSocket::new();
+        let endpoint = publisher
+            .bind("tcp://127.0.0.1:0")
+            .await
+            .unwrap()
+            .to_string();
+        let mut notifications = subscribe(Some(&endpoint), Some(&endpoint)).await.unwrap();
+
+        // The publisher drops the messages sent before the subscriptions reached it, the one to
+        // `rawtx` coming last.
+        let received = loop {
+            publisher
+                .send(message("rawtx", serialize(&tx)))
+                .await
+                .unwrap();
+            let wait = tokio::time::timeout(Duration::from_millis(100), notifications.recv());
+            if let Ok(received) = wait.await {
+                break received;
+            }
+        };
+        match received {
+            Some(Ok(Notification::Tx(received))) => assert_eq!(received, tx),
+            other => panic!("expected a transaction, got {other:?}"),
+        }
+
+        publisher
+            .send(message("rawblock", vec![0; 80]))
+            .await
+            .unwrap();
+        loop {
+            match notifications.recv().await {
+                Some(Ok(Notification::Tx(_))) => continue,
+                Some(Ok(Notification::Block)) => break,
+                other => panic!("expected a block, got {other:?}"),
+            }
+        }
+
+        publisher
+            .send(message("rawtx", vec![1, 2, 3]))
+            .await
+            .unwrap();
+        assert!(matches!(notifications.recv().await, Some(Err(_))));
+
+        assert!(
+            subscribe(Some("ipc:///tmp/bitcoind.sock"), None)
+                .await
+                .is_err()
+        );
+    }
+}
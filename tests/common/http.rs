@@ -0,0 +1,139 @@
+// Copyright (c) 2020-2026 Bitcoin Dev Kit Developers
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! A fake HTTP server
+//!
+//! Stands for the web services of the tests, such as esplora servers, faucets or price
+//! providers, answering every request with a handler on its own thread.
+
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+
+/// A request to a fake server.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// The headers, with lowercase names.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The value of the header `name`, given in lowercase.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The response of a fake server.
+#[derive(Clone, Debug)]
+pub struct Response {
+    /// The status line, such as `200 OK`.
+    pub status: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// A `200 OK` response with `body`.
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: "200 OK",
+            body: body.into(),
+        }
+    }
+
+    /// An empty response with `status`.
+    pub fn status(status: &'static str) -> Self {
+        Self {
+            status,
+            body: Vec::new(),
+        }
+    }
+}
+
+/// Serves the requests with `handler` on a local port, returning the `http://` url of the
+/// server.
+pub fn serve<F>(handler: F) -> String
+where
+    F: Fn(&Request) -> Response + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let Some(request) = read_request(&mut BufReader::new(stream.try_clone().unwrap()))
+            else {
+                continue;
+            };
+            let response = handler(&request);
+            let head = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                response.status,
+                response.body.len()
+            );
+            let _ = stream
+                .write_all(head.as_bytes())
+                .and_then(|_| stream.write_all(&response.body));
+        }
+    });
+    url
+}
+
+/// A regtest esplora server on its genesis block, answering the other requests with `handler`.
+pub fn serve_esplora<F>(handler: F) -> String
+where
+    F: Fn(&Request) -> Response + Send + 'static,
+{
+    serve(move |request| match request.path.as_str() {
+        "/block-height/0" => Response::ok(REGTEST_GENESIS),
+        _ => handler(request),
+    })
+}
+
+/// The hash of the regtest genesis block.
+pub const REGTEST_GENESIS: &str =
+    "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206";
+
+fn read_request(reader: &mut impl BufRead) -> Option<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split(' ');
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).ok()? == 0 {
+            break;
+        }
+        let Some((name, value)) = line.trim_end().split_once(':') else {
+            break;
+        };
+        headers.push((name.to_lowercase(), value.trim().to_string()));
+    }
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: Vec::new(),
+    };
+    let length = request
+        .header("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body).ok()?;
+    Some(request)
+}
@@ -13,6 +13,8 @@
 use assert_cmd::Command;
 use std::path::PathBuf;
 
+pub mod http;
+
 /// The bdk-cli command struct
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -46,6 +46,28 @@ mod test_key {
             .success()
             .stdout(predicate::str::contains("\"xprv\":"))
             .stdout(predicate::str::contains("\"xpub\":"));
+
+        let output = cli
+            .key_cmd(&["derive", "--xprv", xprv, "--path", "m/86h/1h/0h"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let derived: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let fingerprint = generate_json["fingerprint"].as_str().unwrap();
+        let xpub = derived["xpub"].as_str().unwrap();
+        assert!(xpub.starts_with("tpub"));
+        assert_eq!(derived["fingerprint"], fingerprint);
+        assert_eq!(
+            derived["descriptor_key"],
+            format!("[{fingerprint}/86'/1'/0']{xpub}/*")
+        );
+        assert_eq!(
+            derived["descriptor_secret_key"],
+            format!(
+                "[{fingerprint}/86'/1'/0']{}/*",
+                derived["xprv"].as_str().unwrap()
+            )
+        );
     }
 
     #[test]
@@ -101,6 +123,459 @@ mod test_key {
             "The restored fingerprint does not match the generated fingerprint!"
         );
     }
+
+    #[test]
+    fn test_cli_key_restore_with_passphrase() {
+        let cli = BdkCli::new("bitcoin", None);
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let restore = |args: &[&str], stdin: &str| -> Value {
+            let output = cli
+                .key_cmd(&[&["restore", "--mnemonic", mnemonic], args].concat())
+                .write_stdin(stdin)
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            serde_json::from_slice(&output.stdout).unwrap()
+        };
+
+        // BIP39 test vector for the "TREZOR" passphrase.
+        let expected = "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF";
+        assert_eq!(restore(&["--passphrase", "TREZOR"], "")["xprv"], expected);
+        assert_eq!(restore(&["--ask-passphrase"], "TREZOR\n")["xprv"], expected);
+        assert_ne!(restore(&[], "")["xprv"], expected);
+
+        // `PASSWORD` is still read when `PASSPHRASE` is not set.
+        let output = cli
+            .key_cmd(&["restore", "--mnemonic", mnemonic])
+            .env("PASSWORD", "TREZOR")
+            .output()
+            .unwrap();
+        let restored: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(restored["xprv"], expected);
+    }
+
+    #[test]
+    fn test_cli_key_restore_taproot_descriptors() {
+        let cli = BdkCli::new("bitcoin", None);
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let output = cli
+            .key_cmd(&["restore", "--mnemonic", mnemonic, "--type", "tr"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let restored: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+        // BIP86 test vector account key.
+        let key = "[73c5da0a/86'/0'/0']xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ";
+        let public = &restored["public_descriptors"];
+        let private = &restored["private_descriptors"];
+        assert!(
+            public["external"]
+                .as_str()
+                .unwrap()
+                .starts_with(&format!("tr({key}/0/*)#"))
+        );
+        assert!(
+            public["internal"]
+                .as_str()
+                .unwrap()
+                .starts_with(&format!("tr({key}/1/*)#"))
+        );
+        let xprv = restored["xprv"].as_str().unwrap();
+        assert!(
+            private["external"]
+                .as_str()
+                .unwrap()
+                .starts_with(&format!("tr({xprv}/86'/0'/0'/0/*)#"))
+        );
+        assert!(
+            private["internal"]
+                .as_str()
+                .unwrap()
+                .starts_with(&format!("tr({xprv}/86'/0'/0'/1/*)#"))
+        );
+        assert!(
+            restored["multipath_descriptor"]
+                .as_str()
+                .unwrap()
+                .starts_with(&format!("tr({key}/<0;1>/*)#"))
+        );
+
+        let output = cli.key_cmd(&["generate", "--type", "tr"]).output().unwrap();
+        assert!(output.status.success());
+        let generated: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let fingerprint = generated["fingerprint"].as_str().unwrap();
+        assert!(
+            generated["public_descriptors"]["external"]
+                .as_str()
+                .unwrap()
+                .starts_with(&format!("tr([{fingerprint}/86'/0'/0']xpub"))
+        );
+    }
+
+    #[test]
+    fn test_cli_key_info() {
+        let cli = BdkCli::new("bitcoin", None);
+        let info = |key: &str| -> Value {
+            let output = cli.key_cmd(&["info", key]).output().unwrap();
+            assert!(output.status.success());
+            serde_json::from_slice(&output.stdout).unwrap()
+        };
+
+        // BIP86 test vector master and account keys.
+        let xprv = "xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu";
+        let master = &info(xprv)["keys"][0];
+        assert_eq!(master["is_private"], true);
+        assert_eq!(master["master_fingerprint"], "73c5da0a");
+        assert_eq!(master["depth"], 0);
+        assert_eq!(master["network"], "mainnet");
+
+        let xpub = "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ";
+        let account = &info(xpub)["keys"][0];
+        assert_eq!(account["depth"], 3);
+        assert_eq!(account["child_number"], "0'");
+        assert!(account["master_fingerprint"].is_null());
+
+        let pubkey = "02e5b88fdb71c696e1a473f309a47535b7190e21a22bd25e7fc8bd055db3bba12f";
+        let descriptor = format!("wsh(multi(1,[73c5da0a/86'/0'/0']{xpub}/<0;1>/*,{pubkey}))");
+        let result = info(&descriptor);
+        let keys = result["keys"].as_array().unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0]["master_fingerprint"], "73c5da0a");
+        assert_eq!(keys[0]["origin_path"], "m/86'/0'/0'");
+        assert_eq!(
+            keys[0]["derivation_paths"],
+            serde_json::json!(["m/0/*", "m/1/*"])
+        );
+        assert_eq!(keys[1]["key"], pubkey);
+
+        cli.key_cmd(&["info", "not-a-key"]).assert().failure();
+    }
+
+    #[test]
+    fn test_cli_key_template() {
+        let cli = BdkCli::new("bitcoin", None);
+        let template = |args: &[&str]| -> Value {
+            let output = cli
+                .key_cmd(&[&["template"], args].concat())
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            serde_json::from_slice(&output.stdout).unwrap()
+        };
+
+        // BIP86 test vector master and account keys.
+        let xprv = "xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu";
+        let xpub = "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ";
+
+        let private = template(&[xprv, "--bip", "86"]);
+        let public = &private["public_descriptors"];
+        let external = public["external"].as_str().unwrap();
+        assert!(
+            external.starts_with(&format!("tr([73c5da0a/86'/0'/0']{xpub}/0/*)#")),
+            "{external}"
+        );
+        assert!(
+            private["private_descriptors"]["internal"]
+                .as_str()
+                .unwrap()
+                .contains("[73c5da0a/86'/0'/0']xprv")
+        );
+
+        let with_origin = format!("[73c5da0a/86'/0'/0']{xpub}");
+        assert_eq!(
+            &template(&[&with_origin, "--bip", "86"])["public_descriptors"],
+            public
+        );
+        let with_fingerprint = template(&[xpub, "--bip", "86", "--fingerprint", "73c5da0a"]);
+        assert_eq!(&with_fingerprint["public_descriptors"], public);
+        assert!(with_fingerprint["private_descriptors"].is_null());
+
+        // The account key must match the template.
+        cli.key_cmd(&["template", &with_origin, "--bip", "84"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("is not the BIP-84 account path"));
+        cli.key_cmd(&["template", xpub, "--bip", "86"])
+            .assert()
+            .failure();
+
+        let account = template(&[xprv, "--bip", "84", "--account", "1"]);
+        assert!(
+            account["public_descriptors"]["external"]
+                .as_str()
+                .unwrap()
+                .starts_with("wpkh([73c5da0a/84'/0'/1']xpub")
+        );
+    }
+
+    #[test]
+    fn test_cli_key_bip85() {
+        let cli = BdkCli::new("bitcoin", None);
+        // BIP85 test vector master key.
+        let xprv = "xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb";
+        let bip85 = |args: &[&str]| -> Value {
+            let output = cli
+                .key_cmd(&[&["bip85", "--xprv", xprv], args].concat())
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            serde_json::from_slice(&output.stdout).unwrap()
+        };
+
+        let result = bip85(&["--index", "0"]);
+        assert_eq!(result["path"], "m/83696968'/39'/0'/12'/0'");
+        assert_eq!(
+            result["mnemonic"],
+            "girl mad pet galaxy egg matter matrix prison refuse sense ordinary nose"
+        );
+
+        let result = bip85(&["--application", "wif"]);
+        assert_eq!(
+            result["wif"],
+            "Kzyv4uF39d4Jrw2W7UryTHwZr1zQVNk4dAFyqE6BuMrMh1Za7uhp"
+        );
+
+        let result = bip85(&["--application", "hex", "--length", "64"]);
+        assert_eq!(result["path"], "m/83696968'/128169'/64'/0'");
+        assert_eq!(
+            result["hex"],
+            "492db4698cf3b73a5a24998aa3e9d7fa96275d85724a91e71aa2d645442f8785\
+             55d078fd1f1f67e368976f04137b1f7a0d19232136ca50c44614af72b5582a5c"
+        );
+
+        cli.key_cmd(&["bip85", "--xprv", xprv, "--words", "13"])
+            .assert()
+            .failure();
+    }
+
+    #[test]
+    fn test_cli_key_checksum() {
+        let cli = BdkCli::new("testnet", None);
+        let tpub = "tpubEBr4i6yk5nf5DAaJpsi9N2pPYBeJ7fZ5Z9rmN4977iYLCGco1VyjB9tvvuvYtfZzjD5A8igzgw3HeWeeKFmanHYqksqZXYXGsw5zjnj7KM9";
+        let descriptor = format!("wpkh([01020304/84h/1h/0h]{tpub}/0/*)");
+
+        let output = cli.key_cmd(&["checksum", &descriptor]).output().unwrap();
+        assert!(output.status.success());
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let checksum = result["checksum"].as_str().unwrap();
+        assert_eq!(checksum.len(), 8);
+        assert_eq!(result["descriptor"], format!("{descriptor}#{checksum}"));
+        assert!(
+            result["canonical"]
+                .as_str()
+                .unwrap()
+                .starts_with("wpkh([01020304/84'/1'/0']")
+        );
+        assert_eq!(result["script_type"], "wpkh");
+        assert!(result["warnings"].as_array().unwrap().is_empty());
+
+        cli.key_cmd(&["checksum", &format!("{descriptor}#{checksum}")])
+            .assert()
+            .success();
+        cli.key_cmd(&["checksum", &format!("{descriptor}#aaaaaaaa")])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Checksum mismatch"));
+        cli.key_cmd(&["checksum", &format!("wpkh({tpub}/84h/0/*)")])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("hardened step 84h"));
+
+        let output = cli
+            .key_cmd(&["checksum", &format!("wsh(multi(1,{tpub}/0/*,{tpub}/1))")])
+            .output()
+            .unwrap();
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(result["script_type"], "wsh");
+        let warnings = result["warnings"].as_array().unwrap();
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[2].as_str().unwrap().contains("single address"));
+    }
+
+    #[test]
+    fn test_cli_key_generate_with_custom_entropy() {
+        let cli = BdkCli::new("testnet", None);
+        let generate = |args: &[&str]| -> Value {
+            let output = cli
+                .key_cmd(&[&["generate"], args].concat())
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            serde_json::from_slice(&output.stdout).unwrap()
+        };
+        let word_count = |key: &Value| key["mnemonic"].as_str().unwrap().split_whitespace().count();
+
+        assert_eq!(word_count(&generate(&["--words", "15"])), 15);
+        assert_eq!(word_count(&generate(&["--words", "21"])), 21);
+        cli.key_cmd(&["generate", "--words", "13"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid word count"));
+
+        let key = generate(&["--entropy-hex", &"00".repeat(16)]);
+        assert_eq!(key["mnemonic"], format!("{}about", "abandon ".repeat(11)));
+
+        // Rolls 1, 2, 3 and 4 give the bits 00 01 10 11.
+        let from_dice = generate(&["--dice", &"1234".repeat(16)]);
+        let from_hex = generate(&["--entropy-hex", &"1b".repeat(16)]);
+        assert_eq!(from_dice["mnemonic"], from_hex["mnemonic"]);
+        cli.key_cmd(&["generate", "--dice", "123456"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("roll about"));
+    }
+
+    #[test]
+    fn test_cli_key_runs_without_datadir() {
+        let temp_dir = TempDir::new().unwrap();
+        // A data directory that could not be created, below a file.
+        let blocked = temp_dir.path().join("file");
+        std::fs::write(&blocked, "").unwrap();
+        let cli = BdkCli::new("testnet", Some(blocked.join("datadir")));
+
+        cli.key_cmd(&["generate"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("\"xprv\":"));
+        cli.key_cmd(&[
+            "checksum",
+            "wpkh(02e5b88fdb71c696e1a473f309a47535b7190e21a22bd25e7fc8bd055db3bba12f)",
+        ])
+        .assert()
+        .success();
+        #[cfg(feature = "compiler")]
+        cli.cmd(
+            "compile",
+            &[
+                "pk(02e5b88fdb71c696e1a473f309a47535b7190e21a22bd25e7fc8bd055db3bba12f)",
+                "--type",
+                "wsh",
+            ],
+        )
+        .assert()
+        .success();
+
+        // The keystore commands still read the config of the data directory.
+        cli.key_cmd(&["encrypt", "--wallet", "missing"])
+            .assert()
+            .failure();
+
+        let unused = temp_dir.path().join("unused");
+        BdkCli::new("testnet", Some(unused.clone()))
+            .key_cmd(&["generate"])
+            .assert()
+            .success();
+        assert!(!unused.exists());
+    }
+
+    #[test]
+    fn test_cli_key_mnemonic_languages() {
+        let cli = BdkCli::new("testnet", None);
+        let key = |args: &[&str]| -> Value {
+            let output = cli.key_cmd(args).output().unwrap();
+            assert!(output.status.success());
+            serde_json::from_slice(&output.stdout).unwrap()
+        };
+
+        let zero_entropy = "00".repeat(16);
+        let spanish = key(&[
+            "generate",
+            "--entropy-hex",
+            &zero_entropy,
+            "--language",
+            "spanish",
+        ]);
+        // Words are output in the decomposed form of the wordlists.
+        assert_eq!(
+            spanish["mnemonic"],
+            format!("{}abierto", "a\u{301}baco ".repeat(11))
+        );
+        assert_eq!(spanish["language"], "spanish");
+
+        // The language is detected when restoring, whatever the accent encoding.
+        let mnemonic = format!("{}abierto", "ábaco ".repeat(11));
+        let restored = key(&["restore", "--mnemonic", &mnemonic]);
+        assert_eq!(restored["language"], "spanish");
+        assert_eq!(restored["xprv"], spanish["xprv"]);
+        cli.key_cmd(&["restore", "--mnemonic", &mnemonic, "--language", "english"])
+            .assert()
+            .failure();
+
+        let japanese = key(&[
+            "generate",
+            "--entropy-hex",
+            &zero_entropy,
+            "--language",
+            "japanese",
+        ]);
+        let restored = key(&[
+            "restore",
+            "--mnemonic",
+            japanese["mnemonic"].as_str().unwrap(),
+        ]);
+        assert_eq!(restored["language"], "japanese");
+        assert_eq!(restored["xprv"], japanese["xprv"]);
+        assert_ne!(japanese["xprv"], spanish["xprv"]);
+    }
+
+    #[test]
+    fn test_cli_key_slip39_generate_and_restore() {
+        let cli = BdkCli::new("testnet", None);
+
+        let output = cli
+            .key_cmd(&[
+                "generate",
+                "--slip39",
+                "--groups",
+                "2-of-3,1-of-1",
+                "--group-threshold",
+                "2",
+                "--passphrase",
+                "secret",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let generated: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(generated.get("mnemonic").is_none());
+        let shares = generated["shares"].as_array().unwrap();
+        assert_eq!(shares[0].as_array().unwrap().len(), 3);
+        assert_eq!(shares[1].as_array().unwrap().len(), 1);
+
+        let restore = |chosen: &[&Value], passphrase: &str| {
+            let mut args = vec!["restore", "--passphrase", passphrase, "--slip39"];
+            args.extend(chosen.iter().map(|share| share.as_str().unwrap()));
+            cli.key_cmd(&args).output().unwrap()
+        };
+        let output = restore(&[&shares[1][0], &shares[0][2], &shares[0][0]], "secret");
+        assert!(output.status.success());
+        let restored: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(restored["xprv"], generated["xprv"]);
+        assert_eq!(restored["fingerprint"], generated["fingerprint"]);
+
+        let output = restore(&[&shares[1][0], &shares[0][2], &shares[0][0]], "wrong");
+        let restored: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_ne!(restored["xprv"], generated["xprv"]);
+
+        let output = restore(&[&shares[1][0], &shares[0][2]], "secret");
+        assert!(!output.status.success());
+    }
+
+    #[test]
+    fn test_cli_key_generate_asks_passphrase_twice() {
+        let cli = BdkCli::new("testnet", None);
+
+        cli.key_cmd(&["generate", "--ask-passphrase"])
+            .write_stdin("secret\nsecret\n")
+            .assert()
+            .success();
+        cli.key_cmd(&["generate", "--ask-passphrase"])
+            .write_stdin("secret\nsecert\n")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("do not match"));
+    }
 }
 
 // --- WALLETS COMMAND TESTS ---
@@ -164,6 +639,541 @@ mod test_wallets {
     }
 }
 
+// --- PSBT COMMAND TESTS ---
+mod test_psbt {
+    use super::*;
+    use bdk_wallet::bitcoin::absolute::LockTime;
+    use bdk_wallet::bitcoin::base64::{Engine, prelude::BASE64_STANDARD};
+    use bdk_wallet::bitcoin::consensus::encode::deserialize_hex;
+    use bdk_wallet::bitcoin::hex::DisplayHex;
+    use bdk_wallet::bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+    use bdk_wallet::bitcoin::sighash::{EcdsaSighashType, SighashCache};
+    use bdk_wallet::bitcoin::transaction::Version;
+    use bdk_wallet::bitcoin::{
+        Amount, CompressedPublicKey, OutPoint, Psbt, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+        Witness, ecdsa,
+    };
+    use serde_json::Value;
+
+    /// A one input, one output PSBT paying a 1000 sat fee.
+    fn sample_psbt() -> Psbt {
+        let script_pubkey =
+            ScriptBuf::from_hex("0014d85c2b71d0060b09c9886aeb815e50991dda124d").unwrap();
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(9_000),
+                script_pubkey: script_pubkey.clone(),
+            }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey,
+        });
+        psbt
+    }
+
+    /// [`sample_psbt`] spending a P2WPKH output, carrying a valid partial signature.
+    fn signed_psbt() -> Psbt {
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let public_key = CompressedPublicKey(secret_key.public_key(&secp));
+        let script_pubkey = ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash());
+
+        let mut psbt = sample_psbt();
+        let prevout = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey,
+        };
+        let sighash = SighashCache::new(&psbt.unsigned_tx)
+            .p2wpkh_signature_hash(
+                0,
+                &prevout.script_pubkey,
+                prevout.value,
+                EcdsaSighashType::All,
+            )
+            .unwrap();
+        let signature = secp.sign_ecdsa(&Message::from(sighash), &secret_key);
+        psbt.inputs[0].witness_utxo = Some(prevout);
+        psbt.inputs[0]
+            .partial_sigs
+            .insert(public_key.into(), ecdsa::Signature::sighash_all(signature));
+        psbt
+    }
+
+    #[test]
+    fn test_psbt_finalize_reports_per_input_status() {
+        let cli = BdkCli::new("regtest", None);
+
+        let unsigned = BASE64_STANDARD.encode(sample_psbt().serialize());
+        let output = cli.cmd("psbt", &["finalize", &unsigned]).output().unwrap();
+        assert!(output.status.success());
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(result["is_finalized"], false);
+        assert_eq!(result["inputs"][0]["is_finalized"], false);
+        assert!(result["inputs"][0]["error"].is_string());
+
+        let signed = BASE64_STANDARD.encode(signed_psbt().serialize());
+        let output = cli.cmd("psbt", &["finalize", &signed]).output().unwrap();
+        assert!(output.status.success());
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(result["is_finalized"], true);
+        assert!(result["inputs"][0].get("error").is_none());
+    }
+
+    #[test]
+    fn test_psbt_extract_writes_raw_transaction() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", None);
+
+        let unsigned = BASE64_STANDARD.encode(sample_psbt().serialize());
+        cli.cmd("psbt", &["extract", &unsigned])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("not finalized"));
+
+        let output = cli
+            .cmd(
+                "psbt",
+                &[
+                    "finalize",
+                    &BASE64_STANDARD.encode(signed_psbt().serialize()),
+                ],
+            )
+            .output()
+            .unwrap();
+        let finalized: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+        let tx_file = temp_dir.path().join("signed.tx");
+        let output = cli
+            .cmd(
+                "psbt",
+                &[
+                    "extract",
+                    finalized["psbt"].as_str().unwrap(),
+                    "--out-file",
+                    tx_file.to_str().unwrap(),
+                ],
+            )
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+
+        let extracted: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let raw_tx = extracted["raw_tx"].as_str().unwrap();
+        assert_eq!(std::fs::read_to_string(&tx_file).unwrap().trim(), raw_tx);
+        let tx: Transaction = deserialize_hex(raw_tx).unwrap();
+        assert_eq!(extracted["txid"], tx.compute_txid().to_string());
+        assert_eq!(tx.input[0].witness.len(), 2);
+    }
+
+    #[test]
+    fn test_psbt_analyze_reports_next_role() {
+        let cli = BdkCli::new("regtest", None);
+        let analyze = |psbt: &Psbt| -> Value {
+            let encoded = BASE64_STANDARD.encode(psbt.serialize());
+            let output = cli.cmd("psbt", &["analyze", &encoded]).output().unwrap();
+            assert!(output.status.success());
+            serde_json::from_slice(&output.stdout).unwrap()
+        };
+
+        let mut without_utxo = sample_psbt();
+        without_utxo.inputs[0].witness_utxo = None;
+        let analysis = analyze(&without_utxo);
+        assert_eq!(analysis["next"], "updater");
+        assert!(analysis["estimated_vsize"].is_null());
+
+        assert_eq!(analyze(&sample_psbt())["next"], "signer");
+
+        let analysis = analyze(&signed_psbt());
+        assert_eq!(analysis["next"], "finalizer");
+        assert_eq!(analysis["fee"], 1_000);
+        let vsize = analysis["estimated_vsize"].as_u64().unwrap();
+        assert!((109..=111).contains(&vsize), "unexpected vsize {vsize}");
+    }
+
+    #[test]
+    fn test_psbt_diff_reports_changed_fields() {
+        let cli = BdkCli::new("regtest", None);
+        let diff = |first: &Psbt, second: &Psbt| -> Value {
+            let first = BASE64_STANDARD.encode(first.serialize());
+            let second = BASE64_STANDARD.encode(second.serialize());
+            let output = cli
+                .cmd("psbt", &["diff", &first, &second])
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            serde_json::from_slice(&output.stdout).unwrap()
+        };
+
+        let result = diff(&sample_psbt(), &sample_psbt());
+        assert_eq!(result["identical"], true);
+
+        let result = diff(&sample_psbt(), &signed_psbt());
+        assert_eq!(result["identical"], false);
+        assert!(result["outputs"].as_array().unwrap().is_empty());
+        let input = &result["inputs"][0];
+        assert_eq!(input["change"], "changed");
+        let fields = input["fields"].as_array().unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0]["field"], "witness_utxo");
+        assert_eq!(fields[0]["change"], "changed");
+        assert_eq!(fields[1]["field"], "partial_sig");
+        assert_eq!(fields[1]["change"], "added");
+        assert_eq!(fields[1]["key"].as_str().unwrap().len(), 66);
+
+        let mut injected = sample_psbt();
+        injected.unsigned_tx.output.push(TxOut {
+            value: Amount::from_sat(500),
+            script_pubkey: ScriptBuf::new_op_return([1, 2, 3]),
+        });
+        injected.outputs.push(Default::default());
+        let result = diff(&sample_psbt(), &injected);
+        assert!(result["inputs"].as_array().unwrap().is_empty());
+        let outputs = result["outputs"].as_array().unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0]["change"], "added");
+        assert_eq!(outputs[0]["second"], 1);
+        assert!(outputs[0]["first"].is_null());
+    }
+
+    #[test]
+    fn test_psbt_ur_round_trip() {
+        let cli = BdkCli::new("regtest", None);
+        let psbt = sample_psbt();
+        let encoded = BASE64_STANDARD.encode(psbt.serialize());
+
+        let output = cli
+            .cmd("psbt", &["ur-encode", &encoded, "--max-fragment-len", "30"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let encoded_parts: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let mut parts: Vec<&str> = encoded_parts["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|part| part.as_str().unwrap())
+            .collect();
+        assert!(parts.len() > 1);
+        assert!(parts.iter().all(|part| part.starts_with("ur:crypto-psbt/")));
+
+        parts.reverse();
+        let mut args = vec!["ur-decode"];
+        args.extend(&parts);
+        let output = cli.cmd("psbt", &args).output().unwrap();
+        assert!(output.status.success());
+        let decoded: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(decoded["psbt"], encoded);
+
+        let output = cli.cmd("psbt", &args[..args.len() - 1]).output().unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("missing parts 1"));
+
+        // One PNG QR code per part, and the codes printed in turn when not on a terminal.
+        let dir = TempDir::new().unwrap();
+        let qr_dir = dir.path().join("frames");
+        let output = cli
+            .cmd(
+                "psbt",
+                &[
+                    "ur-encode",
+                    &encoded,
+                    "--max-fragment-len",
+                    "30",
+                    "--qr",
+                    "--qr-dir",
+                    qr_dir.to_str().unwrap(),
+                ],
+            )
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains('▀'));
+        for index in 1..=parts.len() {
+            let png = std::fs::read(qr_dir.join(format!("part-{index}.png"))).unwrap();
+            assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        }
+        assert!(
+            !qr_dir
+                .join(format!("part-{}.png", parts.len() + 1))
+                .exists()
+        );
+    }
+
+    #[test]
+    fn test_psbt_bbqr_split_and_join() {
+        let cli = BdkCli::new("regtest", None);
+        let encoded = BASE64_STANDARD.encode(sample_psbt().serialize());
+
+        for encoding in ["hex", "base32"] {
+            let output = cli
+                .cmd(
+                    "psbt",
+                    &[
+                        "bbqr-split",
+                        &encoded,
+                        "--encoding",
+                        encoding,
+                        "--max-part-len",
+                        "40",
+                    ],
+                )
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            let split: Value = serde_json::from_slice(&output.stdout).unwrap();
+            let mut parts: Vec<&str> = split["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|part| part.as_str().unwrap())
+                .collect();
+            assert!(parts.len() > 1);
+            assert!(parts.iter().all(|part| part.starts_with("B$")));
+
+            parts.reverse();
+            let mut args = vec!["bbqr-join"];
+            args.extend(&parts);
+            let output = cli.cmd("psbt", &args).output().unwrap();
+            assert!(output.status.success());
+            let joined: Value = serde_json::from_slice(&output.stdout).unwrap();
+            assert_eq!(joined["psbt"], encoded);
+
+            let output = cli
+                .cmd("psbt", &["decode", &parts.join(" ")])
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+        }
+    }
+
+    #[test]
+    fn test_psbt_decode_base64_and_hex() {
+        let cli = BdkCli::new("regtest", None);
+        let psbt = sample_psbt();
+
+        for encoded in [
+            BASE64_STANDARD.encode(psbt.serialize()),
+            psbt.serialize().to_lower_hex_string(),
+        ] {
+            let output = cli.cmd("psbt", &["decode", &encoded]).output().unwrap();
+            assert!(output.status.success());
+
+            let decoded: Value = serde_json::from_slice(&output.stdout).unwrap();
+            assert_eq!(decoded["fee"], 1_000);
+            assert_eq!(decoded["is_finalized"], false);
+            assert_eq!(
+                decoded["outputs"][0]["address"],
+                "bcrt1qmpwzkuwsqc9snjvgdt4czhjsnywa5yjdqpxskv"
+            );
+        }
+    }
+
+    #[test]
+    fn test_psbt_decode_binary_and_base64_files() {
+        let cli = BdkCli::new("regtest", None);
+        let psbt = sample_psbt();
+        let dir = TempDir::new().unwrap();
+        let binary = dir.path().join("binary.psbt");
+        let text = dir.path().join("text.psbt");
+        std::fs::write(&binary, psbt.serialize()).unwrap();
+        std::fs::write(&text, BASE64_STANDARD.encode(psbt.serialize()) + "\n").unwrap();
+
+        for path in [&binary, &text] {
+            let output = cli
+                .cmd("psbt", &["decode", "--psbt-file", path.to_str().unwrap()])
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            let decoded: Value = serde_json::from_slice(&output.stdout).unwrap();
+            assert_eq!(decoded["fee"], 1_000);
+        }
+    }
+
+    #[test]
+    fn test_psbt_decode_rejects_garbage() {
+        BdkCli::new("regtest", None)
+            .cmd("psbt", &["decode", "not a psbt"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("neither hex nor base64"));
+    }
+}
+
+// --- MULTISIG COMMAND TESTS ---
+mod test_multisig {
+    use super::*;
+    use bdk_wallet::bitcoin::absolute::LockTime;
+    use bdk_wallet::bitcoin::base64::{Engine, prelude::BASE64_STANDARD};
+    use bdk_wallet::bitcoin::bip32::{DerivationPath, Fingerprint};
+    use bdk_wallet::bitcoin::opcodes::all::{OP_CHECKMULTISIG, OP_PUSHNUM_1, OP_PUSHNUM_2};
+    use bdk_wallet::bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+    use bdk_wallet::bitcoin::sighash::{EcdsaSighashType, SighashCache};
+    use bdk_wallet::bitcoin::transaction::Version;
+    use bdk_wallet::bitcoin::{
+        Amount, OutPoint, Psbt, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+        ecdsa,
+    };
+    use serde_json::Value;
+
+    /// A 2-of-2 P2WSH spend, and the same PSBT signed by each cosigner.
+    fn multisig_psbts() -> (Psbt, Psbt, Psbt) {
+        let secp = Secp256k1::new();
+        let secret_keys = [[1; 32], [2; 32]].map(|key| SecretKey::from_slice(&key).unwrap());
+        let public_keys = secret_keys.map(|key| PublicKey::new(key.public_key(&secp)));
+        let witness_script = ScriptBuf::builder()
+            .push_opcode(OP_PUSHNUM_2)
+            .push_key(&public_keys[0])
+            .push_key(&public_keys[1])
+            .push_opcode(OP_PUSHNUM_2)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script();
+        let prevout = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2wsh(&witness_script.wscript_hash()),
+        };
+
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(9_000),
+                script_pubkey: prevout.script_pubkey.clone(),
+            }],
+        };
+        let mut unsigned = Psbt::from_unsigned_tx(tx).unwrap();
+        unsigned.inputs[0].witness_utxo = Some(prevout.clone());
+        unsigned.inputs[0].witness_script = Some(witness_script.clone());
+        for (index, public_key) in public_keys.iter().enumerate() {
+            unsigned.inputs[0].bip32_derivation.insert(
+                public_key.inner,
+                (
+                    Fingerprint::from([index as u8 + 1; 4]),
+                    DerivationPath::master(),
+                ),
+            );
+        }
+
+        let sighash = SighashCache::new(&unsigned.unsigned_tx)
+            .p2wsh_signature_hash(0, &witness_script, prevout.value, EcdsaSighashType::All)
+            .unwrap();
+        let [first, second] = [0, 1].map(|index| {
+            let signature = secp.sign_ecdsa(&Message::from(sighash), &secret_keys[index]);
+            let mut signed = unsigned.clone();
+            signed.inputs[0]
+                .partial_sigs
+                .insert(public_keys[index], ecdsa::Signature::sighash_all(signature));
+            signed
+        });
+        (unsigned, first, second)
+    }
+
+    #[test]
+    fn test_psbt_signers_reports_outstanding_cosigners() {
+        let cli = BdkCli::new("regtest", None);
+        let (_, first, _) = multisig_psbts();
+
+        let output = cli
+            .cmd(
+                "psbt",
+                &[
+                    "signers",
+                    &BASE64_STANDARD.encode(first.serialize()),
+                    "--alias",
+                    "01010101=alice",
+                ],
+            )
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+
+        let signers: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let input = &signers["inputs"][0];
+        assert_eq!(input["signed"][0]["fingerprint"], "01010101");
+        assert_eq!(input["signed"][0]["alias"], "alice");
+        assert_eq!(input["outstanding"][0]["fingerprint"], "02020202");
+        assert!(input["outstanding"][0]["alias"].is_null());
+        assert_eq!(input["threshold"], 2);
+
+        // A single signature completes a 1-of-2 input.
+        let mut one_of_two = first.clone();
+        let witness_script = one_of_two.inputs[0].witness_script.as_mut().unwrap();
+        let mut script = witness_script.to_bytes();
+        script[0] = OP_PUSHNUM_1.to_u8();
+        *witness_script = ScriptBuf::from_bytes(script);
+        let output = cli
+            .cmd(
+                "psbt",
+                &["signers", &BASE64_STANDARD.encode(one_of_two.serialize())],
+            )
+            .output()
+            .unwrap();
+        let signers: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(signers["inputs"][0]["threshold"], 1);
+        assert_eq!(signers["inputs"][0]["outstanding"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_multisig_session_collects_signatures_until_final() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let run = |args: &[&str]| -> Value {
+            let output = cli.cmd("multisig", args).output().unwrap();
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            serde_json::from_slice(&output.stdout).unwrap()
+        };
+        let encode = |psbt: &Psbt| BASE64_STANDARD.encode(psbt.serialize());
+        let (unsigned, first, second) = multisig_psbts();
+
+        let status = run(&["create", "spend", &encode(&unsigned)]);
+        assert_eq!(status["cosigners"].as_array().unwrap().len(), 2);
+        assert_eq!(status["ready"], false);
+
+        let status = run(&["add", "spend", &encode(&first)]);
+        assert_eq!(status["cosigners"][0]["fingerprint"], "01010101");
+        assert_eq!(status["cosigners"][0]["signed"], true);
+        assert_eq!(status["cosigners"][1]["signed"], false);
+        assert_eq!(status["ready"], false);
+        assert_eq!(run(&["finalize", "spend"])["is_finalized"], false);
+
+        let status = run(&["add", "spend", &encode(&second)]);
+        assert_eq!(status["contributions"], 2);
+        assert_eq!(status["ready"], true);
+
+        let finalized = run(&["finalize", "spend"]);
+        assert_eq!(finalized["is_finalized"], true);
+        assert_eq!(run(&["status", "spend"])["is_finalized"], true);
+
+        cli.cmd("multisig", &["add", "spend", &encode(&first)])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("already finalized"));
+        cli.cmd("multisig", &["create", "spend", &encode(&unsigned)])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("already exists"));
+    }
+}
+
 // --- DESCRIPTOR COMMAND TESTS ---
 mod test_descriptor {
     use super::*;
@@ -203,6 +1213,76 @@ mod test_compile {
             .stdout(predicate::str::contains("wsh("));
     }
 
+    #[test]
+    fn test_compile_taproot_policy() {
+        let cli = BdkCli::new("testnet", None);
+        let key_a = "e5b88fdb71c696e1a473f309a47535b7190e21a22bd25e7fc8bd055db3bba12f";
+        let key_b = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+        let compile = |policy: &str| -> serde_json::Value {
+            let output = cli
+                .cmd("compile", &[policy, "--type", "tr"])
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            serde_json::from_slice(&output.stdout).unwrap()
+        };
+
+        // The likely branch spends through the key path, the other one is a leaf.
+        let result = compile(&format!("or(9@pk({key_a}),1@and(pk({key_b}),older(144)))"));
+        let descriptor = result["descriptor"].as_str().unwrap();
+        assert!(
+            descriptor.starts_with(&format!("tr({key_a},and_v(v:pk({key_b}),older(144)))")),
+            "{descriptor}"
+        );
+        assert!(result["r"].is_null());
+
+        // Without a single key to extract, the internal key is unspendable.
+        let result = compile(&format!("and(pk({key_a}),pk({key_b}))"));
+        let descriptor = result["descriptor"].as_str().unwrap();
+        assert!(
+            !descriptor.starts_with(&format!("tr({key_a}")),
+            "{descriptor}"
+        );
+        assert!(result["r"].is_string());
+    }
+
+    #[test]
+    fn test_compile_analyze_spending_paths() {
+        let cli = BdkCli::new("testnet", None);
+        let output = cli
+            .cmd(
+                "compile",
+                &[
+                    "or(9@pk(A),1@and(pk(B),older(144)))",
+                    "--type",
+                    "wsh",
+                    "--analyze",
+                    "--fee_rate",
+                    "2",
+                ],
+            )
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let paths = result["spending_paths"].as_array().unwrap();
+        assert_eq!(paths.len(), 2);
+
+        // A signature and the 67 bytes witness script.
+        assert_eq!(paths[0]["signers"], serde_json::json!(["A"]));
+        assert!(paths[0]["older"].is_null());
+        assert_eq!(paths[0]["satisfaction_weight"], 146);
+        assert_eq!(paths[0]["input_vbytes"], 77);
+        assert_eq!(paths[0]["fee"], 154);
+
+        assert_eq!(paths[1]["signers"], serde_json::json!(["B"]));
+        assert_eq!(paths[1]["older"], 144);
+        assert!(
+            paths[1]["satisfaction_weight"].as_u64().unwrap()
+                > paths[0]["satisfaction_weight"].as_u64().unwrap()
+        );
+    }
+
     #[test]
     fn test_compile_invalid_policy() {
         let temp_dir = TempDir::new().unwrap();
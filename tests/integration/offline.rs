@@ -3,7 +3,7 @@ mod test_offline {
     use crate::common::BdkCli;
     use assert_cmd::Command;
     use predicates::prelude::*;
-    use serde_json::Value;
+    use serde_json::{Value, json};
     use tempfile::TempDir;
 
     static WALLET_NAME: &str = "test_config_wallet";
@@ -67,6 +67,36 @@ mod test_offline {
             .stdout(predicate::str::contains("\"address\":"));
     }
 
+    #[test]
+    fn test_wallet_in_use_is_not_opened() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .assert()
+            .success();
+
+        // A daemon keeping the wallet loaded holds its lock.
+        let lock_path = cli
+            .datadir
+            .clone()
+            .unwrap()
+            .join(WALLET_NAME)
+            .join("wallet.lock");
+        let lock = std::fs::File::open(&lock_path).unwrap();
+        lock.lock().unwrap();
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(format!(
+                "Wallet '{WALLET_NAME}' is in use by another process"
+            )));
+
+        lock.unlock().unwrap();
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .assert()
+            .success();
+    }
+
     #[test]
     fn test_empty_wallet_balances_and_lists() {
         let (cli, mut cmd_init) = setup_wallet_config();
@@ -115,6 +145,91 @@ mod test_offline {
             .stdout(predicate::str::contains("internal"));
     }
 
+    #[test]
+    fn test_multipath_descriptor_expands_into_both_keychains() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+
+        let desc = cli.cmd("descriptor", &["--type", "wpkh"]).output().unwrap();
+        let desc_values: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let multipath = desc_values["multipath_descriptor"].as_str().unwrap();
+        assert!(multipath.contains("/<0;1>/*"), "{multipath}");
+
+        let config = |extra: &[&str]| {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args(["wallet", "--wallet", WALLET_NAME, "config", "--force"])
+                .args(["--ext-descriptor", multipath])
+                .args(extra)
+                .args(["--client-type", "rpc", "--database-type", "sqlite"])
+                .args(["--url", "http://localhost:18443"]);
+            cmd
+        };
+        config(&[]).assert().success();
+
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "public_descriptor"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let descriptors: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let public = &desc_values["public_descriptors"];
+        assert_eq!(descriptors["external"], public["external"]);
+        assert_eq!(descriptors["internal"], public["internal"]);
+        assert_eq!(descriptors["multipath"], multipath);
+
+        let internal = public["internal"].as_str().unwrap();
+        config(&["--int-descriptor", internal]).assert().success();
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("drop --int-descriptor"));
+    }
+
+    #[test]
+    fn test_gap_warns_when_nearing_stop_gap() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.arg("--stop-gap").arg("4").assert().success();
+
+        for _ in 0..3 {
+            cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+                .assert()
+                .success();
+        }
+
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "gap"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("\"largest_unused_gap\": 3"))
+            .stdout(predicate::str::contains("\"stop_gap\": 4"))
+            .stderr(predicate::str::contains("nearing the stop gap of 4"));
+    }
+
+    #[test]
+    fn test_timelocks_reports_absolute_timelock() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+
+        let key = cli.key_cmd(&["generate"]).output().unwrap();
+        let key: Value = serde_json::from_slice(&key.stdout).unwrap();
+        let xprv = key["xprv"].as_str().unwrap();
+        let ext_desc = format!("wsh(and_v(v:pk({xprv}/0/*),after(1000000)))");
+
+        cli.build_base_cmd()
+            .args(["wallet", "--wallet", WALLET_NAME, "config"])
+            .args(["--ext-descriptor", &ext_desc])
+            .args(["--client-type", "rpc", "--database-type", "sqlite"])
+            .args(["--url", "http://localhost:18443"])
+            .assert()
+            .success();
+
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "timelocks"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("\"kind\": \"absolute\""))
+            .stdout(predicate::str::contains("\"satisfiable\": false"))
+            .stdout(predicate::str::contains("\"unlock_height\": 1000001"));
+    }
+
     #[test]
     fn test_create_tx_insufficient_funds() {
         let (cli, mut cmd_init) = setup_wallet_config();
@@ -133,6 +248,130 @@ mod test_offline {
         .stderr(predicate::str::contains("Insufficient"));
     }
 
+    #[test]
+    fn test_label_round_trip_and_create_tx_label_filter() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+
+        let address = "tb1p4tp4l6glyr2gs94neqcpr5gha7344nfyznfkc8szkreflscsdkgqsdent4";
+        cli.wallet_cmd(&[
+            "--wallet",
+            WALLET_NAME,
+            "label",
+            "--ref",
+            address,
+            "--label",
+            "donations",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"type\": \"addr\""));
+
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "labels"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("\"label\": \"donations\""));
+
+        // No wallet utxo carries the label, so there is nothing to spend.
+        cli.wallet_cmd(&[
+            "--wallet",
+            WALLET_NAME,
+            "create_tx",
+            "--to",
+            &format!("{address}:1000"),
+            "--from-label",
+            "donations",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No spendable utxos"));
+
+        // The `LABEL` of `label` is not taken as a filter of `create_tx`.
+        cli.wallet_cmd(&[
+            "--wallet",
+            WALLET_NAME,
+            "create_tx",
+            "--to",
+            &format!("{address}:1000"),
+        ])
+        .env("LABEL", "donations")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No spendable utxos").not());
+
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "label", "--ref", address])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("\"count\": 0"));
+    }
+
+    #[test]
+    fn test_vanity_finds_and_reserves_address() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let vanity = |args: &[&str]| -> Value {
+            let output = cli
+                .wallet_cmd(&[&["--wallet", WALLET_NAME, "vanity"], args].concat())
+                .output()
+                .unwrap();
+            assert!(
+                output.status.success(),
+                "vanity failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            serde_json::from_slice(&output.stdout).unwrap()
+        };
+
+        let found = vanity(&["--pattern", "bcrt1pq", "--max-index", "1000"]);
+        let index = found["index"].as_u64().unwrap();
+        assert!(found["address"].as_str().unwrap().starts_with("bcrt1pq"));
+        assert_eq!(found["tried"].as_u64().unwrap(), index + 1);
+        assert_eq!(found["reserved"], false);
+
+        // Without reserving, the same address is found again.
+        let reserved = vanity(&[
+            "--pattern",
+            "bcrt1pq",
+            "--max-index",
+            "1000",
+            "--reserve",
+            "--label",
+            "vanity",
+        ]);
+        assert_eq!(reserved["address"], found["address"]);
+        assert_eq!(reserved["reserved"], true);
+
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "labels"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(found["address"].as_str().unwrap()));
+
+        // Reserved addresses are revealed and no longer searched or handed out.
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .output()
+            .unwrap();
+        let next: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(next["index"].as_u64().unwrap(), index + 1);
+        assert_ne!(
+            vanity(&["--pattern", "bcrt1pq", "--max-index", "2000"])["address"],
+            found["address"]
+        );
+
+        cli.wallet_cmd(&[
+            "--wallet",
+            WALLET_NAME,
+            "vanity",
+            "--pattern",
+            "bcrt1pqqqqqqqq",
+            "--max-index",
+            "10",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No external address"));
+    }
+
     #[test]
     fn test_combine_psbt_invalid_input() {
         let (cli, mut cmd_init) = setup_wallet_config();
@@ -151,154 +390,2589 @@ mod test_offline {
         .stderr(predicate::str::contains("Invalid"));
     }
 
-    #[cfg(feature = "bip322")]
     #[test]
-    fn test_sign_message_and_verify_message() {
+    fn test_combine_psbt_from_file_reports_conflicts() {
+        use bdk_wallet::bitcoin::base64::{Engine, prelude::BASE64_STANDARD};
+        use bdk_wallet::bitcoin::transaction::Version;
+        use bdk_wallet::bitcoin::{
+            EcdsaSighashType, OutPoint, Psbt, Sequence, Transaction, TxIn, absolute::LockTime,
+        };
+
         let (cli, mut cmd_init) = setup_wallet_config();
         cmd_init.assert().success();
 
-        let message = "bdk-cli integration test";
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            }],
+            output: vec![],
+        };
+        let encode = |sighash: EcdsaSighashType| {
+            let mut psbt = Psbt::from_unsigned_tx(tx.clone()).unwrap();
+            psbt.inputs[0].sighash_type = Some(sighash.into());
+            BASE64_STANDARD.encode(psbt.serialize())
+        };
 
-        // Reveal exactly one address and reuse it for both sign and verify.
-        let addr_output = cli
-            .wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
-            .output()
-            .expect("Failed to generate address");
-        assert!(
-            addr_output.status.success(),
-            "new_address failed: {}",
-            String::from_utf8_lossy(&addr_output.stderr)
-        );
-        let addr_json: Value = serde_json::from_slice(&addr_output.stdout).unwrap();
-        let address = addr_json["address"]
-            .as_str()
-            .expect("missing address")
-            .to_string();
-        println!("DEBUG signing address: {address}");
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("psbts.txt");
+        std::fs::write(&file, encode(EcdsaSighashType::All)).unwrap();
 
-        // Sign (default signature_type = "simple").
-        let sign_output = cli
+        let output = cli
             .wallet_cmd(&[
                 "--wallet",
                 WALLET_NAME,
-                "sign_message",
-                "--message",
-                message,
-                "--address",
-                &address,
+                "combine_psbt",
+                "--file",
+                file.to_str().unwrap(),
+                &encode(EcdsaSighashType::Single),
             ])
             .output()
-            .expect("Failed to sign message");
-        assert!(
-            sign_output.status.success(),
-            "sign_message failed: {}",
-            String::from_utf8_lossy(&sign_output.stderr)
+            .unwrap();
+        assert!(output.status.success());
+
+        let combined: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(combined["conflicts"][0]["input"], 0);
+        assert_eq!(combined["conflicts"][0]["field"], "sighash_type");
+        assert_eq!(combined["conflicts"][0]["psbt"], 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sign_with_hwi_merges_device_signature() {
+        use bdk_wallet::bitcoin::base64::{Engine, prelude::BASE64_STANDARD};
+        use bdk_wallet::bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+        use bdk_wallet::bitcoin::transaction::Version;
+        use bdk_wallet::bitcoin::{
+            OutPoint, Psbt, PublicKey, Sequence, Transaction, TxIn, absolute::LockTime, ecdsa,
+        };
+        use std::os::unix::fs::PermissionsExt;
+
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            }],
+            output: vec![],
+        };
+        let unsigned = Psbt::from_unsigned_tx(tx).unwrap();
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let mut signed = unsigned.clone();
+        signed.inputs[0].partial_sigs.insert(
+            PublicKey::new(secret_key.public_key(&secp)),
+            ecdsa::Signature::sighash_all(
+                secp.sign_ecdsa(&Message::from_digest([7; 32]), &secret_key),
+            ),
         );
-        let sign_json: Value = serde_json::from_slice(&sign_output.stdout).unwrap();
-        let proof = sign_json["proof"].as_str().expect("missing proof");
-        println!("DEBUG proof: {proof}");
-        println!("DEBUG verifying same address: {address}");
 
-        // Verify with the identical address + message.
-        cli.wallet_cmd(&[
-            "--wallet",
-            WALLET_NAME,
-            "verify_message",
-            "--address",
-            &address,
-            "--message",
-            message,
-            "--proof",
-            proof,
-        ])
-        .assert()
-        .success()
-        .stdout(predicate::str::contains("\"valid\": true"));
+        // A stand-in for HWI with two devices connected.
+        let temp_dir = TempDir::new().unwrap();
+        let hwi = temp_dir.path().join("hwi");
+        std::fs::write(
+            &hwi,
+            format!(
+                r#"#!/bin/sh
+case "$*" in
+  *enumerate*) echo '[{{"type":"coldcard","model":"coldcard","fingerprint":"01020304"}},{{"type":"trezor","model":"trezor_t","fingerprint":"0a0b0c0d"}}]' ;;
+  *"--fingerprint 01020304 signtx"*) echo '{{"psbt":"{}"}}' ;;
+  *) echo '{{"error":"unexpected call","code":-1}}' ;;
+esac
+"#,
+                BASE64_STANDARD.encode(signed.serialize())
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&hwi, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let encoded = BASE64_STANDARD.encode(unsigned.serialize());
+        let sign = |device: Option<&str>| {
+            let mut args = vec![
+                "--wallet",
+                WALLET_NAME,
+                "sign",
+                &encoded,
+                "--hwi",
+                "--hwi_path",
+                hwi.to_str().unwrap(),
+            ];
+            if let Some(device) = device {
+                args.extend(["--device", device]);
+            }
+            cli.wallet_cmd(&args).output().unwrap()
+        };
+
+        let output = sign(None);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("select one with --device"));
+
+        let output = sign(Some("01020304"));
+        assert!(output.status.success());
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let psbt = Psbt::deserialize(
+            &BASE64_STANDARD
+                .decode(result["psbt"].as_str().unwrap())
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 1);
     }
 
-    #[cfg(feature = "bip322")]
+    #[cfg(unix)]
     #[test]
-    fn test_verify_message_rejects_tampered_message() {
+    fn test_sign_with_external_signer() {
+        use bdk_wallet::bitcoin::base64::{Engine, prelude::BASE64_STANDARD};
+        use bdk_wallet::bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+        use bdk_wallet::bitcoin::transaction::Version;
+        use bdk_wallet::bitcoin::{
+            OutPoint, Psbt, PublicKey, Sequence, Transaction, TxIn, absolute::LockTime, ecdsa,
+        };
+
         let (cli, mut cmd_init) = setup_wallet_config();
         cmd_init.assert().success();
 
-        let addr_output = cli
-            .wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
-            .output()
-            .expect("Failed to generate address");
-        let addr_json: Value = serde_json::from_slice(&addr_output.stdout).unwrap();
-        let address = addr_json["address"].as_str().unwrap();
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            }],
+            output: vec![],
+        };
+        let unsigned = Psbt::from_unsigned_tx(tx).unwrap();
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let mut signed = unsigned.clone();
+        signed.inputs[0].partial_sigs.insert(
+            PublicKey::new(secret_key.public_key(&secp)),
+            ecdsa::Signature::sighash_all(
+                secp.sign_ecdsa(&Message::from_digest([7; 32]), &secret_key),
+            ),
+        );
 
-        let sign_output = cli
-            .wallet_cmd(&[
+        // The signer checks it received the PSBT before answering.
+        let encoded = BASE64_STANDARD.encode(unsigned.serialize());
+        let temp_dir = TempDir::new().unwrap();
+        let signer = temp_dir.path().join("signer.sh");
+        std::fs::write(
+            &signer,
+            format!(
+                "[ \"$(cat)\" = \"{encoded}\" ] && echo '{}'",
+                BASE64_STANDARD.encode(signed.serialize())
+            ),
+        )
+        .unwrap();
+
+        let sign = |command: &str| {
+            cli.wallet_cmd(&[
                 "--wallet",
                 WALLET_NAME,
-                "sign_message",
-                "--message",
-                "original message",
-                "--address",
-                address,
+                "sign",
+                &encoded,
+                "--external-signer",
+                command,
             ])
             .output()
-            .expect("Failed to sign message");
-        let sign_json: Value = serde_json::from_slice(&sign_output.stdout).unwrap();
-        let proof = sign_json["proof"].as_str().unwrap();
+            .unwrap()
+        };
 
-        // A tampered message should fail
+        let output = sign(&format!("sh {}", signer.display()));
+        assert!(output.status.success());
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let psbt = Psbt::deserialize(
+            &BASE64_STANDARD
+                .decode(result["psbt"].as_str().unwrap())
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 1);
+
+        let output = sign("exit 3");
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("failed with exit status: 3"));
+
+        // The signed PSBT goes to `--out-file`, the file it was read from is left as is.
+        let input = temp_dir.path().join("unsigned.psbt");
+        let out = temp_dir.path().join("signed.psbt");
+        std::fs::write(&input, unsigned.serialize()).unwrap();
         cli.wallet_cmd(&[
             "--wallet",
             WALLET_NAME,
-            "verify_message",
-            "--proof",
-            proof,
-            "--message",
-            "tampered message",
-            "--address",
-            address,
+            "sign",
+            "--external-signer",
+            &format!("sh {}", signer.display()),
         ])
+        .env("PSBT_FILE", &input)
+        .env("PSBT_OUT_FILE", &out)
         .assert()
-        .success()
-        .stdout(predicate::str::contains("\"valid\": false"));
+        .success();
+        assert_eq!(std::fs::read(&input).unwrap(), unsigned.serialize());
+        let psbt = Psbt::deserialize(&std::fs::read(&out).unwrap()).unwrap();
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 1);
     }
 
+    #[cfg(feature = "cbf")]
     #[test]
-    fn test_create_tx_send_all_rejects_multiple_recipients() {
+    fn test_config_saves_cbf_peers() {
         let (cli, mut cmd_init) = setup_wallet_config();
-        cmd_init.assert().success();
+        cmd_init
+            .args([
+                "--cbf-peer",
+                "127.0.0.1:18444,node.local:18444",
+                "--cbf-whitelist-only",
+                "--cbf-response-timeout",
+                "30",
+            ])
+            .assert()
+            .success();
 
-        // Same address twice => two recipients => must be rejected under --send_all.
-        let recipient = "tb1p4tp4l6glyr2gs94neqcpr5gha7344nfyznfkc8szkreflscsdkgqsdent4:0";
+        let config_path = cli.datadir.clone().unwrap().join("config.toml");
+        let config = std::fs::read_to_string(config_path).unwrap();
+        assert!(config.contains("cbf_peers = ["));
+        assert!(
+            config.contains(r#""127.0.0.1:18444""#) && config.contains(r#""node.local:18444""#)
+        );
+        assert!(config.contains("cbf_whitelist_only = true"));
+        assert!(config.contains("cbf_response_timeout = 30"));
+
+        let (_, mut cmd_init) = setup_wallet_config();
+        cmd_init
+            .args(["--cbf-peer", "node.local"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "expected an IP address or host:port",
+            ));
+
+        let (_, mut cmd_init) = setup_wallet_config();
+        cmd_init.arg("--cbf-whitelist-only").assert().failure();
+    }
+
+    #[test]
+    fn test_config_reads_bitcoin_conf_of_node() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        let node = TempDir::new().unwrap();
+        std::fs::write(
+            node.path().join("bitcoin.conf"),
+            "signet=1\nrpcuser=alice\nrpcpassword=secret\n[signet]\nrpcport=1\n",
+        )
+        .unwrap();
+
+        // `--node` replaces `--url`.
+        cmd_init
+            .arg("--node")
+            .arg(node.path())
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_values: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_values["public_descriptors"];
         cli.wallet_cmd(&[
             "--wallet",
             WALLET_NAME,
-            "create_tx",
-            "--send_all",
-            "--to",
-            recipient,
-            "--to",
-            recipient,
+            "config",
+            "--ext-descriptor",
+            public["external"].as_str().unwrap(),
+            "--int-descriptor",
+            public["internal"].as_str().unwrap(),
+            "--client-type",
+            "rpc",
+            "--database-type",
+            "sqlite",
+            "--node",
+            node.path().to_str().unwrap(),
+            "--rpc-wallet",
+            "watchonly",
         ])
         .assert()
-        .failure()
+        .success()
         .stderr(predicate::str::contains(
-            "Wallet can only be drained to a single output",
+            "Bitcoin Core runs on signet, configuring the wallet for signet",
         ));
-    }
-}
 
-#[cfg(all(feature = "repl", feature = "electrum"))]
-mod repl_tests {
-    use crate::common::BdkCli;
-    use serde_json::Value;
-    use tempfile::TempDir;
+        let config_path = cli.datadir.clone().unwrap().join("config.toml");
+        let config = std::fs::read_to_string(config_path).unwrap();
+        assert!(config.contains(r#"network = "signet""#));
+        assert!(config.contains(r#"rpc_wallet = "watchonly""#));
 
-    const WALLET_NAME: &str = "repl_test_wallet";
+        let output = BdkCli::new("signet", cli.datadir.clone())
+            .wallet_cmd(&["--wallet", WALLET_NAME, "sync"])
+            .output()
+            .unwrap();
+        // The rpc client connects to the rpcport of the signet section.
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Couldn't connect to host"));
+    }
 
-    fn setup_repl_wallet() -> (BdkCli, TempDir) {
-        let temp = TempDir::new().unwrap();
-        let cli = BdkCli::new("regtest", Some(temp.path().to_path_buf()));
+    #[test]
+    fn test_custom_signet_challenge() {
+        // A 1-of-1 multisig of an arbitrary key.
+        let challenge =
+            "512103abababababababababababababababababababababababababababababababab51ae";
 
-        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let (_, mut cmd_init) = setup_wallet_config();
+        cmd_init
+            .args(["--signet-challenge", challenge])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "--signet-challenge only applies to signet wallets, not to regtest",
+            ));
+
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("signet", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_values: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_values["public_descriptors"];
+        let config = |challenge: &str| {
+            cli.wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "config",
+                "--force",
+                "--ext-descriptor",
+                public["external"].as_str().unwrap(),
+                "--int-descriptor",
+                public["internal"].as_str().unwrap(),
+                "--client-type",
+                "rpc",
+                "--database-type",
+                "sqlite",
+                "--url",
+                "127.0.0.1:38332",
+                "--signet-challenge",
+                challenge,
+            ])
+            .assert()
+        };
+        config("51zz")
+            .failure()
+            .stderr(predicate::str::contains("Invalid signet challenge"));
+        config(challenge)
+            .success()
+            .stdout(predicate::str::contains("on the custom signet of magic"));
+
+        let config_path = temp_dir.path().join("config.toml");
+        let saved = std::fs::read_to_string(&config_path).unwrap();
+        assert!(saved.contains(&format!(r#"signet_challenge = "{challenge}""#)));
+
+        // Custom signets share the addresses of the default signet.
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("\"address\": \"tb1p"));
+    }
+
+    #[cfg(feature = "esplora")]
+    #[test]
+    fn test_mutinynet_preset() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("mutinynet", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_values: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_values["public_descriptors"];
+
+        // The backend and challenge of Mutinynet are the defaults.
+        cli.wallet_cmd(&[
+            "--wallet",
+            WALLET_NAME,
+            "config",
+            "--ext-descriptor",
+            public["external"].as_str().unwrap(),
+            "--int-descriptor",
+            public["internal"].as_str().unwrap(),
+            "--database-type",
+            "sqlite",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("on the custom signet of magic"));
+
+        let saved = std::fs::read_to_string(temp_dir.path().join("config.toml")).unwrap();
+        assert!(saved.contains(r#"network = "signet""#));
+        assert!(saved.contains(r#"client_type = "esplora""#));
+        assert!(saved.contains(r#"server_url = "https://mutinynet.com/api""#));
+        assert!(saved.contains(
+            r#"signet_challenge = "512102f7561d208dd9ae99bf497273e16f389bdbd6c4742ddb8e6b216e64fa2928ad8f51ae""#
+        ));
+
+        // The wallet is a signet wallet for the later commands.
+        BdkCli::new("signet", cli.datadir.clone())
+            .wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("\"address\": \"tb1p"));
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .assert()
+            .success();
+
+        // A wallet of the default signet is not on Mutinynet.
+        let signet = BdkCli::new("signet", cli.datadir.clone());
+        signet
+            .wallet_cmd(&[
+                "--wallet",
+                "default-signet",
+                "config",
+                "--ext-descriptor",
+                public["external"].as_str().unwrap(),
+                "--database-type",
+                "sqlite",
+                "--client-type",
+                "esplora",
+                "--url",
+                "https://mempool.space/signet/api",
+            ])
+            .assert()
+            .success();
+        cli.wallet_cmd(&["--wallet", "default-signet", "new_address"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("is not on Mutinynet"));
+    }
+
+    #[cfg(feature = "esplora")]
+    #[test]
+    fn test_testnet4_defaults() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("testnet4", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_values: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_values["public_descriptors"];
+        let config = |client: &str| {
+            cli.wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "config",
+                "--force",
+                "--ext-descriptor",
+                public["external"].as_str().unwrap(),
+                "--int-descriptor",
+                public["internal"].as_str().unwrap(),
+                "--database-type",
+                "sqlite",
+                "--client-type",
+                client,
+            ])
+        };
+
+        // Esplora defaults to the public testnet4 server.
+        config("esplora").assert().success();
+        let saved = std::fs::read_to_string(temp_dir.path().join("config.toml")).unwrap();
+        assert!(saved.contains(r#"network = "testnet4""#));
+        assert!(saved.contains(r#"server_url = "https://mempool.space/testnet4/api""#));
+
+        // A node has no public server.
+        config("rpc")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("pass it with --url"));
+
+        // The database of the testnet4 wallet is not reused by a testnet3 one of the same name.
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .assert()
+            .success();
+        BdkCli::new("testnet", cli.datadir.clone())
+            .wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "config",
+                "--force",
+                "--ext-descriptor",
+                public["external"].as_str().unwrap(),
+                "--int-descriptor",
+                public["internal"].as_str().unwrap(),
+                "--database-type",
+                "sqlite",
+                "--client-type",
+                "esplora",
+                "--url",
+                "https://mempool.space/testnet/api",
+            ])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("has a testnet4 database"));
+    }
+
+    #[test]
+    fn test_encrypted_keystore() {
+        use bdk_wallet::bitcoin::base64::{Engine, prelude::BASE64_STANDARD};
+        use bdk_wallet::bitcoin::transaction::Version;
+        use bdk_wallet::bitcoin::{Psbt, Transaction, absolute::LockTime};
+
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init
+            .arg("--encrypt")
+            .env("BDK_CLI_KEY_PASSWORD", "correct horse")
+            .assert()
+            .success();
+
+        let datadir = cli.datadir.clone().unwrap();
+        let config_path = datadir.join("config.toml");
+        let keystore_path = datadir.join(WALLET_NAME).join("keystore.json");
+        let config = std::fs::read_to_string(&config_path).unwrap();
+        assert!(!config.contains("tprv"));
+        assert!(keystore_path.exists());
+
+        // Only signing needs the password.
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "public_descriptor"])
+            .assert()
+            .success();
+
+        let psbt = Psbt::from_unsigned_tx(Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        })
+        .unwrap();
+        let psbt = BASE64_STANDARD.encode(psbt.serialize());
+        let sign = |password: &str| {
+            cli.wallet_cmd(&["--wallet", WALLET_NAME, "sign", &psbt])
+                .env("BDK_CLI_KEY_PASSWORD", password)
+                .assert()
+        };
+        sign("battery staple")
+            .failure()
+            .stderr(predicate::str::contains("Wrong password"));
+        sign("correct horse").success();
+
+        cli.key_cmd(&["decrypt", "--wallet", WALLET_NAME])
+            .env("BDK_CLI_KEY_PASSWORD", "correct horse")
+            .assert()
+            .success();
+        assert!(
+            std::fs::read_to_string(&config_path)
+                .unwrap()
+                .contains("tprv")
+        );
+        assert!(!keystore_path.exists());
+
+        cli.key_cmd(&["encrypt", "--wallet", WALLET_NAME])
+            .env("BDK_CLI_KEY_PASSWORD", "correct horse")
+            .assert()
+            .success();
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), config);
+        cli.key_cmd(&["encrypt", "--wallet", WALLET_NAME])
+            .env("BDK_CLI_KEY_PASSWORD", "correct horse")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("hold no private key"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hwi_list_and_display_address() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "unused_address"])
+            .output()
+            .unwrap();
+        let address: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(address["index"], 0);
+        let address = address["address"].as_str().unwrap();
+
+        // A stand-in for HWI that shows the first receive address whatever it is asked.
+        let temp_dir = TempDir::new().unwrap();
+        let hwi = temp_dir.path().join("hwi");
+        std::fs::write(
+            &hwi,
+            format!(
+                r#"#!/bin/sh
+case "$*" in
+  *enumerate*) echo '[{{"type":"coldcard","model":"coldcard","fingerprint":"01020304"}}]' ;;
+  *"--fingerprint 01020304 displayaddress --desc tr("*) echo '{{"address":"{address}"}}' ;;
+  *) echo '{{"error":"unexpected call","code":-1}}' ;;
+esac
+"#
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&hwi, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let hwi_path = hwi.to_str().unwrap();
+
+        let output = cli
+            .key_cmd(&["hwi", "list", "--hwi_path", hwi_path])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let devices: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(devices["count"], 1);
+        assert_eq!(devices["items"][0]["fingerprint"], "01020304");
+
+        let display = |index: &str| {
+            cli.wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "hwi",
+                "display-address",
+                "--index",
+                index,
+                "--hwi_path",
+                hwi_path,
+            ])
+            .output()
+            .unwrap()
+        };
+        let output = display("0");
+        assert!(output.status.success());
+        let shown: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(shown["address"], address);
+
+        let output = display("1");
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("do not use this address"));
+    }
+
+    #[cfg(feature = "bip322")]
+    #[test]
+    fn test_sign_message_and_verify_message() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+
+        let message = "bdk-cli integration test";
+
+        // Reveal exactly one address and reuse it for both sign and verify.
+        let addr_output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .output()
+            .expect("Failed to generate address");
+        assert!(
+            addr_output.status.success(),
+            "new_address failed: {}",
+            String::from_utf8_lossy(&addr_output.stderr)
+        );
+        let addr_json: Value = serde_json::from_slice(&addr_output.stdout).unwrap();
+        let address = addr_json["address"]
+            .as_str()
+            .expect("missing address")
+            .to_string();
+        println!("DEBUG signing address: {address}");
+
+        // Sign (default signature_type = "simple").
+        let sign_output = cli
+            .wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "sign_message",
+                "--message",
+                message,
+                "--address",
+                &address,
+            ])
+            .output()
+            .expect("Failed to sign message");
+        assert!(
+            sign_output.status.success(),
+            "sign_message failed: {}",
+            String::from_utf8_lossy(&sign_output.stderr)
+        );
+        let sign_json: Value = serde_json::from_slice(&sign_output.stdout).unwrap();
+        let proof = sign_json["proof"].as_str().expect("missing proof");
+        println!("DEBUG proof: {proof}");
+        println!("DEBUG verifying same address: {address}");
+
+        // Verify with the identical address + message.
+        cli.wallet_cmd(&[
+            "--wallet",
+            WALLET_NAME,
+            "verify_message",
+            "--address",
+            &address,
+            "--message",
+            message,
+            "--proof",
+            proof,
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"valid\": true"));
+    }
+
+    #[cfg(feature = "bip322")]
+    #[test]
+    fn test_verify_message_rejects_tampered_message() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+
+        let addr_output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .output()
+            .expect("Failed to generate address");
+        let addr_json: Value = serde_json::from_slice(&addr_output.stdout).unwrap();
+        let address = addr_json["address"].as_str().unwrap();
+
+        let sign_output = cli
+            .wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "sign_message",
+                "--message",
+                "original message",
+                "--address",
+                address,
+            ])
+            .output()
+            .expect("Failed to sign message");
+        let sign_json: Value = serde_json::from_slice(&sign_output.stdout).unwrap();
+        let proof = sign_json["proof"].as_str().unwrap();
+
+        // A tampered message should fail
+        cli.wallet_cmd(&[
+            "--wallet",
+            WALLET_NAME,
+            "verify_message",
+            "--proof",
+            proof,
+            "--message",
+            "tampered message",
+            "--address",
+            address,
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"valid\": false"));
+    }
+
+    #[test]
+    fn test_create_tx_send_all_rejects_multiple_recipients() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+
+        // Same address twice => two recipients => must be rejected under --send_all.
+        let recipient = "tb1p4tp4l6glyr2gs94neqcpr5gha7344nfyznfkc8szkreflscsdkgqsdent4:0";
+        cli.wallet_cmd(&[
+            "--wallet",
+            WALLET_NAME,
+            "create_tx",
+            "--send_all",
+            "--to",
+            recipient,
+            "--to",
+            recipient,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Wallet can only be drained to a single output",
+        ));
+    }
+
+    #[test]
+    fn test_sync_scans_a_wallet_that_was_never_synced() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+
+        // No node is running, so both fail after picking how to sync.
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "sync"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "The wallet was never synced, running a full scan instead",
+            ));
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "sync", "--revealed-only"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("never synced").not());
+    }
+
+    #[test]
+    fn test_offline_mode_refuses_network_commands() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        // The config arguments, after the `--network` and `--datadir` of the base command.
+        let config_args: Vec<_> = cmd_init.get_args().skip(4).map(|a| a.to_owned()).collect();
+        cmd_init.assert().success();
+        let refused = "This command needs network access";
+
+        let mut sync = cli.build_base_cmd();
+        sync.args(["--offline", "wallet", "--wallet", WALLET_NAME, "sync"]);
+        sync.assert()
+            .failure()
+            .stderr(predicate::str::contains(refused));
+
+        let mut config = cli.build_base_cmd();
+        config.arg("--offline").args(&config_args).arg("--force");
+        config.assert().success();
+        let config_toml =
+            std::fs::read_to_string(cli.datadir.as_ref().unwrap().join("config.toml")).unwrap();
+        assert!(config_toml.contains("offline = true"), "{config_toml}");
+
+        // Reconfiguring the wallet without the flag keeps offline mode.
+        let mut reconfigure = cli.build_base_cmd();
+        reconfigure.args(&config_args).arg("--force");
+        reconfigure.assert().success();
+
+        let recipient = "tb1p4tp4l6glyr2gs94neqcpr5gha7344nfyznfkc8szkreflscsdkgqsdent4:1000";
+        for args in [
+            vec!["sync"],
+            vec!["create_tx", "--to", recipient, "--target-blocks", "1"],
+        ] {
+            cli.wallet_cmd(&[&["--wallet", WALLET_NAME][..], &args].concat())
+                .assert()
+                .failure()
+                .stderr(predicate::str::contains(refused));
+        }
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "balance"])
+            .assert()
+            .success();
+    }
+
+    #[test]
+    fn test_node_commands_need_a_regtest_node() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let node_dir = TempDir::new().unwrap();
+        let node = format!("--node={}", node_dir.path().display());
+
+        let mut mine = cli.build_base_cmd();
+        mine.args(["node", "--wallet", WALLET_NAME, &node, "mine", "1"]);
+        mine.assert()
+            .failure()
+            .stderr(predicate::str::contains("is Bitcoin Core running?"));
+
+        let mut offline = cli.build_base_cmd();
+        offline.args([
+            "--offline",
+            "node",
+            "--wallet",
+            WALLET_NAME,
+            "fund",
+            "bcrt1qsdtedxkv2mdgtstsv9fkkt2qdf4rr2mxdh6y9z",
+            "1000sat",
+        ]);
+        offline.assert().failure().stderr(predicate::str::contains(
+            "This command needs network access",
+        ));
+
+        let mut zero = cli.build_base_cmd();
+        zero.args(["node", "--wallet", WALLET_NAME, "mine", "0"]);
+        zero.assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid value '0' for '<BLOCKS>'"));
+    }
+
+    #[test]
+    fn test_read_only_mode_refuses_mutating_commands() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let read_only = |args: &[&str]| {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args(["--read-only", "wallet", "--wallet", WALLET_NAME])
+                .args(args);
+            cmd
+        };
+
+        // The database is only created by a command with write access.
+        read_only(&["balance"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("--read-only cannot create it"));
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .assert()
+            .success();
+        let db = cli
+            .datadir
+            .as_ref()
+            .unwrap()
+            .join(WALLET_NAME)
+            .join("wallet.sqlite");
+        let before = std::fs::read(&db).unwrap();
+
+        let address = "tb1p4tp4l6glyr2gs94neqcpr5gha7344nfyznfkc8szkreflscsdkgqsdent4";
+        for args in [
+            vec!["new_address"],
+            vec!["label", "--ref", address, "--label", "audit"],
+            vec!["snapshot", "create", "audit"],
+        ] {
+            read_only(&args)
+                .assert()
+                .failure()
+                .stderr(predicate::str::contains("--read-only forbids"));
+        }
+        read_only(&["balance"]).assert().success();
+        read_only(&["labels"]).assert().success();
+
+        assert_eq!(std::fs::read(&db).unwrap(), before);
+        assert!(
+            !cli.datadir
+                .as_ref()
+                .unwrap()
+                .join(WALLET_NAME)
+                .join("labels.jsonl")
+                .exists()
+        );
+    }
+
+    #[test]
+    fn test_paths_of_datadir_and_wallet() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let datadir = cli.datadir.clone().unwrap();
+
+        let output = cli
+            .cmd("paths", &["--wallet", WALLET_NAME])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let paths: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(paths["source"], "--datadir");
+        assert_eq!(
+            paths["config"],
+            datadir.join("config.toml").display().to_string()
+        );
+        assert_eq!(
+            paths["wallet"]["database"],
+            datadir
+                .join(WALLET_NAME)
+                .join("wallet.sqlite")
+                .display()
+                .to_string()
+        );
+        assert_eq!(
+            paths["wallet"]["labels"],
+            datadir
+                .join(WALLET_NAME)
+                .join("labels.jsonl")
+                .display()
+                .to_string()
+        );
+
+        // Without `--datadir`, `BDK_CLI_DATADIR` picks the data directory.
+        let mut paths = Command::cargo_bin("bdk-cli").unwrap();
+        paths
+            .env_remove("DATADIR")
+            .env("BDK_CLI_DATADIR", &datadir)
+            .args(["paths", "--wallet", WALLET_NAME]);
+        let output = paths.output().unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let paths: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(paths["source"], "BDK_CLI_DATADIR");
+        assert_eq!(paths["datadir"], datadir.display().to_string());
+    }
+
+    #[test]
+    fn test_run_script_of_commands() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+
+        let output = cli
+            .cmd(
+                "run",
+                &[
+                    "--wallet",
+                    WALLET_NAME,
+                    "--commands",
+                    "wallet new_address; wallet new_address; wallet balance",
+                ],
+            )
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let results: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(results.as_array().unwrap().len(), 3);
+        assert_eq!(results[0]["output"][0]["index"], 0);
+        assert_eq!(results[1]["output"][0]["index"], 1);
+        assert_eq!(results[2]["command"], "wallet balance");
+
+        // The revealed addresses are persisted once the script ends.
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .output()
+            .unwrap();
+        let address: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(address["index"], 2);
+
+        // An invalid command stops the script before anything runs.
+        let script = cli.datadir.as_ref().unwrap().join("script.bdk");
+        std::fs::write(
+            &script,
+            "# Reveal one address\nwallet new_address\nwallet nothing\n",
+        )
+        .unwrap();
+        cli.cmd("run", &["--wallet", WALLET_NAME, script.to_str().unwrap()])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid command 2"));
+
+        // A failed command stops the script, unless `--keep-going`.
+        let commands = "wallet sign invalid; wallet balance";
+        let output = cli
+            .cmd("run", &["--wallet", WALLET_NAME, "--commands", commands])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let results: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(results.as_array().unwrap().len(), 1);
+        assert!(results[0]["error"].is_string());
+        let output = cli
+            .cmd(
+                "run",
+                &[
+                    "--wallet",
+                    WALLET_NAME,
+                    "--commands",
+                    commands,
+                    "--keep-going",
+                ],
+            )
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let results: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(results.as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_output_formats() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let output_cmd = |format: &str, command: &str| {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args([
+                "--output",
+                format,
+                "wallet",
+                "--wallet",
+                WALLET_NAME,
+                command,
+            ]);
+            cmd.output().unwrap()
+        };
+
+        let output = output_cmd("csv", "new_address");
+        assert!(output.status.success(), "{output:?}");
+        let csv = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "address,index");
+        assert!(lines[1].ends_with(",0"), "{csv}");
+
+        let output = output_cmd("table", "balance");
+        assert!(output.status.success(), "{output:?}");
+        let table = String::from_utf8(output.stdout).unwrap();
+        assert!(
+            table.contains("field") && table.contains("confirmed"),
+            "{table}"
+        );
+
+        let output = output_cmd("json", "labels");
+        let labels: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(labels["count"], 0);
+    }
+
+    #[test]
+    fn test_table_columns() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let columns_cmd = |format: &str, command: &str, columns: &str| {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args([
+                "--output",
+                format,
+                "wallet",
+                "--wallet",
+                WALLET_NAME,
+                command,
+                "--columns",
+                columns,
+            ]);
+            cmd.output().unwrap()
+        };
+
+        let output = columns_cmd("table", "balance", "total,confirmed");
+        assert!(output.status.success(), "{output:?}");
+        let table = String::from_utf8(output.stdout).unwrap();
+        assert!(
+            table.contains("total") && !table.contains("immature"),
+            "{table}"
+        );
+
+        let output = columns_cmd("csv", "balance", "confirmed,total");
+        assert!(output.status.success(), "{output:?}");
+        let csv = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(csv.lines().collect::<Vec<_>>(), ["confirmed,total", "0,0"]);
+
+        let output = columns_cmd("table", "balance", "total,fee");
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("Unknown column 'fee'"), "{stderr}");
+
+        // Piped outputs stay JSON without `--output`.
+        let mut cmd = cli.build_base_cmd();
+        cmd.args(["wallet", "--wallet", WALLET_NAME, "transactions"]);
+        let output = cmd.output().unwrap();
+        let transactions: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(transactions["count"], 0);
+    }
+
+    #[test]
+    fn test_json_logs_in_log_file() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let log_file = cli.datadir.as_ref().unwrap().join("bdk-cli.log");
+
+        let mut cmd = cli.build_base_cmd();
+        cmd.env("RUST_LOG", "debug").args([
+            "--log-format",
+            "json",
+            "--log-file",
+            log_file.to_str().unwrap(),
+            "wallet",
+            "--wallet",
+            WALLET_NAME,
+            "new_address",
+        ]);
+        let output = cmd.output().unwrap();
+        assert!(output.status.success(), "{output:?}");
+        // The command output stays apart from the logs.
+        let address: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(address["address"].is_string());
+
+        let logs = std::fs::read_to_string(&log_file).unwrap();
+        let lines: Vec<Value> = logs
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert!(
+            lines.iter().any(|line| line["level"] == "DEBUG"
+                && line["target"] == "bdk_cli"
+                && line["timestamp"].is_string()
+                && line["message"] == "network: Regtest"),
+            "{logs}"
+        );
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let failing_cmd = |args: &[&str]| {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args(["--output", "json"]).args(args);
+            let output = cmd.output().unwrap();
+            let error: Value = serde_json::from_slice(&output.stdout).unwrap();
+            (output.status.code(), error)
+        };
+
+        let (status, error) = failing_cmd(&["wallet", "--wallet", "missing", "balance"]);
+        assert_eq!(status, Some(4));
+        assert_eq!(error["error"]["code"], "WALLET_NOT_FOUND");
+        assert_eq!(error["error"]["category"], "wallet");
+        assert_eq!(
+            error["error"]["message"],
+            "Wallet 'missing' not found in config"
+        );
+
+        let (status, error) = failing_cmd(&[
+            "--read-only",
+            "wallet",
+            "--wallet",
+            WALLET_NAME,
+            "new_address",
+        ]);
+        assert_eq!(status, Some(7));
+        assert_eq!(error["error"]["code"], "READ_ONLY_MODE");
+
+        let (status, error) = failing_cmd(&[
+            "wallet",
+            "--wallet",
+            WALLET_NAME,
+            "create_tx",
+            "--to",
+            "tb1p4tp4l6glyr2gs94neqcpr5gha7344nfyznfkc8szkreflscsdkgqsdent4:1000",
+            "--from-label",
+            "savings",
+        ]);
+        assert_eq!(status, Some(5));
+        assert_eq!(error["error"]["code"], "NO_SPENDABLE_UTXOS");
+
+        let (status, error) = failing_cmd(&[
+            "wallet",
+            "--wallet",
+            WALLET_NAME,
+            "label",
+            "--ref",
+            "nowhere",
+            "--label",
+            "savings",
+        ]);
+        assert_eq!(status, Some(3));
+        assert_eq!(error["error"]["code"], "INVALID_LABEL");
+
+        let (status, error) = failing_cmd(&[
+            "wallet",
+            "--wallet",
+            WALLET_NAME,
+            "create_tx",
+            "--to",
+            "@nobody:1000",
+        ]);
+        assert_eq!(status, Some(3));
+        assert_eq!(error["error"]["code"], "INVALID_ARGUMENT");
+
+        // Without `--output json` the failure is only written to stderr.
+        let mut cmd = cli.build_base_cmd();
+        cmd.args(["wallet", "--wallet", "missing", "balance"]);
+        cmd.assert()
+            .code(4)
+            .stdout(predicate::str::is_empty())
+            .stderr(predicate::str::contains("Error: Wallet 'missing'"));
+    }
+
+    #[test]
+    fn test_amount_units() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let recipient = |amount: &str| {
+            format!("tb1p4tp4l6glyr2gs94neqcpr5gha7344nfyznfkc8szkreflscsdkgqsdent4:{amount}")
+        };
+        let create_tx = |unit: &str, amount: &str| {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args(["--unit", unit, "wallet", "--wallet", WALLET_NAME])
+                .args(["create_tx", "--to", &recipient(amount)]);
+            cmd
+        };
+
+        // Amounts parse in every unit, the empty wallet then lacking the funds.
+        for (unit, amount) in [
+            ("sat", "0.015btc"),
+            ("sat", "150000sat"),
+            ("btc", "0.015"),
+            ("btc", "150000sats"),
+        ] {
+            create_tx(unit, amount)
+                .assert()
+                .failure()
+                .stderr(predicate::str::contains("Insufficient"));
+        }
+        create_tx("sat", "0.015")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid amount"));
+        create_tx("btc", "0.000000001")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid amount"));
+
+        let mut cmd = cli.build_base_cmd();
+        cmd.args([
+            "--unit",
+            "btc",
+            "wallet",
+            "--wallet",
+            WALLET_NAME,
+            "balance",
+        ]);
+        let output = cmd.output().unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let balance: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(balance["total"], "0.00000000");
+
+        // The unit given to `wallet config`, here by `AMOUNT_UNIT`, is saved for the later
+        // commands.
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.env("AMOUNT_UNIT", "btc").assert().success();
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "balance"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let balance: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(balance["total"], "0.00000000");
+        let mut cmd = cli.build_base_cmd();
+        cmd.args([
+            "--unit",
+            "sat",
+            "wallet",
+            "--wallet",
+            WALLET_NAME,
+            "balance",
+        ]);
+        let output = cmd.output().unwrap();
+        let balance: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(balance["total"], 0);
+    }
+
+    #[test]
+    fn test_fiat_values_from_cached_prices() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let balance = |currency: &str| {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args(["--offline", "--fiat", currency]).args([
+                "wallet",
+                "--wallet",
+                WALLET_NAME,
+                "balance",
+            ]);
+            let output = cmd.output().unwrap();
+            assert!(output.status.success(), "{output:?}");
+            serde_json::from_slice::<Value>(&output.stdout).unwrap()
+        };
+
+        // Without a cached quote, offline, the fiat value is skipped.
+        assert!(balance("USD").get("fiat").is_none());
+
+        let prices = json!({
+            "url": "https://mempool.space/api/v1/prices",
+            "fetched_at": 0,
+            "time": 1703252411,
+            "prices": {"USD": 43753.0, "EUR": 40545.0},
+        });
+        let cache = cli.datadir.clone().unwrap().join("prices.json");
+        std::fs::write(cache, prices.to_string()).unwrap();
+        let fiat = &balance("usd")["fiat"];
+        assert_eq!(fiat["currency"], "USD");
+        assert_eq!(fiat["price"], 43753.0);
+        assert_eq!(fiat["value"], 0.0);
+        assert_eq!(fiat["quoted_at"], 1703252411);
+        assert!(balance("JPY").get("fiat").is_none());
+    }
+
+    #[test]
+    fn test_fiat_values_from_the_price_provider() {
+        use crate::common::http::{Response, serve};
+        use std::sync::{Arc, Mutex};
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let requested = requests.clone();
+        let url = serve(move |request| {
+            requested.lock().unwrap().push(request.path.clone());
+            Response::ok(r#"{"time":1703252411,"USD":43753,"EUR":40545.5}"#)
+        });
+        let price_url = format!("{url}/api/v1/prices");
+
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let balance = || {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args(["--fiat", "EUR", "--price-url", &price_url])
+                .args(["wallet", "--wallet", WALLET_NAME, "balance"]);
+            let output = cmd.output().unwrap();
+            assert!(output.status.success(), "{output:?}");
+            serde_json::from_slice::<Value>(&output.stdout).unwrap()
+        };
+
+        let fiat = &balance()["fiat"];
+        assert_eq!(fiat["currency"], "EUR");
+        assert_eq!(fiat["price"], 40545.5);
+        assert_eq!(fiat["quoted_at"], 1703252411);
+        let cache = cli.datadir.clone().unwrap().join("prices.json");
+        let cached: Value = serde_json::from_slice(&std::fs::read(cache).unwrap()).unwrap();
+        assert_eq!(cached["url"], price_url);
+
+        // The fresh quote is taken from the cache.
+        assert_eq!(balance()["fiat"]["price"], 40545.5);
+        assert_eq!(*requests.lock().unwrap(), ["/api/v1/prices"]);
+    }
+
+    #[test]
+    fn test_no_colors_when_piped() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        for args in [
+            &["--output", "table"][..],
+            &["--output", "table", "--no-color"],
+        ] {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args(args)
+                .args(["wallet", "--wallet", WALLET_NAME, "transactions"]);
+            let output = cmd.output().unwrap();
+            assert!(output.status.success(), "{output:?}");
+            let table = String::from_utf8(output.stdout).unwrap();
+            assert!(!table.contains('\x1b'), "{table}");
+            for column in ["txid", "net", "confirmed"] {
+                assert!(table.contains(column), "{table}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_contacts_as_recipients() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let alice = "bcrt1qmpwzkuwsqc9snjvgdt4czhjsnywa5yjdqpxskv";
+        let contacts = |args: &[&str]| {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args(["wallet", "--wallet", WALLET_NAME, "contacts"])
+                .args(args);
+            let output = cmd.output().unwrap();
+            assert!(output.status.success(), "{output:?}");
+            serde_json::from_slice::<Value>(&output.stdout).unwrap()
+        };
+
+        let added = contacts(&["add", "--name", "@alice", "--address", alice]);
+        assert_eq!(added["items"], json!([{"name": "alice", "address": alice}]));
+        assert_eq!(contacts(&["list"])["count"], 1);
+
+        let create_tx = |recipient: &str| {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args([
+                "wallet",
+                "--wallet",
+                WALLET_NAME,
+                "create_tx",
+                "--to",
+                recipient,
+            ]);
+            cmd
+        };
+        // The resolved address must be confirmed, and the empty wallet then lacks the funds.
+        create_tx("@alice:10000").assert().failure().code(7);
+        create_tx("@alice:10000")
+            .env("ASSUME_YES", "true")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Insufficient"));
+        create_tx("@bob:10000")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Unknown contact '@bob'"));
+
+        // Contacts are the BIP-329 labels of their addresses.
+        let mut cmd = cli.build_base_cmd();
+        cmd.args(["wallet", "--wallet", WALLET_NAME, "labels"]);
+        let output = cmd.output().unwrap();
+        let labels: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(
+            labels["items"],
+            json!([{"type": "addr", "ref": alice, "label": "@alice"}])
+        );
+
+        // Other address labels are not contacts, and a name given to two addresses is refused.
+        let label = |reference: &str, label: &str| {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args(["wallet", "--wallet", WALLET_NAME, "label"])
+                .args(["--ref", reference, "--label", label]);
+            cmd.assert().success();
+        };
+        let other = "bcrt1qsdtedxkv2mdgtstsv9fkkt2qdf4rr2mxdh6y9z";
+        label(other, "bob");
+        assert_eq!(contacts(&["list"])["count"], 1);
+        create_tx("@bob:10000")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Unknown contact '@bob'"));
+        label(other, "@alice");
+        assert_eq!(contacts(&["list"])["count"], 2);
+        create_tx("@alice:10000")
+            .env("ASSUME_YES", "true")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Contact '@alice' is ambiguous"));
+
+        let added = contacts(&["add", "--name", "alice", "--address", alice]);
+        assert_eq!(added["count"], 1);
+        assert_eq!(contacts(&["remove", "--name", "alice"])["count"], 0);
+    }
+
+    #[test]
+    fn test_init_wizard() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let init = |args: &[&str]| {
+            let mut cmd = cli.build_base_cmd();
+            cmd.arg("init")
+                .args(["-d", "sqlite", "-c", "rpc", "-u", "http://localhost:18443"])
+                .args(args)
+                .write_stdin("");
+            cmd
+        };
+
+        // Piped answers run out before the wallet name.
+        init(&["--generate"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("init needs an answer"));
+
+        let output = init(&[
+            "-w",
+            "wizard",
+            "--generate",
+            "--script-type",
+            "tr",
+            "--no-scan",
+        ])
+        .output()
+        .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(result["wallet"], "wizard");
+        assert_eq!(result["network"], "regtest");
+        assert_eq!(result["scanned"], false);
+        assert!(
+            result["public_descriptors"]["external"]
+                .as_str()
+                .unwrap()
+                .starts_with("tr(")
+        );
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Write down these words"));
+
+        let mut cmd = cli.build_base_cmd();
+        cmd.arg("wallets");
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("wizard"));
+
+        init(&["-w", "wizard", "--generate", "--no-scan"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("already exists"));
+
+        // The wallet name is the name of its directory in the data directory.
+        init(&["-w", "../wizard", "--generate", "--no-scan"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid wallet name '../wizard'"));
+    }
+
+    #[test]
+    fn test_stats_after_command() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+
+        let mut cmd = cli.build_base_cmd();
+        cmd.args(["--stats", "wallet", "--wallet", WALLET_NAME, "balance"]);
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("Statistics:"))
+            .stderr(predicate::str::contains("wallet load"))
+            .stderr(predicate::str::contains("requests"));
+
+        let mut cmd = cli.build_base_cmd();
+        cmd.args([
+            "--stats",
+            "--output",
+            "json",
+            "wallet",
+            "--wallet",
+            WALLET_NAME,
+            "balance",
+        ]);
+        let output = cmd.output().unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stats: Value = serde_json::from_str(stderr.lines().last().unwrap()).unwrap();
+        assert!(stats["wallet_load_ms"].as_f64().unwrap() > 0.0);
+        assert_eq!(stats["requests"], 0);
+        assert_eq!(stats["scripts_scanned"], 0);
+
+        // Without --stats nothing is added to stderr.
+        let mut cmd = cli.build_base_cmd();
+        cmd.args(["wallet", "--wallet", WALLET_NAME, "balance"]);
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("Statistics:").not());
+    }
+
+    #[test]
+    fn test_run_variables() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+
+        let output = cli
+            .cmd(
+                "run",
+                &[
+                    "--wallet",
+                    WALLET_NAME,
+                    "--commands",
+                    "let addr = wallet new_address; wallet label --ref $addr.address --label first",
+                ],
+            )
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let results: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let address = &results[0]["output"][0]["address"];
+        assert_eq!(results[1]["output"][0]["items"][0]["ref"], *address);
+        assert_eq!(results[1]["output"][0]["items"][0]["label"], "first");
+
+        cli.cmd(
+            "run",
+            &[
+                "--wallet",
+                WALLET_NAME,
+                "--commands",
+                "wallet label --ref $missing.address --label first",
+            ],
+        )
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown variable `$missing`"));
+
+        // The lines referencing variables are checked before anything runs, but for their values.
+        let run = |commands: &str| {
+            cli.cmd("run", &["--wallet", WALLET_NAME, "--commands", commands])
+                .output()
+                .unwrap()
+        };
+        let output = run(
+            "wallet new_address; wallet label --ref $later.address --label first; \
+             let later = wallet new_address",
+        );
+        assert!(!output.status.success());
+        assert!(output.stdout.is_empty(), "{output:?}");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("Unknown variable `$later`"),
+            "{output:?}"
+        );
+        // The `;` of quoted arguments does not end the command.
+        let output = run("let addr = wallet new_address; \
+             wallet label --ref $addr.address --label 'first; second'");
+        assert!(output.status.success(), "{output:?}");
+        let results: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(results.as_array().unwrap().len(), 2, "{results}");
+        assert_eq!(
+            results[1]["output"][0]["items"][0]["label"],
+            "first; second"
+        );
+        let output = run("let addr = wallet new_address; wallet new_address; \
+             wallet label --ref $addr.address --lable first");
+        assert!(!output.status.success());
+        assert!(output.stdout.is_empty(), "{output:?}");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("Invalid command 3"),
+            "{output:?}"
+        );
+    }
+
+    #[test]
+    fn test_run_aliases() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+
+        let output = cli
+            .cmd(
+                "run",
+                &[
+                    "--wallet",
+                    WALLET_NAME,
+                    "--commands",
+                    "alias addr = wallet new_address; addr; alias",
+                ],
+            )
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let results: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(results[1]["output"][0]["address"].is_string());
+        assert_eq!(results[2]["output"][0]["items"][0]["name"], "addr");
+        assert_eq!(
+            results[2]["output"][0]["items"][0]["command"],
+            "wallet new_address"
+        );
+
+        cli.cmd("run", &["--wallet", WALLET_NAME, "--commands", "use other"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("only available in the REPL"));
+    }
+
+    #[test]
+    fn test_links_to_block_explorer() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        let config_args: Vec<_> = cmd_init.get_args().skip(4).map(|a| a.to_owned()).collect();
+        cmd_init.assert().success();
+
+        // Regtest has no public explorer to default to.
+        let mut new_address = cli.build_base_cmd();
+        new_address.args(["--links", "wallet", "--wallet", WALLET_NAME, "new_address"]);
+        new_address
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("No block explorer for regtest"));
+
+        // A template must say where the txid or address goes.
+        let mut new_address = cli.build_base_cmd();
+        new_address
+            .args(["--links", "--explorer-url", "http://127.0.0.1:8080"])
+            .args(["wallet", "--wallet", WALLET_NAME, "new_address"]);
+        new_address
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("has no {id} placeholder"));
+
+        let mut new_address = cli.build_base_cmd();
+        new_address
+            .args([
+                "--links",
+                "--explorer-url",
+                "http://127.0.0.1:8080/{kind}/{id}",
+            ])
+            .args(["wallet", "--wallet", WALLET_NAME, "new_address"]);
+        let output = new_address.output().unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let address = result["address"].as_str().unwrap();
+        assert_eq!(
+            result["address_link"],
+            format!("http://127.0.0.1:8080/address/{address}")
+        );
+
+        // The explorer given to `wallet config` is saved for the network of the wallet.
+        let mut config = cli.build_base_cmd();
+        config
+            .args(["--explorer-url", "http://explorer.local/{kind}/{id}"])
+            .args(&config_args)
+            .arg("--force");
+        config.assert().success();
+        let config_toml =
+            std::fs::read_to_string(cli.datadir.as_ref().unwrap().join("config.toml")).unwrap();
+        assert!(
+            config_toml.contains("regtest = \"http://explorer.local/{kind}/{id}\""),
+            "{config_toml}"
+        );
+        let mut new_address = cli.build_base_cmd();
+        new_address.args(["--links", "wallet", "--wallet", WALLET_NAME, "new_address"]);
+        new_address
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("http://explorer.local/address/"));
+    }
+
+    #[test]
+    fn test_network_mismatches_are_rejected() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+
+        // A test network descriptor cannot make a mainnet wallet.
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_value: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let mainnet = BdkCli::new("bitcoin", cli.datadir.clone());
+        mainnet
+            .build_base_cmd()
+            .args(["wallet", "--wallet", "mainnet", "config", "--ext-descriptor"])
+            .arg(desc_value["public_descriptors"]["external"].as_str().unwrap())
+            .args(["--client-type", "rpc", "--database-type", "sqlite"])
+            .args(["--url", "http://localhost:8332"])
+            .assert()
+            .code(3)
+            .stderr(predicate::str::contains(
+                "Network mismatch: the descriptor has test network keys (tpub, tprv), but the network is bitcoin",
+            ));
+
+        // `--network` cannot point a wallet at another network than its own.
+        BdkCli::new("signet", cli.datadir.clone())
+            .wallet_cmd(&["--wallet", WALLET_NAME, "balance"])
+            .assert()
+            .code(3)
+            .stderr(predicate::str::contains(format!(
+                "Network mismatch: wallet '{WALLET_NAME}' is configured for regtest, but --network is signet"
+            )));
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "balance"])
+            .assert()
+            .success();
+    }
+
+    #[test]
+    #[cfg(feature = "redb")]
+    fn test_redb_wallets_share_one_database() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let mut addresses = Vec::new();
+        for wallet in ["alice", "bob"] {
+            let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+            let desc_value: Value = serde_json::from_slice(&desc.stdout).unwrap();
+            let public = &desc_value["public_descriptors"];
+            cli.build_base_cmd()
+                .args(["wallet", "--wallet", wallet, "config"])
+                .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+                .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+                .args(["--client-type", "rpc", "--database", "redb"])
+                .args(["--url", "http://localhost:18443"])
+                .assert()
+                .success();
+            let output = cli
+                .wallet_cmd(&["--wallet", wallet, "new_address"])
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{output:?}");
+            let address: Value = serde_json::from_slice(&output.stdout).unwrap();
+            addresses.push(address["address"].clone());
+        }
+        assert!(temp_dir.path().join("wallet.redb").exists());
+        assert!(!temp_dir.path().join("alice").join("wallet.sqlite").exists());
+        // The shared database is not a file of the wallet a snapshot could hold.
+        cli.wallet_cmd(&["--wallet", "alice", "snapshot", "create", "first"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "Snapshots only hold sqlite wallets",
+            ));
+
+        // Each wallet keeps its own state in the shared database.
+        for (wallet, first) in ["alice", "bob"].into_iter().zip(&addresses) {
+            let output = cli
+                .wallet_cmd(&["--wallet", wallet, "unused_address"])
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{output:?}");
+            let unused: Value = serde_json::from_slice(&output.stdout).unwrap();
+            assert_eq!(&unused["address"], first);
+        }
+
+        // The regtest wallet in the shared database is not reused by a testnet one.
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_value: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_value["public_descriptors"];
+        BdkCli::new("testnet", cli.datadir.clone())
+            .build_base_cmd()
+            .args(["wallet", "--wallet", "alice", "config", "--force"])
+            .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+            .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+            .args(["--client-type", "rpc", "--database", "redb"])
+            .args(["--url", "http://localhost:18332"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("has a regtest database"));
+    }
+
+    /// Runs against the database of `BDK_CLI_TEST_POSTGRES_URL`, a url with a password, and is
+    /// skipped without it.
+    #[test]
+    #[cfg(feature = "postgres")]
+    fn test_postgres_wallets_share_one_database() {
+        let Ok(url) = std::env::var("BDK_CLI_TEST_POSTGRES_URL") else {
+            eprintln!("BDK_CLI_TEST_POSTGRES_URL is not set, skipping");
+            return;
+        };
+        let password = url
+            .split_once("://")
+            .and_then(|(_, rest)| rest.split_once('@'))
+            .and_then(|(userinfo, _)| userinfo.split_once(':'))
+            .map(|(_, password)| password.to_string())
+            .expect("a url with a password");
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        // The database outlives the test, which names its wallets after the process.
+        let wallets = ["alice", "bob"].map(|name| format!("{name}_{}", std::process::id()));
+        let mut addresses = Vec::new();
+        for wallet in &wallets {
+            let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+            let desc_value: Value = serde_json::from_slice(&desc.stdout).unwrap();
+            let public = &desc_value["public_descriptors"];
+            cli.build_base_cmd()
+                .args(["wallet", "--wallet", wallet, "config"])
+                .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+                .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+                .args(["--client-type", "rpc", "--database", &url])
+                .args(["--url", "http://localhost:18443"])
+                .assert()
+                .success();
+            let output = cli
+                .wallet_cmd(&["--wallet", wallet, "new_address"])
+                .env("BDK_CLI_POSTGRES_PASSWORD", &password)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{output:?}");
+            let address: Value = serde_json::from_slice(&output.stdout).unwrap();
+            addresses.push(address["address"].clone());
+        }
+        let config = std::fs::read_to_string(temp_dir.path().join("config.toml")).unwrap();
+        assert!(!config.contains(&format!(":{password}@")));
+        assert!(
+            !temp_dir
+                .path()
+                .join(&wallets[0])
+                .join("wallet.sqlite")
+                .exists()
+        );
+
+        // Each wallet keeps its own state in the shared database.
+        for (wallet, first) in wallets.iter().zip(&addresses) {
+            let output = cli
+                .wallet_cmd(&["--wallet", wallet, "unused_address"])
+                .env("BDK_CLI_POSTGRES_PASSWORD", &password)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{output:?}");
+            let unused: Value = serde_json::from_slice(&output.stdout).unwrap();
+            assert_eq!(&unused["address"], first);
+        }
+        // The password is not saved with the wallet.
+        cli.wallet_cmd(&["--wallet", &wallets[0], "unused_address"])
+            .env_remove("BDK_CLI_POSTGRES_PASSWORD")
+            .assert()
+            .failure();
+    }
+
+    #[test]
+    fn test_backup_and_restore_bundle() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .assert()
+            .success();
+        let address = "tb1p4tp4l6glyr2gs94neqcpr5gha7344nfyznfkc8szkreflscsdkgqsdent4";
+        cli.wallet_cmd(&[
+            "--wallet",
+            WALLET_NAME,
+            "label",
+            "--ref",
+            address,
+            "--label",
+            "savings",
+        ])
+        .assert()
+        .success();
+        let bundle = cli.datadir.as_ref().unwrap().join("wallet.tar.zst");
+        let bundle_arg = bundle.to_str().unwrap();
+
+        let output = cli
+            .wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "backup",
+                "--out",
+                bundle_arg,
+                "--encrypt",
+            ])
+            .env("BDK_CLI_BACKUP_PASSWORD", "correct horse")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let backup: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(backup["encrypted"], true);
+        // An existing file is never overwritten.
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "backup", "--out", bundle_arg])
+            .assert()
+            .failure();
+
+        let other_dir = TempDir::new().unwrap();
+        let other = BdkCli::new("regtest", Some(other_dir.path().to_path_buf()));
+        other
+            .wallet_cmd(&["--wallet", "restored", "restore", bundle_arg])
+            .env("BDK_CLI_BACKUP_PASSWORD", "battery staple")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Wrong password"));
+        other
+            .wallet_cmd(&["--wallet", "restored", "restore", bundle_arg])
+            .env("BDK_CLI_BACKUP_PASSWORD", "correct horse")
+            .assert()
+            .success();
+
+        // The restored wallet keeps the revealed addresses, without the private keys.
+        let config = std::fs::read_to_string(other_dir.path().join("config.toml")).unwrap();
+        assert!(!config.contains("tprv"));
+        other
+            .wallet_cmd(&["--wallet", "restored", "labels"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("savings"));
+        let unused = |cli: &BdkCli, wallet: &str| {
+            let output = cli
+                .wallet_cmd(&["--wallet", wallet, "unused_address"])
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{output:?}");
+            serde_json::from_slice::<Value>(&output.stdout).unwrap()["address"].clone()
+        };
+        assert_eq!(unused(&cli, WALLET_NAME), unused(&other, "restored"));
+        other
+            .wallet_cmd(&["--wallet", "restored", "restore", bundle_arg])
+            .env("BDK_CLI_BACKUP_PASSWORD", "correct horse")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("already exists"));
+    }
+
+    #[test]
+    fn test_debug_changesets() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        for _ in 0..2 {
+            cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+                .assert()
+                .success();
+        }
+
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "debug", "changesets"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let dump: Value = serde_json::from_slice(&output.stdout).unwrap();
+        // The sqlite store merges the changesets into one.
+        assert_eq!(dump["count"], 1);
+        let dump = &dump["items"][0];
+        assert_eq!(dump["network"], "regtest");
+        assert_eq!(dump["last_revealed"]["external"], 1);
+        assert_eq!(dump["blocks"][0]["height"], 0);
+        assert_eq!(dump["transactions"], Value::Array(vec![]));
+
+        // The genesis block is before the height of `--since`.
+        let output = cli
+            .wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "debug",
+                "changesets",
+                "--since",
+                "1",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let dump: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(dump["items"][0]["blocks"], Value::Array(vec![]));
+    }
+
+    #[test]
+    fn test_db_vacuum() {
+        use bdk_wallet::bitcoin::consensus::serialize;
+        use bdk_wallet::bitcoin::transaction::Version;
+        use bdk_wallet::bitcoin::{Amount, ScriptBuf, Transaction, TxOut, absolute::LockTime};
+        use bdk_wallet::rusqlite::Connection;
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .assert()
+            .success();
+
+        // Two transactions outside of the wallet history, last seen long ago and just now.
+        let db_path = cli
+            .datadir
+            .clone()
+            .unwrap()
+            .join(WALLET_NAME)
+            .join("wallet.sqlite");
+        let db = Connection::open(&db_path).unwrap();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut txids = Vec::new();
+        for (value, last_seen) in [(1_000, 1_000), (2_000, now)] {
+            let tx = Transaction {
+                version: Version::TWO,
+                lock_time: LockTime::ZERO,
+                input: vec![],
+                output: vec![TxOut {
+                    value: Amount::from_sat(value),
+                    script_pubkey: ScriptBuf::new(),
+                }],
+            };
+            db.execute(
+                "INSERT INTO bdk_txs (txid, raw_tx, last_seen) VALUES (?1, ?2, ?3)",
+                (tx.compute_txid().to_string(), serialize(&tx), last_seen),
+            )
+            .unwrap();
+            txids.push(tx.compute_txid().to_string());
+        }
+        drop(db);
+
+        let output = cli
+            .wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "db",
+                "vacuum",
+                "--prune-older-than",
+                "30",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(result["pruned"], json!([txids[0]]));
+        let db = Connection::open(&db_path).unwrap();
+        let left: Vec<String> = db
+            .prepare("SELECT txid FROM bdk_txs")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(left, vec![txids[1].clone()]);
+        drop(db);
+
+        let output = cli
+            .wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "db",
+                "vacuum",
+                "--prune-older-than",
+                "30",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(result["size_after"].as_u64().unwrap() > 0);
+        assert_eq!(result["pruned"], Value::Array(vec![]));
+
+        // The wallet still loads from the compacted database.
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "unused_address"])
+            .assert()
+            .success();
+    }
+
+    #[test]
+    fn test_rollback_restores_last_automatic_snapshot() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "rollback"])
+            .env("ASSUME_YES", "true")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("no automatic snapshot"));
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+            .assert()
+            .success();
+        let label = |name: &str| {
+            let address = "tb1p4tp4l6glyr2gs94neqcpr5gha7344nfyznfkc8szkreflscsdkgqsdent4";
+            cli.wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "label",
+                "--ref",
+                address,
+                "--label",
+                name,
+            ])
+            .assert()
+            .success();
+        };
+        let labels = || {
+            let output = cli
+                .wallet_cmd(&["--wallet", WALLET_NAME, "labels"])
+                .output()
+                .unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        label("old");
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "snapshot", "create", "old"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let created: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(created["files"], json!(["wallet.sqlite", "labels.jsonl"]));
+        label("new");
+        // Restoring a snapshot takes an automatic one of the state it replaces.
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "snapshot", "restore", "old"])
+            .env("ASSUME_YES", "true")
+            .assert()
+            .success();
+        assert!(labels().contains("old"));
+
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "rollback"])
+            .env("ASSUME_YES", "true")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(result["snapshot"].as_str().unwrap().ends_with("-restore"));
+        assert_eq!(result["files"], json!(["wallet.sqlite", "labels.jsonl"]));
+        assert!(labels().contains("new"));
+
+        // Each rollback uses up its snapshot.
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "rollback"])
+            .env("ASSUME_YES", "true")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("no automatic snapshot"));
+    }
+
+    #[test]
+    fn test_named_snapshots() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let label = |name: &str| {
+            let address = "tb1p4tp4l6glyr2gs94neqcpr5gha7344nfyznfkc8szkreflscsdkgqsdent4";
+            cli.wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "label",
+                "--ref",
+                address,
+                "--label",
+                name,
+            ])
+            .assert()
+            .success();
+        };
+        label("before");
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "snapshot", "create", "auto-x"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid snapshot name"));
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "snapshot", "create", "first"])
+            .assert()
+            .success();
+        label("after");
+
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "snapshot", "list"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let list: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(list["count"], 1);
+        assert_eq!(list["items"][0]["name"], "first");
+        assert_eq!(list["items"][0]["automatic"], false);
+
+        // Without a terminal to confirm on, overwriting the wallet needs --yes.
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "snapshot", "restore", "first"])
+            .assert()
+            .code(7)
+            .stderr(predicate::str::contains("pass --yes"));
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "snapshot", "restore", "first"])
+            .env("ASSUME_YES", "true")
+            .assert()
+            .success();
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "labels"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("before"));
+
+        // The state before the restore is one rollback away.
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "rollback"])
+            .env("ASSUME_YES", "true")
+            .assert()
+            .success();
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "labels"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("after"));
+    }
+
+    /// The script of the first address of the configured wallet, a transaction paying it and the
+    /// checksum of the public external descriptor, as a bdk 0.x database of the wallet holds them.
+    fn legacy_wallet_state(
+        cli: &BdkCli,
+    ) -> (
+        bdk_wallet::bitcoin::ScriptBuf,
+        bdk_wallet::bitcoin::Transaction,
+        String,
+    ) {
+        use bdk_wallet::bitcoin::transaction::Version;
+        use bdk_wallet::bitcoin::{
+            Address, Amount, OutPoint, Transaction, TxIn, TxOut, absolute::LockTime,
+        };
+        use std::str::FromStr;
+
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "unused_address"])
+            .output()
+            .unwrap();
+        let address: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let script = Address::from_str(address["address"].as_str().unwrap())
+            .unwrap()
+            .assume_checked()
+            .script_pubkey();
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(
+                    "4d3ff9a5ae5a1f4c9d3d5e2c1b0a99887766554433221100ffeeddccbbaa9988"
+                        .parse()
+                        .unwrap(),
+                    0,
+                ),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(50_000),
+                script_pubkey: script.clone(),
+            }],
+        };
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "public_descriptor"])
+            .output()
+            .unwrap();
+        let descriptors: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let (_, checksum) = descriptors["external"]
+            .as_str()
+            .unwrap()
+            .split_once('#')
+            .unwrap();
+        (script, tx, checksum.to_string())
+    }
+
+    /// Runs `migrate --from <from>` on the configured wallet.
+    fn migrate_from(cli: &BdkCli, from: &std::path::Path) -> std::process::Output {
+        cli.wallet_cmd(&[
+            "--wallet",
+            WALLET_NAME,
+            "migrate",
+            "--from",
+            from.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_migrate_legacy_sqlite_database() {
+        use bdk_wallet::bitcoin::consensus::serialize;
+        use bdk_wallet::rusqlite::Connection;
+
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let (script, tx, checksum) = legacy_wallet_state(&cli);
+
+        // The tables of a bdk 0.x sqlite database, with a transaction synced without its raw
+        // transaction and the checksum of a descriptor on another network.
+        let old_dir = TempDir::new().unwrap();
+        let old_wallet_dir = old_dir.path().join(WALLET_NAME);
+        std::fs::create_dir_all(&old_wallet_dir).unwrap();
+        let old_db = Connection::open(old_wallet_dir.join("wallet.sqlite")).unwrap();
+        old_db
+            .execute_batch(
+                "CREATE TABLE last_derivation_indices (keychain TEXT, value INTEGER);
+                 CREATE TABLE script_pubkeys (keychain TEXT, child INTEGER, script BLOB);
+                 CREATE TABLE transactions (txid BLOB, raw_tx BLOB);
+                 CREATE TABLE checksums (keychain TEXT, checksum BLOB);
+                 INSERT INTO last_derivation_indices VALUES ('\"External\"', 4);
+                 INSERT INTO transactions VALUES (x'00', NULL);
+                 INSERT INTO checksums VALUES ('\"External\"', CAST('qqqqqqqq' AS BLOB));",
+            )
+            .unwrap();
+        old_db
+            .execute(
+                "INSERT INTO script_pubkeys VALUES ('\"External\"', 0, ?1)",
+                [script.as_bytes()],
+            )
+            .unwrap();
+        old_db
+            .execute(
+                "INSERT INTO transactions VALUES (?1, ?2)",
+                (tx.compute_txid().to_string(), serialize(&tx)),
+            )
+            .unwrap();
+
+        let output = migrate_from(&cli, old_dir.path());
+        assert!(!output.status.success());
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("on another network"),
+            "{output:?}"
+        );
+
+        old_db
+            .execute("UPDATE checksums SET checksum = ?1", [checksum.as_bytes()])
+            .unwrap();
+        drop(old_db);
+        let output = migrate_from(&cli, old_dir.path());
+        assert!(output.status.success(), "{output:?}");
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(result["last_revealed"]["external"], 4);
+        assert_eq!(result["transactions"], 1);
+
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "debug", "changesets"])
+            .output()
+            .unwrap();
+        let dump: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let dump = &dump["items"][0];
+        assert_eq!(dump["last_revealed"]["external"], 4);
+        assert_eq!(
+            dump["transactions"][0]["txid"],
+            tx.compute_txid().to_string()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "legacy-sled")]
+    fn test_migrate_legacy_sled_database() {
+        use bdk_wallet::bitcoin::consensus::serialize;
+
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+        let (script, tx, checksum) = legacy_wallet_state(&cli);
+
+        // The tree of the wallet in a bdk 0.x sled database.
+        let old_dir = TempDir::new().unwrap();
+        let old_wallet_dir = old_dir.path().join(WALLET_NAME);
+        let db = sled::open(&old_wallet_dir).unwrap();
+        let tree = db.open_tree(WALLET_NAME).unwrap();
+        tree.insert(b"ce", &4u32.to_be_bytes()).unwrap();
+        tree.insert(
+            [b"pe".as_slice(), &0u32.to_be_bytes()].concat(),
+            serialize(&script),
+        )
+        .unwrap();
+        tree.insert(b"de", checksum.as_bytes()).unwrap();
+        tree.insert(
+            [b"r".as_slice(), &serialize(&tx.compute_txid())].concat(),
+            serialize(&tx),
+        )
+        .unwrap();
+        db.flush().unwrap();
+        drop(tree);
+        drop(db);
+
+        let output = migrate_from(&cli, old_dir.path());
+        assert!(output.status.success(), "{output:?}");
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(result["from"], old_wallet_dir.display().to_string());
+        assert_eq!(result["last_revealed"]["external"], 4);
+        assert_eq!(result["transactions"], 1);
+    }
+
+    #[test]
+    #[cfg(feature = "sqlcipher")]
+    fn test_encrypted_wallet_database() {
+        let (cli, mut cmd_init) = setup_wallet_config();
+        let config_args: Vec<_> = cmd_init.get_args().skip(4).map(|a| a.to_owned()).collect();
+        cmd_init.assert().success();
+        let database = cli
+            .datadir
+            .as_ref()
+            .unwrap()
+            .join(WALLET_NAME)
+            .join("wallet.sqlite");
+        let is_plaintext = || {
+            std::fs::read(&database)
+                .unwrap()
+                .starts_with(b"SQLite format 3")
+        };
+        let new_address = |password: &str| {
+            cli.wallet_cmd(&["--wallet", WALLET_NAME, "new_address"])
+                .env("BDK_CLI_DB_PASSWORD", password)
+                .output()
+                .unwrap()
+        };
+
+        let output = new_address("");
+        assert!(output.status.success(), "{output:?}");
+        let first: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(is_plaintext());
+
+        // The existing database is encrypted on its next opening, keeping the wallet history.
+        let mut config = cli.build_base_cmd();
+        config.args(&config_args).args(["--force", "--encrypt-db"]);
+        config.assert().success();
+        let output = new_address("correct horse");
+        assert!(output.status.success(), "{output:?}");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Encrypted the database"));
+        let second: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_ne!(first["address"], second["address"]);
+        assert!(!is_plaintext());
+
+        let output = new_address("battery staple");
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("wrong database password"));
+        let output = new_address("correct horse");
+        assert!(output.status.success(), "{output:?}");
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("Encrypted the database"));
+    }
+
+    #[test]
+    fn test_mempool_space_fee_estimates() {
+        use crate::common::http::{Response, serve};
+        use std::sync::{Arc, Mutex};
+
+        // A mempool.space instance that records the requested paths.
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let requested = paths.clone();
+        let url = serve(move |request| {
+            requested.lock().unwrap().push(request.path.clone());
+            Response::ok(
+                r#"{"fastestFee":25,"halfHourFee":20,"hourFee":12,"economyFee":3,"minimumFee":1}"#,
+            )
+        });
+        let url = format!("{url}/api");
+
+        let (cli, mut cmd_init) = setup_wallet_config();
+        cmd_init.assert().success();
+
+        let output = cli
+            .wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "estimate_fee",
+                "--provider",
+                "mempool",
+                "--mempool-url",
+                &url,
+                "--target-blocks",
+                "2",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let estimate: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(estimate["provider"], "mempool");
+        assert_eq!(estimate["target_blocks"], 2);
+        assert_eq!(estimate["fee_rate"], 20.0);
+
+        // The wallet is empty, so the transaction fails after the fee rate is fetched.
+        let recipient = "tb1p4tp4l6glyr2gs94neqcpr5gha7344nfyznfkc8szkreflscsdkgqsdent4:1000";
+        let output = cli
+            .wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "create_tx",
+                "--to",
+                recipient,
+                "--target-blocks",
+                "1",
+                "--mempool-url",
+                &url,
+            ])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Insufficient funds"));
+        assert_eq!(
+            *paths.lock().unwrap(),
+            ["/api/v1/fees/recommended", "/api/v1/fees/recommended"]
+        );
+
+        cli.wallet_cmd(&[
+            "--wallet",
+            WALLET_NAME,
+            "create_tx",
+            "--to",
+            recipient,
+            "--target-blocks",
+            "1",
+            "--fee_rate",
+            "2",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    }
+}
+
+#[cfg(all(feature = "repl", feature = "electrum"))]
+mod repl_tests {
+    use crate::common::BdkCli;
+    use serde_json::{Value, json};
+    use tempfile::TempDir;
+
+    const WALLET_NAME: &str = "repl_test_wallet";
+
+    fn setup_repl_wallet() -> (BdkCli, TempDir) {
+        let temp = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp.path().to_path_buf()));
+
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
         let v: Value = serde_json::from_slice(&desc.stdout).unwrap();
         let ext = v["private_descriptors"]["external"].as_str().unwrap();
         let int = v["private_descriptors"]["internal"].as_str().unwrap();
@@ -314,6 +2988,35 @@ mod repl_tests {
         (cli, temp)
     }
 
+    #[test]
+    fn test_run_reports_why_the_backend_is_unavailable() {
+        let (cli, _temp) = setup_repl_wallet();
+
+        // The offline commands still run without a backend.
+        let output = cli
+            .cmd(
+                "run",
+                &[
+                    "--wallet",
+                    WALLET_NAME,
+                    "--commands",
+                    "wallet balance; wallet sync",
+                ],
+            )
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let results: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(results[0]["error"].is_null(), "{results}");
+        let error = results[1]["error"].as_str().unwrap();
+        assert!(
+            error
+                .split_once("Online commands require a backend: ")
+                .is_some_and(|(_, cause)| !cause.is_empty()),
+            "{error}"
+        );
+    }
+
     #[test]
     fn test_repl_executes_commands_and_exits() {
         let (cli, _temp) = setup_repl_wallet();
@@ -343,9 +3046,49 @@ mod repl_tests {
             stdout.contains("\"confirmed\":"),
             "balance output missing:\n{stdout}"
         );
+        let stderr = String::from_utf8_lossy(&output.stderr);
         assert!(
-            stdout.contains("Exiting..."),
-            "no exit acknowledgement:\n{stdout}"
+            stderr.contains("Exiting..."),
+            "no exit acknowledgement:\n{stderr}"
         );
     }
+
+    #[test]
+    fn test_repl_reports_stats_once_per_line() {
+        let (cli, _temp) = setup_repl_wallet();
+
+        let output = cli
+            .build_base_cmd()
+            .args(["--stats", "repl", "--wallet", WALLET_NAME])
+            .write_stdin("wallet balance\nwallet new_address\nexit\n")
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "{output:?}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(stderr.matches("Statistics:").count(), 2, "{stderr}");
+    }
+
+    #[test]
+    fn test_repl_keeps_running_after_errors() {
+        let (cli, _temp) = setup_repl_wallet();
+
+        let output = cli
+            .build_base_cmd()
+            .args(["repl", "--wallet", WALLET_NAME])
+            .write_stdin(
+                "let = wallet balance\nwallet label --ref $missing --label x\n\
+                 wallet sign invalid\nwallet new_address\nexit\n",
+            )
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "{output:?}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Invalid variable name"), "{stderr}");
+        assert!(stderr.contains("Unknown variable `$missing`"), "{stderr}");
+        assert!(stderr.matches("error:").count() >= 3, "{stderr}");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("\"address\":"), "{stdout}");
+    }
 }
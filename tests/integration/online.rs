@@ -15,8 +15,36 @@ mod test_online {
     /// Spins up the envt, a Regtest node + electrs, and saves the wallet config.
     fn setup_online_wallet() -> (BdkCli, Command, TestEnv) {
         let env = TestEnv::new().expect("Failed to start bdk_testenv");
-        let server_url = env.electrsd.electrum_url.as_str();
+        let server_url = env.electrsd.electrum_url.clone();
+        setup_wallet_on(env, "electrum", &server_url)
+    }
+
+    /// Spins up the envt and saves the config of a wallet syncing from the esplora API of its
+    /// electrs.
+    #[cfg(feature = "esplora")]
+    fn setup_esplora_wallet() -> (BdkCli, Command, TestEnv) {
+        let env = TestEnv::new().expect("Failed to start bdk_testenv");
+        let server_url = esplora_url(&env);
+        setup_wallet_on(env, "esplora", &server_url)
+    }
+
+    /// The url of the esplora API of the electrs of `env`.
+    #[cfg(feature = "esplora")]
+    fn esplora_url(env: &TestEnv) -> String {
+        let esplora = env
+            .electrsd
+            .esplora_url
+            .clone()
+            .expect("esplora http endpoint (TestEnv sets http_enabled)");
+        format!("http://{}", esplora.replace("0.0.0.0", "127.0.0.1"))
+    }
 
+    /// Saves the config of a wallet syncing from `server_url` with `client_type`.
+    fn setup_wallet_on(
+        env: TestEnv,
+        client_type: &str,
+        server_url: &str,
+    ) -> (BdkCli, Command, TestEnv) {
         let temp_dir = TempDir::new().unwrap();
         let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
 
@@ -41,7 +69,7 @@ mod test_online {
             .arg("--int-descriptor")
             .arg(int_desc)
             .arg("--client-type")
-            .arg("electrum")
+            .arg(client_type)
             .arg("--database-type")
             .arg("sqlite")
             .arg("--url")
@@ -94,8 +122,10 @@ mod test_online {
         let mut full_args = vec!["--wallet", WALLET_NAME];
         full_args.extend_from_slice(args);
 
+        // Broadcasts run without a terminal to confirm them on.
         let output = cli
             .wallet_cmd(&full_args)
+            .env("ASSUME_YES", "true")
             .output()
             .unwrap_or_else(|e| panic!("failed to spawn `{}`: {e}", args.join(" ")));
 
@@ -178,6 +208,41 @@ mod test_online {
         Txid::from_str(txid).expect("broadcast: invalid txid")
     }
 
+    /// A data directory whose `bitcoin.conf` points `--node` at the rpc port and cookie of the
+    /// test node.
+    #[cfg(feature = "rpc")]
+    fn node_conf(env: &TestEnv) -> TempDir {
+        let node_dir = TempDir::new().unwrap();
+        let port = env.bitcoind.params.rpc_socket.port();
+        let cookie = env.bitcoind.params.cookie_file.display();
+        std::fs::write(
+            node_dir.path().join("bitcoin.conf"),
+            format!("regtest=1\n[regtest]\nrpcport={port}\nrpccookiefile={cookie}\n"),
+        )
+        .unwrap();
+        node_dir
+    }
+
+    /// Runs `node --wallet <WALLET_NAME> --node <node> <args>`, asserts it succeeded, and parses
+    /// stdout as JSON.
+    #[cfg(feature = "rpc")]
+    fn cli_node(cli: &BdkCli, node: &TempDir, args: &[&str]) -> Value {
+        let output = cli
+            .build_base_cmd()
+            .args(["node", "--wallet", WALLET_NAME, "--node"])
+            .arg(node.path())
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "`node {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        serde_json::from_slice(&output.stdout).unwrap()
+    }
+
     #[test]
     fn test_funded_wallet_unspent_and_transactions() {
         let (cli, mut cmd_init, env) = setup_online_wallet();
@@ -244,6 +309,699 @@ mod test_online {
         );
     }
 
+    #[test]
+    fn test_electrum_falls_back_to_next_server() {
+        // Nothing listens on the discard port.
+        let dead = "tcp://127.0.0.1:9";
+        let (cli, mut cmd_init, env) = setup_online_wallet();
+        cmd_init.arg("--server").arg(dead).assert().success();
+        let live = env.electrsd.electrum_url.as_str();
+
+        // Pretend the dead server was the last healthy one, so it is tried first.
+        let server_file = cli
+            .datadir
+            .as_ref()
+            .unwrap()
+            .join(WALLET_NAME)
+            .join("electrum_server");
+        std::fs::write(&server_file, dead).unwrap();
+
+        let full_scan = || {
+            let output = cli
+                .wallet_cmd(&["--wallet", WALLET_NAME, "full_scan"])
+                .output()
+                .unwrap();
+            assert!(
+                output.status.success(),
+                "full_scan failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            String::from_utf8(output.stderr).unwrap()
+        };
+        assert!(full_scan().contains(&format!("Electrum server {dead} is unavailable")));
+        assert_eq!(std::fs::read_to_string(&server_file).unwrap(), live);
+        assert!(!full_scan().contains("is unavailable"));
+    }
+
+    #[test]
+    fn test_electrum_fails_when_no_server_is_available() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_values: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_values["public_descriptors"];
+
+        cli.wallet_cmd(&[
+            "--wallet",
+            WALLET_NAME,
+            "config",
+            "--ext-descriptor",
+            public["external"].as_str().unwrap(),
+            "--int-descriptor",
+            public["internal"].as_str().unwrap(),
+            "--client-type",
+            "electrum",
+            "--database-type",
+            "sqlite",
+            "--server",
+            "tcp://127.0.0.1:9,tcp://127.0.0.1:7",
+        ])
+        .assert()
+        .success();
+
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "full_scan"])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("No Electrum server is available"));
+        assert!(stderr.contains("tcp://127.0.0.1:9:") && stderr.contains("tcp://127.0.0.1:7:"));
+    }
+
+    #[test]
+    fn test_electrum_tls_options_reject_plaintext_servers() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_values: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_values["public_descriptors"];
+        let config = |extra: &[&str]| {
+            cli.wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "config",
+                "--force",
+                "--ext-descriptor",
+                public["external"].as_str().unwrap(),
+                "--int-descriptor",
+                public["internal"].as_str().unwrap(),
+                "--client-type",
+                "electrum",
+                "--database-type",
+                "sqlite",
+                "--server",
+                "ssl://127.0.0.1:9,tcp://127.0.0.1:7",
+            ])
+            .args(extra)
+            .output()
+            .unwrap()
+        };
+
+        let output = config(&["--tls-cert-fingerprint", "00:11"]);
+        assert!(!output.status.success());
+        assert!(
+            String::from_utf8_lossy(&output.stderr)
+                .contains("Invalid SHA256 certificate fingerprint")
+        );
+
+        let fingerprint = "AB:".repeat(31) + "AB";
+        for extra in [
+            &["--require-tls"][..],
+            &["--tls-cert-fingerprint", &fingerprint][..],
+        ] {
+            assert!(config(extra).status.success());
+            let output = cli
+                .wallet_cmd(&["--wallet", WALLET_NAME, "full_scan"])
+                .output()
+                .unwrap();
+            assert!(!output.status.success());
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(
+                stderr.contains("Electrum server tcp://127.0.0.1:7 is not a TLS server"),
+                "{stderr}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_electrum_connects_through_socks5_proxy() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_values: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_values["public_descriptors"];
+        let onion = "tcp://bdkclitestbdkclitestbdkclitestbdkclitestbdkclitestbdk.onion:50001";
+        let config = |proxy: &str| {
+            cli.wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "config",
+                "--ext-descriptor",
+                public["external"].as_str().unwrap(),
+                "--int-descriptor",
+                public["internal"].as_str().unwrap(),
+                "--client-type",
+                "electrum",
+                "--database-type",
+                "sqlite",
+                "--server",
+                onion,
+                "--proxy",
+                proxy,
+            ])
+            .assert()
+        };
+
+        config("http://127.0.0.1:8080")
+            .failure()
+            .stderr(predicates::str::contains("only SOCKS5 proxies"));
+        config("socks5://127.0.0.1:9").success();
+
+        // The onion name is not resolved locally, the proxy connection itself is refused.
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "full_scan"])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(&format!("Electrum server {onion} is unavailable")));
+        assert!(stderr.contains("Connection refused"), "{stderr}");
+    }
+
+    #[test]
+    fn test_prices_are_asked_through_the_proxy() {
+        use std::io::Read;
+
+        let proxy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_values: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_values["public_descriptors"];
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "config"])
+            .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+            .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+            .args(["--client-type", "electrum", "--database-type", "sqlite"])
+            .args(["--server", "tcp://127.0.0.1:9"])
+            .args([
+                "--proxy",
+                &format!("socks5://{}", proxy.local_addr().unwrap()),
+            ])
+            .assert()
+            .success();
+
+        let mut balance = cli.build_base_cmd();
+        balance
+            .args([
+                "--fiat",
+                "USD",
+                "--price-url",
+                "http://prices.invalid/v1/prices",
+            ])
+            .args(["wallet", "--wallet", WALLET_NAME, "balance"]);
+        let balance = std::thread::spawn(move || balance.output().unwrap());
+
+        // The request opens with the SOCKS5 greeting, and fails with the proxy going away.
+        let (mut stream, _) = proxy.accept().unwrap();
+        let mut version = [0; 1];
+        stream.read_exact(&mut version).unwrap();
+        assert_eq!(version, [5]);
+        drop(stream);
+        let output = balance.join().unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let balance: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(balance.get("fiat").is_none());
+    }
+
+    #[cfg(feature = "esplora")]
+    #[test]
+    fn test_sync_verify_with_esplora_agrees() {
+        let (cli, mut cmd_init, env) = setup_online_wallet();
+        cmd_init.assert().success();
+        fund_and_sync_wallet(&cli, &env);
+        let url = esplora_url(&env);
+
+        let sync = run_wallet_json(&cli, &["sync", "--verify-with", &url]);
+        let verification = &sync["verification"];
+        assert_eq!(verification["server"].as_str(), Some(url.as_str()));
+        assert_eq!(verification["transactions"].as_u64(), Some(1), "{sync}");
+        assert_eq!(verification["utxos"].as_u64(), Some(1), "{sync}");
+        assert_eq!(
+            verification["discrepancies"].as_array().map(Vec::len),
+            Some(0),
+            "{sync}"
+        );
+    }
+
+    #[cfg(feature = "esplora")]
+    #[test]
+    fn test_esplora_backend_transaction_lifecycle() {
+        let (cli, mut cmd_init, env) = setup_esplora_wallet();
+        cmd_init.assert().success();
+        fund_and_sync_wallet(&cli, &env);
+
+        let unsigned_psbt = cli_create_tx(&cli, &format!("{RECIPIENT}:20000"));
+        let (signed_psbt, finalized) = cli_sign(&cli, &unsigned_psbt);
+        assert!(finalized, "PSBT should be finalized after signing");
+        let spend_txid = cli_broadcast(&cli, &cli_extract_psbt(&cli, &signed_psbt));
+        env.wait_until_electrum_sees_txid(spend_txid, Duration::from_secs(10))
+            .expect("electrs did not see the spend");
+
+        cli_sync(&cli);
+        let txs = run_wallet_json(&cli, &["transactions"]);
+        assert_eq!(txs["count"].as_u64(), Some(2), "{txs}");
+
+        env.mine_blocks(1, None)
+            .expect("Failed to mine spend confirmation block");
+        env.wait_until_electrum_sees_block(Duration::from_secs(10))
+            .expect("electrs did not catch up to spend confirmation");
+        cli_sync(&cli);
+        let final_balance = cli_balance(&cli);
+        assert!(
+            (49_900_000..50_000_000).contains(&final_balance),
+            "unexpected post-spend confirmed balance: {final_balance}"
+        );
+    }
+
+    #[cfg(all(feature = "esplora", feature = "rpc"))]
+    #[test]
+    fn test_node_commands_sync_an_esplora_wallet() {
+        let (cli, mut cmd_init, env) = setup_esplora_wallet();
+        cmd_init.assert().success();
+        let node = node_conf(&env);
+        let run_node = |args: &[&str]| cli_node(&cli, &node, args);
+
+        let mined = run_node(&["mine", "101"]);
+        assert_eq!(mined["block_hashes"].as_array().map(Vec::len), Some(101));
+        assert_eq!(mined["synced_height"], mined["height"], "{mined}");
+
+        let address = cli_new_address(&cli).to_string();
+        let funded = run_node(&["fund", &address, "50000000sat", "--confirm"]);
+        assert_eq!(funded["confirmed"].as_bool(), Some(true), "{funded}");
+        assert_eq!(cli_balance(&cli), 50_000_000);
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn test_node_reorg_reports_unconfirmed_transactions() {
+        let (cli, mut cmd_init, env) = setup_online_wallet();
+        cmd_init.assert().success();
+        fund_and_sync_wallet(&cli, &env);
+        let node = node_conf(&env);
+        let funding = run_wallet_json(&cli, &["transactions"])["items"][0]["txid"].clone();
+
+        // The funding transaction is 3 blocks deep, the empty competing chain leaves it out.
+        let reorg = cli_node(&cli, &node, &["reorg", "--depth", "3", "--empty"]);
+        assert_eq!(reorg["invalidated"].as_array().map(Vec::len), Some(3));
+        assert_eq!(reorg["block_hashes"].as_array().map(Vec::len), Some(4));
+        assert_eq!(reorg["synced_height"], reorg["height"], "{reorg}");
+        let transactions = reorg["transactions"].as_array().unwrap();
+        assert_eq!(transactions.len(), 1, "{reorg}");
+        assert_eq!(transactions[0]["txid"], funding);
+        assert_eq!(transactions[0]["status"].as_str(), Some("unconfirmed"));
+        assert_eq!(cli_balance(&cli), 0);
+
+        // Mined again, it confirms on the new chain.
+        cli_node(&cli, &node, &["mine", "1"]);
+        assert_eq!(cli_balance(&cli), 50_000_000);
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn test_generate_test_funds_a_regtest_wallet() {
+        let env = TestEnv::new().expect("Failed to start bdk_testenv");
+        let node = node_conf(&env);
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+
+        let output = cli
+            .build_base_cmd()
+            .args(["wallet", "--wallet", WALLET_NAME, "generate-test", "--node"])
+            .arg(node.path())
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(result["utxos"].as_u64(), Some(6), "{result}");
+        assert_eq!(result["unconfirmed"].as_u64(), Some(1), "{result}");
+        // A transaction per block confirming the UTXOs, and one left unconfirmed.
+        assert_eq!(result["txids"].as_array().map(Vec::len), Some(6));
+        assert_eq!(result["total"].as_u64(), Some(11_161_000));
+
+        let unspent = run_wallet_json(&cli, &["unspent"]);
+        assert_eq!(unspent["count"].as_u64(), Some(6), "{unspent}");
+        assert_eq!(cli_balance(&cli), 1_161_000);
+
+        // The wallet exists, only replaced with --force.
+        let again = cli
+            .build_base_cmd()
+            .args(["wallet", "--wallet", WALLET_NAME, "generate-test", "--node"])
+            .arg(node.path())
+            .output()
+            .unwrap();
+        assert!(!again.status.success());
+        // Nor with --force, which would fund the database of the wallet again.
+        let forced = cli
+            .build_base_cmd()
+            .args([
+                "wallet",
+                "--wallet",
+                WALLET_NAME,
+                "generate-test",
+                "--force",
+            ])
+            .arg("--node")
+            .arg(node.path())
+            .output()
+            .unwrap();
+        assert!(!forced.status.success());
+        assert!(String::from_utf8_lossy(&forced.stderr).contains("already has a database"));
+
+        // The node is also reached at its rpc address, with the credentials of its cookie.
+        let cookie = std::fs::read_to_string(&env.bitcoind.params.cookie_file).unwrap();
+        let url = format!("127.0.0.1:{}", env.bitcoind.params.rpc_socket.port());
+        let output = cli
+            .build_base_cmd()
+            .args([
+                "wallet",
+                "--wallet",
+                "by-url",
+                "generate-test",
+                "--node-url",
+                &url,
+            ])
+            .env("NODE_AUTH", cookie.trim())
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(result["utxos"].as_u64(), Some(6), "{result}");
+    }
+
+    #[test]
+    fn test_sync_and_full_scan_report_json_progress() {
+        let (cli, mut cmd_init, env) = setup_online_wallet();
+        cmd_init.assert().success();
+        fund_and_sync_wallet(&cli, &env);
+        let tip = env.rpc_client().get_block_count().unwrap();
+
+        // Runs the command and returns its progress events, the result staying alone on stdout.
+        let progress_events = |command: &str| -> Vec<Value> {
+            let output = cli
+                .wallet_cmd(&["--wallet", WALLET_NAME, command, "--progress", "json"])
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{output:?}");
+            serde_json::from_slice::<Value>(&output.stdout).unwrap();
+            String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        };
+
+        let events = progress_events("full_scan");
+        assert!(
+            events
+                .iter()
+                .any(|event| event["event"] == "script" && event["keychain"] == "external"),
+            "{events:?}"
+        );
+        let done = events.last().unwrap();
+        assert_eq!(done["event"], "done");
+        assert_eq!(done["height"].as_u64(), Some(tip));
+
+        let events = progress_events("sync");
+        let checked: Vec<_> = events
+            .iter()
+            .filter(|event| event["event"] == "sync")
+            .map(|event| (event["checked"].as_u64(), event["total"].as_u64()))
+            .collect();
+        assert!(!checked.is_empty(), "{events:?}");
+        let (last_checked, total) = checked[checked.len() - 1];
+        assert_eq!(last_checked, total);
+        assert_eq!(events.last().unwrap()["event"], "done");
+    }
+
+    #[test]
+    fn test_sync_scans_restored_wallet() {
+        let (cli, mut cmd_init, env) = setup_online_wallet();
+        cmd_init.assert().success();
+        fund_and_sync_wallet(&cli, &env);
+
+        // The same descriptors in a new wallet, without any revealed address.
+        let config =
+            std::fs::read_to_string(cli.datadir.clone().unwrap().join("config.toml")).unwrap();
+        let restore = |datadir: &TempDir| {
+            std::fs::write(datadir.path().join("config.toml"), &config).unwrap();
+            BdkCli::new("regtest", Some(datadir.path().to_path_buf()))
+        };
+
+        let datadir = TempDir::new().unwrap();
+        let restored = restore(&datadir);
+        let sync = run_wallet_json(&restored, &["sync", "--revealed-only"]);
+        assert_eq!(sync["message"], "Wallet synced successfully.");
+        assert_eq!(cli_balance(&restored), 0);
+
+        let datadir = TempDir::new().unwrap();
+        let restored = restore(&datadir);
+        let sync = run_wallet_json(&restored, &["sync"]);
+        assert_eq!(sync["message"], "Wallet scanned successfully.");
+        assert_eq!(cli_balance(&restored), 50_000_000);
+    }
+
+    #[test]
+    fn test_rescan_finds_transactions_below_the_tip() {
+        let (cli, mut cmd_init, env) = setup_online_wallet();
+        cmd_init.assert().success();
+
+        // The same descriptors in another wallet reveal an address the wallet never revealed.
+        let config =
+            std::fs::read_to_string(cli.datadir.clone().unwrap().join("config.toml")).unwrap();
+        let other_dir = TempDir::new().unwrap();
+        std::fs::write(other_dir.path().join("config.toml"), &config).unwrap();
+        let other = BdkCli::new("regtest", Some(other_dir.path().to_path_buf()));
+        let address = cli_new_address(&other);
+
+        let node_address = env
+            .rpc_client()
+            .get_new_address(None, None)
+            .unwrap()
+            .assume_checked();
+        env.mine_blocks(101, Some(node_address))
+            .expect("Failed to mine initial blocks");
+        let txid = env
+            .send(&address, Amount::from_btc(0.5).unwrap())
+            .expect("Failed to fund wallet address");
+        env.wait_until_electrum_sees_txid(txid, Duration::from_secs(10))
+            .expect("Electrum did not see funding tx");
+        env.mine_blocks(1, None)
+            .expect("Failed to confirm funding tx");
+        let funded_height = env.rpc_client().get_block_count().unwrap();
+        env.mine_blocks(5, None).expect("Failed to mine blocks");
+        env.wait_until_electrum_sees_block(Duration::from_secs(10))
+            .expect("Electrum did not catch up to the blocks");
+
+        // Syncing the revealed scripts moves the tip of the wallet past the transaction.
+        run_wallet_json(&cli, &["sync", "--revealed-only"]);
+        assert_eq!(cli_balance(&cli), 0);
+
+        let rescan = run_wallet_json(
+            &cli,
+            &["rescan", "--from-height", &funded_height.to_string()],
+        );
+        assert_eq!(
+            rescan["message"],
+            format!("Rescan from height {funded_height} completed successfully.")
+        );
+        assert_eq!(cli_balance(&cli), 50_000_000);
+        let txs = run_wallet_json(&cli, &["transactions"]);
+        assert_eq!(txs["count"].as_u64(), Some(1), "{txs}");
+        assert_eq!(txs["items"][0]["txid"], txid.to_string());
+    }
+
+    #[test]
+    fn test_rebroadcast_unconfirmed_transactions() {
+        let (cli, mut cmd_init, env) = setup_online_wallet();
+        cmd_init.assert().success();
+        fund_and_sync_wallet(&cli, &env);
+
+        let (signed_psbt, _) = cli_sign(&cli, &cli_create_tx(&cli, &format!("{RECIPIENT}:15000")));
+        let txid = cli_broadcast(&cli, &cli_extract_psbt(&cli, &signed_psbt));
+        env.wait_until_electrum_sees_txid(txid, Duration::from_secs(10))
+            .expect("electrs did not see the broadcast tx");
+        cli_sync(&cli);
+
+        for args in [
+            vec!["rebroadcast"],
+            vec!["rebroadcast", "--txid", &txid.to_string()],
+        ] {
+            let result = run_wallet_json(&cli, &args);
+            let transactions = result["transactions"].as_array().unwrap();
+            assert_eq!(transactions.len(), 1, "{result}");
+            assert_eq!(transactions[0]["txid"], txid.to_string());
+            assert!(
+                ["accepted", "already_known"]
+                    .contains(&transactions[0]["status"].as_str().unwrap()),
+                "{result}"
+            );
+        }
+
+        env.mine_blocks(1, None).expect("Failed to confirm tx");
+        env.wait_until_electrum_sees_block(Duration::from_secs(10))
+            .expect("Electrum did not catch up to the confirmation");
+        cli_sync(&cli);
+        let result = run_wallet_json(&cli, &["rebroadcast"]);
+        assert_eq!(result["transactions"], serde_json::json!([]));
+        cli.wallet_cmd(&[
+            "--wallet",
+            WALLET_NAME,
+            "rebroadcast",
+            "--txid",
+            &txid.to_string(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("is already confirmed"));
+    }
+
+    #[test]
+    fn test_watch_ndjson_reports_events_and_confirmations() {
+        use std::io::{BufRead, BufReader};
+        use std::process::{Child, Stdio};
+        use std::sync::mpsc;
+
+        let (cli, mut cmd_init, env) = setup_online_wallet();
+        cmd_init.assert().success();
+        fund_and_sync_wallet(&cli, &env);
+        let address = cli_new_address(&cli);
+
+        struct Watch(Child);
+        impl Drop for Watch {
+            fn drop(&mut self) {
+                let _ = self.0.kill();
+                let _ = self.0.wait();
+            }
+        }
+        let mut watch = Watch(
+            std::process::Command::new(env!("CARGO_BIN_EXE_bdk-cli"))
+                .args(["--network", "regtest", "--datadir"])
+                .arg(cli.datadir.as_ref().unwrap())
+                .args(["wallet", "--wallet", WALLET_NAME, "watch"])
+                .args(["--ndjson", "--interval", "1"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap(),
+        );
+        let (sender, events) = mpsc::channel();
+        let stdout = BufReader::new(watch.0.stdout.take().unwrap());
+        std::thread::spawn(move || {
+            for line in stdout.lines() {
+                let event: Value = serde_json::from_str(&line.unwrap()).unwrap();
+                if sender.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+        let next_event = |name: &str| loop {
+            let event = events
+                .recv_timeout(Duration::from_secs(30))
+                .unwrap_or_else(|_| panic!("watch did not report a `{name}` event"));
+            if event["event"] == name {
+                break event;
+            }
+        };
+
+        let txid = env
+            .send(&address, Amount::from_sat(20_000))
+            .expect("Failed to send to the wallet");
+        let seen = next_event("tx_unconfirmed");
+        assert_eq!(seen["txid"], txid.to_string(), "{seen}");
+
+        env.mine_blocks(1, None).expect("Failed to confirm tx");
+        let confirmed = next_event("tx_confirmed");
+        assert_eq!(confirmed["txid"], txid.to_string(), "{confirmed}");
+        let confirmations = loop {
+            let event = next_event("confirmations");
+            if event["txid"] == txid.to_string() {
+                break event;
+            }
+        };
+        assert_eq!(confirmations["confirmations"], 1, "{confirmations}");
+    }
+
+    #[test]
+    fn test_faucet_funds_the_next_unused_address() {
+        use crate::common::http::{Request, Response, serve};
+        use std::sync::{Mutex, mpsc};
+
+        let (cli, mut cmd_init, env) = setup_online_wallet();
+        cmd_init.assert().success();
+        env.mine_blocks(101, None)
+            .expect("Failed to mine initial blocks");
+        env.wait_until_electrum_sees_block(Duration::from_secs(10))
+            .expect("Electrum did not catch up to initial blocks");
+
+        // A faucet handing its requests to the test, and answering with the txid it is given.
+        let (requests, requested) = mpsc::channel::<Request>();
+        let (replies, replied) = mpsc::channel::<String>();
+        let replied = Mutex::new(replied);
+        let url = serve(move |request| {
+            requests.send(request.clone()).unwrap();
+            Response::ok(replied.lock().unwrap().recv().unwrap())
+        });
+        let url = format!("{url}/api/onchain");
+        let mut faucet = cli.wallet_cmd(&["--wallet", WALLET_NAME, "faucet"]);
+        faucet.args(["--faucet-url", &url, "--faucet-token", "secret"]);
+        faucet.args(["--amount", "25000sat", "--timeout", "60"]);
+        let faucet = std::thread::spawn(move || faucet.output().unwrap());
+
+        // Pays the request of the wallet from the node, as a faucet would.
+        let request = requested.recv().unwrap();
+        assert_eq!(request.path, "/api/onchain");
+        assert_eq!(request.header("authorization"), Some("Bearer secret"));
+        let body: Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["sats"], 25_000);
+        let address = Address::from_str(body["address"].as_str().unwrap())
+            .unwrap()
+            .assume_checked();
+        let txid = env.send(&address, Amount::from_sat(25_000)).unwrap();
+        replies.send(format!(r#"{{"txid":"{txid}"}}"#)).unwrap();
+
+        let output = faucet.join().unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(result["address"], address.to_string());
+        assert_eq!(result["txid"], txid.to_string());
+        assert_eq!(result["received"], true);
+        let balance = run_wallet_json(&cli, &["balance"]);
+        assert_eq!(balance["untrusted_pending"], 25_000, "{balance}");
+
+        // Without a faucet of its own, regtest needs --faucet-url.
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "faucet"])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("set --faucet-url"));
+    }
+
+    #[test]
+    fn test_utxo_report_classifies_dust_and_age() {
+        let (cli, mut cmd_init, env) = setup_online_wallet();
+        cmd_init.assert().success();
+        fund_and_sync_wallet(&cli, &env);
+
+        let report = run_wallet_json(&cli, &["utxo_report"]);
+        assert_eq!(report["dust_count"].as_u64(), Some(0));
+        assert_eq!(report["economical_value"].as_u64(), Some(50_000_000));
+        let utxo = &report["utxos"][0];
+        assert_eq!(utxo["confirmations"].as_u64(), Some(3));
+        assert_eq!(utxo["age"].as_str(), Some("fresh"));
+
+        // At an absurd fee rate the same UTXO costs more to spend than it is worth.
+        let report = run_wallet_json(&cli, &["utxo_report", "--fee_rate", "1000000"]);
+        assert_eq!(report["dust_count"].as_u64(), Some(1));
+        assert_eq!(report["dust_value"].as_u64(), Some(50_000_000));
+    }
+
     #[test]
     fn test_create_tx_send_all_drains_wallet() {
         let (cli, mut cmd_init, env) = setup_online_wallet();
@@ -416,21 +1174,59 @@ mod test_online {
         let bumped_raw_tx = cli_extract_psbt(&cli, &signed_bumped);
         let bumped_txid = cli_broadcast(&cli, &bumped_raw_tx);
 
-        assert_ne!(
-            original_txid, bumped_txid,
-            "fee-bumped transaction should have a different txid than the original"
-        );
+        assert_ne!(
+            original_txid, bumped_txid,
+            "fee-bumped transaction should have a different txid than the original"
+        );
+
+        env.mine_blocks(1, None)
+            .expect("Failed to confirm bumped tx");
+        env.wait_until_electrum_sees_block(Duration::from_secs(10))
+            .expect("Electrum did not catch up after bump confirmation");
+        cli_sync(&cli);
+
+        let final_balance = cli_balance(&cli);
+        assert!(
+            final_balance < 50_000_000,
+            "balance should reflect exactly one confirmed spend after RBF, got {final_balance}"
+        );
+    }
+
+    #[test]
+    fn test_create_tx_psbt_v2_round_trips_through_sign() {
+        let (cli, mut cmd_init, env) = setup_online_wallet();
+        cmd_init.assert().success();
+        fund_and_sync_wallet(&cli, &env);
+
+        let psbt_v2 = run_wallet_json(
+            &cli,
+            &[
+                "create_tx",
+                "--to",
+                &format!("{RECIPIENT}:20000"),
+                "--psbt-version",
+                "2",
+            ],
+        )["psbt"]
+            .as_str()
+            .expect("create_tx: missing 'psbt' field")
+            .to_string();
+
+        let output = cli.cmd("psbt", &["decode", &psbt_v2]).output().unwrap();
+        let decoded: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(decoded["psbt_version"], 2);
 
-        env.mine_blocks(1, None)
-            .expect("Failed to confirm bumped tx");
-        env.wait_until_electrum_sees_block(Duration::from_secs(10))
-            .expect("Electrum did not catch up after bump confirmation");
-        cli_sync(&cli);
+        let signed = run_wallet_json(&cli, &["sign", &psbt_v2]);
+        assert_eq!(signed["is_finalized"], true);
 
-        let final_balance = cli_balance(&cli);
-        assert!(
-            final_balance < 50_000_000,
-            "balance should reflect exactly one confirmed spend after RBF, got {final_balance}"
+        let output = cli
+            .cmd("psbt", &["decode", signed["psbt"].as_str().unwrap()])
+            .output()
+            .unwrap();
+        let decoded: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(
+            decoded["psbt_version"], 2,
+            "sign should keep the PSBT version"
         );
     }
 
@@ -483,6 +1279,43 @@ mod test_online {
         );
     }
 
+    #[test]
+    fn test_create_tx_bip69_ordering() {
+        use bdk_wallet::bitcoin::Psbt;
+
+        let (cli, mut cmd_init, env) = setup_online_wallet();
+        cmd_init.assert().success();
+        fund_and_sync_wallet(&cli, &env);
+
+        let second = cli_new_address(&cli);
+        let psbt: Psbt = run_wallet_json(
+            &cli,
+            &[
+                "create_tx",
+                "--to",
+                &format!("{RECIPIENT}:30000"),
+                "--to",
+                &format!("{second}:20000"),
+                "--ordering",
+                "bip69",
+            ],
+        )["psbt"]
+            .as_str()
+            .expect("create_tx: missing 'psbt' field")
+            .parse()
+            .expect("create_tx returned an invalid PSBT");
+
+        let outputs: Vec<_> = psbt
+            .unsigned_tx
+            .output
+            .iter()
+            .map(|o| (o.value, o.script_pubkey.clone()))
+            .collect();
+        let mut sorted = outputs.clone();
+        sorted.sort();
+        assert_eq!(outputs, sorted, "outputs are not in BIP-69 order");
+    }
+
     // `create_tx --add_string` embeds an OP_RETURN (nulldata) output carrying
     // the given text, at zero value.
     #[test]
@@ -1145,4 +1978,537 @@ mod test_esplora {
             "esplora-synced confirmed balance mismatch: {bj}"
         );
     }
+
+    #[test]
+    fn test_sync_rejects_unknown_verifier_url() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_value: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_value["public_descriptors"];
+        cli.build_base_cmd()
+            .args(["wallet", "--wallet", WALLET_NAME, "config"])
+            .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+            .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+            .args(["--client-type", "esplora", "--database-type", "sqlite"])
+            .args(["--url", "http://127.0.0.1:9"])
+            .assert()
+            .success();
+
+        // The verifier is checked before the sync reaches the unreachable server.
+        let output = cli
+            .wallet_cmd(&[
+                "--wallet",
+                WALLET_NAME,
+                "sync",
+                "--verify-with",
+                "ftp://127.0.0.1:21",
+            ])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(
+            String::from_utf8_lossy(&output.stderr)
+                .contains("Cannot verify with ftp://127.0.0.1:21")
+        );
+    }
+
+    #[test]
+    fn test_config_saves_scan_parallelism() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_value: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_value["public_descriptors"];
+        let config = |parallel: &str| {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args(["wallet", "--wallet", WALLET_NAME, "config", "--force"])
+                .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+                .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+                .args(["--client-type", "esplora", "--database-type", "sqlite"])
+                .args(["--url", "http://127.0.0.1:3002", "--parallel", parallel]);
+            cmd
+        };
+
+        config("0")
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("invalid value '0'"));
+        config("16").assert().success();
+        let config_toml = std::fs::read_to_string(temp_dir.path().join("config.toml")).unwrap();
+        assert!(config_toml.contains("parallel = 16"), "{config_toml}");
+    }
+
+    #[test]
+    fn test_esplora_retries_rate_limited_requests() {
+        use crate::common::http::{Response, serve};
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // A server that rate limits every request.
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let url = serve(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Response::status("429 Too Many Requests")
+        });
+
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_value: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_value["public_descriptors"];
+        cli.build_base_cmd()
+            .args(["wallet", "--wallet", WALLET_NAME, "config"])
+            .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+            .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+            .args(["--client-type", "esplora", "--database-type", "sqlite"])
+            .args([
+                "--url",
+                &url,
+                "--esplora-retries",
+                "2",
+                "--esplora-backoff",
+                "1",
+            ])
+            .assert()
+            .success();
+
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "sync"])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(
+            stderr.matches("Esplora server is rate limiting").count(),
+            2,
+            "{stderr}"
+        );
+        assert!(stderr.contains("retrying in 1ms") && stderr.contains("retrying in 2ms"));
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_backend_bench_measures_servers() {
+        use crate::common::http::{REGTEST_GENESIS, Response, serve};
+        use std::sync::{Arc, Mutex};
+
+        // An esplora server on the regtest genesis block, rejecting every broadcast.
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let requested = requests.clone();
+        let header = format!(
+            "01000000{}3ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4adae5494dffff7f2002000000",
+            "00".repeat(32)
+        );
+        let url = serve(move |request| {
+            let path = request.path.as_str();
+            requested
+                .lock()
+                .unwrap()
+                .push(format!("{} {path}", request.method));
+            match path {
+                "/blocks/tip/height" => Response::ok("0"),
+                "/blocks/tip/hash" => Response::ok(REGTEST_GENESIS),
+                path if path == format!("/block/{REGTEST_GENESIS}/header") => {
+                    Response::ok(header.clone())
+                }
+                path if path.ends_with("/txs") => Response::ok("[]"),
+                _ => Response {
+                    status: "400 Bad Request",
+                    body: b"bad-txns-prevout-null".to_vec(),
+                },
+            }
+        });
+
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_value: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_value["public_descriptors"];
+        cli.build_base_cmd()
+            .args(["wallet", "--wallet", WALLET_NAME, "config"])
+            .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+            .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+            .args(["--client-type", "esplora", "--database-type", "sqlite"])
+            .args(["--url", &url])
+            .assert()
+            .success();
+
+        let bench = |global: &[&str], args: &[&str]| {
+            let mut cmd = cli.build_base_cmd();
+            cmd.args(global)
+                .args(["backend", "--wallet", WALLET_NAME, "bench"])
+                .args(["--scripts", "3", "--url", "ftp://127.0.0.1:21"])
+                .args(args);
+            cmd.output().unwrap()
+        };
+
+        let tx = format!(
+            "0200000001{}ffffffff00ffffffff010000000000000000016a00000000",
+            "00".repeat(32)
+        );
+        let output = bench(&[], &["--tx", &tx]);
+        assert!(output.status.success(), "{output:?}");
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(result["count"], 2);
+        let servers = result["items"].as_array().unwrap();
+        assert_eq!(servers.len(), 2);
+        assert_eq!(servers[0]["url"], url.as_str());
+        assert_eq!(servers[0]["backend"], "esplora");
+        assert_eq!(servers[0]["scripts"], 3);
+        for measurement in ["connect_ms", "header_ms", "scripts_per_sec", "broadcast_ms"] {
+            assert!(servers[0][measurement].is_f64(), "{measurement}: {result}");
+        }
+        assert!(servers[0].get("error").is_none());
+        assert!(servers[1]["connect_ms"].is_null());
+        assert!(
+            servers[1]["error"]
+                .as_str()
+                .unwrap()
+                .contains("Unsupported server ftp://127.0.0.1:21")
+        );
+        let made = requests.lock().unwrap().clone();
+        assert_eq!(made.iter().filter(|r| r.ends_with("/txs")).count(), 3);
+        assert!(made.contains(&"POST /tx".to_string()), "{made:?}");
+
+        // Nothing is broadcast without `--tx`.
+        let output = bench(&["--output", "table"], &[]);
+        assert!(output.status.success(), "{output:?}");
+        let table = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            table.contains("scripts_per_sec") && table.contains(&url),
+            "{table}"
+        );
+        let posts = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.starts_with("POST"))
+            .count();
+        assert_eq!(posts, 1);
+    }
+
+    #[test]
+    fn test_broadcast_through_all_backends() {
+        use crate::common::http::{Response, serve_esplora};
+
+        // A regtest esplora server answering every broadcast with `status`.
+        let server = |status: &'static str| serve_esplora(move |_| Response::status(status));
+        let accepting = server("200 OK");
+        let rejecting = server("400 Bad Request");
+
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_value: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_value["public_descriptors"];
+        cli.build_base_cmd()
+            .args(["wallet", "--wallet", WALLET_NAME, "config"])
+            .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+            .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+            .args(["--client-type", "esplora", "--database-type", "sqlite"])
+            .args(["--url", &accepting])
+            .assert()
+            .success();
+
+        let tx = format!(
+            "0200000001{}ffffffff00ffffffff010000000000000000016a00000000",
+            "00".repeat(32)
+        );
+        let broadcast = |args: &[&str]| {
+            cli.wallet_cmd(&["--wallet", WALLET_NAME, "broadcast", "--tx", &tx])
+                .env("ASSUME_YES", "true")
+                .args(args)
+                .output()
+                .unwrap()
+        };
+
+        let servers = format!("{rejecting},ftp://127.0.0.1:21");
+        let output = broadcast(&["--all-backends", "--server", &servers]);
+        assert!(output.status.success(), "{output:?}");
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let endpoints = result["endpoints"].as_array().unwrap();
+        let accepted: Vec<_> = endpoints
+            .iter()
+            .map(|endpoint| {
+                (
+                    endpoint["server"].as_str().unwrap(),
+                    endpoint["accepted"].as_bool().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            accepted,
+            [
+                (accepting.as_str(), true),
+                (rejecting.as_str(), false),
+                ("ftp://127.0.0.1:21", false)
+            ]
+        );
+        assert!(endpoints[0].get("error").is_none());
+        assert!(
+            endpoints[2]["error"]
+                .as_str()
+                .unwrap()
+                .contains("Unsupported server")
+        );
+
+        // The server of the wallet broadcasts along with the ones of `--server`.
+        let output = broadcast(&["--server", &rejecting]);
+        assert!(output.status.success(), "{output:?}");
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let servers: Vec<_> = result["endpoints"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|endpoint| endpoint["server"].as_str().unwrap())
+            .collect();
+        assert_eq!(servers, [accepting.as_str(), rejecting.as_str()]);
+
+        // Given again with `--server`, the server of the wallet is only asked once.
+        cli.build_base_cmd()
+            .args(["wallet", "--wallet", "rejected", "config"])
+            .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+            .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+            .args(["--client-type", "esplora", "--database-type", "sqlite"])
+            .args(["--url", &rejecting])
+            .assert()
+            .success();
+        let output = cli
+            .wallet_cmd(&["--wallet", "rejected", "broadcast", "--tx", &tx])
+            .env("ASSUME_YES", "true")
+            .args(["--server", &rejecting])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains(&format!(
+            "No backend accepted the transaction: {rejecting}: "
+        )));
+        assert!(!String::from_utf8_lossy(&output.stderr).contains(&format!("; {rejecting}")));
+    }
+
+    #[test]
+    fn test_esplora_headers_reach_the_wallet_server() {
+        use crate::common::http::{Response, serve_esplora};
+
+        // A regtest esplora server accepting the broadcasts sent with the API key only, and
+        // telling whether it received the key.
+        let server = || {
+            serve_esplora(|request| {
+                match request.header("authorization") == Some("Bearer secret") {
+                    true => Response::status("200 OK"),
+                    false => Response::status("401 Unauthorized"),
+                }
+            })
+        };
+        let wallet_server = server();
+        let other_server = server();
+
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_value: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_value["public_descriptors"];
+        cli.build_base_cmd()
+            .args(["wallet", "--wallet", WALLET_NAME, "config"])
+            .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+            .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+            .args(["--client-type", "esplora", "--database-type", "sqlite"])
+            .args(["--url", &wallet_server])
+            .args(["--esplora-header", "Authorization: Bearer secret"])
+            .assert()
+            .success();
+        let config = std::fs::read_to_string(temp_dir.path().join("config.toml")).unwrap();
+        assert!(config.contains("Authorization: Bearer secret"));
+
+        // The saved header reaches the server of the wallet, but not another server.
+        let tx = format!(
+            "0200000001{}ffffffff00ffffffff010000000000000000016a00000000",
+            "00".repeat(32)
+        );
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "broadcast", "--tx", &tx])
+            .env("ASSUME_YES", "true")
+            .args(["--all-backends", "--server", &other_server])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let accepted: Vec<_> = result["endpoints"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|endpoint| endpoint["accepted"].as_bool().unwrap())
+            .collect();
+        assert_eq!(accepted, [true, false]);
+
+        let header = cli
+            .build_base_cmd()
+            .args(["wallet", "--wallet", WALLET_NAME, "config"])
+            .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+            .args(["--client-type", "esplora", "--database-type", "sqlite"])
+            .args(["--url", &wallet_server, "--esplora-header", "no colon"])
+            .output()
+            .unwrap();
+        assert!(!header.status.success());
+    }
+
+    #[test]
+    #[cfg(feature = "server")]
+    fn test_rest_broadcast_is_confirmed_by_the_spend_credential() {
+        use crate::common::http::{Response, serve_esplora};
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::process::{Child, Stdio};
+
+        // A regtest esplora server accepting every broadcast.
+        let esplora = serve_esplora(|_| Response::status("200 OK"));
+
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_value: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_value["public_descriptors"];
+        cli.build_base_cmd()
+            .args(["wallet", "--wallet", WALLET_NAME, "config"])
+            .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+            .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+            .args(["--client-type", "esplora", "--database-type", "sqlite"])
+            .args(["--url", &esplora])
+            .assert()
+            .success();
+
+        // The server has no terminal to ask for a confirmation on.
+        struct Server(Child);
+        impl Drop for Server {
+            fn drop(&mut self) {
+                let _ = self.0.kill();
+                let _ = self.0.wait();
+            }
+        }
+        let listen = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let _server = Server(
+            std::process::Command::new(env!("CARGO_BIN_EXE_bdk-cli"))
+                .args(["--network", "regtest", "--datadir"])
+                .arg(temp_dir.path())
+                .args(["serve", "--listen", &listen.to_string()])
+                .args(["--token", "spender", "--read-token", "reader"])
+                .stdin(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap(),
+        );
+        for _ in 0..100 {
+            if TcpStream::connect(listen).is_ok() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        let broadcast = |token: &str| {
+            let tx = format!(
+                "0200000001{}ffffffff00ffffffff010000000000000000016a00000000",
+                "00".repeat(32)
+            );
+            let body = serde_json::json!({ "tx": tx }).to_string();
+            let mut stream = TcpStream::connect(listen).unwrap();
+            write!(
+                stream,
+                "POST /wallets/{WALLET_NAME}/broadcasts HTTP/1.1\r\nHost: {listen}\r\n\
+                 Authorization: Bearer {token}\r\nContent-Type: application/json\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            let (head, body) = response.split_once("\r\n\r\n").unwrap();
+            let status = head.split(' ').nth(1).unwrap().to_string();
+            (status, serde_json::from_str::<Value>(body).unwrap())
+        };
+
+        let (status, response) = broadcast("reader");
+        assert_eq!(status, "403", "{response}");
+        let (status, response) = broadcast("spender");
+        assert_eq!(status, "200", "{response}");
+        assert_eq!(response["txid"].as_str().map(str::len), Some(64));
+    }
+
+    #[test]
+    fn test_backend_on_another_network_is_rejected() {
+        use crate::common::http::{Response, serve};
+
+        // A mainnet esplora server.
+        let url = serve(|_| {
+            Response::ok("000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f")
+        });
+
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_value: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_value["public_descriptors"];
+        cli.build_base_cmd()
+            .args(["wallet", "--wallet", WALLET_NAME, "config"])
+            .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+            .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+            .args(["--client-type", "esplora", "--database-type", "sqlite"])
+            .args(["--url", &url])
+            .assert()
+            .success();
+
+        let mismatch =
+            "Network mismatch: the esplora server is on bitcoin, but the wallet is on regtest";
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "sync"])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains(mismatch));
+        let tx = format!(
+            "0200000001{}ffffffff00ffffffff010000000000000000016a00000000",
+            "00".repeat(32)
+        );
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "broadcast", "--tx", &tx])
+            .env("ASSUME_YES", "true")
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains(mismatch));
+    }
+
+    #[test]
+    fn test_rebroadcast_empty_wallet() {
+        let temp_dir = TempDir::new().unwrap();
+        let cli = BdkCli::new("regtest", Some(temp_dir.path().to_path_buf()));
+        let desc = cli.cmd("descriptor", &["--type", "tr"]).output().unwrap();
+        let desc_value: Value = serde_json::from_slice(&desc.stdout).unwrap();
+        let public = &desc_value["public_descriptors"];
+        cli.build_base_cmd()
+            .args(["wallet", "--wallet", WALLET_NAME, "config"])
+            .args(["--ext-descriptor", public["external"].as_str().unwrap()])
+            .args(["--int-descriptor", public["internal"].as_str().unwrap()])
+            .args(["--client-type", "esplora", "--database-type", "sqlite"])
+            .args(["--url", "http://127.0.0.1:3002"])
+            .assert()
+            .success();
+
+        // Nothing to submit, so the unreachable server is never contacted.
+        let output = cli
+            .wallet_cmd(&["--wallet", WALLET_NAME, "rebroadcast"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(result["transactions"], serde_json::json!([]));
+
+        let txid = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+        cli.wallet_cmd(&["--wallet", WALLET_NAME, "rebroadcast", "--txid", txid])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains(format!(
+                "Transaction {txid} is not in the wallet"
+            )));
+    }
 }